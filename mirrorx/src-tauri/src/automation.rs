@@ -0,0 +1,609 @@
+//! Local control socket for automating MirrorX from scripts. It speaks JSON-RPC 2.0
+//! with one request or response per line, over the unix domain socket
+//! `automation/automation.sock` in the app data dir, or the named pipe
+//! `\\.\pipe\mirrorx-automation` on Windows. Only the user who runs MirrorX can
+//! connect to either.
+
+use crate::{
+    command::{file_manager::file_manager_send_file, signaling::signaling_visit, AppState},
+    utility::format_device_id,
+};
+use mirrorx_core::{
+    api::endpoint::{client::trace, id::EndPointID},
+    component::{
+        session_stats::{self, SessionStats},
+        video_recorder,
+    },
+    core_error,
+    error::CoreResult,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{path::PathBuf, sync::Mutex};
+use tauri::{AppHandle, Manager};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    task::JoinHandle,
+};
+
+#[cfg(not(target_os = "windows"))]
+const SOCKET_DIR_NAME: &str = "automation";
+
+#[cfg(not(target_os = "windows"))]
+const SOCKET_FILE_NAME: &str = "automation.sock";
+
+#[cfg(target_os = "windows")]
+const PIPE_NAME: &str = r"\\.\pipe\mirrorx-automation";
+
+// a longer request closes the connection, so a client can't exhaust memory
+const MAX_REQUEST_LINE_SIZE: u64 = 1024 * 1024;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct ConnectParams {
+    remote_device_id: String,
+    password: String,
+    #[serde(default = "default_visit_desktop")]
+    visit_desktop: bool,
+}
+
+fn default_visit_desktop() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct SendFileParams {
+    remote_device_id: String,
+    local_path: PathBuf,
+    remote_path: PathBuf,
+    // overwrite the existing remote file by sending only changed blocks
+    #[serde(default)]
+    delta: bool,
+}
+
+#[derive(Deserialize)]
+struct StartRecordingParams {
+    remote_device_id: String,
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct StartTraceParams {
+    remote_device_id: String,
+    path: PathBuf,
+    // keep the desktop and audio of the session out of the trace
+    #[serde(default)]
+    redact_media: bool,
+}
+
+#[derive(Deserialize)]
+struct SessionParams {
+    remote_device_id: String,
+}
+
+#[derive(Deserialize)]
+struct QuerySessionStatsParams {
+    remote_device_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SessionStatsEntry {
+    remote: String,
+    recording: bool,
+    stats: SessionStats,
+}
+
+enum RpcError {
+    Parse(String),
+    InvalidParams(String),
+    MethodNotFound,
+    Server(String),
+}
+
+pub struct AutomationServer {
+    serve_handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl AutomationServer {
+    pub fn new() -> Self {
+        Self {
+            serve_handle: Mutex::new(None),
+        }
+    }
+
+    /// Start (or restart) listening on the local control socket.
+    pub fn serve(&self, app_handle: AppHandle) -> CoreResult<()> {
+        #[cfg(not(target_os = "windows"))]
+        let handle = {
+            use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+            let socket_dir = app_handle
+                .path_resolver()
+                .app_data_dir()
+                .ok_or(core_error!("read app dir from path resolver failed"))?
+                .join(SOCKET_DIR_NAME);
+
+            // the socket is bound inside a dir which only the user can enter, so others
+            // can't connect before the mode of socket is set
+            std::fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(&socket_dir)?;
+            std::fs::set_permissions(&socket_dir, std::fs::Permissions::from_mode(0o700))?;
+
+            let socket_path = socket_dir.join(SOCKET_FILE_NAME);
+
+            // the socket file is left when app exits, remove it or bind fails
+            self.stop();
+            let _ = std::fs::remove_file(&socket_path);
+
+            let listener = tokio::net::UnixListener::bind(&socket_path)?;
+
+            // only the user who runs MirrorX can control it
+            std::fs::set_permissions(&socket_path, std::fs::Permissions::from_mode(0o600))?;
+
+            tracing::info!(path = ?socket_path, "automation socket listening");
+
+            tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            tokio::spawn(serve_connection(app_handle.clone(), stream));
+                        }
+                        Err(err) => {
+                            tracing::error!(?err, "accept automation connection failed");
+                            break;
+                        }
+                    }
+                }
+
+                tracing::info!("automation serve loop exit");
+            })
+        };
+
+        #[cfg(target_os = "windows")]
+        let handle = {
+            self.stop();
+
+            let security = PipeSecurity::current_user()?;
+            let mut server = security.create_pipe(true)?;
+
+            tracing::info!(name = PIPE_NAME, "automation pipe listening");
+
+            tokio::spawn(async move {
+                loop {
+                    if let Err(err) = server.connect().await {
+                        tracing::error!(?err, "accept automation connection failed");
+                        break;
+                    }
+
+                    // create the next instance before serving, so clients can always connect
+                    let next_server = match security.create_pipe(false) {
+                        Ok(next_server) => next_server,
+                        Err(err) => {
+                            tracing::error!(?err, "create automation pipe failed");
+                            break;
+                        }
+                    };
+
+                    let stream = std::mem::replace(&mut server, next_server);
+                    tokio::spawn(serve_connection(app_handle.clone(), stream));
+                }
+
+                tracing::info!("automation serve loop exit");
+            })
+        };
+
+        *self.serve_handle.lock().unwrap() = Some(handle);
+
+        Ok(())
+    }
+
+    pub fn stop(&self) {
+        if let Some(handle) = self.serve_handle.lock().unwrap().take() {
+            handle.abort();
+        }
+    }
+}
+
+impl Drop for AutomationServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+async fn serve_connection<S>(app_handle: AppHandle, stream: S)
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+
+        let read = (&mut reader)
+            .take(MAX_REQUEST_LINE_SIZE)
+            .read_until(b'\n', &mut line)
+            .await;
+
+        match read {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(err) => {
+                tracing::error!(?err, "read automation request failed");
+                break;
+            }
+        }
+
+        if line.last() != Some(&b'\n') && line.len() as u64 == MAX_REQUEST_LINE_SIZE {
+            tracing::error!("automation request is too long");
+
+            let response = error_response(
+                Value::Null,
+                RpcError::Parse(String::from("request is too long")),
+            );
+            let _ = write_response(&mut writer, response).await;
+            break;
+        }
+
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+
+        let response = match serde_json::from_slice::<RpcRequest>(&line) {
+            Ok(request) => {
+                tracing::info!(method = ?request.method, "automation request");
+
+                let result = dispatch(&app_handle, &request.method, request.params).await;
+
+                // requests without id are notifications which expect no response
+                let Some(id) = request.id else {
+                    continue;
+                };
+
+                match result {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err(err) => error_response(id, err),
+                }
+            }
+            Err(err) => error_response(Value::Null, RpcError::Parse(err.to_string())),
+        };
+
+        if let Err(err) = write_response(&mut writer, response).await {
+            tracing::error!(?err, "write automation response failed");
+            break;
+        }
+    }
+}
+
+async fn write_response(
+    writer: &mut (impl AsyncWrite + Unpin),
+    response: Value,
+) -> std::io::Result<()> {
+    let mut buffer = response.to_string();
+    buffer.push('\n');
+    writer.write_all(buffer.as_bytes()).await
+}
+
+async fn dispatch(app_handle: &AppHandle, method: &str, params: Value) -> Result<Value, RpcError> {
+    match method {
+        "connect" => {
+            let params: ConnectParams = parse_params(params)?;
+            let remote_device_id = parse_device_id(&params.remote_device_id)?;
+
+            signaling_visit(
+                app_handle.clone(),
+                app_handle.state(),
+                app_handle.state(),
+                format_device_id(remote_device_id),
+                params.password,
+                params.visit_desktop,
+                None,
+            )
+            .await?;
+
+            Ok(Value::Null)
+        }
+        "send_file" => {
+            let params: SendFileParams = parse_params(params)?;
+            let remote_device_id = parse_device_id(&params.remote_device_id)?;
+
+            let (id, size) = file_manager_send_file(
+                app_handle.state(),
+                format_device_id(remote_device_id),
+                params.local_path,
+                params.remote_path,
+                params.delta,
+            )
+            .await?;
+
+            Ok(json!({ "id": id, "size": size }))
+        }
+        "start_recording" => {
+            let params: StartRecordingParams = parse_params(params)?;
+            let endpoint_id = device_endpoint_id(app_handle, &params.remote_device_id).await?;
+
+            video_recorder::start_recording(endpoint_id, &params.path)?;
+
+            Ok(Value::Null)
+        }
+        "stop_recording" => {
+            let params: SessionParams = parse_params(params)?;
+            let endpoint_id = device_endpoint_id(app_handle, &params.remote_device_id).await?;
+
+            let path = video_recorder::stop_recording(&endpoint_id)?;
+
+            Ok(json!({ "path": path }))
+        }
+        "start_trace" => {
+            let params: StartTraceParams = parse_params(params)?;
+            let endpoint_id = device_endpoint_id(app_handle, &params.remote_device_id).await?;
+
+            trace::start_trace(endpoint_id, &params.path, params.redact_media)?;
+
+            Ok(Value::Null)
+        }
+        "stop_trace" => {
+            let params: SessionParams = parse_params(params)?;
+            let endpoint_id = device_endpoint_id(app_handle, &params.remote_device_id).await?;
+
+            let path = trace::stop_trace(&endpoint_id)?;
+
+            Ok(json!({ "path": path }))
+        }
+        "query_session_stats" => {
+            let params: QuerySessionStatsParams = parse_params(params)?;
+
+            let entries: Vec<SessionStatsEntry> = match params.remote_device_id {
+                Some(remote_device_id) => {
+                    let endpoint_id = device_endpoint_id(app_handle, &remote_device_id).await?;
+                    let stats = session_stats::session_stats(&endpoint_id)
+                        .ok_or_else(|| RpcError::Server(String::from("session not exists")))?;
+
+                    vec![stats_entry(endpoint_id, stats)]
+                }
+                None => session_stats::all_session_stats()
+                    .into_iter()
+                    .map(|(endpoint_id, stats)| stats_entry(endpoint_id, stats))
+                    .collect(),
+            };
+
+            Ok(serde_json::to_value(entries).map_err(|err| RpcError::Server(err.to_string()))?)
+        }
+        _ => Err(RpcError::MethodNotFound),
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    // absent params are accepted for methods whose params are all optional
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|err| RpcError::InvalidParams(err.to_string()))
+}
+
+fn parse_device_id(device_id: &str) -> Result<i64, RpcError> {
+    device_id
+        .replace('-', "")
+        .parse()
+        .map_err(|_| RpcError::InvalidParams(String::from("invalid remote device id")))
+}
+
+async fn device_endpoint_id(
+    app_handle: &AppHandle,
+    remote_device_id: &str,
+) -> Result<EndPointID, RpcError> {
+    let remote_device_id = parse_device_id(remote_device_id)?;
+    let local_device_id = app_handle
+        .state::<AppState>()
+        .primary_domain()
+        .await?
+        .device_id;
+
+    Ok(EndPointID::DeviceID {
+        local_device_id,
+        remote_device_id,
+    })
+}
+
+fn stats_entry(endpoint_id: EndPointID, stats: SessionStats) -> SessionStatsEntry {
+    let remote = match endpoint_id {
+        EndPointID::DeviceID {
+            remote_device_id, ..
+        } => format_device_id(remote_device_id),
+        EndPointID::LANID { remote_ip, .. } => remote_ip.to_string(),
+    };
+
+    SessionStatsEntry {
+        remote,
+        recording: video_recorder::is_recording(&endpoint_id),
+        stats,
+    }
+}
+
+fn error_response(id: Value, err: RpcError) -> Value {
+    let (code, message) = match err {
+        RpcError::Parse(message) => (PARSE_ERROR, message),
+        RpcError::InvalidParams(message) => (INVALID_PARAMS, message),
+        RpcError::MethodNotFound => (METHOD_NOT_FOUND, String::from("method not found")),
+        RpcError::Server(message) => (SERVER_ERROR, message),
+    };
+
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+impl From<mirrorx_core::error::CoreError> for RpcError {
+    fn from(err: mirrorx_core::error::CoreError) -> Self {
+        RpcError::Server(err.to_string())
+    }
+}
+
+// security descriptor which grants the pipe to the current user only, the default DACL
+// lets everyone on the machine read it
+#[cfg(target_os = "windows")]
+struct PipeSecurity {
+    descriptor: windows::Win32::Security::PSECURITY_DESCRIPTOR,
+}
+
+// the descriptor is only read by the system once created
+#[cfg(target_os = "windows")]
+unsafe impl Send for PipeSecurity {}
+
+#[cfg(target_os = "windows")]
+impl PipeSecurity {
+    fn current_user() -> CoreResult<Self> {
+        use windows::{
+            core::HSTRING,
+            Win32::{
+                Foundation::GetLastError,
+                Security::{
+                    Authorization::{
+                        ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+                    },
+                    PSECURITY_DESCRIPTOR,
+                },
+            },
+        };
+
+        let sid = current_user_sid()?;
+
+        // protected DACL with the only entry allowing everything to the user
+        let sddl = HSTRING::from(format!("D:P(A;;GA;;;{sid})"));
+        let mut descriptor = PSECURITY_DESCRIPTOR::default();
+
+        unsafe {
+            if !ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                &sddl,
+                SDDL_REVISION_1,
+                &mut descriptor,
+                None,
+            )
+            .as_bool()
+            {
+                return Err(core_error!(
+                    "create pipe security descriptor failed ({:?})",
+                    GetLastError().to_hresult()
+                ));
+            }
+        }
+
+        Ok(Self { descriptor })
+    }
+
+    fn create_pipe(
+        &self,
+        first_pipe_instance: bool,
+    ) -> std::io::Result<tokio::net::windows::named_pipe::NamedPipeServer> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+        use windows::Win32::Security::SECURITY_ATTRIBUTES;
+
+        let mut attributes = SECURITY_ATTRIBUTES {
+            nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: self.descriptor.0,
+            bInheritHandle: false.into(),
+        };
+
+        unsafe {
+            ServerOptions::new()
+                .first_pipe_instance(first_pipe_instance)
+                .reject_remote_clients(true)
+                .create_with_security_attributes_raw(
+                    PIPE_NAME,
+                    &mut attributes as *mut SECURITY_ATTRIBUTES as *mut std::ffi::c_void,
+                )
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl Drop for PipeSecurity {
+    fn drop(&mut self) {
+        unsafe {
+            windows::Win32::System::Memory::LocalFree(self.descriptor.0 as isize);
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn current_user_sid() -> CoreResult<String> {
+    use windows::{
+        core::PWSTR,
+        Win32::{
+            Foundation::{CloseHandle, GetLastError, HANDLE},
+            Security::{
+                Authorization::ConvertSidToStringSidW, GetTokenInformation, TokenUser, TOKEN_QUERY,
+                TOKEN_USER,
+            },
+            System::{
+                Memory::LocalFree,
+                Threading::{GetCurrentProcess, OpenProcessToken},
+            },
+        },
+    };
+
+    unsafe {
+        let mut token = HANDLE::default();
+        if !OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).as_bool() {
+            return Err(core_error!(
+                "open process token failed ({:?})",
+                GetLastError().to_hresult()
+            ));
+        }
+
+        // TOKEN_USER is followed by the SID it points to
+        let mut buffer = [0usize; 64];
+        let mut return_length = 0;
+
+        let queried = GetTokenInformation(
+            token,
+            TokenUser,
+            Some(buffer.as_mut_ptr() as *mut std::ffi::c_void),
+            std::mem::size_of_val(&buffer) as u32,
+            &mut return_length,
+        )
+        .as_bool();
+
+        let _ = CloseHandle(token);
+
+        if !queried {
+            return Err(core_error!(
+                "query token user failed ({:?})",
+                GetLastError().to_hresult()
+            ));
+        }
+
+        let user = &*(buffer.as_ptr() as *const TOKEN_USER);
+
+        let mut sid = PWSTR::null();
+        if !ConvertSidToStringSidW(user.User.Sid, &mut sid).as_bool() {
+            return Err(core_error!(
+                "convert user sid failed ({:?})",
+                GetLastError().to_hresult()
+            ));
+        }
+
+        let result = sid
+            .to_string()
+            .map_err(|_| core_error!("user sid is invalid"));
+
+        LocalFree(sid.0 as isize);
+
+        result
+    }
+}
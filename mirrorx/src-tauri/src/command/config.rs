@@ -1,26 +1,98 @@
-use crate::command::AppState;
+use crate::{
+    automation::AutomationServer,
+    command::{
+        signaling::{ensure_signaling_enabled, follow_lan_only_changes},
+        AppState,
+    },
+    event,
+    hotkey::register_hotkeys,
+    logging::{follow_log_filter_changes, parse_log_filter, set_log_filter},
+    notification::Notifier,
+    tray,
+};
 use mirrorx_core::{
     api::{
         config::{
-            entity::{domain::Domain, history::Record, kv::Theme},
+            change::serve_config_changes,
+            entity::{
+                acl::{AclAction, AclMatcher, AclRule},
+                domain::Domain,
+                history::{RankedRecord, Record},
+                hotkey::{Hotkey, HotkeyAction},
+                kv::{NotificationKind, Theme, UpdateChannel},
+                tls::RelayTls,
+            },
+            key_store::KeyStoreBackend,
             LocalStorage,
         },
-        signaling::http_message::Response,
+        endpoint::{
+            acl::{set_acl_rules, validate_acl_matcher},
+            client::{
+                socket::{set_transport_tuning, TransportTuning},
+                tls::validate_relay_tls,
+            },
+            message::{GopPolicy, SessionProfile},
+            permission::set_view_only_by_default,
+            queue::set_max_sessions,
+        },
+        signaling::{
+            http_message::Response,
+            visit_prompt::{set_confirm_visit, set_reject_message},
+            SignalingClient,
+        },
+    },
+    component::{
+        autostart::{autostart_enabled, set_autostart_enabled},
+        availability::{set_availability, Availability},
+        codec_probe,
+        desktop::{
+            idle::set_idle_frame_rate,
+            test_pattern::{set_test_pattern, TestPattern},
+        },
+        fs::{
+            receive_policy::{init_quarantine_area, set_receive_policy, ReceivePolicy},
+            staging::{init_staging_area, set_staging_quota, set_staging_retention_days},
+        },
+        governor::priority::{
+            parse_cores, set_encoder_cores, set_encoder_thread_priority, ThreadPriority,
+        },
+        lan::{
+            key::{generate_lan_key, set_lan_key},
+            keystore::load_keystore,
+            set_advertised_device_ids,
+        },
+        power::{keep_awake::set_keep_awake_enabled, set_power_saving_enabled},
+        quality_alert::set_quality_auto_tune,
+        session_profile::set_session_profile,
+        telemetry::{self, TelemetryReport, DEFAULT_TELEMETRY_URL},
+        unattended_login::set_unattended_login_enabled,
+        updater::DEFAULT_UPDATE_URL,
+        video_decoder::decoder::{set_gop_policy, set_tile_codec_preferred},
+        video_encoder::watermark::set_watermark_enabled,
     },
     core_error,
-    error::CoreResult,
+    error::{CoreError, CoreResult},
+    service::plugin,
+    utility::{
+        net::set_bind_ip,
+        os::{enum_broadcast_network_interfaces, enum_network_interfaces},
+    },
 };
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
-use tauri::{
-    http::Uri, AppHandle, CustomMenuItem, Manager, State, SystemTrayMenu, SystemTrayMenuItem,
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    time::{SystemTime, UNIX_EPOCH},
 };
+use tauri::{http::Uri, AppHandle, Manager, State};
 
 #[tauri::command]
-#[tracing::instrument(skip(app_handle, app_state))]
+#[tracing::instrument(skip(app_handle, app_state, notifier, automation_server))]
 pub async fn config_init(
     app_handle: tauri::AppHandle,
     app_state: tauri::State<'_, AppState>,
+    notifier: tauri::State<'_, Notifier>,
+    automation_server: tauri::State<'_, AutomationServer>,
 ) -> CoreResult<()> {
     let config_dir = app_handle
         .path_resolver()
@@ -35,6 +107,93 @@ pub async fn config_init(
     let storage = LocalStorage::new(storage_path)?;
     let domain_count = storage.domain().get_domain_count()?;
 
+    notifier.serve(app_handle.clone(), storage.clone());
+    event::serve(app_handle.clone());
+    tray::serve(app_handle.clone());
+
+    if let Err(err) = register_hotkeys(&app_handle, &storage.hotkey().get_hotkeys()?) {
+        tracing::error!(?err, "register hotkeys failed");
+    }
+
+    set_power_saving_enabled(storage.kv().get_power_saving_enabled()?);
+    set_keep_awake_enabled(storage.kv().get_keep_awake_enabled()?);
+    set_view_only_by_default(storage.kv().get_view_only_enabled()?);
+    set_confirm_visit(storage.kv().get_visit_confirm_enabled()?);
+    set_unattended_login_enabled(storage.kv().get_unattended_login_enabled()?);
+    set_reject_message(storage.kv().get_reject_message()?);
+    set_availability(storage.kv().get_availability()?);
+    set_max_sessions(storage.kv().get_max_sessions()? as usize);
+    set_idle_frame_rate(storage.kv().get_idle_frame_rate()?);
+    set_watermark_enabled(storage.kv().get_watermark_enabled()?);
+    set_tile_codec_preferred(storage.kv().get_tile_codec_preferred()?);
+    set_gop_policy(storage.kv().get_gop_policy()?);
+    set_session_profile(storage.kv().get_session_profile()?);
+    set_quality_auto_tune(storage.kv().get_quality_auto_tune()?);
+    set_test_pattern(
+        storage
+            .kv()
+            .get_test_pattern()?
+            .and_then(|pattern| pattern.parse().ok()),
+    );
+    set_encoder_thread_priority(storage.kv().get_encoder_thread_priority()?);
+    set_encoder_cores(
+        storage
+            .kv()
+            .get_encoder_cores()?
+            .and_then(|cores| parse_cores(&cores).ok()),
+    );
+    set_lan_key(storage.kv().get_lan_key()?);
+    load_keystore(&storage)?;
+    advertise_device_ids(&storage)?;
+    set_acl_rules(storage.acl().get_rules()?);
+    set_staging_quota(storage.kv().get_staging_quota_mb()? as u64 * 1024 * 1024);
+    set_staging_retention_days(storage.kv().get_staging_retention_days()?);
+    set_transport_tuning(storage.kv().get_transport_tuning()?);
+    set_receive_policy(storage.kv().get_receive_policy()?);
+    set_bind_ip(storage.kv().get_bind_ip()?);
+    if let Err(err) = set_log_filter(storage.kv().get_log_filter()?.as_deref()) {
+        tracing::error!(?err, "apply log filter failed");
+    }
+    telemetry::set_telemetry_enabled(storage.kv().get_telemetry_enabled()?);
+    telemetry::serve_batch_upload(storage.clone());
+    codec_probe::serve_probe(storage.clone());
+
+    // settings written from now on are applied as soon as they're saved
+    serve_config_changes(storage.clone());
+    follow_lan_only_changes(app_handle.clone());
+    follow_log_filter_changes();
+
+    if storage.kv().get_automation_enabled()? {
+        if let Err(err) = automation_server.serve(app_handle.clone()) {
+            tracing::error!(?err, "serve automation failed");
+        }
+    }
+
+    let app_data_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or(core_error!("read app data dir from path resolver failed"))?;
+
+    if let Err(err) = init_staging_area(app_data_dir.join("staging")) {
+        tracing::error!(?err, "init staging area failed");
+    }
+
+    if let Err(err) = init_quarantine_area(app_data_dir.join("quarantine")) {
+        tracing::error!(?err, "init quarantine area failed");
+    }
+
+    match plugin::load_plugins_from_dir(&config_dir.join("plugins")) {
+        Ok(names) => tracing::info!(?names, "load plugins"),
+        Err(err) => tracing::error!(?err, "load plugins failed"),
+    }
+
+    // dirty sessions are kept and reported after signaling is enabled again
+    if !storage.kv().get_lan_only_enabled()? {
+        if let Err(err) = report_dirty_shutdown(&storage) {
+            tracing::error!(?err, "report dirty shutdown failed");
+        }
+    }
+
     let mut storage_guard = app_state.storage.lock().await;
     *storage_guard = Some(storage);
     drop(storage_guard);
@@ -95,6 +254,10 @@ pub async fn config_domain_create(
     is_primary: bool,
     remarks: String,
 ) -> CoreResult<()> {
+    if let Some(ref storage) = *app_state.storage.lock().await {
+        ensure_signaling_enabled(storage)?;
+    }
+
     let uri = addr
         .parse::<SocketAddr>()
         .map(|addr| {
@@ -110,7 +273,7 @@ pub async fn config_domain_create(
     let client = mirrorx_core::api::signaling::SignalingClient::new(uri.to_string())?;
     let response = match client.identity().await? {
         Response::Message(resp) => resp,
-        Response::Error(err) => return Err(core_error!("http error: {:?}", err)),
+        Response::Error(err) => return Err(CoreError::SignalingServerError(err)),
     };
 
     let Some(ref storage) = *app_state.storage.lock().await else {
@@ -127,7 +290,7 @@ pub async fn config_domain_create(
     let finger_print = mirrorx_core::utility::rand::generate_device_finger_print();
     let response = match client.domain_register(0, &finger_print).await? {
         Response::Message(resp) => resp,
-        Response::Error(err) => return Err(core_error!("http error: {:?}", err)),
+        Response::Error(err) => return Err(CoreError::SignalingServerError(err)),
     };
 
     storage.domain().add_domain(Domain {
@@ -143,6 +306,8 @@ pub async fn config_domain_create(
         remarks,
     })?;
 
+    advertise_device_ids(storage)?;
+
     Ok(())
 }
 
@@ -156,7 +321,26 @@ pub async fn config_domain_delete(id: i64, app_state: State<'_, AppState>) -> Co
     let domain = storage.domain().get_domain_by_id(id)?;
     storage.domain().delete_domain(id)?;
     storage.history().delete_domain_related(&domain.name)?;
+    storage.peer().delete_domain_related(&domain.name)?;
+    storage.tls().delete_domain_related(&domain.name)?;
+    advertise_device_ids(storage)?;
+
+    // dropping the client closes its subscription
+    app_state.signaling_clients.lock().await.remove(&id);
+
+    Ok(())
+}
 
+// lan peers sharing the lan key find the device by the ids of all domains
+fn advertise_device_ids(storage: &LocalStorage) -> CoreResult<()> {
+    let device_ids = storage
+        .domain()
+        .get_all_domains()?
+        .into_iter()
+        .map(|domain| domain.device_id)
+        .collect();
+
+    set_advertised_device_ids(device_ids);
     Ok(())
 }
 
@@ -207,16 +391,8 @@ pub async fn config_domain_update(
     };
 
     match req.update_type {
-        ConfigDomainUpdateType::SetPrimary => {
-            let current_signaling = app_state.signaling_client.lock().await;
-            if let Some((domain_id, _)) = *current_signaling {
-                if domain_id == req.id {
-                    return Ok(());
-                }
-            }
-
-            storage.domain().set_domain_is_primary(req.id)?;
-        }
+        // all domains keep subscribed, primary domain is only the default one to visit with
+        ConfigDomainUpdateType::SetPrimary => storage.domain().set_domain_is_primary(req.id)?,
         ConfigDomainUpdateType::Password(new_password) => storage
             .domain()
             .set_domain_device_password(req.id, &new_password)?,
@@ -228,6 +404,41 @@ pub async fn config_domain_update(
     Ok(())
 }
 
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_domain_relay_tls_get(
+    app_state: State<'_, AppState>,
+    domain_id: i64,
+) -> CoreResult<Option<RelayTls>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    let domain = storage.domain().get_domain_by_id(domain_id)?;
+    storage.tls().get_relay_tls(&domain.name)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state, relay_tls))]
+pub async fn config_domain_relay_tls_set(
+    app_state: State<'_, AppState>,
+    domain_id: i64,
+    relay_tls: Option<RelayTls>,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    if let Some(ref relay_tls) = relay_tls {
+        validate_relay_tls(relay_tls)?;
+    }
+
+    let domain = storage.domain().get_domain_by_id(domain_id)?;
+    storage
+        .tls()
+        .set_relay_tls(&domain.name, relay_tls.as_ref())
+}
+
 #[tauri::command]
 #[tracing::instrument(skip(app_state))]
 pub async fn config_language_get(app_state: State<'_, AppState>) -> CoreResult<String> {
@@ -270,36 +481,7 @@ pub async fn config_language_set(
 
     // update menu language
 
-    let (quit_text, show_text, hide_text, about_text) = match language.as_str() {
-        "en" => ("Quit", "Show", "Hide", "About"),
-        "zh" => ("退出", "显示", "隐藏", "关于"),
-        _ => return Ok(()),
-    };
-
-    let quit = CustomMenuItem::new("quit", quit_text);
-    let show = CustomMenuItem::new("show", show_text);
-    let hide = CustomMenuItem::new("hide", hide_text);
-    let about = CustomMenuItem::new("about", about_text);
-
-    let tray_menu = if cfg!(target_os = "macos") {
-        SystemTrayMenu::new()
-            .add_item(hide)
-            .add_item(show)
-            .add_native_item(SystemTrayMenuItem::Separator)
-            .add_item(quit)
-    } else {
-        SystemTrayMenu::new()
-            .add_item(hide)
-            .add_item(show)
-            .add_native_item(SystemTrayMenuItem::Separator)
-            .add_item(about)
-            .add_native_item(SystemTrayMenuItem::Separator)
-            .add_item(quit)
-    };
-
-    if let Err(err) = app_handle.tray_handle().set_menu(tray_menu) {
-        tracing::error!(?err, "set new tray menu failed");
-    }
+    tray::set_language(&app_handle, &language);
 
     #[cfg(target_os = "macos")]
     {
@@ -307,9 +489,9 @@ pub async fn config_language_set(
             return Ok(());
         };
 
-        let about_text = match language.as_str() {
-            "en" => "About",
-            "zh" => "关于",
+        let (quit_text, about_text) = match language.as_str() {
+            "en" => ("Quit", "About"),
+            "zh" => ("退出", "关于"),
             _ => return Ok(()),
         };
 
@@ -366,3 +548,1169 @@ pub async fn config_history_get(
 
     Ok(records)
 }
+
+/// History ranked for reconnecting with the last known online status, devices found on
+/// LAN now are online.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_history_ranked_get(
+    app_state: State<'_, AppState>,
+    limit: Option<usize>,
+) -> CoreResult<Vec<RankedRecord>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    let mut records = storage.history().query_ranked()?;
+    if let Some(limit) = limit {
+        records.truncate(limit);
+    }
+
+    if let Some(ref lan_provider) = *app_state.lan_provider.lock().await {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .ok();
+
+        for record in records.iter_mut() {
+            let device_id = record.record.device_id;
+            if lan_provider.find_device(device_id).await.is_some() {
+                record.online = Some(true);
+                record.online_timestamp = now;
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_history_pinned_set(
+    app_state: State<'_, AppState>,
+    device_id: i64,
+    domain: String,
+    pinned: bool,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.history().set_pinned(device_id, &domain, pinned)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_history_favorite_set(
+    app_state: State<'_, AppState>,
+    device_id: i64,
+    domain: String,
+    favorite: bool,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.history().set_favorite(device_id, &domain, favorite)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_notification_get(
+    app_state: State<'_, AppState>,
+    kind: NotificationKind,
+) -> CoreResult<bool> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_notification_enabled(kind)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_notification_set(
+    app_state: State<'_, AppState>,
+    kind: NotificationKind,
+    enabled: bool,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_notification_enabled(kind, enabled)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_hotkey_list(app_state: State<'_, AppState>) -> CoreResult<Vec<Hotkey>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.hotkey().get_hotkeys()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_handle, app_state))]
+pub async fn config_hotkey_set(
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+    action: HotkeyAction,
+    accelerator: String,
+    enabled: bool,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    let hotkeys = storage.hotkey().get_hotkeys()?;
+    if enabled
+        && hotkeys.iter().any(|hotkey| {
+            hotkey.action != action && hotkey.enabled && hotkey.accelerator == accelerator
+        })
+    {
+        return Err(core_error!("hotkey '{}' already in use", accelerator));
+    }
+
+    storage.hotkey().set_hotkey(action, &accelerator, enabled)?;
+
+    if let Err(err) = register_hotkeys(&app_handle, &storage.hotkey().get_hotkeys()?) {
+        // rollback to keep the stored hotkeys consistent with the registered ones
+        for hotkey in hotkeys.iter().filter(|hotkey| hotkey.action == action) {
+            storage
+                .hotkey()
+                .set_hotkey(action, &hotkey.accelerator, hotkey.enabled)?;
+        }
+
+        register_hotkeys(&app_handle, &hotkeys)?;
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_handle, app_state))]
+pub async fn config_hotkey_reset(
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+) -> CoreResult<Vec<Hotkey>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.hotkey().reset_hotkeys()?;
+
+    let hotkeys = storage.hotkey().get_hotkeys()?;
+    register_hotkeys(&app_handle, &hotkeys)?;
+
+    Ok(hotkeys)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_acl_rule_list(app_state: State<'_, AppState>) -> CoreResult<Vec<AclRule>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.acl().get_rules()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_acl_rule_add(
+    app_state: State<'_, AppState>,
+    action: AclAction,
+    matcher: AclMatcher,
+) -> CoreResult<i64> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    validate_acl_matcher(&matcher)?;
+
+    storage.acl().add_rule(action, &matcher)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_acl_rule_delete(app_state: State<'_, AppState>, id: i64) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.acl().delete_rule(id)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_power_saving_get(app_state: State<'_, AppState>) -> CoreResult<bool> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_power_saving_enabled()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_power_saving_set(
+    app_state: State<'_, AppState>,
+    enabled: bool,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_power_saving_enabled(enabled)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_keep_awake_get(app_state: State<'_, AppState>) -> CoreResult<bool> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_keep_awake_enabled()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_keep_awake_set(
+    app_state: State<'_, AppState>,
+    enabled: bool,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_keep_awake_enabled(enabled)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_update_channel_get(
+    app_state: State<'_, AppState>,
+) -> CoreResult<UpdateChannel> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_update_channel()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_update_channel_set(
+    app_state: State<'_, AppState>,
+    channel: UpdateChannel,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_update_channel(channel)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_update_url_get(app_state: State<'_, AppState>) -> CoreResult<String> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    Ok(storage
+        .kv()
+        .get_update_url()?
+        .unwrap_or_else(|| DEFAULT_UPDATE_URL.to_string()))
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_update_url_set(app_state: State<'_, AppState>, url: String) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    Uri::try_from(url.as_str()).map_err(|_| core_error!("invalid update url"))?;
+
+    storage.kv().set_update_url(&url)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_telemetry_get(app_state: State<'_, AppState>) -> CoreResult<bool> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_telemetry_enabled()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_telemetry_set(app_state: State<'_, AppState>, enabled: bool) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_telemetry_enabled(enabled)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_telemetry_url_get(app_state: State<'_, AppState>) -> CoreResult<String> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    Ok(storage
+        .kv()
+        .get_telemetry_url()?
+        .unwrap_or_else(|| DEFAULT_TELEMETRY_URL.to_string()))
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_telemetry_url_set(
+    app_state: State<'_, AppState>,
+    url: String,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    Uri::try_from(url.as_str()).map_err(|_| core_error!("invalid telemetry url"))?;
+
+    storage.kv().set_telemetry_url(&url)
+}
+
+/// Preview exactly the report which would be uploaded in next batch.
+#[tauri::command]
+#[tracing::instrument]
+pub fn config_telemetry_preview() -> TelemetryReport {
+    telemetry::preview()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_automation_get(app_state: State<'_, AppState>) -> CoreResult<bool> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_automation_enabled()
+}
+
+/// Enable or disable the local control socket for scripts, it's disabled by default.
+#[tauri::command]
+#[tracing::instrument(skip(app_handle, app_state, automation_server))]
+pub async fn config_automation_set(
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+    automation_server: State<'_, AutomationServer>,
+    enabled: bool,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    if enabled {
+        automation_server.serve(app_handle)?;
+    } else {
+        automation_server.stop();
+    }
+
+    storage.kv().set_automation_enabled(enabled)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_view_only_get(app_state: State<'_, AppState>) -> CoreResult<bool> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_view_only_enabled()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_view_only_set(app_state: State<'_, AppState>, enabled: bool) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    // only affects the sessions created later
+    storage.kv().set_view_only_enabled(enabled)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_visit_confirm_get(app_state: State<'_, AppState>) -> CoreResult<bool> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_visit_confirm_enabled()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_visit_confirm_set(
+    app_state: State<'_, AppState>,
+    enabled: bool,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_visit_confirm_enabled(enabled)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_unattended_login_get(app_state: State<'_, AppState>) -> CoreResult<bool> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_unattended_login_enabled()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_unattended_login_set(
+    app_state: State<'_, AppState>,
+    enabled: bool,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_unattended_login_enabled(enabled)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_reject_message_get(
+    app_state: State<'_, AppState>,
+) -> CoreResult<Option<String>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_reject_message()
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_reject_message_set(
+    app_state: State<'_, AppState>,
+    message: Option<String>,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_reject_message(message.as_deref())
+}
+
+/// Trust `fingerprint` as the identity of remote device, it's called after user verified
+/// the changed identity with the remote user.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_peer_trust(
+    app_state: State<'_, AppState>,
+    domain: String,
+    remote_device_id: String,
+    fingerprint: String,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    let remote_device_id = remote_device_id.replace('-', "").parse()?;
+
+    storage
+        .peer()
+        .set_fingerprint(&domain, remote_device_id, &fingerprint)
+}
+
+/// The bandwidth cap of sessions with the remote device in kilobits per second, None when
+/// unlimited.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_peer_bandwidth_limit_get(
+    app_state: State<'_, AppState>,
+    domain: String,
+    remote_device_id: String,
+) -> CoreResult<Option<u32>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    let remote_device_id = remote_device_id.replace('-', "").parse()?;
+
+    storage
+        .peer()
+        .get_bandwidth_limit(&domain, remote_device_id)
+}
+
+/// Set the bandwidth cap of the remote device, the connection with it is capped at once
+/// if it's open.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_peer_bandwidth_limit_set(
+    app_state: State<'_, AppState>,
+    domain: String,
+    remote_device_id: String,
+    max_kbps: Option<u32>,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    let remote_device_id = remote_device_id.replace('-', "").parse()?;
+
+    // open sessions with the remote device follow the change
+    storage
+        .peer()
+        .set_bandwidth_limit(&domain, remote_device_id, max_kbps)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_availability_get(app_state: State<'_, AppState>) -> CoreResult<Availability> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_availability()
+}
+
+/// Set the availability of user, signaling servers and LAN devices are told at once.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_availability_set(
+    app_state: State<'_, AppState>,
+    availability: Availability,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_availability(availability)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_max_sessions_get(app_state: State<'_, AppState>) -> CoreResult<u32> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_max_sessions()
+}
+
+/// Set the max count of sessions served at the same time, zero means unlimited.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_max_sessions_set(
+    app_state: State<'_, AppState>,
+    max_sessions: u32,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_max_sessions(max_sessions)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_idle_frame_rate_get(app_state: State<'_, AppState>) -> CoreResult<u8> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_idle_frame_rate()
+}
+
+/// Set the frame rate which capture drops to while the desktop stays unchanged, zero
+/// disables it.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_idle_frame_rate_set(
+    app_state: State<'_, AppState>,
+    frame_rate: u8,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_idle_frame_rate(frame_rate)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_watermark_get(app_state: State<'_, AppState>) -> CoreResult<bool> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_watermark_enabled()
+}
+
+/// Set whether desktop is streamed with the viewer's device id and the time stamped on
+/// it, sessions negotiated before keep what they negotiated.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_watermark_set(app_state: State<'_, AppState>, enabled: bool) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_watermark_enabled(enabled)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_tile_codec_preferred_get(app_state: State<'_, AppState>) -> CoreResult<bool> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_tile_codec_preferred()
+}
+
+/// Set whether the visits negotiated from now on ask for the tile codec before H.264.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_tile_codec_preferred_set(
+    app_state: State<'_, AppState>,
+    preferred: bool,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_tile_codec_preferred(preferred)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_gop_policy_get(app_state: State<'_, AppState>) -> CoreResult<GopPolicy> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_gop_policy()
+}
+
+/// Set the key frame interval and B-frames which remote encodes the desktop with for the
+/// visits negotiated from now on, interactive use wants neither.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_gop_policy_set(
+    app_state: State<'_, AppState>,
+    policy: GopPolicy,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    policy.validate()?;
+    storage.kv().set_gop_policy(policy)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_session_profile_get(
+    app_state: State<'_, AppState>,
+) -> CoreResult<SessionProfile> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_session_profile()
+}
+
+/// Set whether the visits negotiated from now on are tuned for interactive control or
+/// for smooth streaming.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_session_profile_set(
+    app_state: State<'_, AppState>,
+    profile: SessionProfile,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_session_profile(profile)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_quality_auto_tune_get(app_state: State<'_, AppState>) -> CoreResult<bool> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_quality_auto_tune()
+}
+
+/// Set whether sessions reduce their stream quality by themselves once the quality alerts
+/// suggest it, other remedies are only suggested.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_quality_auto_tune_set(
+    app_state: State<'_, AppState>,
+    enabled: bool,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_quality_auto_tune(enabled)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_test_pattern_get(app_state: State<'_, AppState>) -> CoreResult<Option<String>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_test_pattern()
+}
+
+/// Set the test pattern like `1280x720@30` which sessions negotiated from now on stream in
+/// place of the primary monitor, None streams the real monitors.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_test_pattern_set(
+    app_state: State<'_, AppState>,
+    pattern: Option<String>,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    match pattern {
+        Some(pattern) => {
+            let pattern: TestPattern = pattern
+                .parse()
+                .map_err(|err| core_error!("invalid test pattern ({})", err))?;
+            storage.kv().set_test_pattern(&pattern.to_string())
+        }
+        None => storage.kv().set_test_pattern(""),
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_encoder_thread_priority_get(
+    app_state: State<'_, AppState>,
+) -> CoreResult<ThreadPriority> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_encoder_thread_priority()
+}
+
+/// Set the priority of capture and encode threads, sessions started from now on run
+/// their threads with it.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_encoder_thread_priority_set(
+    app_state: State<'_, AppState>,
+    priority: ThreadPriority,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_encoder_thread_priority(priority)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_encoder_cores_get(
+    app_state: State<'_, AppState>,
+) -> CoreResult<Option<String>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_encoder_cores()
+}
+
+/// Pin capture and encode threads to the cores like `0,2,4-7`, None lets them run on any
+/// core.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_encoder_cores_set(
+    app_state: State<'_, AppState>,
+    cores: Option<String>,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    match cores {
+        Some(cores) => {
+            parse_cores(&cores).map_err(|err| core_error!("invalid encoder cores ({})", err))?;
+            storage.kv().set_encoder_cores(&cores)
+        }
+        None => storage.kv().set_encoder_cores(""),
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_staging_quota_get(app_state: State<'_, AppState>) -> CoreResult<u32> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_staging_quota_mb()
+}
+
+/// Set the quota of staging area in megabytes, dropped files beyond it are refused.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_staging_quota_set(
+    app_state: State<'_, AppState>,
+    quota_mb: u32,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_staging_quota_mb(quota_mb)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_staging_retention_get(app_state: State<'_, AppState>) -> CoreResult<u32> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_staging_retention_days()
+}
+
+/// Set the days which staged files are kept after their session ends.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_staging_retention_set(
+    app_state: State<'_, AppState>,
+    days: u32,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_staging_retention_days(days)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_lan_only_get(app_state: State<'_, AppState>) -> CoreResult<bool> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_lan_only_enabled()
+}
+
+/// Enable or disable LAN-only mode, signaling servers are disconnected and never
+/// contacted while it's enabled, and connected again once it's disabled.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_lan_only_set(app_state: State<'_, AppState>, enabled: bool) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_lan_only_enabled(enabled)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_lan_key_get(app_state: State<'_, AppState>) -> CoreResult<Option<String>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_lan_key()
+}
+
+/// Set the LAN key shared with other devices in LAN, empty key disables it and LAN
+/// connections are not encrypted.
+#[tauri::command]
+#[tracing::instrument(skip(app_state, lan_key))]
+pub async fn config_lan_key_set(app_state: State<'_, AppState>, lan_key: String) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().set_lan_key(&lan_key)
+}
+
+/// Generate and set a new LAN key, it's displayed to be entered on other devices.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_lan_key_generate(app_state: State<'_, AppState>) -> CoreResult<String> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    let lan_key = generate_lan_key();
+    storage.kv().set_lan_key(&lan_key)?;
+
+    Ok(lan_key)
+}
+
+/// Where the long-term secrets of this device are kept, the config file is the fallback
+/// when the secure store of OS isn't available.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_key_store_backend_get(
+    app_state: State<'_, AppState>,
+) -> CoreResult<KeyStoreBackend> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    Ok(storage.kv().key_store_backend())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_lan_listen_ip_get(
+    app_state: State<'_, AppState>,
+) -> CoreResult<Option<IpAddr>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_lan_listen_ip()
+}
+
+/// Set the interface address which the LAN listener binds, None listens on all of them.
+/// The running listener is bound again on it.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_lan_listen_ip_set(
+    app_state: State<'_, AppState>,
+    listen_ip: Option<IpAddr>,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    if let Some(listen_ip) = listen_ip {
+        let interfaces = enum_broadcast_network_interfaces()?;
+        if !interfaces.iter().any(|(_, ip)| *ip == listen_ip) {
+            return Err(core_error!(
+                "{} isn't an address of any interface",
+                listen_ip
+            ));
+        }
+    }
+
+    storage.kv().set_lan_listen_ip(listen_ip)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_bind_ip_get(app_state: State<'_, AppState>) -> CoreResult<Option<IpAddr>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_bind_ip()
+}
+
+/// Set the interface address which outgoing connections leave from, None lets the OS
+/// pick the route. Connections made from now on are bound on it.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_bind_ip_set(
+    app_state: State<'_, AppState>,
+    bind_ip: Option<IpAddr>,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    if let Some(bind_ip) = bind_ip {
+        let interfaces = enum_network_interfaces()?;
+        if !interfaces.iter().any(|interface| interface.ip == bind_ip) {
+            return Err(core_error!("{} isn't an address of any interface", bind_ip));
+        }
+    }
+
+    storage.kv().set_bind_ip(bind_ip)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_log_filter_get(app_state: State<'_, AppState>) -> CoreResult<Option<String>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_log_filter()
+}
+
+/// Set the directives which override the default log filter, like
+/// `mirrorx_core::service::endpoint=debug`. They take effect without restarting.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_log_filter_set(
+    app_state: State<'_, AppState>,
+    directives: Option<String>,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    let directives = directives
+        .map(|directives| directives.trim().to_string())
+        .filter(|directives| !directives.is_empty());
+
+    // reject the invalid directives before they're stored
+    parse_log_filter(directives.as_deref())?;
+
+    storage.kv().set_log_filter(directives.as_deref())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_transport_tuning_get(
+    app_state: State<'_, AppState>,
+) -> CoreResult<TransportTuning> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_transport_tuning()
+}
+
+/// Set the keepalive and DSCP marking of endpoint connections, only the sessions
+/// connected later are tuned with it.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_transport_tuning_set(
+    app_state: State<'_, AppState>,
+    tuning: TransportTuning,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    tuning.validate()?;
+    storage.kv().set_transport_tuning(tuning)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_receive_policy_get(
+    app_state: State<'_, AppState>,
+) -> CoreResult<ReceivePolicy> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_receive_policy()
+}
+
+/// Set which files remote can send to this device and whether they're quarantined until
+/// user confirms, the transfers in progress keep the policy they started with.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn config_receive_policy_set(
+    app_state: State<'_, AppState>,
+    policy: ReceivePolicy,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    policy.validate()?;
+    storage.kv().set_receive_policy(policy)
+}
+
+#[tauri::command]
+#[tracing::instrument]
+pub async fn config_autostart_get() -> CoreResult<bool> {
+    autostart_enabled()
+}
+
+/// Set whether the app starts minimized to tray at login, the registration is kept by
+/// the platform instead of storage.
+#[tauri::command]
+#[tracing::instrument]
+pub async fn config_autostart_set(enabled: bool) -> CoreResult<()> {
+    set_autostart_enabled(enabled)
+}
+
+// session markers remained means the app crashed or was force-quit last time,
+// tell the remote devices through their domains that these sessions were gone
+fn report_dirty_shutdown(storage: &LocalStorage) -> CoreResult<()> {
+    let markers = storage.recovery().get_sessions()?;
+    storage.recovery().clear_sessions()?;
+
+    let mut dirty_sessions: HashMap<(String, i64), Vec<i64>> = HashMap::new();
+    for marker in markers {
+        dirty_sessions
+            .entry((marker.domain, marker.local_device_id))
+            .or_default()
+            .push(marker.remote_device_id);
+    }
+
+    for ((domain, local_device_id), remote_device_ids) in dirty_sessions {
+        let domain = match storage.domain().get_domain_by_name(domain) {
+            Ok(domain) => domain,
+            Err(err) => {
+                tracing::warn!(?err, "domain of dirty session not exists");
+                continue;
+            }
+        };
+
+        tracing::info!(
+            domain = domain.name,
+            ?remote_device_ids,
+            "report dirty shutdown sessions"
+        );
+
+        tokio::spawn(async move {
+            let client = match SignalingClient::new(domain.addr) {
+                Ok(client) => client,
+                Err(err) => {
+                    tracing::error!(?err, "create signaling client failed");
+                    return;
+                }
+            };
+
+            match client
+                .dirty_shutdown(local_device_id, remote_device_ids)
+                .await
+            {
+                Ok(Response::Message(_)) => {}
+                Ok(Response::Error(err)) => tracing::error!(?err, "report dirty shutdown failed"),
+                Err(err) => tracing::error!(?err, "report dirty shutdown failed"),
+            }
+        });
+    }
+
+    Ok(())
+}
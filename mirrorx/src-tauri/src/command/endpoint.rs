@@ -0,0 +1,242 @@
+//! The desktop and file manager windows of one remote device share the endpoint
+//! connection, each window holds a channel of it and the connection is closed with the
+//! last channel.
+
+use super::AppState;
+use crate::window::create_desktop_window;
+use mirrorx_core::{
+    api::{
+        config::entity::history::ConnectPath,
+        endpoint::{client::EndPointClient, message::EndPointChannel, open_desktop_channel},
+    },
+    core_error,
+    error::CoreResult,
+    DesktopDecodeFrame,
+};
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+use tauri::{Manager, WindowEvent};
+use tauri_egui::EguiPluginHandle;
+
+// the preferred path connects alone for a while before the other one joins the race,
+// so a healthy path doesn't cost the remote device a second connection
+const PATH_HEAD_START: Duration = Duration::from_millis(300);
+
+/// The active endpoint client of a visit, desktop visit comes with its decoded frames.
+pub(super) enum VisitEndPoint {
+    Desktop(
+        Arc<EndPointClient>,
+        tokio::sync::mpsc::Receiver<DesktopDecodeFrame>,
+    ),
+    Files(Arc<EndPointClient>),
+}
+
+pub(super) type ConnectFuture<'a> =
+    Pin<Box<dyn Future<Output = CoreResult<VisitEndPoint>> + Send + 'a>>;
+
+/// Race the lan and relay paths to the remote device happy-eyeballs style, the one which
+/// completes its handshake first is kept and the other is dropped.
+pub(super) async fn race_connect_paths(
+    preferred: ConnectPath,
+    lan: ConnectFuture<'_>,
+    relay: ConnectFuture<'_>,
+) -> CoreResult<(ConnectPath, VisitEndPoint)> {
+    let (first_path, mut first, second_path, mut second) = match preferred {
+        ConnectPath::Lan => (ConnectPath::Lan, lan, ConnectPath::Relay, relay),
+        ConnectPath::Relay => (ConnectPath::Relay, relay, ConnectPath::Lan, lan),
+    };
+
+    match tokio::time::timeout(PATH_HEAD_START, &mut first).await {
+        Ok(Ok(endpoint)) => return Ok((first_path, endpoint)),
+        Ok(Err(err)) => {
+            tracing::warn!(?err, path = ?first_path, "connect path failed");
+            return second.await.map(|endpoint| (second_path, endpoint));
+        }
+        Err(_) => {}
+    }
+
+    // the failed path leaves the race to the other one
+    tokio::select! {
+        result = &mut first => match result {
+            Ok(endpoint) => Ok((first_path, endpoint)),
+            Err(err) => {
+                tracing::warn!(?err, path = ?first_path, "connect path failed");
+                second.await.map(|endpoint| (second_path, endpoint))
+            }
+        },
+        result = &mut second => match result {
+            Ok(endpoint) => Ok((second_path, endpoint)),
+            Err(err) => {
+                tracing::warn!(?err, path = ?second_path, "connect path failed");
+                first.await.map(|endpoint| (first_path, endpoint))
+            }
+        },
+    }
+}
+
+impl AppState {
+    /// The connection which the other window of the remote device opened, keyed by remote
+    /// device id or remote ip of lan.
+    pub(super) async fn shared_endpoint(&self, key: &str) -> Option<Arc<EndPointClient>> {
+        let endpoints = self.endpoints.lock().await;
+        let client = endpoints.get(key)?;
+
+        if client.closed() {
+            endpoints.invalidate(key).await;
+            return None;
+        }
+
+        Some(client)
+    }
+
+    /// Release the channel of the closed window, the connection is dropped once no window
+    /// uses it.
+    pub(super) async fn release_endpoint_channel(&self, key: &str, channel: EndPointChannel) {
+        let endpoints = self.endpoints.lock().await;
+        let Some(client) = endpoints.get(key) else {
+            return;
+        };
+
+        client.close_channel(channel);
+
+        if client.closed() {
+            endpoints.invalidate(key).await;
+        }
+    }
+}
+
+/// Open the window on the connection which the other window of the remote device opened,
+/// instead of visiting the remote device again.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn open_shared_endpoint_window(
+    app_handle: tauri::AppHandle,
+    app_state: &AppState,
+    egui_plugin: &EguiPluginHandle,
+    client: Arc<EndPointClient>,
+    key: String,
+    window_label: String,
+    window_title: String,
+    visit_desktop: bool,
+) -> CoreResult<()> {
+    tracing::info!(%key, visit_desktop, "share endpoint connection");
+
+    if visit_desktop {
+        let render_frame_rx = open_desktop_channel(&client).await?;
+        create_desktop_endpoint_window(
+            app_handle,
+            egui_plugin,
+            client,
+            render_frame_rx,
+            window_label,
+            window_title,
+        )
+    } else {
+        if client.channel_opened(EndPointChannel::Files) {
+            return Err(core_error!("remote file manager is opened already"));
+        }
+
+        client.open_files_channel()?;
+        create_file_manager_endpoint_window(app_handle, app_state, key, window_label, window_title)
+            .await
+    }
+}
+
+pub(super) fn create_desktop_endpoint_window(
+    app_handle: tauri::AppHandle,
+    egui_plugin: &EguiPluginHandle,
+    client: Arc<EndPointClient>,
+    render_frame_rx: tokio::sync::mpsc::Receiver<DesktopDecodeFrame>,
+    window_label: String,
+    window_title: String,
+) -> CoreResult<()> {
+    let endpoint_id = client.endpoint_id();
+    let window_client = client.clone();
+
+    if let Err(err) = egui_plugin.create_window(
+        window_label,
+        Box::new(move |cc| {
+            if let Some(gl_context) = cc.gl.as_ref() {
+                Box::new(create_desktop_window(
+                    cc,
+                    app_handle,
+                    gl_context.clone(),
+                    endpoint_id,
+                    window_client,
+                    render_frame_rx,
+                ))
+            } else {
+                panic!("get gl context failed");
+            }
+        }),
+        window_title,
+        tauri_egui::eframe::NativeOptions {
+            // hardware_acceleration: HardwareAcceleration::Required,
+            ..Default::default()
+        },
+    ) {
+        client.close_channel(EndPointChannel::Desktop);
+        tracing::error!(?err, "create desktop window failed");
+        return Err(core_error!("create remote desktop window failed"));
+    }
+
+    Ok(())
+}
+
+/// Create the file manager window of the connection which is cached by `key`, its files
+/// channel is released once the window is destroyed.
+pub(super) async fn create_file_manager_endpoint_window(
+    app_handle: tauri::AppHandle,
+    app_state: &AppState,
+    key: String,
+    window_label: String,
+    window_title: String,
+) -> CoreResult<()> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    let window_key = key.clone();
+    tokio::spawn(async move {
+        let build_result = tauri::WindowBuilder::new(
+            &app_handle,
+            window_label,
+            tauri::WindowUrl::App(format!("/files?device_id={window_key}").into()),
+        )
+        .center()
+        .inner_size(960., 680.)
+        .min_inner_size(960., 680.)
+        .title(window_title)
+        .build();
+
+        match build_result {
+            Ok(window) => {
+                window.on_window_event(move |event| {
+                    if let WindowEvent::Destroyed = event {
+                        let app_handle = app_handle.clone();
+                        let key = window_key.clone();
+                        tauri::async_runtime::spawn(async move {
+                            app_handle
+                                .state::<AppState>()
+                                .release_endpoint_channel(&key, EndPointChannel::Files)
+                                .await;
+                        });
+                    }
+                });
+
+                let _ = tx.send(None);
+            }
+            Err(err) => {
+                let _ = tx.send(Some(err));
+            }
+        }
+    });
+
+    let create_result = rx.await.map_err(|_| core_error!("create window failed"))?;
+
+    if let Some(err) = create_result {
+        app_state
+            .release_endpoint_channel(&key, EndPointChannel::Files)
+            .await;
+        tracing::error!(?err, "create file manager window failed");
+        return Err(core_error!("create remote file manager window failed"));
+    }
+
+    Ok(())
+}
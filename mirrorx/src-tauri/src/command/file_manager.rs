@@ -1,23 +1,35 @@
 use super::AppState;
 use base64::{engine::general_purpose::STANDARD as base64_standard, Engine};
 use mirrorx_core::{
-    api::endpoint::message::{
-        EndPointCallRequest, EndPointDownloadFileReply, EndPointDownloadFileRequest,
-        EndPointFileTransferError, EndPointMessage, EndPointSendFileReply, EndPointSendFileRequest,
-        EndPointVisitDirectoryRequest, EndPointVisitDirectoryResponse,
+    api::{
+        config::entity::recovery::{TransferDirection, TransferManifest},
+        endpoint::{
+            client::{state::SessionState, EndPointClient},
+            message::{
+                EndPointCallRequest, EndPointDownloadFileReply, EndPointDownloadFileRequest,
+                EndPointFileManifestReply, EndPointFileManifestRequest, EndPointFileTransferError,
+                EndPointMessage, EndPointSendFileReply, EndPointSendFileRequest,
+                EndPointVisitDirectoryRequest, EndPointVisitDirectoryResponse,
+            },
+        },
     },
     component::fs::{
-        transfer::{
-            create_file_append_session, query_transferred_bytes_count, send_file_to_remote,
-        },
+        bridge::FsBridge,
+        manifest::{build_manifest, compare_manifests, VerificationReport},
+        transfer::{create_file_append_session, send_file_delta_to_remote, send_file_to_remote},
         IconType,
     },
     core_error,
     error::CoreResult,
+    utility::cancel,
 };
 use rayon::prelude::*;
-use serde::Serialize;
-use std::{collections::HashMap, path::PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 #[derive(Serialize)]
 pub struct DirectoryResult {
@@ -36,24 +48,56 @@ pub struct EntryResult {
     pub icon_hash: Option<String>,
 }
 
+#[derive(Serialize)]
+pub struct SessionStateResult {
+    pub session_id: String,
+    #[serde(flatten)]
+    pub state: SessionState,
+}
+
+/// Current state of the session, later transitions are emitted as `core/session/state`
+/// events of the returned session id.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn file_manager_session_state(
+    app_state: tauri::State<'_, AppState>,
+    remote_device_id: String,
+) -> CoreResult<SessionStateResult> {
+    let client = app_state
+        .endpoints
+        .lock()
+        .await
+        .get(&remote_device_id)
+        .ok_or_else(|| core_error!("remote file manager not exist"))?;
+
+    Ok(SessionStateResult {
+        session_id: client.session_id().to_string(),
+        state: client.state(),
+    })
+}
+
 #[tauri::command]
 #[tracing::instrument(skip(app_state))]
 pub async fn file_manager_visit_remote(
     app_state: tauri::State<'_, AppState>,
     remote_device_id: String,
     path: Option<PathBuf>,
+    request_id: Option<String>,
 ) -> CoreResult<DirectoryResult> {
+    let registration = cancel::register(request_id);
+
     let client = app_state
-        .files_endpoints
+        .endpoints
         .lock()
         .await
         .get(&remote_device_id)
         .ok_or_else(|| core_error!("remote file manager not exist"))?;
 
     let reply: EndPointVisitDirectoryResponse = client
-        .call(EndPointCallRequest::VisitDirectoryRequest(
-            EndPointVisitDirectoryRequest { path },
-        ))
+        .call_cancelable(
+            EndPointCallRequest::VisitDirectoryRequest(EndPointVisitDirectoryRequest { path }),
+            registration.token(),
+        )
         .await?;
 
     let path = reply.dir.path;
@@ -101,10 +145,13 @@ pub async fn file_manager_visit_remote(
 
 #[tauri::command]
 #[tracing::instrument]
-pub async fn file_manager_visit_local(path: Option<PathBuf>) -> CoreResult<DirectoryResult> {
+pub async fn file_manager_visit_local(
+    path: Option<PathBuf>,
+    request_id: Option<String>,
+) -> CoreResult<DirectoryResult> {
     let directory = if let Some(path) = path {
         tracing::info!(?path, "require path");
-        mirrorx_core::component::fs::read_directory(&path)
+        mirrorx_core::component::fs::read_directory_with_progress(&path, request_id)
     } else {
         mirrorx_core::component::fs::read_root_directory()
     }?;
@@ -156,6 +203,7 @@ pub async fn file_manager_send_file(
     remote_device_id: String,
     local_path: PathBuf,
     remote_path: PathBuf,
+    delta: bool,
 ) -> CoreResult<(String, u64)> {
     if !local_path.is_file() {
         return Err(core_error!("local path is not a file"));
@@ -176,24 +224,48 @@ pub async fn file_manager_send_file(
     let id = uuid::Uuid::new_v4().to_string();
 
     let client = app_state
-        .files_endpoints
+        .endpoints
         .lock()
         .await
         .get(&remote_device_id)
         .ok_or_else(|| core_error!("remote file manager not exist"))?;
 
-    let _: EndPointSendFileReply = client
+    let reply: EndPointSendFileReply = client
         .call(EndPointCallRequest::SendFileRequest(
             EndPointSendFileRequest {
                 id: id.clone(),
                 filename,
-                path: remote_path,
+                path: remote_path.clone(),
                 size,
+                resume: false,
+                staged: false,
+                delta,
             },
         ))
         .await?;
 
-    send_file_to_remote(id.clone(), client, &local_path).await?;
+    // the existing remote file is only replaced when the delta is complete, so an
+    // interrupted delta transfer has nothing to resume
+    if let Some(signature) = reply.signature {
+        tracing::info!(?id, blocks = signature.blocks.len(), "send file delta");
+        send_file_delta_to_remote(id.clone(), client, &local_path, signature).await?;
+        return Ok((id, size));
+    }
+
+    save_transfer_manifest(
+        &app_state,
+        TransferManifest::new(
+            id.clone(),
+            remote_device_id,
+            TransferDirection::Send,
+            local_path.clone(),
+            remote_path,
+            size,
+        ),
+    )
+    .await;
+
+    send_file_to_remote(id.clone(), client, &local_path, 0).await?;
 
     Ok((id, size))
 }
@@ -213,7 +285,7 @@ pub async fn file_manager_download_file(
     let id = uuid::Uuid::new_v4().to_string();
 
     let client = app_state
-        .files_endpoints
+        .endpoints
         .lock()
         .await
         .get(&remote_device_id)
@@ -223,25 +295,274 @@ pub async fn file_manager_download_file(
         .call(EndPointCallRequest::DownloadFileRequest(
             EndPointDownloadFileRequest {
                 id: id.clone(),
-                path: remote_path,
+                path: remote_path.clone(),
+                offset: 0,
             },
         ))
         .await?;
 
-    if let Err(err) = create_file_append_session(id.clone(), &local_path).await {
+    save_transfer_manifest(
+        &app_state,
+        TransferManifest::new(
+            id.clone(),
+            remote_device_id,
+            TransferDirection::Download,
+            local_path.clone(),
+            remote_path,
+            reply.size,
+        ),
+    )
+    .await;
+
+    start_download_session(&client, &id, &local_path, 0, reply.size).await?;
+
+    Ok((id, reply.size))
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn file_manager_interrupted_transfers(
+    app_state: tauri::State<'_, AppState>,
+) -> CoreResult<Vec<TransferManifest>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.recovery().get_transfers()
+}
+
+/// Resume the interrupted transfer from where it was stopped, the file manager of
+/// the remote device must be visited before resuming.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn file_manager_resume_transfer(
+    app_state: tauri::State<'_, AppState>,
+    id: String,
+) -> CoreResult<(String, u64)> {
+    let manifest = {
+        let Some(ref storage) = *app_state.storage.lock().await else {
+            return Err(core_error!("storage not initialize"));
+        };
+
+        storage
+            .recovery()
+            .get_transfer(&id)?
+            .ok_or_else(|| core_error!("interrupted transfer not exist"))?
+    };
+
+    let client = app_state
+        .endpoints
+        .lock()
+        .await
+        .get(&manifest.remote_device_id)
+        .ok_or_else(|| core_error!("remote file manager not exist"))?;
+
+    match manifest.direction {
+        TransferDirection::Send => {
+            if !manifest.local_path.is_file() {
+                return Err(core_error!("local path is not a file"));
+            }
+
+            let Some(filename) = manifest.local_path.file_name() else {
+                return Err(core_error!("local path get filename failed"));
+            };
+
+            let filename = filename
+                .to_str()
+                .ok_or_else(|| core_error!("convert filename failed"))?
+                .to_string();
+
+            let size = manifest.local_path.metadata()?.len();
+
+            let reply: EndPointSendFileReply = client
+                .call(EndPointCallRequest::SendFileRequest(
+                    EndPointSendFileRequest {
+                        id: id.clone(),
+                        filename,
+                        path: manifest.remote_path.clone(),
+                        size,
+                        resume: true,
+                        staged: false,
+                        delta: false,
+                    },
+                ))
+                .await?;
+
+            tracing::info!(?id, offset = reply.offset, "resume sending file");
+
+            send_file_to_remote(id.clone(), client, &manifest.local_path, reply.offset).await?;
+
+            Ok((id, size))
+        }
+        TransferDirection::Download => {
+            // the buffered but unflushed bytes were lost, so the partial file length
+            // is where the download continues from
+            let offset = match manifest.local_path.metadata() {
+                Ok(meta) if meta.is_file() => meta.len(),
+                _ => 0,
+            };
+
+            let reply: EndPointDownloadFileReply = client
+                .call(EndPointCallRequest::DownloadFileRequest(
+                    EndPointDownloadFileRequest {
+                        id: id.clone(),
+                        path: manifest.remote_path.clone(),
+                        offset,
+                    },
+                ))
+                .await?;
+
+            tracing::info!(?id, offset, "resume downloading file");
+
+            start_download_session(&client, &id, &manifest.local_path, offset, reply.size).await?;
+
+            Ok((id, reply.size))
+        }
+    }
+}
+
+/// Give up the interrupted transfer and remove the partial file it downloaded.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn file_manager_discard_transfer(
+    app_state: tauri::State<'_, AppState>,
+    id: String,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    if let Some(manifest) = storage.recovery().get_transfer(&id)? {
+        if manifest.direction == TransferDirection::Download && manifest.local_path.is_file() {
+            std::fs::remove_file(&manifest.local_path)?;
+        }
+    }
+
+    storage.recovery().delete_transfer(&id)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TransferRoot {
+    pub local_path: PathBuf,
+    pub remote_path: PathBuf,
+}
+
+/// Compare the manifests of both ends for each root of a finished transfer, the report
+/// tells whether every file is complete and intact.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn file_manager_verify_transfer(
+    app_state: tauri::State<'_, AppState>,
+    remote_device_id: String,
+    roots: Vec<TransferRoot>,
+) -> CoreResult<VerificationReport> {
+    let client = app_state
+        .endpoints
+        .lock()
+        .await
+        .get(&remote_device_id)
+        .ok_or_else(|| core_error!("remote file manager not exist"))?;
+
+    let mut report = VerificationReport::default();
+
+    for root in roots {
+        let reply: EndPointFileManifestReply = client
+            .call(EndPointCallRequest::FileManifestRequest(
+                EndPointFileManifestRequest {
+                    path: root.remote_path,
+                },
+            ))
+            .await?;
+
+        let local_path = root.local_path.clone();
+        let local_entries = tokio::task::spawn_blocking(move || build_manifest(&local_path))
+            .await
+            .map_err(|_| core_error!("build file manifest failed"))??;
+
+        report.merge(compare_manifests(
+            &root.local_path,
+            local_entries,
+            reply.entries,
+        ));
+    }
+
+    tracing::info!(
+        intact = report.intact,
+        verified_bytes = report.verified_bytes,
+        "verify transfer"
+    );
+
+    Ok(report)
+}
+
+/// Serve the remote filesystem as a WebDAV endpoint on localhost, the returned url can
+/// be mounted in file explorer.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn file_manager_bridge_start(
+    app_state: tauri::State<'_, AppState>,
+    remote_device_id: String,
+) -> CoreResult<String> {
+    let mut fs_bridges = app_state.fs_bridges.lock().await;
+    if let Some(bridge) = fs_bridges.get(&remote_device_id) {
+        return Ok(bridge.url());
+    }
+
+    let client = app_state
+        .endpoints
+        .lock()
+        .await
+        .get(&remote_device_id)
+        .ok_or_else(|| core_error!("remote file manager not exist"))?;
+
+    let bridge = FsBridge::serve(client).await?;
+    let url = bridge.url();
+    fs_bridges.insert(remote_device_id, bridge);
+
+    Ok(url)
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn file_manager_bridge_stop(
+    app_state: tauri::State<'_, AppState>,
+    remote_device_id: String,
+) -> CoreResult<()> {
+    // the bridge stops serving when it's dropped
+    app_state.fs_bridges.lock().await.remove(&remote_device_id);
+    Ok(())
+}
+
+async fn save_transfer_manifest(
+    app_state: &tauri::State<'_, AppState>,
+    manifest: TransferManifest,
+) {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return;
+    };
+
+    // the transfer still works without manifest, only it can't be resumed after crash
+    if let Err(err) = storage.recovery().save_transfer(&manifest) {
+        tracing::error!(?err, id = manifest.id, "save transfer manifest failed");
+    }
+}
+
+async fn start_download_session(
+    client: &Arc<EndPointClient>,
+    id: &str,
+    local_path: &Path,
+    offset: u64,
+    total: u64,
+) -> CoreResult<()> {
+    if let Err(err) = create_file_append_session(id.to_string(), local_path, offset, total).await {
         let _ = client
             .send(&EndPointMessage::FileTransferError(
-                EndPointFileTransferError { id: id.clone() },
+                EndPointFileTransferError { id: id.to_string() },
             ))
             .await;
 
         return Err(err);
     }
 
-    Ok((id, reply.size))
-}
-
-#[tauri::command]
-pub async fn file_manager_query_transferred_bytes_count(id: String) -> u64 {
-    query_transferred_bytes_count(&id)
+    Ok(())
 }
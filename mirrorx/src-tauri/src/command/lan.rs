@@ -1,12 +1,26 @@
-use crate::{command::AppState, window::create_desktop_window};
+use super::{
+    endpoint::{
+        create_desktop_endpoint_window, create_file_manager_endpoint_window,
+        open_shared_endpoint_window,
+    },
+    AppState,
+};
 use mirrorx_core::{
-    api::endpoint::{
-        create_desktop_active_endpoint_client, create_file_manager_active_endpoint_client,
-        id::EndPointID, EndPointStream,
+    api::{
+        config::entity::keystore::PairedPeer,
+        endpoint::{
+            create_desktop_active_endpoint_client, create_file_manager_active_endpoint_client,
+            id::EndPointID, EndPointStream,
+        },
+    },
+    component::lan::{
+        key::{connect_with_lan_key, lan_key},
+        keystore::{connect_with_peer_key, export_peer_card, has_paired_peers, import_peer_card},
+        LANProvider, ListenerReport, Node, LISTEN_PORT,
     },
-    component::lan::{LANProvider, Node},
     core_error,
-    error::CoreResult,
+    error::{CoreError, CoreResult},
+    utility::cancel,
 };
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use tauri_egui::EguiPluginHandle;
@@ -14,10 +28,28 @@ use tauri_egui::EguiPluginHandle;
 #[tauri::command]
 #[tracing::instrument(skip(app_state))]
 pub async fn lan_init(app_state: tauri::State<'_, AppState>, force: bool) -> CoreResult<()> {
+    // storage may not be initialized yet, devices are discoverable by default and listen
+    // on all interfaces
+    let (discoverable, listen_ip) = match *app_state.storage.lock().await {
+        Some(ref storage) => (
+            storage.kv().get_lan_discoverable()?,
+            storage
+                .kv()
+                .get_lan_listen_ip()?
+                .or(storage.kv().get_bind_ip()?),
+        ),
+        None => (true, None),
+    };
+
     let mut lan_provider = app_state.lan_provider.lock().await;
 
     if force || lan_provider.is_none() {
-        *lan_provider = Some(LANProvider::new().await?);
+        // the previous listener must release the port before the new one binds it
+        if let Some(previous) = lan_provider.take() {
+            previous.close().await;
+        }
+
+        *lan_provider = Some(LANProvider::new(discoverable, listen_ip).await?);
     }
 
     Ok(())
@@ -31,7 +63,10 @@ pub async fn lan_connect(
     egui_plugin: tauri::State<'_, EguiPluginHandle>,
     addr: String,
     visit_desktop: bool,
+    request_id: Option<String>,
 ) -> CoreResult<()> {
+    let registration = cancel::register(request_id);
+
     let remote_ip: IpAddr = addr
         .parse()
         .map_err(|_| core_error!("parse addr to IpAddr failed"))?;
@@ -48,94 +83,114 @@ pub async fn lan_connect(
         format!("MirrorX File Transfer {remote_ip}")
     };
 
-    let remote_addr = SocketAddr::new(remote_ip, 48001);
+    if let Some(client) = app_state.shared_endpoint(&remote_ip.to_string()).await {
+        return open_shared_endpoint_window(
+            app_handle,
+            &app_state,
+            &egui_plugin,
+            client,
+            remote_ip.to_string(),
+            window_label,
+            window_title,
+            visit_desktop,
+        )
+        .await;
+    }
+
+    let remote_addr = SocketAddr::new(remote_ip, LISTEN_PORT);
 
     let endpoint_id = EndPointID::LANID {
         local_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
         remote_ip,
     };
 
+    let lan_only = match *app_state.storage.lock().await {
+        Some(ref storage) => storage.kv().get_lan_only_enabled()?,
+        None => return Err(core_error!("storage not initialize")),
+    };
+
+    // paired devices authenticate with their identity keys, the lan key is the fallback
+    // when remote isn't paired with this device
+    let paired = if has_paired_peers() {
+        match cancel::cancelable(registration.token(), connect_with_peer_key(remote_addr)).await {
+            Ok((stream, key_pair, peer)) => {
+                tracing::info!(name = %peer.name, "paired peer authenticated");
+                Some((EndPointStream::PassiveTCP(stream), Some(key_pair)))
+            }
+            Err(CoreError::Canceled) => return Err(CoreError::Canceled),
+            Err(err) => {
+                tracing::info!(?err, "peer key exchange failed, fall back to lan key");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // with lan key, the stream is connected and encrypted by the key agreed with remote,
+    // lan only mode requires it since there's no signaling server to exchange key
+    let (stream, key_pair) = match (paired, lan_key()) {
+        (Some(paired), _) => paired,
+        (None, Some(lan_key)) => {
+            let (stream, key_pair) = cancel::cancelable(
+                registration.token(),
+                connect_with_lan_key(remote_addr, &lan_key),
+            )
+            .await?;
+            (EndPointStream::PassiveTCP(stream), Some(key_pair))
+        }
+        (None, None) if lan_only => {
+            return Err(core_error!(
+                "lan key or pairing is required in lan only mode"
+            ))
+        }
+        (None, None) => (EndPointStream::ActiveTCP(remote_addr), None),
+    };
+
     if visit_desktop {
-        let (client, render_frame_rx) = create_desktop_active_endpoint_client(
-            endpoint_id,
-            None,
-            EndPointStream::ActiveTCP(remote_addr),
-            None,
+        let (client, render_frame_rx) = cancel::cancelable(
+            registration.token(),
+            create_desktop_active_endpoint_client(endpoint_id, key_pair, stream, None),
         )
         .await?;
 
-        if let Err(err) = egui_plugin.create_window(
+        app_state
+            .endpoints
+            .lock()
+            .await
+            .insert(remote_ip.to_string(), client.clone())
+            .await;
+
+        create_desktop_endpoint_window(
+            app_handle,
+            &egui_plugin,
+            client,
+            render_frame_rx,
             window_label.clone(),
-            Box::new(move |cc| {
-                if let Some(gl_context) = cc.gl.as_ref() {
-                    Box::new(create_desktop_window(
-                        cc,
-                        gl_context.clone(),
-                        endpoint_id,
-                        client,
-                        render_frame_rx,
-                    ))
-                } else {
-                    panic!("get gl context failed");
-                }
-            }),
             window_label,
-            tauri_egui::eframe::NativeOptions {
-                // hardware_acceleration: HardwareAcceleration::Required,
-                ..Default::default()
-            },
-        ) {
-            tracing::error!(?err, "create desktop window failed");
-            return Err(core_error!("create remote desktop window failed"));
-        }
+        )?;
     } else {
-        let client = create_file_manager_active_endpoint_client(
-            endpoint_id,
-            None,
-            EndPointStream::ActiveTCP(remote_addr),
-            None,
+        let client = cancel::cancelable(
+            registration.token(),
+            create_file_manager_active_endpoint_client(endpoint_id, key_pair, stream, None),
         )
         .await?;
 
         app_state
-            .files_endpoints
+            .endpoints
             .lock()
             .await
             .insert(remote_ip.to_string(), client)
             .await;
 
-        let (tx, rx) = tokio::sync::oneshot::channel();
-
-        tokio::spawn(async move {
-            if let Err(err) = tauri::WindowBuilder::new(
-                &app_handle,
-                window_label,
-                tauri::WindowUrl::App(format!("/files?device_id={remote_ip}").into()),
-            )
-            .center()
-            .inner_size(960., 680.)
-            .min_inner_size(960., 680.)
-            .title(window_title)
-            .build()
-            {
-                let _ = tx.send(Some(err));
-            } else {
-                let _ = tx.send(None);
-            }
-        });
-
-        let create_result = rx.await.map_err(|_| core_error!("create window failed"))?;
-
-        if let Some(err) = create_result {
-            app_state
-                .files_endpoints
-                .lock()
-                .await
-                .invalidate(&remote_ip.to_string())
-                .await;
-            tracing::error!(?err, "create file manager window failed");
-            return Err(core_error!("create remote file manager window failed"));
-        }
+        create_file_manager_endpoint_window(
+            app_handle,
+            &app_state,
+            remote_ip.to_string(),
+            window_label,
+            window_title,
+        )
+        .await?;
     }
 
     Ok(())
@@ -186,20 +241,92 @@ pub async fn lan_discoverable_set(
     app_state: tauri::State<'_, AppState>,
     discoverable: bool,
 ) -> CoreResult<()> {
-    if let Some(ref discover) = *app_state.lan_provider.lock().await {
-        discover.set_discoverable(discoverable);
-        Ok(())
-    } else {
-        Err(core_error!("lan discover is empty"))
-    }
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    // the running LAN provider follows the change
+    storage.kv().set_lan_discoverable(discoverable)
 }
 
 #[tauri::command]
 #[tracing::instrument(skip(app_state))]
 pub async fn lan_discoverable_get(app_state: tauri::State<'_, AppState>) -> CoreResult<bool> {
-    if let Some(ref discover) = *app_state.lan_provider.lock().await {
-        Ok(discover.discoverable())
-    } else {
-        Err(core_error!("lan discover is empty"))
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.kv().get_lan_discoverable()
+}
+
+/// Probe the listener which LAN visitors connect to, the hints tell user why visitors
+/// can't connect.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn lan_listener_report(
+    app_state: tauri::State<'_, AppState>,
+) -> CoreResult<ListenerReport> {
+    let Some(ref lan_provider) = *app_state.lan_provider.lock().await else {
+        return Err(core_error!("lan discover is empty"));
+    };
+
+    let report = lan_provider.listener_report().await;
+    if !report.hints.is_empty() {
+        tracing::warn!(?report, "lan listener may be unreachable");
     }
+
+    Ok(report)
+}
+
+/// The peer card of this device, other devices import it to be paired with this device.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn lan_peer_card_export(app_state: tauri::State<'_, AppState>) -> CoreResult<String> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    export_peer_card(storage)
+}
+
+/// Pair the device of the peer card which was scanned or read from file, returns the
+/// fingerprint of it for user to compare with the one shown on that device.
+#[tauri::command]
+#[tracing::instrument(skip(app_state, card))]
+pub async fn lan_peer_card_import(
+    app_state: tauri::State<'_, AppState>,
+    card: String,
+) -> CoreResult<String> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    Ok(import_peer_card(storage, &card)?.fingerprint())
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn lan_paired_peers_list(
+    app_state: tauri::State<'_, AppState>,
+) -> CoreResult<Vec<PairedPeer>> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.keystore().get_peers()
+}
+
+/// Unpair the device, connections from it aren't accepted anymore unless it knows the
+/// lan key.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn lan_paired_peer_revoke(
+    app_state: tauri::State<'_, AppState>,
+    id: i64,
+) -> CoreResult<()> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    storage.keystore().delete_peer(id)
 }
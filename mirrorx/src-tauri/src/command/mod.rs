@@ -1,31 +1,70 @@
 pub mod config;
+mod endpoint;
 pub mod file_manager;
 pub mod lan;
+pub mod permission;
+pub mod quarantine;
+pub mod queue;
 pub mod signaling;
+pub mod staging;
+pub mod updater;
 pub mod utility;
 
 use mirrorx_core::{
-    api::{config::LocalStorage, endpoint::client::EndPointClient, signaling::SignalingClient},
-    component::lan::LANProvider,
+    api::{
+        config::{entity::domain::Domain, LocalStorage},
+        endpoint::client::EndPointClient,
+        signaling::SignalingClient,
+    },
+    component::{fs::bridge::FsBridge, lan::LANProvider},
+    core_error,
+    error::CoreResult,
 };
 use moka::future::{Cache, CacheBuilder};
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use tauri::async_runtime::Mutex;
 
 pub struct AppState {
     storage: Mutex<Option<LocalStorage>>,
-    signaling_client: Mutex<Option<(i64, SignalingClient)>>,
+    // signaling clients subscribed with the device identity of each domain, keyed by domain id
+    signaling_clients: Mutex<HashMap<i64, SignalingClient>>,
     lan_provider: Mutex<Option<LANProvider>>,
-    files_endpoints: Mutex<Cache<String, Arc<EndPointClient>>>,
+    // connections shared by the desktop and file manager windows of each remote device
+    endpoints: Mutex<Cache<String, Arc<EndPointClient>>>,
+    // WebDAV bridges of remote filesystems, keyed by remote device id
+    fs_bridges: Mutex<HashMap<String, FsBridge>>,
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
             storage: Mutex::new(None),
-            signaling_client: Mutex::new(None),
+            signaling_clients: Mutex::new(HashMap::new()),
             lan_provider: Mutex::new(None),
-            files_endpoints: Mutex::new(CacheBuilder::new(64).build()),
+            endpoints: Mutex::new(CacheBuilder::new(64).build()),
+            fs_bridges: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn primary_domain(&self) -> CoreResult<Domain> {
+        let Some(ref storage) = *self.storage.lock().await else {
+            return Err(core_error!("storage not initialize"));
+        };
+
+        storage.domain().get_primary_domain()
+    }
+
+    /// Sessions are closed on purpose when user quits the app, so they shouldn't be
+    /// reported as dirty shutdown on next launch.
+    pub fn clear_session_markers(&self) {
+        let Ok(storage) = self.storage.try_lock() else {
+            return;
+        };
+
+        if let Some(ref storage) = *storage {
+            if let Err(err) = storage.recovery().clear_sessions() {
+                tracing::error!(?err, "clear session markers failed");
+            }
         }
     }
 }
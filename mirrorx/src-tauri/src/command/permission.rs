@@ -0,0 +1,75 @@
+use super::AppState;
+use mirrorx_core::{
+    api::endpoint::permission::{reply_elevation, ElevationDecision, GrantScope, Permissions},
+    component::local_input_block::reply_local_input_block,
+    core_error,
+    error::CoreResult,
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct ElevationResult {
+    pub permissions: Vec<&'static str>,
+    pub granted: Vec<&'static str>,
+    // None when nothing is granted or granted for the whole session
+    pub expires_in_secs: Option<u32>,
+}
+
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn permission_request(
+    app_state: tauri::State<'_, AppState>,
+    remote_device_id: String,
+    permissions: Vec<String>,
+) -> CoreResult<ElevationResult> {
+    let permissions = Permissions::from_names(&permissions)?;
+
+    let client = app_state
+        .endpoints
+        .lock()
+        .await
+        .get(&remote_device_id)
+        .ok_or_else(|| core_error!("remote file manager not exist"))?;
+
+    let reply = client.request_permissions(permissions).await?;
+
+    Ok(ElevationResult {
+        permissions: reply.permissions.names(),
+        granted: reply.granted.names(),
+        expires_in_secs: match reply.scope {
+            Some(GrantScope::Temporary { secs }) => Some(secs),
+            _ => None,
+        },
+    })
+}
+
+/// Reply the elevation request which is prompted by `popup_dialog_permission_request`,
+/// granting none rejects it and `temporary_secs` being None grants for the session.
+#[tauri::command]
+#[tracing::instrument]
+pub async fn permission_reply(
+    request_id: String,
+    granted: Vec<String>,
+    temporary_secs: Option<u32>,
+) -> CoreResult<()> {
+    let scope = match temporary_secs {
+        Some(secs) => GrantScope::Temporary { secs },
+        None => GrantScope::Session,
+    };
+
+    reply_elevation(
+        &request_id,
+        ElevationDecision {
+            granted: Permissions::from_names(&granted)?,
+            scope,
+        },
+    )
+}
+
+/// Reply the request of remote to block the local mouse and keyboard, it's prompted by
+/// the `core/input/block_request` event.
+#[tauri::command]
+#[tracing::instrument]
+pub async fn local_input_block_reply(request_id: String, allowed: bool) -> CoreResult<()> {
+    reply_local_input_block(&request_id, allowed)
+}
@@ -0,0 +1,27 @@
+use mirrorx_core::{
+    component::fs::receive_policy::{
+        discard_quarantined_file, quarantined_files, release_quarantined_file, QuarantinedFile,
+    },
+    error::CoreResult,
+};
+use std::path::PathBuf;
+
+/// Received files which wait for user to confirm.
+#[tauri::command]
+#[tracing::instrument]
+pub async fn quarantine_list() -> CoreResult<Vec<QuarantinedFile>> {
+    Ok(quarantined_files())
+}
+
+/// Move the quarantined file to where remote sent it, returns the destination.
+#[tauri::command]
+#[tracing::instrument]
+pub async fn quarantine_release(id: String) -> CoreResult<PathBuf> {
+    release_quarantined_file(&id)
+}
+
+#[tauri::command]
+#[tracing::instrument]
+pub async fn quarantine_discard(id: String) -> CoreResult<()> {
+    discard_quarantined_file(&id)
+}
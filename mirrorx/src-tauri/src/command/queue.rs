@@ -0,0 +1,23 @@
+use mirrorx_core::{
+    api::endpoint::queue::{move_queued_visit, queued_visits, reject_queued_visit, QueuedVisit},
+    error::CoreResult,
+};
+
+/// Visits which are waiting for a free session, the head is admitted first.
+#[tauri::command]
+#[tracing::instrument]
+pub async fn queue_list() -> CoreResult<Vec<QueuedVisit>> {
+    Ok(queued_visits())
+}
+
+#[tauri::command]
+#[tracing::instrument]
+pub async fn queue_move(id: String, position: usize) -> CoreResult<()> {
+    move_queued_visit(&id, position)
+}
+
+#[tauri::command]
+#[tracing::instrument]
+pub async fn queue_reject(id: String) -> CoreResult<()> {
+    reject_queued_visit(&id)
+}
@@ -1,50 +1,187 @@
-use super::AppState;
-use crate::window::create_desktop_window;
+use super::{
+    endpoint::{
+        create_desktop_endpoint_window, create_file_manager_endpoint_window,
+        open_shared_endpoint_window, race_connect_paths, VisitEndPoint,
+    },
+    AppState,
+};
+use crate::{tray, utility::format_device_id};
 use mirrorx_core::{
     api::{
+        config::{
+            change::{self, ConfigChange},
+            entity::{domain::Domain, history::ConnectPath},
+            LocalStorage,
+        },
         endpoint::{
+            client::{EndPointClient, ReconnectParams, Reconnector},
             create_desktop_active_endpoint_client, create_file_manager_active_endpoint_client,
-            id::EndPointID, EndPointStream,
+            id::EndPointID,
+            message::{
+                EndPointCallRequest, EndPointInviteObserverReply, EndPointInviteObserverRequest,
+            },
+            EndPointStream,
         },
-        signaling::{http_message::Response, SignalingClient},
+        signaling::{
+            fingerprint::{load_identity_key, verify_peer},
+            http_message::{HttpError, Response},
+            observer::ObserverInvitation,
+            pairing::{create_pairing_payload, revoke_pairing_secrets, PairingPayload},
+            visit_prompt::reply_visit,
+            SignalingClient, SignalingState,
+        },
+    },
+    component::{
+        lan::key::{connect_with_lan_key, lan_key},
+        telemetry::{self, FailureCategory},
     },
     core_error,
-    error::CoreResult,
+    error::{CoreError, CoreResult},
+    utility::cancel,
+};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs},
 };
-use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
-use tauri::http::Uri;
+use tauri::{http::Uri, AppHandle, Manager};
 use tauri_egui::EguiPluginHandle;
 
+// remote caps it with its own limit
+const DEFAULT_OBSERVER_INVITATION_TTL_SECS: u32 = 10 * 60;
+
+/// Subscribe to the signaling server of every domain, so the device can be visited by
+/// its identity in any domain. Connected domains are skipped unless `force` is true.
 #[tauri::command]
 #[tracing::instrument(skip(app_state))]
 pub async fn signaling_connect(
     app_state: tauri::State<'_, AppState>,
     force: bool,
 ) -> CoreResult<()> {
-    let mut current_signaling = app_state.signaling_client.lock().await;
+    connect_all_domains(&app_state, force).await
+}
+
+/// Disconnect signaling servers once LAN-only mode is enabled and connect them again once
+/// it's disabled, whoever changes it.
+pub fn follow_lan_only_changes(app_handle: AppHandle) {
+    let mut rx = change::subscribe();
+
+    tokio::spawn(async move {
+        loop {
+            let change = match rx.recv().await {
+                Ok(change) => change,
+                Err(_) if rx.is_closed() => return,
+                // overflowed, only the latest changes matter anyway
+                Err(_) => continue,
+            };
+
+            let ConfigChange::LanOnly(enabled) = change else {
+                continue;
+            };
+
+            let app_state = app_handle.state::<AppState>();
+
+            if enabled {
+                // dropping the clients closes their subscriptions
+                app_state.signaling_clients.lock().await.clear();
+            } else if let Err(err) = connect_all_domains(&app_state, false).await {
+                tracing::error!(?err, "reconnect signaling after lan only mode failed");
+            }
+        }
+    });
+}
+
+async fn connect_all_domains(app_state: &AppState, force: bool) -> CoreResult<()> {
+    let mut signaling_clients = app_state.signaling_clients.lock().await;
 
     let Some(ref storage) = *app_state.storage.lock().await else {
         return Err(core_error!("storage not initialize"));
     };
 
-    let primary_domain = storage.domain().get_primary_domain()?;
+    if storage.kv().get_lan_only_enabled()? {
+        tracing::info!("skip signaling connect in lan only mode");
+        signaling_clients.clear();
+        return Ok(());
+    }
+
+    let domains = storage.domain().get_all_domains()?;
 
-    if let Some((current_domain_id, _)) = *current_signaling {
-        if current_domain_id == primary_domain.id && !force {
-            return Ok(());
+    // drop the subscriptions of deleted domains
+    signaling_clients.retain(|domain_id, _| domains.iter().any(|domain| domain.id == *domain_id));
+
+    // only the failure of primary domain is reported, all of them keep retrying in background
+    let mut result = Ok(());
+
+    for domain in domains {
+        if signaling_clients.contains_key(&domain.id) && !force {
+            continue;
+        }
+
+        // the old subscription must be closed before the new one reports its state
+        signaling_clients.remove(&domain.id);
+
+        if let Err(err) = connect_domain(&domain, storage.clone(), &mut signaling_clients).await {
+            tracing::error!(
+                ?err,
+                domain = domain.name,
+                "connect domain signaling failed"
+            );
+
+            if domain.is_primary {
+                result = Err(err);
+            }
         }
     }
 
-    let addrs: Vec<SocketAddr> = if let Ok(ipv4_addr) = primary_domain.addr.parse::<Ipv4Addr>() {
-        vec![(ipv4_addr, primary_domain.subscribe_port).into()]
-    } else if let Ok(ipv6_addr) = primary_domain.addr.parse::<Ipv6Addr>() {
-        vec![(ipv6_addr, primary_domain.subscribe_port).into()]
-    } else if let Ok(url_addr) = primary_domain.addr.parse::<Uri>() {
+    result
+}
+
+/// Connection state of the subscription of domain `domain_id`, or the primary domain
+/// when it's None.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn signaling_state(
+    app_state: tauri::State<'_, AppState>,
+    domain_id: Option<i64>,
+) -> CoreResult<SignalingState> {
+    let domain_id = match domain_id {
+        Some(domain_id) => domain_id,
+        None => {
+            let Some(ref storage) = *app_state.storage.lock().await else {
+                return Err(core_error!("storage not initialize"));
+            };
+
+            storage.domain().get_primary_domain()?.id
+        }
+    };
+
+    Ok(app_state
+        .signaling_clients
+        .lock()
+        .await
+        .get(&domain_id)
+        .map(|client| client.state())
+        .unwrap_or(SignalingState::Disconnected))
+}
+
+/// Subscribe to the signaling server of `domain`, the client is kept in
+/// `signaling_clients` once its address is resolved, since the subscription keeps
+/// reconnecting in background even if the first attempt fails.
+async fn connect_domain(
+    domain: &Domain,
+    storage: LocalStorage,
+    signaling_clients: &mut HashMap<i64, SignalingClient>,
+) -> CoreResult<()> {
+    let addrs: Vec<SocketAddr> = if let Ok(ipv4_addr) = domain.addr.parse::<Ipv4Addr>() {
+        vec![(ipv4_addr, domain.subscribe_port).into()]
+    } else if let Ok(ipv6_addr) = domain.addr.parse::<Ipv6Addr>() {
+        vec![(ipv6_addr, domain.subscribe_port).into()]
+    } else if let Ok(url_addr) = domain.addr.parse::<Uri>() {
         if let Some(host) = url_addr.host() {
             let host = host.to_string();
+            let subscribe_port = domain.subscribe_port;
             let (tx, rx) = tokio::sync::oneshot::channel();
             tokio::task::spawn_blocking(move || {
-                match (host, primary_domain.subscribe_port).to_socket_addrs() {
+                match (host, subscribe_port).to_socket_addrs() {
                     Ok(addrs) => {
                         let addrs: Vec<SocketAddr> = addrs.collect();
                         let _ = tx.send(Some(addrs));
@@ -75,22 +212,25 @@ pub async fn signaling_connect(
         return Err(core_error!("invalid domain addr"));
     };
 
-    let mut client = SignalingClient::new(primary_domain.addr)?;
+    let mut client = SignalingClient::new(domain.addr.clone())?;
 
-    client
+    let result = client
         .subscribe(
             addrs,
-            primary_domain.device_id,
-            &primary_domain.finger_print,
-            storage.clone(),
+            domain.id,
+            domain.device_id,
+            &domain.finger_print,
+            storage,
         )
-        .await?;
+        .await;
 
-    *current_signaling = Some((primary_domain.id, client));
+    signaling_clients.insert(domain.id, client);
 
-    Ok(())
+    result
 }
 
+/// Visit the remote device with the identity of domain `domain_id`, or the primary
+/// domain when it's None.
 #[tauri::command]
 #[tracing::instrument(skip(app_handle, app_state, egui_plugin, password))]
 pub async fn signaling_visit(
@@ -100,7 +240,40 @@ pub async fn signaling_visit(
     remote_device_id: String,
     password: String,
     visit_desktop: bool,
+    domain_id: Option<i64>,
+    request_id: Option<String>,
 ) -> CoreResult<()> {
+    visit(
+        app_handle,
+        app_state,
+        egui_plugin,
+        remote_device_id,
+        password,
+        None,
+        false,
+        visit_desktop,
+        domain_id,
+        request_id,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn visit(
+    app_handle: tauri::AppHandle,
+    app_state: tauri::State<'_, AppState>,
+    egui_plugin: tauri::State<'_, EguiPluginHandle>,
+    remote_device_id: String,
+    password: String,
+    // id of the one-time pairing secret or observer token which is the password
+    secret_id: Option<String>,
+    observer: bool,
+    visit_desktop: bool,
+    domain_id: Option<i64>,
+    request_id: Option<String>,
+) -> CoreResult<()> {
+    let registration = cancel::register(request_id);
+
     let window_label = if visit_desktop {
         format!("Desktop:{remote_device_id}")
     } else {
@@ -117,28 +290,204 @@ pub async fn signaling_visit(
         return Err(core_error!("storage not initialize"));
     };
 
-    let Some((_,ref signaling_client)) = *app_state.signaling_client.lock().await else {
-        return Err(core_error!("storage not initialize"));
+    ensure_signaling_enabled(storage)?;
+
+    // the shared connection would give the observer a session of its own
+    if let Some(client) = app_state
+        .shared_endpoint(&remote_device_id)
+        .await
+        .filter(|_| !observer)
+    {
+        return open_shared_endpoint_window(
+            app_handle,
+            &app_state,
+            &egui_plugin,
+            client,
+            remote_device_id,
+            window_label,
+            window_title,
+            visit_desktop,
+        )
+        .await;
+    }
+
+    let domain = match domain_id {
+        Some(domain_id) => storage.domain().get_domain_by_id(domain_id)?,
+        None => storage.domain().get_primary_domain()?,
+    };
+
+    let signaling_clients = app_state.signaling_clients.lock().await;
+    let Some(signaling_client) = signaling_clients.get(&domain.id) else {
+        return Err(core_error!(
+            "signaling of domain '{}' not connected",
+            domain.name
+        ));
     };
 
     let remote_device_id_num = remote_device_id.replace('-', "").parse()?;
-    let primary_domain = storage.domain().get_primary_domain()?;
-    let local_device_id = primary_domain.device_id;
+    let local_device_id = domain.device_id;
+
+    let relay = Box::pin(connect_relay(
+        storage,
+        &domain,
+        signaling_client,
+        remote_device_id_num,
+        password.clone(),
+        secret_id.clone(),
+        visit_desktop,
+    ));
+
+    let connect = async {
+        // observer token is only redeemed through signaling
+        let lan_peer = if observer {
+            None
+        } else {
+            find_lan_peer(&app_state, remote_device_id_num).await
+        };
+
+        match lan_peer {
+            Some((remote_ip, lan_key)) => {
+                let lan = Box::pin(connect_lan(remote_ip, lan_key, visit_desktop));
+
+                // lan path goes first unless relay won the last race
+                let history = storage.history();
+                let preferred = match history.get_connect_path(remote_device_id_num, &domain.name) {
+                    Ok(path) => path.unwrap_or(ConnectPath::Lan),
+                    Err(err) => {
+                        tracing::error!(?err, "read connect path failed");
+                        ConnectPath::Lan
+                    }
+                };
+
+                let (path, endpoint) = race_connect_paths(preferred, lan, relay).await?;
+                tracing::info!(?path, ?remote_ip, "connect path won");
+
+                if let Err(err) = history.set_connect_path(remote_device_id_num, &domain.name, path)
+                {
+                    tracing::error!(?err, "save connect path failed");
+                }
+
+                Ok(endpoint)
+            }
+            None => relay.await,
+        }
+    };
+
+    // the visit can be canceled until the endpoint is connected
+    let endpoint = cancel::cancelable(registration.token(), connect).await?;
+
+    match endpoint {
+        VisitEndPoint::Desktop(client, render_frame_rx) => {
+            // one-time pairing secret can't be used again
+            if secret_id.is_none() {
+                client.set_reconnector(desktop_reconnector(
+                    storage.clone(),
+                    domain.clone(),
+                    remote_device_id_num,
+                    password,
+                ));
+            }
+
+            apply_bandwidth_limit(storage, &domain.name, remote_device_id_num, &client).await;
+
+            app_state
+                .endpoints
+                .lock()
+                .await
+                .insert(remote_device_id.clone(), client.clone())
+                .await;
+
+            create_desktop_endpoint_window(
+                app_handle,
+                &egui_plugin,
+                client,
+                render_frame_rx,
+                window_label,
+                window_title,
+            )?;
+        }
+        VisitEndPoint::Files(client) => {
+            apply_bandwidth_limit(storage, &domain.name, remote_device_id_num, &client).await;
+
+            app_state
+                .endpoints
+                .lock()
+                .await
+                .insert(remote_device_id.clone(), client)
+                .await;
+
+            create_file_manager_endpoint_window(
+                app_handle,
+                &app_state,
+                remote_device_id,
+                window_label,
+                window_title,
+            )
+            .await?;
+        }
+    }
+
+    let _ = storage.history().create(remote_device_id_num, &domain.name);
+
+    // the invitation can't be used again, so there is nothing to recover
+    if observer {
+        return Ok(());
+    }
+
+    if let Err(err) = storage.recovery().create_session(
+        local_device_id,
+        remote_device_id_num,
+        &domain.name,
+        visit_desktop,
+    ) {
+        tracing::error!(?err, "create session marker failed");
+    }
+
+    Ok(())
+}
+
+/// Visit the remote device through signaling server and connect the endpoint which the
+/// server assigns.
+async fn connect_relay(
+    storage: &LocalStorage,
+    domain: &Domain,
+    signaling_client: &SignalingClient,
+    remote_device_id: i64,
+    password: String,
+    secret_id: Option<String>,
+    visit_desktop: bool,
+) -> CoreResult<VisitEndPoint> {
+    let local_device_id = domain.device_id;
+    let identity_key = load_identity_key(storage)?;
     let resp = signaling_client
         .visit(
-            primary_domain.device_id,
-            remote_device_id_num,
+            local_device_id,
+            remote_device_id,
             password,
+            secret_id,
             visit_desktop,
+            &identity_key,
         )
         .await?;
 
-    let (endpoint_addr, visit_credentials, opening_key, sealing_key) = match resp {
+    let (endpoint_addr, visit_credentials, opening_key, sealing_key, peer) = match resp {
         Response::Message(result) => match result {
             Ok(v) => v,
-            Err(reason) => return Err(core_error!("Visit Failed ({:?})", reason)),
+            Err(reason) => {
+                telemetry::record_connect_failure(FailureCategory::VisitRejected);
+
+                // the remote was online to reject it
+                record_online(storage, domain, remote_device_id, true);
+                return Err(CoreError::VisitFailed(reason));
+            }
         },
-        Response::Error(err) => return Err(core_error!("Visit Failed ({:?})", err)),
+        Response::Error(err) => {
+            if matches!(err, HttpError::RemoteOffline) {
+                record_online(storage, domain, remote_device_id, false);
+            }
+
+            return Err(CoreError::SignalingServerError(err));
+        }
     };
 
     let endpoint_addr: SocketAddr = endpoint_addr
@@ -147,98 +496,351 @@ pub async fn signaling_visit(
 
     tracing::info!(?local_device_id, ?remote_device_id, "key exchange success");
 
+    // a changed identity is warned loudly by notification, user decides whether to go on
+    if let Err(err) = verify_peer(
+        storage,
+        &domain.name,
+        local_device_id,
+        remote_device_id,
+        &peer,
+    ) {
+        tracing::error!(?err, "verify peer identity failed");
+    }
+
     let endpoint_id = EndPointID::DeviceID {
         local_device_id,
-        remote_device_id: remote_device_id_num,
+        remote_device_id,
     };
 
+    let key_pair = Some((opening_key, sealing_key));
+    let stream = EndPointStream::relay(storage, &domain.name, endpoint_addr)?;
+
     if visit_desktop {
         let (client, render_frame_rx) = create_desktop_active_endpoint_client(
             endpoint_id,
-            Some((opening_key, sealing_key)),
-            EndPointStream::ActiveTCP(endpoint_addr),
+            key_pair,
+            stream,
             Some(visit_credentials),
         )
         .await?;
-
-        if let Err(err) = egui_plugin.create_window(
-            window_label,
-            Box::new(move |cc| {
-                if let Some(gl_context) = cc.gl.as_ref() {
-                    Box::new(create_desktop_window(
-                        cc,
-                        gl_context.clone(),
-                        endpoint_id,
-                        client,
-                        render_frame_rx,
-                    ))
-                } else {
-                    panic!("get gl context failed");
-                }
-            }),
-            window_title,
-            tauri_egui::eframe::NativeOptions {
-                // hardware_acceleration: HardwareAcceleration::Required,
-                ..Default::default()
-            },
-        ) {
-            tracing::error!(?err, "create desktop window failed");
-            return Err(core_error!("create remote desktop window failed"));
-        }
+        Ok(VisitEndPoint::Desktop(client, render_frame_rx))
     } else {
         let client = create_file_manager_active_endpoint_client(
             endpoint_id,
-            Some((opening_key, sealing_key)),
-            EndPointStream::ActiveTCP(endpoint_addr),
+            key_pair,
+            stream,
             Some(visit_credentials),
         )
         .await?;
+        Ok(VisitEndPoint::Files(client))
+    }
+}
 
-        app_state
-            .files_endpoints
-            .lock()
-            .await
-            .insert(remote_device_id.clone(), client)
-            .await;
+/// Connect the remote device directly like `lan_connect`, the connection is encrypted by
+/// the key agreed with the lan key.
+async fn connect_lan(
+    remote_ip: IpAddr,
+    lan_key: String,
+    visit_desktop: bool,
+) -> CoreResult<VisitEndPoint> {
+    let remote_addr = SocketAddr::new(remote_ip, 48001);
+    let (stream, key_pair) = connect_with_lan_key(remote_addr, &lan_key).await?;
 
-        let (tx, rx) = tokio::sync::oneshot::channel();
+    let endpoint_id = EndPointID::LANID {
+        local_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        remote_ip,
+    };
 
-        let device_id = remote_device_id.clone();
-        tokio::spawn(async move {
-            if let Err(err) = tauri::WindowBuilder::new(
-                &app_handle,
-                window_label,
-                tauri::WindowUrl::App(format!("/files?device_id={device_id}").into()),
-            )
-            .center()
-            .inner_size(960., 680.)
-            .min_inner_size(960., 680.)
-            .title(window_title)
-            .build()
-            {
-                let _ = tx.send(Some(err));
-            } else {
-                let _ = tx.send(None);
-            }
-        });
+    let key_pair = Some(key_pair);
+    let stream = EndPointStream::PassiveTCP(stream);
 
-        let create_result = rx.await.map_err(|_| core_error!("create window failed"))?;
+    if visit_desktop {
+        let (client, render_frame_rx) =
+            create_desktop_active_endpoint_client(endpoint_id, key_pair, stream, None).await?;
+        Ok(VisitEndPoint::Desktop(client, render_frame_rx))
+    } else {
+        let client =
+            create_file_manager_active_endpoint_client(endpoint_id, key_pair, stream, None).await?;
+        Ok(VisitEndPoint::Files(client))
+    }
+}
 
-        if let Some(err) = create_result {
-            app_state
-                .files_endpoints
-                .lock()
-                .await
-                .invalidate(&remote_device_id)
-                .await;
-            tracing::error!(?err, "create file manager window failed");
-            return Err(core_error!("create remote file manager window failed"));
+// the remote device is raced on lan when it's discovered there and both share the lan key
+async fn find_lan_peer(app_state: &AppState, remote_device_id: i64) -> Option<(IpAddr, String)> {
+    let lan_key = lan_key()?;
+    let lan_provider = app_state.lan_provider.lock().await;
+    let remote_ip = lan_provider.as_ref()?.find_device(remote_device_id).await?;
+    Some((remote_ip, lan_key))
+}
+
+/// Cap the new connection with the bandwidth limit which user set for the remote device.
+async fn apply_bandwidth_limit(
+    storage: &LocalStorage,
+    domain: &str,
+    remote_device_id: i64,
+    client: &EndPointClient,
+) {
+    let max_kbps = match storage.peer().get_bandwidth_limit(domain, remote_device_id) {
+        Ok(Some(max_kbps)) => max_kbps,
+        Ok(None) => return,
+        Err(err) => {
+            tracing::error!(?err, "read peer bandwidth limit failed");
+            return;
         }
+    };
+
+    if let Err(err) = client.set_bandwidth_limit(Some(max_kbps)).await {
+        tracing::error!(?err, "set bandwidth limit failed");
     }
+}
+
+/// Visit the remote device again with the same password, so the desktop session can be
+/// resumed after remote device is rebooted.
+fn desktop_reconnector(
+    storage: LocalStorage,
+    domain: Domain,
+    remote_device_id: i64,
+    password: String,
+) -> Reconnector {
+    Reconnector::new(move || {
+        let storage = storage.clone();
+        let domain = domain.clone();
+        let password = password.clone();
+
+        async move {
+            let signaling_client = SignalingClient::new(domain.addr.clone())?;
+            let identity_key = load_identity_key(&storage)?;
+
+            let resp = signaling_client
+                .visit(
+                    domain.device_id,
+                    remote_device_id,
+                    password,
+                    None,
+                    true,
+                    &identity_key,
+                )
+                .await?;
+
+            let (endpoint_addr, visit_credentials, opening_key, sealing_key, peer) = match resp {
+                Response::Message(result) => result.map_err(CoreError::VisitFailed)?,
+                Response::Error(err) => return Err(CoreError::SignalingServerError(err)),
+            };
+
+            let endpoint_addr: SocketAddr = endpoint_addr
+                .parse()
+                .map_err(|_| core_error!("parse endpoint addr failed"))?;
+
+            if let Err(err) = verify_peer(
+                &storage,
+                &domain.name,
+                domain.device_id,
+                remote_device_id,
+                &peer,
+            ) {
+                tracing::error!(?err, "verify peer identity failed");
+            }
 
-    let _ = storage
+            Ok(ReconnectParams {
+                key_pair: Some((opening_key, sealing_key)),
+                stream: EndPointStream::relay(&storage, &domain.name, endpoint_addr)?,
+                visit_credentials: Some(visit_credentials),
+            })
+        }
+    })
+}
+
+// the reconnect list on home screen shows the last known online status of devices
+fn record_online(storage: &LocalStorage, domain: &Domain, remote_device_id: i64, online: bool) {
+    if let Err(err) = storage
         .history()
-        .create(remote_device_id_num, &primary_domain.name);
+        .set_online(remote_device_id, &domain.name, online)
+    {
+        tracing::error!(?err, "save online status of device failed");
+    }
+}
+
+/// Accept or deny the incoming visit which is prompted by `popup_dialog_visit_request`,
+/// denied visitor is shown `message` instead of the configured reject message.
+#[tauri::command]
+#[tracing::instrument(skip(app_handle))]
+pub async fn signaling_visit_reply(
+    app_handle: AppHandle,
+    request_id: String,
+    allow: bool,
+    message: Option<String>,
+) -> CoreResult<()> {
+    tray::remove_visit_request(&app_handle, &request_id);
+    reply_visit(&request_id, allow, message)
+}
+
+/// Create the pairing payload with one-time secret, which is displayed as QR code for
+/// other devices to scan.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn signaling_pairing_create(app_state: tauri::State<'_, AppState>) -> CoreResult<String> {
+    let Some(ref storage) = *app_state.storage.lock().await else {
+        return Err(core_error!("storage not initialize"));
+    };
+
+    ensure_signaling_enabled(storage)?;
+
+    let primary_domain = storage.domain().get_primary_domain()?;
+
+    Ok(create_pairing_payload(&primary_domain).encode())
+}
+
+/// Signaling servers are never contacted in LAN-only mode.
+pub(super) fn ensure_signaling_enabled(storage: &LocalStorage) -> CoreResult<()> {
+    if storage.kv().get_lan_only_enabled()? {
+        return Err(core_error!("signaling is disabled in lan only mode"));
+    }
 
     Ok(())
 }
+
+#[tauri::command]
+#[tracing::instrument]
+pub fn signaling_pairing_revoke() {
+    revoke_pairing_secrets();
+}
+
+/// Visit the device of scanned pairing payload with its one-time secret.
+#[tauri::command]
+#[tracing::instrument(skip(app_handle, app_state, egui_plugin, payload))]
+pub async fn signaling_pairing_visit(
+    app_handle: tauri::AppHandle,
+    app_state: tauri::State<'_, AppState>,
+    egui_plugin: tauri::State<'_, EguiPluginHandle>,
+    payload: String,
+    visit_desktop: bool,
+    request_id: Option<String>,
+) -> CoreResult<()> {
+    let payload = PairingPayload::parse(&payload)?;
+
+    let domain_id = {
+        let Some(ref storage) = *app_state.storage.lock().await else {
+            return Err(core_error!("storage not initialize"));
+        };
+
+        if !storage.domain().domain_exist(&payload.domain)? {
+            return Err(core_error!(
+                "pairing device is in domain '{}', add the domain first",
+                payload.domain
+            ));
+        }
+
+        storage.domain().get_domain_by_name(payload.domain)?.id
+    };
+
+    visit(
+        app_handle,
+        app_state,
+        egui_plugin,
+        format_device_id(payload.device_id),
+        payload.secret,
+        Some(payload.secret_id),
+        false,
+        visit_desktop,
+        Some(domain_id),
+        request_id,
+    )
+    .await
+}
+
+/// Invite another device to watch the desktop session with the remote device, returns
+/// the invitation link which the observer visits with.
+#[tauri::command]
+#[tracing::instrument(skip(app_state))]
+pub async fn signaling_observer_invite(
+    app_state: tauri::State<'_, AppState>,
+    remote_device_id: String,
+    ttl_secs: Option<u32>,
+) -> CoreResult<String> {
+    let client = app_state
+        .endpoints
+        .lock()
+        .await
+        .get(&remote_device_id)
+        .ok_or_else(|| core_error!("remote desktop not exist"))?;
+
+    let EndPointID::DeviceID {
+        local_device_id,
+        remote_device_id,
+    } = client.endpoint_id()
+    else {
+        return Err(core_error!("observer can't be invited to lan session"));
+    };
+
+    let domain = {
+        let Some(ref storage) = *app_state.storage.lock().await else {
+            return Err(core_error!("storage not initialize"));
+        };
+
+        storage
+            .domain()
+            .get_all_domains()?
+            .into_iter()
+            .find(|domain| domain.device_id == local_device_id)
+            .ok_or_else(|| core_error!("domain of the session not exists"))?
+    };
+
+    let reply: EndPointInviteObserverReply = client
+        .call(EndPointCallRequest::InviteObserverRequest(
+            EndPointInviteObserverRequest {
+                ttl_secs: ttl_secs.unwrap_or(DEFAULT_OBSERVER_INVITATION_TTL_SECS),
+            },
+        ))
+        .await?;
+
+    Ok(ObserverInvitation {
+        device_id: remote_device_id,
+        domain: domain.name,
+        token_id: reply.token_id,
+        token: reply.token,
+        expire: reply.expire,
+    }
+    .encode())
+}
+
+/// Watch the desktop session which the invitation is for, the observer can't control it.
+#[tauri::command]
+#[tracing::instrument(skip(app_handle, app_state, egui_plugin, invitation))]
+pub async fn signaling_observer_visit(
+    app_handle: tauri::AppHandle,
+    app_state: tauri::State<'_, AppState>,
+    egui_plugin: tauri::State<'_, EguiPluginHandle>,
+    invitation: String,
+    request_id: Option<String>,
+) -> CoreResult<()> {
+    let invitation = ObserverInvitation::parse(&invitation)?;
+
+    let domain_id = {
+        let Some(ref storage) = *app_state.storage.lock().await else {
+            return Err(core_error!("storage not initialize"));
+        };
+
+        if !storage.domain().domain_exist(&invitation.domain)? {
+            return Err(core_error!(
+                "observed device is in domain '{}', add the domain first",
+                invitation.domain
+            ));
+        }
+
+        storage.domain().get_domain_by_name(invitation.domain)?.id
+    };
+
+    visit(
+        app_handle,
+        app_state,
+        egui_plugin,
+        format_device_id(invitation.device_id),
+        invitation.token,
+        Some(invitation.token_id),
+        true,
+        true,
+        Some(domain_id),
+        request_id,
+    )
+    .await
+}
@@ -0,0 +1,30 @@
+use mirrorx_core::{
+    component::fs::staging::{self, clear_staging_area, staging_root},
+    core_error,
+    error::CoreResult,
+};
+use tauri::Manager;
+
+/// Open the staging area which keeps files dropped into sessions in file explorer.
+#[tauri::command]
+#[tracing::instrument(skip(app_handle))]
+pub async fn staging_open(app_handle: tauri::AppHandle) -> CoreResult<()> {
+    let path = staging_root()?;
+
+    tauri::api::shell::open(&app_handle.shell_scope(), path.to_string_lossy(), None)
+        .map_err(|err| core_error!("open staging area failed ({})", err))
+}
+
+/// Bytes taken by the staging area.
+#[tauri::command]
+#[tracing::instrument]
+pub async fn staging_usage() -> CoreResult<u64> {
+    staging::staging_usage()
+}
+
+/// Remove staged files of ended sessions, files of ongoing sessions are kept.
+#[tauri::command]
+#[tracing::instrument]
+pub async fn staging_clear() -> CoreResult<()> {
+    clear_staging_area()
+}
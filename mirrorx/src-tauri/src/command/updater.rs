@@ -0,0 +1,52 @@
+use super::AppState;
+use mirrorx_core::{
+    component::updater::{self, ReleaseManifest, DEFAULT_UPDATE_URL},
+    core_error,
+    error::CoreResult,
+};
+use tauri::{AppHandle, State};
+
+#[tauri::command]
+#[tracing::instrument(skip(app_handle, app_state))]
+pub async fn updater_check(
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+) -> CoreResult<Option<ReleaseManifest>> {
+    let (update_url, channel) = {
+        let Some(ref storage) = *app_state.storage.lock().await else {
+            return Err(core_error!("storage not initialize"));
+        };
+
+        let update_url = storage
+            .kv()
+            .get_update_url()?
+            .unwrap_or_else(|| DEFAULT_UPDATE_URL.to_string());
+
+        (update_url, storage.kv().get_update_channel()?)
+    };
+
+    let current_version = app_handle.package_info().version.to_string();
+
+    updater::check_update(&update_url, channel, &current_version).await
+}
+
+/// Download the newer release and hand off to the OS installer, the app quits once
+/// the installer launched.
+#[tauri::command]
+#[tracing::instrument(skip(app_handle, app_state))]
+pub async fn updater_install(
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+) -> CoreResult<()> {
+    // check again rather than trusting the manifest from frontend
+    let Some(manifest) = updater_check(app_handle.clone(), app_state).await? else {
+        return Err(core_error!("no available update"));
+    };
+
+    let download_dir = std::env::temp_dir().join("mirrorx_update");
+    let package_path = updater::download_package(&manifest, &download_dir).await?;
+
+    updater::launch_installer(&package_path)?;
+
+    crate::quit(&app_handle)
+}
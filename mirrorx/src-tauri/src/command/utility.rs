@@ -1,5 +1,43 @@
-use mirrorx_core::{error::CoreResult, utility::os::GraphicsCards};
-use tauri::{AppHandle, Manager};
+use super::AppState;
+use crate::utility::{log_dir, LOG_FILE_PREFIX};
+use mirrorx_core::{
+    component::crash::{self, CrashReport, DEFAULT_CRASH_REPORT_URL},
+    core_error,
+    error::{error_catalog, CoreResult, ErrorKeyEntry},
+    utility::{
+        cancel,
+        os::{GraphicsCards, InterfaceAddr},
+    },
+};
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use tauri::{AppHandle, Manager, State};
+use tracing::Level;
+
+const DEFAULT_QUERY_LOGS_LIMIT: usize = 1000;
+
+#[derive(Debug, Serialize)]
+pub struct LogRecord {
+    pub timestamp: String,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    pub session_id: Option<String>,
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Cancel the command which was invoked with `request_id`, false when it's finished
+/// already.
+#[tauri::command]
+#[tracing::instrument]
+pub fn utility_cancel_request(request_id: String) -> bool {
+    cancel::cancel(&request_id)
+}
 
 #[tauri::command]
 #[tracing::instrument]
@@ -29,6 +67,12 @@ pub fn utility_enum_graphics_cards() -> CoreResult<Vec<GraphicsCards>> {
     mirrorx_core::utility::os::enum_graphics_cards()
 }
 
+#[tauri::command]
+#[tracing::instrument]
+pub fn utility_enum_network_interfaces() -> CoreResult<Vec<InterfaceAddr>> {
+    mirrorx_core::utility::os::enum_network_interfaces()
+}
+
 #[tauri::command]
 #[tracing::instrument(skip(app_handle))]
 pub fn utility_hide_macos_zoom_button(app_handle: AppHandle) {
@@ -57,3 +101,177 @@ pub fn utility_hide_macos_zoom_button(app_handle: AppHandle) {
         "shouldn't invoke on non-macos platform"
     );
 }
+
+/// Every key which errors of commands carry, with their params and English messages to
+/// build translations from.
+#[tauri::command]
+#[tracing::instrument]
+pub fn utility_error_catalog() -> Vec<ErrorKeyEntry> {
+    error_catalog().to_vec()
+}
+
+/// The panic reports and minidumps left by previous crashes, the newest first.
+#[tauri::command]
+#[tracing::instrument]
+pub fn utility_crash_report_list() -> CoreResult<Vec<CrashReport>> {
+    crash::list_reports()
+}
+
+/// Upload the crash report once user agrees, it's removed after uploaded.
+#[tauri::command]
+#[tracing::instrument]
+pub async fn utility_crash_report_upload(name: String) -> CoreResult<()> {
+    crash::upload_report(DEFAULT_CRASH_REPORT_URL, &name).await
+}
+
+#[tauri::command]
+#[tracing::instrument]
+pub fn utility_crash_report_discard(name: String) -> CoreResult<()> {
+    crash::discard_report(&name)
+}
+
+/// Write the redacted diagnostics zip to `path` for attaching to bug reports.
+#[tauri::command]
+#[tracing::instrument(skip(app_handle, app_state))]
+pub async fn utility_generate_diagnostics(
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+    path: PathBuf,
+) -> CoreResult<()> {
+    let log_dir =
+        log_dir(&app_handle.path_resolver()).ok_or(core_error!("get app log dir failed"))?;
+
+    // NAT test takes seconds, don't hold the storage lock during it
+    let storage = match *app_state.storage.lock().await {
+        Some(ref storage) => storage.clone(),
+        None => return Err(core_error!("storage not initialize")),
+    };
+
+    mirrorx_core::utility::diagnostics::create_diagnostics_bundle(&storage, log_dir, path).await
+}
+
+/// Query the latest log records from rolling log files for problem report, records can
+/// be filtered by the session id and the min severity level.
+#[tauri::command]
+#[tracing::instrument(skip(app_handle))]
+pub async fn utility_query_logs(
+    app_handle: AppHandle,
+    session_id: Option<String>,
+    level: Option<String>,
+    limit: Option<usize>,
+) -> CoreResult<Vec<LogRecord>> {
+    let log_dir =
+        log_dir(&app_handle.path_resolver()).ok_or(core_error!("get app log dir failed"))?;
+
+    let min_level = match level {
+        Some(level) => Level::from_str(&level).map_err(|_| core_error!("invalid log level"))?,
+        None => Level::TRACE,
+    };
+
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LOGS_LIMIT);
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    tokio::task::spawn_blocking(move || {
+        let _ = tx.send(query_logs(
+            &log_dir,
+            session_id.as_deref(),
+            min_level,
+            limit,
+        ));
+    });
+
+    rx.await?
+}
+
+fn query_logs(
+    log_dir: &Path,
+    session_id: Option<&str>,
+    min_level: Level,
+    limit: usize,
+) -> CoreResult<Vec<LogRecord>> {
+    // rolling log files are suffixed by date, so the name order is the time order
+    let mut log_files: Vec<_> = std::fs::read_dir(log_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| name.starts_with(LOG_FILE_PREFIX))
+        })
+        .collect();
+
+    log_files.sort();
+
+    let mut records = VecDeque::with_capacity(limit);
+
+    for log_file in log_files {
+        let reader = BufReader::new(std::fs::File::open(log_file)?);
+
+        for line in reader.lines() {
+            // skip the lines written in plain text by earlier versions
+            let Some(record) = parse_log_record(&line?) else {
+                continue;
+            };
+
+            if session_id.map_or(false, |id| record.session_id.as_deref() != Some(id)) {
+                continue;
+            }
+
+            // more verbose level is greater
+            if Level::from_str(&record.level).map_or(true, |level| level > min_level) {
+                continue;
+            }
+
+            if records.len() == limit {
+                records.pop_front();
+            }
+
+            records.push_back(record);
+        }
+    }
+
+    Ok(records.into())
+}
+
+fn parse_log_record(line: &str) -> Option<LogRecord> {
+    let serde_json::Value::Object(mut record) = serde_json::from_str(line).ok()? else {
+        return None;
+    };
+
+    let session_id = record
+        .get("spans")
+        .and_then(|spans| spans.as_array())
+        .and_then(|spans| {
+            spans
+                .iter()
+                .find_map(|span| span.get("session_id").and_then(|id| id.as_str()))
+        })
+        .map(String::from);
+
+    let mut fields = match record.remove("fields") {
+        Some(serde_json::Value::Object(fields)) => fields,
+        _ => serde_json::Map::new(),
+    };
+
+    let message = match fields.remove("message") {
+        Some(serde_json::Value::String(message)) => message,
+        _ => String::default(),
+    };
+
+    let get_string = |key: &str| {
+        record
+            .get(key)
+            .and_then(|value| value.as_str())
+            .map(String::from)
+            .unwrap_or_default()
+    };
+
+    Some(LogRecord {
+        timestamp: get_string("timestamp"),
+        level: get_string("level"),
+        target: get_string("target"),
+        message,
+        session_id,
+        fields,
+    })
+}
@@ -0,0 +1,57 @@
+use mirrorx_core::api::event::{subscribe, CoreEvent};
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tokio::task::JoinHandle;
+
+static SERVE_HANDLE: Lazy<Mutex<Option<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(None));
+
+/// Start (or restart) dispatching the events which core publishes by itself, like progress
+/// of long operations.
+pub fn serve(app_handle: AppHandle) {
+    let mut rx = subscribe();
+
+    let handle = tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => dispatch(&app_handle, event),
+                Err(_) if rx.is_closed() => return,
+                // overflowed, the skipped progress is superseded by the later one
+                Err(_) => continue,
+            }
+        }
+    });
+
+    if let Some(previous) = SERVE_HANDLE.lock().unwrap().replace(handle) {
+        previous.abort();
+    }
+}
+
+/// Emit the core event under its topic, prompts bring main window to front and are only
+/// emitted to it, other events are emitted to all windows.
+pub fn dispatch(app_handle: &AppHandle, event: CoreEvent) {
+    // serialized once here rather than by every emit
+    let payload = match serde_json::to_value(&event) {
+        Ok(payload) => payload,
+        Err(err) => {
+            tracing::error!(?err, topic = event.topic(), "serialize core event failed");
+            return;
+        }
+    };
+
+    let result = if event.is_prompt() {
+        let Some(window) = app_handle.get_window("main") else {
+            return;
+        };
+
+        let _ = window.show();
+        let _ = window.unminimize();
+        window.emit(event.topic(), payload)
+    } else {
+        app_handle.emit_all(event.topic(), payload)
+    };
+
+    if let Err(err) = result {
+        tracing::error!(?err, topic = event.topic(), "emit core event failed");
+    }
+}
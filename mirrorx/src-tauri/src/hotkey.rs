@@ -0,0 +1,71 @@
+use mirrorx_core::{
+    api::{
+        config::entity::hotkey::{Hotkey, HotkeyAction},
+        endpoint::id::EndPointID,
+    },
+    core_error,
+    error::CoreResult,
+};
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+use tauri::{AppHandle, GlobalShortcutManager};
+use tokio::sync::broadcast::{Receiver, Sender};
+
+static HOTKEY_ACTION_TX: Lazy<Sender<HotkeyAction>> =
+    Lazy::new(|| tokio::sync::broadcast::channel(16).0);
+
+// the desktop window which received user input lastly, hotkey actions only apply to it
+static ACTIVE_DESKTOP: Lazy<Mutex<Option<EndPointID>>> = Lazy::new(|| Mutex::new(None));
+
+/// Replace all registered global shortcuts with the enabled hotkeys.
+pub fn register_hotkeys(app_handle: &AppHandle, hotkeys: &[Hotkey]) -> CoreResult<()> {
+    let mut manager = app_handle.global_shortcut_manager();
+
+    manager
+        .unregister_all()
+        .map_err(|err| core_error!("unregister global shortcuts failed ({})", err))?;
+
+    for hotkey in hotkeys.iter().filter(|hotkey| hotkey.enabled) {
+        let action = hotkey.action;
+
+        manager
+            .register(&hotkey.accelerator, move || {
+                tracing::info!(?action, "hotkey pressed");
+                let _ = HOTKEY_ACTION_TX.send(action);
+            })
+            .map_err(|err| {
+                core_error!(
+                    "register global shortcut '{}' failed ({})",
+                    hotkey.accelerator,
+                    err
+                )
+            })?;
+    }
+
+    Ok(())
+}
+
+pub fn subscribe() -> Receiver<HotkeyAction> {
+    HOTKEY_ACTION_TX.subscribe()
+}
+
+pub fn set_active_desktop(endpoint_id: EndPointID) {
+    *ACTIVE_DESKTOP.lock().unwrap() = Some(endpoint_id);
+}
+
+pub fn clear_active_desktop(endpoint_id: &EndPointID) {
+    let mut active_desktop = ACTIVE_DESKTOP.lock().unwrap();
+    if active_desktop.as_ref() == Some(endpoint_id) {
+        *active_desktop = None;
+    }
+}
+
+/// Returns true if the desktop window should respond to hotkey actions, when no
+/// desktop window has received input yet, every desktop window responds.
+pub fn is_active_desktop(endpoint_id: &EndPointID) -> bool {
+    ACTIVE_DESKTOP
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or(true, |active_desktop| active_desktop == endpoint_id)
+}
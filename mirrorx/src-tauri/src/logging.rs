@@ -0,0 +1,107 @@
+//! Logs are written to console and the rolling json files. The filter of them can be
+//! changed while the app runs, so users can capture debug logs of one module for a
+//! single repro without restarting the app.
+
+use crate::utility::LOG_FILE_PREFIX;
+use mirrorx_core::{
+    api::config::change::{self, ConfigChange},
+    core_error,
+    error::CoreResult,
+};
+use once_cell::sync::{Lazy, OnceCell};
+use std::{path::Path, sync::Mutex};
+use tokio::task::JoinHandle;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{
+    layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
+};
+
+/// The filter which directives of user override, other targets are logged by it.
+pub const DEFAULT_LOG_FILTER: &str = "info,tao=info";
+
+static RELOAD_HANDLE: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+
+static FOLLOW_HANDLE: Lazy<Mutex<Option<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(None));
+
+/// Install the global subscriber, logs are written until the returned guard is dropped.
+pub fn init(log_dir: &Path) -> WorkerGuard {
+    // write json lines with span list, so logs can be queried by session id
+    let file_appender = tracing_appender::rolling::daily(log_dir, LOG_FILE_PREFIX);
+    let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
+    let file_layer = tracing_subscriber::fmt::layer()
+        .json()
+        .with_current_span(false)
+        .with_span_list(true)
+        .with_writer(non_blocking);
+
+    let console_layer = tracing_subscriber::fmt::layer()
+        .pretty()
+        .with_writer(std::io::stderr);
+
+    let (filter_layer, reload_handle) = reload::Layer::new(EnvFilter::new(DEFAULT_LOG_FILTER));
+    let _ = RELOAD_HANDLE.set(reload_handle);
+
+    tracing_subscriber::Registry::default()
+        .with(filter_layer)
+        .with(console_layer)
+        .with(file_layer)
+        .init();
+
+    guard
+}
+
+/// Build the filter from directives like `mirrorx_core::service::endpoint=debug`, they
+/// override the default filter. None restores the default filter.
+pub fn parse_log_filter(directives: Option<&str>) -> CoreResult<EnvFilter> {
+    let filter = match directives.map(str::trim) {
+        Some(directives) if !directives.is_empty() => {
+            format!("{DEFAULT_LOG_FILTER},{directives}")
+        }
+        _ => String::from(DEFAULT_LOG_FILTER),
+    };
+
+    EnvFilter::try_new(&filter).map_err(|err| core_error!("invalid log filter ({})", err))
+}
+
+pub fn set_log_filter(directives: Option<&str>) -> CoreResult<()> {
+    let filter = parse_log_filter(directives)?;
+    let reload_handle = RELOAD_HANDLE
+        .get()
+        .ok_or(core_error!("log subscriber not initialize"))?;
+
+    reload_handle
+        .reload(filter)
+        .map_err(|err| core_error!("reload log filter failed ({})", err))?;
+
+    tracing::info!(?directives, "set log filter");
+
+    Ok(())
+}
+
+/// Start (or restart) applying the log filter once it's changed in settings.
+pub fn follow_log_filter_changes() {
+    let mut rx = change::subscribe();
+
+    let handle = tokio::spawn(async move {
+        loop {
+            let change = match rx.recv().await {
+                Ok(change) => change,
+                Err(_) if rx.is_closed() => return,
+                // overflowed, only the latest changes matter anyway
+                Err(_) => continue,
+            };
+
+            let ConfigChange::LogFilter(directives) = change else {
+                continue;
+            };
+
+            if let Err(err) = set_log_filter(directives.as_deref()) {
+                tracing::error!(?err, "apply log filter failed");
+            }
+        }
+    });
+
+    if let Some(previous) = FOLLOW_HANDLE.lock().unwrap().replace(handle) {
+        previous.abort();
+    }
+}
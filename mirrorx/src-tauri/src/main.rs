@@ -3,15 +3,21 @@
     windows_subsystem = "windows"
 )]
 
+mod automation;
 mod command;
+mod event;
+mod hotkey;
+mod logging;
+mod notification;
+mod tray;
 mod utility;
 mod window;
 
 #[cfg(target_os = "macos")]
 use tauri::Icon;
 
-use tauri::{App, Manager, SystemTray, SystemTrayEvent, WindowEvent};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use mirrorx_core::component::{autostart::MINIMIZED_ARG, crash};
+use tauri::{App, AppHandle, Manager, SystemTray, SystemTrayEvent, WindowEvent};
 
 #[cfg(target_os = "macos")]
 static TRAY_ICON_MACOS: &[u8] = include_bytes!("../assets/icons/tray-macOS.png");
@@ -23,38 +29,37 @@ async fn main() {
 
     let app = build_app();
 
-    let log_dir = app
-        .path_resolver()
-        .app_log_dir()
-        .expect("get app log dir failed")
-        .join("logs");
+    let log_dir = utility::log_dir(&app.path_resolver()).expect("get app log dir failed");
 
-    let file_appender = tracing_appender::rolling::daily(&log_dir, "mirrorx.log");
-    let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-    let file_layer = tracing_subscriber::fmt::layer()
-        .with_ansi(false)
-        .with_writer(non_blocking);
-
-    let console_layer = tracing_subscriber::fmt::layer()
-        .pretty()
-        .with_writer(std::io::stderr);
-
-    tracing_subscriber::Registry::default()
-        .with(EnvFilter::from("info,tao=info"))
-        .with(console_layer)
-        .with(file_layer)
-        .init();
+    let _guard = logging::init(&log_dir);
 
     tracing::info!(path = ?log_dir, "log dir");
 
+    match utility::crash_dir(&app.path_resolver()) {
+        Some(crash_dir) => {
+            if let Err(err) = crash::install(crash_dir) {
+                tracing::error!(?err, "install crash handler failed");
+            }
+        }
+        None => tracing::error!("get app crash dir failed"),
+    }
+
     app.run(|app_handle, event| match event {
         tauri::RunEvent::WindowEvent { label, event, .. } => {
             if label == "main" {
-                if let WindowEvent::CloseRequested { api, .. } = event {
-                    if let Some(window) = app_handle.get_window(&label) {
-                        let _ = window.hide();
-                        api.prevent_close();
+                match event {
+                    WindowEvent::CloseRequested { api, .. } => {
+                        if let Some(window) = app_handle.get_window(&label) {
+                            let _ = window.hide();
+                            api.prevent_close();
+                        }
                     }
+                    WindowEvent::Focused(true) => {
+                        app_handle
+                            .state::<notification::Notifier>()
+                            .perform_pending_action(app_handle);
+                    }
+                    _ => {}
                 }
             }
         }
@@ -74,6 +79,8 @@ fn build_app() -> App {
 
     tauri::Builder::default()
         .manage(command::AppState::new())
+        .manage(notification::Notifier::new())
+        .manage(automation::AutomationServer::new())
         .system_tray(tray)
         .enable_macos_default_menu(false)
         .on_system_tray_event(|app, event| {
@@ -85,7 +92,7 @@ fn build_app() -> App {
             }
             if let SystemTrayEvent::MenuItemClick { id, .. } = event {
                 match id.as_str() {
-                    "quit" => std::process::exit(0),
+                    "quit" => quit(app),
                     "show" => app.windows().values().for_each(|window| {
                         let _ = window.show();
                     }),
@@ -95,7 +102,7 @@ fn build_app() -> App {
                     "about" => {
                         let _ = app.emit_all("/dialog/about", ());
                     }
-                    _ => {}
+                    id => tray::handle_menu_item_click(app, id),
                 }
             }
         })
@@ -105,7 +112,7 @@ fn build_app() -> App {
             }
 
             if event.menu_item_id() == "quit" {
-                std::process::exit(0)
+                quit(&event.window().app_handle())
             }
         })
         .setup(|app| {
@@ -124,7 +131,9 @@ fn build_app() -> App {
                 .fullscreen(false)
                 .resizable(false)
                 .maximized(false)
-                .inner_size(360., 640.);
+                .inner_size(360., 640.)
+                // started at login, the app stays in tray until user opens it
+                .visible(!std::env::args().any(|arg| arg == MINIMIZED_ARG));
 
                 #[cfg(target_os = "macos")]
                 {
@@ -168,29 +177,157 @@ fn build_app() -> App {
             command::config::config_domain_delete,
             command::config::config_domain_list,
             command::config::config_domain_update,
+            command::config::config_domain_relay_tls_get,
+            command::config::config_domain_relay_tls_set,
             command::config::config_language_get,
             command::config::config_language_set,
             command::config::config_theme_get,
             command::config::config_theme_set,
             command::config::config_history_get,
+            command::config::config_history_ranked_get,
+            command::config::config_history_pinned_set,
+            command::config::config_history_favorite_set,
+            command::config::config_notification_get,
+            command::config::config_notification_set,
+            command::config::config_hotkey_list,
+            command::config::config_hotkey_set,
+            command::config::config_hotkey_reset,
+            command::config::config_acl_rule_list,
+            command::config::config_acl_rule_add,
+            command::config::config_acl_rule_delete,
+            command::config::config_power_saving_get,
+            command::config::config_power_saving_set,
+            command::config::config_keep_awake_get,
+            command::config::config_keep_awake_set,
+            command::config::config_update_channel_get,
+            command::config::config_update_channel_set,
+            command::config::config_update_url_get,
+            command::config::config_update_url_set,
+            command::config::config_telemetry_get,
+            command::config::config_telemetry_set,
+            command::config::config_telemetry_url_get,
+            command::config::config_telemetry_url_set,
+            command::config::config_telemetry_preview,
+            command::config::config_automation_get,
+            command::config::config_automation_set,
+            command::config::config_view_only_get,
+            command::config::config_view_only_set,
+            command::config::config_visit_confirm_get,
+            command::config::config_visit_confirm_set,
+            command::config::config_unattended_login_get,
+            command::config::config_unattended_login_set,
+            command::config::config_reject_message_get,
+            command::config::config_reject_message_set,
+            command::config::config_availability_get,
+            command::config::config_availability_set,
+            command::config::config_max_sessions_get,
+            command::config::config_max_sessions_set,
+            command::config::config_idle_frame_rate_get,
+            command::config::config_idle_frame_rate_set,
+            command::config::config_watermark_get,
+            command::config::config_watermark_set,
+            command::config::config_tile_codec_preferred_get,
+            command::config::config_tile_codec_preferred_set,
+            command::config::config_gop_policy_get,
+            command::config::config_gop_policy_set,
+            command::config::config_session_profile_get,
+            command::config::config_session_profile_set,
+            command::config::config_quality_auto_tune_get,
+            command::config::config_quality_auto_tune_set,
+            command::config::config_test_pattern_get,
+            command::config::config_test_pattern_set,
+            command::config::config_encoder_thread_priority_get,
+            command::config::config_encoder_thread_priority_set,
+            command::config::config_encoder_cores_get,
+            command::config::config_encoder_cores_set,
+            command::config::config_staging_quota_get,
+            command::config::config_staging_quota_set,
+            command::config::config_staging_retention_get,
+            command::config::config_staging_retention_set,
+            command::config::config_peer_trust,
+            command::config::config_peer_bandwidth_limit_get,
+            command::config::config_peer_bandwidth_limit_set,
+            command::config::config_lan_only_get,
+            command::config::config_lan_only_set,
+            command::config::config_lan_key_get,
+            command::config::config_lan_key_set,
+            command::config::config_lan_key_generate,
+            command::config::config_key_store_backend_get,
+            command::config::config_lan_listen_ip_get,
+            command::config::config_lan_listen_ip_set,
+            command::config::config_bind_ip_get,
+            command::config::config_bind_ip_set,
+            command::config::config_autostart_get,
+            command::config::config_autostart_set,
+            command::config::config_log_filter_get,
+            command::config::config_log_filter_set,
+            command::config::config_transport_tuning_get,
+            command::config::config_transport_tuning_set,
+            command::config::config_receive_policy_get,
+            command::config::config_receive_policy_set,
             command::lan::lan_init,
             command::lan::lan_connect,
             command::lan::lan_nodes_list,
             command::lan::lan_nodes_search,
             command::lan::lan_discoverable_get,
             command::lan::lan_discoverable_set,
+            command::lan::lan_listener_report,
+            command::lan::lan_peer_card_export,
+            command::lan::lan_peer_card_import,
+            command::lan::lan_paired_peers_list,
+            command::lan::lan_paired_peer_revoke,
             command::signaling::signaling_connect,
+            command::signaling::signaling_state,
             command::signaling::signaling_visit,
+            command::signaling::signaling_visit_reply,
+            command::signaling::signaling_pairing_create,
+            command::signaling::signaling_pairing_revoke,
+            command::signaling::signaling_pairing_visit,
+            command::signaling::signaling_observer_invite,
+            command::signaling::signaling_observer_visit,
             command::file_manager::file_manager_visit_remote,
             command::file_manager::file_manager_visit_local,
+            command::file_manager::file_manager_session_state,
             command::file_manager::file_manager_send_file,
+            command::file_manager::file_manager_verify_transfer,
+            command::file_manager::file_manager_bridge_start,
+            command::file_manager::file_manager_bridge_stop,
             command::file_manager::file_manager_download_file,
-            command::file_manager::file_manager_query_transferred_bytes_count,
+            command::file_manager::file_manager_interrupted_transfers,
+            command::file_manager::file_manager_resume_transfer,
+            command::file_manager::file_manager_discard_transfer,
+            command::permission::permission_request,
+            command::permission::permission_reply,
+            command::permission::local_input_block_reply,
+            command::queue::queue_list,
+            command::queue::queue_move,
+            command::queue::queue_reject,
+            command::staging::staging_open,
+            command::staging::staging_usage,
+            command::staging::staging_clear,
+            command::quarantine::quarantine_list,
+            command::quarantine::quarantine_release,
+            command::quarantine::quarantine_discard,
+            command::utility::utility_cancel_request,
             command::utility::utility_generate_random_password,
             command::utility::utility_detect_os_platform,
             command::utility::utility_enum_graphics_cards,
+            command::utility::utility_enum_network_interfaces,
             command::utility::utility_hide_macos_zoom_button,
+            command::utility::utility_query_logs,
+            command::utility::utility_generate_diagnostics,
+            command::utility::utility_error_catalog,
+            command::utility::utility_crash_report_list,
+            command::utility::utility_crash_report_upload,
+            command::utility::utility_crash_report_discard,
+            command::updater::updater_check,
+            command::updater::updater_install,
         ])
         .build(tauri::generate_context!())
         .expect("error while running tauri application")
 }
+
+fn quit(app_handle: &AppHandle) -> ! {
+    app_handle.state::<command::AppState>().clear_session_markers();
+    std::process::exit(0)
+}
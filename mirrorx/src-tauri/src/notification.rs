@@ -0,0 +1,615 @@
+use crate::{
+    event::dispatch,
+    tray,
+    utility::{format_device_id, format_endpoint_id},
+};
+use mirrorx_core::api::{
+    config::{entity::kv::NotificationKind, LocalStorage},
+    endpoint::id::EndPointID,
+    event::CoreEvent,
+    notification::{subscribe, Notification},
+    signaling::fingerprint::PeerTrust,
+};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tauri::{AppHandle, Manager};
+use tokio::task::JoinHandle;
+
+// an action is only performed when user clicks the notification in time
+const PENDING_ACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub enum NotificationAction {
+    ShowVisitRequest {
+        request_id: Option<String>,
+        active_device_id: i64,
+        passive_device_id: i64,
+        visit_desktop: bool,
+    },
+    // the permission or local input block request event to emit again
+    ShowPermissionRequest(CoreEvent),
+    // the quarantined file event to emit again
+    ShowQuarantinedFile(CoreEvent),
+    OpenFolder(PathBuf),
+}
+
+pub struct Notifier {
+    pending_action: Arc<Mutex<Option<(Instant, NotificationAction)>>>,
+    serve_handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Self {
+            pending_action: Arc::new(Mutex::new(None)),
+            serve_handle: Mutex::new(None),
+        }
+    }
+
+    /// Start (or restart) receiving core notifications and raise them as os native notifications.
+    pub fn serve(&self, app_handle: AppHandle, storage: LocalStorage) {
+        let pending_action = self.pending_action.clone();
+        let mut rx = subscribe();
+
+        let handle = tokio::spawn(async move {
+            while let Ok(notification) = rx.recv().await {
+                clear_recovery_state(&storage, &notification);
+
+                let (kind, action) = match notification {
+                    Notification::VisitRequest {
+                        ref request_id,
+                        active_device_id,
+                        passive_device_id,
+                        visit_desktop,
+                    } => {
+                        // the remote is waiting for decision, prompt even if user disabled
+                        // the native notification of it
+                        if let Some(request_id) = request_id {
+                            dispatch(
+                                &app_handle,
+                                CoreEvent::VisitRequest {
+                                    request_id: Some(request_id.clone()),
+                                    active_device_id,
+                                    passive_device_id,
+                                    visit_desktop,
+                                },
+                            );
+
+                            // finding and opening the hidden window may take longer than
+                            // the remote waits, so it can be decided from tray
+                            if !main_window_visible(&app_handle) {
+                                tray::add_visit_request(
+                                    &app_handle,
+                                    request_id.clone(),
+                                    active_device_id,
+                                    visit_desktop,
+                                );
+                            }
+                        }
+
+                        (
+                            NotificationKind::VisitRequest,
+                            Some(NotificationAction::ShowVisitRequest {
+                                request_id: request_id.clone(),
+                                active_device_id,
+                                passive_device_id,
+                                visit_desktop,
+                            }),
+                        )
+                    }
+                    Notification::VisitRequestCanceled {
+                        request_id: ref canceled_request_id,
+                    } => {
+                        // the canceled visit shouldn't pop up when main window get focused
+                        discard_pending_visit_request(&pending_action, canceled_request_id);
+                        tray::remove_visit_request(&app_handle, canceled_request_id);
+
+                        dispatch(
+                            &app_handle,
+                            CoreEvent::VisitRequestCanceled {
+                                request_id: canceled_request_id.clone(),
+                            },
+                        );
+
+                        continue;
+                    }
+                    Notification::FileTransferSucceeded { ref path, .. } => (
+                        NotificationKind::FileTransferSucceeded,
+                        path.parent()
+                            .map(|dir| NotificationAction::OpenFolder(dir.to_path_buf())),
+                    ),
+                    Notification::FileTransferFailed { .. } => {
+                        (NotificationKind::FileTransferFailed, None)
+                    }
+                    Notification::FileQuarantined { ref file } => {
+                        let event = CoreEvent::FileQuarantined(file.clone());
+
+                        // it stays in quarantine until user decides, prompt even if user
+                        // disabled the native notification of it
+                        dispatch(&app_handle, event.clone());
+
+                        (
+                            NotificationKind::FileTransferSucceeded,
+                            Some(NotificationAction::ShowQuarantinedFile(event)),
+                        )
+                    }
+                    Notification::SessionDisconnected { .. } => {
+                        (NotificationKind::SessionDisconnected, None)
+                    }
+                    Notification::RemotePowerSaving { .. } => {
+                        (NotificationKind::RemotePowerSaving, None)
+                    }
+                    Notification::PermissionElevationRequest {
+                        ref request_id,
+                        ref endpoint_id,
+                        permissions,
+                    } => {
+                        let event = CoreEvent::PermissionRequest {
+                            request_id: request_id.clone(),
+                            endpoint_id: *endpoint_id,
+                            permissions,
+                        };
+
+                        // the remote is waiting for decision, prompt even if user disabled
+                        // the native notification of it
+                        dispatch(&app_handle, event.clone());
+
+                        (
+                            NotificationKind::PermissionRequest,
+                            Some(NotificationAction::ShowPermissionRequest(event)),
+                        )
+                    }
+                    Notification::LocalInputBlockRequest {
+                        ref request_id,
+                        ref endpoint_id,
+                    } => {
+                        let event = CoreEvent::LocalInputBlockRequest {
+                            request_id: request_id.clone(),
+                            endpoint_id: *endpoint_id,
+                        };
+
+                        // the remote is waiting for confirmation, prompt even if user
+                        // disabled the native notification of it
+                        dispatch(&app_handle, event.clone());
+
+                        (
+                            NotificationKind::PermissionRequest,
+                            Some(NotificationAction::ShowPermissionRequest(event)),
+                        )
+                    }
+                    Notification::PeerVerified {
+                        ref domain,
+                        remote_device_id,
+                        ref fingerprint,
+                        ref short_auth_string,
+                        ref trust,
+                        ..
+                    } => {
+                        // frontend displays the short auth string for users to compare
+                        dispatch(
+                            &app_handle,
+                            CoreEvent::PeerVerified {
+                                domain: domain.clone(),
+                                remote_device_id,
+                                fingerprint: fingerprint.clone(),
+                                short_auth_string: short_auth_string.clone(),
+                                trust: trust.clone(),
+                            },
+                        );
+
+                        // only the changed identity is worth a native notification
+                        if !matches!(trust, PeerTrust::Changed { .. }) {
+                            continue;
+                        }
+
+                        (NotificationKind::PeerIdentityChanged, None)
+                    }
+                    // queue changes are frequent, they're shown in window only
+                    Notification::VisitQueueChanged { ref visits } => {
+                        dispatch(
+                            &app_handle,
+                            CoreEvent::VisitQueueChanged {
+                                visits: visits.clone(),
+                            },
+                        );
+
+                        continue;
+                    }
+                    Notification::VisitQueuePosition {
+                        active_device_id,
+                        passive_device_id,
+                        position,
+                    } => {
+                        dispatch(
+                            &app_handle,
+                            CoreEvent::VisitQueuePosition {
+                                active_device_id,
+                                passive_device_id,
+                                position,
+                            },
+                        );
+
+                        continue;
+                    }
+                    // frontend suggests the remedy to user of the session
+                    Notification::QualityAlert {
+                        endpoint_id,
+                        ref alert,
+                    } => {
+                        dispatch(
+                            &app_handle,
+                            CoreEvent::QualityAlert {
+                                endpoint_id,
+                                alert: alert.clone(),
+                            },
+                        );
+
+                        continue;
+                    }
+                    // home screen shows connectivity of the primary domain
+                    Notification::SignalingStateChanged { domain_id, state } => {
+                        dispatch(&app_handle, CoreEvent::SignalingState { domain_id, state });
+
+                        continue;
+                    }
+                };
+
+                match storage.kv().get_notification_enabled(kind) {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(err) => {
+                        tracing::error!(?err, ?kind, "read notification config failed");
+                        continue;
+                    }
+                }
+
+                let language = storage.kv().get_language().ok().flatten();
+                let (title, mut body) = notification_text(language.as_deref(), &notification);
+
+                if let Notification::VisitRequest {
+                    request_id: Some(ref request_id),
+                    ..
+                } = notification
+                {
+                    if tray::has_visit_request(request_id) {
+                        body.push_str(if language.as_deref() == Some("zh") {
+                            "，可在托盘菜单中接受或拒绝"
+                        } else {
+                            ", accept or reject it from the tray menu"
+                        });
+                    }
+                }
+
+                if let Err(err) = tauri::api::notification::Notification::new(
+                    &app_handle.config().tauri.bundle.identifier,
+                )
+                .title(title)
+                .body(body)
+                .show()
+                {
+                    tracing::error!(?err, ?kind, "show notification failed");
+                    continue;
+                }
+
+                if let Some(action) = action {
+                    *pending_action.lock().unwrap() = Some((Instant::now(), action));
+                }
+            }
+
+            tracing::info!("notification serve loop exit");
+        });
+
+        if let Some(old_handle) = self.serve_handle.lock().unwrap().replace(handle) {
+            old_handle.abort();
+        }
+    }
+
+    /// Forget the visit request which has been replied elsewhere, so it isn't prompted
+    /// when main window get focused.
+    pub fn discard_visit_request(&self, request_id: &str) {
+        discard_pending_visit_request(&self.pending_action, request_id);
+    }
+
+    /// Os brings the app to front when user clicks the notification, so the latest
+    /// unexpired action will be performed when main window get focused.
+    pub fn perform_pending_action(&self, app_handle: &AppHandle) {
+        let Some((instant, action)) = self.pending_action.lock().unwrap().take() else {
+            return;
+        };
+
+        if instant.elapsed() > PENDING_ACTION_TIMEOUT {
+            return;
+        }
+
+        tracing::info!(?action, "perform notification action");
+
+        match action {
+            NotificationAction::ShowVisitRequest {
+                request_id,
+                active_device_id,
+                passive_device_id,
+                visit_desktop,
+            } => dispatch(
+                app_handle,
+                CoreEvent::VisitRequest {
+                    request_id,
+                    active_device_id,
+                    passive_device_id,
+                    visit_desktop,
+                },
+            ),
+            NotificationAction::ShowPermissionRequest(event)
+            | NotificationAction::ShowQuarantinedFile(event) => dispatch(app_handle, event),
+            NotificationAction::OpenFolder(path) => {
+                if let Err(err) =
+                    tauri::api::shell::open(&app_handle.shell_scope(), path.to_string_lossy(), None)
+                {
+                    tracing::error!(?err, ?path, "open folder failed");
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        if let Some(handle) = self.serve_handle.lock().unwrap().take() {
+            handle.abort();
+        }
+    }
+}
+
+fn discard_pending_visit_request(
+    pending_action: &Mutex<Option<(Instant, NotificationAction)>>,
+    discarded_request_id: &str,
+) {
+    let mut pending_action = pending_action.lock().unwrap();
+    let discarded = match *pending_action {
+        Some((_, NotificationAction::ShowVisitRequest { ref request_id, .. })) => {
+            request_id.as_deref() == Some(discarded_request_id)
+        }
+        _ => false,
+    };
+
+    if discarded {
+        pending_action.take();
+    }
+}
+
+fn main_window_visible(app_handle: &AppHandle) -> bool {
+    app_handle
+        .get_window("main")
+        .and_then(|window| window.is_visible().ok())
+        .unwrap_or(false)
+}
+
+// transfers and sessions which finished normally needn't be recovered after crash
+fn clear_recovery_state(storage: &LocalStorage, notification: &Notification) {
+    let result = match notification {
+        Notification::FileTransferSucceeded { id, .. }
+        | Notification::FileTransferFailed { id, .. } => storage.recovery().delete_transfer(id),
+        Notification::FileQuarantined { file } => storage.recovery().delete_transfer(&file.id),
+        Notification::SessionDisconnected {
+            endpoint_id:
+                EndPointID::DeviceID {
+                    local_device_id,
+                    remote_device_id,
+                },
+        } => storage
+            .recovery()
+            .delete_session(*local_device_id, *remote_device_id),
+        _ => return,
+    };
+
+    if let Err(err) = result {
+        tracing::error!(?err, "clear recovery state failed");
+    }
+}
+
+fn notification_text(language: Option<&str>, notification: &Notification) -> (String, String) {
+    let zh = language == Some("zh");
+
+    match notification {
+        Notification::VisitRequest {
+            active_device_id,
+            visit_desktop,
+            ..
+        } => {
+            let device_id = format_device_id(*active_device_id);
+            if zh {
+                let resource = if *visit_desktop { "桌面" } else { "文件" };
+                (
+                    String::from("访问请求"),
+                    format!("设备 {device_id} 请求访问您的{resource}"),
+                )
+            } else {
+                let resource = if *visit_desktop { "desktop" } else { "files" };
+                (
+                    String::from("Visit Request"),
+                    format!("Device {device_id} wants to visit your {resource}"),
+                )
+            }
+        }
+        Notification::FileTransferSucceeded { path, .. } => {
+            let filename = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            if zh {
+                (
+                    String::from("文件传输完成"),
+                    format!("{filename} 已传输完成"),
+                )
+            } else {
+                (
+                    String::from("File Transfer Completed"),
+                    format!("{filename} has been transferred"),
+                )
+            }
+        }
+        Notification::FileTransferFailed { path, .. } => {
+            let filename = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            if zh {
+                (String::from("文件传输失败"), format!("{filename} 传输失败"))
+            } else {
+                (
+                    String::from("File Transfer Failed"),
+                    format!("{filename} failed to transfer"),
+                )
+            }
+        }
+        Notification::FileQuarantined { file } => {
+            let filename = file
+                .destination
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            if zh {
+                (
+                    String::from("文件已隔离"),
+                    format!("{filename} 已接收到隔离区，确认后才会保存"),
+                )
+            } else {
+                (
+                    String::from("File Quarantined"),
+                    format!("{filename} is received into quarantine, confirm to save it"),
+                )
+            }
+        }
+        Notification::SessionDisconnected { endpoint_id } => {
+            let remote = format_endpoint_id(endpoint_id);
+            if zh {
+                (
+                    String::from("会话已断开"),
+                    format!("与 {remote} 的会话已断开"),
+                )
+            } else {
+                (
+                    String::from("Session Disconnected"),
+                    format!("Session with {remote} has been disconnected"),
+                )
+            }
+        }
+        Notification::RemotePowerSaving {
+            endpoint_id,
+            on_battery,
+            power_saver,
+            max_frame_rate,
+        } => {
+            let remote = format_endpoint_id(endpoint_id);
+            match (max_frame_rate, zh) {
+                (Some(frame_rate), true) => {
+                    let reason = if *power_saver {
+                        "处于省电模式"
+                    } else if *on_battery {
+                        "正在使用电池供电"
+                    } else {
+                        "正在节能"
+                    };
+
+                    (
+                        String::from("远程设备正在节能"),
+                        format!("{remote} {reason}，画面帧率已降低至 {frame_rate} FPS"),
+                    )
+                }
+                (Some(frame_rate), false) => {
+                    let reason = if *power_saver {
+                        "is in power saver mode"
+                    } else if *on_battery {
+                        "is running on battery"
+                    } else {
+                        "is saving power"
+                    };
+
+                    (
+                        String::from("Remote Device Saving Power"),
+                        format!("{remote} {reason}, frame rate is reduced to {frame_rate} FPS"),
+                    )
+                }
+                (None, true) => (
+                    String::from("远程设备已恢复"),
+                    format!("{remote} 已恢复正常帧率"),
+                ),
+                (None, false) => (
+                    String::from("Remote Device Restored"),
+                    format!("{remote} has restored the normal frame rate"),
+                ),
+            }
+        }
+        Notification::PermissionElevationRequest {
+            endpoint_id,
+            permissions,
+            ..
+        } => {
+            let remote = format_endpoint_id(endpoint_id);
+            let permissions = permissions.names().join(", ");
+            if zh {
+                (
+                    String::from("权限请求"),
+                    format!("{remote} 请求以下权限：{permissions}"),
+                )
+            } else {
+                (
+                    String::from("Permission Request"),
+                    format!("{remote} requests permissions: {permissions}"),
+                )
+            }
+        }
+        Notification::LocalInputBlockRequest { endpoint_id, .. } => {
+            let remote = format_endpoint_id(endpoint_id);
+            if zh {
+                (
+                    String::from("屏蔽本地输入请求"),
+                    format!("{remote} 请求屏蔽本机的鼠标和键盘，可随时按 Ctrl+Alt+Esc 恢复"),
+                )
+            } else {
+                (
+                    String::from("Block Input Request"),
+                    format!(
+                        "{remote} requests to block the mouse and keyboard of this device, \
+                         press Ctrl+Alt+Esc to restore them any time"
+                    ),
+                )
+            }
+        }
+        // only the changed identity is notified
+        Notification::PeerVerified {
+            remote_device_id,
+            fingerprint,
+            ..
+        } => {
+            let device_id = format_device_id(*remote_device_id);
+            if zh {
+                (
+                    String::from("设备身份已变更"),
+                    format!("设备 {device_id} 的身份指纹变为 {fingerprint}，会话可能被拦截"),
+                )
+            } else {
+                (
+                    String::from("Device Identity Changed"),
+                    format!(
+                        "Identity of {device_id} changed to {fingerprint}, beware of interception"
+                    ),
+                )
+            }
+        }
+        Notification::VisitQueueChanged { .. }
+        | Notification::VisitQueuePosition { .. }
+        | Notification::VisitRequestCanceled { .. }
+        | Notification::SignalingStateChanged { .. }
+        | Notification::QualityAlert { .. } => {
+            unreachable!(
+                "queue, cancel, signaling and quality notifications are only shown in window"
+            )
+        }
+    }
+}
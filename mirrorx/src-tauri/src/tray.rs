@@ -0,0 +1,309 @@
+//! Tray menu of the app. Besides showing and hiding windows, it lists the live sessions
+//! with their bandwidth from the session summary of core, so users can disconnect them
+//! without opening the main window. Visit requests arriving while the main window is
+//! hidden are listed on top, so they can be decided before they time out.
+
+use crate::{
+    event::dispatch,
+    notification::Notifier,
+    utility::{format_device_id, format_endpoint_id},
+};
+use mirrorx_core::api::{
+    endpoint::sessions::{self, SessionSummary, SessionsSummary},
+    event::CoreEvent,
+    signaling::visit_prompt::reply_visit,
+};
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+use tauri::{AppHandle, CustomMenuItem, Manager, SystemTrayMenu, SystemTrayMenuItem};
+use tokio::task::JoinHandle;
+
+const SUMMARY_ITEM_ID: &str = "sessions";
+const DISCONNECT_ITEM_ID_PREFIX: &str = "disconnect/";
+const ACCEPT_VISIT_ITEM_ID_PREFIX: &str = "visit/accept/";
+const REJECT_VISIT_ITEM_ID_PREFIX: &str = "visit/reject/";
+
+static TRAY: Lazy<Mutex<Tray>> = Lazy::new(|| {
+    Mutex::new(Tray {
+        zh: false,
+        summary: SessionsSummary::default(),
+        visit_requests: Vec::new(),
+    })
+});
+
+static SERVE_HANDLE: Lazy<Mutex<Option<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(None));
+
+struct Tray {
+    zh: bool,
+    summary: SessionsSummary,
+    visit_requests: Vec<VisitRequest>,
+}
+
+struct VisitRequest {
+    request_id: String,
+    active_device_id: i64,
+    visit_desktop: bool,
+}
+
+/// Build the tray menu in the language again, languages without translation are ignored.
+pub fn set_language(app_handle: &AppHandle, language: &str) {
+    let zh = match language {
+        "en" => false,
+        "zh" => true,
+        _ => return,
+    };
+
+    let mut tray = TRAY.lock().unwrap();
+    tray.zh = zh;
+    set_menu(app_handle, &tray);
+}
+
+/// Start (or restart) following the session summary which core publishes.
+pub fn serve(app_handle: AppHandle) {
+    let mut rx = sessions::subscribe();
+
+    let handle = tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(summary) => update_sessions(&app_handle, summary),
+                Err(_) if rx.is_closed() => return,
+                // overflowed, the skipped summary is superseded by the later one
+                Err(_) => continue,
+            }
+        }
+    });
+
+    if let Some(previous) = SERVE_HANDLE.lock().unwrap().replace(handle) {
+        previous.abort();
+    }
+}
+
+/// List the visit request which waits for decision, it's removed once it's replied or
+/// canceled.
+pub fn add_visit_request(
+    app_handle: &AppHandle,
+    request_id: String,
+    active_device_id: i64,
+    visit_desktop: bool,
+) {
+    let mut tray = TRAY.lock().unwrap();
+    tray.visit_requests.push(VisitRequest {
+        request_id,
+        active_device_id,
+        visit_desktop,
+    });
+
+    set_menu(app_handle, &tray);
+}
+
+pub fn has_visit_request(request_id: &str) -> bool {
+    TRAY.lock()
+        .unwrap()
+        .visit_requests
+        .iter()
+        .any(|visit_request| visit_request.request_id == request_id)
+}
+
+pub fn remove_visit_request(app_handle: &AppHandle, request_id: &str) {
+    let mut tray = TRAY.lock().unwrap();
+    let count = tray.visit_requests.len();
+    tray.visit_requests
+        .retain(|visit_request| visit_request.request_id != request_id);
+
+    if tray.visit_requests.len() != count {
+        set_menu(app_handle, &tray);
+    }
+}
+
+/// Disconnect the session or reply the visit request which the clicked item belongs to.
+pub fn handle_menu_item_click(app_handle: &AppHandle, id: &str) {
+    if let Some(session_id) = id.strip_prefix(DISCONNECT_ITEM_ID_PREFIX) {
+        if let Err(err) = sessions::close_session(session_id) {
+            tracing::warn!(?err, ?session_id, "disconnect session from tray failed");
+        }
+    } else if let Some(request_id) = id.strip_prefix(ACCEPT_VISIT_ITEM_ID_PREFIX) {
+        reply_visit_request(app_handle, request_id, true);
+    } else if let Some(request_id) = id.strip_prefix(REJECT_VISIT_ITEM_ID_PREFIX) {
+        reply_visit_request(app_handle, request_id, false);
+    }
+}
+
+fn reply_visit_request(app_handle: &AppHandle, request_id: &str, allow: bool) {
+    remove_visit_request(app_handle, request_id);
+
+    if let Err(err) = reply_visit(request_id, allow, None) {
+        tracing::warn!(?err, ?request_id, "reply visit request from tray failed");
+    }
+
+    // the prompt in window and the notification are stale now
+    app_handle
+        .state::<Notifier>()
+        .discard_visit_request(request_id);
+
+    dispatch(
+        app_handle,
+        CoreEvent::VisitRequestCanceled {
+            request_id: request_id.to_string(),
+        },
+    );
+}
+
+fn update_sessions(app_handle: &AppHandle, summary: SessionsSummary) {
+    let mut tray = TRAY.lock().unwrap();
+    if tray.summary == summary {
+        return;
+    }
+
+    let same_sessions = tray.summary.sessions.len() == summary.sessions.len()
+        && tray
+            .summary
+            .sessions
+            .iter()
+            .zip(summary.sessions.iter())
+            .all(|(a, b)| a.session_id == b.session_id);
+
+    tray.summary = summary;
+
+    // setting the menu closes it if user is browsing it, so only the titles are updated
+    // while sessions stay the same
+    if same_sessions {
+        update_titles(app_handle, &tray);
+    } else {
+        set_menu(app_handle, &tray);
+    }
+}
+
+fn set_menu(app_handle: &AppHandle, tray: &Tray) {
+    let (quit_text, show_text, hide_text, about_text) = if tray.zh {
+        ("退出", "显示", "隐藏", "关于")
+    } else {
+        ("Quit", "Show", "Hide", "About")
+    };
+
+    let mut tray_menu = SystemTrayMenu::new();
+
+    for visit_request in &tray.visit_requests {
+        let (accept_text, reject_text) = if tray.zh {
+            ("接受", "拒绝")
+        } else {
+            ("Accept", "Reject")
+        };
+
+        tray_menu = tray_menu
+            .add_item(
+                CustomMenuItem::new(
+                    format!("visit/{}", visit_request.request_id),
+                    visit_request_title(tray.zh, visit_request),
+                )
+                .disabled(),
+            )
+            .add_item(CustomMenuItem::new(
+                format!("{ACCEPT_VISIT_ITEM_ID_PREFIX}{}", visit_request.request_id),
+                accept_text,
+            ))
+            .add_item(CustomMenuItem::new(
+                format!("{REJECT_VISIT_ITEM_ID_PREFIX}{}", visit_request.request_id),
+                reject_text,
+            ))
+            .add_native_item(SystemTrayMenuItem::Separator);
+    }
+
+    if !tray.summary.sessions.is_empty() {
+        tray_menu = tray_menu
+            .add_item(CustomMenuItem::new(SUMMARY_ITEM_ID, summary_title(tray)).disabled());
+
+        for session in &tray.summary.sessions {
+            tray_menu = tray_menu.add_item(CustomMenuItem::new(
+                disconnect_item_id(session),
+                session_title(tray.zh, session),
+            ));
+        }
+
+        tray_menu = tray_menu.add_native_item(SystemTrayMenuItem::Separator);
+    }
+
+    tray_menu = tray_menu
+        .add_item(CustomMenuItem::new("hide", hide_text))
+        .add_item(CustomMenuItem::new("show", show_text))
+        .add_native_item(SystemTrayMenuItem::Separator);
+
+    if !cfg!(target_os = "macos") {
+        tray_menu = tray_menu
+            .add_item(CustomMenuItem::new("about", about_text))
+            .add_native_item(SystemTrayMenuItem::Separator);
+    }
+
+    tray_menu = tray_menu.add_item(CustomMenuItem::new("quit", quit_text));
+
+    if let Err(err) = app_handle.tray_handle().set_menu(tray_menu) {
+        tracing::error!(?err, "set new tray menu failed");
+    }
+}
+
+fn update_titles(app_handle: &AppHandle, tray: &Tray) {
+    let tray_handle = app_handle.tray_handle();
+
+    if let Err(err) = tray_handle
+        .get_item(SUMMARY_ITEM_ID)
+        .set_title(summary_title(tray))
+    {
+        tracing::error!(?err, "set tray menu item title failed");
+    }
+
+    for session in &tray.summary.sessions {
+        if let Err(err) = tray_handle
+            .get_item(&disconnect_item_id(session))
+            .set_title(session_title(tray.zh, session))
+        {
+            tracing::error!(?err, "set tray menu item title failed");
+        }
+    }
+}
+
+fn disconnect_item_id(session: &SessionSummary) -> String {
+    format!("{DISCONNECT_ITEM_ID_PREFIX}{}", session.session_id)
+}
+
+fn summary_title(tray: &Tray) -> String {
+    let count = tray.summary.sessions.len();
+    let bit_rate = format_bit_rate(tray.summary.total_bit_rate_kbps());
+
+    if tray.zh {
+        format!("{count} 个会话 · {bit_rate}")
+    } else if count == 1 {
+        format!("1 session · {bit_rate}")
+    } else {
+        format!("{count} sessions · {bit_rate}")
+    }
+}
+
+fn visit_request_title(zh: bool, visit_request: &VisitRequest) -> String {
+    let device_id = format_device_id(visit_request.active_device_id);
+
+    match (zh, visit_request.visit_desktop) {
+        (true, true) => format!("{device_id} 请求访问桌面"),
+        (true, false) => format!("{device_id} 请求访问文件"),
+        (false, true) => format!("{device_id} wants to visit desktop"),
+        (false, false) => format!("{device_id} wants to visit files"),
+    }
+}
+
+fn session_title(zh: bool, session: &SessionSummary) -> String {
+    let remote = format_endpoint_id(&session.endpoint_id);
+    let bit_rate = format_bit_rate(session.bit_rate_kbps);
+
+    match (zh, session.active) {
+        (true, true) => format!("断开 {remote}(控制中,{bit_rate})"),
+        (true, false) => format!("断开 {remote}(被控中,{bit_rate})"),
+        (false, true) => format!("Disconnect {remote} (visiting, {bit_rate})"),
+        (false, false) => format!("Disconnect {remote} (visited, {bit_rate})"),
+    }
+}
+
+fn format_bit_rate(kbps: u64) -> String {
+    if kbps >= 1000 {
+        format!("{:.1} Mbps", kbps as f64 / 1000.0)
+    } else {
+        format!("{kbps} kbps")
+    }
+}
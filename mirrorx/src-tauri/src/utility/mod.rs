@@ -1,6 +1,32 @@
+use mirrorx_core::api::endpoint::id::EndPointID;
+use std::path::PathBuf;
+use tauri::PathResolver;
+
+pub const LOG_FILE_PREFIX: &str = "mirrorx.log";
+
 pub fn format_device_id(device_id: i64) -> String {
     let mut device_id = format!("{device_id:0>10}");
     device_id.insert(2, '-');
     device_id.insert(7, '-');
     device_id
 }
+
+/// The remote of endpoint in the form which user sees, like `12-3456-7890`.
+pub fn format_endpoint_id(endpoint_id: &EndPointID) -> String {
+    match endpoint_id {
+        EndPointID::DeviceID {
+            remote_device_id, ..
+        } => format_device_id(*remote_device_id),
+        EndPointID::LANID { remote_ip, .. } => remote_ip.to_string(),
+    }
+}
+
+/// The directory where the rolling log files are written.
+pub fn log_dir(path_resolver: &PathResolver) -> Option<PathBuf> {
+    path_resolver.app_log_dir().map(|dir| dir.join("logs"))
+}
+
+/// The directory where the panic reports and minidumps are written.
+pub fn crash_dir(path_resolver: &PathResolver) -> Option<PathBuf> {
+    path_resolver.app_log_dir().map(|dir| dir.join("crashes"))
+}
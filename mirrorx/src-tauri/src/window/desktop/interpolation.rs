@@ -0,0 +1,57 @@
+//! Smooths the motion of remote desktop which is streamed at low frame rate, a frame
+//! blended from the previous and the current frame is shown half an interval before the
+//! current one. It doubles the frame rate at the cost of CPU time and half an interval of
+//! latency, so it's optional and off by default.
+
+use mirrorx_core::DesktopDecodeFrame;
+use std::time::Duration;
+
+// faster streams move smoothly already
+const MIN_INTERPOLATION_INTERVAL: Duration = Duration::from_millis(25);
+
+// remote desktop idles or stalls, delaying the next frame would only add latency
+const MAX_INTERPOLATION_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whether frames arriving at `interval` are worth interpolating.
+pub fn should_interpolate(interval: Duration) -> bool {
+    (MIN_INTERPOLATION_INTERVAL..=MAX_INTERPOLATION_INTERVAL).contains(&interval)
+}
+
+/// The frame halfway between two frames, None when their layout differs like after the
+/// remote monitor is switched.
+pub fn blend_frames(
+    previous: &DesktopDecodeFrame,
+    current: &DesktopDecodeFrame,
+) -> Option<DesktopDecodeFrame> {
+    if previous.width != current.width
+        || previous.height != current.height
+        || previous.line_sizes != current.line_sizes
+        || previous.plane_data.len() != current.plane_data.len()
+    {
+        return None;
+    }
+
+    let mut plane_data = Vec::with_capacity(current.plane_data.len());
+    for (previous_plane, current_plane) in previous.plane_data.iter().zip(&current.plane_data) {
+        if previous_plane.len() != current_plane.len() {
+            return None;
+        }
+
+        plane_data.push(
+            previous_plane
+                .iter()
+                .zip(current_plane)
+                .map(|(a, b)| ((*a as u16 + *b as u16 + 1) / 2) as u8)
+                .collect(),
+        );
+    }
+
+    Some(DesktopDecodeFrame {
+        width: current.width,
+        height: current.height,
+        plane_data,
+        line_sizes: current.line_sizes.clone(),
+        format: current.format.clone(),
+        color_space: current.color_space,
+    })
+}
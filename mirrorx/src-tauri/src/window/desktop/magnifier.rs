@@ -0,0 +1,104 @@
+//! Zoom into remote desktop to read small text over slow links. The desktop frame is
+//! scaled up locally and the region in view is streamed by remote at native resolution
+//! besides it, the region is painted over the scaled desktop once it arrives. Pointer is
+//! used to pan and zoom instead of controlling remote while magnifying.
+
+use mirrorx_core::api::endpoint::message::MagnifierRegion;
+use std::time::{Duration, Instant};
+use tauri_egui::egui::{Pos2, Rect, Vec2};
+
+pub const MIN_ZOOM: f32 = 1.0;
+pub const MAX_ZOOM: f32 = 8.0;
+
+// every pan moves the region, ask remote for it at most this often
+const REGION_REQUEST_INTERVAL: Duration = Duration::from_millis(200);
+
+pub struct Magnifier {
+    zoom: f32,
+    // the remote pixel which is shown at the center of window
+    center: Pos2,
+    requested_region: Option<MagnifierRegion>,
+    requested_at: Option<Instant>,
+}
+
+impl Magnifier {
+    pub fn new(frame_width: i32, frame_height: i32) -> Self {
+        Magnifier {
+            zoom: 2.0,
+            center: Pos2::new(frame_width as f32 / 2.0, frame_height as f32 / 2.0),
+            requested_region: None,
+            requested_at: None,
+        }
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    /// Move the view by the distance in window points.
+    pub fn pan(&mut self, delta: Vec2, scale_ratio: f32) {
+        self.center -= delta / scale_ratio;
+    }
+
+    /// Scale ratio from remote pixels to window points, and the position of remote's
+    /// origin in window. The view is kept inside the desktop.
+    pub fn layout(&mut self, view: Rect, frame_width: i32, frame_height: i32) -> (f32, Pos2) {
+        let frame_size = Vec2::new(frame_width as f32, frame_height as f32);
+        let fit_ratio = (view.width() / frame_size.x).min(view.height() / frame_size.y);
+        let scale_ratio = fit_ratio * self.zoom;
+
+        let half_view = view.size() / scale_ratio / 2.0;
+        let half_frame = frame_size / 2.0;
+        self.center = Pos2::new(
+            self.center.x.clamp(
+                half_view.x.min(half_frame.x),
+                (frame_size.x - half_view.x).max(half_frame.x),
+            ),
+            self.center.y.clamp(
+                half_view.y.min(half_frame.y),
+                (frame_size.y - half_view.y).max(half_frame.y),
+            ),
+        );
+
+        (
+            scale_ratio,
+            view.center() - self.center.to_vec2() * scale_ratio,
+        )
+    }
+
+    /// The region in view if it should be requested now, remote clamps it into the
+    /// monitor.
+    pub fn take_region_request(
+        &mut self,
+        view: Rect,
+        scale_ratio: f32,
+        origin: Pos2,
+    ) -> Option<MagnifierRegion> {
+        let min = ((view.min - origin) / scale_ratio).max(Vec2::ZERO);
+        let size = view.size() / scale_ratio;
+
+        let region = MagnifierRegion {
+            x: min.x as u16,
+            y: min.y as u16,
+            width: size.x.ceil() as u16,
+            height: size.y.ceil() as u16,
+        };
+
+        if self.requested_region == Some(region)
+            || self
+                .requested_at
+                .map_or(false, |at| at.elapsed() < REGION_REQUEST_INTERVAL)
+        {
+            return None;
+        }
+
+        self.requested_region = Some(region);
+        self.requested_at = Some(Instant::now());
+
+        Some(region)
+    }
+}
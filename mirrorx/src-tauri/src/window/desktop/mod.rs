@@ -1,49 +1,94 @@
+mod interpolation;
+mod magnifier;
+mod pacing;
+mod pip;
 mod render;
 mod state;
 
-use self::render::Render;
+use self::{
+    magnifier::{MAX_ZOOM, MIN_ZOOM},
+    pip::PipWindow,
+    render::Render,
+};
+use crate::hotkey;
 use egui_extras::RetainedImage;
 use mirrorx_core::{
-    api::endpoint::{
-        client::EndPointClient,
-        id::EndPointID,
-        message::{EndPointInput, EndPointMessage, InputEvent, KeyboardEvent, MouseEvent},
+    api::{
+        config::entity::hotkey::HotkeyAction,
+        endpoint::{
+            client::EndPointClient,
+            id::EndPointID,
+            message::{
+                AudioCaptureTarget, AudioSource, CaptureInterruption, EndPointChannel,
+                EndPointCursorShape, EndPointInput, EndPointMessage, InputBlockReason, InputEvent,
+                KeyboardEvent, MouseEvent, StreamQuality,
+            },
+        },
     },
-    component::input::key::MouseKey,
+    component::{desktop::span::SPAN_MONITOR_ID, input::key::MouseKey, session_stats},
     DesktopDecodeFrame,
 };
-use state::State;
+use state::{FullscreenRequest, ScaleMode, State};
 use std::{
-    sync::{Arc, Mutex, RwLock},
+    path::PathBuf,
+    sync::{atomic::Ordering, Arc, Mutex, RwLock},
     time::Duration,
 };
+use tauri::{AppHandle, Manager};
 use tauri_egui::{
     eframe::{
         egui_glow::CallbackFn,
         glow::{self, Context},
     },
     egui::{
-        epaint::Shadow, style::Margin, Align, CentralPanel, Color32, FontId, Frame, Layout, Pos2,
-        Rect, RichText, Rounding, Sense, Stroke, Ui, Vec2,
+        epaint::Shadow, style::Margin, Align, Align2, CentralPanel, Checkbox, Color32, ColorImage,
+        FontId, Frame, Grid, Layout, Pos2, Rect, RichText, Rounding, ScrollArea, Sense, Slider,
+        Stroke, TextEdit, TextureFilter, TextureHandle, Ui, Vec2, Window,
     },
 };
 
 static ICON_MAXIMIZE_BYTES:&[u8]=br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 448 512"><!--! Font Awesome Pro 6.2.0 by @fontawesome - https://fontawesome.com License - https://fontawesome.com/license (Commercial License) Copyright 2022 Fonticons, Inc. --><path style="fill:rgb(255,255,255)" d="M168 32H24C10.7 32 0 42.7 0 56V200c0 9.7 5.8 18.5 14.8 22.2s19.3 1.7 26.2-5.2l40-40 79 79L81 335 41 295c-6.9-6.9-17.2-8.9-26.2-5.2S0 302.3 0 312V456c0 13.3 10.7 24 24 24H168c9.7 0 18.5-5.8 22.2-14.8s1.7-19.3-5.2-26.2l-40-40 79-79 79 79-40 40c-6.9 6.9-8.9 17.2-5.2 26.2s12.5 14.8 22.2 14.8H424c13.3 0 24-10.7 24-24V312c0-9.7-5.8-18.5-14.8-22.2s-19.3-1.7-26.2 5.2l-40 40-79-79 79-79 40 40c6.9 6.9 17.2 8.9 26.2 5.2s14.8-12.5 14.8-22.2V56c0-13.3-10.7-24-24-24H280c-9.7 0-18.5 5.8-22.2 14.8s-1.7 19.3 5.2 26.2l40 40-79 79-79-79 40-40c6.9-6.9 8.9-17.2 5.2-26.2S177.7 32 168 32z"/></svg>"#;
 static ICON_SCALE_BYTES:&[u8]=br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 640 512"><!--! Font Awesome Pro 6.2.0 by @fontawesome - https://fontawesome.com License - https://fontawesome.com/license (Commercial License) Copyright 2022 Fonticons, Inc. --><path style="fill:rgb(255,255,255)" d="M32 64c17.7 0 32 14.3 32 32l0 320c0 17.7-14.3 32-32 32s-32-14.3-32-32V96C0 78.3 14.3 64 32 64zm214.6 73.4c12.5 12.5 12.5 32.8 0 45.3L205.3 224l229.5 0-41.4-41.4c-12.5-12.5-12.5-32.8 0-45.3s32.8-12.5 45.3 0l96 96c12.5 12.5 12.5 32.8 0 45.3l-96 96c-12.5 12.5-32.8 12.5-45.3 0s-12.5-32.8 0-45.3L434.7 288l-229.5 0 41.4 41.4c12.5 12.5 12.5 32.8 0 45.3s-32.8 12.5-45.3 0l-96-96c-12.5-12.5-12.5-32.8 0-45.3l96-96c12.5-12.5 32.8-12.5 45.3 0zM640 96V416c0 17.7-14.3 32-32 32s-32-14.3-32-32V96c0-17.7 14.3-32 32-32s32 14.3 32 32z"/></svg>"#;
 
+// resolutions offered for remote monitor, the ones it doesn't support are rejected
+const REMOTE_RESOLUTIONS: &[(u16, u16)] = &[
+    (2560, 1440),
+    (1920, 1080),
+    (1600, 900),
+    (1366, 768),
+    (1280, 720),
+];
+
+#[derive(Default)]
+struct LoginForm {
+    username: String,
+    // empty for the accounts of remote machine
+    domain: String,
+    password: String,
+}
+
 pub struct DesktopWindow {
     state: State,
     icon_maximize: RetainedImage,
     icon_scale: RetainedImage,
     render: Arc<RwLock<Render>>,
     render_call_back: Arc<CallbackFn>,
+    magnifier_render: Arc<RwLock<Render>>,
+    magnifier_render_call_back: Arc<CallbackFn>,
     last_show_cursor: bool,
     current_show_cursor: bool,
+    hotkey_rx: tokio::sync::broadcast::Receiver<HotkeyAction>,
+    app_handle: AppHandle,
+    hidden_for_pip: bool,
+    cursor_texture: Option<(Arc<EndPointCursorShape>, TextureHandle)>,
+    // credentials for the login screen of remote, None while the form is closed
+    login_form: Option<LoginForm>,
 }
 
 impl DesktopWindow {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
+        app_handle: AppHandle,
         endpoint_id: EndPointID,
         gl_context: Arc<Context>,
         client: Arc<EndPointClient>,
@@ -68,6 +113,28 @@ impl DesktopWindow {
             }
         });
 
+        let magnifier_render = Arc::new(RwLock::new(
+            Render::new(gl_context.as_ref()).expect("create magnifier render failed"),
+        ));
+
+        let magnifier_render_clone = magnifier_render.clone();
+        let magnifier_slot = state.magnifier_slot();
+
+        let magnifier_cb = CallbackFn::new(move |_info, painter| {
+            let mut render = magnifier_render_clone.write().unwrap();
+            let magnified_frame = magnifier_slot.lock().unwrap();
+
+            if let Some(magnified_frame) = magnified_frame.as_ref() {
+                if let Err(err) = render.paint(
+                    painter.gl(),
+                    &magnified_frame.frame,
+                    painter.intermediate_fbo(),
+                ) {
+                    tracing::error!(?err, "magnifier render failed");
+                }
+            }
+        });
+
         Self {
             state,
             icon_maximize: RetainedImage::from_color_image(
@@ -80,8 +147,15 @@ impl DesktopWindow {
             ),
             render: desktop_render,
             render_call_back: Arc::new(cb),
+            magnifier_render,
+            magnifier_render_call_back: Arc::new(magnifier_cb),
             last_show_cursor: true,
             current_show_cursor: true,
+            hotkey_rx: hotkey::subscribe(),
+            app_handle,
+            hidden_for_pip: false,
+            cursor_texture: None,
+            login_form: None,
         }
     }
 
@@ -135,7 +209,7 @@ impl DesktopWindow {
     }
 
     fn build_desktop_texture(&mut self, ui: &mut Ui) {
-        let (frame_width, frame_height) = self.state.update_desktop_frame();
+        let (frame_width, frame_height) = self.state.desktop_frame_size();
 
         if frame_width > 0 && frame_height > 0 {
             // when client area bigger than original desktop frame, disable scale button
@@ -144,12 +218,30 @@ impl DesktopWindow {
                     || ui.available_height() < frame_height as _,
             );
 
-            if self.state.desktop_frame_scaled()
-                && (ui.available_width() < frame_width as _
-                    || ui.available_height() < frame_height as _)
-            {
-                let left = ((ui.available_width() - frame_width as f32) / 2.0).max(0.0);
-                let top = ((ui.available_height() - frame_height as f32) / 2.0).max(0.0);
+            if self.state.magnifier_mut().is_some() {
+                self.build_magnified_desktop(ui, frame_width, frame_height);
+                return;
+            }
+
+            let scroll_scale_ratio = match self.state.scale_mode() {
+                ScaleMode::Fit => None,
+                ScaleMode::Original => {
+                    if ui.available_width() < frame_width as _
+                        || ui.available_height() < frame_height as _
+                    {
+                        Some(1.0)
+                    } else {
+                        None
+                    }
+                }
+                ScaleMode::FitWidth => Some(ui.available_width() / frame_width as f32),
+            };
+
+            if let Some(scale_ratio) = scroll_scale_ratio {
+                let display_width = frame_width as f32 * scale_ratio;
+                let display_height = frame_height as f32 * scale_ratio;
+                let left = ((ui.available_width() - display_width) / 2.0).max(0.0);
+                let top = ((ui.available_height() - display_height) / 2.0).max(0.0);
 
                 let mut available_rect = ui.available_rect_before_wrap();
                 available_rect.min = Pos2::new(left, top);
@@ -158,23 +250,29 @@ impl DesktopWindow {
                     tauri_egui::egui::ScrollArea::both()
                         .auto_shrink([false; 2])
                         .show_viewport(ui, |ui, view_port| {
-                            ui.set_width(frame_width as f32);
-                            ui.set_height(frame_height as f32);
+                            ui.set_width(display_width);
+                            ui.set_height(display_height);
+
+                            let desktop_rect = ui.available_rect_before_wrap();
 
                             let callback = tauri_egui::egui::PaintCallback {
-                                rect: ui.available_rect_before_wrap(),
+                                rect: desktop_rect,
                                 callback: self.render_call_back.clone(),
                             };
 
                             ui.painter().add(callback);
 
+                            self.paint_remote_cursor(ui, desktop_rect.min, scale_ratio);
+
                             let input = ui.ctx().input();
                             let events = input.events.as_slice();
                             let left_top = view_port.left_top();
 
                             self.current_show_cursor = !input.pointer.has_pointer();
 
-                            self.emit_input(events, move |pos| Some(pos + left_top.to_vec2()));
+                            self.emit_input(events, move |pos| {
+                                Some(((pos + left_top.to_vec2()).to_vec2() / scale_ratio).to_pos2())
+                            });
                         });
                 });
             } else {
@@ -205,6 +303,8 @@ impl DesktopWindow {
 
                 ui.painter().add(callback);
 
+                self.paint_remote_cursor(ui, space_around_image.to_pos2(), scale_ratio);
+
                 let input = ui.ctx().input();
                 let events = input.events.as_slice();
                 if let Some(pos) = input.pointer.hover_pos() {
@@ -245,9 +345,74 @@ impl DesktopWindow {
         }
     }
 
+    fn build_magnified_desktop(&mut self, ui: &mut Ui, frame_width: i32, frame_height: i32) {
+        let view = ui.available_rect_before_wrap();
+        let response = ui.allocate_rect(view, Sense::drag());
+
+        let Some(magnifier) = self.state.magnifier_mut() else {
+            return;
+        };
+
+        let (scale_ratio, origin) = magnifier.layout(view, frame_width, frame_height);
+
+        if response.dragged() {
+            magnifier.pan(response.drag_delta(), scale_ratio);
+        }
+
+        let input = ui.ctx().input();
+        if response.hovered() && input.scroll_delta.y != 0.0 {
+            magnifier.set_zoom(magnifier.zoom() * (1.0 + input.scroll_delta.y / 500.0));
+        }
+
+        let region = magnifier.take_region_request(view, scale_ratio, origin);
+
+        // pointer pans and zooms, only keys reach remote
+        let key_events: Vec<_> = input
+            .events
+            .iter()
+            .filter(|event| matches!(event, tauri_egui::egui::Event::RawKeyInput { .. }))
+            .cloned()
+            .collect();
+        drop(input);
+
+        if region.is_some() {
+            self.state.switch_magnifier_region(region);
+        }
+
+        ui.painter().add(tauri_egui::egui::PaintCallback {
+            rect: Rect::from_min_size(
+                origin,
+                Vec2::new(frame_width as f32, frame_height as f32) * scale_ratio,
+            ),
+            callback: self.render_call_back.clone(),
+        });
+
+        let magnified_region = self
+            .state
+            .magnifier_slot()
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|magnified_frame| magnified_frame.region);
+
+        if let Some(region) = magnified_region {
+            ui.painter().add(tauri_egui::egui::PaintCallback {
+                rect: Rect::from_min_size(
+                    origin + Vec2::new(region.x as f32, region.y as f32) * scale_ratio,
+                    Vec2::new(region.width as f32, region.height as f32) * scale_ratio,
+                ),
+                callback: self.magnifier_render_call_back.clone(),
+            });
+        }
+
+        self.paint_remote_cursor(ui, origin, scale_ratio);
+
+        self.emit_input(&key_events, |_| None);
+    }
+
     fn build_toolbar(&mut self, ui: &mut Ui) {
         // put the toolbar at central top
-        let (mut rect, _) = ui.allocate_at_least(Vec2::new(220.0, 35.0), Sense::click());
+        let (mut rect, _) = ui.allocate_at_least(Vec2::new(300.0, 35.0), Sense::click());
         rect.set_center(Pos2::new(ui.max_rect().width() / 2.0, 50.0));
 
         ui.allocate_ui_at_rect(rect, |ui| {
@@ -271,8 +436,32 @@ impl DesktopWindow {
 
                         self.build_toolbar_button_scale(ui);
 
+                        self.build_toolbar_menu_view(ui);
+
+                        self.build_toolbar_button_request_control(ui);
+
+                        self.build_toolbar_label_input_blocked(ui);
+
+                        self.build_toolbar_label_capture_interrupted(ui);
+
+                        self.build_toolbar_label_audio_source(ui);
+
+                        self.build_toolbar_label_keyboard_layout(ui);
+
+                        self.build_toolbar_label_watermark(ui);
+
+                        self.build_toolbar_menu_audio(ui);
+
+                        self.build_toolbar_menu_volume(ui);
+
+                        self.build_toolbar_menu_processes(ui);
+
+                        self.build_toolbar_menu_power(ui);
+
                         ui.separator();
 
+                        self.build_toolbar_label_latency(ui);
+
                         // FPS
 
                         ui.label(
@@ -288,7 +477,7 @@ impl DesktopWindow {
         // when use_original_resolution is true, the button should display 'fit size' icon
         ui.add_enabled_ui(self.state.desktop_frame_scalable(), |ui| {
             // ui.visuals_mut().widgets.active.fg_stroke = Stroke::new(1.0, Color32::WHITE);
-            let button = if self.state.desktop_frame_scaled() {
+            let button = if self.state.scale_mode() != ScaleMode::Fit {
                 tauri_egui::egui::ImageButton::new(
                     self.icon_scale.texture_id(ui.ctx()),
                     Vec2::new(18.0, 18.0),
@@ -302,19 +491,586 @@ impl DesktopWindow {
             .tint(ui.visuals().noninteractive().fg_stroke.color);
 
             if ui.add(button).clicked() {
+                if self.state.scale_mode() == ScaleMode::Fit {
+                    self.state.set_scale_mode(ScaleMode::Original);
+                } else {
+                    self.state.set_scale_mode(ScaleMode::Fit);
+                }
+            }
+        });
+    }
+
+    fn build_toolbar_button_request_control(&mut self, ui: &mut Ui) {
+        // the session is view-only until remote user grants input permission
+        if self.state.input_permitted() {
+            return;
+        }
+
+        ui.add_enabled_ui(!self.state.elevation_pending(), |ui| {
+            if ui.button("Request Control").clicked() {
+                self.state.request_input_permission();
+            }
+        });
+    }
+
+    fn build_toolbar_label_input_blocked(&mut self, ui: &mut Ui) {
+        let Some(reason) = self.state.input_block_reason() else {
+            return;
+        };
+
+        let hover_text = match reason {
+            InputBlockReason::NotElevated => {
+                "Remote device shows a secure desktop like UAC prompt, MirrorX on it isn't \
+                 running as administrator so it can't control the secure desktop"
+            }
+            InputBlockReason::NotService => {
+                "Remote device shows a secure desktop like UAC prompt, only MirrorX running \
+                 as service can control the secure desktop"
+            }
+        };
+
+        ui.label(RichText::new("Input Blocked").color(Color32::YELLOW))
+            .on_hover_text(hover_text);
+    }
+
+    fn build_toolbar_label_capture_interrupted(&mut self, ui: &mut Ui) {
+        let Some(interruption) = self.state.capture_interruption() else {
+            return;
+        };
+
+        let (text, hover_text) = match interruption {
+            CaptureInterruption::FallbackCapture => (
+                "Fallback Capture",
+                "A fullscreen application holds the monitor of remote device, its desktop is \
+                 captured in the slower way until the application exits fullscreen",
+            ),
+            CaptureInterruption::ContentProtected => (
+                "Protected Content",
+                "Remote device shows protected content like DRM video, it's blacked out of \
+                 the desktop by the system and can't be streamed",
+            ),
+        };
+
+        ui.label(RichText::new(text).color(Color32::YELLOW))
+            .on_hover_text(hover_text);
+    }
+
+    fn build_toolbar_label_audio_source(&mut self, ui: &mut Ui) {
+        // nothing is shown when remote sends its system audio as usual
+        let (text, hover_text) = match self.state.endpoint_client().audio_source() {
+            Some(AudioSource::Microphone) => (
+                "Mic Only",
+                "Remote device can't record its system audio, its microphone is sent instead",
+            ),
+            Some(AudioSource::Unavailable) => ("No Audio", "Remote device can't record any audio"),
+            _ => return,
+        };
+
+        ui.label(text).on_hover_text(hover_text);
+    }
+
+    fn build_toolbar_label_keyboard_layout(&mut self, ui: &mut Ui) {
+        let Some((layout, mismatch)) = self.state.remote_keyboard_layout() else {
+            return;
+        };
+
+        let mut hover_text = format!(
+            "Remote device types with the {} layout ({}), its locale is {}",
+            layout.language, layout.id, layout.locale
+        );
+
+        let mut text = RichText::new(&layout.language);
+        if mismatch {
+            hover_text.push_str(
+                "\nIt differs from the layout of this device, keys may type other characters \
+                 than the ones printed on them",
+            );
+            text = text.color(Color32::YELLOW);
+        }
+
+        ui.label(text).on_hover_text(hover_text);
+    }
+
+    fn build_toolbar_label_watermark(&mut self, ui: &mut Ui) {
+        if !self.state.endpoint_client().watermark_enabled() {
+            return;
+        }
+
+        ui.label("Watermarked").on_hover_text(
+            "Remote device stamps your device id and the time on its desktop, so what's \
+             captured of it can be traced back to this session",
+        );
+    }
+
+    fn build_toolbar_label_latency(&mut self, ui: &mut Ui) {
+        // shown once the clock of remote device is estimated
+        let Some(stats) = session_stats::session_stats(self.state.endpoint_id()) else {
+            return;
+        };
+
+        let Some(video_latency_ms) = stats.video_latency_ms else {
+            return;
+        };
+
+        let mut hover_text = format!("Video takes {video_latency_ms}ms from remote encoder");
+        if let Some(audio_latency_ms) = stats.audio_latency_ms {
+            hover_text.push_str(&format!(", audio takes {audio_latency_ms}ms"));
+        }
+        if let Some(audio_jitter_delay_ms) = stats.audio_jitter_delay_ms {
+            hover_text.push_str(&format!(
+                "\nAudio is buffered {audio_jitter_delay_ms}ms against jitter, {} frames concealed",
+                stats.audio_concealed_frames
+            ));
+        }
+        if let Some(clock_offset_ms) = stats.clock_offset_ms {
+            hover_text.push_str(&format!(
+                "\nClock of remote device is {clock_offset_ms:+}ms off, it's excluded"
+            ));
+        }
+
+        ui.label(RichText::new(format!("{video_latency_ms}ms")).font(FontId::monospace(16.0)))
+            .on_hover_text(hover_text);
+    }
+
+    fn build_toolbar_menu_audio(&mut self, ui: &mut Ui) {
+        // only shown when remote device can capture the audio of single application or
+        // records its microphone which may pick up echo
+        let client = self.state.endpoint_client();
+        let microphone = client.audio_source() == Some(AudioSource::Microphone);
+        if client.audio_applications().is_empty() && !microphone {
+            return;
+        }
+
+        ui.menu_button("Audio", |ui| {
+            if microphone {
+                let mut enabled = client.audio_processing_enabled();
+                if ui
+                    .checkbox(&mut enabled, "Echo Cancellation")
+                    .on_hover_text("Cancel echo and suppress noise of remote microphone")
+                    .changed()
+                {
+                    self.state.switch_audio_processing(enabled);
+                    ui.close_menu();
+                }
+            }
+
+            if client.audio_applications().is_empty() {
+                return;
+            }
+
+            let current_target = client.blocking_audio_capture_target();
+            let mut target = current_target;
+            ui.radio_value(&mut target, AudioCaptureTarget::System, "All Applications");
+
+            ui.separator();
+
+            for application in client.audio_applications().iter() {
+                ui.radio_value(
+                    &mut target,
+                    AudioCaptureTarget::Process(application.process_id),
+                    &application.name,
+                );
+            }
+
+            if target != current_target {
+                self.state.switch_audio_capture_target(target);
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn build_toolbar_menu_volume(&mut self, ui: &mut Ui) {
+        ui.add_enabled_ui(self.state.input_permitted(), |ui| {
+            let response = ui.menu_button("Volume", |ui| {
+                let Some(volume) = self.state.remote_volume() else {
+                    ui.spinner();
+                    return;
+                };
+
+                let mut muted = volume.muted;
+                if ui.checkbox(&mut muted, "Mute").changed() {
+                    self.state.control_remote_volume(None, Some(muted));
+                }
+
+                // the volume is applied once, when the slider is released
+                let mut level = volume.level;
+                let response = ui.add_enabled(!muted, Slider::new(&mut level, 0..=100).suffix("%"));
+                if response.changed() {
+                    self.state.preview_remote_volume_level(level);
+                }
+                if response.drag_released() || (response.changed() && !response.dragged()) {
+                    self.state.control_remote_volume(Some(level), None);
+                }
+            });
+
+            // remote user may have changed it since, so it's refreshed on every open
+            if response.response.clicked() {
+                self.state.control_remote_volume(None, None);
+            }
+        });
+    }
+
+    fn build_toolbar_menu_processes(&mut self, ui: &mut Ui) {
+        let response = ui.menu_button("Processes", |ui| {
+            // admin tools are never granted with the session, remote user grants them
+            if !self.state.admin_tools_permitted() {
+                ui.add_enabled_ui(!self.state.elevation_pending(), |ui| {
+                    if ui.button("Request Admin Tools").clicked() {
+                        self.state.request_admin_tools_permission();
+                        ui.close_menu();
+                    }
+                });
+                return;
+            }
+
+            if ui.button("Refresh").clicked() {
+                self.state.refresh_remote_processes();
+            }
+
+            ui.separator();
+
+            let Some(processes) = self.state.remote_processes() else {
+                ui.spinner();
+                return;
+            };
+
+            ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                Grid::new("remote_processes").striped(true).show(ui, |ui| {
+                    ui.strong("Name");
+                    ui.strong("PID");
+                    ui.strong("CPU");
+                    ui.strong("Memory");
+                    ui.end_row();
+
+                    for process in processes {
+                        ui.label(&process.name);
+                        ui.label(process.pid.to_string());
+                        ui.label(format!("{:.1}%", process.cpu_usage));
+                        ui.label(format!("{:.1} MB", process.memory as f64 / 1024.0 / 1024.0));
+                        if ui.small_button("Kill").clicked() {
+                            self.state.kill_remote_process(process.pid);
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+
+        // processes come and go, so they're listed on every open
+        if response.response.clicked() && self.state.admin_tools_permitted() {
+            self.state.refresh_remote_processes();
+        }
+    }
+
+    fn build_toolbar_menu_power(&mut self, ui: &mut Ui) {
+        if self.state.reconnecting() {
+            ui.spinner();
+            ui.label("Reconnecting")
+                .on_hover_text("Waiting for remote device to be back online");
+            return;
+        }
+
+        ui.add_enabled_ui(self.state.input_permitted(), |ui| {
+            ui.menu_button("Power", |ui| {
+                if ui.button("Wake Display").clicked() {
+                    self.state.wake_remote_display();
+                    ui.close_menu();
+                }
+
+                if ui.button("Reboot and Reconnect").clicked() {
+                    self.state.reboot_remote();
+                    ui.close_menu();
+                }
+
+                ui.separator();
+
+                // signing in hands over the whole machine, so it takes admin tools too
+                ui.add_enabled_ui(self.state.admin_tools_permitted(), |ui| {
+                    if ui
+                        .button("Sign In...")
+                        .on_disabled_hover_text("Signing in requires admin tools permission")
+                        .clicked()
+                    {
+                        self.login_form = Some(LoginForm::default());
+                        ui.close_menu();
+                    }
+                });
+            });
+        });
+    }
+
+    fn build_login_form(&mut self, ctx: &tauri_egui::egui::Context) {
+        let Some(form) = self.login_form.as_mut() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut submitted = false;
+
+        Window::new("Sign In")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("Sign in at the login screen of remote device");
+
+                Grid::new("login_form").num_columns(2).show(ui, |ui| {
+                    ui.label("User Name");
+                    ui.text_edit_singleline(&mut form.username);
+                    ui.end_row();
+
+                    ui.label("Domain");
+                    ui.add(TextEdit::singleline(&mut form.domain).hint_text("Local account"));
+                    ui.end_row();
+
+                    ui.label("Password");
+                    ui.add(TextEdit::singleline(&mut form.password).password(true));
+                    ui.end_row();
+                });
+
+                ui.add_enabled_ui(!form.username.is_empty(), |ui| {
+                    submitted = ui.button("Sign In").clicked();
+                });
+            });
+
+        if submitted {
+            if let Some(form) = self.login_form.take() {
+                let domain = (!form.domain.is_empty()).then_some(form.domain);
                 self.state
-                    .set_desktop_frame_scaled(!self.state.desktop_frame_scaled());
+                    .login_remote(form.username, domain, form.password);
+            }
+        } else if !open {
+            self.login_form = None;
+        }
+    }
+
+    fn build_toolbar_menu_view(&mut self, ui: &mut Ui) {
+        ui.menu_button("View", |ui| {
+            let mut scale_mode = self.state.scale_mode();
+            ui.radio_value(&mut scale_mode, ScaleMode::Fit, "Fit Window");
+            ui.radio_value(&mut scale_mode, ScaleMode::Original, "Original Size");
+            ui.radio_value(&mut scale_mode, ScaleMode::FitWidth, "Fit Width");
+            if scale_mode != self.state.scale_mode() {
+                self.state.set_scale_mode(scale_mode);
+                ui.close_menu();
+            }
+
+            let mut magnifier_enabled = self.state.magnifier_mut().is_some();
+            if ui
+                .checkbox(&mut magnifier_enabled, "Magnifier")
+                .on_hover_text(
+                    "Drag to pan and scroll to zoom, the region in view is streamed at native \
+                     resolution to keep small text readable",
+                )
+                .changed()
+            {
+                self.state.set_magnifier_enabled(magnifier_enabled);
+                ui.close_menu();
+            }
+
+            if let Some(magnifier) = self.state.magnifier_mut() {
+                let mut zoom = magnifier.zoom();
+                if ui
+                    .add(Slider::new(&mut zoom, MIN_ZOOM..=MAX_ZOOM).text("Zoom"))
+                    .changed()
+                {
+                    magnifier.set_zoom(zoom);
+                }
+            }
+
+            ui.separator();
+
+            let mut spanned = self.state.remote_monitors_spanned();
+            if ui.checkbox(&mut spanned, "Span All Monitors").changed() {
+                if spanned {
+                    self.state
+                        .switch_remote_monitor(Some(SPAN_MONITOR_ID.to_string()));
+                } else {
+                    self.state.switch_remote_monitor(None);
+                }
+                ui.close_menu();
+            }
+
+            if ui.button("Next Monitor").clicked() {
+                self.state.switch_remote_monitor(None);
+                ui.close_menu();
+            }
+
+            if !spanned {
+                ui.add_enabled_ui(self.state.input_permitted(), |ui| {
+                    ui.menu_button("Resolution", |ui| {
+                        if ui
+                            .button("Original")
+                            .on_hover_text("Restore the resolution which remote monitor had")
+                            .clicked()
+                        {
+                            self.state.switch_remote_resolution(None);
+                            ui.close_menu();
+                        }
+
+                        ui.separator();
+
+                        for (width, height) in REMOTE_RESOLUTIONS {
+                            if ui.button(format!("{width}x{height}")).clicked() {
+                                self.state.switch_remote_resolution(Some((*width, *height)));
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                });
+            }
+
+            ui.separator();
+
+            let client = self.state.endpoint_client();
+            let current_quality = client.stream_quality();
+            let mut quality = current_quality;
+            ui.radio_value(&mut quality, StreamQuality::Full, "Full Color");
+            ui.radio_value(&mut quality, StreamQuality::ReducedChroma, "Reduced Color")
+                .on_hover_text("Blur colors and lower frame rate for links slower than 1Mbps");
+            ui.radio_value(&mut quality, StreamQuality::Grayscale, "Grayscale")
+                .on_hover_text("Drop colors and lower frame rate for links slower than 1Mbps");
+            if quality != current_quality {
+                self.state.switch_stream_quality(quality);
+                ui.close_menu();
+            }
+
+            let mut host_priority = client.host_priority();
+            if ui
+                .checkbox(&mut host_priority, "Host Priority")
+                .on_hover_text(
+                    "Keep remote desktop responsive for its user, streaming runs at the lowest \
+                     priority and a lower frame rate",
+                )
+                .changed()
+            {
+                self.state.switch_host_priority(host_priority);
+                ui.close_menu();
+            }
+
+            let mut local_input_blocked = client.remote_local_input_blocked();
+            if ui
+                .add_enabled(
+                    self.state.input_permitted(),
+                    Checkbox::new(&mut local_input_blocked, "Block Remote Input"),
+                )
+                .on_hover_text(
+                    "Keep remote user's mouse and keyboard off the desktop once they confirm, \
+                     they can restore them any time with Ctrl+Alt+Esc",
+                )
+                .changed()
+            {
+                self.state
+                    .switch_remote_local_input_block(local_input_blocked);
+                ui.close_menu();
+            }
+
+            ui.separator();
+
+            let mut interpolation_enabled = self.state.interpolation_enabled();
+            if ui
+                .checkbox(&mut interpolation_enabled, "Smooth Motion")
+                .on_hover_text(
+                    "Blend frames to double the frame rate when remote desktop is streamed \
+                     slowly, it uses more CPU and adds a little latency",
+                )
+                .changed()
+            {
+                self.state.set_interpolation_enabled(interpolation_enabled);
+                ui.close_menu();
+            }
+
+            ui.separator();
+
+            if ui.button("Picture in Picture").clicked() {
+                self.open_pip_window(ui.ctx().clone());
+                ui.close_menu();
+            }
+
+            if self.state.fullscreen() {
+                if ui.button("Exit Fullscreen").clicked() {
+                    self.state.request_fullscreen(FullscreenRequest::Exit);
+                    ui.close_menu();
+                }
+            } else {
+                let mut request = None;
+                for monitor in self.state.local_monitors() {
+                    if ui
+                        .button(format!("Fullscreen on {}", monitor.name))
+                        .clicked()
+                    {
+                        request = Some(FullscreenRequest::Enter(Some(monitor.clone())));
+                    }
+                }
+
+                if let Some(request) = request {
+                    self.state.request_fullscreen(request);
+                    ui.close_menu();
+                }
             }
         });
     }
 }
 
 impl DesktopWindow {
+    /// Draw the cursor which is sent separately by remote, `origin` is the position of
+    /// desktop frame's left top corner.
+    fn paint_remote_cursor(&mut self, ui: &Ui, origin: Pos2, scale_ratio: f32) {
+        let Some(cursor) = self.state.endpoint_client().blocking_cursor() else {
+            return;
+        };
+
+        let (true, Some(shape)) = (cursor.visible, cursor.shape) else {
+            return;
+        };
+
+        let texture = match self.cursor_texture {
+            Some((ref cached_shape, ref texture)) if Arc::ptr_eq(cached_shape, &shape) => {
+                texture.clone()
+            }
+            _ => {
+                let texture = ui.ctx().load_texture(
+                    "remote_cursor",
+                    ColorImage::from_rgba_unmultiplied(
+                        [shape.width as usize, shape.height as usize],
+                        &shape.rgba,
+                    ),
+                    TextureFilter::Linear,
+                );
+
+                self.cursor_texture = Some((shape.clone(), texture.clone()));
+                texture
+            }
+        };
+
+        let left_top = origin
+            + Vec2::new(
+                (cursor.x - shape.hotspot_x) as f32,
+                (cursor.y - shape.hotspot_y) as f32,
+            ) * scale_ratio;
+
+        ui.painter().image(
+            texture.id(),
+            Rect::from_min_size(
+                left_top,
+                Vec2::new(shape.width as f32, shape.height as f32) * scale_ratio,
+            ),
+            Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+            Color32::WHITE,
+        );
+    }
+
     fn emit_input(
         &mut self,
         events: &[tauri_egui::egui::Event],
         pos_calc_fn: impl Fn(Pos2) -> Option<Pos2>,
     ) {
+        // typing credentials into the login form never reaches remote
+        if self.login_form.is_some() {
+            return;
+        }
+
         let mut input_commands = Vec::new();
         for event in events.iter() {
             match event {
@@ -361,6 +1117,10 @@ impl DesktopWindow {
                 tauri_egui::egui::Event::RawKeyInput { key, pressed } => {
                     tracing::info!(?key, "raw key");
 
+                    if !self.state.keyboard_grabbed() {
+                        continue;
+                    }
+
                     let keyboard_event = if *pressed {
                         KeyboardEvent::KeyDown(*key)
                     } else {
@@ -373,10 +1133,13 @@ impl DesktopWindow {
             }
         }
 
-        if input_commands.is_empty() {
+        // remote drops input commands of view-only session
+        if input_commands.is_empty() || !self.state.input_permitted() {
             return;
         }
 
+        hotkey::set_active_desktop(*self.state.endpoint_id());
+
         if let Err(err) = self
             .state
             .endpoint_client()
@@ -389,10 +1152,149 @@ impl DesktopWindow {
     }
 }
 
+impl DesktopWindow {
+    fn handle_hotkey_actions(&mut self, frame: &mut tauri_egui::eframe::Frame) {
+        loop {
+            let action = match self.hotkey_rx.try_recv() {
+                Ok(action) => action,
+                Err(tokio::sync::broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => return,
+            };
+
+            if !hotkey::is_active_desktop(self.state.endpoint_id()) {
+                continue;
+            }
+
+            match action {
+                HotkeyAction::ToggleFullscreen => {
+                    if self.state.fullscreen() {
+                        self.state.request_fullscreen(FullscreenRequest::Exit);
+                    } else {
+                        self.state
+                            .request_fullscreen(FullscreenRequest::Enter(None));
+                    }
+                }
+                HotkeyAction::ReleaseKeyboardGrab => {
+                    self.state
+                        .set_keyboard_grabbed(!self.state.keyboard_grabbed());
+                }
+                HotkeyAction::DisconnectSession => frame.close(),
+                HotkeyAction::SwitchMonitor => self.state.switch_remote_monitor(None),
+            }
+        }
+    }
+}
+
+impl DesktopWindow {
+    fn open_pip_window(&mut self, desktop_ctx: tauri_egui::egui::Context) {
+        let pip_active = self.state.pip_active();
+        if pip_active.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let app_handle = self.app_handle.clone();
+        let frame_slot = self.state.frame_slot();
+        let window_label = format!(
+            "PictureInPicture:{}",
+            self.state.format_remote_device_id().replace('.', "_")
+        );
+        let window_title = format!("MirrorX {}", self.state.format_remote_device_id());
+
+        // create window outside of the event loop thread
+        tauri::async_runtime::spawn(async move {
+            let egui_plugin = app_handle.state::<tauri_egui::EguiPluginHandle>();
+            let pip_active_clone = pip_active.clone();
+            let desktop_ctx_clone = desktop_ctx.clone();
+
+            if let Err(err) = egui_plugin.create_window(
+                window_label,
+                Box::new(move |cc| {
+                    if let Some(gl_context) = cc.gl.as_ref() {
+                        Box::new(PipWindow::new(
+                            gl_context.clone(),
+                            frame_slot,
+                            pip_active_clone,
+                            desktop_ctx_clone,
+                        ))
+                    } else {
+                        panic!("get gl context failed");
+                    }
+                }),
+                window_title,
+                tauri_egui::eframe::NativeOptions {
+                    always_on_top: true,
+                    decorated: false,
+                    initial_window_size: Some(Vec2::new(320.0, 180.0)),
+                    min_window_size: Some(Vec2::new(160.0, 90.0)),
+                    ..Default::default()
+                },
+            ) {
+                tracing::error!(?err, "create pip window failed");
+                pip_active.store(false, Ordering::SeqCst);
+                desktop_ctx.request_repaint();
+            }
+        });
+    }
+
+    // hide desktop window while picture-in-picture window shows, and show it again
+    // when picture-in-picture window is restored or closed
+    fn handle_dropped_files(&mut self, ctx: &tauri_egui::egui::Context) {
+        let paths: Vec<PathBuf> = ctx
+            .input()
+            .raw
+            .dropped_files
+            .iter()
+            .filter_map(|file| file.path.clone())
+            .filter(|path| path.is_file())
+            .collect();
+
+        if !paths.is_empty() {
+            self.state.stage_files(paths);
+        }
+    }
+
+    fn handle_pip_visibility(&mut self, frame: &mut tauri_egui::eframe::Frame) {
+        let pip_active = self.state.pip_active().load(Ordering::SeqCst);
+
+        if pip_active != self.hidden_for_pip {
+            frame.set_visible(!pip_active);
+            self.hidden_for_pip = pip_active;
+        }
+    }
+
+    fn handle_fullscreen_request(&mut self, frame: &mut tauri_egui::eframe::Frame) {
+        let Some(request) = self.state.take_fullscreen_request() else {
+            return;
+        };
+
+        match request {
+            FullscreenRequest::Enter(monitor) => {
+                // move window to the chosen monitor first, fullscreen always takes
+                // the monitor where the window located
+                if let Some(monitor) = monitor {
+                    frame.set_window_pos(Pos2::new(monitor.left as f32, monitor.top as f32));
+                }
+
+                frame.set_fullscreen(true);
+                self.state.set_fullscreen(true);
+            }
+            FullscreenRequest::Exit => {
+                frame.set_fullscreen(false);
+                self.state.set_fullscreen(false);
+            }
+        }
+    }
+}
+
 impl tauri_egui::eframe::App for DesktopWindow {
-    fn update(&mut self, ctx: &tauri_egui::egui::Context, _: &mut tauri_egui::eframe::Frame) {
+    fn update(&mut self, ctx: &tauri_egui::egui::Context, frame: &mut tauri_egui::eframe::Frame) {
         let update_instant = std::time::Instant::now();
 
+        self.handle_hotkey_actions(frame);
+        self.handle_fullscreen_request(frame);
+        self.handle_pip_visibility(frame);
+        self.handle_dropped_files(ctx);
+
         self.current_show_cursor = true;
 
         CentralPanel::default()
@@ -401,6 +1303,8 @@ impl tauri_egui::eframe::App for DesktopWindow {
                 self.build_panel(ui);
             });
 
+        self.build_login_form(ctx);
+
         if self.current_show_cursor != self.last_show_cursor {
             mirrorx_core::api::system::set_show_cursor(self.current_show_cursor);
             self.last_show_cursor = self.current_show_cursor;
@@ -416,8 +1320,17 @@ impl tauri_egui::eframe::App for DesktopWindow {
     }
 
     fn on_exit(&mut self, gl: Option<&glow::Context>) {
+        hotkey::clear_active_desktop(self.state.endpoint_id());
+        self.state.endpoint_client().cancel_reconnect();
+        // file manager window of the remote device may still use the connection
+        self.state
+            .endpoint_client()
+            .close_channel(EndPointChannel::Desktop);
+        self.state.pip_active().store(false, Ordering::SeqCst);
+
         if let Some(gl) = gl {
             self.render.write().unwrap().destroy(gl);
+            self.magnifier_render.write().unwrap().destroy(gl);
         }
     }
 }
@@ -0,0 +1,114 @@
+//! Render clock of streaming sessions. Decoded frames are held for a short buffer and
+//! presented at the pace which they arrive at on average, so the jitter of network and
+//! decoding doesn't show as stutter. Presenting speeds up a little when frames pile up in
+//! the buffer and slows down when it runs low, it starts buffering again once it runs dry.
+
+use mirrorx_core::DesktopDecodeFrame;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::Receiver;
+
+// latency which streaming sessions trade for smoothness
+const RENDER_BUFFER: Duration = Duration::from_millis(150);
+
+// the pace until the intervals of frames are measured
+const DEFAULT_FRAME_INTERVAL: Duration = Duration::from_micros(16_667);
+
+// remote desktop idles rather than streams at such a low frame rate
+const MAX_FRAME_INTERVAL: Duration = Duration::from_millis(500);
+
+// the oldest frames are dropped beyond it
+const MAX_BUFFERED_FRAMES: usize = 120;
+
+pub struct FramePacer {
+    frames: VecDeque<(DesktopDecodeFrame, Instant)>,
+    interval: Duration,
+    last_arrival: Option<Instant>,
+    next_present: Option<Instant>,
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self {
+            frames: VecDeque::new(),
+            interval: DEFAULT_FRAME_INTERVAL,
+            last_arrival: None,
+            next_present: None,
+        }
+    }
+}
+
+impl FramePacer {
+    pub fn push(&mut self, frame: DesktopDecodeFrame, now: Instant) {
+        if let Some(last_arrival) = self.last_arrival.replace(now) {
+            let interval = now.duration_since(last_arrival);
+            if interval <= MAX_FRAME_INTERVAL {
+                self.interval = (self.interval * 7 + interval) / 8;
+            }
+        }
+
+        // the frame missed its turn, buffer again from it
+        if self.frames.is_empty() && self.next_present.map_or(false, |next| next < now) {
+            self.next_present = None;
+        }
+
+        self.frames.push_back((frame, now));
+        if self.frames.len() > MAX_BUFFERED_FRAMES {
+            self.frames.pop_front();
+        }
+    }
+
+    /// When the next frame is due, None when no frame is buffered.
+    pub fn deadline(&self) -> Option<Instant> {
+        let (_, arrived_at) = self.frames.front()?;
+        Some(self.next_present.unwrap_or(*arrived_at + RENDER_BUFFER))
+    }
+
+    /// Take the frame which is due and schedule the next one.
+    pub fn pop(&mut self, now: Instant) -> Option<DesktopDecodeFrame> {
+        let (frame, _) = self.frames.pop_front()?;
+
+        let buffered = self.interval * self.frames.len() as u32;
+        let interval = if buffered > RENDER_BUFFER * 2 {
+            self.interval * 7 / 8
+        } else if buffered < RENDER_BUFFER / 2 {
+            self.interval * 9 / 8
+        } else {
+            self.interval
+        };
+
+        self.next_present = Some(now + interval);
+
+        Some(frame)
+    }
+}
+
+/// Present the frames of `render_frame_rx` into `frame_slot` at a steady pace until the
+/// session closes.
+pub async fn present_paced(
+    mut render_frame_rx: Receiver<DesktopDecodeFrame>,
+    frame_slot: Arc<Mutex<DesktopDecodeFrame>>,
+) {
+    let mut pacer = FramePacer::default();
+
+    loop {
+        let deadline = pacer.deadline();
+
+        tokio::select! {
+            frame = render_frame_rx.recv() => match frame {
+                Some(frame) => pacer.push(frame, Instant::now()),
+                None => return,
+            },
+            _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now).into()),
+                if deadline.is_some() =>
+            {
+                if let Some(frame) = pacer.pop(Instant::now()) {
+                    *frame_slot.lock().unwrap() = frame;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use super::render::Render;
+use mirrorx_core::DesktopDecodeFrame;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Duration,
+};
+use tauri_egui::{
+    eframe::{
+        egui_glow::CallbackFn,
+        glow::{self, Context},
+    },
+    egui::{Align2, CentralPanel, Color32, FontId, Rect, Sense, Vec2},
+};
+
+// picture-in-picture only needs a glance of remote desktop, so render it in low frame rate
+const PIP_REPAINT_INTERVAL: Duration = Duration::from_millis(66);
+
+pub struct PipWindow {
+    render: Arc<RwLock<Render>>,
+    render_call_back: Arc<CallbackFn>,
+    frame_slot: Arc<Mutex<DesktopDecodeFrame>>,
+    pip_active: Arc<AtomicBool>,
+    desktop_ctx: tauri_egui::egui::Context,
+}
+
+impl PipWindow {
+    pub fn new(
+        gl_context: Arc<Context>,
+        frame_slot: Arc<Mutex<DesktopDecodeFrame>>,
+        pip_active: Arc<AtomicBool>,
+        desktop_ctx: tauri_egui::egui::Context,
+    ) -> Self {
+        // every window has its own gl context, so the textures can't be shared with
+        // desktop window and an individual render is required
+        let pip_render = Arc::new(RwLock::new(
+            Render::new(gl_context.as_ref()).expect("create pip render failed"),
+        ));
+
+        let pip_render_clone = pip_render.clone();
+        let frame_slot_clone = frame_slot.clone();
+
+        let cb = CallbackFn::new(move |_info, painter| {
+            let mut render = pip_render_clone.write().unwrap();
+            let frame = frame_slot_clone.lock().unwrap();
+
+            if let Err(err) = render.paint(painter.gl(), &frame, painter.intermediate_fbo()) {
+                tracing::error!(?err, "pip render failed");
+            }
+        });
+
+        Self {
+            render: pip_render,
+            render_call_back: Arc::new(cb),
+            frame_slot,
+            pip_active,
+            desktop_ctx,
+        }
+    }
+
+    fn restore_desktop_window(&self, frame: &mut tauri_egui::eframe::Frame) {
+        self.pip_active.store(false, Ordering::SeqCst);
+        self.desktop_ctx.request_repaint();
+        frame.close();
+    }
+}
+
+impl tauri_egui::eframe::App for PipWindow {
+    fn update(&mut self, ctx: &tauri_egui::egui::Context, frame: &mut tauri_egui::eframe::Frame) {
+        // desktop window was closed or restored by others
+        if !self.pip_active.load(Ordering::SeqCst) {
+            frame.close();
+            return;
+        }
+
+        let (frame_width, frame_height) = {
+            let frame = self.frame_slot.lock().unwrap();
+            (frame.width, frame.height)
+        };
+
+        CentralPanel::default()
+            .frame(tauri_egui::egui::Frame::none().fill(Color32::BLACK))
+            .show(ctx, |ui| {
+                let available_rect = ui.available_rect_before_wrap();
+                let response = ui.allocate_rect(available_rect, Sense::click_and_drag());
+
+                if frame_width > 0 && frame_height > 0 {
+                    let aspect_ratio = (frame_width as f32) / (frame_height as f32);
+                    let size = if (available_rect.width() / aspect_ratio) < available_rect.height()
+                    {
+                        Vec2::new(
+                            available_rect.width(),
+                            available_rect.width() / aspect_ratio,
+                        )
+                    } else {
+                        Vec2::new(
+                            available_rect.height() * aspect_ratio,
+                            available_rect.height(),
+                        )
+                    };
+
+                    ui.painter().add(tauri_egui::egui::PaintCallback {
+                        rect: Rect::from_center_size(available_rect.center(), size),
+                        callback: self.render_call_back.clone(),
+                    });
+                }
+
+                if response.hovered() {
+                    ui.painter().text(
+                        available_rect.center_bottom() - Vec2::new(0.0, 8.0),
+                        Align2::CENTER_BOTTOM,
+                        "Click to restore",
+                        FontId::proportional(12.0),
+                        Color32::WHITE,
+                    );
+                }
+
+                if response.drag_started() {
+                    frame.drag_window();
+                } else if response.clicked() {
+                    self.restore_desktop_window(frame);
+                }
+            });
+
+        ctx.request_repaint_after(PIP_REPAINT_INTERVAL);
+    }
+
+    fn on_exit(&mut self, gl: Option<&glow::Context>) {
+        if let Some(gl) = gl {
+            self.render.write().unwrap().destroy(gl);
+        }
+
+        self.pip_active.store(false, Ordering::SeqCst);
+        self.desktop_ctx.request_repaint();
+    }
+}
@@ -1,4 +1,7 @@
-use mirrorx_core::{component::frame::DesktopDecodeFrameFormat, DesktopDecodeFrame};
+use mirrorx_core::{
+    component::frame::{ColorPrimaries, ColorRange, DesktopDecodeFrameFormat},
+    DesktopDecodeFrame,
+};
 use tauri_egui::eframe::{egui_glow::check_for_gl_error, glow::*};
 
 #[rustfmt::skip]
@@ -32,6 +35,7 @@ const VERTICES_INDICES_SLICE: &[u8] = unsafe {
 pub struct Render {
     program: Program,
     textures: Vec<NativeTexture>,
+    texture_size: (i32, i32),
     vao: NativeVertexArray,
     vbo: NativeBuffer,
     ebo: NativeBuffer,
@@ -67,6 +71,8 @@ impl Render {
             #version 330 core
 
             uniform int use_nv12;
+            uniform int full_range;
+            uniform int display_p3;
 
             uniform sampler2D nv12_textureY;
             uniform sampler2D nv12_textureUV;
@@ -78,33 +84,58 @@ impl Render {
             in vec2 texCoord;
             layout (location = 0) out vec4 fragColor;
 
-            // const mat3 YCbCrToRGBmatrix = mat3(
-            //     1.164, 0.000, 1.857,
-            //     1.164,-0.217,-0.543,
-            //     1.164, 2.016, 0.000
-            // );
-
+            // BT.709 YCbCr to RGB
             const mat3 YCbCrToRGBmatrix = mat3(
-                1.000,   0.000,   1.570,
-                1.000,  -0.187,  -0.467,
-                1.000,   1.856,   0.000
+                1.0000,  0.0000,  1.5748,
+                1.0000, -0.1873, -0.4681,
+                1.0000,  1.8556,  0.0000
+            );
+
+            // linear Display P3 to linear sRGB, both with D65 white point
+            const mat3 P3ToSRGBmatrix = mat3(
+                 1.2249, -0.2247,  0.0000,
+                -0.0420,  1.0419,  0.0000,
+                -0.0197, -0.0786,  1.0979
             );
 
+            vec3 srgb_to_linear(vec3 color)
+            {
+                return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), step(0.04045, color));
+            }
+
+            vec3 linear_to_srgb(vec3 color)
+            {
+                return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, color));
+            }
+
             void main(void)
             {
                 vec3 yuv;
                 vec3 rgb;
                 if (use_nv12 == 1) {
-                    yuv.x = texture(nv12_textureY, texCoord).r - 0.0625;
+                    yuv.x = texture(nv12_textureY, texCoord).r;
                     yuv.y = texture(nv12_textureUV, texCoord).r - 0.5;
                     yuv.z = texture(nv12_textureUV, texCoord).g - 0.5;
                 } else {
-                    yuv.x = texture(yuv420p_textureY, texCoord).r - 0.0625;
+                    yuv.x = texture(yuv420p_textureY, texCoord).r;
                     yuv.y = texture(yuv420p_textureU, texCoord).r - 0.5;
                     yuv.z = texture(yuv420p_textureV, texCoord).r - 0.5;
                 }
-                
-                rgb = yuv * YCbCrToRGBmatrix;
+
+                // expand limited range (Y in [16, 235], UV in [16, 240]) to full range
+                if (full_range == 0) {
+                    yuv.x = (yuv.x - 0.0625) * 1.164383;
+                    yuv.yz = yuv.yz * 1.138393;
+                }
+
+                rgb = clamp(yuv * YCbCrToRGBmatrix, 0.0, 1.0);
+
+                // Display P3 shares the transfer function with sRGB
+                if (display_p3 == 1) {
+                    rgb = srgb_to_linear(rgb) * P3ToSRGBmatrix;
+                    rgb = linear_to_srgb(clamp(rgb, 0.0, 1.0));
+                }
+
                 fragColor = vec4(rgb, 1.0);
             }"#;
 
@@ -230,6 +261,7 @@ impl Render {
             Ok(Self {
                 program,
                 textures: Vec::new(),
+                texture_size: (0, 0),
                 vao,
                 vbo,
                 ebo,
@@ -279,6 +311,16 @@ impl Render {
         }
 
         unsafe {
+            // frames change size with the monitor or the magnified region
+            if self.texture_size != (frame.width, frame.height) {
+                for texture in self.textures.drain(..) {
+                    gl.delete_texture(texture);
+                    check_for_gl_error!(gl);
+                }
+
+                self.texture_size = (frame.width, frame.height);
+            }
+
             if self.textures.is_empty() {
                 match frame.format {
                     DesktopDecodeFrameFormat::NV12 => {
@@ -320,8 +362,7 @@ impl Render {
             gl.use_program(Some(self.program));
             check_for_gl_error!(gl);
 
-            // disable srgb frame buffer since desktop frame has already adjust
-            // to Rec.709
+            // disable srgb frame buffer since shader outputs sRGB encoded color
             gl.disable(FRAMEBUFFER_SRGB);
             check_for_gl_error!(gl);
 
@@ -342,6 +383,24 @@ impl Render {
             gl.uniform_1_i32(use_nv12_uniform_location.as_ref(), use_nv12_value);
             check_for_gl_error!(gl);
 
+            let full_range_uniform_location = gl.get_uniform_location(self.program, "full_range");
+            check_for_gl_error!(gl);
+
+            gl.uniform_1_i32(
+                full_range_uniform_location.as_ref(),
+                (frame.color_space.range == ColorRange::Full) as i32,
+            );
+            check_for_gl_error!(gl);
+
+            let display_p3_uniform_location = gl.get_uniform_location(self.program, "display_p3");
+            check_for_gl_error!(gl);
+
+            gl.uniform_1_i32(
+                display_p3_uniform_location.as_ref(),
+                (frame.color_space.primaries == ColorPrimaries::DisplayP3) as i32,
+            );
+            check_for_gl_error!(gl);
+
             gl.bind_vertex_array(Some(self.vao));
             check_for_gl_error!(gl);
 
@@ -1,26 +1,104 @@
+use super::{
+    interpolation::{blend_frames, should_interpolate},
+    magnifier::Magnifier,
+    pacing::present_paced,
+};
 use crate::utility::format_device_id;
 use mirrorx_core::{
-    api::endpoint::{client::EndPointClient, id::EndPointID},
+    api::endpoint::{
+        client::EndPointClient,
+        handlers::magnifier_frame::MagnifiedFrame,
+        id::EndPointID,
+        message::{
+            AudioCaptureTarget, CaptureInterruption, EndPointBlockLocalInputReply,
+            EndPointBlockLocalInputRequest, EndPointCallRequest, EndPointLoginReply,
+            EndPointLoginRequest, EndPointMagnifyReply, EndPointMagnifyRequest, EndPointProcess,
+            EndPointProcessKillReply, EndPointProcessKillRequest, EndPointProcessListReply,
+            EndPointProcessListRequest, EndPointSendFileReply, EndPointSendFileRequest,
+            EndPointSwitchAudioCaptureReply, EndPointSwitchAudioCaptureRequest,
+            EndPointSwitchAudioProcessingReply, EndPointSwitchAudioProcessingRequest,
+            EndPointSwitchHostPriorityReply, EndPointSwitchHostPriorityRequest,
+            EndPointSwitchMonitorReply, EndPointSwitchMonitorRequest,
+            EndPointSwitchResolutionReply, EndPointSwitchResolutionRequest,
+            EndPointSwitchStreamQualityReply, EndPointSwitchStreamQualityRequest,
+            EndPointSystemVolumeReply, EndPointSystemVolumeRequest, InputBlockReason,
+            KeyboardLayout, MagnifierRegion, SessionProfile, StreamQuality,
+        },
+        permission::Permissions,
+    },
+    component::{
+        desktop::{
+            monitor::{get_active_monitors, Monitor},
+            span::SPAN_MONITOR_ID,
+        },
+        fs::transfer::send_file_to_remote,
+        input::layout::{current_keyboard_layout, layouts_match},
+    },
+    core_error,
+    error::CoreResult,
     DesktopDecodeFrame,
 };
-use std::sync::{Arc, Mutex};
-use tokio::sync::mpsc::Receiver;
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+// user may switch the layout of this device during the session
+const KEYBOARD_LAYOUT_QUERY_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleMode {
+    /// Scale the whole desktop frame into the window and keep aspect ratio.
+    Fit,
+    /// Display the desktop frame in original resolution, scrollable when it's bigger than window.
+    Original,
+    /// Scale the desktop frame to the window width, scrollable vertically.
+    FitWidth,
+}
+
+pub enum FullscreenRequest {
+    /// Enter fullscreen on the given local monitor, or the current one when it's None.
+    Enter(Option<Monitor>),
+    Exit,
+}
 
 pub struct State {
+    endpoint_id: EndPointID,
     format_remote_device_id: String,
     endpoint_client: Arc<EndPointClient>,
-    desktop_frame_scaled: bool,
+    scale_mode: ScaleMode,
     desktop_frame_scalable: bool,
-    render_rx: Receiver<DesktopDecodeFrame>,
     frame_slot: Arc<Mutex<DesktopDecodeFrame>>,
-    frame_size: (i32, i32),
+    keyboard_grabbed: bool,
+    fullscreen: bool,
+    fullscreen_request: Option<FullscreenRequest>,
+    local_monitors: Vec<Monitor>,
+    remote_monitors_spanned: Arc<AtomicBool>,
+    pip_active: Arc<AtomicBool>,
+    elevation_pending: Arc<AtomicBool>,
+    interpolation_enabled: Arc<AtomicBool>,
+    // volume of remote machine as of the last call, None until it's queried
+    remote_volume: Arc<Mutex<Option<EndPointSystemVolumeReply>>>,
+    // processes of remote machine as of the last listing, None while it's listed
+    remote_processes: Arc<Mutex<Option<Vec<EndPointProcess>>>>,
+    // client side zoom, None when the desktop is shown as a whole
+    magnifier: Option<Magnifier>,
+    // the latest region which remote streamed at native resolution
+    magnifier_slot: Arc<Mutex<Option<MagnifiedFrame>>>,
+    // layout of this device as of the last query, compared with the one of remote
+    local_keyboard_layout: Option<KeyboardLayout>,
+    local_keyboard_layout_queried_at: Instant,
 }
 
 impl State {
     pub fn new(
         endpoint_id: EndPointID,
         client: Arc<EndPointClient>,
-        render_frame_rx: tokio::sync::mpsc::Receiver<DesktopDecodeFrame>,
+        mut render_frame_rx: tokio::sync::mpsc::Receiver<DesktopDecodeFrame>,
         frame_slot: Arc<Mutex<DesktopDecodeFrame>>,
     ) -> Self {
         let format_remote_device_id = match endpoint_id {
@@ -33,17 +111,85 @@ impl State {
             } => remote.to_string(),
         };
 
+        let local_monitors = match get_active_monitors(false) {
+            Ok(monitors) => monitors,
+            Err(err) => {
+                tracing::error!(?err, "get local monitors failed");
+                Vec::new()
+            }
+        };
+
+        // keep the latest decoded frame in slot, so the desktop window and the
+        // picture-in-picture window can render the same frame stream
+        let frame_slot_clone = frame_slot.clone();
+        let interpolation_enabled = Arc::new(AtomicBool::new(false));
+        let interpolation_enabled_clone = interpolation_enabled.clone();
+        let session_profile = client.session_profile();
+        tauri::async_runtime::spawn(async move {
+            if session_profile == SessionProfile::Streaming {
+                present_paced(render_frame_rx, frame_slot_clone).await;
+                return;
+            }
+
+            let mut previous: Option<(DesktopDecodeFrame, Instant)> = None;
+
+            while let Some(mut frame) = render_frame_rx.recv().await {
+                // interactive sessions skip the frames which queued up while presenting
+                while let Ok(next_frame) = render_frame_rx.try_recv() {
+                    frame = next_frame;
+                }
+
+                let arrived_at = Instant::now();
+
+                if !interpolation_enabled_clone.load(Ordering::SeqCst) {
+                    previous = None;
+                    *frame_slot_clone.lock().unwrap() = frame;
+                    continue;
+                }
+
+                if let Some((previous_frame, previous_arrived_at)) = previous.take() {
+                    let interval = arrived_at.duration_since(previous_arrived_at);
+                    if should_interpolate(interval) {
+                        if let Some(blended_frame) = blend_frames(&previous_frame, &frame) {
+                            *frame_slot_clone.lock().unwrap() = blended_frame;
+                            tokio::time::sleep(interval / 2).await;
+                        }
+                    }
+                }
+
+                previous = Some((frame.clone(), arrived_at));
+                *frame_slot_clone.lock().unwrap() = frame;
+            }
+        });
+
         Self {
+            endpoint_id,
             format_remote_device_id,
             endpoint_client: client,
-            desktop_frame_scaled: true,
+            scale_mode: ScaleMode::Original,
             desktop_frame_scalable: true,
-            render_rx: render_frame_rx,
             frame_slot,
-            frame_size: (0, 0),
+            keyboard_grabbed: true,
+            fullscreen: false,
+            fullscreen_request: None,
+            local_monitors,
+            remote_monitors_spanned: Arc::new(AtomicBool::new(false)),
+            pip_active: Arc::new(AtomicBool::new(false)),
+            elevation_pending: Arc::new(AtomicBool::new(false)),
+            interpolation_enabled,
+            remote_volume: Arc::new(Mutex::new(None)),
+            remote_processes: Arc::new(Mutex::new(None)),
+            magnifier: None,
+            magnifier_slot: Arc::new(Mutex::new(None)),
+            local_keyboard_layout: current_keyboard_layout(),
+            local_keyboard_layout_queried_at: Instant::now(),
         }
     }
 
+    pub fn endpoint_id(&self) -> &EndPointID {
+        &self.endpoint_id
+    }
+
     pub fn format_remote_device_id(&self) -> &str {
         self.format_remote_device_id.as_ref()
     }
@@ -52,35 +198,517 @@ impl State {
         self.endpoint_client.clone()
     }
 
-    pub fn desktop_frame_scaled(&self) -> bool {
-        self.desktop_frame_scaled
-    }
+    /// Keyboard layout of remote device and whether keys pressed here type other
+    /// characters on it, None when remote doesn't tell its layout.
+    pub fn remote_keyboard_layout(&mut self) -> Option<(KeyboardLayout, bool)> {
+        let remote = self.endpoint_client.remote_keyboard_layout()?;
 
-    pub fn update_desktop_frame(&mut self) -> (i32, i32) {
-        let mut new_frame = None;
-        while let Ok(frame) = self.render_rx.try_recv() {
-            new_frame = Some(frame);
+        if self.local_keyboard_layout_queried_at.elapsed() >= KEYBOARD_LAYOUT_QUERY_INTERVAL {
+            self.local_keyboard_layout = current_keyboard_layout();
+            self.local_keyboard_layout_queried_at = Instant::now();
         }
 
-        if let Some(new_frame) = new_frame {
-            self.frame_size = (new_frame.width, new_frame.height);
-            (*self.frame_slot.lock().unwrap()) = new_frame;
-        }
+        // nothing to warn about when the local layout can't be told
+        let mismatch = self
+            .local_keyboard_layout
+            .as_ref()
+            .map_or(false, |local| !layouts_match(local, &remote));
+
+        Some((remote, mismatch))
+    }
+
+    pub fn scale_mode(&self) -> ScaleMode {
+        self.scale_mode
+    }
 
-        self.frame_size
+    pub fn desktop_frame_size(&self) -> (i32, i32) {
+        let frame = self.frame_slot.lock().unwrap();
+        (frame.width, frame.height)
+    }
+
+    pub fn frame_slot(&self) -> Arc<Mutex<DesktopDecodeFrame>> {
+        self.frame_slot.clone()
     }
 
     pub fn desktop_frame_scalable(&self) -> bool {
         self.desktop_frame_scalable
     }
+
+    pub fn keyboard_grabbed(&self) -> bool {
+        self.keyboard_grabbed
+    }
+
+    pub fn fullscreen(&self) -> bool {
+        self.fullscreen
+    }
+
+    pub fn local_monitors(&self) -> &[Monitor] {
+        &self.local_monitors
+    }
+
+    pub fn interpolation_enabled(&self) -> bool {
+        self.interpolation_enabled.load(Ordering::SeqCst)
+    }
+
+    pub fn set_interpolation_enabled(&self, enabled: bool) {
+        self.interpolation_enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    pub fn magnifier_mut(&mut self) -> Option<&mut Magnifier> {
+        self.magnifier.as_mut()
+    }
+
+    pub fn magnifier_slot(&self) -> Arc<Mutex<Option<MagnifiedFrame>>> {
+        self.magnifier_slot.clone()
+    }
+
+    /// Zoom into the center of remote desktop, or show it as a whole again.
+    pub fn set_magnifier_enabled(&mut self, enabled: bool) {
+        if enabled == self.magnifier.is_some() {
+            return;
+        }
+
+        if enabled {
+            let (frame_width, frame_height) = self.desktop_frame_size();
+            self.magnifier = Some(Magnifier::new(frame_width, frame_height));
+
+            let mut magnified_frame_rx = self.endpoint_client.serve_magnifier();
+            let magnifier_slot = self.magnifier_slot.clone();
+            tauri::async_runtime::spawn(async move {
+                while let Some(magnified_frame) = magnified_frame_rx.recv().await {
+                    *magnifier_slot.lock().unwrap() = Some(magnified_frame);
+                }
+            });
+        } else {
+            self.magnifier = None;
+            self.endpoint_client.stop_magnifier();
+            *self.magnifier_slot.lock().unwrap() = None;
+            self.switch_magnifier_region(None);
+        }
+    }
+
+    /// Ask remote to stream the region at native resolution, None stops it.
+    pub fn switch_magnifier_region(&self, region: Option<MagnifierRegion>) {
+        let client = self.endpoint_client.clone();
+
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = client
+                .call::<EndPointMagnifyReply>(EndPointCallRequest::MagnifyRequest(
+                    EndPointMagnifyRequest { region },
+                ))
+                .await
+            {
+                tracing::error!(?err, "switch magnifier region failed");
+            }
+        });
+    }
+
+    pub fn remote_monitors_spanned(&self) -> bool {
+        self.remote_monitors_spanned.load(Ordering::SeqCst)
+    }
+
+    pub fn pip_active(&self) -> Arc<AtomicBool> {
+        self.pip_active.clone()
+    }
+
+    pub fn input_permitted(&self) -> bool {
+        self.endpoint_client
+            .permissions()
+            .contains(Permissions::INPUT)
+    }
+
+    pub fn admin_tools_permitted(&self) -> bool {
+        self.endpoint_client
+            .permissions()
+            .contains(Permissions::ADMIN_TOOLS)
+    }
+
+    pub fn elevation_pending(&self) -> bool {
+        self.elevation_pending.load(Ordering::SeqCst)
+    }
+
+    pub fn reconnecting(&self) -> bool {
+        self.endpoint_client.reconnecting()
+    }
+
+    pub fn input_block_reason(&self) -> Option<InputBlockReason> {
+        self.endpoint_client.input_block_reason()
+    }
+
+    pub fn capture_interruption(&self) -> Option<CaptureInterruption> {
+        self.endpoint_client.capture_interruption()
+    }
+
+    pub fn take_fullscreen_request(&mut self) -> Option<FullscreenRequest> {
+        self.fullscreen_request.take()
+    }
 }
 
 impl State {
-    pub fn set_desktop_frame_scaled(&mut self, scaled: bool) {
-        self.desktop_frame_scaled = scaled
+    pub fn set_scale_mode(&mut self, scale_mode: ScaleMode) {
+        self.scale_mode = scale_mode
     }
 
     pub fn set_desktop_frame_scalable(&mut self, scalable: bool) {
         self.desktop_frame_scalable = scalable
     }
+
+    pub fn set_keyboard_grabbed(&mut self, grabbed: bool) {
+        self.keyboard_grabbed = grabbed
+    }
+
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.fullscreen = fullscreen
+    }
+
+    pub fn request_fullscreen(&mut self, request: FullscreenRequest) {
+        self.fullscreen_request = Some(request)
+    }
+
+    /// Ask remote to capture another monitor, `SPAN_MONITOR_ID` stitches all remote monitors
+    /// and None switches to the next one.
+    pub fn switch_remote_monitor(&self, monitor_id: Option<String>) {
+        let client = self.endpoint_client.clone();
+        let remote_monitors_spanned = self.remote_monitors_spanned.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match client
+                .call::<EndPointSwitchMonitorReply>(EndPointCallRequest::SwitchMonitorRequest(
+                    EndPointSwitchMonitorRequest { monitor_id },
+                ))
+                .await
+            {
+                Ok(reply) => {
+                    remote_monitors_spanned
+                        .store(reply.monitor.id == SPAN_MONITOR_ID, Ordering::SeqCst);
+                    client.set_monitor(reply.monitor).await;
+                }
+                Err(err) => tracing::error!(?err, "switch remote monitor failed"),
+            }
+        });
+    }
+
+    /// Change the resolution of remote monitor, None restores its original resolution.
+    pub fn switch_remote_resolution(&self, resolution: Option<(u16, u16)>) {
+        let client = self.endpoint_client.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match client
+                .call::<EndPointSwitchResolutionReply>(
+                    EndPointCallRequest::SwitchResolutionRequest(EndPointSwitchResolutionRequest {
+                        resolution,
+                    }),
+                )
+                .await
+            {
+                Ok(reply) => client.set_monitor(reply.monitor).await,
+                Err(err) => tracing::error!(?err, "switch remote resolution failed"),
+            }
+        });
+    }
+
+    pub fn switch_audio_capture_target(&self, target: AudioCaptureTarget) {
+        let client = self.endpoint_client.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match client
+                .call::<EndPointSwitchAudioCaptureReply>(
+                    EndPointCallRequest::SwitchAudioCaptureRequest(
+                        EndPointSwitchAudioCaptureRequest { target },
+                    ),
+                )
+                .await
+            {
+                Ok(reply) => client.set_audio_capture_target(reply.target).await,
+                Err(err) => tracing::error!(?err, "switch audio capture target failed"),
+            }
+        });
+    }
+
+    pub fn switch_audio_processing(&self, enabled: bool) {
+        let client = self.endpoint_client.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match client
+                .call::<EndPointSwitchAudioProcessingReply>(
+                    EndPointCallRequest::SwitchAudioProcessingRequest(
+                        EndPointSwitchAudioProcessingRequest { enabled },
+                    ),
+                )
+                .await
+            {
+                Ok(reply) => client.set_audio_processing_enabled(reply.enabled),
+                Err(err) => tracing::error!(?err, "switch audio processing failed"),
+            }
+        });
+    }
+
+    pub fn switch_stream_quality(&self, quality: StreamQuality) {
+        let client = self.endpoint_client.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match client
+                .call::<EndPointSwitchStreamQualityReply>(
+                    EndPointCallRequest::SwitchStreamQualityRequest(
+                        EndPointSwitchStreamQualityRequest { quality },
+                    ),
+                )
+                .await
+            {
+                Ok(reply) => client.set_stream_quality(reply.quality),
+                Err(err) => tracing::error!(?err, "switch stream quality failed"),
+            }
+        });
+    }
+
+    pub fn switch_host_priority(&self, enabled: bool) {
+        let client = self.endpoint_client.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match client
+                .call::<EndPointSwitchHostPriorityReply>(
+                    EndPointCallRequest::SwitchHostPriorityRequest(
+                        EndPointSwitchHostPriorityRequest { enabled },
+                    ),
+                )
+                .await
+            {
+                Ok(reply) => client.set_host_priority(reply.enabled),
+                Err(err) => tracing::error!(?err, "switch host priority failed"),
+            }
+        });
+    }
+
+    /// Ask remote to block the mouse and keyboard of its user, the state of endpoint client
+    /// is updated once remote user confirms.
+    pub fn switch_remote_local_input_block(&self, blocked: bool) {
+        let client = self.endpoint_client.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match client
+                .call::<EndPointBlockLocalInputReply>(EndPointCallRequest::BlockLocalInputRequest(
+                    EndPointBlockLocalInputRequest { blocked },
+                ))
+                .await
+            {
+                Ok(reply) => client.set_remote_local_input_blocked(reply.blocked),
+                Err(err) => tracing::error!(?err, "switch remote local input block failed"),
+            }
+        });
+    }
+
+    /// Sign in at the login screen of remote, it takes remote running as service with
+    /// unattended login enabled.
+    pub fn login_remote(&self, username: String, domain: Option<String>, password: String) {
+        let client = self.endpoint_client.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match client
+                .call::<EndPointLoginReply>(EndPointCallRequest::LoginRequest(
+                    EndPointLoginRequest {
+                        username,
+                        domain,
+                        password,
+                    },
+                ))
+                .await
+            {
+                Ok(_) => tracing::info!("remote signed in"),
+                Err(err) => tracing::error!(?err, "sign in remote failed"),
+            }
+        });
+    }
+
+    /// Ask remote user to allow controlling the desktop, the permissions of endpoint
+    /// client are updated once remote user decides.
+    pub fn request_input_permission(&self) {
+        self.request_permissions(Permissions::INPUT);
+    }
+
+    /// Ask remote user to allow listing and killing its processes.
+    pub fn request_admin_tools_permission(&self) {
+        self.request_permissions(Permissions::ADMIN_TOOLS);
+    }
+
+    fn request_permissions(&self, permissions: Permissions) {
+        if self.elevation_pending.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let client = self.endpoint_client.clone();
+        let elevation_pending = self.elevation_pending.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match client.request_permissions(permissions).await {
+                Ok(reply) => tracing::info!(
+                    requested = ?permissions.names(),
+                    granted = ?reply.granted.names(),
+                    scope = ?reply.scope,
+                    "request permissions finished"
+                ),
+                Err(err) => tracing::error!(
+                    ?err,
+                    requested = ?permissions.names(),
+                    "request permissions failed"
+                ),
+            }
+
+            elevation_pending.store(false, Ordering::SeqCst);
+        });
+    }
+
+    pub fn remote_volume(&self) -> Option<EndPointSystemVolumeReply> {
+        *self.remote_volume.lock().unwrap()
+    }
+
+    /// Change the shown volume while the slider is dragged, it's applied on release.
+    pub fn preview_remote_volume_level(&self, level: u8) {
+        if let Some(ref mut volume) = *self.remote_volume.lock().unwrap() {
+            volume.level = level;
+        }
+    }
+
+    /// Change the volume or mute of remote machine, what's None is left unchanged, so
+    /// passing neither refreshes the shown volume.
+    pub fn control_remote_volume(&self, level: Option<u8>, muted: Option<bool>) {
+        let client = self.endpoint_client.clone();
+        let remote_volume = self.remote_volume.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match client
+                .call::<EndPointSystemVolumeReply>(EndPointCallRequest::SystemVolumeRequest(
+                    EndPointSystemVolumeRequest { level, muted },
+                ))
+                .await
+            {
+                Ok(reply) => *remote_volume.lock().unwrap() = Some(reply),
+                Err(err) => tracing::error!(?err, "control remote volume failed"),
+            }
+        });
+    }
+
+    pub fn remote_processes(&self) -> Option<Vec<EndPointProcess>> {
+        self.remote_processes.lock().unwrap().clone()
+    }
+
+    /// List processes of remote machine again, the busiest come first.
+    pub fn refresh_remote_processes(&self) {
+        let client = self.endpoint_client.clone();
+        let remote_processes = self.remote_processes.clone();
+        *remote_processes.lock().unwrap() = None;
+
+        tauri::async_runtime::spawn(async move {
+            list_remote_processes(&client, &remote_processes).await;
+        });
+    }
+
+    /// Terminate the process of remote machine, the list is refreshed after it.
+    pub fn kill_remote_process(&self, pid: u32) {
+        let client = self.endpoint_client.clone();
+        let remote_processes = self.remote_processes.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match client
+                .call::<EndPointProcessKillReply>(EndPointCallRequest::ProcessKillRequest(
+                    EndPointProcessKillRequest { pid },
+                ))
+                .await
+            {
+                Ok(_) => tracing::info!(pid, "remote process killed"),
+                Err(err) => tracing::error!(?err, pid, "kill remote process failed"),
+            }
+
+            list_remote_processes(&client, &remote_processes).await;
+        });
+    }
+
+    /// Reboot remote machine, endpoint client reconnects once it's back online.
+    pub fn reboot_remote(&self) {
+        let client = self.endpoint_client.clone();
+
+        tauri::async_runtime::spawn(async move {
+            match client.reboot_remote().await {
+                Ok(reply) => tracing::info!(delay_secs = reply.delay_secs, "remote reboots"),
+                Err(err) => tracing::error!(?err, "reboot remote failed"),
+            }
+        });
+    }
+
+    /// Turn on the display of remote which is turned off by idle timeout.
+    pub fn wake_remote_display(&self) {
+        let client = self.endpoint_client.clone();
+
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = client.wake_remote_display().await {
+                tracing::error!(?err, "wake remote display failed");
+            }
+        });
+    }
+
+    /// Send files which are dropped into the window to the staging area of remote.
+    pub fn stage_files(&self, paths: Vec<PathBuf>) {
+        if !self
+            .endpoint_client
+            .permissions()
+            .contains(Permissions::FILE_TRANSFER)
+        {
+            tracing::warn!("drop files without file transfer permission");
+            return;
+        }
+
+        for path in paths {
+            let client = self.endpoint_client.clone();
+
+            tauri::async_runtime::spawn(async move {
+                if let Err(err) = stage_file(client, &path).await {
+                    tracing::error!(?err, ?path, "stage file failed");
+                }
+            });
+        }
+    }
+}
+
+async fn list_remote_processes(
+    client: &EndPointClient,
+    remote_processes: &Mutex<Option<Vec<EndPointProcess>>>,
+) {
+    match client
+        .call::<EndPointProcessListReply>(EndPointCallRequest::ProcessListRequest(
+            EndPointProcessListRequest {},
+        ))
+        .await
+    {
+        Ok(reply) => *remote_processes.lock().unwrap() = Some(reply.processes),
+        Err(err) => {
+            tracing::error!(?err, "list remote processes failed");
+            *remote_processes.lock().unwrap() = Some(Vec::new());
+        }
+    }
+}
+
+async fn stage_file(client: Arc<EndPointClient>, path: &Path) -> CoreResult<()> {
+    let filename = path
+        .file_name()
+        .and_then(|filename| filename.to_str())
+        .ok_or_else(|| core_error!("convert filename failed"))?
+        .to_string();
+
+    let size = path.metadata()?.len();
+    let id = uuid::Uuid::new_v4().to_string();
+
+    let _: EndPointSendFileReply = client
+        .call(EndPointCallRequest::SendFileRequest(
+            EndPointSendFileRequest {
+                id: id.clone(),
+                filename,
+                path: PathBuf::new(),
+                size,
+                resume: false,
+                staged: true,
+                delta: false,
+            },
+        ))
+        .await?;
+
+    send_file_to_remote(id, client, path, 0).await
 }
@@ -7,6 +7,7 @@ use mirrorx_core::{
 };
 use once_cell::sync::Lazy;
 use std::{collections::HashMap, sync::Arc};
+use tauri::AppHandle;
 use tauri_egui::{
     eframe::CreationContext,
     egui::{FontData, FontDefinitions, FontFamily},
@@ -51,6 +52,7 @@ static MONOSPACE_FONTS: Lazy<HashMap<&str, &[u8]>> = Lazy::new(|| {
 #[allow(clippy::too_many_arguments)]
 pub fn create_desktop_window(
     cc: &CreationContext,
+    app_handle: AppHandle,
     gl_context: Arc<tauri_egui::eframe::glow::Context>,
     endpoint_id: EndPointID,
     client: Arc<EndPointClient>,
@@ -60,7 +62,13 @@ pub fn create_desktop_window(
 
     // cc.egui_ctx.set_debug_on_hover(true);
 
-    crate::window::desktop::DesktopWindow::new(endpoint_id, gl_context, client, render_frame_rx)
+    crate::window::desktop::DesktopWindow::new(
+        app_handle,
+        endpoint_id,
+        gl_context,
+        client,
+        render_frame_rx,
+    )
 }
 
 fn set_fonts(ctx: &tauri_egui::egui::Context) {
@@ -1,5 +1,47 @@
-import { invoke } from '@tauri-apps/api';
-import type { Directory, Domain, HistoryRecord, LanDiscoverNode } from '$lib/components/types';
+import { invoke as tauriInvoke } from '@tauri-apps/api';
+import type { InvokeArgs } from '@tauri-apps/api/tauri';
+import type {
+	Directory,
+	Domain,
+	HistoryRecord,
+	LanDiscoverNode,
+	LanListenerReport,
+	RankedHistoryRecord
+} from '$lib/components/types';
+
+// error of commands, its key and params let the message be translated
+export class CoreError extends Error {
+	key: string;
+	params: Record<string, string>;
+
+	constructor(error: { key: string; params: Record<string, string>; message: string }) {
+		super(error.message);
+		this.name = 'CoreError';
+		this.key = error.key;
+		this.params = error.params;
+	}
+
+	// callers compare and search the message as before
+	toString(): string {
+		return this.message;
+	}
+}
+
+export interface ErrorKeyEntry {
+	key: string;
+	params: Array<string>;
+	message: string;
+}
+
+function invoke<T>(cmd: string, args?: InvokeArgs): Promise<T> {
+	return tauriInvoke<T>(cmd, args).catch((error) => {
+		if (error && typeof error === 'object' && typeof error.key === 'string') {
+			throw new CoreError(error);
+		}
+
+		throw error;
+	});
+}
 
 export function invoke_config_init(): Promise<void> {
 	return invoke('config_init');
@@ -39,6 +81,23 @@ export function invoke_config_domain_update(
 	return invoke('config_domain_update', { req: { id, update_type } });
 }
 
+export interface RelayTls {
+	relay_fingerprint: string;
+	client_cert: string | null;
+	client_key: string | null;
+}
+
+export function invoke_config_domain_relay_tls_get(domainId: number): Promise<RelayTls | null> {
+	return invoke('config_domain_relay_tls_get', { domainId });
+}
+
+export function invoke_config_domain_relay_tls_set(
+	domainId: number,
+	relayTls: RelayTls | null
+): Promise<void> {
+	return invoke('config_domain_relay_tls_set', { domainId, relayTls });
+}
+
 export function invoke_config_language_get(): Promise<string> {
 	return invoke('config_language_get');
 }
@@ -61,12 +120,449 @@ export function invoke_config_history_get(
 	return invoke('config_history_get', { timeRange: time_range });
 }
 
+export function invoke_config_history_ranked_get(
+	limit: number | null
+): Promise<Array<RankedHistoryRecord>> {
+	return invoke('config_history_ranked_get', { limit });
+}
+
+export function invoke_config_history_pinned_set(
+	deviceId: number,
+	domain: string,
+	pinned: boolean
+): Promise<void> {
+	return invoke('config_history_pinned_set', { deviceId, domain, pinned });
+}
+
+export function invoke_config_history_favorite_set(
+	deviceId: number,
+	domain: string,
+	favorite: boolean
+): Promise<void> {
+	return invoke('config_history_favorite_set', { deviceId, domain, favorite });
+}
+
+export type NotificationKind =
+	| 'visit_request'
+	| 'file_transfer_succeeded'
+	| 'file_transfer_failed'
+	| 'session_disconnected'
+	| 'remote_power_saving'
+	| 'permission_request'
+	| 'peer_identity_changed';
+
+export function invoke_config_notification_get(kind: NotificationKind): Promise<boolean> {
+	return invoke('config_notification_get', { kind });
+}
+
+export function invoke_config_notification_set(
+	kind: NotificationKind,
+	enabled: boolean
+): Promise<void> {
+	return invoke('config_notification_set', { kind, enabled });
+}
+
+export type HotkeyAction =
+	| 'toggle_fullscreen'
+	| 'release_keyboard_grab'
+	| 'disconnect_session'
+	| 'switch_monitor';
+
+export interface Hotkey {
+	action: HotkeyAction;
+	accelerator: string;
+	enabled: boolean;
+}
+
+export function invoke_config_hotkey_list(): Promise<Array<Hotkey>> {
+	return invoke('config_hotkey_list');
+}
+
+export function invoke_config_hotkey_set(
+	action: HotkeyAction,
+	accelerator: string,
+	enabled: boolean
+): Promise<void> {
+	return invoke('config_hotkey_set', { action, accelerator, enabled });
+}
+
+export function invoke_config_hotkey_reset(): Promise<Array<Hotkey>> {
+	return invoke('config_hotkey_reset');
+}
+
+export type AclAction = 'allow' | 'deny';
+
+export type AclMatcher =
+	| { kind: 'device_id'; value: number }
+	| { kind: 'device_id_prefix'; value: string }
+	| { kind: 'subnet'; value: string };
+
+export interface AclRule {
+	id: number;
+	action: AclAction;
+	matcher: AclMatcher;
+}
+
+export function invoke_config_acl_rule_list(): Promise<Array<AclRule>> {
+	return invoke('config_acl_rule_list');
+}
+
+export function invoke_config_acl_rule_add(action: AclAction, matcher: AclMatcher): Promise<number> {
+	return invoke('config_acl_rule_add', { action, matcher });
+}
+
+export function invoke_config_acl_rule_delete(id: number): Promise<void> {
+	return invoke('config_acl_rule_delete', { id });
+}
+
+export function invoke_config_power_saving_get(): Promise<boolean> {
+	return invoke('config_power_saving_get');
+}
+
+export function invoke_config_power_saving_set(enabled: boolean): Promise<void> {
+	return invoke('config_power_saving_set', { enabled });
+}
+
+export function invoke_config_keep_awake_get(): Promise<boolean> {
+	return invoke('config_keep_awake_get');
+}
+
+export function invoke_config_keep_awake_set(enabled: boolean): Promise<void> {
+	return invoke('config_keep_awake_set', { enabled });
+}
+
+export type UpdateChannel = 'stable' | 'beta';
+
+export function invoke_config_update_channel_get(): Promise<UpdateChannel> {
+	return invoke('config_update_channel_get');
+}
+
+export function invoke_config_update_channel_set(channel: UpdateChannel): Promise<void> {
+	return invoke('config_update_channel_set', { channel });
+}
+
+export function invoke_config_update_url_get(): Promise<string> {
+	return invoke('config_update_url_get');
+}
+
+export function invoke_config_update_url_set(url: string): Promise<void> {
+	return invoke('config_update_url_set', { url });
+}
+
+export function invoke_config_telemetry_get(): Promise<boolean> {
+	return invoke('config_telemetry_get');
+}
+
+export function invoke_config_telemetry_set(enabled: boolean): Promise<void> {
+	return invoke('config_telemetry_set', { enabled });
+}
+
+export function invoke_config_telemetry_url_get(): Promise<string> {
+	return invoke('config_telemetry_url_get');
+}
+
+export function invoke_config_telemetry_url_set(url: string): Promise<void> {
+	return invoke('config_telemetry_url_set', { url });
+}
+
+export type FailureCategory =
+	| 'visit_rejected'
+	| 'timeout'
+	| 'network'
+	| 'key_exchange'
+	| 'negotiate'
+	| 'other';
+
+export interface TelemetryReport {
+	os: string;
+	arch: string;
+	period_start: number;
+	period_end: number;
+	connect_attempts: number;
+	connect_success_rate?: number;
+	average_bit_rate_kbps?: number;
+	codecs: Record<string, number>;
+	failures: Partial<Record<FailureCategory, number>>;
+}
+
+export function invoke_config_telemetry_preview(): Promise<TelemetryReport> {
+	return invoke('config_telemetry_preview');
+}
+
+export function invoke_config_automation_get(): Promise<boolean> {
+	return invoke('config_automation_get');
+}
+
+export function invoke_config_automation_set(enabled: boolean): Promise<void> {
+	return invoke('config_automation_set', { enabled });
+}
+
+export function invoke_config_view_only_get(): Promise<boolean> {
+	return invoke('config_view_only_get');
+}
+
+export function invoke_config_view_only_set(enabled: boolean): Promise<void> {
+	return invoke('config_view_only_set', { enabled });
+}
+
+export function invoke_config_visit_confirm_get(): Promise<boolean> {
+	return invoke('config_visit_confirm_get');
+}
+
+export function invoke_config_visit_confirm_set(enabled: boolean): Promise<void> {
+	return invoke('config_visit_confirm_set', { enabled });
+}
+
+export function invoke_config_unattended_login_get(): Promise<boolean> {
+	return invoke('config_unattended_login_get');
+}
+
+export function invoke_config_unattended_login_set(enabled: boolean): Promise<void> {
+	return invoke('config_unattended_login_set', { enabled });
+}
+
+export function invoke_config_reject_message_get(): Promise<string | null> {
+	return invoke('config_reject_message_get');
+}
+
+export function invoke_config_reject_message_set(message: string | null): Promise<void> {
+	return invoke('config_reject_message_set', { message });
+}
+
+export function invoke_config_peer_trust(
+	domain: string,
+	remoteDeviceId: string,
+	fingerprint: string
+): Promise<void> {
+	return invoke('config_peer_trust', { domain, remoteDeviceId, fingerprint });
+}
+
+export function invoke_config_peer_bandwidth_limit_get(
+	domain: string,
+	remoteDeviceId: string
+): Promise<number | null> {
+	return invoke('config_peer_bandwidth_limit_get', { domain, remoteDeviceId });
+}
+
+export function invoke_config_peer_bandwidth_limit_set(
+	domain: string,
+	remoteDeviceId: string,
+	maxKbps: number | null
+): Promise<void> {
+	return invoke('config_peer_bandwidth_limit_set', { domain, remoteDeviceId, maxKbps });
+}
+
+export type Availability = 'available' | 'busy' | 'do_not_disturb';
+
+export function invoke_config_availability_get(): Promise<Availability> {
+	return invoke('config_availability_get');
+}
+
+export function invoke_config_availability_set(availability: Availability): Promise<void> {
+	return invoke('config_availability_set', { availability });
+}
+
+export function invoke_config_max_sessions_get(): Promise<number> {
+	return invoke('config_max_sessions_get');
+}
+
+export function invoke_config_max_sessions_set(maxSessions: number): Promise<void> {
+	return invoke('config_max_sessions_set', { maxSessions });
+}
+
+export function invoke_config_idle_frame_rate_get(): Promise<number> {
+	return invoke('config_idle_frame_rate_get');
+}
+
+export function invoke_config_idle_frame_rate_set(frameRate: number): Promise<void> {
+	return invoke('config_idle_frame_rate_set', { frameRate });
+}
+
+export function invoke_config_watermark_get(): Promise<boolean> {
+	return invoke('config_watermark_get');
+}
+
+export function invoke_config_watermark_set(enabled: boolean): Promise<void> {
+	return invoke('config_watermark_set', { enabled });
+}
+
+export function invoke_config_tile_codec_preferred_get(): Promise<boolean> {
+	return invoke('config_tile_codec_preferred_get');
+}
+
+export function invoke_config_tile_codec_preferred_set(preferred: boolean): Promise<void> {
+	return invoke('config_tile_codec_preferred_set', { preferred });
+}
+
+export interface GopPolicy {
+	key_frame_interval_secs: number;
+	b_frames: number;
+}
+
+export function invoke_config_gop_policy_get(): Promise<GopPolicy> {
+	return invoke('config_gop_policy_get');
+}
+
+export function invoke_config_gop_policy_set(policy: GopPolicy): Promise<void> {
+	return invoke('config_gop_policy_set', { policy });
+}
+
+export type SessionProfile = 'Interactive' | 'Streaming';
+
+export function invoke_config_session_profile_get(): Promise<SessionProfile> {
+	return invoke('config_session_profile_get');
+}
+
+export function invoke_config_session_profile_set(profile: SessionProfile): Promise<void> {
+	return invoke('config_session_profile_set', { profile });
+}
+
+export function invoke_config_quality_auto_tune_get(): Promise<boolean> {
+	return invoke('config_quality_auto_tune_get');
+}
+
+export function invoke_config_quality_auto_tune_set(enabled: boolean): Promise<void> {
+	return invoke('config_quality_auto_tune_set', { enabled });
+}
+
+export function invoke_config_test_pattern_get(): Promise<string | null> {
+	return invoke('config_test_pattern_get');
+}
+
+export function invoke_config_test_pattern_set(pattern: string | null): Promise<void> {
+	return invoke('config_test_pattern_set', { pattern });
+}
+
+export type ThreadPriority = 'normal' | 'below_normal' | 'lowest';
+
+export function invoke_config_encoder_thread_priority_get(): Promise<ThreadPriority> {
+	return invoke('config_encoder_thread_priority_get');
+}
+
+export function invoke_config_encoder_thread_priority_set(priority: ThreadPriority): Promise<void> {
+	return invoke('config_encoder_thread_priority_set', { priority });
+}
+
+export function invoke_config_encoder_cores_get(): Promise<string | null> {
+	return invoke('config_encoder_cores_get');
+}
+
+export function invoke_config_encoder_cores_set(cores: string | null): Promise<void> {
+	return invoke('config_encoder_cores_set', { cores });
+}
+
+export function invoke_config_staging_quota_get(): Promise<number> {
+	return invoke('config_staging_quota_get');
+}
+
+export function invoke_config_staging_quota_set(quotaMb: number): Promise<void> {
+	return invoke('config_staging_quota_set', { quotaMb });
+}
+
+export function invoke_config_staging_retention_get(): Promise<number> {
+	return invoke('config_staging_retention_get');
+}
+
+export function invoke_config_staging_retention_set(days: number): Promise<void> {
+	return invoke('config_staging_retention_set', { days });
+}
+
+export function invoke_config_lan_only_get(): Promise<boolean> {
+	return invoke('config_lan_only_get');
+}
+
+export function invoke_config_lan_only_set(enabled: boolean): Promise<void> {
+	return invoke('config_lan_only_set', { enabled });
+}
+
+export function invoke_config_lan_key_get(): Promise<string | null> {
+	return invoke('config_lan_key_get');
+}
+
+export function invoke_config_lan_key_set(lanKey: string): Promise<void> {
+	return invoke('config_lan_key_set', { lanKey });
+}
+
+export function invoke_config_lan_key_generate(): Promise<string> {
+	return invoke('config_lan_key_generate');
+}
+
+export type KeyStoreBackend = 'os' | 'file';
+
+export function invoke_config_key_store_backend_get(): Promise<KeyStoreBackend> {
+	return invoke('config_key_store_backend_get');
+}
+
+export function invoke_config_lan_listen_ip_get(): Promise<string | null> {
+	return invoke('config_lan_listen_ip_get');
+}
+
+export function invoke_config_lan_listen_ip_set(listenIp: string | null): Promise<void> {
+	return invoke('config_lan_listen_ip_set', { listenIp });
+}
+
+export function invoke_config_bind_ip_get(): Promise<string | null> {
+	return invoke('config_bind_ip_get');
+}
+
+export function invoke_config_bind_ip_set(bindIp: string | null): Promise<void> {
+	return invoke('config_bind_ip_set', { bindIp });
+}
+
+export function invoke_config_autostart_get(): Promise<boolean> {
+	return invoke('config_autostart_get');
+}
+
+export function invoke_config_autostart_set(enabled: boolean): Promise<void> {
+	return invoke('config_autostart_set', { enabled });
+}
+
+export function invoke_config_log_filter_get(): Promise<string | null> {
+	return invoke('config_log_filter_get');
+}
+
+export function invoke_config_log_filter_set(directives: string | null): Promise<void> {
+	return invoke('config_log_filter_set', { directives });
+}
+
+export interface TransportTuning {
+	keepalive_idle_secs: number;
+	keepalive_interval_secs: number;
+	dscp_marking: boolean;
+}
+
+export function invoke_config_transport_tuning_get(): Promise<TransportTuning> {
+	return invoke('config_transport_tuning_get');
+}
+
+export function invoke_config_transport_tuning_set(tuning: TransportTuning): Promise<void> {
+	return invoke('config_transport_tuning_set', { tuning });
+}
+
+export interface ReceivePolicy {
+	blocked_extensions: string[];
+	max_size_mb: number | null;
+	quarantine: boolean;
+}
+
+export function invoke_config_receive_policy_get(): Promise<ReceivePolicy> {
+	return invoke('config_receive_policy_get');
+}
+
+export function invoke_config_receive_policy_set(policy: ReceivePolicy): Promise<void> {
+	return invoke('config_receive_policy_set', { policy });
+}
+
 export function invoke_lan_init(force: boolean): Promise<void> {
 	return invoke('lan_init', { force });
 }
 
-export function invoke_lan_connect(addr: string, visitDesktop: boolean): Promise<void> {
-	return invoke('lan_connect', { addr, visitDesktop });
+export function invoke_lan_connect(
+	addr: string,
+	visitDesktop: boolean,
+	requestId?: string
+): Promise<void> {
+	return invoke('lan_connect', { addr, visitDesktop, requestId });
 }
 
 export function invoke_lan_nodes_list(): Promise<Array<LanDiscoverNode>> {
@@ -85,35 +581,139 @@ export function invoke_lan_discoverable_set(discoverable: boolean): Promise<void
 	return invoke('lan_discoverable_set', { discoverable });
 }
 
+export function invoke_lan_listener_report(): Promise<LanListenerReport> {
+	return invoke('lan_listener_report');
+}
+
+export interface PairedPeer {
+	id: number;
+	name: string;
+	fingerprint: string;
+	paired_at: number;
+}
+
+export function invoke_lan_peer_card_export(): Promise<string> {
+	return invoke('lan_peer_card_export');
+}
+
+export function invoke_lan_peer_card_import(card: string): Promise<string> {
+	return invoke('lan_peer_card_import', { card });
+}
+
+export function invoke_lan_paired_peers_list(): Promise<Array<PairedPeer>> {
+	return invoke('lan_paired_peers_list');
+}
+
+export function invoke_lan_paired_peer_revoke(id: number): Promise<void> {
+	return invoke('lan_paired_peer_revoke', { id });
+}
+
 export function invoke_signaling_connect(force: boolean): Promise<void> {
 	return invoke('signaling_connect', { force });
 }
 
+export type SignalingState =
+	| { state: 'connecting' }
+	| { state: 'connected' }
+	| { state: 'reconnecting'; attempt: number; retry_in_secs: number }
+	| { state: 'disconnected' };
+
+export function invoke_signaling_state(domainId?: number): Promise<SignalingState> {
+	return invoke('signaling_state', { domainId });
+}
+
 export function invoke_signaling_visit(
 	remoteDeviceId: string,
 	password: string,
-	visitDesktop: boolean
+	visitDesktop: boolean,
+	domainId?: number,
+	requestId?: string
 ): Promise<void> {
-	return invoke('signaling_visit', { remoteDeviceId, password, visitDesktop });
+	return invoke('signaling_visit', {
+		remoteDeviceId,
+		password,
+		visitDesktop,
+		domainId,
+		requestId
+	});
+}
+
+export function invoke_signaling_visit_reply(
+	requestId: string,
+	allow: boolean,
+	message: string | null = null
+): Promise<void> {
+	return invoke('signaling_visit_reply', { requestId, allow, message });
+}
+
+export function invoke_signaling_pairing_create(): Promise<string> {
+	return invoke('signaling_pairing_create');
+}
+
+export function invoke_signaling_pairing_revoke(): Promise<void> {
+	return invoke('signaling_pairing_revoke');
+}
+
+export function invoke_signaling_pairing_visit(
+	payload: string,
+	visitDesktop: boolean,
+	requestId?: string
+): Promise<void> {
+	return invoke('signaling_pairing_visit', { payload, visitDesktop, requestId });
+}
+
+export function invoke_signaling_observer_invite(
+	remoteDeviceId: string,
+	ttlSecs?: number
+): Promise<string> {
+	return invoke('signaling_observer_invite', { remoteDeviceId, ttlSecs });
+}
+
+export function invoke_signaling_observer_visit(
+	invitation: string,
+	requestId?: string
+): Promise<void> {
+	return invoke('signaling_observer_visit', { invitation, requestId });
+}
+
+export type SessionState =
+	| { state: 'connecting' }
+	| { state: 'handshaking' }
+	| { state: 'negotiating' }
+	| { state: 'streaming' }
+	| { state: 'reconnecting' }
+	| { state: 'closed'; reason: 'local' | 'disconnected' | 'reconnect_failed' | 'failed' };
+
+export type SessionStateResult = { session_id: string } & SessionState;
+
+export function invoke_file_manager_session_state(
+	remoteDeviceId: string
+): Promise<SessionStateResult> {
+	return invoke('file_manager_session_state', { remoteDeviceId });
 }
 
 export function invoke_file_manager_visit_remote(
 	remoteDeviceId: string,
-	path: string | null
+	path: string | null,
+	requestId?: string
 ): Promise<Directory> {
-	return invoke('file_manager_visit_remote', { remoteDeviceId, path });
+	return invoke('file_manager_visit_remote', { remoteDeviceId, path, requestId });
 }
 
-export function invoke_file_manager_visit_local(path: string | null): Promise<Directory> {
-	return invoke('file_manager_visit_local', { path });
+export function invoke_file_manager_visit_local(
+	path: string | null,
+	requestId?: string
+): Promise<Directory> {
+	return invoke('file_manager_visit_local', { path, requestId });
 }
 
 export function invoke_file_manager_send_file(
 	remoteDeviceId: string,
 	localPath: string,
-	remotePath: string
+	remotePath: string,
+	delta: boolean = false
 ): Promise<[string, number]> {
-	return invoke('file_manager_send_file', { remoteDeviceId, localPath, remotePath });
+	return invoke('file_manager_send_file', { remoteDeviceId, localPath, remotePath, delta });
 }
 
 export function invoke_file_manager_download_file(
@@ -124,8 +724,63 @@ export function invoke_file_manager_download_file(
 	return invoke('file_manager_download_file', { remoteDeviceId, localPath, remotePath });
 }
 
-export function invoke_file_manager_query_transferred_bytes_count(id: string): Promise<number> {
-	return invoke('file_manager_query_transferred_bytes_count', { id });
+export interface TransferRoot {
+	local_path: string;
+	remote_path: string;
+}
+
+export interface VerificationEntry {
+	path: string;
+	status: 'matched' | 'size_mismatched' | 'hash_mismatched' | 'missing_local' | 'missing_remote';
+	local_size: number | null;
+	remote_size: number | null;
+}
+
+export interface VerificationReport {
+	entries: Array<VerificationEntry>;
+	verified_bytes: number;
+	intact: boolean;
+}
+
+export function invoke_file_manager_verify_transfer(
+	remoteDeviceId: string,
+	roots: Array<TransferRoot>
+): Promise<VerificationReport> {
+	return invoke('file_manager_verify_transfer', { remoteDeviceId, roots });
+}
+
+export function invoke_file_manager_bridge_start(remoteDeviceId: string): Promise<string> {
+	return invoke('file_manager_bridge_start', { remoteDeviceId });
+}
+
+export function invoke_file_manager_bridge_stop(remoteDeviceId: string): Promise<void> {
+	return invoke('file_manager_bridge_stop', { remoteDeviceId });
+}
+
+export interface TransferManifest {
+	id: string;
+	remote_device_id: string;
+	direction: 'send' | 'download';
+	local_path: string;
+	remote_path: string;
+	size: number;
+	timestamp: number;
+}
+
+export function invoke_file_manager_interrupted_transfers(): Promise<Array<TransferManifest>> {
+	return invoke('file_manager_interrupted_transfers');
+}
+
+export function invoke_file_manager_resume_transfer(id: string): Promise<[string, number]> {
+	return invoke('file_manager_resume_transfer', { id });
+}
+
+export function invoke_file_manager_discard_transfer(id: string): Promise<void> {
+	return invoke('file_manager_discard_transfer', { id });
+}
+
+export function invoke_utility_cancel_request(requestId: string): Promise<boolean> {
+	return invoke('utility_cancel_request', { requestId });
 }
 
 export function invoke_utility_generate_random_password(): Promise<string> {
@@ -142,6 +797,154 @@ export function invoke_utility_enum_graphics_cards(): Promise<
 	return invoke('utility_enum_graphics_cards');
 }
 
+export interface InterfaceAddr {
+	name: string;
+	ip: string;
+	broadcast: boolean;
+}
+
+export function invoke_utility_enum_network_interfaces(): Promise<Array<InterfaceAddr>> {
+	return invoke('utility_enum_network_interfaces');
+}
+
 export function invoke_utility_hide_macos_zoom_button(): Promise<void> {
 	return invoke('utility_hide_macos_zoom_button');
 }
+
+export interface LogRecord {
+	timestamp: string;
+	level: string;
+	target: string;
+	message: string;
+	session_id?: string;
+	fields: Record<string, unknown>;
+}
+
+export function invoke_utility_query_logs(
+	sessionId?: string,
+	level?: string,
+	limit?: number
+): Promise<Array<LogRecord>> {
+	return invoke('utility_query_logs', { sessionId, level, limit });
+}
+
+export function invoke_utility_generate_diagnostics(path: string): Promise<void> {
+	return invoke('utility_generate_diagnostics', { path });
+}
+
+export function invoke_utility_error_catalog(): Promise<Array<ErrorKeyEntry>> {
+	return invoke('utility_error_catalog');
+}
+
+export function invoke_utility_crash_report_list(): Promise<Array<CrashReport>> {
+	return invoke('utility_crash_report_list');
+}
+
+export function invoke_utility_crash_report_upload(name: string): Promise<void> {
+	return invoke('utility_crash_report_upload', { name });
+}
+
+export function invoke_utility_crash_report_discard(name: string): Promise<void> {
+	return invoke('utility_crash_report_discard', { name });
+}
+
+export interface CrashReport {
+	name: string;
+	kind: 'panic' | 'minidump';
+	timestamp: number;
+	size: number;
+}
+
+export interface ReleaseManifest {
+	version: string;
+	channel: UpdateChannel;
+	notes: string;
+	pub_date: number;
+	packages: Record<string, { url: string; sha256: string; size: number }>;
+}
+
+export function invoke_updater_check(): Promise<ReleaseManifest | null> {
+	return invoke('updater_check');
+}
+
+export function invoke_updater_install(): Promise<void> {
+	return invoke('updater_install');
+}
+
+export type Permission = 'input' | 'clipboard' | 'file_transfer' | 'admin_tools' | 'plugins';
+
+export interface ElevationResult {
+	permissions: Array<Permission>;
+	granted: Array<Permission>;
+	expires_in_secs: number | null;
+}
+
+export function invoke_permission_request(
+	remoteDeviceId: string,
+	permissions: Array<Permission>
+): Promise<ElevationResult> {
+	return invoke('permission_request', { remoteDeviceId, permissions });
+}
+
+export function invoke_permission_reply(
+	requestId: string,
+	granted: Array<Permission>,
+	temporarySecs: number | null
+): Promise<void> {
+	return invoke('permission_reply', { requestId, granted, temporarySecs });
+}
+
+export function invoke_local_input_block_reply(
+	requestId: string,
+	allowed: boolean
+): Promise<void> {
+	return invoke('local_input_block_reply', { requestId, allowed });
+}
+
+export interface QueuedVisit {
+	id: string;
+	remote: string;
+	queued_at: number;
+}
+
+export function invoke_queue_list(): Promise<Array<QueuedVisit>> {
+	return invoke('queue_list');
+}
+
+export function invoke_queue_move(id: string, position: number): Promise<void> {
+	return invoke('queue_move', { id, position });
+}
+
+export function invoke_queue_reject(id: string): Promise<void> {
+	return invoke('queue_reject', { id });
+}
+
+export function invoke_staging_open(): Promise<void> {
+	return invoke('staging_open');
+}
+
+export function invoke_staging_usage(): Promise<number> {
+	return invoke('staging_usage');
+}
+
+export function invoke_staging_clear(): Promise<void> {
+	return invoke('staging_clear');
+}
+
+export interface QuarantinedFile {
+	id: string;
+	destination: string;
+	size: number;
+}
+
+export function invoke_quarantine_list(): Promise<QuarantinedFile[]> {
+	return invoke('quarantine_list');
+}
+
+export function invoke_quarantine_release(id: string): Promise<string> {
+	return invoke('quarantine_release', { id });
+}
+
+export function invoke_quarantine_discard(id: string): Promise<void> {
+	return invoke('quarantine_discard', { id });
+}
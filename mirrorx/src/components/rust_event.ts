@@ -1,3 +1,9 @@
+import type { SignalingState } from '$lib/components/command';
+
 export interface UpdateLanguageEvent {
 	language: string;
 }
+
+export type SignalingStateEvent = SignalingState & {
+	domain_id: number;
+};
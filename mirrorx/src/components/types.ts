@@ -18,6 +18,26 @@ export interface LanDiscoverNode {
 	addrs: Map<string, number>;
 	os: string;
 	os_version: string;
+	availability: 'available' | 'busy' | 'do_not_disturb';
+}
+
+export type LanListenerHint =
+	| 'not_listening'
+	| 'no_interface'
+	| 'interface_unreachable'
+	| 'firewall_blocks_all_inbound'
+	| 'firewall_blocks_app'
+	| 'public_network'
+	| 'allow_app_in_firewall';
+
+export interface LanListenerReport {
+	bound_addr: string | null;
+	loopback_reachable: boolean | null;
+	interface_addr: string | null;
+	interface_reachable: boolean;
+	remote_accepted: boolean;
+	firewall: 'open' | 'likely_blocked' | 'unknown';
+	hints: Array<LanListenerHint>;
 }
 
 export interface HistoryRecord {
@@ -27,6 +47,15 @@ export interface HistoryRecord {
 	timestamp: number;
 }
 
+export interface RankedHistoryRecord extends HistoryRecord {
+	visit_count: number;
+	pinned: boolean;
+	favorite: boolean;
+	online: boolean | null;
+	online_timestamp: number | null;
+	score: number;
+}
+
 export interface Directory {
 	path: string;
 	entries: Array<Entry>;
@@ -67,3 +96,13 @@ export interface FileTransferItem {
 	succeed_at: number;
 	failed_at: number;
 }
+
+export interface Progress {
+	operation_id: string;
+	kind: 'send' | 'receive' | 'directory_listing' | 'recording';
+	state: 'running' | 'succeeded' | 'failed';
+	done: number;
+	total: number | null;
+	throughput: number;
+	eta_secs: number | null;
+}
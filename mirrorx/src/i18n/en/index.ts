@@ -26,7 +26,11 @@ const en: BaseTranslation = {
 		GenerateRandomPasswordTooltip: 'Generate Random Password',
 		DomainActions: 'Domain Actions',
 		DomainActionsEdit: 'Edit',
-		SelectPrimaryDomain: 'Select Primary Domain'
+		SelectPrimaryDomain: 'Select Primary Domain',
+		SignalingConnecting: 'Connecting',
+		SignalingConnected: 'Online',
+		SignalingReconnecting: 'Reconnecting',
+		SignalingDisconnected: 'Offline'
 	},
 	LAN: {
 		HostnameOrIP: 'Search Hostname or IP (Case Sensitive)',
@@ -78,7 +82,17 @@ const en: BaseTranslation = {
 			TableStatus: 'Status',
 			TableTotalSize: 'Total Size',
 			TableFinishAt: 'Finish At',
-			TableTimeCost: 'Time Cost'
+			TableTimeCost: 'Time Cost',
+			Verify: 'Verify',
+			VerifyIntact: 'All transferred files are complete and intact',
+			VerifyBroken: 'Some transferred files are missing or corrupted'
+		},
+		Session: {
+			Connecting: 'Connecting...',
+			Handshaking: 'Handshaking...',
+			Negotiating: 'Negotiating...',
+			Reconnecting: 'Reconnecting...',
+			Closed: 'Session closed'
 		}
 	},
 	Dialogs: {
@@ -86,6 +100,13 @@ const en: BaseTranslation = {
 			Copy: 'Copy',
 			Version: 'Version'
 		},
+		VisitRequest: {
+			Title: 'Visit Request',
+			ContentPrefix: 'Device',
+			ContentSuffix: 'wants to visit your',
+			Desktop: 'Desktop',
+			Files: 'Files'
+		},
 		VisitPrepare: {
 			Content: "Please input this device's password"
 		},
@@ -113,6 +134,13 @@ const en: BaseTranslation = {
 				Tooltip: `Finger print is a random string generated at local once you connected to a new domain. It is used to prove your device has authority to hold a Device Id that Domain allocated for you for a while and it can't be used to track your device.`
 			},
 			Remarks: 'Remarks',
+			RelayTls: {
+				Label: 'Relay TLS',
+				Tooltip: `Connections to the relay of this domain are encrypted with TLS once the SHA-256 fingerprint of the relay certificate is set. Client certificate and its key are optional, they are presented when the relay requires one.`,
+				Fingerprint: 'Relay certificate SHA-256 fingerprint',
+				ClientCert: 'Client certificate (PEM, optional)',
+				ClientKey: 'Client private key (PEM, optional)'
+			},
 			Delete: 'Delete',
 			Edit: 'Edit'
 		},
@@ -132,7 +160,9 @@ const en: BaseTranslation = {
 			ContentSuffix: `? Once you delete it and you can't recovery!`
 		},
 		HistoryConnect: {
-			Tip: 'Automatically Switch Domain'
+			Tip: 'Automatically Switch Domain',
+			BandwidthLimit: 'Bandwidth Limit',
+			Unlimited: 'Unlimited'
 		}
 	},
 	FileType: {
@@ -109,6 +109,22 @@ type RootTranslation = {
 		 * S​e​l​e​c​t​ ​P​r​i​m​a​r​y​ ​D​o​m​a​i​n
 		 */
 		SelectPrimaryDomain: string
+		/**
+		 * C​o​n​n​e​c​t​i​n​g
+		 */
+		SignalingConnecting: string
+		/**
+		 * O​n​l​i​n​e
+		 */
+		SignalingConnected: string
+		/**
+		 * R​e​c​o​n​n​e​c​t​i​n​g
+		 */
+		SignalingReconnecting: string
+		/**
+		 * O​f​f​l​i​n​e
+		 */
+		SignalingDisconnected: string
 	}
 	LAN: {
 		/**
@@ -262,6 +278,40 @@ type RootTranslation = {
 			 * T​i​m​e​ ​C​o​s​t
 			 */
 			TableTimeCost: string
+			/**
+			 * V​e​r​i​f​y
+			 */
+			Verify: string
+			/**
+			 * A​l​l​ ​t​r​a​n​s​f​e​r​r​e​d​ ​f​i​l​e​s​ ​a​r​e​ ​c​o​m​p​l​e​t​e​ ​a​n​d​ ​i​n​t​a​c​t
+			 */
+			VerifyIntact: string
+			/**
+			 * S​o​m​e​ ​t​r​a​n​s​f​e​r​r​e​d​ ​f​i​l​e​s​ ​a​r​e​ ​m​i​s​s​i​n​g​ ​o​r​ ​c​o​r​r​u​p​t​e​d
+			 */
+			VerifyBroken: string
+		}
+		Session: {
+			/**
+			 * C​o​n​n​e​c​t​i​n​g​.​.​.
+			 */
+			Connecting: string
+			/**
+			 * H​a​n​d​s​h​a​k​i​n​g​.​.​.
+			 */
+			Handshaking: string
+			/**
+			 * N​e​g​o​t​i​a​t​i​n​g​.​.​.
+			 */
+			Negotiating: string
+			/**
+			 * R​e​c​o​n​n​e​c​t​i​n​g​.​.​.
+			 */
+			Reconnecting: string
+			/**
+			 * S​e​s​s​i​o​n​ ​c​l​o​s​e​d
+			 */
+			Closed: string
 		}
 	}
 	Dialogs: {
@@ -275,6 +325,28 @@ type RootTranslation = {
 			 */
 			Version: string
 		}
+		VisitRequest: {
+			/**
+			 * V​i​s​i​t​ ​R​e​q​u​e​s​t
+			 */
+			Title: string
+			/**
+			 * D​e​v​i​c​e
+			 */
+			ContentPrefix: string
+			/**
+			 * w​a​n​t​s​ ​t​o​ ​v​i​s​i​t​ ​y​o​u​r
+			 */
+			ContentSuffix: string
+			/**
+			 * D​e​s​k​t​o​p
+			 */
+			Desktop: string
+			/**
+			 * F​i​l​e​s
+			 */
+			Files: string
+		}
 		VisitPrepare: {
 			/**
 			 * P​l​e​a​s​e​ ​i​n​p​u​t​ ​t​h​i​s​ ​d​e​v​i​c​e​'​s​ ​p​a​s​s​w​o​r​d
@@ -344,6 +416,28 @@ type RootTranslation = {
 			 * R​e​m​a​r​k​s
 			 */
 			Remarks: string
+			RelayTls: {
+				/**
+				 * R​e​l​a​y​ ​T​L​S
+				 */
+				Label: string
+				/**
+				 * C​o​n​n​e​c​t​i​o​n​s​ ​t​o​ ​t​h​e​ ​r​e​l​a​y​ ​o​f​ ​t​h​i​s​ ​d​o​m​a​i​n​ ​a​r​e​ ​e​n​c​r​y​p​t​e​d​ ​w​i​t​h​ ​T​L​S​ ​o​n​c​e​ ​t​h​e​ ​S​H​A​-​2​5​6​ ​f​i​n​g​e​r​p​r​i​n​t​ ​o​f​ ​t​h​e​ ​r​e​l​a​y​ ​c​e​r​t​i​f​i​c​a​t​e​ ​i​s​ ​s​e​t​.​ ​C​l​i​e​n​t​ ​c​e​r​t​i​f​i​c​a​t​e​ ​a​n​d​ ​i​t​s​ ​k​e​y​ ​a​r​e​ ​o​p​t​i​o​n​a​l​,​ ​t​h​e​y​ ​a​r​e​ ​p​r​e​s​e​n​t​e​d​ ​w​h​e​n​ ​t​h​e​ ​r​e​l​a​y​ ​r​e​q​u​i​r​e​s​ ​o​n​e​.
+				 */
+				Tooltip: string
+				/**
+				 * R​e​l​a​y​ ​c​e​r​t​i​f​i​c​a​t​e​ ​S​H​A​-​2​5​6​ ​f​i​n​g​e​r​p​r​i​n​t
+				 */
+				Fingerprint: string
+				/**
+				 * C​l​i​e​n​t​ ​c​e​r​t​i​f​i​c​a​t​e​ ​(​P​E​M​,​ ​o​p​t​i​o​n​a​l​)
+				 */
+				ClientCert: string
+				/**
+				 * C​l​i​e​n​t​ ​p​r​i​v​a​t​e​ ​k​e​y​ ​(​P​E​M​,​ ​o​p​t​i​o​n​a​l​)
+				 */
+				ClientKey: string
+			}
 			/**
 			 * D​e​l​e​t​e
 			 */
@@ -400,6 +494,15 @@ type RootTranslation = {
 			 * A​u​t​o​m​a​t​i​c​a​l​l​y​ ​S​w​i​t​c​h​ ​D​o​m​a​i​n
 			 */
 			Tip: string
+
+			/**
+			 * B​a​n​d​w​i​d​t​h​ ​L​i​m​i​t
+			 */
+			BandwidthLimit: string
+			/**
+			 * U​n​l​i​m​i​t​e​d
+			 */
+			Unlimited: string
 		}
 	}
 	FileType: {
@@ -514,6 +617,22 @@ export type TranslationFunctions = {
 		 * Select Primary Domain
 		 */
 		SelectPrimaryDomain: () => LocalizedString
+		/**
+		 * Connecting
+		 */
+		SignalingConnecting: () => LocalizedString
+		/**
+		 * Online
+		 */
+		SignalingConnected: () => LocalizedString
+		/**
+		 * Reconnecting
+		 */
+		SignalingReconnecting: () => LocalizedString
+		/**
+		 * Offline
+		 */
+		SignalingDisconnected: () => LocalizedString
 	}
 	LAN: {
 		/**
@@ -667,6 +786,40 @@ export type TranslationFunctions = {
 			 * Time Cost
 			 */
 			TableTimeCost: () => LocalizedString
+			/**
+			 * Verify
+			 */
+			Verify: () => LocalizedString
+			/**
+			 * All transferred files are complete and intact
+			 */
+			VerifyIntact: () => LocalizedString
+			/**
+			 * Some transferred files are missing or corrupted
+			 */
+			VerifyBroken: () => LocalizedString
+		}
+		Session: {
+			/**
+			 * Connecting...
+			 */
+			Connecting: () => LocalizedString
+			/**
+			 * Handshaking...
+			 */
+			Handshaking: () => LocalizedString
+			/**
+			 * Negotiating...
+			 */
+			Negotiating: () => LocalizedString
+			/**
+			 * Reconnecting...
+			 */
+			Reconnecting: () => LocalizedString
+			/**
+			 * Session closed
+			 */
+			Closed: () => LocalizedString
 		}
 	}
 	Dialogs: {
@@ -680,6 +833,28 @@ export type TranslationFunctions = {
 			 */
 			Version: () => LocalizedString
 		}
+		VisitRequest: {
+			/**
+			 * Visit Request
+			 */
+			Title: () => LocalizedString
+			/**
+			 * Device
+			 */
+			ContentPrefix: () => LocalizedString
+			/**
+			 * wants to visit your
+			 */
+			ContentSuffix: () => LocalizedString
+			/**
+			 * Desktop
+			 */
+			Desktop: () => LocalizedString
+			/**
+			 * Files
+			 */
+			Files: () => LocalizedString
+		}
 		VisitPrepare: {
 			/**
 			 * Please input this device's password
@@ -749,6 +924,28 @@ export type TranslationFunctions = {
 			 * Remarks
 			 */
 			Remarks: () => LocalizedString
+			RelayTls: {
+				/**
+				 * Relay TLS
+				 */
+				Label: () => LocalizedString
+				/**
+				 * Connections to the relay of this domain are encrypted with TLS once the SHA-256 fingerprint of the relay certificate is set. Client certificate and its key are optional, they are presented when the relay requires one.
+				 */
+				Tooltip: () => LocalizedString
+				/**
+				 * Relay certificate SHA-256 fingerprint
+				 */
+				Fingerprint: () => LocalizedString
+				/**
+				 * Client certificate (PEM, optional)
+				 */
+				ClientCert: () => LocalizedString
+				/**
+				 * Client private key (PEM, optional)
+				 */
+				ClientKey: () => LocalizedString
+			}
 			/**
 			 * Delete
 			 */
@@ -805,6 +1002,15 @@ export type TranslationFunctions = {
 			 * Automatically Switch Domain
 			 */
 			Tip: () => LocalizedString
+
+			/**
+			 * Bandwidth Limit
+			 */
+			BandwidthLimit: () => LocalizedString
+			/**
+			 * Unlimited
+			 */
+			Unlimited: () => LocalizedString
 		}
 	}
 	FileType: {
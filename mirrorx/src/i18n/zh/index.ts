@@ -26,7 +26,11 @@ const zh_Hans: Translation = {
 		GenerateRandomPasswordTooltip: '生成随机密码',
 		DomainActions: '域操作',
 		DomainActionsEdit: '编辑',
-		SelectPrimaryDomain: '选择主域'
+		SelectPrimaryDomain: '选择主域',
+		SignalingConnecting: '连接中',
+		SignalingConnected: '在线',
+		SignalingReconnecting: '重新连接中',
+		SignalingDisconnected: '离线'
 	},
 	LAN: {
 		HostnameOrIP: '搜索主机名或IP（大小写敏感）',
@@ -69,7 +73,17 @@ const zh_Hans: Translation = {
 			TableStatus: '状态',
 			TableTotalSize: '总大小',
 			TableFinishAt: '完成于',
-			TableTimeCost: '耗时'
+			TableTimeCost: '耗时',
+			Verify: '校验',
+			VerifyIntact: '所有传输的文件均完整无误',
+			VerifyBroken: '部分传输的文件缺失或已损坏'
+		},
+		Session: {
+			Connecting: '连接中...',
+			Handshaking: '握手中...',
+			Negotiating: '协商中...',
+			Reconnecting: '重连中...',
+			Closed: '会话已关闭'
 		}
 	},
 	Dialogs: {
@@ -77,6 +91,13 @@ const zh_Hans: Translation = {
 			Copy: '复制',
 			Version: '版本'
 		},
+		VisitRequest: {
+			Title: '访问请求',
+			ContentPrefix: '设备',
+			ContentSuffix: '请求访问您的',
+			Desktop: '桌面',
+			Files: '文件'
+		},
 		VisitPrepare: {
 			Content: '请输入该设备的密码'
 		},
@@ -104,6 +125,13 @@ const zh_Hans: Translation = {
 				Tooltip: `指纹是一串在你连接到新的域时在本地随机生成的字符串。它用来证明你的设备有权利持有域分配给你的设备ID一段时间并且不会被用来追踪你的设备。`
 			},
 			Remarks: '备注',
+			RelayTls: {
+				Label: '中继TLS',
+				Tooltip: `设置中继证书的SHA-256指纹后，与该域中继的连接将使用TLS加密。客户端证书及其私钥是可选的，当中继要求时会提供给中继。`,
+				Fingerprint: '中继证书SHA-256指纹',
+				ClientCert: '客户端证书（PEM，可选）',
+				ClientKey: '客户端私钥（PEM，可选）'
+			},
 			Delete: '删除',
 			Edit: '修改'
 		},
@@ -123,7 +151,9 @@ const zh_Hans: Translation = {
 			ContentSuffix: `吗？一旦你删除将无法恢复！`
 		},
 		HistoryConnect: {
-			Tip: '自动切换域'
+			Tip: '自动切换域',
+			BandwidthLimit: '带宽限制',
+			Unlimited: '不限'
 		}
 	},
 	Settings: {
@@ -0,0 +1,82 @@
+//! AES-256-GCM which the endpoint streams seal and open every message with, over the
+//! sizes of input messages, small video frames and key frames.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mirrorx_core::utility::nonce_value::NonceValue;
+use ring::aead::{Aad, BoundKey, OpeningKey, SealingKey, UnboundKey, AES_256_GCM, NONCE_LEN};
+
+const SIZES: [usize; 4] = [256, 16 * 1024, 128 * 1024, 512 * 1024];
+
+const KEY: [u8; 32] = [7u8; 32];
+const NONCE: [u8; NONCE_LEN] = [3u8; NONCE_LEN];
+
+fn sealing_key() -> SealingKey<NonceValue> {
+    SealingKey::new(
+        UnboundKey::new(&AES_256_GCM, &KEY).unwrap(),
+        NonceValue::new(NONCE),
+    )
+}
+
+fn opening_key() -> OpeningKey<NonceValue> {
+    OpeningKey::new(
+        UnboundKey::new(&AES_256_GCM, &KEY).unwrap(),
+        NonceValue::new(NONCE),
+    )
+}
+
+fn payload(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+fn seal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("aead/seal");
+
+    for size in SIZES {
+        let payload = payload(size);
+        let mut sealing_key = sealing_key();
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &payload, |b, payload| {
+            b.iter(|| {
+                let mut buffer = payload.clone();
+                sealing_key
+                    .seal_in_place_append_tag(Aad::empty(), &mut buffer)
+                    .unwrap();
+                buffer
+            })
+        });
+    }
+
+    group.finish();
+}
+
+// nonces of both keys advance together like the two ends of a stream, so every sealed
+// buffer is opened by the key of the other end
+fn seal_open(c: &mut Criterion) {
+    let mut group = c.benchmark_group("aead/seal_open");
+
+    for size in SIZES {
+        let payload = payload(size);
+        let mut sealing_key = sealing_key();
+        let mut opening_key = opening_key();
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &payload, |b, payload| {
+            b.iter(|| {
+                let mut buffer = payload.clone();
+                sealing_key
+                    .seal_in_place_append_tag(Aad::empty(), &mut buffer)
+                    .unwrap();
+                opening_key
+                    .open_in_place(Aad::empty(), &mut buffer)
+                    .unwrap()
+                    .len()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, seal, seal_open);
+criterion_main!(benches);
@@ -0,0 +1,128 @@
+//! Per frame work on NV12 before encoding: quality filters, watermark and stitching of
+//! spanned monitors. Frames are drawn by the test pattern source.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use mirrorx_core::{
+    api::endpoint::{id::EndPointID, message::StreamQuality},
+    component::{
+        desktop::{
+            span::FrameStitcher,
+            test_pattern::{test_pattern_monitor, TestPattern, TestPatternGenerator},
+        },
+        video_encoder::{filter::filter_frame, watermark::Watermark},
+    },
+};
+use std::time::Duration;
+
+const SIZES: [(u16, u16); 2] = [(1280, 720), (1920, 1080)];
+
+fn generator(width: u16, height: u16) -> TestPatternGenerator {
+    TestPatternGenerator::new(TestPattern {
+        width,
+        height,
+        frame_rate: 30,
+    })
+}
+
+fn nv12_size(width: u16, height: u16) -> u64 {
+    width as u64 * height as u64 * 3 / 2
+}
+
+fn filter(c: &mut Criterion) {
+    let mut group = c.benchmark_group("filter");
+
+    for (width, height) in SIZES {
+        let generator = generator(width, height);
+        group.throughput(Throughput::Bytes(nv12_size(width, height)));
+
+        for quality in [StreamQuality::ReducedChroma, StreamQuality::Grayscale] {
+            group.bench_with_input(
+                BenchmarkId::new(format!("{:?}", quality), format!("{}x{}", width, height)),
+                &quality,
+                |b, &quality| {
+                    b.iter_batched(
+                        || generator.draw(Duration::ZERO),
+                        |mut frame| {
+                            filter_frame(&mut frame, quality);
+                            frame
+                        },
+                        BatchSize::LargeInput,
+                    )
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn watermark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("watermark");
+    let endpoint_id = EndPointID::DeviceID {
+        local_device_id: 1_000_000_001,
+        remote_device_id: 1_000_000_002,
+    };
+
+    for (width, height) in SIZES {
+        let generator = generator(width, height);
+        let mut watermark = Watermark::new(&endpoint_id);
+        group.throughput(Throughput::Bytes(nv12_size(width, height)));
+
+        group.bench_function(format!("{}x{}", width, height), |b| {
+            b.iter_batched(
+                || generator.draw(Duration::ZERO),
+                |mut frame| {
+                    watermark.apply(&mut frame);
+                    frame
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+fn stitch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("stitch");
+
+    for (width, height) in SIZES {
+        let generator = generator(width, height);
+        let pattern = TestPattern {
+            width,
+            height,
+            frame_rate: 30,
+        };
+
+        // two monitors side by side
+        let left = test_pattern_monitor(&pattern);
+        let mut right = test_pattern_monitor(&pattern);
+        right.id.push_str("_right");
+        right.left = width;
+        right.is_primary = false;
+
+        let mut stitcher = FrameStitcher::new(vec![left, right]);
+        group.throughput(Throughput::Bytes(nv12_size(width, height) * 2));
+
+        group.bench_function(format!("2x{}x{}", width, height), |b| {
+            b.iter_batched(
+                || {
+                    (
+                        generator.draw(Duration::ZERO),
+                        generator.draw(Duration::ZERO),
+                    )
+                },
+                |(left, right)| {
+                    stitcher.update(0, left).unwrap();
+                    stitcher.update(1, right).unwrap()
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, filter, watermark, stitch);
+criterion_main!(benches);
@@ -0,0 +1,67 @@
+//! The encode→decode loop of a desktop session on frames of the test pattern source,
+//! without network and rendering. Frames advance in time so the encoder sees motion
+//! like a real stream rather than one picture repeated.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use mirrorx_core::{
+    api::endpoint::message::EndPointVideoFrame,
+    component::{
+        desktop::test_pattern::{TestPattern, TestPatternGenerator},
+        video_decoder::decoder::VideoDecoder,
+        video_encoder::{config::libx264::Libx264Config, encoder::VideoEncoder},
+    },
+};
+use std::time::Duration;
+
+fn encode_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pipeline");
+    // every iteration encodes a whole frame, keep the sample count low
+    group.sample_size(30);
+    group.throughput(Throughput::Elements(1));
+
+    for (width, height) in [(1280, 720), (1920, 1080)] {
+        let pattern = TestPattern {
+            width,
+            height,
+            frame_rate: 30,
+        };
+        let frame_interval = Duration::from_secs(1) / pattern.frame_rate as u32;
+        let generator = TestPatternGenerator::new(pattern);
+
+        let (video_frame_tx, video_frame_rx) = std::sync::mpsc::channel::<EndPointVideoFrame>();
+        let mut encoder = VideoEncoder::new(Libx264Config::default(), video_frame_tx).unwrap();
+
+        let (render_frame_tx, mut render_frame_rx) = tokio::sync::mpsc::channel(180);
+        let mut decoder = VideoDecoder::new(render_frame_tx);
+
+        let mut capture_time = Duration::ZERO;
+
+        group.bench_function(format!("libx264/{}x{}", width, height), |b| {
+            b.iter_batched(
+                || {
+                    capture_time += frame_interval;
+                    generator.draw(capture_time)
+                },
+                |frame| {
+                    encoder.encode(frame).unwrap();
+
+                    while let Ok(video_frame) = video_frame_rx.try_recv() {
+                        decoder.decode(video_frame).unwrap();
+                    }
+
+                    let mut decoded = 0;
+                    while render_frame_rx.try_recv().is_ok() {
+                        decoded += 1;
+                    }
+                    decoded
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, encode_decode);
+criterion_main!(benches);
@@ -0,0 +1,75 @@
+//! Serialization of video frame messages in both wire formats.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use mirrorx_core::{
+    api::endpoint::{
+        client::WireFormat,
+        message::{EndPointMessage, EndPointVideoFrame},
+    },
+    utility::bincode::{bincode_deserialize, bincode_serialize},
+};
+
+const SIZES: [usize; 3] = [16 * 1024, 128 * 1024, 512 * 1024];
+
+fn video_frame(size: usize) -> EndPointMessage {
+    EndPointMessage::VideoFrame(EndPointVideoFrame {
+        width: 1920,
+        height: 1080,
+        pts: 1_234_567,
+        buffer: (0..size).map(|i| (i * 31 % 251) as u8).collect(),
+        timestamp_micros: 1_672_531_200_000_000,
+    })
+}
+
+fn bincode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bincode");
+
+    for size in SIZES {
+        let message = video_frame(size);
+        let buffer = bincode_serialize(&message).unwrap();
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("serialize", size),
+            &message,
+            |b, message| b.iter(|| bincode_serialize(message).unwrap()),
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("deserialize", size),
+            &buffer,
+            |b, buffer| b.iter(|| bincode_deserialize::<EndPointMessage>(buffer).unwrap()),
+        );
+    }
+
+    group.finish();
+}
+
+fn wire_format(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wire_format");
+
+    for format in [WireFormat::Bincode, WireFormat::Cbor] {
+        for size in SIZES {
+            let message = video_frame(size);
+            let buffer = format.serialize(&message).unwrap();
+            group.throughput(Throughput::Bytes(size as u64));
+
+            group.bench_with_input(
+                BenchmarkId::new(format!("{:?}/serialize", format), size),
+                &message,
+                |b, message| b.iter(|| format.serialize(message).unwrap()),
+            );
+
+            group.bench_with_input(
+                BenchmarkId::new(format!("{:?}/deserialize", format), size),
+                &buffer,
+                |b, buffer| b.iter(|| format.deserialize::<EndPointMessage>(buffer).unwrap()),
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bincode, wire_format);
+criterion_main!(benches);
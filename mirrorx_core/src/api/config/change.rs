@@ -0,0 +1,199 @@
+//! Settings used to be applied by whoever wrote them, so those which live components hold
+//! took effect only after restarting the app or reconnecting. Repositories publish every
+//! change of them here after it's written, and components subscribe to apply them live.
+
+use super::LocalStorage;
+use crate::{
+    api::{
+        endpoint::{
+            acl::set_acl_rules,
+            client::socket::{set_transport_tuning, TransportTuning},
+            message::{GopPolicy, SessionProfile},
+            permission::set_view_only_by_default,
+            queue::set_max_sessions,
+        },
+        signaling::visit_prompt::{set_confirm_visit, set_reject_message},
+    },
+    component::{
+        availability::{set_availability, Availability},
+        desktop::idle::set_idle_frame_rate,
+        fs::{
+            receive_policy::{set_receive_policy, ReceivePolicy},
+            staging::{set_staging_quota, set_staging_retention_days},
+        },
+        governor::priority::{
+            parse_cores, set_encoder_cores, set_encoder_thread_priority, ThreadPriority,
+        },
+        lan::{key::set_lan_key, keystore::set_paired_peers},
+        power::{keep_awake::set_keep_awake_enabled, set_power_saving_enabled},
+        quality_alert::set_quality_auto_tune,
+        session_profile::set_session_profile,
+        telemetry,
+        video_decoder::decoder::{set_gop_policy, set_tile_codec_preferred},
+    },
+    utility::net::set_bind_ip,
+};
+use async_broadcast::{InactiveReceiver, Receiver, RecvError, Sender};
+use once_cell::sync::Lazy;
+use std::{net::IpAddr, sync::Mutex};
+use tokio::task::JoinHandle;
+
+#[cfg(feature = "host")]
+use crate::component::{
+    desktop::test_pattern::set_test_pattern, unattended_login::set_unattended_login_enabled,
+    video_encoder::watermark::set_watermark_enabled,
+};
+
+#[derive(Clone)]
+pub enum ConfigChange {
+    PowerSaving(bool),
+    KeepAwake(bool),
+    ViewOnly(bool),
+    VisitConfirm(bool),
+    RejectMessage(Option<String>),
+    UnattendedLogin(bool),
+    Availability(Availability),
+    MaxSessions(u32),
+    IdleFrameRate(u8),
+    Watermark(bool),
+    TileCodecPreferred(bool),
+    GopPolicy(GopPolicy),
+    SessionProfile(SessionProfile),
+    QualityAutoTune(bool),
+    TestPattern(Option<String>),
+    EncoderThreadPriority(ThreadPriority),
+    EncoderCores(Option<String>),
+    Telemetry(bool),
+    StagingQuotaMb(u32),
+    StagingRetentionDays(u32),
+    LanKey(Option<String>),
+    LanOnly(bool),
+    LanDiscoverable(bool),
+    LanListenIp(Option<IpAddr>),
+    BindIp(Option<IpAddr>),
+    LogFilter(Option<String>),
+    TransportTuning(TransportTuning),
+    ReceivePolicy(ReceivePolicy),
+    // subscribers read the rules from storage again
+    AclRules,
+    // subscribers read the paired peers from storage again
+    PairedPeers,
+    PeerBandwidthLimit {
+        domain: String,
+        device_id: i64,
+        max_kbps: Option<u32>,
+    },
+}
+
+static CONFIG_CHANGE_CHANNEL: Lazy<(Sender<ConfigChange>, InactiveReceiver<ConfigChange>)> =
+    Lazy::new(|| {
+        let (mut tx, rx) = async_broadcast::broadcast(64);
+        tx.set_overflow(true);
+        (tx, rx.deactivate())
+    });
+
+static APPLY_HANDLE: Lazy<Mutex<Option<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(None));
+
+/// Publish the change to all active subscribers, it's dropped silently when nobody
+/// subscribes.
+pub fn publish(change: ConfigChange) {
+    let _ = CONFIG_CHANGE_CHANNEL.0.try_broadcast(change);
+}
+
+pub fn subscribe() -> Receiver<ConfigChange> {
+    CONFIG_CHANGE_CHANNEL.1.activate_cloned()
+}
+
+/// Start (or restart) applying the changes of the settings which core components read
+/// from globals. Settings held by live components, like LAN discoverability and peer
+/// bandwidth limits, are applied by the components themselves, and the log filter by
+/// the app which owns the subscriber.
+pub fn serve_config_changes(storage: LocalStorage) {
+    let mut rx = subscribe();
+
+    let handle = tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(change) => apply_change(&storage, change),
+                Err(RecvError::Overflowed(skipped)) => {
+                    tracing::warn!(skipped, "config changes overflowed, some aren't applied");
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    });
+
+    if let Some(previous) = APPLY_HANDLE.lock().unwrap().replace(handle) {
+        previous.abort();
+    }
+}
+
+fn apply_change(storage: &LocalStorage, change: ConfigChange) {
+    match change {
+        ConfigChange::PowerSaving(enabled) => set_power_saving_enabled(enabled),
+        ConfigChange::KeepAwake(enabled) => set_keep_awake_enabled(enabled),
+        // only affects the sessions created later
+        ConfigChange::ViewOnly(enabled) => set_view_only_by_default(enabled),
+        ConfigChange::VisitConfirm(enabled) => set_confirm_visit(enabled),
+        ConfigChange::RejectMessage(message) => set_reject_message(message),
+        #[cfg(feature = "host")]
+        ConfigChange::UnattendedLogin(enabled) => set_unattended_login_enabled(enabled),
+        // viewer builds never sign in for others
+        #[cfg(not(feature = "host"))]
+        ConfigChange::UnattendedLogin(_) => {}
+        // signaling servers and LAN devices are told by availability subscribers
+        ConfigChange::Availability(availability) => set_availability(availability),
+        ConfigChange::MaxSessions(max_sessions) => set_max_sessions(max_sessions as usize),
+        ConfigChange::IdleFrameRate(frame_rate) => set_idle_frame_rate(frame_rate),
+        #[cfg(feature = "host")]
+        ConfigChange::Watermark(enabled) => set_watermark_enabled(enabled),
+        // viewer builds never stream their desktop
+        #[cfg(not(feature = "host"))]
+        ConfigChange::Watermark(_) => {}
+        // only affects the sessions negotiated later
+        ConfigChange::TileCodecPreferred(preferred) => set_tile_codec_preferred(preferred),
+        // only affects the sessions negotiated later
+        ConfigChange::GopPolicy(policy) => set_gop_policy(policy),
+        // only affects the sessions negotiated later
+        ConfigChange::SessionProfile(profile) => set_session_profile(profile),
+        // open sessions apply it to the alerts raised later
+        ConfigChange::QualityAutoTune(enabled) => set_quality_auto_tune(enabled),
+        // the stored pattern is validated when it's set
+        #[cfg(feature = "host")]
+        ConfigChange::TestPattern(pattern) => {
+            set_test_pattern(pattern.and_then(|pattern| pattern.parse().ok()))
+        }
+        #[cfg(not(feature = "host"))]
+        ConfigChange::TestPattern(_) => {}
+        // capture and encode threads started later pick them up
+        ConfigChange::EncoderThreadPriority(priority) => set_encoder_thread_priority(priority),
+        // the stored cores are validated when they're set
+        ConfigChange::EncoderCores(cores) => {
+            set_encoder_cores(cores.and_then(|cores| parse_cores(&cores).ok()))
+        }
+        ConfigChange::Telemetry(enabled) => telemetry::set_telemetry_enabled(enabled),
+        ConfigChange::StagingQuotaMb(quota_mb) => set_staging_quota(quota_mb as u64 * 1024 * 1024),
+        ConfigChange::StagingRetentionDays(days) => set_staging_retention_days(days),
+        ConfigChange::LanKey(lan_key) => set_lan_key(lan_key),
+        // signaling clients and LAN providers created later pick it up, connections are
+        // bound on it from now on
+        ConfigChange::BindIp(bind_ip) => set_bind_ip(bind_ip),
+        // connections made later pick it up
+        ConfigChange::TransportTuning(tuning) => set_transport_tuning(tuning),
+        // transfers requested later pick it up
+        ConfigChange::ReceivePolicy(policy) => set_receive_policy(policy),
+        ConfigChange::AclRules => match storage.acl().get_rules() {
+            Ok(rules) => set_acl_rules(rules),
+            Err(err) => tracing::error!(?err, "reload acl rules failed"),
+        },
+        ConfigChange::PairedPeers => match storage.keystore().get_peers() {
+            Ok(peers) => set_paired_peers(peers),
+            Err(err) => tracing::error!(?err, "reload paired peers failed"),
+        },
+        ConfigChange::LanOnly(_)
+        | ConfigChange::LanDiscoverable(_)
+        | ConfigChange::LanListenIp(_)
+        | ConfigChange::LogFilter(_)
+        | ConfigChange::PeerBandwidthLimit { .. } => {}
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{
+    api::config::change::{publish, ConfigChange},
+    core_error,
+    error::CoreResult,
+};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, Row};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AclAction {
+    Allow,
+    Deny,
+}
+
+impl<'a> From<AclAction> for &'a str {
+    fn from(val: AclAction) -> Self {
+        match val {
+            AclAction::Allow => "allow",
+            AclAction::Deny => "deny",
+        }
+    }
+}
+
+impl FromStr for AclAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(AclAction::Allow),
+            "deny" => Ok(AclAction::Deny),
+            _ => Err(String::from("Unknown acl action")),
+        }
+    }
+}
+
+/// What an ACL rule matches. Device rules match visits relayed by signaling server, subnet
+/// rules match visits from LAN since they're the only ones whose source is known.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum AclMatcher {
+    DeviceId(i64),
+    /// Leading digits of the 10 digits device id, like `12` or `123456`.
+    DeviceIdPrefix(String),
+    /// Source subnet in CIDR notation, like `192.168.1.0/24`.
+    Subnet(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AclRule {
+    pub id: i64,
+    pub action: AclAction,
+    pub matcher: AclMatcher,
+}
+
+pub struct AclRepository {
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl AclRepository {
+    pub fn new(pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { pool }
+    }
+
+    pub fn ensure_table(&self) -> CoreResult<()> {
+        const COMMAND: &str = r"
+        CREATE TABLE IF NOT EXISTS acl_rules(
+            id INTEGER PRIMARY KEY,
+            action TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            value TEXT NOT NULL
+        )";
+
+        let _ = self.pool.get()?.execute(COMMAND, [])?;
+
+        Ok(())
+    }
+
+    pub fn get_rules(&self) -> CoreResult<Vec<AclRule>> {
+        const COMMAND: &str = r"SELECT id, action, kind, value FROM acl_rules ORDER BY id ASC";
+
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(COMMAND)?;
+        let rows = stmt.query_and_then([], parse_rule)?;
+
+        let mut rules = Vec::new();
+        for row in rows {
+            rules.push(row?);
+        }
+
+        Ok(rules)
+    }
+
+    pub fn add_rule(&self, action: AclAction, matcher: &AclMatcher) -> CoreResult<i64> {
+        const COMMAND: &str = r"INSERT INTO acl_rules(action, kind, value) VALUES(?, ?, ?)";
+
+        let action: &str = action.into();
+        let (kind, value) = match matcher {
+            AclMatcher::DeviceId(device_id) => ("device_id", device_id.to_string()),
+            AclMatcher::DeviceIdPrefix(prefix) => ("device_id_prefix", prefix.clone()),
+            AclMatcher::Subnet(subnet) => ("subnet", subnet.clone()),
+        };
+
+        let conn = self.pool.get()?;
+        let _ = conn.execute(COMMAND, params![action, kind, value])?;
+        let id = conn.last_insert_rowid();
+
+        publish(ConfigChange::AclRules);
+
+        Ok(id)
+    }
+
+    pub fn delete_rule(&self, id: i64) -> CoreResult<()> {
+        const COMMAND: &str = r"DELETE FROM acl_rules WHERE id = ?";
+
+        let _ = self.pool.get()?.execute(COMMAND, params![id])?;
+
+        publish(ConfigChange::AclRules);
+
+        Ok(())
+    }
+}
+
+fn parse_rule(row: &Row) -> CoreResult<AclRule> {
+    let action: String = row.get(1)?;
+    let kind: String = row.get(2)?;
+    let value: String = row.get(3)?;
+
+    let matcher = match kind.as_str() {
+        "device_id" => AclMatcher::DeviceId(
+            value
+                .parse()
+                .map_err(|_| core_error!("invalid acl device id ({})", value))?,
+        ),
+        "device_id_prefix" => AclMatcher::DeviceIdPrefix(value),
+        "subnet" => AclMatcher::Subnet(value),
+        _ => return Err(core_error!("Unknown acl rule kind ({})", kind)),
+    };
+
+    Ok(AclRule {
+        id: row.get(0)?,
+        action: AclAction::from_str(&action).map_err(|err| core_error!("{}", err))?,
+        matcher,
+    })
+}
@@ -1,8 +1,12 @@
-use crate::error::{CoreError, CoreResult};
+use crate::{
+    api::config::key_store::{domain_password_key, KeyStore, KeyStoreBackend},
+    error::{CoreError, CoreResult},
+};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::{params, OptionalExtension, Row};
 use serde::Serialize;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Domain {
@@ -20,11 +24,13 @@ pub struct Domain {
 
 pub struct DomainRepository {
     pool: Pool<SqliteConnectionManager>,
+    // device passwords are kept in it, the password column is left empty
+    key_store: Arc<dyn KeyStore>,
 }
 
 impl DomainRepository {
-    pub fn new(pool: Pool<SqliteConnectionManager>) -> Self {
-        Self { pool }
+    pub fn new(pool: Pool<SqliteConnectionManager>, key_store: Arc<dyn KeyStore>) -> Self {
+        Self { pool, key_store }
     }
 
     pub fn ensure_table(&self) -> CoreResult<()> {
@@ -74,7 +80,7 @@ impl DomainRepository {
                 domain.subscribe_port,
                 domain.is_primary,
                 domain.device_id,
-                domain.password,
+                "",
                 domain.finger_print,
                 domain.remarks,
             ],
@@ -82,15 +88,26 @@ impl DomainRepository {
 
         domain.id = conn.last_insert_rowid();
 
+        if let Err(err) = self
+            .key_store
+            .set(&domain_password_key(domain.id), &domain.password)
+        {
+            self.delete_domain(domain.id)?;
+            return Err(err);
+        }
+
         Ok(domain)
     }
 
     pub fn get_primary_domain(&self) -> CoreResult<Domain> {
         const COMMAND: &str = r"SELECT * FROM domains WHERE is_primary = 1 LIMIT 1";
 
-        self.pool
+        let domain = self
+            .pool
             .get()?
-            .query_row_and_then(COMMAND, [], parse_domain)
+            .query_row_and_then(COMMAND, [], parse_domain)?;
+
+        self.with_password(domain)
     }
 
     pub fn domain_exist(&self, name: &str) -> CoreResult<bool> {
@@ -130,7 +147,7 @@ impl DomainRepository {
             .get()?
             .query_row_and_then(COMMAND, [name], parse_domain)?;
 
-        Ok(domain)
+        self.with_password(domain)
     }
 
     pub fn get_domain_by_id(&self, domain_id: i64) -> CoreResult<Domain> {
@@ -141,7 +158,7 @@ impl DomainRepository {
             .get()?
             .query_row_and_then(COMMAND, [domain_id], parse_domain)?;
 
-        Ok(domain)
+        self.with_password(domain)
     }
 
     pub fn get_domains(&self, page: u32, limit: u32) -> CoreResult<(u32, Vec<Domain>)> {
@@ -159,12 +176,27 @@ impl DomainRepository {
 
         let mut domains = Vec::new();
         for row in rows {
-            domains.push(row?);
+            domains.push(self.with_password(row?)?);
         }
 
         Ok((count, domains))
     }
 
+    pub fn get_all_domains(&self) -> CoreResult<Vec<Domain>> {
+        const COMMAND: &str = r"SELECT * FROM domains";
+
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(COMMAND)?;
+        let rows = stmt.query_and_then([], parse_domain)?;
+
+        let mut domains = Vec::new();
+        for row in rows {
+            domains.push(self.with_password(row?)?);
+        }
+
+        Ok(domains)
+    }
+
     pub fn get_domain_count(&self) -> CoreResult<u32> {
         const COMMAND: &str = r"SELECT COUNT(*) FROM domains";
         self.pool
@@ -205,11 +237,12 @@ impl DomainRepository {
     }
 
     pub fn set_domain_device_password(&self, domain_id: i64, password: &str) -> CoreResult<()> {
-        const COMMAND: &str = r"UPDATE domains SET password = ? WHERE id =?";
+        const COMMAND: &str = r"UPDATE domains SET password = '' WHERE id =?";
 
-        self.pool
-            .get()?
-            .execute(COMMAND, params![password, domain_id])?;
+        self.key_store
+            .set(&domain_password_key(domain_id), password)?;
+
+        self.pool.get()?.execute(COMMAND, [domain_id])?;
 
         Ok(())
     }
@@ -228,9 +261,53 @@ impl DomainRepository {
         const COMMAND: &str = r"DELETE FROM domains WHERE id = ?";
 
         self.pool.get()?.execute(COMMAND, [domain_id])?;
+        self.key_store.delete(&domain_password_key(domain_id))?;
 
         Ok(())
     }
+
+    /// Move the device passwords which the domains table still keeps into the secure store
+    /// of OS, the column is cleared once they're moved.
+    pub fn migrate_secrets(&self) -> CoreResult<()> {
+        const COMMAND: &str = r"SELECT id, password FROM domains WHERE password <> ''";
+        const CLEAR_COMMAND: &str = r"UPDATE domains SET password = '' WHERE id = ?";
+
+        if self.key_store.backend() == KeyStoreBackend::File {
+            return Ok(());
+        }
+
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(COMMAND)?;
+        let rows = stmt.query_and_then([], |row| -> CoreResult<(i64, String)> {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+
+        for row in rows {
+            let (domain_id, password) = row?;
+            let key = domain_password_key(domain_id);
+
+            // the one in the secure store wins, the file may be restored from a backup
+            if self.key_store.get(&key)?.is_none() {
+                self.key_store.set(&key, &password)?;
+            }
+
+            conn.execute(CLEAR_COMMAND, [domain_id])?;
+
+            tracing::info!(domain_id, "move domain device password into os key store");
+        }
+
+        Ok(())
+    }
+
+    // the password column is only read when it's not moved into the key store, like when
+    // the secure store of OS isn't available
+    fn with_password(&self, mut domain: Domain) -> CoreResult<Domain> {
+        if let Some(password) = self.key_store.get(&domain_password_key(domain.id))? {
+            domain.password = password;
+        }
+
+        Ok(domain)
+    }
 }
 
 fn parse_domain(row: &Row) -> CoreResult<Domain> {
@@ -1,9 +1,12 @@
 use crate::error::CoreResult;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{params, Row};
+use rusqlite::{params, OptionalExtension, Row};
 use serde::Serialize;
 
+// the recency of a visit counts half after a week
+const RECENCY_HALF_LIFE_SECS: f64 = 7.0 * 24.0 * 60.0 * 60.0;
+
 #[derive(Debug, Clone, Serialize)]
 pub struct Record {
     pub id: i64,
@@ -12,6 +15,45 @@ pub struct Record {
     pub timestamp: i64,
 }
 
+/// The record with what ranks it in the reconnect list.
+#[derive(Debug, Clone, Serialize)]
+pub struct RankedRecord {
+    #[serde(flatten)]
+    pub record: Record,
+    pub visit_count: u32,
+    pub pinned: bool,
+    pub favorite: bool,
+    /// Whether the device was online when it was visited last time, None when it's never
+    /// been told.
+    pub online: Option<bool>,
+    pub online_timestamp: Option<i64>,
+    pub score: f64,
+}
+
+/// The path which a connection to the remote device went through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectPath {
+    Lan,
+    Relay,
+}
+
+impl ConnectPath {
+    fn as_str(self) -> &'static str {
+        match self {
+            ConnectPath::Lan => "lan",
+            ConnectPath::Relay => "relay",
+        }
+    }
+
+    fn parse(value: &str) -> Option<ConnectPath> {
+        match value {
+            "lan" => Some(ConnectPath::Lan),
+            "relay" => Some(ConnectPath::Relay),
+            _ => None,
+        }
+    }
+}
+
 pub struct HistoryRepository {
     pool: Pool<SqliteConnectionManager>,
 }
@@ -39,18 +81,45 @@ impl HistoryRepository {
 
         conn.execute(CREATE_UNIQUE_INDEX_COMMAND, [])?;
 
+        const CREATE_PATH_TABLE_COMMAND: &str = r"
+        CREATE TABLE IF NOT EXISTS history_paths(
+            device_id INTEGER NOT NULL,
+            domain TEXT NOT NULL,
+            path TEXT NOT NULL,
+            PRIMARY KEY(device_id, domain)
+        )";
+
+        conn.execute(CREATE_PATH_TABLE_COMMAND, [])?;
+
+        const CREATE_STATS_TABLE_COMMAND: &str = r"
+        CREATE TABLE IF NOT EXISTS history_stats(
+            device_id INTEGER NOT NULL,
+            domain TEXT NOT NULL,
+            visit_count INTEGER NOT NULL DEFAULT 0,
+            pinned INTEGER NOT NULL DEFAULT 0,
+            favorite INTEGER NOT NULL DEFAULT 0,
+            online INTEGER,
+            online_timestamp INTEGER,
+            PRIMARY KEY(device_id, domain)
+        )";
+
+        conn.execute(CREATE_STATS_TABLE_COMMAND, [])?;
+
         Ok(())
     }
 
     pub fn create(&self, device_id: i64, domain: &str) -> CoreResult<()> {
         const COMMAND: &str = r"INSERT INTO history(device_id, domain, timestamp) VALUES(?, ?, ?) ON CONFLICT DO UPDATE SET timestamp = ?";
+        const STATS_COMMAND: &str = r"INSERT INTO history_stats(device_id, domain, visit_count, online, online_timestamp) VALUES(?, ?, 1, 1, ?) ON CONFLICT DO UPDATE SET visit_count = visit_count + 1, online = 1, online_timestamp = ?";
 
         let timestamp = chrono::Utc::now().timestamp();
 
-        let _ = self
-            .pool
-            .get()?
-            .execute(COMMAND, params![device_id, domain, timestamp, timestamp])?;
+        let conn = self.pool.get()?;
+        let _ = conn.execute(COMMAND, params![device_id, domain, timestamp, timestamp])?;
+        let _ = conn.execute(
+            STATS_COMMAND,
+            params![device_id, domain, timestamp, timestamp],
+        )?;
 
         Ok(())
     }
@@ -74,10 +143,117 @@ impl HistoryRepository {
         Ok(records)
     }
 
+    /// Records ranked for reconnecting, pinned ones go first and the others follow by how
+    /// recently and frequently they were visited, favorites count double.
+    pub fn query_ranked(&self) -> CoreResult<Vec<RankedRecord>> {
+        const COMMAND: &str = r"
+        SELECT history.*, history_stats.visit_count, history_stats.pinned, history_stats.favorite,
+            history_stats.online, history_stats.online_timestamp
+        FROM history LEFT JOIN history_stats
+            ON history.device_id = history_stats.device_id AND history.domain = history_stats.domain";
+
+        let now = chrono::Utc::now().timestamp();
+
+        let conn = self.pool.get()?;
+
+        let mut stmt = conn.prepare(COMMAND)?;
+        let rows = stmt.query_and_then([], |row| parse_ranked_record(row, now))?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            records.push(row?);
+        }
+
+        records.sort_by(|a, b| {
+            b.pinned
+                .cmp(&a.pinned)
+                .then(b.score.total_cmp(&a.score))
+                .then(b.record.timestamp.cmp(&a.record.timestamp))
+        });
+
+        Ok(records)
+    }
+
+    pub fn set_pinned(&self, device_id: i64, domain: &str, pinned: bool) -> CoreResult<()> {
+        const COMMAND: &str = r"INSERT INTO history_stats(device_id, domain, pinned) VALUES(?, ?, ?) ON CONFLICT DO UPDATE SET pinned = ?";
+
+        let _ = self
+            .pool
+            .get()?
+            .execute(COMMAND, params![device_id, domain, pinned, pinned])?;
+
+        Ok(())
+    }
+
+    pub fn set_favorite(&self, device_id: i64, domain: &str, favorite: bool) -> CoreResult<()> {
+        const COMMAND: &str = r"INSERT INTO history_stats(device_id, domain, favorite) VALUES(?, ?, ?) ON CONFLICT DO UPDATE SET favorite = ?";
+
+        let _ = self
+            .pool
+            .get()?
+            .execute(COMMAND, params![device_id, domain, favorite, favorite])?;
+
+        Ok(())
+    }
+
+    /// Remember whether the device was online when it was visited, successful visits
+    /// record it themselves.
+    pub fn set_online(&self, device_id: i64, domain: &str, online: bool) -> CoreResult<()> {
+        const COMMAND: &str = r"INSERT INTO history_stats(device_id, domain, online, online_timestamp) VALUES(?, ?, ?, ?) ON CONFLICT DO UPDATE SET online = ?, online_timestamp = ?";
+
+        let timestamp = chrono::Utc::now().timestamp();
+
+        let _ = self.pool.get()?.execute(
+            COMMAND,
+            params![device_id, domain, online, timestamp, online, timestamp],
+        )?;
+
+        Ok(())
+    }
+
+    /// Remember the path which won the last connection race, it's tried first next time.
+    pub fn set_connect_path(
+        &self,
+        device_id: i64,
+        domain: &str,
+        path: ConnectPath,
+    ) -> CoreResult<()> {
+        const COMMAND: &str = r"INSERT INTO history_paths(device_id, domain, path) VALUES(?, ?, ?) ON CONFLICT DO UPDATE SET path = ?";
+
+        let _ = self.pool.get()?.execute(
+            COMMAND,
+            params![device_id, domain, path.as_str(), path.as_str()],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_connect_path(
+        &self,
+        device_id: i64,
+        domain: &str,
+    ) -> CoreResult<Option<ConnectPath>> {
+        const COMMAND: &str =
+            r"SELECT path FROM history_paths WHERE device_id = ? AND domain = ? LIMIT 1";
+
+        let path: Option<String> = self
+            .pool
+            .get()?
+            .query_row(COMMAND, params![device_id, domain], |row| row.get(0))
+            .optional()?;
+
+        Ok(path.as_deref().and_then(ConnectPath::parse))
+    }
+
     pub fn delete_domain_related(&self, domain: &str) -> CoreResult<()> {
         const COMMAND: &str = r"DELETE FROM history WHERE domain = ?";
+        const PATH_COMMAND: &str = r"DELETE FROM history_paths WHERE domain = ?";
+        const STATS_COMMAND: &str = r"DELETE FROM history_stats WHERE domain = ?";
 
-        let _ = self.pool.get()?.execute(COMMAND, params![domain])?;
+        let conn = self.pool.get()?;
+        let _ = conn.execute(COMMAND, params![domain])?;
+        let _ = conn.execute(PATH_COMMAND, params![domain])?;
+        let _ = conn.execute(STATS_COMMAND, params![domain])?;
 
         Ok(())
     }
@@ -91,3 +267,29 @@ fn parse_record(row: &Row) -> CoreResult<Record> {
         timestamp: row.get(3)?,
     })
 }
+
+fn parse_ranked_record(row: &Row, now: i64) -> CoreResult<RankedRecord> {
+    let record = parse_record(row)?;
+
+    // records visited before the stats were kept count as one visit
+    let visit_count: Option<u32> = row.get(4)?;
+    let visit_count = visit_count.unwrap_or(1).max(1);
+    let pinned: Option<bool> = row.get(5)?;
+    let favorite: Option<bool> = row.get(6)?;
+    let favorite = favorite.unwrap_or(false);
+
+    let age_secs = (now - record.timestamp).max(0) as f64;
+    let recency = 0.5f64.powf(age_secs / RECENCY_HALF_LIFE_SECS);
+    let frequency = 1.0 + (visit_count as f64).ln();
+    let score = recency * frequency * if favorite { 2.0 } else { 1.0 };
+
+    Ok(RankedRecord {
+        record,
+        visit_count,
+        pinned: pinned.unwrap_or(false),
+        favorite,
+        online: row.get(7)?,
+        online_timestamp: row.get(8)?,
+        score,
+    })
+}
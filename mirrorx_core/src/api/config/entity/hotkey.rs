@@ -0,0 +1,158 @@
+use crate::{core_error, error::CoreResult};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, Row};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum HotkeyAction {
+    ToggleFullscreen,
+    ReleaseKeyboardGrab,
+    DisconnectSession,
+    SwitchMonitor,
+}
+
+impl HotkeyAction {
+    pub const ALL: [HotkeyAction; 4] = [
+        HotkeyAction::ToggleFullscreen,
+        HotkeyAction::ReleaseKeyboardGrab,
+        HotkeyAction::DisconnectSession,
+        HotkeyAction::SwitchMonitor,
+    ];
+
+    pub fn default_accelerator(&self) -> &'static str {
+        match self {
+            HotkeyAction::ToggleFullscreen => "CmdOrCtrl+Alt+Shift+F",
+            HotkeyAction::ReleaseKeyboardGrab => "CmdOrCtrl+Alt+Shift+G",
+            HotkeyAction::DisconnectSession => "CmdOrCtrl+Alt+Shift+Q",
+            HotkeyAction::SwitchMonitor => "CmdOrCtrl+Alt+Shift+M",
+        }
+    }
+}
+
+impl<'a> From<HotkeyAction> for &'a str {
+    fn from(val: HotkeyAction) -> Self {
+        match val {
+            HotkeyAction::ToggleFullscreen => "toggle_fullscreen",
+            HotkeyAction::ReleaseKeyboardGrab => "release_keyboard_grab",
+            HotkeyAction::DisconnectSession => "disconnect_session",
+            HotkeyAction::SwitchMonitor => "switch_monitor",
+        }
+    }
+}
+
+impl FromStr for HotkeyAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "toggle_fullscreen" => Ok(HotkeyAction::ToggleFullscreen),
+            "release_keyboard_grab" => Ok(HotkeyAction::ReleaseKeyboardGrab),
+            "disconnect_session" => Ok(HotkeyAction::DisconnectSession),
+            "switch_monitor" => Ok(HotkeyAction::SwitchMonitor),
+            _ => Err(String::from("Unknown hotkey action")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Hotkey {
+    pub action: HotkeyAction,
+    pub accelerator: String,
+    pub enabled: bool,
+}
+
+pub struct HotkeyRepository {
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl HotkeyRepository {
+    pub fn new(pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { pool }
+    }
+
+    pub fn ensure_table(&self) -> CoreResult<()> {
+        let conn = self.pool.get()?;
+
+        const COMMAND: &str = r"
+        CREATE TABLE IF NOT EXISTS hotkeys(
+            id INTEGER PRIMARY KEY,
+            action TEXT NOT NULL UNIQUE,
+            accelerator TEXT NOT NULL,
+            enabled BOOLEAN NOT NULL
+        )";
+
+        conn.execute(COMMAND, [])?;
+
+        Ok(())
+    }
+
+    /// Returns hotkeys of all actions, actions which never rebind use default accelerator.
+    pub fn get_hotkeys(&self) -> CoreResult<Vec<Hotkey>> {
+        const COMMAND: &str = r"SELECT action, accelerator, enabled FROM hotkeys";
+
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(COMMAND)?;
+        let rows = stmt.query_and_then([], parse_hotkey)?;
+
+        let mut stored_hotkeys = Vec::new();
+        for row in rows {
+            stored_hotkeys.push(row?);
+        }
+
+        let hotkeys = HotkeyAction::ALL
+            .iter()
+            .map(|action| {
+                stored_hotkeys
+                    .iter()
+                    .find(|hotkey| hotkey.action == *action)
+                    .cloned()
+                    .unwrap_or_else(|| Hotkey {
+                        action: *action,
+                        accelerator: action.default_accelerator().to_string(),
+                        enabled: true,
+                    })
+            })
+            .collect();
+
+        Ok(hotkeys)
+    }
+
+    pub fn set_hotkey(
+        &self,
+        action: HotkeyAction,
+        accelerator: &str,
+        enabled: bool,
+    ) -> CoreResult<()> {
+        const COMMAND: &str = r"INSERT INTO hotkeys(action, accelerator, enabled) VALUES(?, ?, ?) ON CONFLICT DO UPDATE SET accelerator = ?, enabled = ?";
+
+        let action: &str = action.into();
+
+        let _ = self.pool.get()?.execute(
+            COMMAND,
+            params![action, accelerator, enabled, accelerator, enabled],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn reset_hotkeys(&self) -> CoreResult<()> {
+        const COMMAND: &str = r"DELETE FROM hotkeys";
+
+        let _ = self.pool.get()?.execute(COMMAND, [])?;
+
+        Ok(())
+    }
+}
+
+fn parse_hotkey(row: &Row) -> CoreResult<Hotkey> {
+    let action: String = row.get(0)?;
+
+    Ok(Hotkey {
+        action: HotkeyAction::from_str(&action).map_err(|err| core_error!("{}", err))?,
+        accelerator: row.get(1)?,
+        enabled: row.get(2)?,
+    })
+}
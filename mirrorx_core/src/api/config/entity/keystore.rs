@@ -0,0 +1,104 @@
+use crate::{
+    api::{
+        config::change::{publish, ConfigChange},
+        signaling::fingerprint::identity_fingerprint,
+    },
+    error::CoreResult,
+};
+use base64::{engine::general_purpose::STANDARD as base64_standard, Engine};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, Row};
+use serde::Serialize;
+
+/// The device which was paired offline, its identity public key authenticates the LAN
+/// connections with it.
+#[derive(Debug, Clone, Serialize)]
+pub struct PairedPeer {
+    pub id: i64,
+    pub name: String,
+    #[serde(skip)]
+    pub public_key: Vec<u8>,
+    pub fingerprint: String,
+    pub paired_at: i64,
+}
+
+/// Identity public keys of the devices paired offline.
+pub struct KeystoreRepository {
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl KeystoreRepository {
+    pub fn new(pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { pool }
+    }
+
+    pub fn ensure_table(&self) -> CoreResult<()> {
+        const COMMAND: &str = r"
+        CREATE TABLE IF NOT EXISTS paired_peers(
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            public_key TEXT NOT NULL UNIQUE,
+            timestamp INTEGER NOT NULL
+        )";
+
+        let _ = self.pool.get()?.execute(COMMAND, [])?;
+
+        Ok(())
+    }
+
+    pub fn get_peers(&self) -> CoreResult<Vec<PairedPeer>> {
+        const COMMAND: &str =
+            r"SELECT id, name, public_key, timestamp FROM paired_peers ORDER BY id ASC";
+
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(COMMAND)?;
+        let rows = stmt.query_and_then([], parse_peer)?;
+
+        let mut peers = Vec::new();
+        for row in rows {
+            peers.push(row?);
+        }
+
+        Ok(peers)
+    }
+
+    /// Pair the device of `public_key`, pairing it again only renames it.
+    pub fn add_peer(&self, name: &str, public_key: &[u8]) -> CoreResult<()> {
+        const COMMAND: &str = r"INSERT INTO paired_peers(name, public_key, timestamp) VALUES(?, ?, ?) ON CONFLICT(public_key) DO UPDATE SET name = excluded.name";
+
+        let timestamp = chrono::Utc::now().timestamp();
+
+        let _ = self.pool.get()?.execute(
+            COMMAND,
+            params![name, base64_standard.encode(public_key), timestamp],
+        )?;
+
+        publish(ConfigChange::PairedPeers);
+
+        Ok(())
+    }
+
+    pub fn delete_peer(&self, id: i64) -> CoreResult<()> {
+        const COMMAND: &str = r"DELETE FROM paired_peers WHERE id = ?";
+
+        let _ = self.pool.get()?.execute(COMMAND, params![id])?;
+
+        publish(ConfigChange::PairedPeers);
+
+        Ok(())
+    }
+}
+
+fn parse_peer(row: &Row) -> CoreResult<PairedPeer> {
+    let public_key: String = row.get(2)?;
+    let public_key = base64_standard.decode(public_key)?;
+
+    Ok(PairedPeer {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        fingerprint: identity_fingerprint(&public_key),
+        public_key,
+        paired_at: row.get(3)?,
+    })
+}
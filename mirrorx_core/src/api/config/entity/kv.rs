@@ -1,9 +1,26 @@
-use crate::{core_error, error::CoreResult};
+use crate::{
+    api::{
+        config::{
+            change::{publish, ConfigChange},
+            key_store::{KeyStore, KeyStoreBackend},
+        },
+        endpoint::{
+            client::socket::TransportTuning,
+            message::{GopPolicy, SessionProfile},
+        },
+    },
+    component::{
+        availability::Availability, fs::receive_policy::ReceivePolicy,
+        governor::priority::ThreadPriority,
+    },
+    core_error,
+    error::CoreResult,
+};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::OptionalExtension;
 use serde::{Deserialize, Serialize};
-use std::str::FromStr;
+use std::{net::IpAddr, str::FromStr, sync::Arc};
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -36,13 +53,74 @@ impl FromStr for Theme {
     }
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateChannel {
+    Stable,
+    Beta,
+}
+
+impl<'a> From<UpdateChannel> for &'a str {
+    fn from(val: UpdateChannel) -> Self {
+        match val {
+            UpdateChannel::Stable => "stable",
+            UpdateChannel::Beta => "beta",
+        }
+    }
+}
+
+impl FromStr for UpdateChannel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stable" => Ok(UpdateChannel::Stable),
+            "beta" => Ok(UpdateChannel::Beta),
+            _ => Err(String::from("Unknown update channel")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    VisitRequest,
+    FileTransferSucceeded,
+    FileTransferFailed,
+    SessionDisconnected,
+    RemotePowerSaving,
+    PermissionRequest,
+    PeerIdentityChanged,
+}
+
+impl<'a> From<NotificationKind> for &'a str {
+    fn from(val: NotificationKind) -> Self {
+        match val {
+            NotificationKind::VisitRequest => "visit_request",
+            NotificationKind::FileTransferSucceeded => "file_transfer_succeeded",
+            NotificationKind::FileTransferFailed => "file_transfer_failed",
+            NotificationKind::SessionDisconnected => "session_disconnected",
+            NotificationKind::RemotePowerSaving => "remote_power_saving",
+            NotificationKind::PermissionRequest => "permission_request",
+            NotificationKind::PeerIdentityChanged => "peer_identity_changed",
+        }
+    }
+}
+
 pub struct KVRepository {
     pool: Pool<SqliteConnectionManager>,
+    // long-term secrets are kept apart from settings
+    key_store: Arc<dyn KeyStore>,
 }
 
 impl KVRepository {
-    pub fn new(pool: Pool<SqliteConnectionManager>) -> Self {
-        Self { pool }
+    pub fn new(pool: Pool<SqliteConnectionManager>, key_store: Arc<dyn KeyStore>) -> Self {
+        Self { pool, key_store }
+    }
+
+    /// Where the long-term secrets are kept.
+    pub fn key_store_backend(&self) -> KeyStoreBackend {
+        self.key_store.backend()
     }
 
     pub fn ensure_table(&self) -> CoreResult<()> {
@@ -82,6 +160,532 @@ impl KVRepository {
         }
     }
 
+    pub fn set_notification_enabled(
+        &self,
+        kind: NotificationKind,
+        enabled: bool,
+    ) -> CoreResult<()> {
+        let kind: &str = kind.into();
+        self.set(&format!("notification.{kind}"), &enabled.to_string())
+    }
+
+    pub fn get_notification_enabled(&self, kind: NotificationKind) -> CoreResult<bool> {
+        let kind: &str = kind.into();
+        match self.get(&format!("notification.{kind}"))? {
+            Some(enabled) => enabled
+                .parse()
+                .map_err(|_| core_error!("invalid notification config value")),
+            None => Ok(true),
+        }
+    }
+
+    pub fn set_power_saving_enabled(&self, enabled: bool) -> CoreResult<()> {
+        self.set("power_saving", &enabled.to_string())?;
+        publish(ConfigChange::PowerSaving(enabled));
+
+        Ok(())
+    }
+
+    pub fn get_power_saving_enabled(&self) -> CoreResult<bool> {
+        match self.get("power_saving")? {
+            Some(enabled) => enabled
+                .parse()
+                .map_err(|_| core_error!("invalid power saving config value")),
+            None => Ok(true),
+        }
+    }
+
+    pub fn set_keep_awake_enabled(&self, enabled: bool) -> CoreResult<()> {
+        self.set("keep_awake", &enabled.to_string())?;
+        publish(ConfigChange::KeepAwake(enabled));
+
+        Ok(())
+    }
+
+    pub fn get_keep_awake_enabled(&self) -> CoreResult<bool> {
+        match self.get("keep_awake")? {
+            Some(enabled) => enabled
+                .parse()
+                .map_err(|_| core_error!("invalid keep awake config value")),
+            None => Ok(true),
+        }
+    }
+
+    pub fn set_update_channel(&self, channel: UpdateChannel) -> CoreResult<()> {
+        self.set("update_channel", channel.into())
+    }
+
+    pub fn get_update_channel(&self) -> CoreResult<UpdateChannel> {
+        match self.get("update_channel")? {
+            Some(channel) => {
+                UpdateChannel::from_str(&channel).map_err(|err| core_error!("{}", err))
+            }
+            None => Ok(UpdateChannel::Stable),
+        }
+    }
+
+    pub fn set_update_url(&self, url: &str) -> CoreResult<()> {
+        self.set("update_url", url)
+    }
+
+    pub fn get_update_url(&self) -> CoreResult<Option<String>> {
+        self.get("update_url")
+    }
+
+    pub fn set_automation_enabled(&self, enabled: bool) -> CoreResult<()> {
+        self.set("automation", &enabled.to_string())
+    }
+
+    pub fn get_automation_enabled(&self) -> CoreResult<bool> {
+        match self.get("automation")? {
+            Some(enabled) => enabled
+                .parse()
+                .map_err(|_| core_error!("invalid automation config value")),
+            None => Ok(false),
+        }
+    }
+
+    pub fn set_view_only_enabled(&self, enabled: bool) -> CoreResult<()> {
+        self.set("view_only", &enabled.to_string())?;
+        publish(ConfigChange::ViewOnly(enabled));
+
+        Ok(())
+    }
+
+    pub fn get_view_only_enabled(&self) -> CoreResult<bool> {
+        match self.get("view_only")? {
+            Some(enabled) => enabled
+                .parse()
+                .map_err(|_| core_error!("invalid view only config value")),
+            None => Ok(false),
+        }
+    }
+
+    pub fn set_visit_confirm_enabled(&self, enabled: bool) -> CoreResult<()> {
+        self.set("visit_confirm", &enabled.to_string())?;
+        publish(ConfigChange::VisitConfirm(enabled));
+
+        Ok(())
+    }
+
+    pub fn get_visit_confirm_enabled(&self) -> CoreResult<bool> {
+        match self.get("visit_confirm")? {
+            Some(enabled) => enabled
+                .parse()
+                .map_err(|_| core_error!("invalid visit confirm config value")),
+            None => Ok(false),
+        }
+    }
+
+    /// Whether remote can sign in at the login screen when it runs as service.
+    pub fn set_unattended_login_enabled(&self, enabled: bool) -> CoreResult<()> {
+        self.set("unattended_login", &enabled.to_string())?;
+        publish(ConfigChange::UnattendedLogin(enabled));
+
+        Ok(())
+    }
+
+    pub fn get_unattended_login_enabled(&self) -> CoreResult<bool> {
+        match self.get("unattended_login")? {
+            Some(enabled) => enabled
+                .parse()
+                .map_err(|_| core_error!("invalid unattended login config value")),
+            None => Ok(false),
+        }
+    }
+
+    /// Text which rejected visitors are shown, empty shows them the reason only.
+    pub fn set_reject_message(&self, message: Option<&str>) -> CoreResult<()> {
+        self.set("reject_message", message.unwrap_or_default())?;
+        publish(ConfigChange::RejectMessage(message.map(String::from)));
+
+        Ok(())
+    }
+
+    pub fn get_reject_message(&self) -> CoreResult<Option<String>> {
+        Ok(self
+            .get("reject_message")?
+            .filter(|message| !message.is_empty()))
+    }
+
+    pub fn set_lan_only_enabled(&self, enabled: bool) -> CoreResult<()> {
+        self.set("lan_only", &enabled.to_string())?;
+        publish(ConfigChange::LanOnly(enabled));
+
+        Ok(())
+    }
+
+    pub fn get_lan_only_enabled(&self) -> CoreResult<bool> {
+        match self.get("lan_only")? {
+            Some(enabled) => enabled
+                .parse()
+                .map_err(|_| core_error!("invalid lan only config value")),
+            None => Ok(false),
+        }
+    }
+
+    pub fn set_lan_discoverable(&self, discoverable: bool) -> CoreResult<()> {
+        self.set("lan_discoverable", &discoverable.to_string())?;
+        publish(ConfigChange::LanDiscoverable(discoverable));
+
+        Ok(())
+    }
+
+    pub fn get_lan_discoverable(&self) -> CoreResult<bool> {
+        match self.get("lan_discoverable")? {
+            Some(discoverable) => discoverable
+                .parse()
+                .map_err(|_| core_error!("invalid lan discoverable config value")),
+            None => Ok(true),
+        }
+    }
+
+    /// Bind the LAN listener on the address of one interface, None listens on all of them.
+    pub fn set_lan_listen_ip(&self, listen_ip: Option<IpAddr>) -> CoreResult<()> {
+        let value = listen_ip.map(|ip| ip.to_string()).unwrap_or_default();
+        self.set("lan_listen_ip", &value)?;
+        publish(ConfigChange::LanListenIp(listen_ip));
+
+        Ok(())
+    }
+
+    pub fn get_lan_listen_ip(&self) -> CoreResult<Option<IpAddr>> {
+        match self.get("lan_listen_ip")? {
+            Some(listen_ip) if !listen_ip.is_empty() => listen_ip
+                .parse()
+                .map(Some)
+                .map_err(|_| core_error!("invalid lan listen ip config value")),
+            _ => Ok(None),
+        }
+    }
+
+    /// Bind outgoing connections on the address of one interface, None leaves the route
+    /// to the OS. The LAN listener takes it too unless it has its own address.
+    pub fn set_bind_ip(&self, bind_ip: Option<IpAddr>) -> CoreResult<()> {
+        let value = bind_ip.map(|ip| ip.to_string()).unwrap_or_default();
+        self.set("bind_ip", &value)?;
+        publish(ConfigChange::BindIp(bind_ip));
+
+        Ok(())
+    }
+
+    pub fn get_bind_ip(&self) -> CoreResult<Option<IpAddr>> {
+        match self.get("bind_ip")? {
+            Some(bind_ip) if !bind_ip.is_empty() => bind_ip
+                .parse()
+                .map(Some)
+                .map_err(|_| core_error!("invalid bind ip config value")),
+            _ => Ok(None),
+        }
+    }
+
+    /// Directives like `mirrorx_core::service::endpoint=debug` override the default log
+    /// filter, empty restores it.
+    pub fn set_log_filter(&self, directives: Option<&str>) -> CoreResult<()> {
+        self.set("log_filter", directives.unwrap_or_default())?;
+        publish(ConfigChange::LogFilter(directives.map(String::from)));
+
+        Ok(())
+    }
+
+    pub fn get_log_filter(&self) -> CoreResult<Option<String>> {
+        Ok(self
+            .get("log_filter")?
+            .filter(|directives| !directives.is_empty()))
+    }
+
+    pub fn set_lan_key(&self, lan_key: &str) -> CoreResult<()> {
+        self.key_store.set("lan_key", lan_key)?;
+        publish(ConfigChange::LanKey(Some(lan_key.to_string())));
+
+        Ok(())
+    }
+
+    pub fn get_lan_key(&self) -> CoreResult<Option<String>> {
+        Ok(self
+            .key_store
+            .get("lan_key")?
+            .filter(|lan_key| !lan_key.is_empty()))
+    }
+
+    pub fn set_identity_key(&self, pkcs8: &str) -> CoreResult<()> {
+        self.key_store.set("identity_key", pkcs8)
+    }
+
+    pub fn get_identity_key(&self) -> CoreResult<Option<String>> {
+        self.key_store.get("identity_key")
+    }
+
+    pub fn set_codec_capabilities(&self, capabilities: &str) -> CoreResult<()> {
+        self.set("codec_capabilities", capabilities)
+    }
+
+    pub fn get_codec_capabilities(&self) -> CoreResult<Option<String>> {
+        self.get("codec_capabilities")
+    }
+
+    pub fn set_telemetry_enabled(&self, enabled: bool) -> CoreResult<()> {
+        self.set("telemetry", &enabled.to_string())?;
+        publish(ConfigChange::Telemetry(enabled));
+
+        Ok(())
+    }
+
+    pub fn get_telemetry_enabled(&self) -> CoreResult<bool> {
+        match self.get("telemetry")? {
+            Some(enabled) => enabled
+                .parse()
+                .map_err(|_| core_error!("invalid telemetry config value")),
+            None => Ok(false),
+        }
+    }
+
+    pub fn set_telemetry_url(&self, url: &str) -> CoreResult<()> {
+        self.set("telemetry_url", url)
+    }
+
+    pub fn get_telemetry_url(&self) -> CoreResult<Option<String>> {
+        self.get("telemetry_url")
+    }
+
+    pub fn set_max_sessions(&self, max_sessions: u32) -> CoreResult<()> {
+        self.set("max_sessions", &max_sessions.to_string())?;
+        publish(ConfigChange::MaxSessions(max_sessions));
+
+        Ok(())
+    }
+
+    /// Zero means unlimited.
+    pub fn get_max_sessions(&self) -> CoreResult<u32> {
+        match self.get("max_sessions")? {
+            Some(max_sessions) => max_sessions
+                .parse()
+                .map_err(|_| core_error!("invalid max sessions config value")),
+            None => Ok(0),
+        }
+    }
+
+    pub fn set_idle_frame_rate(&self, frame_rate: u8) -> CoreResult<()> {
+        self.set("idle_frame_rate", &frame_rate.to_string())?;
+        publish(ConfigChange::IdleFrameRate(frame_rate));
+
+        Ok(())
+    }
+
+    /// Zero disables the idle detection of capture.
+    pub fn get_idle_frame_rate(&self) -> CoreResult<u8> {
+        match self.get("idle_frame_rate")? {
+            Some(frame_rate) => frame_rate
+                .parse()
+                .map_err(|_| core_error!("invalid idle frame rate config value")),
+            None => Ok(2),
+        }
+    }
+
+    pub fn set_watermark_enabled(&self, enabled: bool) -> CoreResult<()> {
+        self.set("watermark", &enabled.to_string())?;
+        publish(ConfigChange::Watermark(enabled));
+
+        Ok(())
+    }
+
+    pub fn get_watermark_enabled(&self) -> CoreResult<bool> {
+        match self.get("watermark")? {
+            Some(enabled) => enabled
+                .parse()
+                .map_err(|_| core_error!("invalid watermark config value")),
+            None => Ok(false),
+        }
+    }
+
+    pub fn set_tile_codec_preferred(&self, preferred: bool) -> CoreResult<()> {
+        self.set("tile_codec_preferred", &preferred.to_string())?;
+        publish(ConfigChange::TileCodecPreferred(preferred));
+
+        Ok(())
+    }
+
+    pub fn get_tile_codec_preferred(&self) -> CoreResult<bool> {
+        match self.get("tile_codec_preferred")? {
+            Some(preferred) => preferred
+                .parse()
+                .map_err(|_| core_error!("invalid tile codec preferred config value")),
+            None => Ok(false),
+        }
+    }
+
+    pub fn set_gop_policy(&self, policy: GopPolicy) -> CoreResult<()> {
+        self.set("gop_policy", &serde_json::to_string(&policy)?)?;
+        publish(ConfigChange::GopPolicy(policy));
+
+        Ok(())
+    }
+
+    pub fn get_gop_policy(&self) -> CoreResult<GopPolicy> {
+        match self.get("gop_policy")? {
+            Some(policy) => serde_json::from_str(&policy)
+                .map_err(|_| core_error!("invalid gop policy config value")),
+            None => Ok(GopPolicy::default()),
+        }
+    }
+
+    pub fn set_session_profile(&self, profile: SessionProfile) -> CoreResult<()> {
+        self.set("session_profile", &serde_json::to_string(&profile)?)?;
+        publish(ConfigChange::SessionProfile(profile));
+
+        Ok(())
+    }
+
+    pub fn get_session_profile(&self) -> CoreResult<SessionProfile> {
+        match self.get("session_profile")? {
+            Some(profile) => serde_json::from_str(&profile)
+                .map_err(|_| core_error!("invalid session profile config value")),
+            None => Ok(SessionProfile::default()),
+        }
+    }
+
+    pub fn set_quality_auto_tune(&self, enabled: bool) -> CoreResult<()> {
+        self.set("quality_auto_tune", &enabled.to_string())?;
+        publish(ConfigChange::QualityAutoTune(enabled));
+
+        Ok(())
+    }
+
+    pub fn get_quality_auto_tune(&self) -> CoreResult<bool> {
+        match self.get("quality_auto_tune")? {
+            Some(enabled) => enabled
+                .parse()
+                .map_err(|_| core_error!("invalid quality auto tune config value")),
+            None => Ok(false),
+        }
+    }
+
+    /// Stream the test pattern like `1280x720@30` in place of the primary monitor, empty
+    /// streams the real monitors.
+    pub fn set_test_pattern(&self, pattern: &str) -> CoreResult<()> {
+        self.set("test_pattern", pattern)?;
+        publish(ConfigChange::TestPattern(
+            Some(pattern.to_string()).filter(|pattern| !pattern.is_empty()),
+        ));
+
+        Ok(())
+    }
+
+    pub fn get_test_pattern(&self) -> CoreResult<Option<String>> {
+        Ok(self
+            .get("test_pattern")?
+            .filter(|pattern| !pattern.is_empty()))
+    }
+
+    pub fn set_staging_quota_mb(&self, quota_mb: u32) -> CoreResult<()> {
+        self.set("staging_quota_mb", &quota_mb.to_string())?;
+        publish(ConfigChange::StagingQuotaMb(quota_mb));
+
+        Ok(())
+    }
+
+    pub fn get_staging_quota_mb(&self) -> CoreResult<u32> {
+        match self.get("staging_quota_mb")? {
+            Some(quota_mb) => quota_mb
+                .parse()
+                .map_err(|_| core_error!("invalid staging quota config value")),
+            None => Ok(1024),
+        }
+    }
+
+    pub fn set_staging_retention_days(&self, days: u32) -> CoreResult<()> {
+        self.set("staging_retention_days", &days.to_string())?;
+        publish(ConfigChange::StagingRetentionDays(days));
+
+        Ok(())
+    }
+
+    pub fn get_staging_retention_days(&self) -> CoreResult<u32> {
+        match self.get("staging_retention_days")? {
+            Some(days) => days
+                .parse()
+                .map_err(|_| core_error!("invalid staging retention config value")),
+            None => Ok(7),
+        }
+    }
+
+    pub fn set_availability(&self, availability: Availability) -> CoreResult<()> {
+        self.set("availability", availability.into())?;
+        publish(ConfigChange::Availability(availability));
+
+        Ok(())
+    }
+
+    pub fn get_availability(&self) -> CoreResult<Availability> {
+        match self.get("availability")? {
+            Some(availability) => {
+                Availability::from_str(&availability).map_err(|err| core_error!("{}", err))
+            }
+            None => Ok(Availability::Available),
+        }
+    }
+
+    pub fn set_encoder_thread_priority(&self, priority: ThreadPriority) -> CoreResult<()> {
+        self.set("encoder_thread_priority", priority.into())?;
+        publish(ConfigChange::EncoderThreadPriority(priority));
+
+        Ok(())
+    }
+
+    pub fn get_encoder_thread_priority(&self) -> CoreResult<ThreadPriority> {
+        match self.get("encoder_thread_priority")? {
+            Some(priority) => {
+                ThreadPriority::from_str(&priority).map_err(|err| core_error!("{}", err))
+            }
+            None => Ok(ThreadPriority::Normal),
+        }
+    }
+
+    /// Pin capture and encode threads to the cores like `0,2,4-7`, empty lets them run
+    /// on any core.
+    pub fn set_encoder_cores(&self, cores: &str) -> CoreResult<()> {
+        self.set("encoder_cores", cores)?;
+        publish(ConfigChange::EncoderCores(
+            Some(cores.to_string()).filter(|cores| !cores.is_empty()),
+        ));
+
+        Ok(())
+    }
+
+    pub fn get_encoder_cores(&self) -> CoreResult<Option<String>> {
+        Ok(self.get("encoder_cores")?.filter(|cores| !cores.is_empty()))
+    }
+
+    pub fn set_transport_tuning(&self, tuning: TransportTuning) -> CoreResult<()> {
+        self.set("transport_tuning", &serde_json::to_string(&tuning)?)?;
+        publish(ConfigChange::TransportTuning(tuning));
+
+        Ok(())
+    }
+
+    pub fn get_transport_tuning(&self) -> CoreResult<TransportTuning> {
+        match self.get("transport_tuning")? {
+            Some(tuning) => serde_json::from_str(&tuning)
+                .map_err(|_| core_error!("invalid transport tuning config value")),
+            None => Ok(TransportTuning::default()),
+        }
+    }
+
+    pub fn set_receive_policy(&self, policy: ReceivePolicy) -> CoreResult<()> {
+        self.set("receive_policy", &serde_json::to_string(&policy)?)?;
+        publish(ConfigChange::ReceivePolicy(policy));
+
+        Ok(())
+    }
+
+    pub fn get_receive_policy(&self) -> CoreResult<ReceivePolicy> {
+        match self.get("receive_policy")? {
+            Some(policy) => serde_json::from_str(&policy)
+                .map_err(|_| core_error!("invalid receive policy config value")),
+            None => Ok(ReceivePolicy::default()),
+        }
+    }
+
     fn set(&self, key: &str, value: &str) -> CoreResult<()> {
         const COMMAND: &str =
             r"INSERT INTO kv(key, value) VALUES(?, ?) ON CONFLICT DO UPDATE SET value = ?";
@@ -1,3 +1,9 @@
+pub mod acl;
 pub mod domain;
 pub mod history;
+pub mod hotkey;
+pub mod keystore;
 pub mod kv;
+pub mod peer;
+pub mod recovery;
+pub mod tls;
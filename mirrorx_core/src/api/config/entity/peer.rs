@@ -0,0 +1,128 @@
+use crate::{
+    api::config::change::{publish, ConfigChange},
+    error::CoreResult,
+};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, OptionalExtension};
+
+/// Long-term identity fingerprints of remote devices, they're trusted on first use, and
+/// the settings which user made for each remote device.
+pub struct PeerRepository {
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl PeerRepository {
+    pub fn new(pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { pool }
+    }
+
+    pub fn ensure_table(&self) -> CoreResult<()> {
+        const COMMAND: &str = r"
+        CREATE TABLE IF NOT EXISTS peer_fingerprints(
+            id INTEGER PRIMARY KEY,
+            domain TEXT NOT NULL,
+            device_id INTEGER NOT NULL,
+            fingerprint TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            UNIQUE(domain, device_id)
+        )";
+
+        const CREATE_BANDWIDTH_TABLE_COMMAND: &str = r"
+        CREATE TABLE IF NOT EXISTS peer_bandwidth_limits(
+            id INTEGER PRIMARY KEY,
+            domain TEXT NOT NULL,
+            device_id INTEGER NOT NULL,
+            max_kbps INTEGER NOT NULL,
+            UNIQUE(domain, device_id)
+        )";
+
+        let conn = self.pool.get()?;
+        let _ = conn.execute(COMMAND, [])?;
+        let _ = conn.execute(CREATE_BANDWIDTH_TABLE_COMMAND, [])?;
+
+        Ok(())
+    }
+
+    pub fn get_fingerprint(&self, domain: &str, device_id: i64) -> CoreResult<Option<String>> {
+        const COMMAND: &str =
+            r"SELECT fingerprint FROM peer_fingerprints WHERE domain = ? AND device_id = ? LIMIT 1";
+
+        let fingerprint = self
+            .pool
+            .get()?
+            .query_row(COMMAND, params![domain, device_id], |row| row.get(0))
+            .optional()?;
+
+        Ok(fingerprint)
+    }
+
+    /// Trust `fingerprint` as the identity of the device, it replaces the previous one.
+    pub fn set_fingerprint(
+        &self,
+        domain: &str,
+        device_id: i64,
+        fingerprint: &str,
+    ) -> CoreResult<()> {
+        const COMMAND: &str = r"INSERT OR REPLACE INTO peer_fingerprints(domain, device_id, fingerprint, timestamp) VALUES(?, ?, ?, ?)";
+
+        let timestamp = chrono::Utc::now().timestamp();
+
+        let _ = self
+            .pool
+            .get()?
+            .execute(COMMAND, params![domain, device_id, fingerprint, timestamp])?;
+
+        Ok(())
+    }
+
+    /// The bandwidth cap of sessions with the device in kilobits per second, None when
+    /// unlimited.
+    pub fn get_bandwidth_limit(&self, domain: &str, device_id: i64) -> CoreResult<Option<u32>> {
+        const COMMAND: &str = r"SELECT max_kbps FROM peer_bandwidth_limits WHERE domain = ? AND device_id = ? LIMIT 1";
+
+        let max_kbps = self
+            .pool
+            .get()?
+            .query_row(COMMAND, params![domain, device_id], |row| row.get(0))
+            .optional()?;
+
+        Ok(max_kbps)
+    }
+
+    pub fn set_bandwidth_limit(
+        &self,
+        domain: &str,
+        device_id: i64,
+        max_kbps: Option<u32>,
+    ) -> CoreResult<()> {
+        const DELETE_COMMAND: &str =
+            r"DELETE FROM peer_bandwidth_limits WHERE domain = ? AND device_id = ?";
+        const UPSERT_COMMAND: &str = r"INSERT OR REPLACE INTO peer_bandwidth_limits(domain, device_id, max_kbps) VALUES(?, ?, ?)";
+
+        let conn = self.pool.get()?;
+        let _ = match max_kbps {
+            Some(max_kbps) => conn.execute(UPSERT_COMMAND, params![domain, device_id, max_kbps])?,
+            None => conn.execute(DELETE_COMMAND, params![domain, device_id])?,
+        };
+
+        publish(ConfigChange::PeerBandwidthLimit {
+            domain: domain.to_string(),
+            device_id,
+            max_kbps,
+        });
+
+        Ok(())
+    }
+
+    pub fn delete_domain_related(&self, domain: &str) -> CoreResult<()> {
+        const COMMAND: &str = r"DELETE FROM peer_fingerprints WHERE domain = ?";
+        const BANDWIDTH_COMMAND: &str = r"DELETE FROM peer_bandwidth_limits WHERE domain = ?";
+
+        let conn = self.pool.get()?;
+        let _ = conn.execute(COMMAND, params![domain])?;
+        let _ = conn.execute(BANDWIDTH_COMMAND, params![domain])?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+use crate::{core_error, error::CoreResult};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, Row};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, str::FromStr};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferDirection {
+    Send,
+    Download,
+}
+
+impl<'a> From<TransferDirection> for &'a str {
+    fn from(val: TransferDirection) -> Self {
+        match val {
+            TransferDirection::Send => "send",
+            TransferDirection::Download => "download",
+        }
+    }
+}
+
+impl FromStr for TransferDirection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "send" => Ok(TransferDirection::Send),
+            "download" => Ok(TransferDirection::Download),
+            _ => Err(String::from("Unknown transfer direction")),
+        }
+    }
+}
+
+/// The in-flight file transfer, it's removed once the transfer finishes, so the
+/// remaining ones were interrupted by crash or force-quit.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferManifest {
+    pub id: String,
+    pub remote_device_id: String,
+    pub direction: TransferDirection,
+    pub local_path: PathBuf,
+    /// The remote directory when sending, the remote file when downloading.
+    pub remote_path: PathBuf,
+    pub size: u64,
+    pub timestamp: i64,
+}
+
+impl TransferManifest {
+    pub fn new(
+        id: String,
+        remote_device_id: String,
+        direction: TransferDirection,
+        local_path: PathBuf,
+        remote_path: PathBuf,
+        size: u64,
+    ) -> Self {
+        Self {
+            id,
+            remote_device_id,
+            direction,
+            local_path,
+            remote_path,
+            size,
+            timestamp: chrono::Utc::now().timestamp(),
+        }
+    }
+}
+
+/// The active session which visits remote device, it's removed once the session
+/// disconnects, so the remaining ones were shut down dirty.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionMarker {
+    pub local_device_id: i64,
+    pub remote_device_id: i64,
+    pub domain: String,
+    pub visit_desktop: bool,
+    pub timestamp: i64,
+}
+
+pub struct RecoveryRepository {
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl RecoveryRepository {
+    pub fn new(pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { pool }
+    }
+
+    pub fn ensure_table(&self) -> CoreResult<()> {
+        let conn = self.pool.get()?;
+
+        const CREATE_TRANSFERS_TABLE_COMMAND: &str = r"
+        CREATE TABLE IF NOT EXISTS transfer_manifests(
+            id TEXT PRIMARY KEY,
+            remote_device_id TEXT NOT NULL,
+            direction TEXT NOT NULL,
+            local_path TEXT NOT NULL,
+            remote_path TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            timestamp INTEGER NOT NULL
+        )";
+
+        conn.execute(CREATE_TRANSFERS_TABLE_COMMAND, [])?;
+
+        const CREATE_SESSIONS_TABLE_COMMAND: &str = r"
+        CREATE TABLE IF NOT EXISTS session_markers(
+            id INTEGER PRIMARY KEY,
+            local_device_id INTEGER NOT NULL,
+            remote_device_id INTEGER NOT NULL,
+            domain TEXT NOT NULL,
+            visit_desktop BOOLEAN NOT NULL,
+            timestamp INTEGER NOT NULL
+        )";
+
+        conn.execute(CREATE_SESSIONS_TABLE_COMMAND, [])?;
+
+        Ok(())
+    }
+
+    pub fn save_transfer(&self, manifest: &TransferManifest) -> CoreResult<()> {
+        const COMMAND: &str = r"INSERT OR REPLACE INTO transfer_manifests(id, remote_device_id, direction, local_path, remote_path, size, timestamp) VALUES(?, ?, ?, ?, ?, ?, ?)";
+
+        let direction: &str = manifest.direction.into();
+
+        let _ = self.pool.get()?.execute(
+            COMMAND,
+            params![
+                manifest.id,
+                manifest.remote_device_id,
+                direction,
+                manifest.local_path.to_string_lossy(),
+                manifest.remote_path.to_string_lossy(),
+                manifest.size as i64,
+                manifest.timestamp
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_transfer(&self, id: &str) -> CoreResult<Option<TransferManifest>> {
+        const COMMAND: &str = r"SELECT id, remote_device_id, direction, local_path, remote_path, size, timestamp FROM transfer_manifests WHERE id = ?";
+
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(COMMAND)?;
+        let mut rows = stmt.query_and_then([id], parse_transfer_manifest)?;
+
+        rows.next().transpose()
+    }
+
+    pub fn get_transfers(&self) -> CoreResult<Vec<TransferManifest>> {
+        const COMMAND: &str = r"SELECT id, remote_device_id, direction, local_path, remote_path, size, timestamp FROM transfer_manifests ORDER BY timestamp DESC";
+
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(COMMAND)?;
+        let rows = stmt.query_and_then([], parse_transfer_manifest)?;
+
+        let mut manifests = Vec::new();
+        for row in rows {
+            manifests.push(row?);
+        }
+
+        Ok(manifests)
+    }
+
+    pub fn delete_transfer(&self, id: &str) -> CoreResult<()> {
+        const COMMAND: &str = r"DELETE FROM transfer_manifests WHERE id = ?";
+
+        let _ = self.pool.get()?.execute(COMMAND, params![id])?;
+
+        Ok(())
+    }
+
+    pub fn create_session(
+        &self,
+        local_device_id: i64,
+        remote_device_id: i64,
+        domain: &str,
+        visit_desktop: bool,
+    ) -> CoreResult<()> {
+        const COMMAND: &str = r"INSERT INTO session_markers(local_device_id, remote_device_id, domain, visit_desktop, timestamp) VALUES(?, ?, ?, ?, ?)";
+
+        let timestamp = chrono::Utc::now().timestamp();
+
+        let _ = self.pool.get()?.execute(
+            COMMAND,
+            params![
+                local_device_id,
+                remote_device_id,
+                domain,
+                visit_desktop,
+                timestamp
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn get_sessions(&self) -> CoreResult<Vec<SessionMarker>> {
+        const COMMAND: &str = r"SELECT local_device_id, remote_device_id, domain, visit_desktop, timestamp FROM session_markers";
+
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(COMMAND)?;
+        let rows = stmt.query_and_then([], parse_session_marker)?;
+
+        let mut markers = Vec::new();
+        for row in rows {
+            markers.push(row?);
+        }
+
+        Ok(markers)
+    }
+
+    /// Delete one marker of the session, the same remote device may be visited by
+    /// multiple sessions (desktop and files) at the same time.
+    pub fn delete_session(&self, local_device_id: i64, remote_device_id: i64) -> CoreResult<()> {
+        const COMMAND: &str = r"DELETE FROM session_markers WHERE id IN (SELECT id FROM session_markers WHERE local_device_id = ? AND remote_device_id = ? LIMIT 1)";
+
+        let _ = self
+            .pool
+            .get()?
+            .execute(COMMAND, params![local_device_id, remote_device_id])?;
+
+        Ok(())
+    }
+
+    pub fn clear_sessions(&self) -> CoreResult<()> {
+        const COMMAND: &str = r"DELETE FROM session_markers";
+
+        let _ = self.pool.get()?.execute(COMMAND, [])?;
+
+        Ok(())
+    }
+}
+
+fn parse_transfer_manifest(row: &Row) -> CoreResult<TransferManifest> {
+    let direction: String = row.get(2)?;
+    let local_path: String = row.get(3)?;
+    let remote_path: String = row.get(4)?;
+    let size: i64 = row.get(5)?;
+
+    Ok(TransferManifest {
+        id: row.get(0)?,
+        remote_device_id: row.get(1)?,
+        direction: TransferDirection::from_str(&direction).map_err(|err| core_error!("{}", err))?,
+        local_path: PathBuf::from(local_path),
+        remote_path: PathBuf::from(remote_path),
+        size: size as u64,
+        timestamp: row.get(6)?,
+    })
+}
+
+fn parse_session_marker(row: &Row) -> CoreResult<SessionMarker> {
+    Ok(SessionMarker {
+        local_device_id: row.get(0)?,
+        remote_device_id: row.get(1)?,
+        domain: row.get(2)?,
+        visit_desktop: row.get(3)?,
+        timestamp: row.get(4)?,
+    })
+}
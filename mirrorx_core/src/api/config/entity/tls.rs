@@ -0,0 +1,148 @@
+use crate::{
+    api::config::key_store::{relay_client_key_key, KeyStore, KeyStoreBackend},
+    error::CoreResult,
+};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// TLS settings of the relay connections of a self-hosted domain. Relay connections are
+/// wrapped in TLS once the relay certificate is pinned, the client certificate is
+/// presented when the relay requires it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayTls {
+    /// Hex SHA-256 fingerprint of the relay server certificate.
+    pub relay_fingerprint: String,
+    /// PEM of the client certificate chain.
+    pub client_cert: Option<String>,
+    /// PEM of the private key of the client certificate.
+    pub client_key: Option<String>,
+}
+
+pub struct TlsRepository {
+    pool: Pool<SqliteConnectionManager>,
+    // private keys of client certificates are kept in it, the client_key column is null
+    key_store: Arc<dyn KeyStore>,
+}
+
+impl TlsRepository {
+    pub fn new(pool: Pool<SqliteConnectionManager>, key_store: Arc<dyn KeyStore>) -> Self {
+        Self { pool, key_store }
+    }
+
+    pub fn ensure_table(&self) -> CoreResult<()> {
+        const COMMAND: &str = r"
+        CREATE TABLE IF NOT EXISTS relay_tls(
+            domain TEXT PRIMARY KEY,
+            relay_fingerprint TEXT NOT NULL,
+            client_cert TEXT,
+            client_key TEXT
+        )";
+
+        let _ = self.pool.get()?.execute(COMMAND, [])?;
+
+        Ok(())
+    }
+
+    pub fn get_relay_tls(&self, domain: &str) -> CoreResult<Option<RelayTls>> {
+        const COMMAND: &str = r"SELECT relay_fingerprint, client_cert, client_key FROM relay_tls WHERE domain = ? LIMIT 1";
+
+        let relay_tls = self
+            .pool
+            .get()?
+            .query_row(COMMAND, params![domain], |row| {
+                Ok(RelayTls {
+                    relay_fingerprint: row.get(0)?,
+                    client_cert: row.get(1)?,
+                    client_key: row.get(2)?,
+                })
+            })
+            .optional()?;
+
+        let Some(mut relay_tls) = relay_tls else {
+            return Ok(None);
+        };
+
+        // the client_key column is only read when it's not moved into the key store, like
+        // when the secure store of OS isn't available
+        if let Some(client_key) = self.key_store.get(&relay_client_key_key(domain))? {
+            relay_tls.client_key = Some(client_key);
+        }
+
+        Ok(Some(relay_tls))
+    }
+
+    /// Set the TLS settings of the domain, None turns TLS off.
+    pub fn set_relay_tls(&self, domain: &str, relay_tls: Option<&RelayTls>) -> CoreResult<()> {
+        const DELETE_COMMAND: &str = r"DELETE FROM relay_tls WHERE domain = ?";
+        const COMMAND: &str = r"INSERT OR REPLACE INTO relay_tls(domain, relay_fingerprint, client_cert, client_key) VALUES(?, ?, ?, ?)";
+
+        let conn = self.pool.get()?;
+        let key = relay_client_key_key(domain);
+
+        match relay_tls {
+            Some(relay_tls) => {
+                match &relay_tls.client_key {
+                    Some(client_key) => self.key_store.set(&key, client_key)?,
+                    None => self.key_store.delete(&key)?,
+                }
+
+                let _ = conn.execute(
+                    COMMAND,
+                    params![
+                        domain,
+                        relay_tls.relay_fingerprint,
+                        relay_tls.client_cert,
+                        Option::<String>::None
+                    ],
+                )?;
+            }
+            None => {
+                let _ = conn.execute(DELETE_COMMAND, params![domain])?;
+                self.key_store.delete(&key)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move the private keys which the relay_tls table still keeps into the secure store of
+    /// OS, the column is cleared once they're moved.
+    pub fn migrate_secrets(&self) -> CoreResult<()> {
+        const COMMAND: &str =
+            r"SELECT domain, client_key FROM relay_tls WHERE client_key IS NOT NULL";
+        const CLEAR_COMMAND: &str = r"UPDATE relay_tls SET client_key = NULL WHERE domain = ?";
+
+        if self.key_store.backend() == KeyStoreBackend::File {
+            return Ok(());
+        }
+
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(COMMAND)?;
+        let rows = stmt.query_and_then([], |row| -> CoreResult<(String, String)> {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+
+        for row in rows {
+            let (domain, client_key) = row?;
+            let key = relay_client_key_key(&domain);
+
+            // the one in the secure store wins, the file may be restored from a backup
+            if self.key_store.get(&key)?.is_none() {
+                self.key_store.set(&key, &client_key)?;
+            }
+
+            conn.execute(CLEAR_COMMAND, [&domain])?;
+
+            tracing::info!(%domain, "move relay client key into os key store");
+        }
+
+        Ok(())
+    }
+
+    pub fn delete_domain_related(&self, domain: &str) -> CoreResult<()> {
+        self.set_relay_tls(domain, None)
+    }
+}
@@ -0,0 +1,260 @@
+//! Long-term secrets of the device, like the identity key, the LAN key, the device
+//! passwords of domains and the private keys of relay client certificates, are kept in the
+//! secure store of OS when there's one: Credential Manager on Windows, which protects them
+//! with DPAPI, Keychain on macOS and secret service on Linux. They're bound to the user of
+//! this machine there, so a copied config directory doesn't carry them. The config file
+//! keeps them only when the secure store isn't available.
+//!
+//! Each config file has its own service in the secure store named after its path, so
+//! profiles don't share secrets. Once secrets are moved there, the config file remembers
+//! it and an unavailable secure store fails opening the config, rather than falling back
+//! to the file and generating a new identity.
+
+use crate::{core_error, error::CoreResult};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::OptionalExtension;
+use serde::Serialize;
+use std::{path::Path, sync::Arc};
+
+/// Keys of the secrets in the key store, they're the keys of kv table in the config file
+/// as well, where the secrets were kept before.
+pub const SECRET_KEYS: &[&str] = &["identity_key", "lan_key"];
+
+// kv key which is set once the secure store of OS keeps the secrets
+const OS_KEY_STORE_MARKER_KEY: &str = "secrets_in_os_key_store";
+
+/// Key of the device password of domain `domain_id`, it was kept in the domains table.
+pub fn domain_password_key(domain_id: i64) -> String {
+    format!("domain_password.{domain_id}")
+}
+
+/// Key of the private key of the relay client certificate of `domain`, it was kept in the
+/// relay_tls table.
+pub fn relay_client_key_key(domain: &str) -> String {
+    format!("relay_client_key.{domain}")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyStoreBackend {
+    Os,
+    File,
+}
+
+pub trait KeyStore: Send + Sync {
+    fn backend(&self) -> KeyStoreBackend;
+
+    fn get(&self, key: &str) -> CoreResult<Option<String>>;
+
+    fn set(&self, key: &str, value: &str) -> CoreResult<()>;
+
+    fn delete(&self, key: &str) -> CoreResult<()>;
+}
+
+/// The fallback which keeps secrets in the config file, the same as settings.
+pub struct FileKeyStore {
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl FileKeyStore {
+    pub fn new(pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { pool }
+    }
+}
+
+impl KeyStore for FileKeyStore {
+    fn backend(&self) -> KeyStoreBackend {
+        KeyStoreBackend::File
+    }
+
+    fn get(&self, key: &str) -> CoreResult<Option<String>> {
+        const COMMAND: &str = r"SELECT value FROM kv WHERE key = ? LIMIT 1";
+
+        let value = self
+            .pool
+            .get()?
+            .query_row(COMMAND, [key], |row| row.get(0))
+            .optional()?;
+
+        Ok(value)
+    }
+
+    fn set(&self, key: &str, value: &str) -> CoreResult<()> {
+        const COMMAND: &str =
+            r"INSERT INTO kv(key, value) VALUES(?, ?) ON CONFLICT DO UPDATE SET value = ?";
+
+        let _ = self.pool.get()?.execute(COMMAND, [key, value, value])?;
+
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> CoreResult<()> {
+        const COMMAND: &str = r"DELETE FROM kv WHERE key = ?";
+
+        let _ = self.pool.get()?.execute(COMMAND, [key])?;
+
+        Ok(())
+    }
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+pub struct OsKeyStore {
+    service: String,
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+impl OsKeyStore {
+    // releases before kept secrets of every config file under this service
+    const LEGACY_SERVICE: &'static str = "MirrorX";
+
+    pub fn new(config_path: &Path) -> Self {
+        Self {
+            service: format!("MirrorX ({})", config_path.display()),
+        }
+    }
+
+    fn entry(service: &str, key: &str) -> CoreResult<keyring::Entry> {
+        keyring::Entry::new(service, key)
+            .map_err(|err| core_error!("open os key store entry failed ({})", err))
+    }
+
+    // the first config file which reads a secret of the legacy service takes it
+    fn take_legacy(&self, key: &str) -> CoreResult<Option<String>> {
+        let legacy_entry = Self::entry(Self::LEGACY_SERVICE, key)?;
+
+        let value = match legacy_entry.get_password() {
+            Ok(value) => value,
+            Err(keyring::Error::NoEntry) => return Ok(None),
+            Err(err) => return Err(core_error!("read os key store failed ({})", err)),
+        };
+
+        self.set(key, &value)?;
+
+        if let Err(err) = legacy_entry.delete_password() {
+            tracing::warn!(
+                key,
+                ?err,
+                "delete secret of legacy os key store service failed"
+            );
+        }
+
+        tracing::info!(key, "move secret from legacy os key store service");
+
+        Ok(Some(value))
+    }
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+impl KeyStore for OsKeyStore {
+    fn backend(&self) -> KeyStoreBackend {
+        KeyStoreBackend::Os
+    }
+
+    fn get(&self, key: &str) -> CoreResult<Option<String>> {
+        match Self::entry(&self.service, key)?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(keyring::Error::NoEntry) => self.take_legacy(key),
+            Err(err) => Err(core_error!("read os key store failed ({})", err)),
+        }
+    }
+
+    fn set(&self, key: &str, value: &str) -> CoreResult<()> {
+        Self::entry(&self.service, key)?
+            .set_password(value)
+            .map_err(|err| core_error!("write os key store failed ({})", err))
+    }
+
+    fn delete(&self, key: &str) -> CoreResult<()> {
+        match Self::entry(&self.service, key)?.delete_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(err) => Err(core_error!("delete from os key store failed ({})", err)),
+        }
+    }
+}
+
+/// The secure store of OS if it can be read, like when secret service isn't running on
+/// Linux it can't, otherwise the config file. Fails if the secrets of the config file at
+/// `config_path` were moved into the secure store which can't be read now.
+#[cfg_attr(
+    not(any(target_os = "windows", target_os = "macos", target_os = "linux")),
+    allow(unused_variables)
+)]
+pub(super) fn open_key_store(
+    pool: Pool<SqliteConnectionManager>,
+    config_path: &Path,
+) -> CoreResult<Arc<dyn KeyStore>> {
+    #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+    {
+        let os_key_store = OsKeyStore::new(config_path);
+
+        match os_key_store.get(SECRET_KEYS[0]) {
+            Ok(_) => return Ok(Arc::new(os_key_store)),
+            Err(err) => {
+                // the file doesn't keep them anymore, a new identity would be generated
+                if secrets_in_os_key_store(&pool)? {
+                    return Err(core_error!(
+                        "os key store which keeps secrets of this device is unavailable ({})",
+                        err
+                    ));
+                }
+
+                tracing::warn!(
+                    ?err,
+                    "os key store unavailable, keep secrets in config file"
+                );
+            }
+        }
+    }
+
+    Ok(Arc::new(FileKeyStore::new(pool)))
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+fn secrets_in_os_key_store(pool: &Pool<SqliteConnectionManager>) -> CoreResult<bool> {
+    const COMMAND: &str =
+        r"SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'kv')";
+
+    // a new config file has no kv table yet
+    let has_kv_table: bool = pool.get()?.query_row(COMMAND, [], |row| row.get(0))?;
+    if !has_kv_table {
+        return Ok(false);
+    }
+
+    let marker = FileKeyStore::new(pool.clone()).get(OS_KEY_STORE_MARKER_KEY)?;
+
+    Ok(marker.is_some())
+}
+
+/// Move the secrets which the config file still keeps into the secure store of OS, they're
+/// deleted from the file once they're moved.
+pub(super) fn migrate_secrets(
+    key_store: &dyn KeyStore,
+    pool: Pool<SqliteConnectionManager>,
+) -> CoreResult<()> {
+    if key_store.backend() == KeyStoreBackend::File {
+        return Ok(());
+    }
+
+    let file_key_store = FileKeyStore::new(pool);
+
+    // set before moving, a secret is gone from the file once it's moved
+    file_key_store.set(OS_KEY_STORE_MARKER_KEY, "1")?;
+
+    for key in SECRET_KEYS {
+        let Some(value) = file_key_store.get(key)? else {
+            continue;
+        };
+
+        // the one in the secure store wins, the file may be restored from a backup
+        if key_store.get(key)?.is_none() {
+            key_store.set(key, &value)?;
+        }
+
+        file_key_store.delete(key)?;
+
+        tracing::info!(key, "move secret into os key store");
+    }
+
+    Ok(())
+}
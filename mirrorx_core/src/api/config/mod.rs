@@ -1,6 +1,15 @@
+pub mod change;
 pub mod entity;
+pub mod key_store;
 
-use self::entity::{domain::DomainRepository, history::HistoryRepository, kv::KVRepository};
+use self::{
+    entity::{
+        acl::AclRepository, domain::DomainRepository, history::HistoryRepository,
+        hotkey::HotkeyRepository, keystore::KeystoreRepository, kv::KVRepository,
+        peer::PeerRepository, recovery::RecoveryRepository, tls::TlsRepository,
+    },
+    key_store::{migrate_secrets, open_key_store},
+};
 use crate::error::CoreResult;
 use r2d2_sqlite::SqliteConnectionManager;
 use std::{path::Path, sync::Arc};
@@ -10,6 +19,12 @@ pub struct LocalStorage {
     domain: Arc<DomainRepository>,
     kv: Arc<KVRepository>,
     history: Arc<HistoryRepository>,
+    hotkey: Arc<HotkeyRepository>,
+    recovery: Arc<RecoveryRepository>,
+    peer: Arc<PeerRepository>,
+    tls: Arc<TlsRepository>,
+    acl: Arc<AclRepository>,
+    keystore: Arc<KeystoreRepository>,
 }
 
 impl LocalStorage {
@@ -17,22 +32,66 @@ impl LocalStorage {
     where
         P: AsRef<Path>,
     {
-        let manager = SqliteConnectionManager::file(db_path);
+        let manager = SqliteConnectionManager::file(&db_path);
         let pool = r2d2::Pool::new(manager)?;
 
-        let domain_repository = DomainRepository::new(pool.clone());
+        // the file is created by pool, the service of secrets in the secure store of OS is
+        // named after its full path
+        let config_path = std::fs::canonicalize(&db_path)?;
+        let key_store = open_key_store(pool.clone(), &config_path)?;
+
+        let domain_repository = DomainRepository::new(pool.clone(), key_store.clone());
         domain_repository.ensure_table()?;
 
-        let kv_repository = KVRepository::new(pool.clone());
+        let kv_repository = KVRepository::new(pool.clone(), key_store.clone());
         kv_repository.ensure_table()?;
 
-        let history_repository = HistoryRepository::new(pool);
+        if let Err(err) = migrate_secrets(key_store.as_ref(), pool.clone()) {
+            tracing::error!(?err, "move secrets into os key store failed");
+        }
+
+        if let Err(err) = domain_repository.migrate_secrets() {
+            tracing::error!(
+                ?err,
+                "move domain device passwords into os key store failed"
+            );
+        }
+
+        let history_repository = HistoryRepository::new(pool.clone());
         history_repository.ensure_table()?;
 
+        let hotkey_repository = HotkeyRepository::new(pool.clone());
+        hotkey_repository.ensure_table()?;
+
+        let recovery_repository = RecoveryRepository::new(pool.clone());
+        recovery_repository.ensure_table()?;
+
+        let peer_repository = PeerRepository::new(pool.clone());
+        peer_repository.ensure_table()?;
+
+        let tls_repository = TlsRepository::new(pool.clone(), key_store.clone());
+        tls_repository.ensure_table()?;
+
+        if let Err(err) = tls_repository.migrate_secrets() {
+            tracing::error!(?err, "move relay client keys into os key store failed");
+        }
+
+        let acl_repository = AclRepository::new(pool.clone());
+        acl_repository.ensure_table()?;
+
+        let keystore_repository = KeystoreRepository::new(pool);
+        keystore_repository.ensure_table()?;
+
         Ok(Self {
             domain: Arc::new(domain_repository),
             kv: Arc::new(kv_repository),
             history: Arc::new(history_repository),
+            hotkey: Arc::new(hotkey_repository),
+            recovery: Arc::new(recovery_repository),
+            peer: Arc::new(peer_repository),
+            tls: Arc::new(tls_repository),
+            acl: Arc::new(acl_repository),
+            keystore: Arc::new(keystore_repository),
         })
     }
 
@@ -47,4 +106,28 @@ impl LocalStorage {
     pub fn history(&self) -> &HistoryRepository {
         &self.history
     }
+
+    pub fn hotkey(&self) -> &HotkeyRepository {
+        &self.hotkey
+    }
+
+    pub fn recovery(&self) -> &RecoveryRepository {
+        &self.recovery
+    }
+
+    pub fn peer(&self) -> &PeerRepository {
+        &self.peer
+    }
+
+    pub fn tls(&self) -> &TlsRepository {
+        &self.tls
+    }
+
+    pub fn acl(&self) -> &AclRepository {
+        &self.acl
+    }
+
+    pub fn keystore(&self) -> &KeystoreRepository {
+        &self.keystore
+    }
 }
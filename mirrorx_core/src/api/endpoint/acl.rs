@@ -0,0 +1,149 @@
+//! Host-side access control. Rules are checked as soon as a visit arrives, before user is
+//! prompted and before the key exchange proceeds, so denied visitors can't even try the
+//! password.
+//!
+//! Deny rules win over allow rules. Once any allow rule of a kind exists it turns into an
+//! allow list, visits of that origin which match none of them are denied: allow rules of
+//! device ids restrict visits relayed by signaling server, allow rules of subnets restrict
+//! visits from LAN.
+
+use crate::{
+    api::config::entity::acl::{AclAction, AclMatcher, AclRule},
+    core_error,
+    error::CoreResult,
+};
+use once_cell::sync::Lazy;
+use std::{net::IpAddr, sync::RwLock};
+
+// the stored rules are cached so LAN server, which has no storage, checks them as well
+static ACL_RULES: Lazy<RwLock<Vec<AclRule>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+#[derive(Debug, Clone, Copy)]
+pub enum AclOrigin {
+    /// Visit relayed by signaling server from the device.
+    Device(i64),
+    /// Visit from the address in LAN.
+    Lan(IpAddr),
+}
+
+/// Replace the rules which visits are checked against, it's called whenever the stored
+/// rules change.
+pub fn set_acl_rules(rules: Vec<AclRule>) {
+    *ACL_RULES.write().unwrap() = rules;
+}
+
+/// Check the visit from `origin` against the rules, false means it's denied.
+pub fn acl_admits(origin: AclOrigin) -> bool {
+    let rules = ACL_RULES.read().unwrap();
+
+    let mut has_allow_rules = false;
+    let mut allowed = false;
+
+    for rule in rules.iter() {
+        let Some(matched) = match_rule(&rule.matcher, origin) else {
+            // the rule doesn't apply to visits of this origin
+            continue;
+        };
+
+        match rule.action {
+            AclAction::Deny if matched => return false,
+            AclAction::Deny => {}
+            AclAction::Allow => {
+                has_allow_rules = true;
+                allowed |= matched;
+            }
+        }
+    }
+
+    !has_allow_rules || allowed
+}
+
+/// Check the matcher before it's saved, so malformed rules are refused at once rather than
+/// silently never matching.
+pub fn validate_acl_matcher(matcher: &AclMatcher) -> CoreResult<()> {
+    match matcher {
+        AclMatcher::DeviceId(device_id) => {
+            if !(0..10_000_000_000).contains(device_id) {
+                return Err(core_error!("device id is out of range"));
+            }
+        }
+        AclMatcher::DeviceIdPrefix(prefix) => {
+            let is_digits = prefix.chars().all(|c| c.is_ascii_digit());
+            if prefix.is_empty() || prefix.len() > 10 || !is_digits {
+                return Err(core_error!("device id prefix must be 1 to 10 digits"));
+            }
+        }
+        AclMatcher::Subnet(subnet) => {
+            let _ = parse_subnet(subnet)?;
+        }
+    }
+
+    Ok(())
+}
+
+// None when the matcher is of other origin
+fn match_rule(matcher: &AclMatcher, origin: AclOrigin) -> Option<bool> {
+    match (matcher, origin) {
+        (AclMatcher::DeviceId(rule_device_id), AclOrigin::Device(device_id)) => {
+            Some(*rule_device_id == device_id)
+        }
+        (AclMatcher::DeviceIdPrefix(prefix), AclOrigin::Device(device_id)) => {
+            // device ids are shown zero padded to 10 digits, prefixes follow what user sees
+            Some(format!("{device_id:010}").starts_with(prefix.as_str()))
+        }
+        (AclMatcher::Subnet(subnet), AclOrigin::Lan(ip)) => match parse_subnet(subnet) {
+            Ok((network, prefix_len)) => Some(subnet_contains(network, prefix_len, ip)),
+            Err(err) => {
+                // broken rule denies rather than lets everyone in
+                tracing::error!(?err, %subnet, "parse acl subnet failed");
+                Some(false)
+            }
+        },
+        _ => None,
+    }
+}
+
+fn parse_subnet(subnet: &str) -> CoreResult<(IpAddr, u8)> {
+    let (network, prefix_len) = match subnet.trim().split_once('/') {
+        Some((network, prefix_len)) => (network, Some(prefix_len)),
+        None => (subnet.trim(), None),
+    };
+
+    let network: IpAddr = network
+        .parse()
+        .map_err(|_| core_error!("invalid subnet address ({})", network))?;
+
+    let max_prefix_len = if network.is_ipv4() { 32 } else { 128 };
+
+    let prefix_len = match prefix_len {
+        Some(prefix_len) => prefix_len
+            .parse::<u8>()
+            .ok()
+            .filter(|prefix_len| *prefix_len <= max_prefix_len)
+            .ok_or_else(|| core_error!("invalid subnet prefix length ({})", prefix_len))?,
+        // a bare address is a subnet of itself
+        None => max_prefix_len,
+    };
+
+    Ok((network, prefix_len))
+}
+
+fn subnet_contains(network: IpAddr, prefix_len: u8, ip: IpAddr) -> bool {
+    // LAN server listens on IPv4 only, but mapped addresses may show up on dual stack
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        ip => ip,
+    };
+
+    match (network, ip) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
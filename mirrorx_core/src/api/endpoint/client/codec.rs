@@ -0,0 +1,206 @@
+//! Every endpoint message is prefixed with one byte header which tells its wire format and
+//! whether the payload is compressed. Large control messages like monitor screenshots of
+//! negotiation are compressed by zstd, media frames and file blocks are sent as is since
+//! they're either compressed by their codecs or too hot to spend time on.
+//!
+//! Bincode encodes enum variants by index and struct fields by position, so reordering
+//! or inserting them silently breaks peers of other releases. CBOR keys variants and
+//! fields by name, fields can be added with `#[serde(default)]` and unknown fields are
+//! skipped, so it's preferred whenever both peers read it.
+//!
+//! The wire format is negotiated once connected. Active endpoint sends a hello frame
+//! which lists the formats it reads, passive endpoint switches to the preferred one of
+//! them, and active endpoint switches as soon as it receives a frame in that format.
+//!
+//! Releases of protocol version 1 sent headerless bincode in the layouts of their own
+//! messages, they're rejected at key exchange (see `signaling::key_exchange`), so every
+//! peer which reaches here reads headers and both formats.
+
+use crate::{
+    api::endpoint::message::EndPointMessage,
+    core_error,
+    error::CoreResult,
+    utility::{
+        bincode::{bincode_deserialize, bincode_serialize, bincode_serialize_into},
+        cbor::{cbor_deserialize, cbor_serialize, cbor_serialize_into},
+    },
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    io::Read,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+const HEADER_BINCODE: u8 = 0xf0;
+const HEADER_BINCODE_ZSTD: u8 = 0xf1;
+const HEADER_CBOR: u8 = 0xf2;
+const HEADER_CBOR_ZSTD: u8 = 0xf3;
+const HEADER_HELLO: u8 = 0xff;
+
+// smaller messages gain little from compression
+const COMPRESS_THRESHOLD: usize = 16 * 1024;
+const COMPRESS_LEVEL: i32 = 3;
+
+// no message is larger than the max frame length of transport
+const MAX_DECOMPRESSED_SIZE: u64 = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum WireFormat {
+    Bincode = 0,
+    Cbor = 1,
+}
+
+impl WireFormat {
+    /// Formats this build reads, the preferred first.
+    pub const SUPPORTED: [WireFormat; 2] = [WireFormat::Cbor, WireFormat::Bincode];
+
+    fn from_u8(value: u8) -> Option<WireFormat> {
+        match value {
+            0 => Some(WireFormat::Bincode),
+            1 => Some(WireFormat::Cbor),
+            _ => None,
+        }
+    }
+
+    // headers of the raw and compressed payload
+    fn headers(self) -> (u8, u8) {
+        match self {
+            WireFormat::Bincode => (HEADER_BINCODE, HEADER_BINCODE_ZSTD),
+            WireFormat::Cbor => (HEADER_CBOR, HEADER_CBOR_ZSTD),
+        }
+    }
+
+    pub fn serialize<T: ?Sized + Serialize>(self, value: &T) -> CoreResult<Vec<u8>> {
+        match self {
+            WireFormat::Bincode => bincode_serialize(value),
+            WireFormat::Cbor => cbor_serialize(value),
+        }
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(self, bytes: &[u8]) -> CoreResult<T> {
+        match self {
+            WireFormat::Bincode => bincode_deserialize(bytes),
+            WireFormat::Cbor => cbor_deserialize(bytes),
+        }
+    }
+
+    fn serialize_into<W, T>(self, writer: W, value: &T) -> CoreResult<()>
+    where
+        W: std::io::Write,
+        T: ?Sized + Serialize,
+    {
+        match self {
+            WireFormat::Bincode => bincode_serialize_into(writer, value),
+            WireFormat::Cbor => cbor_serialize_into(writer, value),
+        }
+    }
+}
+
+/// Wire format of one connection, it starts with bincode until negotiated.
+#[derive(Debug, Default)]
+pub struct WireCodec {
+    format: AtomicU8,
+}
+
+impl WireCodec {
+    /// The frame which active endpoint sends first to negotiate the wire format.
+    pub fn hello() -> Vec<u8> {
+        let mut buffer = vec![HEADER_HELLO];
+        buffer.extend(WireFormat::SUPPORTED.iter().map(|format| *format as u8));
+        buffer
+    }
+
+    pub fn format(&self) -> WireFormat {
+        WireFormat::from_u8(self.format.load(Ordering::SeqCst)).unwrap_or(WireFormat::Bincode)
+    }
+
+    /// Start over with bincode, the connection is replaced and will be negotiated again.
+    pub fn reset(&self) {
+        self.format
+            .store(WireFormat::Bincode as u8, Ordering::SeqCst);
+    }
+
+    fn switch(&self, format: WireFormat) {
+        let previous = self.format.swap(format as u8, Ordering::SeqCst);
+        if previous != format as u8 {
+            tracing::info!(?format, "endpoint wire format switched");
+        }
+    }
+
+    pub fn encode(&self, message: &EndPointMessage) -> CoreResult<Vec<u8>> {
+        let format = self.format();
+        let (raw_header, compressed_header) = format.headers();
+
+        let mut buffer = vec![raw_header];
+        format.serialize_into(&mut buffer, message)?;
+
+        if buffer.len() <= COMPRESS_THRESHOLD || !is_control_message(message) {
+            return Ok(buffer);
+        }
+
+        let mut compressed = vec![compressed_header];
+        zstd::stream::copy_encode(&buffer[1..], &mut compressed, COMPRESS_LEVEL)?;
+
+        // random data like encrypted files doesn't shrink at all
+        if compressed.len() >= buffer.len() {
+            return Ok(buffer);
+        }
+
+        Ok(compressed)
+    }
+
+    /// Decode the frame from peer, returns None for the frames which only negotiate.
+    pub fn decode(&self, buffer: &[u8]) -> CoreResult<Option<EndPointMessage>> {
+        let Some((header, payload)) = buffer.split_first() else {
+            return Err(core_error!("empty endpoint message"));
+        };
+
+        let message = match *header {
+            HEADER_HELLO => {
+                // the first format which both peers read
+                if let Some(format) = WireFormat::SUPPORTED
+                    .into_iter()
+                    .find(|format| payload.contains(&(*format as u8)))
+                {
+                    self.switch(format);
+                }
+
+                return Ok(None);
+            }
+            HEADER_BINCODE => bincode_deserialize(payload)?,
+            HEADER_BINCODE_ZSTD => bincode_deserialize(&decompress(payload)?)?,
+            HEADER_CBOR => {
+                self.switch(WireFormat::Cbor);
+                cbor_deserialize(payload)?
+            }
+            HEADER_CBOR_ZSTD => {
+                self.switch(WireFormat::Cbor);
+                cbor_deserialize(&decompress(payload)?)?
+            }
+            header => return Err(core_error!("unknown endpoint message header ({})", header)),
+        };
+
+        Ok(Some(message))
+    }
+}
+
+fn decompress(payload: &[u8]) -> CoreResult<Vec<u8>> {
+    let mut decompressed = Vec::with_capacity(payload.len() * 4);
+    zstd::stream::Decoder::new(payload)?
+        .take(MAX_DECOMPRESSED_SIZE)
+        .read_to_end(&mut decompressed)?;
+
+    Ok(decompressed)
+}
+
+fn is_control_message(message: &EndPointMessage) -> bool {
+    !matches!(
+        message,
+        EndPointMessage::VideoFrame(_)
+            | EndPointMessage::MagnifierFrame(_)
+            | EndPointMessage::AudioFrame(_)
+            | EndPointMessage::FileTransferBlock(_)
+            | EndPointMessage::FileDeltaBlock(_)
+    )
+}
@@ -1,54 +1,255 @@
+pub(crate) mod codec;
+mod mux;
+pub mod socket;
+pub mod state;
 mod tcp;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+pub mod tls;
+pub mod trace;
 mod udp;
 
-use self::{tcp::serve_tcp, udp::serve_udp};
+use self::{
+    codec::WireCodec,
+    mux::{transfer_stream, Lane, MuxSender},
+    socket::{tune_tcp_stream, tune_udp_socket},
+    state::{CloseReason, SessionState, SessionStateMachine},
+    tcp::serve_tcp,
+    tls::connect_tls,
+    trace::TraceDirection,
+    udp::serve_udp,
+};
+
+pub use self::codec::WireFormat;
 use super::{
-    handlers::negotiate_desktop_params::handle_negotiate_desktop_params_request, id::EndPointID,
-    message::*, EndPointStream,
+    id::EndPointID,
+    message::*,
+    metadata::{local_metadata_message, validate_metadata},
+    permission::{default_permissions, Permissions, SessionPermissions},
+    queue::SessionSlot,
+    sessions::{self, Traffic},
+    EndPointStream,
 };
 use crate::{
-    api::endpoint::handlers::{
-        fs_download_file::handle_download_file_request, fs_send_file::handle_send_file_request,
-        fs_visit_directory::handle_visit_directory_request, input::handle_input,
-        negotiate_finished::handle_negotiate_finished_request,
+    api::{
+        config::{self, change::ConfigChange},
+        endpoint::handlers::{
+            elevate_permissions::handle_elevate_permissions_request,
+            fs_download_file::handle_download_file_request,
+            fs_file_manifest::handle_file_manifest_request,
+            fs_operation::handle_fs_operation_request, fs_send_file::handle_send_file_request,
+            fs_visit_directory::handle_visit_directory_request,
+            magnifier_frame::{serve_magnifier_decode, MagnifiedFrame},
+            video_frame::{self, take_key_frame_wanted},
+        },
+        notification::{self, Notification},
+        signaling::observer::revoke_observer_tokens,
     },
     call,
     component::{
+        clock_sync::{self, CLOCK_SYNC_INTERVAL},
+        codec_probe::decodable_codecs,
         desktop::monitor::Monitor,
-        fs::transfer::{append_file_block, delete_file_append_session},
+        fs::{
+            staging,
+            transfer::{
+                append_file_block, delete_file_append_session, delete_file_patch_session,
+                patch_file_block,
+            },
+        },
+        governor::GovernorGuard,
+        power::keep_awake,
+        quality_alert::{self, QualityAlert, QualityMonitor, QualityRemedy},
+        session_profile::session_profile,
+        session_stats, telemetry,
+        video_decoder::decoder::{gop_policy, preferred_codecs},
     },
     core_error,
     error::{CoreError, CoreResult},
-    utility::{
-        bincode::{bincode_deserialize, bincode_serialize},
-        nonce_value::NonceValue,
-    },
+    service::plugin::{self, SessionEvent},
+    utility::{net::connect_tcp, nonce_value::NonceValue, spawn},
 };
 use bytes::Bytes;
+use futures::future::BoxFuture;
 use ring::aead::{OpeningKey, SealingKey};
 use scopeguard::defer;
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Serialize};
 use std::{
-    fmt::Display,
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
+    future::Future,
     ops::Deref,
-    sync::{atomic::AtomicU16, Arc},
-    time::Duration,
+    sync::{
+        atomic::{AtomicBool, AtomicU16, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{
+    mpsc::{Receiver, Sender},
+    oneshot, RwLock,
+};
+use tokio_util::sync::CancellationToken;
+
+#[cfg(feature = "host")]
+use crate::api::endpoint::handlers::{
+    block_local_input::handle_block_local_input_request, input::handle_input,
+    magnify::handle_magnify_request,
+    negotiate_desktop_params::handle_negotiate_desktop_params_request,
+    negotiate_finished::handle_negotiate_finished_request,
+    observer::handle_invite_observer_request,
+    process::{handle_process_kill_request, handle_process_list_request},
+    switch_audio_capture::handle_switch_audio_capture_request,
+    switch_audio_processing::handle_switch_audio_processing_request,
+    switch_host_priority::handle_switch_host_priority_request,
+    switch_monitor::handle_switch_monitor_request,
+    switch_resolution::handle_switch_resolution_request,
+    switch_stream_quality::handle_switch_stream_quality_request,
+    system_action::handle_system_action_request, system_volume::handle_system_volume_request,
+    unattended_login::handle_login_request,
 };
-use tokio::sync::{mpsc::Sender, RwLock};
+
+#[cfg(feature = "host")]
+use crate::component::{display_control, local_input_block};
 
 const RECV_MESSAGE_TIMEOUT: Duration = Duration::from_secs(30);
 
+// packets which can't be opened are skipped, like the corrupt datagrams of UDP, the
+// connection is dropped only when they keep failing since it's likely tampered
+const MAX_CONSECUTIVE_OPEN_FAILURES: u32 = 16;
+
+// rebooting takes minutes on slow machines, especially when updates are installed
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(600);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
+
+// half a second of frames at the full frame rate, frames behind them are too late to show
+const VIDEO_BACKLOG_WATERMARK: usize = 30;
+
+// encoder waits for the transport longer than this only when the network stalls
+const ENCODER_STALL_THRESHOLD: Duration = Duration::from_millis(500);
+
+type DesktopFrameSenders = (Sender<EndPointVideoFrame>, Sender<EndPointAudioFrame>);
+
 #[derive(Debug, Clone)]
 pub struct EndPointClient {
+    session_id: Arc<String>,
     endpoint_id: EndPointID,
     monitor: Arc<RwLock<Option<Arc<Monitor>>>>,
-    tx: Sender<Vec<u8>>,
+    // replaced when the session is resumed by reconnecting
+    tx: Arc<Mutex<MuxSender>>,
+    // negotiated again when the session is resumed
+    codec: Arc<WireCodec>,
     call_id: Arc<AtomicU16>,
     call_store: Arc<moka::sync::Cache<u16, Sender<Vec<u8>>>>,
+    cursor: Arc<RwLock<Option<RemoteCursor>>>,
+    governor_guard: Option<Arc<GovernorGuard>>,
+    // passive endpoint frees its session slot for queued visits once closed
+    session_slot: Option<Arc<SessionSlot>>,
+    // active endpoint calls its peer and sends input, it never serves them
+    active: bool,
+    permissions: Arc<SessionPermissions>,
+    // desktop params are negotiated again when desktop channel is opened later
+    audio_source: Arc<Mutex<Option<AudioSource>>>,
+    audio_applications: Arc<Mutex<Arc<Vec<AudioApplication>>>>,
+    remote_keyboard_layout: Arc<Mutex<Option<KeyboardLayout>>>,
+    audio_capture_target: Arc<RwLock<AudioCaptureTarget>>,
+    audio_processing: Arc<AtomicBool>,
+    gop_policy: Arc<Mutex<GopPolicy>>,
+    session_profile: Arc<Mutex<SessionProfile>>,
+    stream_quality: Arc<Mutex<StreamQuality>>,
+    host_priority: Arc<AtomicBool>,
+    remote_local_input_blocked: Arc<AtomicBool>,
+    key_frame_requested: Arc<AtomicBool>,
+    watermark: Arc<AtomicBool>,
+    video_codec: Arc<Mutex<VideoCodec>>,
+    // passive endpoint streams the region besides the desktop
+    magnifier_region: Arc<Mutex<Option<MagnifierRegion>>>,
+    // active endpoint decodes the region frames with it once the window asks for them
+    magnifier_frame_tx: Arc<Mutex<Option<Sender<EndPointMagnifierFrame>>>>,
+    // passive endpoint tells observers of the session what it negotiated
+    negotiated_desktop_params: Arc<Mutex<Option<EndPointNegotiateVisitDesktopParams>>>,
+    // passive endpoint of observer streams the desktop of this session instead of its own
+    observed_session_id: Option<Arc<String>>,
+    // passive endpoint sends the media of the session to its observers too
+    observers: Arc<Mutex<Vec<Arc<EndPointClient>>>>,
+    reconnector: Arc<Mutex<Option<Arc<Reconnector>>>>,
+    // passive endpoint is going offline on purpose, like rebooting
+    reconnect_expected: Arc<AtomicBool>,
+    state: Arc<SessionStateMachine>,
+    input_block_reason: Arc<Mutex<Option<InputBlockReason>>>,
+    capture_interruption: Arc<Mutex<Option<CaptureInterruption>>>,
+    // entries which the peer advertised, see `metadata`
+    remote_metadata: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    traffic: Arc<Traffic>,
+    // set by active endpoint for the remote device, passive endpoint follows its peer
+    bandwidth_limit: Arc<Mutex<Option<u32>>>,
+    // logical channels of active endpoint, the connection is closed with the last one
+    channels: Arc<Mutex<HashSet<EndPointChannel>>>,
+    desktop_frame_tx: Arc<Mutex<Option<DesktopFrameSenders>>>,
+    negotiate_tx: Arc<Mutex<Option<oneshot::Sender<EndPointNegotiateDesktopParamsResponse>>>>,
+    // passive endpoint streams desktop with the clients bound to an epoch, which is
+    // bumped when desktop channel is closed or negotiated again
+    desktop_epoch: Arc<AtomicU64>,
+    bound_desktop_epoch: Option<u64>,
+    closed: CancellationToken,
+}
+
+/// What's needed to connect passive endpoint again.
+pub struct ReconnectParams {
+    pub key_pair: Option<(OpeningKey<NonceValue>, SealingKey<NonceValue>)>,
+    pub stream: EndPointStream,
+    pub visit_credentials: Option<Vec<u8>>,
+}
+
+/// What's needed to create the client of a session.
+struct CreateParams {
+    session_id: String,
+    active: bool,
+    endpoint_id: EndPointID,
+    key_pair: Option<(OpeningKey<NonceValue>, SealingKey<NonceValue>)>,
+    stream: EndPointStream,
+    // active endpoint of desktop session hands the frames to them
+    desktop_frame_tx: Option<DesktopFrameSenders>,
+    visit_credentials: Option<Vec<u8>>,
+    // passive endpoint only
+    session_slot: Option<SessionSlot>,
+    observed_session_id: Option<String>,
+}
+
+/// Visits passive endpoint again with stored credentials, it's retried until passive
+/// endpoint is back online.
+pub struct Reconnector(
+    Box<dyn Fn() -> BoxFuture<'static, CoreResult<ReconnectParams>> + Send + Sync>,
+);
+
+impl Reconnector {
+    pub fn new<F, Fut>(reconnect: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = CoreResult<ReconnectParams>> + Send + 'static,
+    {
+        Reconnector(Box::new(move || Box::pin(reconnect())))
+    }
+}
+
+impl Debug for Reconnector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Reconnector")
+    }
+}
+
+/// The latest cursor of passive endpoint when negotiated with `CursorMode::Separate`.
+#[derive(Debug, Clone)]
+pub struct RemoteCursor {
+    pub visible: bool,
+    pub x: i32,
+    pub y: i32,
+    pub shape: Option<Arc<EndPointCursorShape>>,
 }
 
 impl EndPointClient {
     pub async fn new_desktop_active(
+        session_id: String,
         endpoint_id: EndPointID,
         stream_key: Option<(OpeningKey<NonceValue>, SealingKey<NonceValue>)>,
         stream: EndPointStream,
@@ -56,138 +257,597 @@ impl EndPointClient {
         audio_frame_tx: Sender<EndPointAudioFrame>,
         visit_credentials: Option<Vec<u8>>,
     ) -> CoreResult<Arc<EndPointClient>> {
-        EndPointClient::create(
-            true,
+        EndPointClient::create(CreateParams {
+            session_id,
+            active: true,
             endpoint_id,
-            stream_key,
+            key_pair: stream_key,
             stream,
-            Some(video_frame_tx),
-            Some(audio_frame_tx),
+            desktop_frame_tx: Some((video_frame_tx, audio_frame_tx)),
             visit_credentials,
-        )
+            session_slot: None,
+            observed_session_id: None,
+        })
         .await
     }
 
     pub async fn new_file_manager_active(
+        session_id: String,
         endpoint_id: EndPointID,
         stream_key: Option<(OpeningKey<NonceValue>, SealingKey<NonceValue>)>,
         stream: EndPointStream,
         visit_credentials: Option<Vec<u8>>,
     ) -> CoreResult<Arc<EndPointClient>> {
-        EndPointClient::create(
-            true,
+        EndPointClient::create(CreateParams {
+            session_id,
+            active: true,
             endpoint_id,
-            stream_key,
+            key_pair: stream_key,
             stream,
-            None,
-            None,
+            desktop_frame_tx: None,
             visit_credentials,
-        )
+            session_slot: None,
+            observed_session_id: None,
+        })
         .await
     }
 
     pub async fn new_passive(
+        session_id: String,
         endpoint_id: EndPointID,
         key_pair: Option<(OpeningKey<NonceValue>, SealingKey<NonceValue>)>,
         stream: EndPointStream,
         visit_credentials: Option<Vec<u8>>,
+        session_slot: SessionSlot,
+        observed_session_id: Option<String>,
     ) -> CoreResult<()> {
-        let _ = EndPointClient::create(
-            false,
+        let _ = EndPointClient::create(CreateParams {
+            session_id,
+            active: false,
             endpoint_id,
             key_pair,
             stream,
-            None,
-            None,
+            desktop_frame_tx: None,
             visit_credentials,
-        )
+            session_slot: Some(session_slot),
+            observed_session_id,
+        })
         .await?;
         Ok(())
     }
 
-    #[allow(clippy::too_many_arguments)]
-    async fn create(
-        active: bool,
-        endpoint_id: EndPointID,
-        key_pair: Option<(OpeningKey<NonceValue>, SealingKey<NonceValue>)>,
-        stream: EndPointStream,
-        video_frame_tx: Option<Sender<EndPointVideoFrame>>,
-        audio_frame_tx: Option<Sender<EndPointAudioFrame>>,
-        visit_credentials: Option<Vec<u8>>,
-    ) -> CoreResult<Arc<EndPointClient>> {
-        let (opening_key, sealing_key) = match key_pair {
-            Some((opening_key, sealing_key)) => (Some(opening_key), Some(sealing_key)),
-            None => (None, None),
-        };
+    async fn create(params: CreateParams) -> CoreResult<Arc<EndPointClient>> {
+        let CreateParams {
+            session_id,
+            active,
+            endpoint_id,
+            key_pair,
+            stream,
+            desktop_frame_tx,
+            visit_credentials,
+            session_slot,
+            observed_session_id,
+        } = params;
 
-        let (tx, mut rx) = match stream {
-            EndPointStream::ActiveTCP(addr) => {
-                let stream = tokio::time::timeout(
-                    Duration::from_secs(10),
-                    tokio::net::TcpStream::connect(addr),
-                )
-                .await
-                .map_err(|_| CoreError::Timeout)??;
+        // the session is closed as failed if it's dropped by any error below
+        let state = Arc::new(SessionStateMachine::new(session_id.clone(), endpoint_id));
 
-                serve_tcp(
-                    stream,
-                    endpoint_id,
-                    sealing_key,
-                    opening_key,
-                    visit_credentials,
-                )
-                .await?
-            }
-            EndPointStream::ActiveUDP(_) => panic!("not support yet"),
-            EndPointStream::PassiveTCP(stream) => {
-                serve_tcp(
-                    stream,
-                    endpoint_id,
-                    sealing_key,
-                    opening_key,
-                    visit_credentials,
-                )
-                .await?
-            }
-            EndPointStream::PassiveUDP { socket, .. } => {
-                serve_udp(
-                    socket,
-                    endpoint_id,
-                    sealing_key,
-                    opening_key,
-                    visit_credentials,
-                )
-                .await?
-            }
-        };
+        let (tx, mut rx) = connect(
+            endpoint_id,
+            key_pair,
+            stream,
+            visit_credentials,
+            Some(&state),
+            None,
+        )
+        .await?;
+        let codec = Arc::new(WireCodec::default());
+
+        if active {
+            send_hello(&tx).await?;
+        }
 
         // active endpoint should start negotiate with passive endpoint
-        let primary_monitor = if active && video_frame_tx.is_some() && audio_frame_tx.is_some() {
-            let params = serve_active_negotiate(&tx, &mut rx).await?;
-            Some(Arc::new(params.primary_monitor))
+        let desktop_params = if active && desktop_frame_tx.is_some() {
+            state.transit(SessionState::Negotiating);
+            Some(serve_active_negotiate(&codec, &tx, &mut rx).await?)
         } else {
             None
         };
 
+        Ok(EndPointClient::start(
+            session_id,
+            active,
+            endpoint_id,
+            state,
+            (tx, rx),
+            codec,
+            desktop_frame_tx,
+            desktop_params,
+            session_slot,
+            observed_session_id,
+        )
+        .await)
+    }
+
+    /// Serve the connected and negotiated transport, replays of traces start here with
+    /// the recorded messages in place of the transport.
+    #[allow(clippy::too_many_arguments)]
+    async fn start(
+        session_id: String,
+        active: bool,
+        endpoint_id: EndPointID,
+        state: Arc<SessionStateMachine>,
+        (tx, rx): (MuxSender, Receiver<Bytes>),
+        codec: Arc<WireCodec>,
+        desktop_frame_tx: Option<DesktopFrameSenders>,
+        desktop_params: Option<EndPointNegotiateVisitDesktopParams>,
+        session_slot: Option<SessionSlot>,
+        observed_session_id: Option<String>,
+    ) -> Arc<EndPointClient> {
+        let mut channels = HashSet::new();
+        if active {
+            channels.insert(if desktop_frame_tx.is_some() {
+                EndPointChannel::Desktop
+            } else {
+                EndPointChannel::Files
+            });
+        }
+
         let call_store = moka::sync::CacheBuilder::new(32)
             .time_to_live(Duration::from_secs(60))
             .build();
 
         let client = Arc::new(EndPointClient {
+            session_id: Arc::new(session_id),
             endpoint_id,
-            monitor: Arc::new(RwLock::new(primary_monitor)),
-            tx,
+            monitor: Arc::new(RwLock::new(None)),
+            tx: Arc::new(Mutex::new(tx)),
+            codec,
             call_id: Arc::new(AtomicU16::new(0)),
             call_store: Arc::new(call_store),
+            cursor: Arc::new(RwLock::new(None)),
+            // passive endpoint serves the session with host resources, keep them governed
+            governor_guard: (!active).then(|| Arc::new(GovernorGuard::acquire())),
+            session_slot: session_slot.map(Arc::new),
+            active,
+            // active endpoint mirrors the permissions which passive endpoint enforces,
+            // observers can only watch
+            permissions: Arc::new(SessionPermissions::new(if active {
+                Permissions::ALL
+            } else if observed_session_id.is_some() {
+                Permissions::NONE
+            } else {
+                default_permissions()
+            })),
+            audio_source: Arc::new(Mutex::new(None)),
+            audio_applications: Arc::new(Mutex::new(Arc::new(Vec::new()))),
+            remote_keyboard_layout: Arc::new(Mutex::new(None)),
+            audio_capture_target: Arc::new(RwLock::new(AudioCaptureTarget::default())),
+            audio_processing: Arc::new(AtomicBool::new(true)),
+            gop_policy: Arc::new(Mutex::new(GopPolicy::default())),
+            session_profile: Arc::new(Mutex::new(SessionProfile::default())),
+            stream_quality: Arc::new(Mutex::new(StreamQuality::default())),
+            host_priority: Arc::new(AtomicBool::new(false)),
+            remote_local_input_blocked: Arc::new(AtomicBool::new(false)),
+            key_frame_requested: Arc::new(AtomicBool::new(false)),
+            watermark: Arc::new(AtomicBool::new(false)),
+            video_codec: Arc::new(Mutex::new(VideoCodec::H264)),
+            magnifier_region: Arc::new(Mutex::new(None)),
+            magnifier_frame_tx: Arc::new(Mutex::new(None)),
+            negotiated_desktop_params: Arc::new(Mutex::new(None)),
+            observed_session_id: observed_session_id.map(Arc::new),
+            observers: Arc::new(Mutex::new(Vec::new())),
+            reconnector: Arc::new(Mutex::new(None)),
+            reconnect_expected: Arc::new(AtomicBool::new(false)),
+            state,
+            input_block_reason: Arc::new(Mutex::new(None)),
+            capture_interruption: Arc::new(Mutex::new(None)),
+            remote_metadata: Arc::new(Mutex::new(HashMap::new())),
+            traffic: Arc::new(Traffic::default()),
+            bandwidth_limit: Arc::new(Mutex::new(None)),
+            channels: Arc::new(Mutex::new(channels)),
+            desktop_frame_tx: Arc::new(Mutex::new(desktop_frame_tx)),
+            negotiate_tx: Arc::new(Mutex::new(None)),
+            desktop_epoch: Arc::new(AtomicU64::new(0)),
+            bound_desktop_epoch: None,
+            closed: CancellationToken::new(),
         });
 
-        handle_message(client.clone(), rx, video_frame_tx, audio_frame_tx);
+        if let Some(params) = desktop_params {
+            client.apply_desktop_params(params).await;
+        }
+
+        client.state.transit(SessionState::Streaming);
+
+        handle_message(client.clone(), rx);
+
+        if let Some(metadata) = local_metadata_message() {
+            if let Err(err) = client.send(&metadata).await {
+                tracing::warn!(?err, "send local metadata failed");
+            }
+        }
+
+        sessions::register(&client, active);
+        plugin::session_connected(&client, active);
+
+        if active {
+            client.refresh_permissions();
+            client.follow_config_changes();
+            client.serve_clock_sync();
+            client.serve_quality_alerts();
+        } else {
+            // the session dies if host falls asleep
+            keep_awake::session_started(client.session_id());
+        }
 
-        Ok(client)
+        client
     }
 }
 
+/// Connect the transport and handshake over it, `state` is None when the session is
+/// resumed since it stays reconnecting until it's streaming again. Socket buffers are
+/// sized for `bandwidth_limit` when the peer is limited.
+async fn connect(
+    endpoint_id: EndPointID,
+    key_pair: Option<(OpeningKey<NonceValue>, SealingKey<NonceValue>)>,
+    stream: EndPointStream,
+    visit_credentials: Option<Vec<u8>>,
+    state: Option<&SessionStateMachine>,
+    bandwidth_limit: Option<u32>,
+) -> CoreResult<(MuxSender, Receiver<Bytes>)> {
+    let handshaking = || {
+        if let Some(state) = state {
+            state.transit(SessionState::Handshaking);
+        }
+    };
+
+    let (opening_key, sealing_key) = match key_pair {
+        Some((opening_key, sealing_key)) => (Some(opening_key), Some(sealing_key)),
+        None => (None, None),
+    };
+
+    let (tx, rx) = match stream {
+        EndPointStream::ActiveTCP(addr) => {
+            let started = Instant::now();
+            let stream = tokio::time::timeout(Duration::from_secs(10), connect_tcp(addr))
+                .await
+                .map_err(|_| CoreError::Timeout)??;
+
+            tune_tcp_stream(&stream, Some(started.elapsed()), bandwidth_limit);
+            handshaking();
+            serve_tcp(
+                stream,
+                endpoint_id,
+                sealing_key,
+                opening_key,
+                visit_credentials,
+            )
+            .await?
+        }
+        EndPointStream::ActiveTLS(addr, relay_tls) => {
+            let stream = tokio::time::timeout(Duration::from_secs(10), async {
+                let started = Instant::now();
+                let stream = connect_tcp(addr).await?;
+                tune_tcp_stream(&stream, Some(started.elapsed()), bandwidth_limit);
+                handshaking();
+                connect_tls(stream, addr, &relay_tls).await
+            })
+            .await
+            .map_err(|_| CoreError::Timeout)??;
+
+            serve_tcp(
+                stream,
+                endpoint_id,
+                sealing_key,
+                opening_key,
+                visit_credentials,
+            )
+            .await?
+        }
+        EndPointStream::ActiveUDP(_) => panic!("not support yet"),
+        EndPointStream::PassiveTCP(stream) => {
+            tune_tcp_stream(&stream, None, bandwidth_limit);
+            handshaking();
+            serve_tcp(
+                stream,
+                endpoint_id,
+                sealing_key,
+                opening_key,
+                visit_credentials,
+            )
+            .await?
+        }
+        EndPointStream::PassiveUDP { socket, .. } => {
+            tune_udp_socket(&socket, bandwidth_limit);
+            handshaking();
+            serve_udp(
+                socket,
+                endpoint_id,
+                sealing_key,
+                opening_key,
+                visit_credentials,
+            )
+            .await?
+        }
+        #[cfg(feature = "test-support")]
+        EndPointStream::Duplex(stream) => {
+            handshaking();
+            serve_tcp(
+                stream,
+                endpoint_id,
+                sealing_key,
+                opening_key,
+                visit_credentials,
+            )
+            .await?
+        }
+    };
+
+    Ok((tx, rx))
+}
+
+async fn send_hello(tx: &MuxSender) -> CoreResult<()> {
+    tx.send(Lane::Input, WireCodec::hello()).await
+}
+
 impl EndPointClient {
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    pub fn endpoint_id(&self) -> EndPointID {
+        self.endpoint_id
+    }
+
+    pub(crate) fn traffic(&self) -> &Traffic {
+        &self.traffic
+    }
+
+    pub fn permissions(&self) -> Permissions {
+        self.permissions.get()
+    }
+
+    pub(crate) fn session_permissions(&self) -> &SessionPermissions {
+        &self.permissions
+    }
+
+    // permissions of active endpoint are what passive endpoint grants it, the peer of
+    // active endpoint is granted nothing
+    fn granted_permissions(&self) -> Permissions {
+        if self.active {
+            Permissions::NONE
+        } else {
+            self.permissions()
+        }
+    }
+
+    fn ensure_permissions(&self, required: Permissions) -> CoreResult<()> {
+        let missing = required.difference(self.granted_permissions());
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(core_error!(
+                "permission denied ({})",
+                missing.names().join(", ")
+            ))
+        }
+    }
+
+    /// The audio which passive endpoint negotiated to send, only known by active endpoint.
+    pub fn audio_source(&self) -> Option<AudioSource> {
+        *self.audio_source.lock().unwrap()
+    }
+
+    /// Applications of passive endpoint whose audio can be captured alone.
+    pub fn audio_applications(&self) -> Arc<Vec<AudioApplication>> {
+        self.audio_applications.lock().unwrap().clone()
+    }
+
+    /// Keyboard layout which passive endpoint types with, None when it's unknown.
+    pub fn remote_keyboard_layout(&self) -> Option<KeyboardLayout> {
+        self.remote_keyboard_layout.lock().unwrap().clone()
+    }
+
+    async fn apply_desktop_params(&self, params: EndPointNegotiateVisitDesktopParams) {
+        self.set_monitor(params.primary_monitor).await;
+        *self.audio_source.lock().unwrap() = Some(params.audio_source);
+        *self.audio_applications.lock().unwrap() = Arc::new(params.audio_applications);
+        *self.remote_keyboard_layout.lock().unwrap() = params.keyboard_layout;
+        self.set_watermark_enabled(params.watermark);
+        video_frame::set_video_codec(self.endpoint_id(), params.video_codec.clone());
+        self.set_video_codec(params.video_codec);
+        // what the negotiate finished request has just asked for
+        self.set_session_profile(session_profile());
+    }
+
+    pub async fn set_audio_capture_target(&self, target: AudioCaptureTarget) {
+        (*self.audio_capture_target.write().await) = target
+    }
+
+    /// Used by audio capture process and desktop window which run in blocking thread.
+    pub fn blocking_audio_capture_target(&self) -> AudioCaptureTarget {
+        *self.audio_capture_target.blocking_read()
+    }
+
+    pub(crate) fn blocking_set_audio_capture_target(&self, target: AudioCaptureTarget) {
+        (*self.audio_capture_target.blocking_write()) = target
+    }
+
+    /// Whether recorded microphone goes through echo cancellation and noise suppression.
+    pub fn audio_processing_enabled(&self) -> bool {
+        self.audio_processing.load(Ordering::SeqCst)
+    }
+
+    pub fn set_audio_processing_enabled(&self, enabled: bool) {
+        self.audio_processing.store(enabled, Ordering::SeqCst)
+    }
+
+    /// Used by desktop capture process which creates the video encoder with it.
+    pub fn gop_policy(&self) -> GopPolicy {
+        *self.gop_policy.lock().unwrap()
+    }
+
+    pub fn set_gop_policy(&self, policy: GopPolicy) {
+        *self.gop_policy.lock().unwrap() = policy
+    }
+
+    /// The profile which the session is negotiated with, both endpoints tune their part
+    /// of the pipeline with it.
+    pub fn session_profile(&self) -> SessionProfile {
+        *self.session_profile.lock().unwrap()
+    }
+
+    pub fn set_session_profile(&self, profile: SessionProfile) {
+        *self.session_profile.lock().unwrap() = profile
+    }
+
+    /// The value of metadata entry which the peer advertised, None when the peer doesn't
+    /// know the feature of the key.
+    pub fn remote_metadata(&self, key: &str) -> Option<Vec<u8>> {
+        self.remote_metadata.lock().unwrap().get(key).cloned()
+    }
+
+    pub fn remote_metadata_keys(&self) -> Vec<String> {
+        self.remote_metadata
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// Used by desktop capture process which filters every frame with it.
+    pub fn stream_quality(&self) -> StreamQuality {
+        *self.stream_quality.lock().unwrap()
+    }
+
+    pub fn set_stream_quality(&self, quality: StreamQuality) {
+        *self.stream_quality.lock().unwrap() = quality
+    }
+
+    /// Used by desktop capture process which encodes the region of every frame with it.
+    pub fn magnifier_region(&self) -> Option<MagnifierRegion> {
+        *self.magnifier_region.lock().unwrap()
+    }
+
+    pub(crate) fn set_magnifier_region(&self, region: Option<MagnifierRegion>) {
+        *self.magnifier_region.lock().unwrap() = region
+    }
+
+    /// Decode the magnifier frames which arrive from now on into the returned channel,
+    /// it replaces the channel returned before. Call `EndPointMagnifyRequest` to choose
+    /// the region.
+    pub fn serve_magnifier(&self) -> Receiver<MagnifiedFrame> {
+        let (frame_tx, render_rx) = serve_magnifier_decode(self.endpoint_id);
+        *self.magnifier_frame_tx.lock().unwrap() = Some(frame_tx);
+        render_rx
+    }
+
+    /// Drop the magnifier frames which are still in flight, their decoder exits.
+    pub fn stop_magnifier(&self) {
+        *self.magnifier_frame_tx.lock().unwrap() = None
+    }
+
+    /// The session which this session observes, only known by passive endpoint.
+    pub fn observed_session_id(&self) -> Option<&str> {
+        self.observed_session_id
+            .as_ref()
+            .map(|session_id| session_id.as_str())
+    }
+
+    pub(crate) fn negotiated_desktop_params(&self) -> Option<EndPointNegotiateVisitDesktopParams> {
+        self.negotiated_desktop_params.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set_negotiated_desktop_params(
+        &self,
+        params: EndPointNegotiateVisitDesktopParams,
+    ) {
+        *self.negotiated_desktop_params.lock().unwrap() = Some(params)
+    }
+
+    /// Send the media of the session to the observer too, until either of them is closed.
+    pub(crate) fn attach_observer(&self, observer: Arc<EndPointClient>) {
+        self.observers.lock().unwrap().push(observer);
+
+        // the observer can't decode anything before a key frame
+        self.key_frame_requested.store(true, Ordering::SeqCst);
+    }
+
+    // observers share the encoder of the session they observe
+    fn request_key_frame(&self) {
+        match self.observed_session_id().and_then(sessions::find) {
+            Some(observed) => observed.key_frame_requested.store(true, Ordering::SeqCst),
+            None => self.key_frame_requested.store(true, Ordering::SeqCst),
+        }
+    }
+
+    /// Whether streaming yields to the user of passive endpoint, capture and encode
+    /// threads tune themselves with it.
+    pub fn host_priority(&self) -> bool {
+        self.host_priority.load(Ordering::SeqCst)
+    }
+
+    pub fn set_host_priority(&self, enabled: bool) {
+        self.host_priority.store(enabled, Ordering::SeqCst)
+    }
+
+    /// Whether the mouse and keyboard of passive endpoint's user are blocked for this
+    /// session, see `component::local_input_block`.
+    pub fn remote_local_input_blocked(&self) -> bool {
+        self.remote_local_input_blocked.load(Ordering::SeqCst)
+    }
+
+    pub fn set_remote_local_input_blocked(&self, blocked: bool) {
+        self.remote_local_input_blocked
+            .store(blocked, Ordering::SeqCst)
+    }
+
+    /// Whether active endpoint asked for a key frame since the last call, encode threads
+    /// force the next frame to be one.
+    pub fn take_key_frame_request(&self) -> bool {
+        self.key_frame_requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// Whether the desktop of passive endpoint is streamed with watermark.
+    pub fn watermark_enabled(&self) -> bool {
+        self.watermark.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_watermark_enabled(&self, enabled: bool) {
+        self.watermark.store(enabled, Ordering::SeqCst)
+    }
+
+    /// The codec which desktop is streamed with.
+    pub fn video_codec(&self) -> VideoCodec {
+        self.video_codec.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set_video_codec(&self, codec: VideoCodec) {
+        *self.video_codec.lock().unwrap() = codec
+    }
+
+    /// Why input can't reach remote desktop now, None when it can.
+    pub fn input_block_reason(&self) -> Option<InputBlockReason> {
+        *self.input_block_reason.lock().unwrap()
+    }
+
+    /// Why remote desktop isn't captured as usual now, None when it is.
+    pub fn capture_interruption(&self) -> Option<CaptureInterruption> {
+        *self.capture_interruption.lock().unwrap()
+    }
+
+    /// The cap of audio, video and file traffic in kilobits per second, None when unlimited.
+    pub fn bandwidth_limit(&self) -> Option<u32> {
+        *self.bandwidth_limit.lock().unwrap()
+    }
+
+    fn apply_bandwidth_limit(&self, max_kbps: Option<u32>) {
+        *self.bandwidth_limit.lock().unwrap() = max_kbps;
+        self.outgoing_tx().set_bandwidth_limit(max_kbps);
+    }
+
     pub async fn monitor(&self) -> Option<Arc<Monitor>> {
         (*self.monitor.read().await).clone()
     }
@@ -195,32 +855,156 @@ impl EndPointClient {
     pub async fn set_monitor(&self, monitor: Monitor) {
         (*self.monitor.write().await) = Some(Arc::new(monitor))
     }
+
+    /// Used by capture process which runs in blocking thread.
+    pub fn blocking_monitor(&self) -> Option<Arc<Monitor>> {
+        (*self.monitor.blocking_read()).clone()
+    }
+
+    /// Used by desktop window which draws remote cursor in its update loop.
+    pub fn blocking_cursor(&self) -> Option<RemoteCursor> {
+        (*self.cursor.blocking_read()).clone()
+    }
+
+    async fn update_cursor(&self, cursor: EndPointCursor) {
+        let mut current_cursor = self.cursor.write().await;
+
+        // cursor shape is only sent when it changed
+        let shape = match cursor.shape {
+            Some(shape) => Some(Arc::new(shape)),
+            None => current_cursor
+                .as_ref()
+                .and_then(|current_cursor| current_cursor.shape.clone()),
+        };
+
+        *current_cursor = Some(RemoteCursor {
+            visible: cursor.visible,
+            x: cursor.x,
+            y: cursor.y,
+            shape,
+        });
+    }
 }
 
 impl EndPointClient {
+    fn outgoing_tx(&self) -> MuxSender {
+        self.tx.lock().unwrap().clone()
+    }
+
+    // every sent message is encoded here, so it's traced
+    fn encode(&self, message: &EndPointMessage) -> CoreResult<Vec<u8>> {
+        let buffer = self.codec.encode(message)?;
+        trace::record(
+            &self.endpoint_id,
+            TraceDirection::Sent,
+            message,
+            buffer.len(),
+        );
+        self.traffic.record_sent(buffer.len());
+        Ok(buffer)
+    }
+
     pub fn try_send(&self, message: &EndPointMessage) -> CoreResult<()> {
-        let buffer = bincode_serialize(message)?;
-        self.tx
-            .try_send(buffer)
-            .map_err(|_| CoreError::OutgoingMessageChannelDisconnect)
+        self.ensure_desktop_stream()?;
+        self.fan_out(message);
+        let buffer = self.encode(message)?;
+        self.outgoing_tx().try_send(Lane::of(message), buffer)
     }
 
     pub fn blocking_send(&self, message: &EndPointMessage) -> CoreResult<()> {
-        let buffer = bincode_serialize(message)?;
-        self.tx
-            .blocking_send(buffer)
-            .map_err(|_| CoreError::OutgoingMessageChannelDisconnect)
+        self.ensure_desktop_stream()?;
+        self.fan_out(message);
+        let buffer = self.encode(message)?;
+        self.outgoing_tx().blocking_send(Lane::of(message), buffer)
+    }
+
+    /// Send the encoded video frame, the time it waits for the transport is recorded as
+    /// an encoder stall when it's too long.
+    pub fn blocking_send_video_frame(&self, frame: EndPointVideoFrame) -> CoreResult<()> {
+        let started = Instant::now();
+        self.blocking_send(&EndPointMessage::VideoFrame(frame))?;
+
+        let blocked = started.elapsed();
+        if blocked >= ENCODER_STALL_THRESHOLD {
+            tracing::warn!(?blocked, "video encoder stalled by transport");
+            session_stats::record_encoder_stall(self.endpoint_id);
+        }
+
+        Ok(())
+    }
+
+    /// Drop the queued video frames once they exceed the watermark, true if they're
+    /// dropped and the stream needs a key frame to go on.
+    pub fn shed_video_backlog(&self) -> bool {
+        let tx = self.outgoing_tx();
+        let queued_frames = tx.queued_frames(Lane::Video);
+        if queued_frames < VIDEO_BACKLOG_WATERMARK {
+            return false;
+        }
+
+        tx.discard_queued(Lane::Video);
+        session_stats::record_video_backlog_dropped(self.endpoint_id, queued_frames);
+        tracing::warn!(queued_frames, "video backlog exceeds watermark");
+
+        true
+    }
+
+    // observers get the media of the session on a best effort basis, so a slow one never
+    // holds back the session
+    fn fan_out(&self, message: &EndPointMessage) {
+        if !matches!(
+            message,
+            EndPointMessage::VideoFrame(_)
+                | EndPointMessage::AudioFrame(_)
+                | EndPointMessage::Cursor(_)
+        ) {
+            return;
+        }
+
+        self.observers.lock().unwrap().retain(|observer| {
+            if observer.closed() || observer.ensure_desktop_stream().is_err() {
+                tracing::info!(session_id = %observer.session_id(), "observer detached");
+                return false;
+            }
+
+            // frames after the dropped one can't be decoded until the next key frame
+            if observer.try_send(message).is_err()
+                && matches!(message, EndPointMessage::VideoFrame(_))
+            {
+                self.key_frame_requested.store(true, Ordering::SeqCst);
+            }
+
+            true
+        });
     }
 
     pub async fn send(&self, message: &EndPointMessage) -> CoreResult<()> {
-        let buffer = bincode_serialize(message)?;
-        self.tx
-            .send(buffer)
-            .await
-            .map_err(|_| CoreError::OutgoingMessageChannelDisconnect)
+        self.ensure_desktop_stream()?;
+        self.fan_out(message);
+        let buffer = self.encode(message)?;
+
+        // transfers take turns on the lane, uploads and downloads run side by side
+        match transfer_stream(message) {
+            Some(stream) => self.outgoing_tx().send_transfer(stream, buffer).await,
+            None => self.outgoing_tx().send(Lane::of(message), buffer).await,
+        }
     }
 
     pub async fn call<TReply>(&self, message: EndPointCallRequest) -> CoreResult<TReply>
+    where
+        TReply: DeserializeOwned,
+    {
+        self.call_cancelable(message, &CancellationToken::new())
+            .await
+    }
+
+    /// Call like `call` but give up awaiting the reply once `cancel` is canceled, the reply
+    /// which arrives later is dropped.
+    pub async fn call_cancelable<TReply>(
+        &self,
+        message: EndPointCallRequest,
+        cancel: &CancellationToken,
+    ) -> CoreResult<TReply>
     where
         TReply: DeserializeOwned,
     {
@@ -238,179 +1022,1192 @@ impl EndPointClient {
         self.send(&EndPointMessage::CallRequest(call_id, message))
             .await?;
 
-        let reply_bytes = rx.recv().await.ok_or(CoreError::Timeout)?;
+        let reply_bytes = tokio::select! {
+            _ = cancel.cancelled() => return Err(CoreError::Canceled),
+            reply_bytes = rx.recv() => reply_bytes.ok_or(CoreError::Timeout)?,
+        };
 
-        bincode_deserialize::<Result<TReply, String>>(&reply_bytes)?
+        self.codec
+            .format()
+            .deserialize::<Result<TReply, String>>(&reply_bytes)?
             .map_err(|err_str| core_error!("{}", err_str))
     }
-}
 
-impl Display for EndPointClient {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "EndPointClient({})", self.endpoint_id)
+    /// Serialize the reply of call in the wire format of the connection. Peer switches
+    /// the format before it sends any request in it, so both ends agree on the format of
+    /// the replies.
+    pub(crate) fn serialize_reply<T: Serialize>(
+        &self,
+        reply: &Result<T, String>,
+    ) -> CoreResult<Vec<u8>> {
+        self.codec.format().serialize(reply)
     }
-}
 
-async fn serve_active_negotiate(
-    tx: &Sender<Vec<u8>>,
-    rx: &mut tokio::sync::mpsc::Receiver<Bytes>,
-) -> CoreResult<EndPointNegotiateVisitDesktopParams> {
-    let negotiate_request_buffer = bincode_serialize(
-        &EndPointMessage::NegotiateDesktopParamsRequest(EndPointNegotiateDesktopParamsRequest {
-            video_codecs: vec![VideoCodec::H264],
-        }),
-    )?;
+    /// Ask passive endpoint for more permissions, its user will be prompted. Requesting
+    /// none just refreshes the permissions which are granted now.
+    pub async fn request_permissions(
+        &self,
+        permissions: Permissions,
+    ) -> CoreResult<EndPointElevatePermissionsReply> {
+        let reply: EndPointElevatePermissionsReply = self
+            .call(EndPointCallRequest::ElevatePermissionsRequest(
+                EndPointElevatePermissionsRequest { permissions },
+            ))
+            .await?;
+
+        self.permissions.set(reply.permissions);
+
+        Ok(reply)
+    }
 
-    tx.send(negotiate_request_buffer)
+    /// Cap the bandwidth of the session in both directions, peer is told to keep its
+    /// traffic under the same limit.
+    pub async fn set_bandwidth_limit(&self, max_kbps: Option<u32>) -> CoreResult<()> {
+        self.apply_bandwidth_limit(max_kbps);
+        self.send(&EndPointMessage::BandwidthLimit(EndPointBandwidthLimit {
+            max_kbps,
+        }))
         .await
-        .map_err(|_| CoreError::OutgoingMessageChannelDisconnect)?;
+    }
+
+    fn refresh_permissions(self: &Arc<Self>) {
+        let client = self.clone();
+        spawn::spawn(async move {
+            if let Err(err) = client.request_permissions(Permissions::NONE).await {
+                tracing::error!(?err, "query session permissions failed");
+            }
+        });
+    }
+
+    // samples the clock of passive endpoint until the session is closed, replies are
+    // recorded by the message loop
+    fn serve_clock_sync(self: &Arc<Self>) {
+        let client = Arc::downgrade(self);
+        let closed = self.closed.clone();
+
+        spawn::spawn(async move {
+            let mut interval = tokio::time::interval(CLOCK_SYNC_INTERVAL);
+
+            loop {
+                tokio::select! {
+                    _ = closed.cancelled() => return,
+                    _ = interval.tick() => {}
+                };
+
+                let Some(client) = client.upgrade() else {
+                    return;
+                };
+
+                // the sample is lost while reconnecting, the next one makes up for it
+                let request = EndPointMessage::ClockSyncRequest(EndPointClockSyncRequest {
+                    origin_micros: clock_sync::now_micros(),
+                });
+
+                if let Err(err) = client.try_send(&request) {
+                    tracing::debug!(?err, "request clock sync failed");
+                }
+            }
+        });
+    }
+
+    // samples the stats of the desktop stream until the session is closed, sessions without
+    // the desktop channel have no stats and raise nothing
+    fn serve_quality_alerts(self: &Arc<Self>) {
+        let client = Arc::downgrade(self);
+        let closed = self.closed.clone();
+
+        spawn::spawn(async move {
+            let mut interval = tokio::time::interval(quality_alert::SAMPLE_INTERVAL);
+            let mut monitor = QualityMonitor::default();
+
+            loop {
+                tokio::select! {
+                    _ = closed.cancelled() => return,
+                    _ = interval.tick() => {}
+                };
+
+                let Some(client) = client.upgrade() else {
+                    return;
+                };
+
+                let Some(stats) = session_stats::session_stats(&client.endpoint_id) else {
+                    continue;
+                };
+
+                let frame_rate_limited =
+                    client.stream_quality() != StreamQuality::Full || client.host_priority();
+
+                for (issue, value) in monitor.sample(stats, frame_rate_limited) {
+                    let remedy = quality_alert::remedy(
+                        issue,
+                        &client.video_codec(),
+                        client.stream_quality(),
+                    );
+
+                    let mut applied = false;
+                    if let Some(QualityRemedy::ReduceStreamQuality { quality }) = remedy {
+                        if quality_alert::quality_auto_tune() {
+                            match client
+                                .call::<EndPointSwitchStreamQualityReply>(
+                                    EndPointCallRequest::SwitchStreamQualityRequest(
+                                        EndPointSwitchStreamQualityRequest { quality },
+                                    ),
+                                )
+                                .await
+                            {
+                                Ok(reply) => {
+                                    client.set_stream_quality(reply.quality);
+                                    // raised again if the reduced quality doesn't help
+                                    monitor.reset(issue);
+                                    applied = true;
+                                }
+                                Err(err) => {
+                                    tracing::error!(?err, "auto-tune stream quality failed")
+                                }
+                            }
+                        }
+                    }
+
+                    tracing::warn!(?issue, value, ?remedy, applied, "session quality degraded");
+
+                    notification::publish(Notification::QualityAlert {
+                        endpoint_id: client.endpoint_id,
+                        alert: QualityAlert {
+                            issue,
+                            value,
+                            remedy,
+                            applied,
+                        },
+                    });
+                }
+            }
+        });
+    }
+
+    // bandwidth limit of the remote device applies to the open session once it's changed
+    fn follow_config_changes(self: &Arc<Self>) {
+        let EndPointID::DeviceID {
+            remote_device_id, ..
+        } = self.endpoint_id
+        else {
+            return;
+        };
+
+        let client = Arc::downgrade(self);
+        let closed = self.closed.clone();
+        let mut rx = config::change::subscribe();
+
+        spawn::spawn(async move {
+            loop {
+                let change = tokio::select! {
+                    _ = closed.cancelled() => return,
+                    change = rx.recv() => change,
+                };
+
+                let change = match change {
+                    Ok(change) => change,
+                    Err(async_broadcast::RecvError::Overflowed(_)) => continue,
+                    Err(async_broadcast::RecvError::Closed) => return,
+                };
+
+                let ConfigChange::PeerBandwidthLimit {
+                    device_id,
+                    max_kbps,
+                    ..
+                } = change
+                else {
+                    continue;
+                };
+
+                if device_id != remote_device_id {
+                    continue;
+                }
+
+                let Some(client) = client.upgrade() else {
+                    return;
+                };
+
+                if let Err(err) = client.set_bandwidth_limit(max_kbps).await {
+                    tracing::error!(?err, "apply bandwidth limit failed");
+                }
+            }
+        });
+    }
+
+    /// Reboot the machine of passive endpoint, the session is resumed once it's back
+    /// online if a reconnector is set.
+    pub async fn reboot_remote(&self) -> CoreResult<EndPointSystemActionReply> {
+        // the connection may be closed by rebooting before the reply arrives
+        self.reconnect_expected.store(true, Ordering::SeqCst);
+
+        let result = self
+            .call(EndPointCallRequest::SystemActionRequest(
+                EndPointSystemActionRequest {
+                    action: SystemAction::Reboot,
+                },
+            ))
+            .await;
+
+        if result.is_err() {
+            self.reconnect_expected.store(false, Ordering::SeqCst);
+        }
+
+        result
+    }
+
+    /// Turn on the display of passive endpoint which is turned off by idle timeout.
+    pub async fn wake_remote_display(&self) -> CoreResult<EndPointSystemActionReply> {
+        self.call(EndPointCallRequest::SystemActionRequest(
+            EndPointSystemActionRequest {
+                action: SystemAction::WakeDisplay,
+            },
+        ))
+        .await
+    }
+
+    pub fn set_reconnector(&self, reconnector: Reconnector) {
+        *self.reconnector.lock().unwrap() = Some(Arc::new(reconnector));
+    }
+
+    /// Stop reconnecting, it's called when user closes the session.
+    pub fn cancel_reconnect(&self) {
+        self.reconnector.lock().unwrap().take();
+    }
+
+    /// Whether the session is waiting for passive endpoint to be back online.
+    pub fn reconnecting(&self) -> bool {
+        self.state.get() == SessionState::Reconnecting
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state.get()
+    }
+
+    /// Whether the connection is closed, either by its last channel or by passive endpoint.
+    pub fn closed(&self) -> bool {
+        self.closed.is_cancelled()
+    }
+
+    pub fn channel_opened(&self, channel: EndPointChannel) -> bool {
+        self.channels.lock().unwrap().contains(&channel)
+    }
+
+    /// Share the connection with the file manager window of the same remote device.
+    pub fn open_files_channel(&self) -> CoreResult<()> {
+        if self.closed() {
+            return Err(core_error!("endpoint connection is closed"));
+        }
+
+        self.channels.lock().unwrap().insert(EndPointChannel::Files);
+        Ok(())
+    }
+
+    /// Share the connection with the desktop window of the same remote device, desktop
+    /// params are negotiated over the connection which is serving files already.
+    pub async fn open_desktop_channel(
+        &self,
+        video_frame_tx: Sender<EndPointVideoFrame>,
+        audio_frame_tx: Sender<EndPointAudioFrame>,
+    ) -> CoreResult<()> {
+        if self.closed() {
+            return Err(core_error!("endpoint connection is closed"));
+        }
+
+        if self.channel_opened(EndPointChannel::Desktop) {
+            return Err(core_error!("desktop channel is opened already"));
+        }
+
+        self.state.transit(SessionState::Negotiating);
+
+        // files channel keeps streaming whether desktop params are negotiated or not
+        defer! {
+            self.state.transit(SessionState::Streaming);
+        }
+
+        let (negotiate_tx, negotiate_rx) = oneshot::channel();
+        *self.negotiate_tx.lock().unwrap() = Some(negotiate_tx);
+
+        self.send(&negotiate_desktop_params_request()).await?;
+
+        let negotiate_response = tokio::time::timeout(RECV_MESSAGE_TIMEOUT, negotiate_rx)
+            .await
+            .map_err(|_| CoreError::Timeout)??;
+
+        let params = accept_negotiate_response(negotiate_response)?;
+        let negotiate_finished = negotiate_finished_request(&params);
+
+        self.apply_desktop_params(params).await;
+        self.set_audio_capture_target(AudioCaptureTarget::System)
+            .await;
+        self.set_audio_processing_enabled(true);
+        self.set_stream_quality(StreamQuality::default());
+        self.set_host_priority(false);
+
+        // frames may arrive as soon as passive endpoint knows negotiation is finished
+        *self.desktop_frame_tx.lock().unwrap() = Some((video_frame_tx, audio_frame_tx));
+        self.channels
+            .lock()
+            .unwrap()
+            .insert(EndPointChannel::Desktop);
+
+        if let Err(err) = self.send(&negotiate_finished).await {
+            self.close_channel(EndPointChannel::Desktop);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Close the channel when its window is closed, passive endpoint is told to stop its
+    /// traffic. The connection is closed with the last channel.
+    pub fn close_channel(&self, channel: EndPointChannel) {
+        let remaining = {
+            let mut channels = self.channels.lock().unwrap();
+            if !channels.remove(&channel) {
+                return;
+            }
+            channels.len()
+        };
+
+        tracing::info!(?channel, remaining, "endpoint channel closed");
+
+        if channel == EndPointChannel::Desktop {
+            self.desktop_frame_tx.lock().unwrap().take();
+        }
+
+        if remaining == 0 {
+            self.close();
+            return;
+        }
+
+        if let Err(err) = self.try_send(&EndPointMessage::ChannelClosed(channel)) {
+            tracing::warn!(?err, ?channel, "notify remote channel closed failed");
+        }
+    }
+
+    /// Close the connection, message loop exits and the transport is released.
+    pub fn close(&self) {
+        self.cancel_reconnect();
+        self.closed.cancel();
+
+        // the transport write loop exits once the last sender is dropped
+        let (closed_tx, _) = mux::channel();
+        *self.tx.lock().unwrap() = closed_tx;
+    }
+
+    fn desktop_frame_tx(&self) -> Option<DesktopFrameSenders> {
+        self.desktop_frame_tx.lock().unwrap().clone()
+    }
+
+    /// The client which passive endpoint streams desktop with, its messages are rejected
+    /// once desktop channel is closed or negotiated again so the stream processes exit.
+    #[cfg(feature = "host")]
+    fn bind_desktop_stream(&self) -> Arc<EndPointClient> {
+        let epoch = self.desktop_epoch.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut client = self.clone();
+        client.bound_desktop_epoch = Some(epoch);
+        Arc::new(client)
+    }
+
+    fn ensure_desktop_stream(&self) -> CoreResult<()> {
+        match self.bound_desktop_epoch {
+            Some(epoch) if epoch != self.desktop_epoch.load(Ordering::SeqCst) => {
+                Err(CoreError::OutgoingMessageChannelDisconnect)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn resume(
+        &self,
+        params: ReconnectParams,
+        negotiate: bool,
+    ) -> CoreResult<Receiver<Bytes>> {
+        let (tx, mut rx) = connect(
+            self.endpoint_id,
+            params.key_pair,
+            params.stream,
+            params.visit_credentials,
+            None,
+            self.bandwidth_limit(),
+        )
+        .await?;
+
+        self.codec.reset();
+        send_hello(&tx).await?;
+
+        if negotiate {
+            let params = serve_active_negotiate(&self.codec, &tx, &mut rx).await?;
+            self.apply_desktop_params(params).await;
+
+            // passive endpoint starts over with what's negotiated
+            self.set_audio_capture_target(AudioCaptureTarget::System)
+                .await;
+            self.set_audio_processing_enabled(true);
+            self.set_stream_quality(StreamQuality::default());
+            self.set_host_priority(false);
+        }
+
+        // local input of passive endpoint is released along with the old connection
+        self.set_remote_local_input_blocked(false);
+
+        // passive endpoint of the new connection starts unlimited
+        let max_kbps = self.bandwidth_limit();
+        if max_kbps.is_some() {
+            let limit = EndPointMessage::BandwidthLimit(EndPointBandwidthLimit { max_kbps });
+            let limit_buffer = self.codec.encode(&limit)?;
+            tx.send(Lane::Input, limit_buffer).await?;
+        }
+        tx.set_bandwidth_limit(max_kbps);
+
+        // passive endpoint of the new connection hasn't seen the metadata either
+        if let Some(metadata) = local_metadata_message() {
+            let metadata_buffer = self.codec.encode(&metadata)?;
+            tx.send(Lane::Input, metadata_buffer).await?;
+        }
+
+        *self.tx.lock().unwrap() = tx;
+        *self.input_block_reason.lock().unwrap() = None;
+        *self.capture_interruption.lock().unwrap() = None;
+
+        Ok(rx)
+    }
+}
+
+impl Display for EndPointClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EndPointClient({})", self.endpoint_id)
+    }
+}
+
+async fn serve_active_negotiate(
+    codec: &WireCodec,
+    tx: &MuxSender,
+    rx: &mut tokio::sync::mpsc::Receiver<Bytes>,
+) -> CoreResult<EndPointNegotiateVisitDesktopParams> {
+    let request_buffer = codec.encode(&negotiate_desktop_params_request())?;
+    tx.send(Lane::Input, request_buffer).await?;
 
     let negotiate_response_buffer = tokio::time::timeout(RECV_MESSAGE_TIMEOUT, rx.recv())
         .await
         .map_err(|_| CoreError::Timeout)?
         .ok_or(CoreError::OutgoingMessageChannelDisconnect)?;
 
-    let EndPointMessage::NegotiateDesktopParamsResponse(negotiate_response) =
-        bincode_deserialize(negotiate_response_buffer.deref())? else {
-            return Err(core_error!("unexpected negotiate reply"));
-        };
+    let Some(EndPointMessage::NegotiateDesktopParamsResponse(negotiate_response)) =
+        codec.decode(negotiate_response_buffer.deref())?
+    else {
+        return Err(core_error!("unexpected negotiate reply"));
+    };
+
+    let params = accept_negotiate_response(negotiate_response)?;
+
+    let finished_buffer = codec.encode(&negotiate_finished_request(&params))?;
+    tx.send(Lane::Input, finished_buffer).await?;
+
+    Ok(params)
+}
+
+fn negotiate_desktop_params_request() -> EndPointMessage {
+    EndPointMessage::NegotiateDesktopParamsRequest(EndPointNegotiateDesktopParamsRequest {
+        video_codecs: decodable_codecs(&preferred_codecs()),
+        // prefer drawing the cursor locally so it keeps sharp and isn't delayed by encoding
+        cursor_modes: vec![CursorMode::Separate, CursorMode::Composited],
+    })
+}
 
-    let params = match negotiate_response {
+fn accept_negotiate_response(
+    negotiate_response: EndPointNegotiateDesktopParamsResponse,
+) -> CoreResult<EndPointNegotiateVisitDesktopParams> {
+    match negotiate_response {
         EndPointNegotiateDesktopParamsResponse::VideoError(err) => {
             tracing::error!(?err, "negotiate failed with video error");
-            return Err(core_error!("negotiate failed ({})", err));
+            Err(core_error!("negotiate failed ({})", err))
         }
         EndPointNegotiateDesktopParamsResponse::MonitorError(err) => {
             tracing::error!(?err, "negotiate failed with display error");
-            return Err(core_error!("negotiate failed ({})", err));
+            Err(core_error!("negotiate failed ({})", err))
         }
         EndPointNegotiateDesktopParamsResponse::Params(params) => {
             tracing::info!(?params, "negotiate success");
-            params
+            telemetry::record_video_codec(&params.video_codec);
+            Ok(params)
         }
-    };
-
-    let negotiate_request_buffer = bincode_serialize(&EndPointMessage::NegotiateFinishedRequest(
-        EndPointNegotiateFinishedRequest {
-            expected_frame_rate: 60,
-        },
-    ))?;
-
-    tx.send(negotiate_request_buffer)
-        .await
-        .map_err(|_| CoreError::OutgoingMessageChannelDisconnect)?;
+    }
+}
 
-    Ok(params)
+fn negotiate_finished_request(params: &EndPointNegotiateVisitDesktopParams) -> EndPointMessage {
+    EndPointMessage::NegotiateFinishedRequest(EndPointNegotiateFinishedRequest {
+        expected_frame_rate: 60,
+        cursor_mode: params.cursor_mode,
+        // the mix of all applications, viewer can switch to one of them later
+        audio_capture_target: AudioCaptureTarget::System,
+        audio_processing: true,
+        gop_policy: gop_policy(),
+        session_profile: session_profile(),
+    })
 }
 
-fn handle_message(
-    client: Arc<EndPointClient>,
-    mut rx: tokio::sync::mpsc::Receiver<Bytes>,
-    video_frame_tx: Option<Sender<EndPointVideoFrame>>,
-    audio_frame_tx: Option<Sender<EndPointAudioFrame>>,
-) {
-    tokio::spawn(async move {
+fn handle_message(client: Arc<EndPointClient>, mut rx: tokio::sync::mpsc::Receiver<Bytes>) {
+    spawn::spawn(async move {
         loop {
-            let buffer = match rx.recv().await {
-                Some(buffer) => buffer,
-                None => {
-                    tracing::info!("message handle channel is closed");
-                    break;
-                }
-            };
+            loop {
+                let buffer = tokio::select! {
+                    _ = client.closed.cancelled() => {
+                        tracing::info!("endpoint connection is closed");
+                        break;
+                    }
+                    buffer = rx.recv() => match buffer {
+                        Some(buffer) => buffer,
+                        None => {
+                            tracing::info!("message handle channel is closed");
+                            break;
+                        }
+                    },
+                };
 
-            let message = match bincode_deserialize(&buffer) {
-                Ok(message) => message,
-                Err(err) => {
-                    tracing::error!(?err, "deserialize endpoint message failed");
-                    continue;
-                }
-            };
+                client.traffic.record_received(buffer.len());
 
-            match message {
-                EndPointMessage::Error => {
-                    // handle_error(active_device_id, passive_device_id);
-                }
-                EndPointMessage::NegotiateDesktopParamsRequest(req) => {
-                    handle_negotiate_desktop_params_request(client.clone(), req).await
-                }
-                EndPointMessage::NegotiateDesktopParamsResponse(_) => {
-                    // this message should not received at handle_message loop because it already handled
-                    // at negotiate stage from active endpoint
-                }
-                EndPointMessage::NegotiateFinishedRequest(_) => {
-                    handle_negotiate_finished_request(client.clone());
-                }
-                EndPointMessage::VideoFrame(video_frame) => {
-                    if let Some(ref tx) = video_frame_tx {
-                        if let Err(err) = tx.send(video_frame).await {
-                            tracing::error!(%err, "endpoint video frame message channel send failed");
-                            return;
+                let message = match client.codec.decode(&buffer) {
+                    Ok(Some(message)) => message,
+                    Ok(None) => continue,
+                    Err(err) => {
+                        tracing::error!(?err, "deserialize endpoint message failed");
+                        continue;
+                    }
+                };
+
+                trace::record(
+                    &client.endpoint_id,
+                    TraceDirection::Received,
+                    &message,
+                    buffer.len(),
+                );
+
+                match message {
+                    EndPointMessage::Error => {
+                        // handle_error(active_device_id, passive_device_id);
+                    }
+                    #[cfg(feature = "host")]
+                    EndPointMessage::NegotiateDesktopParamsRequest(req) => {
+                        handle_negotiate_desktop_params_request(client.clone(), req).await
+                    }
+                    #[cfg(not(feature = "host"))]
+                    EndPointMessage::NegotiateDesktopParamsRequest(_) => {
+                        tracing::error!(
+                            "build without host feature can't serve as passive endpoint"
+                        );
+                    }
+                    EndPointMessage::NegotiateDesktopParamsResponse(resp) => {
+                        // the first negotiation is handled before the loop starts, later ones
+                        // are awaited by opening desktop channel
+                        if let Some(tx) = client.negotiate_tx.lock().unwrap().take() {
+                            let _ = tx.send(resp);
                         }
-                    } else {
-                        tracing::error!("as passive endpoint, shouldn't receive video frame");
                     }
-                }
-                EndPointMessage::AudioFrame(audio_frame) => {
-                    if let Some(ref tx) = audio_frame_tx {
-                        if let Err(err) = tx.send(audio_frame).await {
-                            tracing::error!(%err, "endpoint audio frame message channel send failed");
-                            return;
+                    #[cfg(feature = "host")]
+                    EndPointMessage::NegotiateFinishedRequest(req) => {
+                        handle_negotiate_finished_request(client.bind_desktop_stream(), req);
+                    }
+                    #[cfg(not(feature = "host"))]
+                    EndPointMessage::NegotiateFinishedRequest(_) => {
+                        tracing::error!(
+                            "build without host feature can't serve as passive endpoint"
+                        );
+                    }
+                    EndPointMessage::VideoFrame(video_frame) => {
+                        // frames in flight are dropped after desktop channel is closed
+                        if let Some((tx, _)) = client.desktop_frame_tx() {
+                            if let Err(err) = tx.send(video_frame).await {
+                                tracing::info!(%err, "endpoint video frame message channel send failed");
+                                client.close_channel(EndPointChannel::Desktop);
+                            }
+                        }
+
+                        if take_key_frame_wanted(&client.endpoint_id) {
+                            if let Err(err) = client.try_send(&EndPointMessage::KeyFrameRequest) {
+                                tracing::warn!(?err, "request key frame failed");
+                            }
                         }
-                    } else {
-                        tracing::error!("as passive endpoint, shouldn't receive audio frame");
                     }
-                }
-                EndPointMessage::InputCommand(input_event) => {
-                    handle_input(client.clone(), input_event).await
-                }
-                EndPointMessage::CallRequest(call_id, message) => {
-                    let client = client.clone();
-                    tokio::spawn(async move {
-                        let reply = match message {
-                            EndPointCallRequest::VisitDirectoryRequest(req) => {
-                                call!(handle_visit_directory_request(req).await)
+                    EndPointMessage::AudioFrame(audio_frame) => {
+                        if let Some((_, tx)) = client.desktop_frame_tx() {
+                            if let Err(err) = tx.send(audio_frame).await {
+                                tracing::info!(%err, "endpoint audio frame message channel send failed");
+                                client.close_channel(EndPointChannel::Desktop);
+                            }
+                        }
+                    }
+                    #[cfg(feature = "host")]
+                    EndPointMessage::InputCommand(input_event) => {
+                        if !client.granted_permissions().contains(Permissions::INPUT) {
+                            tracing::warn!("drop input command without input permission");
+                            continue;
+                        }
+
+                        plugin::publish(SessionEvent::InputReceived {
+                            session_id: client.session_id().to_string(),
+                            events: input_event.events.clone(),
+                        });
+
+                        handle_input(client.clone(), input_event).await
+                    }
+                    #[cfg(not(feature = "host"))]
+                    EndPointMessage::InputCommand(_) => {
+                        tracing::error!("build without host feature can't simulate input");
+                    }
+                    EndPointMessage::CallRequest(call_id, message) => {
+                        let client = client.clone();
+                        spawn::spawn(async move {
+                            if client.active {
+                                tracing::warn!("reject call of passive endpoint");
+                                reply_call(
+                                    &client,
+                                    call_id,
+                                    call!(
+                                        client,
+                                        CoreResult::<()>::Err(core_error!(
+                                            "active endpoint serves no calls"
+                                        ))
+                                    ),
+                                )
+                                .await;
+                                return;
                             }
-                            EndPointCallRequest::SendFileRequest(req) => {
-                                call!(handle_send_file_request(req).await)
+
+                            if let Err(err) =
+                                client.ensure_permissions(message.required_permissions())
+                            {
+                                tracing::warn!(?err, "reject call without permission");
+                                reply_call(
+                                    &client,
+                                    call_id,
+                                    call!(client, CoreResult::<()>::Err(err)),
+                                )
+                                .await;
+                                return;
                             }
-                            EndPointCallRequest::DownloadFileRequest(req) => {
-                                call!(handle_download_file_request(client.clone(), req).await)
+
+                            if client.observed_session_id.is_some() && !message.observer_allowed() {
+                                tracing::warn!("reject call of observer");
+                                reply_call(
+                                    &client,
+                                    call_id,
+                                    call!(
+                                        client,
+                                        CoreResult::<()>::Err(core_error!(
+                                            "observer can only watch the session"
+                                        ))
+                                    ),
+                                )
+                                .await;
+                                return;
                             }
-                        };
 
-                        match reply {
-                            Ok(reply_bytes) => {
-                                if let Err(err) = client
-                                    .send(&EndPointMessage::CallReply(call_id, reply_bytes))
-                                    .await
-                                {
-                                    tracing::error!(?err, "reply Call send message failed");
+                            let reply = match message {
+                                EndPointCallRequest::VisitDirectoryRequest(req) => {
+                                    call!(client, handle_visit_directory_request(req).await)
+                                }
+                                EndPointCallRequest::SendFileRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_send_file_request(client.clone(), req).await
+                                    )
+                                }
+                                EndPointCallRequest::DownloadFileRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_download_file_request(client.clone(), req).await
+                                    )
+                                }
+                                EndPointCallRequest::FileManifestRequest(req) => {
+                                    call!(client, handle_file_manifest_request(req).await)
+                                }
+                                EndPointCallRequest::FsOperationRequest(req) => {
+                                    call!(client, handle_fs_operation_request(req).await)
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::SwitchMonitorRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_switch_monitor_request(client.clone(), req).await
+                                    )
+                                }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::SwitchMonitorRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointSwitchMonitorReply>::Err(core_error!(
+                                            "build without host feature can't switch monitor"
+                                        ))
+                                    )
+                                }
+                                EndPointCallRequest::ElevatePermissionsRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_elevate_permissions_request(client.clone(), req)
+                                            .await
+                                    )
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::SwitchAudioCaptureRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_switch_audio_capture_request(client.clone(), req)
+                                            .await
+                                    )
+                                }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::SwitchAudioCaptureRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointSwitchAudioCaptureReply>::Err(
+                                            core_error!(
+                                                "build without host feature can't capture audio"
+                                            )
+                                        )
+                                    )
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::SwitchAudioProcessingRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_switch_audio_processing_request(client.clone(), req)
+                                            .await
+                                    )
+                                }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::SwitchAudioProcessingRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointSwitchAudioProcessingReply>::Err(
+                                            core_error!(
+                                                "build without host feature can't capture audio"
+                                            )
+                                        )
+                                    )
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::SwitchStreamQualityRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_switch_stream_quality_request(client.clone(), req)
+                                            .await
+                                    )
+                                }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::SwitchStreamQualityRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointSwitchStreamQualityReply>::Err(
+                                            core_error!(
+                                                "build without host feature can't capture desktop"
+                                            )
+                                        )
+                                    )
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::SwitchHostPriorityRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_switch_host_priority_request(client.clone(), req)
+                                            .await
+                                    )
+                                }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::SwitchHostPriorityRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointSwitchHostPriorityReply>::Err(
+                                            core_error!(
+                                                "build without host feature can't capture desktop"
+                                            )
+                                        )
+                                    )
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::SwitchResolutionRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_switch_resolution_request(client.clone(), req).await
+                                    )
+                                }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::SwitchResolutionRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointSwitchResolutionReply>::Err(
+                                            core_error!(
+                                                "build without host feature can't control display"
+                                            )
+                                        )
+                                    )
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::SystemActionRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_system_action_request(client.clone(), req).await
+                                    )
+                                }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::SystemActionRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointSystemActionReply>::Err(core_error!(
+                                        "build without host feature can't perform system action"
+                                    ))
+                                    )
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::SystemVolumeRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_system_volume_request(client.clone(), req).await
+                                    )
+                                }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::SystemVolumeRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointSystemVolumeReply>::Err(core_error!(
+                                            "build without host feature can't control volume"
+                                        ))
+                                    )
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::ProcessListRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_process_list_request(client.clone(), req).await
+                                    )
+                                }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::ProcessListRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointProcessListReply>::Err(core_error!(
+                                            "build without host feature can't list processes"
+                                        ))
+                                    )
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::ProcessKillRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_process_kill_request(client.clone(), req).await
+                                    )
+                                }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::ProcessKillRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointProcessKillReply>::Err(core_error!(
+                                            "build without host feature can't kill processes"
+                                        ))
+                                    )
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::MagnifyRequest(req) => {
+                                    call!(client, handle_magnify_request(client.clone(), req).await)
+                                }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::MagnifyRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointMagnifyReply>::Err(core_error!(
+                                            "build without host feature can't magnify desktop"
+                                        ))
+                                    )
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::InviteObserverRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_invite_observer_request(client.clone(), req).await
+                                    )
                                 }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::InviteObserverRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointInviteObserverReply>::Err(
+                                            core_error!(
+                                                "build without host feature can't be observed"
+                                            )
+                                        )
+                                    )
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::BlockLocalInputRequest(req) => {
+                                    call!(
+                                        client,
+                                        handle_block_local_input_request(client.clone(), req).await
+                                    )
+                                }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::BlockLocalInputRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointBlockLocalInputReply>::Err(
+                                            core_error!(
+                                                "build without host feature can't block input"
+                                            )
+                                        )
+                                    )
+                                }
+                                #[cfg(feature = "host")]
+                                EndPointCallRequest::LoginRequest(req) => {
+                                    call!(client, handle_login_request(client.clone(), req).await)
+                                }
+                                #[cfg(not(feature = "host"))]
+                                EndPointCallRequest::LoginRequest(_) => {
+                                    call!(
+                                        client,
+                                        CoreResult::<EndPointLoginReply>::Err(core_error!(
+                                            "build without host feature can't sign in"
+                                        ))
+                                    )
+                                }
+                            };
+
+                            reply_call(&client, call_id, reply).await;
+                        });
+                    }
+                    EndPointMessage::CallReply(call_id, reply) => {
+                        tracing::info!(?call_id, "receive call reply");
+                        if let Some(tx) = client.call_store.get(&call_id) {
+                            let _ = tx.send(reply).await;
+                        }
+
+                        client.call_store.invalidate(&call_id)
+                    }
+                    EndPointMessage::FileTransferBlock(block) => {
+                        append_file_block(client.clone(), block).await
+                    }
+                    EndPointMessage::FileTransferError(message) => {
+                        delete_file_append_session(&message.id).await;
+                        delete_file_patch_session(&message.id).await
+                    }
+                    EndPointMessage::FileDeltaBlock(block) => {
+                        patch_file_block(client.clone(), block).await
+                    }
+                    EndPointMessage::Cursor(cursor) => client.update_cursor(cursor).await,
+                    EndPointMessage::PowerSaving(power_saving) => {
+                        notification::publish(Notification::RemotePowerSaving {
+                            endpoint_id: client.endpoint_id,
+                            on_battery: power_saving.on_battery,
+                            power_saver: power_saving.power_saver,
+                            max_frame_rate: power_saving.max_frame_rate,
+                        })
+                    }
+                    EndPointMessage::PluginMessage(message) => {
+                        // plugins of both endpoints talk only when passive endpoint granted it
+                        if !client.permissions().contains(Permissions::PLUGINS) {
+                            tracing::warn!("drop plugin message without plugins permission");
+                            continue;
+                        }
+
+                        plugin::dispatch_channel_message(client.session_id(), message)
+                    }
+                    EndPointMessage::PermissionsChanged(permissions) => {
+                        // only passive endpoint grants permissions, a visitor never raises
+                        // its own
+                        if !client.active {
+                            tracing::warn!(
+                                permissions = ?permissions.names(),
+                                "drop permissions change from active endpoint"
+                            );
+                            continue;
+                        }
+
+                        tracing::info!(permissions = ?permissions.names(), "remote permissions changed");
+                        client.permissions.set(permissions)
+                    }
+                    EndPointMessage::InputBlocked(reason) => {
+                        tracing::info!(?reason, "remote input block changed");
+                        *client.input_block_reason.lock().unwrap() = reason;
+                    }
+                    EndPointMessage::ChannelClosed(channel) => {
+                        tracing::info!(?channel, "remote channel closed");
+                        if channel == EndPointChannel::Desktop {
+                            // desktop stream processes exit once their messages are rejected
+                            client.desktop_epoch.fetch_add(1, Ordering::SeqCst);
+                        }
+                    }
+                    EndPointMessage::BandwidthLimit(limit) => {
+                        tracing::info!(max_kbps = ?limit.max_kbps, "peer limits bandwidth");
+                        client.apply_bandwidth_limit(limit.max_kbps);
+                    }
+                    EndPointMessage::CaptureInterrupted(interruption) => {
+                        tracing::info!(?interruption, "remote capture interruption changed");
+                        *client.capture_interruption.lock().unwrap() = interruption;
+                    }
+                    EndPointMessage::KeyFrameRequest => {
+                        tracing::info!("remote requests key frame");
+                        client.request_key_frame();
+                    }
+                    EndPointMessage::ClockSyncRequest(req) => {
+                        let received_micros = clock_sync::now_micros();
+                        let reply = EndPointMessage::ClockSyncReply(EndPointClockSyncReply {
+                            origin_micros: req.origin_micros,
+                            received_micros,
+                            transmitted_micros: clock_sync::now_micros(),
+                        });
+
+                        if let Err(err) = client.try_send(&reply) {
+                            tracing::warn!(?err, "reply clock sync failed");
+                        }
+                    }
+                    EndPointMessage::ClockSyncReply(reply) => {
+                        clock_sync::record_reply(
+                            client.endpoint_id,
+                            &reply,
+                            clock_sync::now_micros(),
+                        );
+                    }
+                    EndPointMessage::Metadata(metadata) => {
+                        if let Err(err) = validate_metadata(&metadata) {
+                            tracing::warn!(?err, "reject remote metadata");
+                            continue;
+                        }
+
+                        tracing::info!(
+                            entries = metadata.entries.len(),
+                            "receive remote metadata"
+                        );
+                        *client.remote_metadata.lock().unwrap() = metadata
+                            .entries
+                            .into_iter()
+                            .map(|entry| (entry.key, entry.value))
+                            .collect();
+                    }
+                    EndPointMessage::MagnifierFrame(magnifier_frame) => {
+                        // frames in flight are dropped after the magnifier is stopped
+                        let tx = client.magnifier_frame_tx.lock().unwrap().clone();
+                        if let Some(tx) = tx {
+                            if tx.send(magnifier_frame).await.is_err() {
+                                tracing::info!("magnifier frame channel closed");
+                                client.stop_magnifier();
                             }
-                            Err(err) => {
-                                tracing::error!(?err, "reply Call failed");
+                        }
+
+                        if take_key_frame_wanted(&client.endpoint_id) {
+                            if let Err(err) = client.try_send(&EndPointMessage::KeyFrameRequest) {
+                                tracing::warn!(?err, "request key frame failed");
                             }
                         }
-                    });
-                }
-                EndPointMessage::CallReply(call_id, reply) => {
-                    tracing::info!(?call_id, "receive call reply");
-                    if let Some(tx) = client.call_store.get(&call_id) {
-                        let _ = tx.send(reply).await;
                     }
-
-                    client.call_store.invalidate(&call_id)
-                }
-                EndPointMessage::FileTransferBlock(block) => {
-                    append_file_block(client.clone(), block).await
-                }
-                EndPointMessage::FileTransferError(message) => {
-                    delete_file_append_session(&message.id).await
+                    EndPointMessage::LocalInputBlocked(blocked) => {
+                        tracing::info!(blocked, "remote local input block changed");
+                        client.set_remote_local_input_blocked(blocked);
+                    }
                 }
             }
+
+            // passive endpoint went offline on purpose, like rebooting, resume the session
+            // once it's back online
+            let negotiate = client.channel_opened(EndPointChannel::Desktop);
+            match reconnect(&client, negotiate).await {
+                Some(reconnected_rx) => rx = reconnected_rx,
+                None => break,
+            }
         }
 
         tracing::info!("message handle loop exit");
+
+        let reason = if client.closed() {
+            CloseReason::Local
+        } else if client.reconnecting() {
+            CloseReason::ReconnectFailed
+        } else {
+            CloseReason::Disconnected
+        };
+
+        client.state.transit(SessionState::Closed { reason });
+
+        // windows of the remote device shouldn't share the connection anymore
+        client.closed.cancel();
+
+        sessions::unregister(client.session_id());
+        revoke_observer_tokens(client.session_id());
+
+        // observers can't watch the session which is gone
+        for observer in client.observers.lock().unwrap().drain(..) {
+            observer.close();
+        }
+
+        plugin::session_disconnected(client.session_id());
+        keep_awake::session_ended(client.session_id());
+        staging::session_ended(client.session_id());
+        #[cfg(feature = "host")]
+        display_control::session_ended(client.session_id());
+        #[cfg(feature = "host")]
+        local_input_block::session_ended(client.session_id());
+        trace::finish_trace(&client.endpoint_id);
+        clock_sync::remove_session(&client.endpoint_id);
+
+        notification::publish(Notification::SessionDisconnected {
+            endpoint_id: client.endpoint_id,
+        });
     });
 }
+
+/// Connect passive endpoint again until it's back online, returns None when reconnect is
+/// not expected, canceled or timeout.
+async fn reconnect(client: &Arc<EndPointClient>, negotiate: bool) -> Option<Receiver<Bytes>> {
+    if !client.reconnect_expected.swap(false, Ordering::SeqCst)
+        || client.reconnector.lock().unwrap().is_none()
+    {
+        return None;
+    }
+
+    tracing::info!("passive endpoint goes offline, start reconnecting");
+
+    client.state.transit(SessionState::Reconnecting);
+
+    let deadline = Instant::now() + RECONNECT_TIMEOUT;
+
+    while Instant::now() < deadline {
+        // passive endpoint takes a while to shut down and start again
+        tokio::time::sleep(RECONNECT_INTERVAL).await;
+
+        let Some(reconnector) = client.reconnector.lock().unwrap().clone() else {
+            tracing::info!("reconnect is canceled");
+            return None;
+        };
+
+        let result = match (reconnector.0)().await {
+            Ok(params) => client.resume(params, negotiate).await,
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Ok(rx) => {
+                tracing::info!("reconnect success");
+                client.state.transit(SessionState::Streaming);
+                client.refresh_permissions();
+                return Some(rx);
+            }
+            Err(err) => tracing::warn!(?err, "reconnect failed, retry later"),
+        }
+    }
+
+    tracing::error!("reconnect timeout");
+    None
+}
+
+async fn reply_call(client: &EndPointClient, call_id: u16, reply: CoreResult<Vec<u8>>) {
+    match reply {
+        Ok(reply_bytes) => {
+            if let Err(err) = client
+                .send(&EndPointMessage::CallReply(call_id, reply_bytes))
+                .await
+            {
+                tracing::error!(?err, "reply Call send message failed");
+            }
+        }
+        Err(err) => {
+            tracing::error!(?err, "reply Call failed");
+        }
+    }
+}
@@ -0,0 +1,475 @@
+//! Outgoing messages of all channels share one transport, they're queued in lanes by
+//! traffic kind and the transport write loop always takes the frame of the highest lane
+//! first: input > audio > video > file transfer. Input lane also carries control messages
+//! like calls and negotiation since they're small and someone is waiting on them.
+//!
+//! Every lane owns credits in bytes, a frame takes its size of credits when it's queued
+//! and gives them back once it's written to the transport. The producers of a lane wait
+//! when its credits run out, so bulk transfers can't flood the queue ahead of interactive
+//! traffic, and they still saturate whatever bandwidth is left by the higher lanes.
+//!
+//! Frames of one lane keep their order, frames of different lanes don't. File transfer
+//! lane is the exception, every transfer is a stream of its own and streams take turns
+//! by bytes, so uploads and downloads running at the same time share the lane fairly
+//! rather than the one queued first holding back the others. Frames of one stream still
+//! keep their order.
+//!
+//! Users on metered connections cap the bandwidth of a session, audio, video and file
+//! transfer lanes share one token bucket for it. Input lane is never throttled.
+//!
+//! Frames queued in a lane can be discarded before they're written, like the video
+//! frames which fall behind when the network can't keep up.
+
+use crate::{
+    api::endpoint::message::EndPointMessage,
+    error::{CoreError, CoreResult},
+};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{
+    mpsc::{UnboundedReceiver, UnboundedSender},
+    Notify, OwnedSemaphorePermit, Semaphore,
+};
+
+// the bucket holds at most the bytes of this period, so idle time isn't saved for bursts
+const BANDWIDTH_BURST: Duration = Duration::from_millis(200);
+
+// bytes a transfer stream may send in its turn, a little more than one file block
+const TRANSFER_QUANTUM: usize = 80 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    Input,
+    Audio,
+    Video,
+    FileTransfer,
+}
+
+impl Lane {
+    pub fn of(message: &EndPointMessage) -> Lane {
+        match message {
+            EndPointMessage::AudioFrame(_) => Lane::Audio,
+            EndPointMessage::VideoFrame(_) | EndPointMessage::MagnifierFrame(_) => Lane::Video,
+            // errors follow the blocks before them
+            EndPointMessage::FileTransferBlock(_)
+            | EndPointMessage::FileDeltaBlock(_)
+            | EndPointMessage::FileTransferError(_) => Lane::FileTransfer,
+            _ => Lane::Input,
+        }
+    }
+
+    // bytes which can be queued in the lane before its producers wait
+    fn credits(self) -> usize {
+        match self {
+            Lane::Input => 1024 * 1024,
+            Lane::Audio => 256 * 1024,
+            // key frames of high resolution take hundreds of kilobytes
+            Lane::Video => 4 * 1024 * 1024,
+            Lane::FileTransfer => 2 * 1024 * 1024,
+        }
+    }
+}
+
+/// The transfer which the message belongs to, it's the stream of file transfer lane.
+pub fn transfer_stream(message: &EndPointMessage) -> Option<&str> {
+    match message {
+        EndPointMessage::FileTransferBlock(block) => Some(&block.id),
+        EndPointMessage::FileDeltaBlock(block) => Some(&block.id),
+        EndPointMessage::FileTransferError(error) => Some(&error.id),
+        _ => None,
+    }
+}
+
+/// One frame which is queued for the transport, its credits are given back to the lane
+/// once it's dropped.
+pub struct MuxFrame {
+    buffer: Vec<u8>,
+    // the transfer of file transfer lane frames
+    stream: Option<String>,
+    sequence: u64,
+    discarded_before: Arc<AtomicU64>,
+    _credit: OwnedSemaphorePermit,
+    _queued: QueuedGuard,
+}
+
+impl MuxFrame {
+    /// Take the bytes to write, the credits are held until the frame is dropped.
+    pub fn take_buffer(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buffer)
+    }
+
+    fn discarded(&self) -> bool {
+        self.sequence < self.discarded_before.load(Ordering::SeqCst)
+    }
+}
+
+// counts the frame as queued in its lane until it's dropped
+struct QueuedGuard(Arc<AtomicUsize>);
+
+impl QueuedGuard {
+    fn new(queued: &Arc<AtomicUsize>) -> Self {
+        queued.fetch_add(1, Ordering::SeqCst);
+        QueuedGuard(queued.clone())
+    }
+}
+
+impl Drop for QueuedGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LaneSender {
+    tx: UnboundedSender<MuxFrame>,
+    credits: Arc<Semaphore>,
+    capacity: usize,
+    // frames queued or being written
+    queued: Arc<AtomicUsize>,
+    sequence: Arc<AtomicU64>,
+    // frames with smaller sequence are dropped instead of written
+    discarded_before: Arc<AtomicU64>,
+}
+
+impl LaneSender {
+    fn new(lane: Lane) -> (LaneSender, UnboundedReceiver<MuxFrame>) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let capacity = lane.credits();
+        let sender = LaneSender {
+            tx,
+            credits: Arc::new(Semaphore::new(capacity)),
+            capacity,
+            queued: Arc::new(AtomicUsize::new(0)),
+            sequence: Arc::new(AtomicU64::new(0)),
+            discarded_before: Arc::new(AtomicU64::new(0)),
+        };
+
+        (sender, rx)
+    }
+
+    // frames larger than the whole credits take all of them instead of waiting forever
+    fn cost(&self, buffer: &[u8]) -> u32 {
+        buffer.len().clamp(1, self.capacity) as u32
+    }
+
+    fn queue(
+        &self,
+        buffer: Vec<u8>,
+        stream: Option<String>,
+        credit: OwnedSemaphorePermit,
+    ) -> CoreResult<()> {
+        self.tx
+            .send(MuxFrame {
+                buffer,
+                stream,
+                sequence: self.sequence.fetch_add(1, Ordering::SeqCst),
+                discarded_before: self.discarded_before.clone(),
+                _credit: credit,
+                _queued: QueuedGuard::new(&self.queued),
+            })
+            .map_err(|_| CoreError::OutgoingMessageChannelDisconnect)
+    }
+
+    fn discard_queued(&self) {
+        self.discarded_before
+            .store(self.sequence.load(Ordering::SeqCst), Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    bytes_per_sec: Option<f64>,
+    // goes negative when a frame is larger than what's left, later frames wait it back
+    balance: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn refill(&mut self) {
+        let now = Instant::now();
+
+        if let Some(bytes_per_sec) = self.bytes_per_sec {
+            let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+            self.balance = (self.balance + elapsed * bytes_per_sec)
+                .min(bytes_per_sec * BANDWIDTH_BURST.as_secs_f64());
+        }
+
+        self.refilled_at = now;
+    }
+}
+
+#[derive(Debug)]
+struct BandwidthLimiter {
+    bucket: Mutex<TokenBucket>,
+    changed: Notify,
+}
+
+impl BandwidthLimiter {
+    fn new() -> Self {
+        BandwidthLimiter {
+            bucket: Mutex::new(TokenBucket {
+                bytes_per_sec: None,
+                balance: 0.0,
+                refilled_at: Instant::now(),
+            }),
+            changed: Notify::new(),
+        }
+    }
+
+    fn set(&self, max_kbps: Option<u32>) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill();
+        bucket.bytes_per_sec = max_kbps.map(|max_kbps| max_kbps.max(1) as f64 * 1000.0 / 8.0);
+        bucket.balance = 0.0;
+        drop(bucket);
+
+        self.changed.notify_waiters();
+    }
+
+    // how long the throttled lanes wait before their next frame
+    fn delay(&self) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill();
+
+        match bucket.bytes_per_sec {
+            Some(bytes_per_sec) if bucket.balance < 0.0 => {
+                Duration::from_secs_f64(-bucket.balance / bytes_per_sec)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
+    fn consume(&self, bytes: usize) {
+        let mut bucket = self.bucket.lock().unwrap();
+        if bucket.bytes_per_sec.is_some() {
+            bucket.refill();
+            bucket.balance -= bytes as f64;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MuxSender {
+    input: LaneSender,
+    audio: LaneSender,
+    video: LaneSender,
+    file_transfer: LaneSender,
+    limiter: Arc<BandwidthLimiter>,
+}
+
+impl MuxSender {
+    fn lane(&self, lane: Lane) -> &LaneSender {
+        match lane {
+            Lane::Input => &self.input,
+            Lane::Audio => &self.audio,
+            Lane::Video => &self.video,
+            Lane::FileTransfer => &self.file_transfer,
+        }
+    }
+
+    pub async fn send(&self, lane: Lane, buffer: Vec<u8>) -> CoreResult<()> {
+        let sender = self.lane(lane);
+        let credit = sender
+            .credits
+            .clone()
+            .acquire_many_owned(sender.cost(&buffer))
+            .await
+            .map_err(|_| CoreError::OutgoingMessageChannelDisconnect)?;
+
+        sender.queue(buffer, None, credit)
+    }
+
+    /// Send the frame of the transfer in file transfer lane, transfers take turns with
+    /// each other.
+    pub async fn send_transfer(&self, stream: &str, buffer: Vec<u8>) -> CoreResult<()> {
+        let sender = self.lane(Lane::FileTransfer);
+        let credit = sender
+            .credits
+            .clone()
+            .acquire_many_owned(sender.cost(&buffer))
+            .await
+            .map_err(|_| CoreError::OutgoingMessageChannelDisconnect)?;
+
+        sender.queue(buffer, Some(stream.to_string()), credit)
+    }
+
+    /// Fails when the lane is out of credits, like sending to a full channel.
+    pub fn try_send(&self, lane: Lane, buffer: Vec<u8>) -> CoreResult<()> {
+        let sender = self.lane(lane);
+        let credit = sender
+            .credits
+            .clone()
+            .try_acquire_many_owned(sender.cost(&buffer))
+            .map_err(|_| CoreError::OutgoingMessageChannelDisconnect)?;
+
+        sender.queue(buffer, None, credit)
+    }
+
+    /// Used by capture processes which run in blocking thread.
+    pub fn blocking_send(&self, lane: Lane, buffer: Vec<u8>) -> CoreResult<()> {
+        futures::executor::block_on(self.send(lane, buffer))
+    }
+
+    /// Frames of the lane which are queued or being written to the transport.
+    pub fn queued_frames(&self, lane: Lane) -> usize {
+        self.lane(lane).queued.load(Ordering::SeqCst)
+    }
+
+    /// Drop the frames queued in the lane so far instead of writing them, the frame being
+    /// written is written completely.
+    pub fn discard_queued(&self, lane: Lane) {
+        self.lane(lane).discard_queued()
+    }
+
+    /// Cap the combined throughput of audio, video and file transfer lanes, it takes
+    /// effect on the next frame.
+    pub fn set_bandwidth_limit(&self, max_kbps: Option<u32>) {
+        self.limiter.set(max_kbps)
+    }
+}
+
+struct TransferStream {
+    stream: Option<String>,
+    frames: VecDeque<MuxFrame>,
+    // bytes the stream may still send in its turn
+    deficit: usize,
+}
+
+// schedules the streams of file transfer lane by deficit round robin, every stream gets
+// `TRANSFER_QUANTUM` bytes per turn whatever the size of its frames
+struct TransferQueue {
+    rx: UnboundedReceiver<MuxFrame>,
+    // streams with queued frames, the front one takes its turn
+    streams: VecDeque<TransferStream>,
+}
+
+impl TransferQueue {
+    fn new(rx: UnboundedReceiver<MuxFrame>) -> Self {
+        TransferQueue {
+            rx,
+            streams: VecDeque::new(),
+        }
+    }
+
+    // it's cancel safe as nothing is awaited once a frame is received
+    async fn recv(&mut self) -> Option<MuxFrame> {
+        if self.streams.is_empty() {
+            let frame = self.rx.recv().await?;
+            self.push(frame);
+        }
+
+        // every stream which has frames queued takes part in the turns
+        while let Ok(frame) = self.rx.try_recv() {
+            self.push(frame);
+        }
+
+        self.pop()
+    }
+
+    fn push(&mut self, frame: MuxFrame) {
+        match self
+            .streams
+            .iter_mut()
+            .find(|stream| stream.stream == frame.stream)
+        {
+            Some(stream) => stream.frames.push_back(frame),
+            None => self.streams.push_back(TransferStream {
+                stream: frame.stream.clone(),
+                frames: VecDeque::from([frame]),
+                deficit: 0,
+            }),
+        }
+    }
+
+    fn pop(&mut self) -> Option<MuxFrame> {
+        loop {
+            let stream = self.streams.front_mut()?;
+            let size = stream.frames.front().map_or(0, |frame| frame.buffer.len());
+
+            if stream.deficit < size {
+                // its turn is over, the bytes left are carried to the next turn
+                stream.deficit += TRANSFER_QUANTUM;
+                self.streams.rotate_left(1);
+                continue;
+            }
+
+            stream.deficit -= size;
+            let frame = stream.frames.pop_front();
+
+            // streams which run out of frames don't save their turn
+            if stream.frames.is_empty() {
+                self.streams.pop_front();
+            }
+
+            return frame;
+        }
+    }
+}
+
+pub struct MuxReceiver {
+    input: UnboundedReceiver<MuxFrame>,
+    audio: UnboundedReceiver<MuxFrame>,
+    video: UnboundedReceiver<MuxFrame>,
+    file_transfer: TransferQueue,
+    limiter: Arc<BandwidthLimiter>,
+}
+
+impl MuxReceiver {
+    /// The queued frame of the highest lane, None once all senders are dropped.
+    pub async fn recv(&mut self) -> Option<MuxFrame> {
+        loop {
+            let delay = self.limiter.delay();
+            let throttled = !delay.is_zero();
+
+            let frame = tokio::select! {
+                biased;
+                Some(frame) = self.input.recv() => return Some(frame),
+                _ = self.limiter.changed.notified(), if throttled => continue,
+                _ = tokio::time::sleep(delay), if throttled => continue,
+                Some(frame) = self.audio.recv(), if !throttled => frame,
+                Some(frame) = self.video.recv(), if !throttled => frame,
+                Some(frame) = self.file_transfer.recv(), if !throttled => frame,
+                else => return None,
+            };
+
+            // its credits are given back once it's dropped here
+            if frame.discarded() {
+                continue;
+            }
+
+            self.limiter.consume(frame.buffer.len());
+            return Some(frame);
+        }
+    }
+}
+
+pub fn channel() -> (MuxSender, MuxReceiver) {
+    let (input, input_rx) = LaneSender::new(Lane::Input);
+    let (audio, audio_rx) = LaneSender::new(Lane::Audio);
+    let (video, video_rx) = LaneSender::new(Lane::Video);
+    let (file_transfer, file_transfer_rx) = LaneSender::new(Lane::FileTransfer);
+    let limiter = Arc::new(BandwidthLimiter::new());
+
+    (
+        MuxSender {
+            input,
+            audio,
+            video,
+            file_transfer,
+            limiter: limiter.clone(),
+        },
+        MuxReceiver {
+            input: input_rx,
+            audio: audio_rx,
+            video: video_rx,
+            file_transfer: TransferQueue::new(file_transfer_rx),
+            limiter,
+        },
+    )
+}
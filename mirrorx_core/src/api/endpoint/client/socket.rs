@@ -0,0 +1,142 @@
+//! Transport sockets are left with the OS defaults otherwise: idle sessions behind NAT
+//! are dropped silently, enterprise QoS treats the stream like bulk traffic and the
+//! default buffers cap the throughput of long links far below the video bit rate. The
+//! sockets are tuned right after they're connected, before the handshake goes over them.
+
+use crate::{core_error, error::CoreResult};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use socket2::{SockRef, TcpKeepalive};
+use std::{net::SocketAddr, sync::RwLock, time::Duration};
+use tokio::net::{TcpStream, UdpSocket};
+
+// AF41 of RFC 4594, the class of interactive video which QoS policies prioritize
+const DSCP_INTERACTIVE: u32 = 34;
+
+// bit rate which the encoder starts with, buffers keep up with it unless the peer is
+// limited below
+const DEFAULT_BIT_RATE_KBPS: u64 = 4000;
+
+// passive endpoints accept the connection without timing it
+const DEFAULT_RTT: Duration = Duration::from_millis(100);
+
+const MIN_BUFFER_SIZE: usize = 256 * 1024;
+const MAX_BUFFER_SIZE: usize = 8 * 1024 * 1024;
+
+static TRANSPORT_TUNING: Lazy<RwLock<TransportTuning>> =
+    Lazy::new(|| RwLock::new(TransportTuning::default()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransportTuning {
+    /// Idle time before the first keepalive probe, zero disables keepalive.
+    pub keepalive_idle_secs: u32,
+    pub keepalive_interval_secs: u32,
+    /// Mark the traffic as interactive video with DSCP.
+    pub dscp_marking: bool,
+}
+
+impl Default for TransportTuning {
+    fn default() -> Self {
+        TransportTuning {
+            keepalive_idle_secs: 15,
+            keepalive_interval_secs: 5,
+            dscp_marking: true,
+        }
+    }
+}
+
+impl TransportTuning {
+    pub fn validate(&self) -> CoreResult<()> {
+        if self.keepalive_idle_secs > 0 && self.keepalive_interval_secs == 0 {
+            return Err(core_error!("keepalive interval must be positive"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Set the tuning of transport sockets, only the connections made later pick it up.
+pub fn set_transport_tuning(tuning: TransportTuning) {
+    *TRANSPORT_TUNING.write().unwrap() = tuning;
+}
+
+pub fn transport_tuning() -> TransportTuning {
+    *TRANSPORT_TUNING.read().unwrap()
+}
+
+/// Tune the connected stream, `rtt` is the time connecting took when it's measured and
+/// `bit_rate_kbps` the bandwidth limit of the peer if any.
+pub(crate) fn tune_tcp_stream(
+    stream: &TcpStream,
+    rtt: Option<Duration>,
+    bit_rate_kbps: Option<u32>,
+) {
+    let tuning = transport_tuning();
+    let socket = SockRef::from(stream);
+
+    if tuning.keepalive_idle_secs > 0 {
+        let keepalive = TcpKeepalive::new()
+            .with_time(Duration::from_secs(tuning.keepalive_idle_secs as u64))
+            .with_interval(Duration::from_secs(tuning.keepalive_interval_secs as u64));
+
+        if let Err(err) = socket.set_tcp_keepalive(&keepalive) {
+            tracing::warn!(?err, "enable tcp keepalive failed");
+        }
+    }
+
+    tune_socket(
+        &socket,
+        stream.peer_addr().ok(),
+        &tuning,
+        rtt,
+        bit_rate_kbps,
+    );
+}
+
+pub(crate) fn tune_udp_socket(socket: &UdpSocket, bit_rate_kbps: Option<u32>) {
+    let tuning = transport_tuning();
+
+    tune_socket(
+        &SockRef::from(socket),
+        socket.peer_addr().ok(),
+        &tuning,
+        None,
+        bit_rate_kbps,
+    );
+}
+
+fn tune_socket(
+    socket: &SockRef,
+    peer_addr: Option<SocketAddr>,
+    tuning: &TransportTuning,
+    rtt: Option<Duration>,
+    bit_rate_kbps: Option<u32>,
+) {
+    // IPv6 traffic class isn't exposed by the socket, those streams go unmarked
+    if tuning.dscp_marking && matches!(peer_addr, Some(SocketAddr::V4(_))) {
+        if let Err(err) = socket.set_tos(DSCP_INTERACTIVE << 2) {
+            tracing::warn!(?err, "set dscp failed");
+        }
+    }
+
+    let buffer_size = buffer_size(rtt.unwrap_or(DEFAULT_RTT), bit_rate_kbps);
+
+    if let Err(err) = socket.set_send_buffer_size(buffer_size) {
+        tracing::warn!(?err, buffer_size, "set send buffer size failed");
+    }
+
+    if let Err(err) = socket.set_recv_buffer_size(buffer_size) {
+        tracing::warn!(?err, buffer_size, "set recv buffer size failed");
+    }
+
+    tracing::info!(?peer_addr, ?rtt, buffer_size, "transport socket tuned");
+}
+
+// twice the bit rate delay product, so key frames which burst over the average bit rate
+// don't stall the sender
+fn buffer_size(rtt: Duration, bit_rate_kbps: Option<u32>) -> usize {
+    let bit_rate_kbps = bit_rate_kbps.map_or(DEFAULT_BIT_RATE_KBPS, |limit| limit as u64);
+    let bytes_in_flight = bit_rate_kbps * 1000 / 8 * rtt.as_millis() as u64 / 1000;
+
+    (bytes_in_flight as usize * 2).clamp(MIN_BUFFER_SIZE, MAX_BUFFER_SIZE)
+}
@@ -0,0 +1,117 @@
+//! Lifecycle of the endpoint session. Transitions are published as core events, so
+//! windows show what the session is doing rather than guessing it from command errors.
+
+use crate::api::{
+    endpoint::id::EndPointID,
+    event::{self, CoreEvent},
+};
+use serde::Serialize;
+use std::sync::Mutex;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum SessionState {
+    /// Establishing the transport to the remote endpoint.
+    Connecting,
+    /// Exchanging keys and credentials over the established transport.
+    Handshaking,
+    /// Negotiating desktop params, both for the first desktop channel and the later one.
+    Negotiating,
+    /// Serving the opened channels, desktop frames or files.
+    Streaming,
+    /// Passive endpoint went offline on purpose and the session is being resumed.
+    Reconnecting,
+    Closed {
+        reason: CloseReason,
+    },
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseReason {
+    /// Closed by this side, like the last window of the session is closed.
+    Local,
+    /// The connection is lost or closed by remote.
+    Disconnected,
+    /// Passive endpoint isn't back online before reconnecting gives up.
+    ReconnectFailed,
+    /// The session is never established.
+    Failed,
+}
+
+#[derive(Debug)]
+pub(super) struct SessionStateMachine {
+    session_id: String,
+    endpoint_id: EndPointID,
+    state: Mutex<SessionState>,
+}
+
+impl SessionStateMachine {
+    pub fn new(session_id: String, endpoint_id: EndPointID) -> Self {
+        let machine = SessionStateMachine {
+            session_id,
+            endpoint_id,
+            state: Mutex::new(SessionState::Connecting),
+        };
+
+        machine.publish(SessionState::Connecting);
+        machine
+    }
+
+    pub fn get(&self) -> SessionState {
+        *self.state.lock().unwrap()
+    }
+
+    pub fn transit(&self, next: SessionState) {
+        {
+            let mut state = self.state.lock().unwrap();
+
+            // operations still in flight when the session is closed don't revive it
+            if *state == next || matches!(*state, SessionState::Closed { .. }) {
+                return;
+            }
+
+            if !can_transit(*state, next) {
+                tracing::warn!(from = ?*state, to = ?next, "invalid session state transition");
+                return;
+            }
+
+            tracing::info!(from = ?*state, to = ?next, "session state changed");
+            *state = next;
+        }
+
+        self.publish(next);
+    }
+
+    fn publish(&self, state: SessionState) {
+        event::publish(CoreEvent::SessionState {
+            session_id: self.session_id.clone(),
+            endpoint_id: self.endpoint_id,
+            state,
+        });
+    }
+}
+
+impl Drop for SessionStateMachine {
+    // the session which is dropped before its message loop starts has never been
+    // established, like handshake failed or the connect race is lost
+    fn drop(&mut self) {
+        self.transit(SessionState::Closed {
+            reason: CloseReason::Failed,
+        });
+    }
+}
+
+fn can_transit(from: SessionState, to: SessionState) -> bool {
+    use SessionState::*;
+
+    matches!(
+        (from, to),
+        (_, Closed { .. })
+            | (Connecting, Handshaking)
+            | (Handshaking, Negotiating | Streaming)
+            | (Negotiating, Streaming)
+            | (Streaming, Negotiating | Reconnecting)
+            | (Reconnecting, Streaming)
+    )
+}
@@ -1,4 +1,7 @@
-use super::RECV_MESSAGE_TIMEOUT;
+use super::{
+    mux::{self, MuxReceiver, MuxSender},
+    MAX_CONSECUTIVE_OPEN_FAILURES, RECV_MESSAGE_TIMEOUT,
+};
 use crate::{
     api::endpoint::{
         id::EndPointID,
@@ -9,6 +12,7 @@ use crate::{
     utility::{
         bincode::{bincode_deserialize, bincode_serialize},
         nonce_value::NonceValue,
+        spawn,
     },
 };
 use bytes::Bytes;
@@ -19,18 +23,23 @@ use futures::{
 use ring::aead::{OpeningKey, SealingKey};
 use std::ops::Deref;
 use tokio::{
-    net::TcpStream,
-    sync::mpsc::{Receiver, Sender},
+    io::{AsyncRead, AsyncWrite},
+    sync::mpsc::Receiver,
 };
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
-pub async fn serve_tcp(
-    stream: TcpStream,
+/// Serve the endpoint messages over the stream, it's a plain tcp stream or the tls stream
+/// of relay connection.
+pub async fn serve_tcp<S>(
+    stream: S,
     endpoint_id: EndPointID,
     sealing_key: Option<SealingKey<NonceValue>>,
     opening_key: Option<OpeningKey<NonceValue>>,
     mut visit_credentials: Option<Vec<u8>>,
-) -> CoreResult<(Sender<Vec<u8>>, Receiver<Bytes>)> {
+) -> CoreResult<(MuxSender, Receiver<Bytes>)>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
     let mut framed = Framed::new(
         stream,
         LengthDelimitedCodec::builder()
@@ -43,18 +52,21 @@ pub async fn serve_tcp(
         serve_handshake(&mut framed, visit_credentials, endpoint_id).await?;
     }
 
-    let (tx, rx) = tokio::sync::mpsc::channel(32);
+    let (tx, rx) = mux::channel();
     let (sink, stream) = framed.split();
     serve_tcp_write(endpoint_id, rx, sealing_key, sink);
     let rx = serve_tcp_read(endpoint_id, opening_key, stream)?;
     Ok((tx, rx))
 }
 
-async fn serve_handshake(
-    stream: &mut Framed<TcpStream, LengthDelimitedCodec>,
+async fn serve_handshake<S>(
+    stream: &mut Framed<S, LengthDelimitedCodec>,
     visit_credentials: Vec<u8>,
     endpoint_id: EndPointID,
-) -> CoreResult<()> {
+) -> CoreResult<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
     let EndPointID::DeviceID { local_device_id, remote_device_id } = endpoint_id else {
         return Err(core_error!("lan connection needn't device id"));
     };
@@ -83,14 +95,19 @@ async fn serve_handshake(
     Ok(())
 }
 
-fn serve_tcp_read(
+fn serve_tcp_read<S>(
     endpoint_id: EndPointID,
     mut opening_key: Option<OpeningKey<NonceValue>>,
-    mut stream: SplitStream<Framed<TcpStream, LengthDelimitedCodec>>,
-) -> CoreResult<tokio::sync::mpsc::Receiver<Bytes>> {
+    mut stream: SplitStream<Framed<S, LengthDelimitedCodec>>,
+) -> CoreResult<tokio::sync::mpsc::Receiver<Bytes>>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
     let (tx, rx) = tokio::sync::mpsc::channel(1);
 
-    tokio::spawn(async move {
+    spawn::spawn(async move {
+        let mut open_failures = 0;
+
         loop {
             let mut buffer = match stream.next().await {
                 Some(packet) => match packet {
@@ -108,10 +125,19 @@ fn serve_tcp_read(
 
             let buffer_len = if let Some(ref mut opening_key) = opening_key {
                 match opening_key.open_in_place(ring::aead::Aad::empty(), buffer.as_mut()) {
-                    Ok(output) => output.len(),
+                    Ok(output) => {
+                        open_failures = 0;
+                        output.len()
+                    }
                     Err(err) => {
-                        tracing::error!(?err, "open endpoint message packet failed");
-                        break;
+                        open_failures += 1;
+                        if open_failures >= MAX_CONSECUTIVE_OPEN_FAILURES {
+                            tracing::error!(?err, "open endpoint message packet failed");
+                            break;
+                        }
+
+                        tracing::warn!(?err, open_failures, "skip unopenable endpoint packet");
+                        continue;
                     }
                 }
             } else {
@@ -132,16 +158,20 @@ fn serve_tcp_read(
     Ok(rx)
 }
 
-fn serve_tcp_write(
+fn serve_tcp_write<S>(
     endpoint_id: EndPointID,
-    mut rx: tokio::sync::mpsc::Receiver<Vec<u8>>,
+    mut rx: MuxReceiver,
     mut sealing_key: Option<SealingKey<NonceValue>>,
-    mut sink: SplitSink<Framed<TcpStream, LengthDelimitedCodec>, Bytes>,
-) {
-    tokio::spawn(async move {
+    mut sink: SplitSink<Framed<S, LengthDelimitedCodec>, Bytes>,
+) where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    spawn::spawn(async move {
         loop {
             match rx.recv().await {
-                Some(mut buffer) => {
+                Some(mut frame) => {
+                    let mut buffer = frame.take_buffer();
+
                     if let Some(ref mut sealing_key) = sealing_key {
                         if let Err(err) = sealing_key
                             .seal_in_place_append_tag(ring::aead::Aad::empty(), &mut buffer)
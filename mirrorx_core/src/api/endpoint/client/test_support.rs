@@ -0,0 +1,305 @@
+//! Endpoints of one session connected in process over an in-memory stream, so negotiation,
+//! input, file transfer and reconnection are exercised end to end without signaling,
+//! network or hardware.
+//!
+//! Files sessions are served by a real passive `EndPointClient`. Desktop sessions are
+//! served by `MockDesktopPeer` in place of capture and encoding, it answers negotiation
+//! with a fake monitor, streams the frames which tests push and records the input it
+//! receives. The active endpoint hands its frames to the receivers of `DesktopPair`
+//! rather than to decoders.
+//!
+//! Reconnecting waits `RECONNECT_INTERVAL` between attempts, tests of it should run with
+//! paused tokio time.
+
+use super::{codec::WireCodec, CreateParams, EndPointClient, ReconnectParams, Reconnector};
+use crate::{
+    api::endpoint::{id::EndPointID, message::*, EndPointStream},
+    component::{
+        desktop::monitor::Monitor,
+        frame::{ColorPrimaries, ColorRange, ColorSpace},
+    },
+    core_error,
+    error::{CoreError, CoreResult},
+    utility::spawn,
+};
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use std::sync::{atomic::Ordering, Arc};
+use tokio::{
+    io::DuplexStream,
+    sync::mpsc::{Receiver, UnboundedReceiver, UnboundedSender},
+};
+use tokio_util::{
+    codec::{Framed, LengthDelimitedCodec},
+    sync::CancellationToken,
+};
+
+pub const ACTIVE_DEVICE_ID: i64 = 1_000_000_001;
+pub const PASSIVE_DEVICE_ID: i64 = 1_000_000_002;
+
+const DUPLEX_BUFFER_SIZE: usize = 1024 * 1024;
+const DESKTOP_FRAME_BUFFER_SIZE: usize = 180;
+
+pub struct EndPointPair {
+    pub active: Arc<EndPointClient>,
+    pub passive: Arc<EndPointClient>,
+}
+
+/// Connect the files session between two clients of this process.
+pub async fn connect_files_pair() -> CoreResult<EndPointPair> {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let (active_stream, passive_stream) = tokio::io::duplex(DUPLEX_BUFFER_SIZE);
+
+    let (active, passive) = tokio::try_join!(
+        EndPointClient::create(CreateParams {
+            session_id: session_id.clone(),
+            active: true,
+            endpoint_id: active_endpoint_id(),
+            key_pair: None,
+            stream: EndPointStream::Duplex(active_stream),
+            desktop_frame_tx: None,
+            visit_credentials: None,
+            session_slot: None,
+            observed_session_id: None,
+        }),
+        EndPointClient::create(CreateParams {
+            session_id,
+            active: false,
+            endpoint_id: passive_endpoint_id(),
+            key_pair: None,
+            stream: EndPointStream::Duplex(passive_stream),
+            desktop_frame_tx: None,
+            visit_credentials: None,
+            session_slot: None,
+            observed_session_id: None,
+        }),
+    )?;
+
+    Ok(EndPointPair { active, passive })
+}
+
+pub struct DesktopPair {
+    pub active: Arc<EndPointClient>,
+    pub video_frames: Receiver<EndPointVideoFrame>,
+    pub audio_frames: Receiver<EndPointAudioFrame>,
+    pub peer: MockDesktopPeer,
+    // peers which serve the session after each reconnection
+    reconnected_peers: UnboundedReceiver<MockDesktopPeer>,
+}
+
+/// Connect the desktop session of an active client to `MockDesktopPeer`.
+pub async fn connect_desktop_pair() -> CoreResult<DesktopPair> {
+    let (active_stream, peer_stream) = tokio::io::duplex(DUPLEX_BUFFER_SIZE);
+    let peer = MockDesktopPeer::serve(peer_stream);
+
+    let (video_frame_tx, video_frames) = tokio::sync::mpsc::channel(DESKTOP_FRAME_BUFFER_SIZE);
+    let (audio_frame_tx, audio_frames) = tokio::sync::mpsc::channel(DESKTOP_FRAME_BUFFER_SIZE);
+
+    let active = EndPointClient::new_desktop_active(
+        uuid::Uuid::new_v4().to_string(),
+        active_endpoint_id(),
+        None,
+        EndPointStream::Duplex(active_stream),
+        video_frame_tx,
+        audio_frame_tx,
+        None,
+    )
+    .await?;
+
+    let (reconnected_peers_tx, reconnected_peers) = tokio::sync::mpsc::unbounded_channel();
+    active.set_reconnector(Reconnector::new(move || {
+        let reconnected_peers_tx = reconnected_peers_tx.clone();
+        async move {
+            let (active_stream, peer_stream) = tokio::io::duplex(DUPLEX_BUFFER_SIZE);
+            let _ = reconnected_peers_tx.send(MockDesktopPeer::serve(peer_stream));
+
+            Ok(ReconnectParams {
+                key_pair: None,
+                stream: EndPointStream::Duplex(active_stream),
+                visit_credentials: None,
+            })
+        }
+    }));
+
+    Ok(DesktopPair {
+        active,
+        video_frames,
+        audio_frames,
+        peer,
+        reconnected_peers,
+    })
+}
+
+impl DesktopPair {
+    /// Take the peer offline as if it's rebooting, the active client reconnects and the
+    /// peer which serves the resumed session replaces it.
+    pub async fn restart_peer(&mut self) -> CoreResult<()> {
+        self.active.reconnect_expected.store(true, Ordering::SeqCst);
+        self.peer.disconnect();
+
+        self.peer = self
+            .reconnected_peers
+            .recv()
+            .await
+            .ok_or(core_error!("reconnector is dropped"))?;
+
+        Ok(())
+    }
+}
+
+/// Passive endpoint of desktop session without capture, encoding and input simulation.
+/// Calls aren't served by it, they time out at the active client.
+pub struct MockDesktopPeer {
+    outgoing_tx: UnboundedSender<EndPointMessage>,
+    inputs: UnboundedReceiver<EndPointInput>,
+    closed: CancellationToken,
+}
+
+impl MockDesktopPeer {
+    fn serve(stream: DuplexStream) -> Self {
+        let (outgoing_tx, mut outgoing_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (input_tx, inputs) = tokio::sync::mpsc::unbounded_channel();
+        let closed = CancellationToken::new();
+
+        let disconnected = closed.clone();
+        spawn::spawn(async move {
+            let codec = WireCodec::default();
+            let mut framed = Framed::new(
+                stream,
+                LengthDelimitedCodec::builder()
+                    .little_endian()
+                    .max_frame_length(32 * 1024 * 1024)
+                    .new_codec(),
+            );
+
+            loop {
+                let message = tokio::select! {
+                    _ = disconnected.cancelled() => break,
+                    message = outgoing_rx.recv() => match message {
+                        Some(message) => message,
+                        None => break,
+                    },
+                    buffer = framed.next() => {
+                        let Some(Ok(buffer)) = buffer else {
+                            break;
+                        };
+
+                        match codec.decode(&buffer) {
+                            Ok(Some(EndPointMessage::NegotiateDesktopParamsRequest(_))) => {
+                                EndPointMessage::NegotiateDesktopParamsResponse(
+                                    EndPointNegotiateDesktopParamsResponse::Params(
+                                        mock_desktop_params(),
+                                    ),
+                                )
+                            }
+                            Ok(Some(EndPointMessage::InputCommand(input))) => {
+                                let _ = input_tx.send(input);
+                                continue;
+                            }
+                            Ok(_) => continue,
+                            Err(err) => {
+                                tracing::warn!(?err, "mock peer decode message failed");
+                                continue;
+                            }
+                        }
+                    }
+                };
+
+                let buffer = match codec.encode(&message) {
+                    Ok(buffer) => buffer,
+                    Err(err) => {
+                        tracing::warn!(?err, "mock peer encode message failed");
+                        continue;
+                    }
+                };
+
+                if framed.send(Bytes::from(buffer)).await.is_err() {
+                    break;
+                }
+            }
+
+            // the active client sees the stream closed once it's dropped
+            tracing::info!("mock peer exit");
+        });
+
+        MockDesktopPeer {
+            outgoing_tx,
+            inputs,
+            closed,
+        }
+    }
+
+    pub fn push_video_frame(&self, frame: EndPointVideoFrame) -> CoreResult<()> {
+        self.push(EndPointMessage::VideoFrame(frame))
+    }
+
+    pub fn push_audio_frame(&self, frame: EndPointAudioFrame) -> CoreResult<()> {
+        self.push(EndPointMessage::AudioFrame(frame))
+    }
+
+    /// Push any message to the active client, like cursor or power saving state.
+    pub fn push(&self, message: EndPointMessage) -> CoreResult<()> {
+        self.outgoing_tx
+            .send(message)
+            .map_err(|_| CoreError::OutgoingMessageChannelDisconnect)
+    }
+
+    /// The next input which the active client sends.
+    pub async fn recv_input(&mut self) -> Option<EndPointInput> {
+        self.inputs.recv().await
+    }
+
+    /// Close the stream as if the peer goes offline.
+    pub fn disconnect(&self) {
+        self.closed.cancel();
+    }
+}
+
+impl Drop for MockDesktopPeer {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
+
+pub fn active_endpoint_id() -> EndPointID {
+    EndPointID::DeviceID {
+        local_device_id: ACTIVE_DEVICE_ID,
+        remote_device_id: PASSIVE_DEVICE_ID,
+    }
+}
+
+pub fn passive_endpoint_id() -> EndPointID {
+    EndPointID::DeviceID {
+        local_device_id: PASSIVE_DEVICE_ID,
+        remote_device_id: ACTIVE_DEVICE_ID,
+    }
+}
+
+fn mock_desktop_params() -> EndPointNegotiateVisitDesktopParams {
+    EndPointNegotiateVisitDesktopParams {
+        video_codec: VideoCodec::H264,
+        os_type: String::from("Mock"),
+        os_version: String::from("1.0"),
+        primary_monitor: Monitor {
+            id: String::from("mock-monitor"),
+            name: String::from("Mock Display"),
+            refresh_rate: 60,
+            width: 1920,
+            height: 1080,
+            is_primary: true,
+            screen_shot: None,
+            left: 0,
+            top: 0,
+            scale_factor: 100,
+        },
+        color_space: ColorSpace {
+            primaries: ColorPrimaries::Bt709,
+            range: ColorRange::Limited,
+        },
+        cursor_mode: CursorMode::Composited,
+        audio_source: AudioSource::SystemAudio,
+        audio_applications: Vec::new(),
+        watermark: false,
+        keyboard_layout: None,
+    }
+}
@@ -0,0 +1,126 @@
+//! Relays of self-hosted domains are reached by ip which the signaling server assigns,
+//! there's no name for a CA to vouch for, so the relay certificate is pinned by its
+//! fingerprint instead. Endpoint messages are sealed by the session keys anyway, TLS
+//! keeps rogue relays from reading the handshake and the traffic metadata, and lets the
+//! relay authenticate devices of the domain by their client certificates.
+
+use crate::{api::config::entity::tls::RelayTls, core_error, error::CoreResult};
+use std::{net::SocketAddr, sync::Arc, time::SystemTime};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{
+        client::{ServerCertVerified, ServerCertVerifier},
+        Certificate, ClientConfig, Error, PrivateKey, ServerName,
+    },
+    TlsConnector,
+};
+
+struct PinnedCertVerifier {
+    fingerprint: Vec<u8>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let digest = ring::digest::digest(&ring::digest::SHA256, &end_entity.0);
+        let pinned =
+            ring::constant_time::verify_slices_are_equal(digest.as_ref(), &self.fingerprint);
+
+        if pinned.is_err() {
+            tracing::error!("relay certificate doesn't match the pinned fingerprint");
+            return Err(Error::General(String::from(
+                "relay certificate doesn't match the pinned fingerprint",
+            )));
+        }
+
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+pub async fn connect_tls(
+    stream: TcpStream,
+    addr: SocketAddr,
+    relay_tls: &RelayTls,
+) -> CoreResult<TlsStream<TcpStream>> {
+    let config = client_config(relay_tls)?;
+    let connector = TlsConnector::from(Arc::new(config));
+
+    // the verifier ignores the name since the certificate is pinned
+    let stream = connector
+        .connect(ServerName::IpAddress(addr.ip()), stream)
+        .await?;
+
+    Ok(stream)
+}
+
+/// Check the settings before they're saved, so misconfiguration shows up at once rather
+/// than as failed visits later.
+pub fn validate_relay_tls(relay_tls: &RelayTls) -> CoreResult<()> {
+    client_config(relay_tls).map(|_| ())
+}
+
+fn client_config(relay_tls: &RelayTls) -> CoreResult<ClientConfig> {
+    let fingerprint = parse_fingerprint(&relay_tls.relay_fingerprint)?;
+
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier { fingerprint }));
+
+    let config = match (&relay_tls.client_cert, &relay_tls.client_key) {
+        (Some(client_cert), Some(client_key)) => builder
+            .with_single_cert(parse_certs(client_cert)?, parse_private_key(client_key)?)
+            .map_err(|err| core_error!("invalid client certificate ({})", err))?,
+        (None, None) => builder.with_no_client_auth(),
+        _ => {
+            return Err(core_error!(
+                "client certificate and its private key must be set together"
+            ))
+        }
+    };
+
+    Ok(config)
+}
+
+// fingerprints are usually shown as colon separated hex like `openssl x509 -fingerprint`
+fn parse_fingerprint(fingerprint: &str) -> CoreResult<Vec<u8>> {
+    let fingerprint = hex::decode(fingerprint.trim().replace(':', ""))
+        .map_err(|_| core_error!("relay fingerprint isn't valid hex"))?;
+
+    if fingerprint.len() != ring::digest::SHA256_OUTPUT_LEN {
+        return Err(core_error!("relay fingerprint isn't a SHA-256 digest"));
+    }
+
+    Ok(fingerprint)
+}
+
+fn parse_certs(pem: &str) -> CoreResult<Vec<Certificate>> {
+    let certs = rustls_pemfile::certs(&mut pem.as_bytes())?;
+    if certs.is_empty() {
+        return Err(core_error!("no certificate in client certificate PEM"));
+    }
+
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn parse_private_key(pem: &str) -> CoreResult<PrivateKey> {
+    let mut reader = pem.as_bytes();
+
+    while let Some(item) = rustls_pemfile::read_one(&mut reader)? {
+        match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(PrivateKey(key)),
+            _ => continue,
+        }
+    }
+
+    Err(core_error!("no private key in client key PEM"))
+}
@@ -0,0 +1,381 @@
+//! Session trace records every message which the endpoint sends and receives into a file,
+//! with its size on wire and the time since tracing started. Users attach traces to the
+//! reports of decoding and rendering issues, and `replay_trace` feeds the received
+//! messages of a trace through the message handling of a client in place of the remote
+//! endpoint, so the issue is reproduced without the remote device.
+//!
+//! The file starts with `MAGIC`, the header and the records follow it, each of them is
+//! CBOR prefixed with its length in 4 bytes little endian. CBOR keys fields by name, so
+//! traces stay readable by later releases as long as the messages do.
+//!
+//! Media frames can be redacted, they're recorded with their size but without payload so
+//! the desktop and audio of the session don't leave the machine. Credentials are always
+//! redacted. Replays skip the redacted messages.
+
+use super::{
+    codec::WireCodec,
+    mux,
+    state::{SessionState, SessionStateMachine},
+    EndPointClient,
+};
+use crate::{
+    api::endpoint::{id::EndPointID, message::*},
+    core_error,
+    error::CoreResult,
+    utility::{
+        cbor::{cbor_deserialize, cbor_serialize},
+        spawn,
+    },
+};
+use bytes::Bytes;
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::Sender;
+
+const MAGIC: &[u8; 8] = b"MXTRACE\x01";
+
+// larger than any message, a larger length means the file is corrupted
+const MAX_RECORD_SIZE: usize = 64 * 1024 * 1024;
+
+// replays don't outrun the decoders, they wait once this many messages are queued
+const REPLAY_BUFFER_SIZE: usize = 64;
+
+static TRACES: Lazy<Mutex<HashMap<EndPointID, Trace>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct Trace {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    started: Instant,
+    redact_media: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceDirection {
+    Sent,
+    Received,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TraceHeader {
+    /// The traced session, for people to read since it isn't parsed back.
+    pub endpoint: String,
+    /// Unix timestamp in seconds when tracing started.
+    pub started_at: i64,
+    pub redact_media: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TraceRecord {
+    /// Microseconds since tracing started.
+    pub elapsed_micros: u64,
+    pub direction: TraceDirection,
+    /// Size of the message on wire, the header of wire format included.
+    pub wire_size: u64,
+    /// The payload of media frame is dropped.
+    pub redacted: bool,
+    pub message: EndPointMessage,
+}
+
+// the same fields as TraceRecord, it borrows the message which is being sent or handled
+#[derive(Serialize)]
+struct TraceRecordRef<'a> {
+    elapsed_micros: u64,
+    direction: TraceDirection,
+    wire_size: u64,
+    redacted: bool,
+    message: &'a EndPointMessage,
+}
+
+/// Start tracing the messages of session into `path`, the payload of video and audio
+/// frames is dropped when `redact_media`.
+pub fn start_trace(endpoint_id: EndPointID, path: &Path, redact_media: bool) -> CoreResult<()> {
+    let mut traces = TRACES.lock().unwrap();
+    if traces.contains_key(&endpoint_id) {
+        return Err(core_error!("session is already tracing"));
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(MAGIC)?;
+    write_frame(
+        &mut writer,
+        &TraceHeader {
+            endpoint: endpoint_id.to_string(),
+            started_at: chrono::Utc::now().timestamp(),
+            redact_media,
+        },
+    )?;
+
+    traces.insert(
+        endpoint_id,
+        Trace {
+            path: path.to_path_buf(),
+            writer,
+            started: Instant::now(),
+            redact_media,
+        },
+    );
+
+    tracing::info!(?endpoint_id, ?path, redact_media, "start trace");
+
+    Ok(())
+}
+
+/// Stop tracing the session and returns the path of trace file.
+pub fn stop_trace(endpoint_id: &EndPointID) -> CoreResult<PathBuf> {
+    let Some(mut trace) = TRACES.lock().unwrap().remove(endpoint_id) else {
+        return Err(core_error!("session is not tracing"));
+    };
+
+    trace.writer.flush()?;
+
+    tracing::info!(?endpoint_id, path = ?trace.path, "stop trace");
+
+    Ok(trace.path)
+}
+
+pub fn is_tracing(endpoint_id: &EndPointID) -> bool {
+    TRACES.lock().unwrap().contains_key(endpoint_id)
+}
+
+/// Stop the trace when session ends, so the file is flushed.
+pub(super) fn finish_trace(endpoint_id: &EndPointID) {
+    if is_tracing(endpoint_id) {
+        if let Err(err) = stop_trace(endpoint_id) {
+            tracing::error!(?endpoint_id, ?err, "finish trace failed");
+        }
+    }
+}
+
+pub(super) fn record(
+    endpoint_id: &EndPointID,
+    direction: TraceDirection,
+    message: &EndPointMessage,
+    wire_size: usize,
+) {
+    let mut traces = TRACES.lock().unwrap();
+    let Some(trace) = traces.get_mut(endpoint_id) else {
+        return;
+    };
+
+    let redacted_message = redact(message, trace.redact_media);
+
+    let record = TraceRecordRef {
+        elapsed_micros: trace.started.elapsed().as_micros() as u64,
+        direction,
+        wire_size: wire_size as u64,
+        redacted: redacted_message.is_some(),
+        message: redacted_message.as_ref().unwrap_or(message),
+    };
+
+    if let Err(err) = write_frame(&mut trace.writer, &record) {
+        tracing::error!(?endpoint_id, ?err, "write trace failed, stop tracing");
+        traces.remove(endpoint_id);
+    }
+}
+
+// media frame without payload when `media`, or the message without credentials, None
+// for other messages
+fn redact(message: &EndPointMessage, media: bool) -> Option<EndPointMessage> {
+    match message {
+        EndPointMessage::CallRequest(call_id, EndPointCallRequest::LoginRequest(req)) => {
+            Some(EndPointMessage::CallRequest(
+                *call_id,
+                EndPointCallRequest::LoginRequest(EndPointLoginRequest {
+                    password: String::new(),
+                    ..req.clone()
+                }),
+            ))
+        }
+        _ if !media => None,
+        EndPointMessage::VideoFrame(frame) => {
+            Some(EndPointMessage::VideoFrame(EndPointVideoFrame {
+                width: frame.width,
+                height: frame.height,
+                pts: frame.pts,
+                buffer: Vec::new(),
+                timestamp_micros: frame.timestamp_micros,
+            }))
+        }
+        EndPointMessage::MagnifierFrame(magnifier_frame) => {
+            Some(EndPointMessage::MagnifierFrame(EndPointMagnifierFrame {
+                region: magnifier_frame.region,
+                frame: EndPointVideoFrame {
+                    width: magnifier_frame.frame.width,
+                    height: magnifier_frame.frame.height,
+                    pts: magnifier_frame.frame.pts,
+                    buffer: Vec::new(),
+                    timestamp_micros: magnifier_frame.frame.timestamp_micros,
+                },
+            }))
+        }
+        EndPointMessage::AudioFrame(frame) => {
+            Some(EndPointMessage::AudioFrame(EndPointAudioFrame {
+                channels: frame.channels,
+                sample_format: frame.sample_format.clone(),
+                sample_rate: frame.sample_rate,
+                buffer: Vec::new(),
+                timestamp_micros: frame.timestamp_micros,
+            }))
+        }
+        _ => None,
+    }
+}
+
+fn write_frame<W: Write, T: Serialize>(writer: &mut W, value: &T) -> CoreResult<()> {
+    let buffer = cbor_serialize(value)?;
+    writer.write_all(&(buffer.len() as u32).to_le_bytes())?;
+    writer.write_all(&buffer)?;
+    Ok(())
+}
+
+// None at the end of file
+fn read_frame<R: Read, T: DeserializeOwned>(reader: &mut R) -> CoreResult<Option<T>> {
+    let mut length = [0u8; 4];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+
+    let length = u32::from_le_bytes(length) as usize;
+    if length > MAX_RECORD_SIZE {
+        return Err(core_error!("trace record is too large ({})", length));
+    }
+
+    let mut buffer = vec![0u8; length];
+    reader.read_exact(&mut buffer)?;
+
+    cbor_deserialize(&buffer).map(Some)
+}
+
+/// Read the records of trace file in order.
+pub struct TraceReader {
+    reader: BufReader<File>,
+    header: TraceHeader,
+}
+
+impl TraceReader {
+    pub fn open(path: &Path) -> CoreResult<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != *MAGIC {
+            return Err(core_error!("not a trace file"));
+        }
+
+        let header = read_frame(&mut reader)?.ok_or(core_error!("trace has no header"))?;
+
+        Ok(TraceReader { reader, header })
+    }
+
+    pub fn header(&self) -> &TraceHeader {
+        &self.header
+    }
+}
+
+impl Iterator for TraceReader {
+    type Item = CoreResult<TraceRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        read_frame(&mut self.reader).transpose()
+    }
+}
+
+/// Serve an active desktop client with the received messages of trace instead of a
+/// remote endpoint, frames go to the senders like the ones from remote. Messages are fed
+/// at the recorded pace when `realtime`, otherwise as fast as they're handled. The
+/// session is closed as disconnected at the end of trace.
+pub(crate) async fn replay_trace(
+    session_id: String,
+    endpoint_id: EndPointID,
+    path: &Path,
+    video_frame_tx: Sender<EndPointVideoFrame>,
+    audio_frame_tx: Sender<EndPointAudioFrame>,
+    realtime: bool,
+) -> CoreResult<Arc<EndPointClient>> {
+    let reader = TraceReader::open(path)?;
+    tracing::info!(?path, endpoint = %reader.header().endpoint, "replay trace");
+
+    let state = Arc::new(SessionStateMachine::new(session_id.clone(), endpoint_id));
+    // nothing to handshake, walk through it so the session can stream
+    state.transit(SessionState::Handshaking);
+
+    // the messages which the replayed client sends, like call replies, go nowhere
+    let (tx, mut outgoing_rx) = mux::channel();
+    spawn::spawn(async move { while outgoing_rx.recv().await.is_some() {} });
+
+    let (message_tx, message_rx) = tokio::sync::mpsc::channel(REPLAY_BUFFER_SIZE);
+    spawn::spawn_blocking(move || feed_trace(reader, message_tx, realtime));
+
+    Ok(EndPointClient::start(
+        session_id,
+        true,
+        endpoint_id,
+        state,
+        (tx, message_rx),
+        Arc::new(WireCodec::default()),
+        Some((video_frame_tx, audio_frame_tx)),
+        None,
+        None,
+        None,
+    )
+    .await)
+}
+
+fn feed_trace(reader: TraceReader, message_tx: Sender<Bytes>, realtime: bool) {
+    // the replay speaks the wire format which every client reads at first
+    let codec = WireCodec::default();
+    let started = Instant::now();
+    let mut skipped = 0;
+
+    for record in reader {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                tracing::error!(?err, "read trace record failed, stop replay");
+                break;
+            }
+        };
+
+        if record.direction != TraceDirection::Received {
+            continue;
+        }
+
+        if record.redacted {
+            skipped += 1;
+            continue;
+        }
+
+        if realtime {
+            let due = Duration::from_micros(record.elapsed_micros);
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+
+        let buffer = match codec.encode(&record.message) {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                tracing::error!(?err, "encode trace record failed, skip it");
+                continue;
+            }
+        };
+
+        if message_tx.blocking_send(Bytes::from(buffer)).is_err() {
+            tracing::info!("replayed session is closed, stop replay");
+            return;
+        }
+    }
+
+    tracing::info!(skipped, "replay trace finished");
+}
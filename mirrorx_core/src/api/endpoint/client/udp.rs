@@ -1,4 +1,7 @@
-use super::RECV_MESSAGE_TIMEOUT;
+use super::{
+    mux::{self, MuxReceiver, MuxSender},
+    MAX_CONSECUTIVE_OPEN_FAILURES, RECV_MESSAGE_TIMEOUT,
+};
 use crate::{
     api::endpoint::{
         id::EndPointID,
@@ -9,6 +12,7 @@ use crate::{
     utility::{
         bincode::{bincode_deserialize, bincode_serialize},
         nonce_value::NonceValue,
+        spawn,
     },
 };
 use bytes::Bytes;
@@ -18,7 +22,7 @@ use futures::{
 };
 use ring::aead::{OpeningKey, SealingKey};
 use std::{net::SocketAddr, ops::Deref};
-use tokio::{net::UdpSocket, sync::mpsc::Sender};
+use tokio::net::UdpSocket;
 use tokio_util::{codec::LengthDelimitedCodec, udp::UdpFramed};
 
 pub async fn serve_udp(
@@ -27,7 +31,7 @@ pub async fn serve_udp(
     sealing_key: Option<SealingKey<NonceValue>>,
     opening_key: Option<OpeningKey<NonceValue>>,
     mut visit_credentials: Option<Vec<u8>>,
-) -> CoreResult<(Sender<Vec<u8>>, tokio::sync::mpsc::Receiver<Bytes>)> {
+) -> CoreResult<(MuxSender, tokio::sync::mpsc::Receiver<Bytes>)> {
     let remote_addr = socket.peer_addr()?;
     let mut framed = UdpFramed::new(
         socket,
@@ -41,7 +45,7 @@ pub async fn serve_udp(
         serve_udp_handshake(remote_addr, &mut framed, visit_credentials, endpoint_id).await?;
     }
 
-    let (tx, rx) = tokio::sync::mpsc::channel(32);
+    let (tx, rx) = mux::channel();
     let (sink, stream) = framed.split();
     serve_udp_write(remote_addr, rx, sealing_key, sink);
     let rx = serve_udp_read(remote_addr, opening_key, stream)?;
@@ -95,7 +99,9 @@ fn serve_udp_read(
 ) -> CoreResult<tokio::sync::mpsc::Receiver<Bytes>> {
     let (tx, rx) = tokio::sync::mpsc::channel(1);
 
-    tokio::spawn(async move {
+    spawn::spawn(async move {
+        let mut open_failures = 0;
+
         loop {
             let mut buffer = match stream.next().await {
                 Some(packet) => match packet {
@@ -118,11 +124,18 @@ fn serve_udp_read(
             };
 
             if let Some(ref mut opening_key) = opening_key {
-                if let Err(err) =
-                    opening_key.open_in_place(ring::aead::Aad::empty(), buffer.as_mut())
-                {
-                    tracing::error!(?err, "open endpoint message packet failed");
-                    break;
+                match opening_key.open_in_place(ring::aead::Aad::empty(), buffer.as_mut()) {
+                    Ok(_) => open_failures = 0,
+                    Err(err) => {
+                        open_failures += 1;
+                        if open_failures >= MAX_CONSECUTIVE_OPEN_FAILURES {
+                            tracing::error!(?err, "open endpoint message packet failed");
+                            break;
+                        }
+
+                        tracing::warn!(?err, open_failures, "skip unopenable endpoint packet");
+                        continue;
+                    }
                 }
             }
 
@@ -140,14 +153,16 @@ fn serve_udp_read(
 
 fn serve_udp_write(
     remote_addr: SocketAddr,
-    mut rx: tokio::sync::mpsc::Receiver<Vec<u8>>,
+    mut rx: MuxReceiver,
     mut sealing_key: Option<SealingKey<NonceValue>>,
     mut sink: SplitSink<UdpFramed<LengthDelimitedCodec>, (Bytes, SocketAddr)>,
 ) {
-    tokio::spawn(async move {
+    spawn::spawn(async move {
         loop {
             match rx.recv().await {
-                Some(mut buffer) => {
+                Some(mut frame) => {
+                    let mut buffer = frame.take_buffer();
+
                     if let Some(ref mut sealing_key) = sealing_key {
                         if let Err(err) = sealing_key
                             .seal_in_place_append_tag(ring::aead::Aad::empty(), &mut buffer)
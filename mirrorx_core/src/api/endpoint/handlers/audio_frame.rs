@@ -1,15 +1,29 @@
 use crate::{
     api::endpoint::{message::EndPointAudioFrame, EndPointID},
-    component::audio::{
-        decoder::AudioDecoder,
-        player::{default_output_config, new_play_stream_and_tx},
+    component::{
+        audio::{
+            decoder::AudioDecoder,
+            jitter_buffer::{JitterBuffer, FRAME_DURATION},
+            player::{default_output_config, new_play_stream},
+            processor::push_render_samples,
+        },
+        clock_sync, session_stats,
     },
+    utility::spawn,
 };
-use cpal::traits::StreamTrait;
-use tokio::sync::mpsc::Receiver;
+use cpal::{traits::StreamTrait, Stream};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use tokio::{runtime::Handle, sync::mpsc::Receiver};
+
+// frames made up in a row before the stream is left to fall silent, longer gaps aren't
+// lost packets but a paused sender
+const MAX_CONSECUTIVE_CONCEALED_FRAMES: u32 = 10;
 
 pub fn serve_audio_decode(id: EndPointID, mut decode_rx: Receiver<EndPointAudioFrame>) {
-    tokio::task::spawn_blocking(move || loop {
+    spawn::spawn_blocking(move || loop {
         tracing::info!(?id, "audio decode process");
 
         let Ok(config) = default_output_config() else {
@@ -25,70 +39,49 @@ pub fn serve_audio_decode(id: EndPointID, mut decode_rx: Receiver<EndPointAudioF
             config.sample_rate(),
         );
 
-        let mut stream = None;
-        let mut samples_tx = None;
+        let jitter_buffer = JitterBuffer::new(
+            config.channels(),
+            config.sample_format().sample_size(),
+            config.sample_rate().0,
+        );
+
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let mut stream: Option<Stream> = None;
+        let mut consecutive_concealed = 0;
 
         loop {
-            match decode_rx.blocking_recv() {
-                Some(audio_frame) => {
+            // the config of the new default device may differ in every way, so the decoder
+            // and its re-sampler are rebuilt along with the stream
+            if device_lost.load(Ordering::SeqCst) {
+                tracing::warn!("audio output device lost, switch to the default one");
+                break;
+            }
+
+            // frames are awaited a little longer than they last, the buffer is concealed
+            // when it runs dry meanwhile
+            let received = Handle::current().block_on(async {
+                tokio::time::timeout(FRAME_DURATION * 2, decode_rx.recv()).await
+            });
+
+            let (buffer, concealed) = match received {
+                Ok(Some(audio_frame)) => {
+                    if let Some(latency) =
+                        clock_sync::one_way_latency(&id, audio_frame.timestamp_micros)
+                    {
+                        session_stats::record_audio_latency(id, latency);
+                    }
+
+                    consecutive_concealed = 0;
+
                     match audio_decoder.decode(audio_frame) {
-                        Ok(buffer) => {
-                            // because active endpoint always output 48000hz and 480 samples per channel after
-                            // opus encode, so here we simply div (48000/480)=100 to get samples count after
-                            // resample.
-                            let valid_min_samples_per_channel = config.sample_rate().0 / 100;
-
-                            if stream.is_none() {
-                                let buffer_size = buffer.len()
-                                    / (config.channels() as usize)
-                                    / config.sample_format().sample_size();
-
-                                // drop the beginning frames
-                                if buffer_size < (valid_min_samples_per_channel as usize) {
-                                    continue;
-                                }
-
-                                tracing::info!(?buffer_size, "use buffer size");
-
-                                match new_play_stream_and_tx(
-                                    config.channels(),
-                                    config.sample_format(),
-                                    config.sample_rate(),
-                                    buffer_size as u32,
-                                ) {
-                                    Ok((play_stream, audio_sample_tx)) => {
-                                        if let Err(err) = play_stream.play() {
-                                            tracing::error!(?err, "play audio stream failed");
-                                            return;
-                                        }
-
-                                        stream = Some(play_stream);
-                                        samples_tx = Some(audio_sample_tx);
-                                    }
-                                    Err(err) => {
-                                        tracing::error!(
-                                            ?err,
-                                            "initialize audio play stream failed"
-                                        );
-                                        continue;
-                                    }
-                                };
-                            }
-
-                            if let Some(ref samples_tx) = samples_tx {
-                                if samples_tx.blocking_send(buffer).is_err() {
-                                    tracing::error!("send audio play buffer failed");
-                                    return;
-                                }
-                            }
-                        }
+                        Ok(buffer) => (buffer, false),
                         Err(err) => {
                             tracing::error!(?err, "decode audio frame failed");
                             break;
                         }
-                    };
+                    }
                 }
-                None => {
+                Ok(None) => {
                     if let Some(ref stream) = stream {
                         let _ = stream.pause();
                     }
@@ -96,7 +89,82 @@ pub fn serve_audio_decode(id: EndPointID, mut decode_rx: Receiver<EndPointAudioF
                     tracing::error!("audio decode process exit");
                     return;
                 }
+                Err(_) => {
+                    if consecutive_concealed >= MAX_CONSECUTIVE_CONCEALED_FRAMES
+                        || !jitter_buffer.lock().unwrap().starving()
+                    {
+                        continue;
+                    }
+
+                    match audio_decoder.conceal() {
+                        Ok(Some(buffer)) => {
+                            consecutive_concealed += 1;
+                            (buffer, true)
+                        }
+                        Ok(None) => continue,
+                        Err(err) => {
+                            tracing::warn!(?err, "conceal audio frame failed");
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            // because active endpoint always output 48000hz and 480 samples per channel after
+            // opus encode, so here we simply div (48000/480)=100 to get samples count after
+            // resample.
+            let valid_min_samples_per_channel = config.sample_rate().0 / 100;
+
+            if stream.is_none() {
+                let buffer_size = buffer.len()
+                    / (config.channels() as usize)
+                    / config.sample_format().sample_size();
+
+                // drop the beginning frames
+                if buffer_size < (valid_min_samples_per_channel as usize) {
+                    continue;
+                }
+
+                tracing::info!(?buffer_size, "use buffer size");
+
+                match new_play_stream(
+                    config.channels(),
+                    config.sample_format(),
+                    config.sample_rate(),
+                    buffer_size as u32,
+                    jitter_buffer.clone(),
+                    device_lost.clone(),
+                ) {
+                    Ok(play_stream) => {
+                        if let Err(err) = play_stream.play() {
+                            tracing::error!(?err, "play audio stream failed");
+                            return;
+                        }
+
+                        stream = Some(play_stream);
+                    }
+                    Err(err) => {
+                        tracing::error!(?err, "initialize audio play stream failed");
+                        continue;
+                    }
+                };
             }
+
+            // what's played may echo into local recorded microphone
+            push_render_samples(
+                &buffer,
+                config.channels(),
+                config.sample_format(),
+                config.sample_rate().0,
+            );
+
+            let mut jitter_buffer = jitter_buffer.lock().unwrap();
+            jitter_buffer.push(&buffer, concealed);
+            session_stats::record_audio_jitter(
+                id,
+                jitter_buffer.take_stats(),
+                jitter_buffer.target_delay(),
+            );
         }
 
         if let Some(ref stream) = stream {
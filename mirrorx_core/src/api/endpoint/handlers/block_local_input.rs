@@ -0,0 +1,58 @@
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{EndPointBlockLocalInputReply, EndPointBlockLocalInputRequest, EndPointMessage},
+    },
+    component::local_input_block::{
+        block_local_input, local_input_blocked_by, prompt_local_input_block, release_local_input,
+    },
+    error::CoreResult,
+    utility::spawn,
+};
+use std::sync::Arc;
+
+pub async fn handle_block_local_input_request(
+    client: Arc<EndPointClient>,
+    req: EndPointBlockLocalInputRequest,
+) -> CoreResult<EndPointBlockLocalInputReply> {
+    let session_id = client.session_id().to_string();
+
+    if !req.blocked {
+        release_local_input(&session_id);
+        return Ok(EndPointBlockLocalInputReply { blocked: false });
+    }
+
+    if local_input_blocked_by(&session_id) {
+        return Ok(EndPointBlockLocalInputReply { blocked: true });
+    }
+
+    tracing::info!("block local input request");
+
+    if !prompt_local_input_block(client.endpoint_id()).await {
+        tracing::info!("block local input rejected");
+        return Ok(EndPointBlockLocalInputReply { blocked: false });
+    }
+
+    let escaped_rx = block_local_input(&session_id)?;
+
+    // the sender is dropped without firing when the session releases it or ends
+    let client = Arc::downgrade(&client);
+    spawn::spawn(async move {
+        if escaped_rx.await.is_err() {
+            return;
+        }
+
+        let Some(client) = client.upgrade() else {
+            return;
+        };
+
+        if let Err(err) = client
+            .send(&EndPointMessage::LocalInputBlocked(false))
+            .await
+        {
+            tracing::error!(?err, "notify local input released failed");
+        }
+    });
+
+    Ok(EndPointBlockLocalInputReply { blocked: true })
+}
@@ -0,0 +1,87 @@
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{
+            EndPointElevatePermissionsReply, EndPointElevatePermissionsRequest, EndPointMessage,
+        },
+        permission::{prompt_elevation, GrantScope, Permissions},
+    },
+    error::CoreResult,
+    utility::spawn,
+};
+use std::{sync::Arc, time::Duration};
+
+pub async fn handle_elevate_permissions_request(
+    client: Arc<EndPointClient>,
+    req: EndPointElevatePermissionsRequest,
+) -> CoreResult<EndPointElevatePermissionsReply> {
+    let requested = req.permissions.difference(client.permissions());
+
+    // observers only watch, host user isn't bothered by them
+    if client.observed_session_id().is_some() {
+        return Ok(EndPointElevatePermissionsReply {
+            permissions: client.permissions(),
+            granted: Permissions::NONE,
+            scope: None,
+        });
+    }
+
+    if requested.is_empty() {
+        return Ok(EndPointElevatePermissionsReply {
+            permissions: client.permissions(),
+            granted: requested,
+            scope: None,
+        });
+    }
+
+    tracing::info!(permissions = ?requested.names(), "elevate permissions request");
+
+    let decision = prompt_elevation(client.endpoint_id(), requested).await;
+
+    // host user can only grant what is requested
+    let granted = decision.granted.intersection(requested);
+    if granted.is_empty() {
+        tracing::info!("elevate permissions rejected");
+        return Ok(EndPointElevatePermissionsReply {
+            permissions: client.permissions(),
+            granted,
+            scope: None,
+        });
+    }
+
+    let permissions = client.session_permissions().grant(granted, decision.scope);
+
+    tracing::info!(
+        permissions = ?permissions.names(),
+        scope = ?decision.scope,
+        "elevate permissions granted"
+    );
+
+    if let GrantScope::Temporary { secs } = decision.scope {
+        let client = Arc::downgrade(&client);
+        spawn::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(secs as u64)).await;
+
+            // the session is already closed
+            let Some(client) = client.upgrade() else {
+                return;
+            };
+
+            let permissions = client.session_permissions().revoke_temporary(granted);
+            tracing::info!(permissions = ?permissions.names(), "temporary permissions expired");
+
+            if let Err(err) = client
+                .send(&EndPointMessage::PermissionsChanged(permissions))
+                .await
+            {
+                tracing::error!(?err, "notify permissions changed failed");
+            }
+        });
+    }
+
+    Ok(EndPointElevatePermissionsReply {
+        permissions,
+        granted,
+        scope: Some(decision.scope),
+    })
+}
@@ -9,6 +9,7 @@ use crate::{
     component::fs::transfer::send_file_to_remote,
     core_error,
     error::CoreResult,
+    utility::spawn,
 };
 use std::{sync::Arc, time::Duration};
 
@@ -24,9 +25,15 @@ pub async fn handle_download_file_request(
     let meta = req.path.metadata()?;
     let size = meta.len();
 
-    tokio::spawn(async move {
+    if req.offset > size {
+        return Err(core_error!("download offset exceeds file size"));
+    }
+
+    spawn::spawn(async move {
         tokio::time::sleep(Duration::from_secs(1)).await;
-        if let Err(err) = send_file_to_remote(id.clone(), client.clone(), &req.path).await {
+        if let Err(err) =
+            send_file_to_remote(id.clone(), client.clone(), &req.path, req.offset).await
+        {
             tracing::error!(?err, "read file block failed");
             let _ = client
                 .send(&EndPointMessage::FileTransferError(
@@ -0,0 +1,18 @@
+use crate::{
+    api::endpoint::message::{EndPointFileManifestReply, EndPointFileManifestRequest},
+    component::fs::manifest::build_manifest,
+    core_error,
+    error::CoreResult,
+};
+
+pub async fn handle_file_manifest_request(
+    req: EndPointFileManifestRequest,
+) -> CoreResult<EndPointFileManifestReply> {
+    tracing::info!(path = ?req.path, "build file manifest");
+
+    let entries = tokio::task::spawn_blocking(move || build_manifest(&req.path))
+        .await
+        .map_err(|_| core_error!("build file manifest failed"))??;
+
+    Ok(EndPointFileManifestReply { entries })
+}
@@ -0,0 +1,156 @@
+use crate::{
+    api::endpoint::message::{
+        EndPointFsOperationReply, EndPointFsOperationRequest, FsEntry, FsOperation,
+    },
+    component::fs::receive_policy::{check_receive, receive_policy},
+    core_error,
+    error::CoreResult,
+};
+use std::{
+    io::{ErrorKind, SeekFrom},
+    path::{Path, PathBuf},
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+// keep the reply in a reasonable size of one message
+const MAX_READ_LEN: u32 = 4 * 1024 * 1024;
+
+pub async fn handle_fs_operation_request(
+    req: EndPointFsOperationRequest,
+) -> CoreResult<EndPointFsOperationReply> {
+    match req.op {
+        FsOperation::Metadata(path) => match tokio::fs::metadata(&path).await {
+            Ok(meta) => Ok(EndPointFsOperationReply::Metadata(Some(fs_entry(
+                path, &meta,
+            )))),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                Ok(EndPointFsOperationReply::Metadata(None))
+            }
+            Err(err) => Err(err.into()),
+        },
+        FsOperation::ReadDirectory(path) => Ok(EndPointFsOperationReply::Directory(
+            read_directory(&path).await?,
+        )),
+        FsOperation::Read { path, offset, len } => {
+            let mut file = tokio::fs::File::open(&path).await?;
+            file.seek(SeekFrom::Start(offset)).await?;
+
+            let mut data = Vec::new();
+            file.take(len.min(MAX_READ_LEN) as u64)
+                .read_to_end(&mut data)
+                .await?;
+
+            Ok(EndPointFsOperationReply::Data(data))
+        }
+        FsOperation::Write {
+            path,
+            offset,
+            data,
+            truncate,
+        } => {
+            let size = offset
+                .checked_add(data.len() as u64)
+                .ok_or(core_error!("invalid write offset"))?;
+
+            check_receive_in_place(&path, size)?;
+
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(&path)
+                .await?;
+
+            if truncate {
+                file.set_len(offset).await?;
+            }
+
+            file.seek(SeekFrom::Start(offset)).await?;
+            file.write_all(&data).await?;
+            file.flush().await?;
+
+            Ok(EndPointFsOperationReply::Done)
+        }
+        FsOperation::CreateDirectory(path) => {
+            tokio::fs::create_dir(&path).await?;
+            Ok(EndPointFsOperationReply::Done)
+        }
+        FsOperation::Remove(path) => {
+            if tokio::fs::metadata(&path).await?.is_dir() {
+                tokio::fs::remove_dir_all(&path).await?;
+            } else {
+                tokio::fs::remove_file(&path).await?;
+            }
+
+            Ok(EndPointFsOperationReply::Done)
+        }
+        FsOperation::Rename { from, to } => {
+            tracing::info!(?from, ?to, "rename by fs operation");
+
+            let meta = tokio::fs::metadata(&from).await?;
+            if meta.is_file() {
+                check_receive_in_place(&to, meta.len())?;
+            }
+
+            tokio::fs::rename(&from, &to).await?;
+            Ok(EndPointFsOperationReply::Done)
+        }
+    }
+}
+
+// the bridge writes files in place piece by piece, they can't be held in quarantine until
+// user confirms, so writes are refused while it's on
+fn check_receive_in_place(path: &Path, size: u64) -> CoreResult<()> {
+    if receive_policy().quarantine {
+        return Err(core_error!(
+            "files can't be written in place while received files are quarantined"
+        ));
+    }
+
+    let filename = path.file_name().ok_or(core_error!("invalid filename"))?;
+    check_receive(&filename.to_string_lossy(), size)
+}
+
+async fn read_directory(path: &Path) -> CoreResult<Vec<FsEntry>> {
+    #[cfg(target_os = "windows")]
+    if path == Path::new("/") {
+        return crate::component::fs::read_root_directory().map(|dir| {
+            dir.entries
+                .into_iter()
+                .map(|entry| FsEntry {
+                    path: entry.path,
+                    is_dir: true,
+                    size: 0,
+                    modified_time: 0,
+                })
+                .collect()
+        });
+    }
+
+    let mut dir = tokio::fs::read_dir(path).await?;
+    let mut entries = Vec::new();
+
+    while let Some(entry) = dir.next_entry().await? {
+        // broken links can't be visited anyway
+        let Ok(meta) = tokio::fs::metadata(entry.path()).await else {
+            continue;
+        };
+
+        entries.push(fs_entry(entry.path(), &meta));
+    }
+
+    Ok(entries)
+}
+
+fn fs_entry(path: PathBuf, meta: &std::fs::Metadata) -> FsEntry {
+    let modified_time = meta
+        .modified()
+        .map(|modified| chrono::DateTime::<chrono::Utc>::from(modified).timestamp())
+        .unwrap_or_default();
+
+    FsEntry {
+        path,
+        is_dir: meta.is_dir(),
+        size: if meta.is_dir() { 0 } else { meta.len() },
+        modified_time,
+    }
+}
@@ -1,20 +1,87 @@
 use crate::{
-    api::endpoint::message::{EndPointSendFileReply, EndPointSendFileRequest},
-    component::fs::transfer::create_file_append_session,
+    api::endpoint::{
+        client::EndPointClient,
+        message::{EndPointSendFileReply, EndPointSendFileRequest},
+    },
+    component::fs::{
+        delta::compute_signature,
+        receive_policy::{check_receive, finish_quarantine, quarantine_path},
+        staging::{reserve_staging_space, session_staging_dir},
+        transfer::{create_file_append_session, create_file_patch_session},
+    },
     core_error,
     error::CoreResult,
 };
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 pub async fn handle_send_file_request(
+    client: Arc<EndPointClient>,
     req: EndPointSendFileRequest,
 ) -> CoreResult<EndPointSendFileReply> {
-    let path = req.path.join(req.filename);
+    check_receive(&req.filename, req.size)?;
+
+    let path = if req.staged {
+        // keep the file inside the staging directory whatever the filename is
+        let filename = Path::new(&req.filename)
+            .file_name()
+            .ok_or(core_error!("invalid staged filename"))?;
+
+        let path = session_staging_dir(client.session_id())?.join(filename);
+        let staged_size = path.metadata().map_or(0, |metadata| metadata.len());
+        reserve_staging_space(req.size.saturating_sub(staged_size))?;
+        path
+    } else {
+        req.path.join(req.filename)
+    };
+
+    // the file is received into quarantine when it's on, the one at destination can't be
+    // the basis of delta transfer or be resumed
+    let quarantine_path = quarantine_path(&req.id, &path, req.size)?;
+
+    if quarantine_path.is_none() && req.delta && !req.resume && path.is_file() {
+        let signature_path = path.clone();
+        let signature = tokio::task::spawn_blocking(move || compute_signature(&signature_path))
+            .await
+            .map_err(|_| core_error!("compute file signature failed"))??;
+
+        create_file_patch_session(req.id, &path, signature.block_size, req.size).await?;
 
-    if path.exists() {
+        return Ok(EndPointSendFileReply {
+            offset: 0,
+            signature: Some(signature),
+        });
+    }
+
+    let offset = if quarantine_path.is_none() && req.resume && path.is_file() {
+        path.metadata()?.len().min(req.size)
+    } else if path.exists() {
+        discard_quarantine(&req.id, &quarantine_path);
         return Err(core_error!("file already exists"));
+    } else {
+        0
+    };
+
+    let received_path = quarantine_path.as_deref().unwrap_or(&path);
+    if let Err(err) =
+        create_file_append_session(req.id.clone(), received_path, offset, req.size).await
+    {
+        discard_quarantine(&req.id, &quarantine_path);
+        return Err(err);
     }
 
-    create_file_append_session(req.id, &path).await?;
+    Ok(EndPointSendFileReply {
+        offset,
+        signature: None,
+    })
+}
 
-    Ok(EndPointSendFileReply {})
+// only the quarantine which this request takes is discarded, the transfer id is given by
+// remote and may be the one of another transfer
+fn discard_quarantine(id: &str, quarantine_path: &Option<PathBuf>) {
+    if quarantine_path.is_some() {
+        finish_quarantine(id, false);
+    }
 }
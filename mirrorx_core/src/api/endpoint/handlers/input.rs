@@ -3,7 +3,15 @@ use crate::{
         client::EndPointClient,
         message::{EndPointInput, InputEvent, KeyboardEvent, MouseEvent},
     },
-    component::{self, desktop::monitor::Monitor, input::key::MouseKey},
+    component::{
+        self,
+        desktop::{
+            monitor::Monitor,
+            span::{resolve_span_position, span_pointer_monitor, SPAN_MONITOR_ID},
+            test_pattern::TEST_PATTERN_MONITOR_ID,
+        },
+        input::key::MouseKey,
+    },
 };
 use std::sync::Arc;
 
@@ -12,7 +20,18 @@ pub async fn handle_input(client: Arc<EndPointClient>, input_event: EndPointInpu
         match event {
             InputEvent::Mouse(event) => {
                 if let Some(monitor) = client.monitor().await {
-                    handle_mouse(&event, &monitor);
+                    // the test pattern has no pointer, the real one isn't moved by it
+                    if monitor.id == TEST_PATTERN_MONITOR_ID {
+                        continue;
+                    }
+
+                    if monitor.id == SPAN_MONITOR_ID {
+                        if let Some((event, monitor)) = resolve_span_mouse_event(event) {
+                            handle_mouse(&event, &monitor);
+                        }
+                    } else {
+                        handle_mouse(&event, &monitor);
+                    }
                 }
             }
             InputEvent::Keyboard(event) => handle_keyboard(&event),
@@ -21,6 +40,9 @@ pub async fn handle_input(client: Arc<EndPointClient>, input_event: EndPointInpu
 }
 
 pub fn handle_mouse(event: &MouseEvent, monitor: &Monitor) {
+    #[cfg(target_os = "windows")]
+    component::desktop::secure_desktop::attach_input_desktop();
+
     match event {
         MouseEvent::Up(key, x, y) => {
             let _ = component::input::mouse_up(monitor, key, *x, *y);
@@ -37,11 +59,30 @@ pub fn handle_mouse(event: &MouseEvent, monitor: &Monitor) {
     }
 }
 
+// translate mouse event from the span canvas to the member monitor
+fn resolve_span_mouse_event(event: MouseEvent) -> Option<(MouseEvent, Monitor)> {
+    match event {
+        MouseEvent::Up(key, x, y) => {
+            resolve_span_position(x, y).map(|(monitor, x, y)| (MouseEvent::Up(key, x, y), monitor))
+        }
+        MouseEvent::Down(key, x, y) => resolve_span_position(x, y)
+            .map(|(monitor, x, y)| (MouseEvent::Down(key, x, y), monitor)),
+        MouseEvent::Move(key, x, y) => resolve_span_position(x, y)
+            .map(|(monitor, x, y)| (MouseEvent::Move(key, x, y), monitor)),
+        MouseEvent::ScrollWheel(delta) => {
+            span_pointer_monitor().map(|monitor| (MouseEvent::ScrollWheel(delta), monitor))
+        }
+    }
+}
+
 pub fn handle_mouse_double_click(key: &MouseKey, x: f32, y: f32, monitor: &Monitor) {
     let _ = component::input::mouse_double_click(monitor, key, x, y);
 }
 
 pub fn handle_keyboard(event: &KeyboardEvent) {
+    #[cfg(target_os = "windows")]
+    component::desktop::secure_desktop::attach_input_desktop();
+
     match event {
         KeyboardEvent::KeyUp(key) => {
             let _ = component::input::keyboard_up(key);
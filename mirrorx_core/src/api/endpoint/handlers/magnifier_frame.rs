@@ -0,0 +1,61 @@
+use super::video_frame::request_key_frame;
+use crate::{
+    api::endpoint::{
+        message::{EndPointMagnifierFrame, MagnifierRegion},
+        EndPointID,
+    },
+    component::{frame::DesktopDecodeFrame, video_decoder::tile::TileDecoder},
+    utility::spawn,
+};
+use tokio::sync::mpsc::{Receiver, Sender};
+
+/// The magnified region of remote monitor at native resolution, the window composites it
+/// over the desktop.
+pub struct MagnifiedFrame {
+    pub region: MagnifierRegion,
+    pub frame: DesktopDecodeFrame,
+}
+
+pub fn serve_magnifier_decode(
+    id: EndPointID,
+) -> (Sender<EndPointMagnifierFrame>, Receiver<MagnifiedFrame>) {
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<EndPointMagnifierFrame>(60);
+    let (render_tx, render_rx) = tokio::sync::mpsc::channel(60);
+
+    spawn::spawn_blocking(move || {
+        tracing::info!(?id, "magnifier decode process");
+
+        let mut decoder = TileDecoder::default();
+        let mut failed = false;
+
+        while let Some(magnifier_frame) = rx.blocking_recv() {
+            match decoder.decode(&magnifier_frame.frame) {
+                Ok(frame) => {
+                    failed = false;
+
+                    let magnified_frame = MagnifiedFrame {
+                        region: magnifier_frame.region,
+                        frame,
+                    };
+
+                    if render_tx.blocking_send(magnified_frame).is_err() {
+                        tracing::info!("magnifier render channel closed");
+                        break;
+                    }
+                }
+                Err(err) => {
+                    // the decoder fails until the key frame arrives, ask for it once
+                    if !failed {
+                        tracing::warn!(?err, "skip undecodable magnifier frame");
+                        request_key_frame(id);
+                        failed = true;
+                    }
+                }
+            }
+        }
+
+        tracing::info!("magnifier decode process exit");
+    });
+
+    (tx, render_rx)
+}
@@ -0,0 +1,30 @@
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{EndPointMagnifyReply, EndPointMagnifyRequest},
+    },
+    component::video_encoder::magnifier::clamp_region,
+    error::CoreResult,
+};
+use std::sync::Arc;
+
+pub async fn handle_magnify_request(
+    client: Arc<EndPointClient>,
+    req: EndPointMagnifyRequest,
+) -> CoreResult<EndPointMagnifyReply> {
+    let (width, height) = client
+        .monitor()
+        .await
+        .map_or((0, 0), |monitor| (monitor.width, monitor.height));
+
+    let region = req
+        .region
+        .and_then(|region| clamp_region(region, width, height));
+
+    tracing::info!(?region, "switch magnifier region");
+
+    // desktop capture process encodes the region of every captured frame
+    client.set_magnifier_region(region);
+
+    Ok(EndPointMagnifyReply { region })
+}
@@ -1,9 +1,42 @@
 pub mod audio_frame;
+#[cfg(feature = "host")]
+pub mod block_local_input;
+pub mod elevate_permissions;
 pub mod error;
 pub mod fs_download_file;
+pub mod fs_file_manifest;
+pub mod fs_operation;
 pub mod fs_send_file;
 pub mod fs_visit_directory;
+#[cfg(feature = "host")]
 pub mod input;
+pub mod magnifier_frame;
+#[cfg(feature = "host")]
+pub mod magnify;
+#[cfg(feature = "host")]
 pub mod negotiate_desktop_params;
+#[cfg(feature = "host")]
 pub mod negotiate_finished;
+#[cfg(feature = "host")]
+pub mod observer;
+#[cfg(feature = "host")]
+pub mod process;
+#[cfg(feature = "host")]
+pub mod switch_audio_capture;
+#[cfg(feature = "host")]
+pub mod switch_audio_processing;
+#[cfg(feature = "host")]
+pub mod switch_host_priority;
+#[cfg(feature = "host")]
+pub mod switch_monitor;
+#[cfg(feature = "host")]
+pub mod switch_resolution;
+#[cfg(feature = "host")]
+pub mod switch_stream_quality;
+#[cfg(feature = "host")]
+pub mod system_action;
+#[cfg(feature = "host")]
+pub mod system_volume;
+#[cfg(feature = "host")]
+pub mod unattended_login;
 pub mod video_frame;
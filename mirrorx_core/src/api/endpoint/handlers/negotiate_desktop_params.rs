@@ -1,13 +1,24 @@
 use crate::{
     api::endpoint::{
         client::EndPointClient,
+        handlers::observer::negotiate_observer_params,
         message::{
-            EndPointMessage, EndPointNegotiateDesktopParamsRequest,
+            AudioSource, CursorMode, EndPointMessage, EndPointNegotiateDesktopParamsRequest,
             EndPointNegotiateDesktopParamsResponse, EndPointNegotiateVisitDesktopParams,
-            VideoCodec,
         },
     },
-    component::desktop::monitor::get_primary_monitor_params,
+    component::{
+        audio::recorder::{audio_applications, record_audio_source},
+        codec_probe::select_encoder,
+        desktop::{
+            monitor::{get_monitor_color_space, get_primary_monitor_params},
+            test_pattern::{test_pattern, test_pattern_monitor, TEST_PATTERN_MONITOR_ID},
+            SUPPORTED_CURSOR_MODES,
+        },
+        frame::ColorSpace,
+        input::layout::current_keyboard_layout,
+        video_encoder::watermark::watermark_enabled,
+    },
 };
 use std::sync::Arc;
 
@@ -15,7 +26,12 @@ pub async fn handle_negotiate_desktop_params_request(
     client: Arc<EndPointClient>,
     req: EndPointNegotiateDesktopParamsRequest,
 ) {
-    let resp = negotiate_media_params(&client, req).await;
+    let resp = match client.observed_session_id() {
+        Some(observed_session_id) => {
+            negotiate_observer_params(&client, observed_session_id, req).await
+        }
+        None => negotiate_media_params(&client, req).await,
+    };
 
     if let Err(err) = client
         .send(&EndPointMessage::NegotiateDesktopParamsResponse(resp))
@@ -30,26 +46,99 @@ pub async fn handle_negotiate_desktop_params_request(
 
 async fn negotiate_media_params(
     client: &EndPointClient,
-    _req: EndPointNegotiateDesktopParamsRequest,
+    req: EndPointNegotiateDesktopParamsRequest,
 ) -> EndPointNegotiateDesktopParamsResponse {
-    // todo: check support video and audio properties
+    // todo: check support audio properties
 
-    let primary_monitor = match get_primary_monitor_params() {
-        Ok(monitor) => monitor,
-        Err(err) => {
-            tracing::error!(?err, "get primary monitor params failed at negotiate stage");
-            return EndPointNegotiateDesktopParamsResponse::MonitorError(err.to_string());
-        }
+    // headless hosts have no monitor to query, the test pattern is drawn in the color
+    // space of its own
+    let (primary_monitor, color_space) = if let Some(pattern) = test_pattern() {
+        tracing::info!(%pattern, "negotiate test pattern as primary monitor");
+        (test_pattern_monitor(&pattern), ColorSpace::default())
+    } else {
+        let primary_monitor = match get_primary_monitor_params() {
+            Ok(monitor) => monitor,
+            Err(err) => {
+                tracing::error!(?err, "get primary monitor params failed at negotiate stage");
+                return EndPointNegotiateDesktopParamsResponse::MonitorError(err.to_string());
+            }
+        };
+
+        let color_space = match get_monitor_color_space(&primary_monitor.id) {
+            Ok(color_space) => color_space,
+            Err(err) => {
+                tracing::error!(
+                    ?err,
+                    "get primary monitor color space failed at negotiate stage"
+                );
+                return EndPointNegotiateDesktopParamsResponse::MonitorError(err.to_string());
+            }
+        };
+
+        (primary_monitor, color_space)
+    };
+
+    // the first codec which viewer prefers and an encoder takes the monitor in
+    let Some(encoder) = req
+        .video_codecs
+        .iter()
+        .find_map(|codec| select_encoder(codec, primary_monitor.width, primary_monitor.height))
+    else {
+        tracing::error!(codecs = ?req.video_codecs, "no encoder supports requested codecs");
+        return EndPointNegotiateDesktopParamsResponse::VideoError(String::from(
+            "no encoder supports requested codecs",
+        ));
+    };
+    tracing::info!(?encoder, "negotiate video encoder");
+
+    // the test pattern has no cursor to send separately
+    let cursor_mode = if primary_monitor.id == TEST_PATTERN_MONITOR_ID {
+        CursorMode::Composited
+    } else {
+        req.cursor_modes
+            .into_iter()
+            .find(|mode| SUPPORTED_CURSOR_MODES.contains(mode))
+            .unwrap_or(CursorMode::Composited)
+    };
+
+    // tell the viewer what it's going to hear, e.g. only microphone on macOS before 13
+    let audio_source = record_audio_source();
+    tracing::info!(?audio_source, "negotiate audio source");
+
+    let audio_applications = if audio_source == AudioSource::SystemAudio {
+        tokio::task::spawn_blocking(audio_applications)
+            .await
+            .unwrap_or_default()
+    } else {
+        Vec::new()
     };
 
     client.set_monitor(primary_monitor.clone()).await;
+    client.set_video_codec(encoder.codec.clone());
+
+    // decided once per negotiation, capture process and viewer follow the same value
+    let watermark = watermark_enabled();
+    client.set_watermark_enabled(watermark);
+
+    // viewer warns its user when it types on another layout
+    let keyboard_layout = current_keyboard_layout();
+    tracing::info!(?keyboard_layout, "negotiate keyboard layout");
 
     let params = EndPointNegotiateVisitDesktopParams {
-        video_codec: VideoCodec::H264,
+        video_codec: encoder.codec,
         os_type: String::from(""),
         os_version: String::from(""),
         primary_monitor,
+        color_space,
+        cursor_mode,
+        audio_source,
+        audio_applications,
+        watermark,
+        keyboard_layout,
     };
 
+    // observers of the session are told the same
+    client.set_negotiated_desktop_params(params.clone());
+
     EndPointNegotiateDesktopParamsResponse::Params(params)
 }
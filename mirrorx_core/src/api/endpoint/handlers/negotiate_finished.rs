@@ -1,15 +1,68 @@
 use crate::{
-    api::endpoint::{client::EndPointClient, message::EndPointMessage},
+    api::endpoint::{
+        client::EndPointClient,
+        handlers::observer::attach_observer,
+        message::{
+            AudioCaptureTarget, AudioSource, CursorMode, EndPointCursor, EndPointMessage,
+            EndPointNegotiateFinishedRequest, EndPointPowerSaving, StreamQuality, VideoCodec,
+        },
+        warm_start,
+    },
     component::{
-        audio::{encoder::AudioEncoder, recorder::new_record_stream_and_rx},
-        desktop::{monitor::get_active_monitors, Duplicator},
-        video_encoder::{config::*, encoder::VideoEncoder},
+        audio::{
+            encoder::AudioEncoder,
+            processor::AudioProcessor,
+            recorder::{new_record_stream_and_rx, record_audio_source},
+        },
+        codec_probe::select_encoder,
+        desktop::{
+            curtain,
+            idle::IdleDetector,
+            monitor::get_active_monitors,
+            span::{SpanDuplicator, SPAN_MONITOR_ID},
+            test_pattern::{test_pattern, TestPatternDuplicator, TEST_PATTERN_MONITOR_ID},
+            Duplicator,
+        },
+        frame::{AudioEncodeFrame, DesktopEncodeFrame},
+        governor::{
+            self,
+            priority::{ThreadTuning, HOST_PRIORITY_MAX_FRAME_RATE},
+        },
+        power::{get_power_state, power_saving_enabled, PowerState},
+        session_profile::{capture_queue_size, take_latest_frame},
+        session_stats,
+        video_encoder::{
+            config::probed::ProbedEncoderConfig,
+            encoder::{VideoEncoder, WarmEncodeContext},
+            filter::filter_frame,
+            magnifier::Magnifier,
+            tile::TileEncoder,
+            watermark::Watermark,
+        },
     },
-    error::CoreError,
+    core_error,
+    error::{CoreError, CoreResult},
+    utility::spawn,
 };
-use cpal::traits::StreamTrait;
 use scopeguard::defer;
-use std::sync::Arc;
+use std::{
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+#[cfg(target_os = "windows")]
+use crate::{
+    api::endpoint::message::CaptureInterruption,
+    component::desktop::secure_desktop::{self, InputDesktop},
+};
+
+const POWER_STATE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[cfg(target_os = "windows")]
+const INPUT_DESKTOP_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 pub struct NegotiateFinishedRequest {
     pub active_device_id: i64,
@@ -18,32 +71,48 @@ pub struct NegotiateFinishedRequest {
     pub texture_id: i64,
 }
 
-pub fn handle_negotiate_finished_request(client: Arc<EndPointClient>) {
-    spawn_desktop_capture_and_encode_process(client.clone());
-    spawn_audio_capture_and_encode_process(client);
+pub fn handle_negotiate_finished_request(
+    client: Arc<EndPointClient>,
+    req: EndPointNegotiateFinishedRequest,
+) {
+    // observers are sent the media of the observed session, nothing is captured for them
+    if let Some(observed_session_id) = client.observed_session_id() {
+        let observed_session_id = observed_session_id.to_string();
+        attach_observer(client, &observed_session_id);
+        return;
+    }
+
+    // capture and encode process is created with them
+    client.set_gop_policy(req.gop_policy.clamped());
+    client.set_session_profile(req.session_profile);
+
+    let max_frame_rate = spawn_power_monitor_process(client.clone());
+    spawn_desktop_capture_and_encode_process(client.clone(), req.cursor_mode, max_frame_rate);
+    client.set_audio_processing_enabled(req.audio_processing);
+    // active endpoint switches to savings modes after negotiation
+    client.set_stream_quality(StreamQuality::default());
+    client.set_host_priority(false);
+    client.set_magnifier_region(None);
+    spawn_audio_capture_and_encode_process(client, req.audio_capture_target);
 }
 
 #[cfg(target_os = "macos")]
-fn spawn_desktop_capture_and_encode_process(client: Arc<EndPointClient>) {
-    let (capture_frame_tx, mut capture_frame_rx) = tokio::sync::mpsc::channel(180);
-
-    tokio::task::spawn_blocking(move || {
-        tracing::info_span!("desktop_capture_and_encode_process", client = ?client);
+fn spawn_desktop_capture_and_encode_process(
+    client: Arc<EndPointClient>,
+    _: CursorMode,
+    max_frame_rate: Arc<AtomicU8>,
+) {
+    spawn::spawn_blocking(move || {
+        let _span = tracing::info_span!("desktop_capture_and_encode_process").entered();
 
         defer! {
+            session_stats::remove_session(&client.endpoint_id());
             tracing::info!("desktop capture process exit");
         }
 
-        let monitors = match get_active_monitors(false) {
-            Ok(params) => params,
-            Err(err) => {
-                tracing::error!(?err, "get_primary_monitor_params failed");
-                return;
-            }
-        };
+        let mut tuning = ThreadTuning::apply(client.host_priority());
 
-        let mut encoder = match VideoEncoder::new(libx264::Libx264Config::default(), client.clone())
-        {
+        let mut encoder = match new_video_encoder(&client) {
             Ok(encoder) => encoder,
             Err(err) => {
                 tracing::error!(?err, "initialize encoder failed");
@@ -51,59 +120,98 @@ fn spawn_desktop_capture_and_encode_process(client: Arc<EndPointClient>) {
             }
         };
 
-        let primary_monitor = monitors.iter().find(|monitor| monitor.is_primary);
+        if let Some(warm_context) = warm_start::take_encode_context() {
+            encoder.adopt_warm_context(warm_context);
+        }
 
-        let (duplicator, monitor_id) = match Duplicator::new(
-            primary_monitor.map(|monitor| monitor.id.to_owned()),
-            capture_frame_tx,
-        ) {
-            Ok(duplicator) => duplicator,
-            Err(err) => {
-                tracing::error!(?err, "initialize encoder failed");
+        // recreate duplicator every time the selected monitor is switched
+        loop {
+            let (duplicator, monitor_id, mut capture_frame_rx) =
+                match DesktopDuplicator::new(&client) {
+                    Ok(duplicator) => duplicator,
+                    Err(err) => {
+                        tracing::error!(?err, "initialize duplicator failed");
+                        return;
+                    }
+                };
+
+            tracing::info!(?monitor_id, "select monitor");
+
+            if let Err(err) = duplicator.start() {
+                tracing::error!(?err, "desktop capture process start failed");
                 return;
             }
-        };
 
-        let select_monitor = match monitors
-            .into_iter()
-            .find(|monitor| monitor.id == monitor_id)
-        {
-            Some(monitor) => monitor,
-            None => {
-                tracing::error!("can't find selected monitor");
-                return;
+            defer! {
+                let _ = duplicator.stop();
             }
-        };
 
-        tracing::info!(?select_monitor.width,?select_monitor.height,"select monitor");
+            let mut idle_detector = IdleDetector::default();
+            let mut watermark = client
+                .watermark_enabled()
+                .then(|| Watermark::new(&client.endpoint_id()));
+            let mut magnifier = Magnifier::new(client.clone());
 
-        // PASSIVE_ENDPOINTS_MONITORS.insert(client.id, select_monitor);
+            loop {
+                if monitor_switched(&client, &monitor_id) {
+                    break;
+                }
 
-        if let Err(err) = duplicator.start() {
-            tracing::error!(?err, "desktop capture process start failed");
-            return;
-        }
+                match capture_frame_rx.blocking_recv() {
+                    Some(capture_frame) => {
+                        let mut capture_frame = take_latest_frame(
+                            client.session_profile(),
+                            &mut capture_frame_rx,
+                            capture_frame,
+                        );
+
+                        // host screen may be visible when curtain is broken, drop the
+                        // frames until the watchdog verifies it again
+                        if !curtain::transmission_allowed() {
+                            continue;
+                        }
 
-        defer! {
-            let _ = duplicator.stop();
-        }
+                        let quality = client.stream_quality();
+                        let host_priority = client.host_priority();
+                        tuning.update(host_priority);
+
+                        let idle_frame_rate = idle_detector.observe(&capture_frame);
+                        apply_encoder_limits(
+                            &mut encoder,
+                            &max_frame_rate,
+                            quality,
+                            host_priority,
+                            idle_frame_rate,
+                        );
+                        filter_frame(&mut capture_frame, quality);
+
+                        if client.take_key_frame_request() {
+                            encoder.force_key_frame();
+                            magnifier.force_key_frame();
+                        }
 
-        loop {
-            match capture_frame_rx.blocking_recv() {
-                Some(capture_frame) => {
-                    if let Err(err) = encoder.encode(capture_frame) {
-                        if let CoreError::OutgoingMessageChannelDisconnect = err {
-                            tracing::info!("desktop capture and encode process exit");
+                        if let Some(watermark) = watermark.as_mut() {
+                            watermark.apply(&mut capture_frame);
+                        }
+
+                        // the region is cropped from the frame which the viewer sees
+                        if let Err(err) = magnifier.encode(&capture_frame) {
+                            tracing::warn!(?err, "magnifier encode failed");
+                        }
+
+                        if let Err(err) = encoder.encode(capture_frame) {
+                            if let CoreError::OutgoingMessageChannelDisconnect = err {
+                                tracing::info!("desktop capture and encode process exit");
+                            } else {
+                                tracing::error!("video encode failed");
+                            }
                             return;
-                        } else {
-                            tracing::error!("video encode failed");
-                            break;
                         }
                     }
-                }
-                None => {
-                    tracing::error!("capture frame rx recv error");
-                    break;
+                    None => {
+                        tracing::error!("capture frame rx recv error");
+                        return;
+                    }
                 }
             }
         }
@@ -111,26 +219,44 @@ fn spawn_desktop_capture_and_encode_process(client: Arc<EndPointClient>) {
 }
 
 #[cfg(target_os = "windows")]
-fn spawn_desktop_capture_and_encode_process(client: Arc<EndPointClient>) {
-    let monitors = match get_active_monitors(false) {
-        Ok(params) => params,
-        Err(err) => {
-            tracing::error!(?err, "get_active_monitors failed");
-            return;
-        }
+fn spawn_desktop_capture_and_encode_process(
+    client: Arc<EndPointClient>,
+    cursor_mode: CursorMode,
+    max_frame_rate: Arc<AtomicU8>,
+) {
+    let (capture_frame_tx, mut capture_frame_rx) =
+        tokio::sync::mpsc::channel(capture_queue_size(client.session_profile()));
+
+    let cursor_tx = if cursor_mode == CursorMode::Separate {
+        let (cursor_tx, mut cursor_rx) = tokio::sync::mpsc::channel(180);
+        let cursor_client = client.clone();
+
+        spawn::spawn(async move {
+            while let Some(cursor) = cursor_rx.recv().await {
+                if let Err(err) = cursor_client.send(&EndPointMessage::Cursor(cursor)).await {
+                    tracing::error!(?err, "send cursor failed");
+                    return;
+                }
+            }
+        });
+
+        Some(cursor_tx)
+    } else {
+        None
     };
 
-    let (capture_frame_tx, mut capture_frame_rx) = tokio::sync::mpsc::channel(180);
+    spawn_input_desktop_monitor_process(client.clone());
 
-    tokio::task::spawn_blocking(move || {
+    let capture_client = client.clone();
+    spawn::spawn_blocking(move || {
         defer! {
             tracing::info!( "desktop capture process exit");
         }
 
-        let primary_monitor = monitors.iter().find(|monitor| monitor.is_primary);
+        let mut tuning = ThreadTuning::apply(capture_client.host_priority());
 
-        let (mut duplicator, _) =
-            match Duplicator::new(primary_monitor.map(|monitor| monitor.id.to_owned())) {
+        let (mut duplicator, mut monitor_id) =
+            match DesktopDuplicator::new(&capture_client, cursor_tx.clone()) {
                 Ok(duplicator) => duplicator,
                 Err(err) => {
                     tracing::error!(?err, "initialize encoder failed");
@@ -138,13 +264,60 @@ fn spawn_desktop_capture_and_encode_process(client: Arc<EndPointClient>) {
                 }
             };
 
+        let mut capture_interruption = None;
+
         loop {
+            tuning.update(capture_client.host_priority());
+
+            if monitor_switched(&capture_client, &monitor_id) {
+                // release the old duplicator before duplicating the new monitor
+                drop(duplicator);
+
+                (duplicator, monitor_id) =
+                    match DesktopDuplicator::new(&capture_client, cursor_tx.clone()) {
+                        Ok(duplicator) => duplicator,
+                        Err(err) => {
+                            tracing::error!(?err, "switch duplicator monitor failed");
+                            return;
+                        }
+                    };
+
+                tracing::info!(?monitor_id, "select monitor");
+            }
+
             match duplicator.capture() {
                 Ok(capture_frame) => {
+                    // viewer explains the black or slow stream instead of leaving users
+                    // guessing, like when protected content is shown
+                    let interruption = duplicator.capture_interruption();
+                    if interruption != capture_interruption {
+                        tracing::info!(?interruption, "desktop capture interruption changed");
+                        capture_interruption = interruption;
+
+                        let message = EndPointMessage::CaptureInterrupted(interruption);
+                        if let Err(err) = capture_client.blocking_send(&message) {
+                            tracing::error!(?err, "send capture interruption failed");
+                        }
+                    }
+
                     if capture_frame_tx.blocking_send(capture_frame).is_err() {
                         return;
                     }
                 }
+                Err(err) if Duplicator::is_access_lost(&err) => {
+                    // input desktop may be switched to the secure desktop of UAC prompt,
+                    // duplicate the new input desktop once it's reachable
+                    drop(duplicator);
+
+                    (duplicator, monitor_id) = match duplicate_input_desktop(
+                        &capture_client,
+                        cursor_tx.clone(),
+                        &capture_frame_tx,
+                    ) {
+                        Some(duplicator) => duplicator,
+                        None => return,
+                    };
+                }
                 Err(err) => {
                     tracing::error!(?err, "desktop duplicator capture failed");
                     break;
@@ -153,24 +326,79 @@ fn spawn_desktop_capture_and_encode_process(client: Arc<EndPointClient>) {
         }
     });
 
-    tokio::task::spawn_blocking(move || {
+    spawn::spawn_blocking(move || {
+        defer! {
+            session_stats::remove_session(&client.endpoint_id());
+        }
+
+        let mut tuning = ThreadTuning::apply(client.host_priority());
+
         loop {
             // defer! {
             //     tracing::info!(?active_device_id, ?passive_device_id, "video encode process exit");
             // }
 
-            let mut encoder =
-                match VideoEncoder::new(libx264::Libx264Config::default(), client.clone()) {
-                    Ok(encoder) => encoder,
-                    Err(err) => {
-                        tracing::error!(?err, "video encoder initialize failed");
-                        return;
-                    }
-                };
+            let mut encoder = match new_video_encoder(&client) {
+                Ok(encoder) => encoder,
+                Err(err) => {
+                    tracing::error!(?err, "video encoder initialize failed");
+                    return;
+                }
+            };
+
+            if let Some(warm_context) = warm_start::take_encode_context() {
+                encoder.adopt_warm_context(warm_context);
+            }
+
+            let mut idle_detector = IdleDetector::default();
+            let mut watermark = client
+                .watermark_enabled()
+                .then(|| Watermark::new(&client.endpoint_id()));
+            let mut magnifier = Magnifier::new(client.clone());
 
             loop {
                 match capture_frame_rx.blocking_recv() {
                     Some(capture_frame) => {
+                        let mut capture_frame = take_latest_frame(
+                            client.session_profile(),
+                            &mut capture_frame_rx,
+                            capture_frame,
+                        );
+
+                        // host screen may be visible when curtain is broken, drop the
+                        // frames until the watchdog verifies it again
+                        if !curtain::transmission_allowed() {
+                            continue;
+                        }
+
+                        let quality = client.stream_quality();
+                        let host_priority = client.host_priority();
+                        tuning.update(host_priority);
+
+                        let idle_frame_rate = idle_detector.observe(&capture_frame);
+                        apply_encoder_limits(
+                            &mut encoder,
+                            &max_frame_rate,
+                            quality,
+                            host_priority,
+                            idle_frame_rate,
+                        );
+                        filter_frame(&mut capture_frame, quality);
+
+                        if client.take_key_frame_request() {
+                            encoder.force_key_frame();
+                            magnifier.force_key_frame();
+                        }
+
+                        if let Some(watermark) = watermark.as_mut() {
+                            watermark.apply(&mut capture_frame);
+                        }
+
+                        // the region is cropped from the frame which the viewer sees
+                        if let Err(err) = magnifier.encode(&capture_frame) {
+                            tracing::warn!(?err, "magnifier encode failed");
+                        }
+
                         if let Err(err) = encoder.encode(capture_frame) {
                             if let CoreError::OutgoingMessageChannelDisconnect = err {
                                 tracing::info!("desktop capture and encode process exit");
@@ -190,66 +418,504 @@ fn spawn_desktop_capture_and_encode_process(client: Arc<EndPointClient>) {
     });
 }
 
-fn spawn_audio_capture_and_encode_process(client: Arc<EndPointClient>) {
-    // let mut exit_rx = client.close_receiver();
+#[cfg(target_os = "macos")]
+enum DesktopDuplicator {
+    Monitor(Duplicator),
+    Span(SpanDuplicator),
+    TestPattern(TestPatternDuplicator),
+}
 
-    tokio::task::spawn_blocking(move || loop {
-        // let Err(async_broadcast::TryRecvError::Empty) = exit_rx.try_recv() else {
-        //     tracing::info!("receive exit signal, exit");
-        //     return;
-        // };
+#[cfg(target_os = "macos")]
+impl DesktopDuplicator {
+    fn new(
+        client: &EndPointClient,
+    ) -> CoreResult<(
+        Self,
+        String,
+        tokio::sync::mpsc::Receiver<DesktopEncodeFrame>,
+    )> {
+        let monitor_id = client
+            .blocking_monitor()
+            .map(|monitor| monitor.id.to_owned());
+
+        let (capture_frame_tx, capture_frame_rx) =
+            tokio::sync::mpsc::channel(capture_queue_size(client.session_profile()));
+
+        // the pattern disabled during the session keeps streaming with the default one
+        // until the viewer switches monitor
+        if monitor_id.as_deref() == Some(TEST_PATTERN_MONITOR_ID) {
+            let (duplicator, monitor_id) =
+                TestPatternDuplicator::new(test_pattern().unwrap_or_default(), capture_frame_tx);
+            return Ok((
+                DesktopDuplicator::TestPattern(duplicator),
+                monitor_id,
+                capture_frame_rx,
+            ));
+        }
 
-        let (stream, mut rx) = match new_record_stream_and_rx() {
-            Ok((stream, rx)) => (stream, rx),
-            Err(err) => {
-                tracing::error!(?err, "initialize audio record stream failed");
+        if monitor_id.as_deref() != Some(SPAN_MONITOR_ID) {
+            if let Some((duplicator, monitor_id, capture_frame_rx)) =
+                warm_start::take_duplicator(monitor_id.as_deref())
+            {
+                tracing::info!("use prewarmed duplicator");
+                return Ok((
+                    DesktopDuplicator::Monitor(duplicator),
+                    monitor_id,
+                    capture_frame_rx,
+                ));
+            }
+        }
+
+        if monitor_id.as_deref() == Some(SPAN_MONITOR_ID) {
+            let (duplicator, monitor_id) =
+                SpanDuplicator::new(get_active_monitors(false)?, capture_frame_tx)?;
+            Ok((
+                DesktopDuplicator::Span(duplicator),
+                monitor_id,
+                capture_frame_rx,
+            ))
+        } else {
+            let (duplicator, monitor_id) = Duplicator::new(monitor_id, capture_frame_tx)?;
+            Ok((
+                DesktopDuplicator::Monitor(duplicator),
+                monitor_id,
+                capture_frame_rx,
+            ))
+        }
+    }
+
+    fn start(&self) -> CoreResult<()> {
+        match self {
+            DesktopDuplicator::Monitor(duplicator) => duplicator.start(),
+            DesktopDuplicator::Span(duplicator) => duplicator.start(),
+            DesktopDuplicator::TestPattern(duplicator) => duplicator.start(),
+        }
+    }
+
+    fn stop(&self) -> CoreResult<()> {
+        match self {
+            DesktopDuplicator::Monitor(duplicator) => duplicator.stop(),
+            DesktopDuplicator::Span(duplicator) => duplicator.stop(),
+            DesktopDuplicator::TestPattern(duplicator) => duplicator.stop(),
+        }
+    }
+}
+
+// wait until the input desktop can be duplicated, returns None when session is closed
+#[cfg(target_os = "windows")]
+fn duplicate_input_desktop(
+    client: &EndPointClient,
+    cursor_tx: Option<tokio::sync::mpsc::Sender<EndPointCursor>>,
+    capture_frame_tx: &tokio::sync::mpsc::Sender<DesktopEncodeFrame>,
+) -> Option<(DesktopDuplicator, String)> {
+    while !capture_frame_tx.is_closed() {
+        if secure_desktop::current_input_desktop() != InputDesktop::SecureInaccessible {
+            match DesktopDuplicator::new(client, cursor_tx.clone()) {
+                Ok(duplicator) => return Some(duplicator),
+                Err(err) => tracing::warn!(?err, "duplicate input desktop failed, retry later"),
+            }
+        }
+
+        std::thread::sleep(INPUT_DESKTOP_POLL_INTERVAL);
+    }
+
+    None
+}
+
+// tell active endpoint whenever its input can't reach the input desktop, like when UAC
+// prompt is shown on the secure desktop which the host agent has no access to
+#[cfg(target_os = "windows")]
+fn spawn_input_desktop_monitor_process(client: Arc<EndPointClient>) {
+    spawn::spawn(async move {
+        let mut interval = tokio::time::interval(INPUT_DESKTOP_POLL_INTERVAL);
+        let mut last_input_desktop = InputDesktop::Default;
+
+        loop {
+            interval.tick().await;
+
+            let input_desktop = secure_desktop::current_input_desktop();
+            if input_desktop == last_input_desktop {
                 continue;
             }
-        };
 
-        if let Err(err) = stream.play() {
-            tracing::error!(?err, "play audio stream failed");
-            continue;
+            tracing::info!(?input_desktop, "input desktop switched");
+
+            secure_desktop::notify_input_desktop_switched();
+            last_input_desktop = input_desktop;
+
+            let message = EndPointMessage::InputBlocked(input_desktop.input_block_reason());
+            if client.send(&message).await.is_err() {
+                tracing::info!("input desktop monitor process exit");
+                return;
+            }
+        }
+    });
+}
+
+#[cfg(target_os = "windows")]
+enum DesktopDuplicator {
+    Monitor(Duplicator),
+    Span(SpanDuplicator),
+    TestPattern(TestPatternDuplicator),
+}
+
+#[cfg(target_os = "windows")]
+impl DesktopDuplicator {
+    fn new(
+        client: &EndPointClient,
+        cursor_tx: Option<tokio::sync::mpsc::Sender<EndPointCursor>>,
+    ) -> CoreResult<(Self, String)> {
+        let monitor_id = client
+            .blocking_monitor()
+            .map(|monitor| monitor.id.to_owned());
+
+        if monitor_id.as_deref() == Some(TEST_PATTERN_MONITOR_ID) {
+            let (duplicator, monitor_id) =
+                TestPatternDuplicator::new(test_pattern().unwrap_or_default());
+            Ok((DesktopDuplicator::TestPattern(duplicator), monitor_id))
+        } else if monitor_id.as_deref() == Some(SPAN_MONITOR_ID) {
+            // cursor position can't be translated to span canvas, so it's always composited
+            let (duplicator, monitor_id) = SpanDuplicator::new(get_active_monitors(false)?)?;
+            Ok((DesktopDuplicator::Span(duplicator), monitor_id))
+        } else {
+            let (mut duplicator, monitor_id) =
+                match warm_start::take_duplicator(monitor_id.as_deref()) {
+                    Some(duplicator) => {
+                        tracing::info!("use prewarmed duplicator");
+                        duplicator
+                    }
+                    None => Duplicator::new(monitor_id)?,
+                };
+            duplicator.set_cursor_tx(cursor_tx);
+            Ok((DesktopDuplicator::Monitor(duplicator), monitor_id))
         }
+    }
+
+    fn capture(&mut self) -> CoreResult<DesktopEncodeFrame> {
+        match self {
+            DesktopDuplicator::Monitor(duplicator) => duplicator.capture(),
+            DesktopDuplicator::Span(duplicator) => duplicator.capture(),
+            DesktopDuplicator::TestPattern(duplicator) => duplicator.capture(),
+        }
+    }
+
+    fn capture_interruption(&self) -> Option<CaptureInterruption> {
+        match self {
+            DesktopDuplicator::Monitor(duplicator) => duplicator.capture_interruption(),
+            DesktopDuplicator::Span(_) | DesktopDuplicator::TestPattern(_) => None,
+        }
+    }
+}
+
+// poll power state of host and limit the capture frame rate to save power, the process
+// exits after the capture process exits and drops its handle of max frame rate
+fn spawn_power_monitor_process(client: Arc<EndPointClient>) -> Arc<AtomicU8> {
+    // zero means unlimited
+    let max_frame_rate = Arc::new(AtomicU8::new(0));
+    let max_frame_rate_clone = max_frame_rate.clone();
+
+    spawn::spawn(async move {
+        let mut interval = tokio::time::interval(POWER_STATE_POLL_INTERVAL);
+        let mut last_power_state = PowerState::default();
 
         loop {
-            let mut audio_encoder = AudioEncoder::default();
+            interval.tick().await;
 
-            loop {
-                // let Err(async_broadcast::TryRecvError::Empty) = exit_rx.try_recv() else {
-                //     tracing::info!("receive exit signal, exit");
-                //     return;
-                // };
-
-                match rx.blocking_recv() {
-                    Some(audio_frame) => match audio_encoder.encode(audio_frame) {
-                        Ok(frame) => {
-                            if let Err(err) =
-                                client.blocking_send(&EndPointMessage::AudioFrame(frame))
+            if Arc::strong_count(&max_frame_rate_clone) == 1 {
+                break;
+            }
+
+            let power_state = if power_saving_enabled() {
+                match get_power_state() {
+                    Ok(power_state) => power_state,
+                    Err(err) => {
+                        tracing::error!(?err, "get power state failed");
+                        continue;
+                    }
+                }
+            } else {
+                PowerState::default()
+            };
+
+            if power_state == last_power_state {
+                continue;
+            }
+
+            last_power_state = power_state;
+
+            let frame_rate = power_state.max_frame_rate();
+            max_frame_rate_clone.store(frame_rate.unwrap_or(0), Ordering::SeqCst);
+
+            tracing::info!(?power_state, ?frame_rate, "power state changed");
+
+            if let Err(err) = client
+                .send(&EndPointMessage::PowerSaving(EndPointPowerSaving {
+                    on_battery: power_state.on_battery,
+                    power_saver: power_state.power_saver,
+                    max_frame_rate: frame_rate,
+                }))
+                .await
+            {
+                tracing::error!(?err, "send power saving message failed");
+                break;
+            }
+        }
+
+        tracing::info!("power monitor process exit");
+    });
+
+    max_frame_rate
+}
+
+enum SessionEncoder {
+    Video(VideoEncoder<ProbedEncoderConfig>),
+    Tile(TileEncoder),
+}
+
+impl SessionEncoder {
+    fn adopt_warm_context(&mut self, warm_context: WarmEncodeContext) {
+        // the tile codec opens nothing, the warm context is freed
+        if let SessionEncoder::Video(encoder) = self {
+            encoder.adopt_warm_context(warm_context);
+        }
+    }
+
+    fn set_max_frame_rate(&mut self, max_frame_rate: Option<u8>) {
+        match self {
+            SessionEncoder::Video(encoder) => encoder.set_max_frame_rate(max_frame_rate),
+            SessionEncoder::Tile(encoder) => encoder.set_max_frame_rate(max_frame_rate),
+        }
+    }
+
+    fn set_bit_rate_scale(&mut self, bit_rate_scale: f32) {
+        // tiles are lossless, there's no bit rate to scale
+        if let SessionEncoder::Video(encoder) = self {
+            encoder.set_bit_rate_scale(bit_rate_scale);
+        }
+    }
+
+    fn force_key_frame(&mut self) {
+        match self {
+            SessionEncoder::Video(encoder) => encoder.force_key_frame(),
+            SessionEncoder::Tile(encoder) => encoder.force_key_frame(),
+        }
+    }
+
+    fn encode(&mut self, capture_frame: DesktopEncodeFrame) -> CoreResult<()> {
+        match self {
+            SessionEncoder::Video(encoder) => encoder.encode(capture_frame),
+            SessionEncoder::Tile(encoder) => encoder.encode(capture_frame),
+        }
+    }
+}
+
+// the preferred encoder of negotiated codec which takes the selected monitor
+fn new_video_encoder(client: &Arc<EndPointClient>) -> CoreResult<SessionEncoder> {
+    let codec = client.video_codec();
+    if codec == VideoCodec::TileCache {
+        tracing::info!("select tile cache encoder");
+        return Ok(SessionEncoder::Tile(TileEncoder::new(client.clone())));
+    }
+
+    let (width, height) = client
+        .blocking_monitor()
+        .map_or((0, 0), |monitor| (monitor.width, monitor.height));
+
+    let encoder = select_encoder(&codec, width, height).ok_or(core_error!(
+        "no encoder supports {:?} at {}x{}",
+        codec,
+        width,
+        height
+    ))?;
+
+    tracing::info!(?encoder, "select video encoder");
+
+    let mut encoder =
+        VideoEncoder::new(ProbedEncoderConfig::from_support(&encoder)?, client.clone())?;
+    encoder.set_gop_policy(client.gop_policy());
+    encoder.set_session_profile(client.session_profile());
+
+    Ok(SessionEncoder::Video(encoder))
+}
+
+// combine the limits of power state, host resource governor, stream quality, host
+// priority mode and idle desktop, the strictest one wins
+fn apply_encoder_limits(
+    encoder: &mut SessionEncoder,
+    power_max_frame_rate: &AtomicU8,
+    quality: StreamQuality,
+    host_priority: bool,
+    idle_frame_rate: Option<u8>,
+) {
+    let max_frame_rate = [
+        load_max_frame_rate(power_max_frame_rate),
+        governor::max_frame_rate(),
+        quality.max_frame_rate(),
+        host_priority.then_some(HOST_PRIORITY_MAX_FRAME_RATE),
+        idle_frame_rate,
+    ]
+    .into_iter()
+    .flatten()
+    .min();
+
+    encoder.set_max_frame_rate(max_frame_rate);
+    encoder.set_bit_rate_scale(governor::bit_rate_scale());
+}
+
+fn load_max_frame_rate(max_frame_rate: &AtomicU8) -> Option<u8> {
+    match max_frame_rate.load(Ordering::SeqCst) {
+        0 => None,
+        frame_rate => Some(frame_rate),
+    }
+}
+
+fn monitor_switched(client: &EndPointClient, monitor_id: &str) -> bool {
+    client
+        .blocking_monitor()
+        .map_or(false, |monitor| monitor.id != monitor_id)
+}
+
+fn spawn_audio_capture_and_encode_process(
+    client: Arc<EndPointClient>,
+    audio_capture_target: AudioCaptureTarget,
+) {
+    // let mut exit_rx = client.close_receiver();
+
+    let audio_source = record_audio_source();
+    if audio_source == AudioSource::Unavailable {
+        tracing::warn!("no audio source to record, session goes on without audio");
+        return;
+    }
+
+    spawn::spawn_blocking(move || {
+        client.blocking_set_audio_capture_target(audio_capture_target);
+
+        loop {
+            // let Err(async_broadcast::TryRecvError::Empty) = exit_rx.try_recv() else {
+            //     tracing::info!("receive exit signal, exit");
+            //     return;
+            // };
+
+            let audio_capture_target = client.blocking_audio_capture_target();
+
+            let (stream, mut rx) =
+                match new_record_stream_and_rx(audio_source, audio_capture_target) {
+                    Ok((stream, rx)) => (stream, rx),
+                    Err(err) => {
+                        tracing::error!(?err, "initialize audio record stream failed");
+                        fallback_to_system_audio(&client, audio_capture_target);
+                        continue;
+                    }
+                };
+
+            if let Err(err) = stream.play() {
+                tracing::error!(?err, "play audio stream failed");
+                fallback_to_system_audio(&client, audio_capture_target);
+                continue;
+            }
+
+            let mut audio_processor = None;
+
+            'record: loop {
+                let mut audio_encoder = AudioEncoder::default();
+
+                loop {
+                    // let Err(async_broadcast::TryRecvError::Empty) = exit_rx.try_recv() else {
+                    //     tracing::info!("receive exit signal, exit");
+                    //     return;
+                    // };
+
+                    // recreate record stream for the target which viewer switched to
+                    if client.blocking_audio_capture_target() != audio_capture_target {
+                        break 'record;
+                    }
+
+                    match rx.blocking_recv() {
+                        Some(mut audio_frame) => {
+                            // system audio has no echo of the room
+                            if audio_source == AudioSource::Microphone
+                                && client.audio_processing_enabled()
                             {
-                                match err {
-                                    CoreError::OutgoingMessageChannelDisconnect => {
-                                        tracing::info!("audio encode process exit");
-                                        return;
-                                    }
-                                    _ => {
-                                        tracing::error!(?err, "audio encode failed");
+                                process_microphone_frame(
+                                    &client,
+                                    &mut audio_processor,
+                                    &mut audio_frame,
+                                );
+                            }
+
+                            match audio_encoder.encode(audio_frame) {
+                                Ok(frame) => {
+                                    if let Err(err) =
+                                        client.blocking_send(&EndPointMessage::AudioFrame(frame))
+                                    {
+                                        match err {
+                                            CoreError::OutgoingMessageChannelDisconnect => {
+                                                tracing::info!("audio encode process exit");
+                                                return;
+                                            }
+                                            _ => {
+                                                tracing::error!(?err, "audio encode failed");
+                                            }
+                                        }
                                     }
                                 }
+
+                                Err(err) => {
+                                    tracing::error!(?err, "audio encode failed");
+                                    break;
+                                }
                             }
                         }
-
-                        Err(err) => {
-                            tracing::error!(?err, "audio encode failed");
-                            break;
+                        None => {
+                            tracing::error!("audio duplicator tx closed");
+                            break 'record;
                         }
-                    },
-                    None => {
-                        tracing::error!("audio duplicator tx closed");
-                        break;
                     }
                 }
             }
         }
     });
 }
+
+// the processing is turned off for the session when the microphone records samples
+// which can't be processed, e.g. not 48kHz
+fn process_microphone_frame(
+    client: &EndPointClient,
+    audio_processor: &mut Option<AudioProcessor>,
+    audio_frame: &mut AudioEncodeFrame,
+) {
+    if audio_processor.is_none() {
+        match AudioProcessor::new(audio_frame.channels) {
+            Ok(processor) => *audio_processor = Some(processor),
+            Err(err) => {
+                tracing::warn!(
+                    ?err,
+                    "create audio processor failed, turn off audio processing"
+                );
+                client.set_audio_processing_enabled(false);
+                return;
+            }
+        }
+    }
+
+    if let Some(processor) = audio_processor {
+        if let Err(err) = processor.process_capture(audio_frame) {
+            tracing::warn!(
+                ?err,
+                "process microphone audio failed, turn off audio processing"
+            );
+            client.set_audio_processing_enabled(false);
+        }
+    }
+}
+
+// the application may exit or not support process loopback, back to the mix of all
+fn fallback_to_system_audio(client: &EndPointClient, audio_capture_target: AudioCaptureTarget) {
+    if audio_capture_target != AudioCaptureTarget::System {
+        tracing::warn!(
+            ?audio_capture_target,
+            "capture audio of application failed, fallback to system audio"
+        );
+        client.blocking_set_audio_capture_target(AudioCaptureTarget::System);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{
+    api::{
+        endpoint::{
+            client::EndPointClient,
+            message::{
+                EndPointInviteObserverReply, EndPointInviteObserverRequest,
+                EndPointNegotiateDesktopParamsRequest, EndPointNegotiateDesktopParamsResponse,
+            },
+            sessions,
+        },
+        signaling::observer::create_observer_token,
+    },
+    core_error,
+    error::CoreResult,
+};
+use std::{sync::Arc, time::Duration};
+
+pub async fn handle_invite_observer_request(
+    client: Arc<EndPointClient>,
+    req: EndPointInviteObserverRequest,
+) -> CoreResult<EndPointInviteObserverReply> {
+    if client.observed_session_id().is_some() {
+        return Err(core_error!("observer can't invite observers"));
+    }
+
+    let (token_id, token, expire) = create_observer_token(
+        client.session_id(),
+        Duration::from_secs(req.ttl_secs as u64),
+    );
+
+    tracing::info!(expire, "invite observer");
+
+    Ok(EndPointInviteObserverReply {
+        token_id,
+        token,
+        expire,
+    })
+}
+
+/// The observer streams what the observed session negotiated, it's rejected when it
+/// can't decode the codec of that stream.
+pub async fn negotiate_observer_params(
+    client: &EndPointClient,
+    observed_session_id: &str,
+    req: EndPointNegotiateDesktopParamsRequest,
+) -> EndPointNegotiateDesktopParamsResponse {
+    let Some(observed) = sessions::find(observed_session_id) else {
+        return EndPointNegotiateDesktopParamsResponse::VideoError(String::from(
+            "observed session is closed",
+        ));
+    };
+
+    let Some(mut params) = observed.negotiated_desktop_params() else {
+        return EndPointNegotiateDesktopParamsResponse::VideoError(String::from(
+            "observed session doesn't stream desktop",
+        ));
+    };
+
+    if !req.video_codecs.contains(&params.video_codec) {
+        tracing::error!(
+            codec = ?params.video_codec,
+            "observer can't decode codec of observed session"
+        );
+        return EndPointNegotiateDesktopParamsResponse::VideoError(format!(
+            "observed session streams {:?} which viewer can't decode",
+            params.video_codec
+        ));
+    }
+
+    // the monitor may be switched since negotiated
+    if let Some(monitor) = observed.monitor().await {
+        params.primary_monitor = (*monitor).clone();
+    }
+
+    client.set_monitor(params.primary_monitor.clone()).await;
+    client.set_video_codec(params.video_codec.clone());
+    client.set_watermark_enabled(params.watermark);
+
+    tracing::info!(?observed_session_id, "negotiate observer");
+
+    EndPointNegotiateDesktopParamsResponse::Params(params)
+}
+
+/// Stream the media of the observed session to the observer, nothing is captured for it.
+pub fn attach_observer(client: Arc<EndPointClient>, observed_session_id: &str) {
+    let Some(observed) = sessions::find(observed_session_id) else {
+        tracing::warn!(?observed_session_id, "observed session is closed");
+        client.close();
+        return;
+    };
+
+    tracing::info!(?observed_session_id, "attach observer");
+
+    observed.attach_observer(client);
+}
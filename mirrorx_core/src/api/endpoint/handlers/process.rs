@@ -0,0 +1,52 @@
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{
+            EndPointProcess, EndPointProcessKillReply, EndPointProcessKillRequest,
+            EndPointProcessListReply, EndPointProcessListRequest,
+        },
+    },
+    component::process,
+    core_error,
+    error::CoreResult,
+};
+use std::sync::Arc;
+
+pub async fn handle_process_list_request(
+    _: Arc<EndPointClient>,
+    _: EndPointProcessListRequest,
+) -> CoreResult<EndPointProcessListReply> {
+    // measuring CPU usage sleeps, so it's kept off the async runtime
+    let processes = tokio::task::spawn_blocking(process::list_processes)
+        .await
+        .map_err(|_| core_error!("list processes failed"))?;
+
+    Ok(EndPointProcessListReply {
+        processes: processes
+            .into_iter()
+            .map(|process| EndPointProcess {
+                pid: process.pid,
+                name: process.name,
+                cpu_usage: process.cpu_usage,
+                memory: process.memory,
+            })
+            .collect(),
+    })
+}
+
+pub async fn handle_process_kill_request(
+    client: Arc<EndPointClient>,
+    req: EndPointProcessKillRequest,
+) -> CoreResult<EndPointProcessKillReply> {
+    tracing::info!(
+        endpoint_id = ?client.endpoint_id(),
+        pid = req.pid,
+        "remote kills process"
+    );
+
+    tokio::task::spawn_blocking(move || process::kill_process(req.pid))
+        .await
+        .map_err(|_| core_error!("kill process failed"))??;
+
+    Ok(EndPointProcessKillReply {})
+}
@@ -0,0 +1,38 @@
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{
+            AudioCaptureTarget, EndPointSwitchAudioCaptureReply, EndPointSwitchAudioCaptureRequest,
+        },
+    },
+    component::audio::recorder::audio_applications,
+    core_error,
+    error::CoreResult,
+};
+use std::sync::Arc;
+
+pub async fn handle_switch_audio_capture_request(
+    client: Arc<EndPointClient>,
+    req: EndPointSwitchAudioCaptureRequest,
+) -> CoreResult<EndPointSwitchAudioCaptureReply> {
+    if let AudioCaptureTarget::Process(process_id) = req.target {
+        let applications = tokio::task::spawn_blocking(audio_applications)
+            .await
+            .map_err(|_| core_error!("list audio applications failed"))?;
+
+        // the application may exit after negotiation
+        if !applications
+            .iter()
+            .any(|application| application.process_id == process_id)
+        {
+            return Err(core_error!("application doesn't play audio"));
+        }
+    }
+
+    tracing::info!(audio_capture_target = ?req.target, "switch audio capture target");
+
+    // audio capture process will pick up the new target at next frame
+    client.set_audio_capture_target(req.target).await;
+
+    Ok(EndPointSwitchAudioCaptureReply { target: req.target })
+}
@@ -0,0 +1,22 @@
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{EndPointSwitchAudioProcessingReply, EndPointSwitchAudioProcessingRequest},
+    },
+    error::CoreResult,
+};
+use std::sync::Arc;
+
+pub async fn handle_switch_audio_processing_request(
+    client: Arc<EndPointClient>,
+    req: EndPointSwitchAudioProcessingRequest,
+) -> CoreResult<EndPointSwitchAudioProcessingReply> {
+    tracing::info!(enabled = req.enabled, "switch audio processing");
+
+    // audio capture process checks it for every recorded frame
+    client.set_audio_processing_enabled(req.enabled);
+
+    Ok(EndPointSwitchAudioProcessingReply {
+        enabled: req.enabled,
+    })
+}
@@ -0,0 +1,22 @@
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{EndPointSwitchHostPriorityReply, EndPointSwitchHostPriorityRequest},
+    },
+    error::CoreResult,
+};
+use std::sync::Arc;
+
+pub async fn handle_switch_host_priority_request(
+    client: Arc<EndPointClient>,
+    req: EndPointSwitchHostPriorityRequest,
+) -> CoreResult<EndPointSwitchHostPriorityReply> {
+    tracing::info!(enabled = req.enabled, "switch host priority");
+
+    // capture and encode threads tune themselves with it before every frame
+    client.set_host_priority(req.enabled);
+
+    Ok(EndPointSwitchHostPriorityReply {
+        enabled: req.enabled,
+    })
+}
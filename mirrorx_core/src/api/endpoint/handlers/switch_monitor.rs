@@ -0,0 +1,58 @@
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{EndPointSwitchMonitorReply, EndPointSwitchMonitorRequest},
+    },
+    component::desktop::{
+        monitor::get_active_monitors,
+        span::{span_monitor, SPAN_MONITOR_ID},
+    },
+    core_error,
+    error::CoreResult,
+};
+use std::sync::Arc;
+
+pub async fn handle_switch_monitor_request(
+    client: Arc<EndPointClient>,
+    req: EndPointSwitchMonitorRequest,
+) -> CoreResult<EndPointSwitchMonitorReply> {
+    let monitors = get_active_monitors(false)?;
+    if monitors.is_empty() {
+        return Err(core_error!("no active monitor"));
+    }
+
+    let monitor = match req.monitor_id {
+        Some(monitor_id) if monitor_id == SPAN_MONITOR_ID => span_monitor(&monitors)?,
+        Some(monitor_id) => monitors
+            .into_iter()
+            .find(|monitor| monitor.id == monitor_id)
+            .ok_or_else(|| core_error!("monitor not exists"))?,
+        None => {
+            let current_index = match client.monitor().await {
+                Some(current_monitor) => monitors
+                    .iter()
+                    .position(|monitor| monitor.id == current_monitor.id),
+                None => None,
+            };
+
+            // back to primary monitor when current monitor is unplugged or spanned
+            let next_index = current_index.map_or_else(
+                || {
+                    monitors
+                        .iter()
+                        .position(|monitor| monitor.is_primary)
+                        .unwrap_or(0)
+                },
+                |index| (index + 1) % monitors.len(),
+            );
+            monitors[next_index].clone()
+        }
+    };
+
+    tracing::info!(?monitor.id, ?monitor.width, ?monitor.height, "switch monitor");
+
+    // desktop capture process will pick up the new monitor at next capture
+    client.set_monitor(monitor.clone()).await;
+
+    Ok(EndPointSwitchMonitorReply { monitor })
+}
@@ -0,0 +1,50 @@
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{EndPointSwitchResolutionReply, EndPointSwitchResolutionRequest},
+    },
+    component::{
+        desktop::{
+            monitor::get_active_monitors, span::SPAN_MONITOR_ID,
+            test_pattern::TEST_PATTERN_MONITOR_ID,
+        },
+        display_control,
+    },
+    core_error,
+    error::CoreResult,
+};
+use std::sync::Arc;
+
+pub async fn handle_switch_resolution_request(
+    client: Arc<EndPointClient>,
+    req: EndPointSwitchResolutionRequest,
+) -> CoreResult<EndPointSwitchResolutionReply> {
+    let Some(monitor) = client.monitor().await else {
+        return Err(core_error!("no monitor is streamed"));
+    };
+
+    if monitor.id == SPAN_MONITOR_ID || monitor.id == TEST_PATTERN_MONITOR_ID {
+        return Err(core_error!(
+            "resolution of virtual monitor can't be changed"
+        ));
+    }
+
+    tracing::info!(?monitor.id, resolution = ?req.resolution, "switch resolution");
+
+    match req.resolution {
+        Some((width, height)) => {
+            display_control::change_resolution(client.session_id(), &monitor.id, width, height)?
+        }
+        None => display_control::restore_resolution(&monitor.id)?,
+    }
+
+    // the size and layout of monitor change with its resolution
+    let monitor = get_active_monitors(false)?
+        .into_iter()
+        .find(|active_monitor| active_monitor.id == monitor.id)
+        .ok_or_else(|| core_error!("monitor not exists"))?;
+
+    client.set_monitor(monitor.clone()).await;
+
+    Ok(EndPointSwitchResolutionReply { monitor })
+}
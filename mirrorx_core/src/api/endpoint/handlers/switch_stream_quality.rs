@@ -0,0 +1,22 @@
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{EndPointSwitchStreamQualityReply, EndPointSwitchStreamQualityRequest},
+    },
+    error::CoreResult,
+};
+use std::sync::Arc;
+
+pub async fn handle_switch_stream_quality_request(
+    client: Arc<EndPointClient>,
+    req: EndPointSwitchStreamQualityRequest,
+) -> CoreResult<EndPointSwitchStreamQualityReply> {
+    tracing::info!(quality = ?req.quality, "switch stream quality");
+
+    // desktop capture process filters every captured frame with it
+    client.set_stream_quality(req.quality);
+
+    Ok(EndPointSwitchStreamQualityReply {
+        quality: req.quality,
+    })
+}
@@ -0,0 +1,41 @@
+use crate::{
+    api::{
+        endpoint::{
+            client::EndPointClient,
+            message::{EndPointSystemActionReply, EndPointSystemActionRequest, SystemAction},
+        },
+        system::schedule_reboot,
+    },
+    component::power::keep_awake::wake_display,
+    error::CoreResult,
+};
+use std::{sync::Arc, time::Duration};
+
+// long enough for the reply to reach active endpoint before the session is gone
+const REBOOT_DELAY: Duration = Duration::from_secs(5);
+
+pub async fn handle_system_action_request(
+    client: Arc<EndPointClient>,
+    req: EndPointSystemActionRequest,
+) -> CoreResult<EndPointSystemActionReply> {
+    tracing::info!(
+        endpoint_id = ?client.endpoint_id(),
+        action = ?req.action,
+        "perform system action"
+    );
+
+    let delay = match req.action {
+        SystemAction::Reboot => {
+            schedule_reboot(REBOOT_DELAY)?;
+            REBOOT_DELAY
+        }
+        SystemAction::WakeDisplay => {
+            wake_display()?;
+            Duration::ZERO
+        }
+    };
+
+    Ok(EndPointSystemActionReply {
+        delay_secs: delay.as_secs() as u32,
+    })
+}
@@ -0,0 +1,38 @@
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{EndPointSystemVolumeReply, EndPointSystemVolumeRequest},
+    },
+    component::system_volume,
+    core_error,
+    error::CoreResult,
+};
+use std::sync::Arc;
+
+pub async fn handle_system_volume_request(
+    client: Arc<EndPointClient>,
+    req: EndPointSystemVolumeRequest,
+) -> CoreResult<EndPointSystemVolumeReply> {
+    // audio APIs block on the device, so they're kept off the async runtime
+    let volume = tokio::task::spawn_blocking(move || {
+        if req.level.is_none() && req.muted.is_none() {
+            system_volume::system_volume()
+        } else {
+            tracing::info!(
+                endpoint_id = ?client.endpoint_id(),
+                level = ?req.level,
+                muted = ?req.muted,
+                "remote changes system volume"
+            );
+
+            system_volume::set_system_volume(req.level, req.muted)
+        }
+    })
+    .await
+    .map_err(|_| core_error!("control system volume failed"))??;
+
+    Ok(EndPointSystemVolumeReply {
+        level: volume.level,
+        muted: volume.muted,
+    })
+}
@@ -0,0 +1,41 @@
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        id::EndPointID,
+        message::{EndPointLoginReply, EndPointLoginRequest},
+    },
+    component::unattended_login::login,
+    core_error,
+    error::CoreResult,
+};
+use std::sync::Arc;
+
+pub async fn handle_login_request(
+    client: Arc<EndPointClient>,
+    req: EndPointLoginRequest,
+) -> CoreResult<EndPointLoginReply> {
+    tracing::info!(
+        endpoint_id = ?client.endpoint_id(),
+        username = ?req.username,
+        domain = ?req.domain,
+        "remote signs in at login screen"
+    );
+
+    // failed attempts are counted against the device rather than the session, which is
+    // renewed by reconnecting
+    let remote = match client.endpoint_id() {
+        EndPointID::DeviceID {
+            remote_device_id, ..
+        } => remote_device_id.to_string(),
+        EndPointID::LANID { remote_ip, .. } => remote_ip.to_string(),
+    };
+
+    // checking credentials with the system may take seconds
+    tokio::task::spawn_blocking(move || {
+        login(&remote, &req.username, req.domain.as_deref(), &req.password)
+    })
+    .await
+    .map_err(|_| core_error!("unattended login failed"))??;
+
+    Ok(EndPointLoginReply {})
+}
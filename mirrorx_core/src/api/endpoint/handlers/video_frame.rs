@@ -1,30 +1,118 @@
 use crate::{
-    api::endpoint::{message::EndPointVideoFrame, EndPointID},
-    component::{frame::DesktopDecodeFrame, video_decoder::decoder::VideoDecoder},
+    api::endpoint::{
+        message::{EndPointVideoFrame, VideoCodec},
+        EndPointID,
+    },
+    component::{
+        clock_sync, frame::DesktopDecodeFrame, session_stats, telemetry,
+        video_decoder::decoder::VideoDecoder, video_recorder,
+    },
+    error::CoreError,
+    utility::spawn,
 };
+use dashmap::{DashMap, DashSet};
+use once_cell::sync::Lazy;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::Sender;
 
+// a corrupt frame breaks the frames referencing it until the next key frame, the stream
+// is given up only when key frames don't help either
+const MAX_CONSECUTIVE_DECODE_FAILURES: u32 = 120;
+
+// key frames are large, don't ask for another one while the previous is on its way
+const KEY_FRAME_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
+// sessions whose decoder waits for a key frame, their message loops ask the remote for it
+static KEY_FRAME_WANTED: Lazy<DashSet<EndPointID>> = Lazy::new(DashSet::new);
+
+// negotiated codec of sessions, the decode process starts before negotiation finishes
+static VIDEO_CODECS: Lazy<DashMap<EndPointID, VideoCodec>> = Lazy::new(DashMap::new);
+
+/// Set the codec which the decoder of session takes from the next frame.
+pub(crate) fn set_video_codec(id: EndPointID, codec: VideoCodec) {
+    VIDEO_CODECS.insert(id, codec);
+}
+
+/// Ask the remote for a key frame with the next message of session, for the decoders
+/// which recover from it.
+pub(crate) fn request_key_frame(id: EndPointID) {
+    KEY_FRAME_WANTED.insert(id);
+}
+
+/// Whether the decoder of session failed since the last call and wants a key frame.
+pub fn take_key_frame_wanted(id: &EndPointID) -> bool {
+    KEY_FRAME_WANTED.remove(id).is_some()
+}
+
 pub fn serve_video_decode(
     id: EndPointID,
     render_tx: Sender<DesktopDecodeFrame>,
 ) -> Sender<EndPointVideoFrame> {
-    let (tx, mut rx) = tokio::sync::mpsc::channel(120);
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<EndPointVideoFrame>(120);
 
-    tokio::task::spawn_blocking(move || {
+    spawn::spawn_blocking(move || {
         tracing::info!(?id, "video decode process");
 
         let mut decoder = VideoDecoder::new(render_tx);
+        let started = Instant::now();
+        let mut received_bytes = 0;
+        let mut consecutive_failures = 0;
+        let mut last_key_frame_request: Option<Instant> = None;
 
         while let Some(video_frame) = rx.blocking_recv() {
+            received_bytes += video_frame.buffer.len() as u64;
+            session_stats::record_video_frame(id, video_frame.buffer.len());
+            if let Some(latency) = clock_sync::one_way_latency(&id, video_frame.timestamp_micros) {
+                session_stats::record_video_latency(id, latency);
+            }
+
+            let codec = VIDEO_CODECS
+                .get(&id)
+                .map(|codec| codec.clone())
+                .unwrap_or_default();
+
+            // recordings are raw H.264 streams, tile frames only make sense to the decoder
+            if codec != VideoCodec::TileCache {
+                video_recorder::write_video_frame(&id, &video_frame.buffer);
+            }
+
+            decoder.set_codec(codec);
+
             // let instant = std::time::Instant::now();
-            if let Err(err) = decoder.decode(video_frame) {
-                tracing::error!(?err, "decode video frame failed");
-                break;
+            match decoder.decode(video_frame) {
+                Ok(_) => consecutive_failures = 0,
+                Err(CoreError::OutgoingMessageChannelDisconnect) => {
+                    tracing::info!("video render channel closed");
+                    break;
+                }
+                Err(err) => {
+                    session_stats::record_decode_error(id);
+                    consecutive_failures += 1;
+                    if consecutive_failures >= MAX_CONSECUTIVE_DECODE_FAILURES {
+                        tracing::error!(?err, consecutive_failures, "decode video frame failed");
+                        break;
+                    }
+
+                    tracing::warn!(?err, consecutive_failures, "skip undecodable video frame");
+
+                    if last_key_frame_request.map_or(true, |requested| {
+                        requested.elapsed() >= KEY_FRAME_REQUEST_INTERVAL
+                    }) {
+                        KEY_FRAME_WANTED.insert(id);
+                        last_key_frame_request = Some(Instant::now());
+                    }
+                }
             }
             // let elapsed = instant.elapsed();
             // tracing::info!(?elapsed, "instant");
         }
 
+        KEY_FRAME_WANTED.remove(&id);
+        VIDEO_CODECS.remove(&id);
+        telemetry::record_video_traffic(received_bytes, started.elapsed());
+        session_stats::remove_session(&id);
+        video_recorder::finish_recording(&id);
+
         tracing::info!("video decode process exit");
     });
 
@@ -1,7 +1,21 @@
-use crate::component::{desktop::monitor::Monitor, fs::Directory, input::key::MouseKey};
+use super::permission::{GrantScope, Permissions};
+use crate::{
+    component::{
+        desktop::monitor::Monitor,
+        frame::ColorSpace,
+        fs::{
+            delta::{DeltaOp, FileSignature},
+            manifest::ManifestEntry,
+            Directory,
+        },
+        input::key::MouseKey,
+    },
+    core_error,
+    error::CoreResult,
+};
 use cpal::SampleFormat;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{fmt, path::PathBuf};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct EndPointHandshakeRequest {
@@ -15,6 +29,9 @@ pub struct EndPointHandshakeResponse {
     pub remote_device_id: i64,
 }
 
+// messages are read by peers of other releases of the same protocol version, append new
+// variants at the end and give new fields `#[serde(default)]` which CBOR skips. Bincode
+// can't skip fields, changing its layout bumps `signaling::key_exchange::PROTOCOL_VERSION`
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum EndPointMessage {
     Error,
@@ -28,6 +45,27 @@ pub enum EndPointMessage {
     InputCommand(EndPointInput),
     FileTransferBlock(EndPointFileTransferBlock),
     FileTransferError(EndPointFileTransferError),
+    Cursor(EndPointCursor),
+    PowerSaving(EndPointPowerSaving),
+    PluginMessage(EndPointPluginMessage),
+    PermissionsChanged(Permissions),
+    // None when input reaches the remote desktop again
+    InputBlocked(Option<InputBlockReason>),
+    FileDeltaBlock(EndPointFileDeltaBlock),
+    // active endpoint closed the window of the channel, its traffic should stop
+    ChannelClosed(EndPointChannel),
+    BandwidthLimit(EndPointBandwidthLimit),
+    // None when the desktop is captured as usual again
+    CaptureInterrupted(Option<CaptureInterruption>),
+    // active endpoint can't decode the stream, it recovers from the next key frame
+    KeyFrameRequest,
+    ClockSyncRequest(EndPointClockSyncRequest),
+    ClockSyncReply(EndPointClockSyncReply),
+    // entries of the sender which replace what the receiver kept, see `metadata`
+    Metadata(EndPointMetadata),
+    MagnifierFrame(EndPointMagnifierFrame),
+    // false once host user releases the block of local input with the escape hotkey
+    LocalInputBlocked(bool),
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -35,11 +73,30 @@ pub enum EndPointCallRequest {
     VisitDirectoryRequest(EndPointVisitDirectoryRequest),
     SendFileRequest(EndPointSendFileRequest),
     DownloadFileRequest(EndPointDownloadFileRequest),
+    SwitchMonitorRequest(EndPointSwitchMonitorRequest),
+    ElevatePermissionsRequest(EndPointElevatePermissionsRequest),
+    SwitchAudioCaptureRequest(EndPointSwitchAudioCaptureRequest),
+    SwitchAudioProcessingRequest(EndPointSwitchAudioProcessingRequest),
+    SystemActionRequest(EndPointSystemActionRequest),
+    FileManifestRequest(EndPointFileManifestRequest),
+    FsOperationRequest(EndPointFsOperationRequest),
+    SwitchStreamQualityRequest(EndPointSwitchStreamQualityRequest),
+    SwitchHostPriorityRequest(EndPointSwitchHostPriorityRequest),
+    SwitchResolutionRequest(EndPointSwitchResolutionRequest),
+    SystemVolumeRequest(EndPointSystemVolumeRequest),
+    ProcessListRequest(EndPointProcessListRequest),
+    ProcessKillRequest(EndPointProcessKillRequest),
+    MagnifyRequest(EndPointMagnifyRequest),
+    InviteObserverRequest(EndPointInviteObserverRequest),
+    BlockLocalInputRequest(EndPointBlockLocalInputRequest),
+    LoginRequest(EndPointLoginRequest),
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct EndPointNegotiateDesktopParamsRequest {
     pub video_codecs: Vec<VideoCodec>,
+    // in order of preference
+    pub cursor_modes: Vec<CursorMode>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -48,6 +105,32 @@ pub struct EndPointNegotiateVisitDesktopParams {
     pub os_type: String,
     pub os_version: String,
     pub primary_monitor: Monitor,
+    pub color_space: ColorSpace,
+    pub cursor_mode: CursorMode,
+    pub audio_source: AudioSource,
+    // applications whose audio can be captured alone, it's empty when the host doesn't
+    // support per-application capture
+    pub audio_applications: Vec<AudioApplication>,
+    // host stamps the viewer's device id and the time into the frames
+    pub watermark: bool,
+    // None from previous releases and hosts which can't tell
+    #[serde(default)]
+    pub keyboard_layout: Option<KeyboardLayout>,
+}
+
+/// Keyboard layout which the host types with, keys pressed on viewer are sent as key
+/// codes so they land as the characters of this layout.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct KeyboardLayout {
+    /// `windows` or `macos`, ids only compare on the same platform.
+    pub platform: String,
+    /// Input source id like `com.apple.keylayout.German` on macOS, the keyboard layout
+    /// handle like `04070407` on Windows.
+    pub id: String,
+    /// BCP 47 tag of the language which the layout types, like `de-DE`.
+    pub language: String,
+    /// BCP 47 tag of the locale of the user, like `en-US`.
+    pub locale: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -63,6 +146,54 @@ pub enum VideoCodec {
     Hevc,
     VP8,
     VP9,
+    /// Lossless tiles which are cached by hash, see `component::tile_cache`.
+    TileCache,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CursorMode {
+    /// Cursor is drawn into the desktop frame by passive endpoint.
+    Composited,
+    /// Cursor is sent by `EndPointMessage::Cursor` and drawn by active endpoint.
+    Separate,
+}
+
+/// The audio which passive endpoint sends, it depends on what the host can record.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AudioSource {
+    /// Everything the host plays.
+    SystemAudio,
+    /// System audio can't be recorded, e.g. macOS before 13, the microphone is sent
+    /// instead.
+    Microphone,
+    Unavailable,
+}
+
+/// What passive endpoint records when the audio source is system audio.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AudioCaptureTarget {
+    /// The mix of all applications.
+    System,
+    /// Only the audio of the process tree, it's supported by Windows 10 2004 and later.
+    Process(u32),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct AudioApplication {
+    pub process_id: u32,
+    pub name: String,
+}
+
+impl Default for CursorMode {
+    fn default() -> Self {
+        CursorMode::Composited
+    }
+}
+
+impl Default for AudioCaptureTarget {
+    fn default() -> Self {
+        AudioCaptureTarget::System
+    }
 }
 
 impl Default for VideoCodec {
@@ -130,6 +261,72 @@ impl Default for AudioSampleFormat {
 pub struct EndPointNegotiateFinishedRequest {
     // pub selected_monitor_id: String,
     pub expected_frame_rate: u8,
+    pub cursor_mode: CursorMode,
+    pub audio_capture_target: AudioCaptureTarget,
+    // echo cancellation and noise suppression when microphone is recorded
+    pub audio_processing: bool,
+    // previous releases always stream without B-frames and with key frames on demand
+    #[serde(default)]
+    pub gop_policy: GopPolicy,
+    // previous releases always stream for interactive control
+    #[serde(default)]
+    pub session_profile: SessionProfile,
+}
+
+/// What the session is for, it tunes the pipeline between latency and smoothness.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum SessionProfile {
+    /// Lowest latency for controlling remote, frames which queue up are dropped and every
+    /// frame is presented as soon as it's decoded.
+    #[default]
+    Interactive,
+    /// Smooth playback for presentations and videos, frames are buffered and presented
+    /// at a steady pace at the cost of some latency.
+    Streaming,
+}
+
+/// Key frames and B-frames of the encoded video. Interactive sessions want no B-frames,
+/// which hold back every frame until the ones after it are encoded, and key frames only
+/// when the decoder asks for one; sessions which are recorded trade latency for better
+/// compression.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct GopPolicy {
+    /// Seconds between key frames, zero means a key frame only when viewer asks for one.
+    pub key_frame_interval_secs: u16,
+    pub b_frames: u8,
+}
+
+impl GopPolicy {
+    pub const MAX_KEY_FRAME_INTERVAL_SECS: u16 = 60;
+    pub const MAX_B_FRAMES: u8 = 4;
+
+    pub fn validate(&self) -> CoreResult<()> {
+        if self.key_frame_interval_secs > Self::MAX_KEY_FRAME_INTERVAL_SECS {
+            return Err(core_error!(
+                "key frame interval must be at most {} seconds",
+                Self::MAX_KEY_FRAME_INTERVAL_SECS
+            ));
+        }
+
+        if self.b_frames > Self::MAX_B_FRAMES {
+            return Err(core_error!(
+                "b-frames must be at most {}",
+                Self::MAX_B_FRAMES
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The policy within the limits, what remote asks for isn't validated.
+    pub fn clamped(self) -> GopPolicy {
+        GopPolicy {
+            key_frame_interval_secs: self
+                .key_frame_interval_secs
+                .min(Self::MAX_KEY_FRAME_INTERVAL_SECS),
+            b_frames: self.b_frames.min(Self::MAX_B_FRAMES),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -140,6 +337,10 @@ pub struct EndPointVideoFrame {
 
     #[serde(with = "serde_bytes")]
     pub buffer: Vec<u8>,
+
+    // wall clock of passive endpoint when it's encoded, zero from previous releases
+    #[serde(default)]
+    pub timestamp_micros: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -149,6 +350,9 @@ pub struct EndPointAudioFrame {
     pub sample_rate: u32,
     #[serde(with = "serde_bytes")]
     pub buffer: Vec<u8>,
+    // wall clock of passive endpoint when it's encoded, zero from previous releases
+    #[serde(default)]
+    pub timestamp_micros: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -192,15 +396,29 @@ pub struct EndPointSendFileRequest {
     pub filename: String,
     pub path: PathBuf,
     pub size: u64,
+    // append to the partial file left by the interrupted transfer
+    pub resume: bool,
+    // the file is pasted or dropped without destination, `path` is ignored and it's
+    // saved in the staging directory of the session
+    pub staged: bool,
+    // keep the existing file as the basis and only transfer blocks which changed
+    pub delta: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
-pub struct EndPointSendFileReply {}
+pub struct EndPointSendFileReply {
+    // the size of the partial file, sender should continue from this offset
+    pub offset: u64,
+    // the signature of the existing file when delta is requested, sender should send
+    // delta blocks against it instead of the whole file
+    pub signature: Option<FileSignature>,
+}
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct EndPointDownloadFileRequest {
     pub id: String,
     pub path: PathBuf,
+    pub offset: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -215,7 +433,409 @@ pub struct EndPointFileTransferBlock {
     pub data: Option<Vec<u8>>,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointFileManifestRequest {
+    // the file or directory which is the root of a finished transfer
+    pub path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointFileManifestReply {
+    pub entries: Vec<ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum FsOperation {
+    Metadata(PathBuf),
+    // "/" lists drives on Windows
+    ReadDirectory(PathBuf),
+    Read {
+        path: PathBuf,
+        offset: u64,
+        len: u32,
+    },
+    // the file is created if it doesn't exist, and truncated to `offset` if `truncate`
+    Write {
+        path: PathBuf,
+        offset: u64,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+        truncate: bool,
+    },
+    CreateDirectory(PathBuf),
+    // directories are removed with everything in them
+    Remove(PathBuf),
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct FsEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified_time: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointFsOperationRequest {
+    pub op: FsOperation,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum EndPointFsOperationReply {
+    // None when the path doesn't exist
+    Metadata(Option<FsEntry>),
+    Directory(Vec<FsEntry>),
+    Data(#[serde(with = "serde_bytes")] Vec<u8>),
+    Done,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointFileDeltaBlock {
+    pub id: String,
+    // None when the file is complete
+    pub op: Option<DeltaOp>,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct EndPointFileTransferError {
     pub id: String,
 }
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSwitchMonitorRequest {
+    // switch to the next active monitor when it's None
+    pub monitor_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSwitchMonitorReply {
+    pub monitor: Monitor,
+}
+
+/// Change the resolution of the monitor which is streamed, it's restored when session
+/// ends.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSwitchResolutionRequest {
+    // width and height, restore the original resolution when it's None
+    pub resolution: Option<(u16, u16)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSwitchResolutionReply {
+    pub monitor: Monitor,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSwitchAudioCaptureRequest {
+    pub target: AudioCaptureTarget,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSwitchAudioCaptureReply {
+    pub target: AudioCaptureTarget,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSwitchAudioProcessingRequest {
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSwitchAudioProcessingReply {
+    pub enabled: bool,
+}
+
+/// How much of the picture passive endpoint streams, the savings modes are for links
+/// slower than 1Mbps.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum StreamQuality {
+    #[default]
+    Full,
+    /// Chroma is averaged over blocks of 8x8 pixels and frame rate is reduced.
+    ReducedChroma,
+    /// Chroma is dropped and frame rate is reduced.
+    Grayscale,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSwitchStreamQualityRequest {
+    pub quality: StreamQuality,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSwitchStreamQualityReply {
+    pub quality: StreamQuality,
+}
+
+/// Host priority mode leaves the host responsive for its user, streaming runs at the
+/// lowest priority and a lower frame rate.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSwitchHostPriorityRequest {
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSwitchHostPriorityReply {
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SystemAction {
+    Reboot,
+    WakeDisplay,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSystemActionRequest {
+    pub action: SystemAction,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSystemActionReply {
+    // the action is performed after the delay, so the reply can be sent back before it
+    pub delay_secs: u32,
+}
+
+/// Changes the master volume of passive endpoint, what's None is left unchanged, so the
+/// request of neither queries it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointSystemVolumeRequest {
+    // in percent
+    pub level: Option<u8>,
+    pub muted: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct EndPointSystemVolumeReply {
+    pub level: u8,
+    pub muted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointProcessListRequest {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct EndPointProcessListReply {
+    // the busiest come first
+    pub processes: Vec<EndPointProcess>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct EndPointProcess {
+    pub pid: u32,
+    pub name: String,
+    // usage of one core in percent
+    pub cpu_usage: f32,
+    // resident memory in bytes
+    pub memory: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointProcessKillRequest {
+    pub pid: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointProcessKillReply {}
+
+/// Region of the streamed monitor in its pixels, the offset and size are even so the
+/// region covers whole chrominance samples.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MagnifierRegion {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// Ask passive endpoint to stream the region at native resolution besides the desktop,
+/// so the viewer which zooms into it reads small text however the desktop is scaled or
+/// compressed. None stops streaming the region.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointMagnifyRequest {
+    pub region: Option<MagnifierRegion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointMagnifyReply {
+    // the region which is streamed, clamped into the monitor
+    pub region: Option<MagnifierRegion>,
+}
+
+/// Frame of the magnified region in the tile codec, see `component::tile_cache`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointMagnifierFrame {
+    pub region: MagnifierRegion,
+    pub frame: EndPointVideoFrame,
+}
+
+/// Ask passive endpoint for a token which lets another device watch the session, see
+/// `signaling::observer`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointInviteObserverRequest {
+    // passive endpoint caps it
+    pub ttl_secs: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointInviteObserverReply {
+    // random id which observer sends to tell which token it uses
+    pub token_id: String,
+    pub token: String,
+    // unix timestamp in seconds
+    pub expire: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointBlockLocalInputRequest {
+    pub blocked: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointBlockLocalInputReply {
+    // false when host user rejects it or the platform can't block input
+    pub blocked: bool,
+}
+
+/// Credentials which passive endpoint signs in with at its login screen.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct EndPointLoginRequest {
+    pub username: String,
+    // the local machine when it's None
+    pub domain: Option<String>,
+    pub password: String,
+}
+
+// call requests are logged, the password never is
+impl fmt::Debug for EndPointLoginRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EndPointLoginRequest")
+            .field("username", &self.username)
+            .field("domain", &self.domain)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointLoginReply {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointElevatePermissionsRequest {
+    // requesting none returns current permissions without prompting host user
+    pub permissions: Permissions,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointElevatePermissionsReply {
+    // all permissions of the session after elevation
+    pub permissions: Permissions,
+    pub granted: Permissions,
+    // None when nothing is granted
+    pub scope: Option<GrantScope>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointCursor {
+    pub visible: bool,
+    // position of the hotspot in desktop frame
+    pub x: i32,
+    pub y: i32,
+    // only present when the cursor shape changed
+    pub shape: Option<EndPointCursorShape>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointCursorShape {
+    pub width: u32,
+    pub height: u32,
+    pub hotspot_x: i32,
+    pub hotspot_y: i32,
+    // unmultiplied RGBA
+    #[serde(with = "serde_bytes")]
+    pub rgba: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointPowerSaving {
+    pub on_battery: bool,
+    pub power_saver: bool,
+    // None means the frame rate is no longer limited
+    pub max_frame_rate: Option<u8>,
+}
+
+/// Why input of active endpoint can't reach the desktop of passive endpoint.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum InputBlockReason {
+    /// The secure desktop, like UAC prompt, is shown and the host agent isn't elevated.
+    NotElevated,
+    /// The secure desktop is shown and the host agent is elevated but not running as
+    /// service, which is still required to reach it.
+    NotService,
+}
+
+/// Why passive endpoint can't capture its desktop as usual.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CaptureInterruption {
+    /// Desktop duplication is unavailable, like when a fullscreen exclusive application
+    /// holds the monitor, the desktop is captured by the slower fallback.
+    FallbackCapture,
+    /// Protected content, like DRM video, is shown and masked out of the frames.
+    ContentProtected,
+}
+
+/// The message of plugin sub-channel, it's delivered to the plugin which owns the
+/// channel on remote endpoint.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointPluginMessage {
+    pub channel: String,
+    #[serde(with = "serde_bytes")]
+    pub payload: Vec<u8>,
+}
+
+/// Logical channels of the session, the desktop and file manager windows of one remote
+/// device share the connection.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum EndPointChannel {
+    Desktop,
+    Files,
+}
+
+/// The bandwidth which peer keeps its audio, video and file traffic under, it's set by
+/// the user of active endpoint for the remote device and changeable during the session.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct EndPointBandwidthLimit {
+    // kilobits per second, None when unlimited
+    pub max_kbps: Option<u32>,
+}
+
+/// Active endpoint samples the clock of passive endpoint with it, see
+/// `component::clock_sync`. Times are microseconds of the unix epoch.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct EndPointClockSyncRequest {
+    pub origin_micros: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct EndPointClockSyncReply {
+    // echoed from the request
+    pub origin_micros: i64,
+    pub received_micros: i64,
+    pub transmitted_micros: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointMetadata {
+    pub entries: Vec<EndPointMetadataEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct EndPointMetadataEntry {
+    pub key: String,
+    #[serde(with = "serde_bytes")]
+    pub value: Vec<u8>,
+}
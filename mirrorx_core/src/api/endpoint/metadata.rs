@@ -0,0 +1,95 @@
+//! Optional features advertise themselves with metadata entries, which both endpoints
+//! send once the session is streaming. Peers keep what they receive as opaque bytes and
+//! features look up their own keys, releases which don't know a key just ignore it, so
+//! a feature can be introduced without a protocol version bump.
+//!
+//! Keys are namespaced by the feature, like `clipboard.formats`. Entries are exchanged
+//! once per connection, what's set later reaches the sessions connected afterwards.
+
+use super::message::{EndPointMessage, EndPointMetadata, EndPointMetadataEntry};
+use crate::{core_error, error::CoreResult};
+use once_cell::sync::Lazy;
+use std::{collections::BTreeMap, sync::RwLock};
+
+pub const MAX_KEY_LEN: usize = 64;
+pub const MAX_VALUE_SIZE: usize = 4 * 1024;
+pub const MAX_ENTRIES: usize = 32;
+
+// ordered, so the message of the same entries is encoded into the same bytes
+static LOCAL_METADATA: Lazy<RwLock<BTreeMap<String, Vec<u8>>>> =
+    Lazy::new(|| RwLock::new(BTreeMap::new()));
+
+/// Set the entry which is sent to the peers of later sessions.
+pub fn set_local_metadata(key: &str, value: Vec<u8>) -> CoreResult<()> {
+    validate_entry(key, &value)?;
+
+    let mut local_metadata = LOCAL_METADATA.write().unwrap();
+    if local_metadata.len() >= MAX_ENTRIES && !local_metadata.contains_key(key) {
+        return Err(core_error!(
+            "metadata holds {} entries at most",
+            MAX_ENTRIES
+        ));
+    }
+
+    local_metadata.insert(key.to_string(), value);
+
+    Ok(())
+}
+
+pub fn remove_local_metadata(key: &str) {
+    LOCAL_METADATA.write().unwrap().remove(key);
+}
+
+/// The message of local entries, None when there's none to send.
+pub(crate) fn local_metadata_message() -> Option<EndPointMessage> {
+    let local_metadata = LOCAL_METADATA.read().unwrap();
+    if local_metadata.is_empty() {
+        return None;
+    }
+
+    let entries = local_metadata
+        .iter()
+        .map(|(key, value)| EndPointMetadataEntry {
+            key: key.clone(),
+            value: value.clone(),
+        })
+        .collect();
+
+    Some(EndPointMessage::Metadata(EndPointMetadata { entries }))
+}
+
+/// Check the metadata of peer against the limits which local entries are held to, a
+/// peer which exceeds them is misbehaving.
+pub(crate) fn validate_metadata(metadata: &EndPointMetadata) -> CoreResult<()> {
+    if metadata.entries.len() > MAX_ENTRIES {
+        return Err(core_error!(
+            "metadata holds {} entries at most",
+            MAX_ENTRIES
+        ));
+    }
+
+    for entry in metadata.entries.iter() {
+        validate_entry(&entry.key, &entry.value)?;
+    }
+
+    Ok(())
+}
+
+fn validate_entry(key: &str, value: &[u8]) -> CoreResult<()> {
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        return Err(core_error!(
+            "metadata key must be 1 to {} bytes",
+            MAX_KEY_LEN
+        ));
+    }
+
+    if value.len() > MAX_VALUE_SIZE {
+        return Err(core_error!(
+            "metadata value of {:?} exceeds {} bytes",
+            key,
+            MAX_VALUE_SIZE
+        ));
+    }
+
+    Ok(())
+}
@@ -1,26 +1,67 @@
+pub mod acl;
 pub mod client;
 pub mod handlers;
 pub mod id;
 pub mod message;
+pub mod metadata;
+pub mod permission;
+#[cfg(feature = "protocol-vectors")]
+pub mod protocol_vectors;
+pub mod queue;
+pub mod sessions;
+#[cfg(feature = "host")]
+pub mod warm_start;
 
 use self::{
-    client::EndPointClient,
+    client::{trace::replay_trace, EndPointClient},
     handlers::{audio_frame::serve_audio_decode, video_frame::serve_video_decode},
     id::EndPointID,
+    queue::SessionSlot,
+};
+use crate::{
+    api::config::{entity::tls::RelayTls, LocalStorage},
+    component::telemetry,
+    error::CoreResult,
+    utility::nonce_value::NonceValue,
+    DesktopDecodeFrame,
 };
-use crate::{error::CoreResult, utility::nonce_value::NonceValue, DesktopDecodeFrame};
 use ring::aead::{OpeningKey, SealingKey};
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, path::Path, sync::Arc};
 use tokio::net::{TcpStream, UdpSocket};
+use tracing::{Instrument, Span};
 
 pub enum EndPointStream {
     ActiveTCP(SocketAddr),
+    /// Relay connection of the domain which pins the relay certificate.
+    ActiveTLS(SocketAddr, RelayTls),
     ActiveUDP(SocketAddr),
     PassiveTCP(TcpStream),
     PassiveUDP {
         remote_addr: SocketAddr,
         socket: UdpSocket,
     },
+    /// In-memory stream between two endpoints of this process, see `client::test_support`.
+    #[cfg(feature = "test-support")]
+    Duplex(tokio::io::DuplexStream),
+}
+
+impl EndPointStream {
+    /// Stream to the relay which the signaling server of `domain` assigns, it's wrapped
+    /// in TLS when the domain pins the relay certificate.
+    pub fn relay(storage: &LocalStorage, domain: &str, addr: SocketAddr) -> CoreResult<Self> {
+        match storage.tls().get_relay_tls(domain)? {
+            Some(relay_tls) => Ok(EndPointStream::ActiveTLS(addr, relay_tls)),
+            None => Ok(EndPointStream::ActiveTCP(addr)),
+        }
+    }
+}
+
+/// Every session has an unique id which is recorded in the span of all tasks serving
+/// the session, so logs of the session can be correlated and queried by it.
+fn new_session_span(endpoint_id: EndPointID) -> (String, Span) {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let span = tracing::info_span!("endpoint_session", %session_id, ?endpoint_id);
+    (session_id, span)
 }
 
 pub async fn create_desktop_active_endpoint_client(
@@ -32,13 +73,18 @@ pub async fn create_desktop_active_endpoint_client(
     Arc<EndPointClient>,
     tokio::sync::mpsc::Receiver<DesktopDecodeFrame>,
 )> {
+    let (session_id, span) = new_session_span(endpoint_id);
     let (render_frame_tx, render_frame_rx) = tokio::sync::mpsc::channel(180);
     let (audio_frame_tx, audio_frame_rx) = tokio::sync::mpsc::channel(180);
 
-    let video_frame_tx = serve_video_decode(endpoint_id, render_frame_tx);
-    serve_audio_decode(endpoint_id, audio_frame_rx);
+    let video_frame_tx = span.in_scope(|| {
+        let video_frame_tx = serve_video_decode(endpoint_id, render_frame_tx);
+        serve_audio_decode(endpoint_id, audio_frame_rx);
+        video_frame_tx
+    });
 
-    let client = EndPointClient::new_desktop_active(
+    let result = EndPointClient::new_desktop_active(
+        session_id,
         endpoint_id,
         key_pair,
         stream,
@@ -46,30 +92,122 @@ pub async fn create_desktop_active_endpoint_client(
         audio_frame_tx,
         visit_credentials,
     )
+    .instrument(span)
+    .await;
+
+    telemetry::record_connect_result(&result);
+
+    Ok((result?, render_frame_rx))
+}
+
+/// Replay the trace of desktop session, its video is decoded and rendered like the frames
+/// of a live session. See `client::trace`.
+pub async fn create_replay_endpoint_client(
+    endpoint_id: EndPointID,
+    path: &Path,
+    realtime: bool,
+) -> CoreResult<(
+    Arc<EndPointClient>,
+    tokio::sync::mpsc::Receiver<DesktopDecodeFrame>,
+)> {
+    let (session_id, span) = new_session_span(endpoint_id);
+    let (render_frame_tx, render_frame_rx) = tokio::sync::mpsc::channel(180);
+    let (audio_frame_tx, audio_frame_rx) = tokio::sync::mpsc::channel(180);
+
+    let video_frame_tx = span.in_scope(|| {
+        let video_frame_tx = serve_video_decode(endpoint_id, render_frame_tx);
+        serve_audio_decode(endpoint_id, audio_frame_rx);
+        video_frame_tx
+    });
+
+    let client = replay_trace(
+        session_id,
+        endpoint_id,
+        path,
+        video_frame_tx,
+        audio_frame_tx,
+        realtime,
+    )
+    .instrument(span)
     .await?;
 
     Ok((client, render_frame_rx))
 }
 
+/// Open desktop channel on the connection which serves the file manager of the same
+/// remote device, instead of visiting it again.
+pub async fn open_desktop_channel(
+    client: &Arc<EndPointClient>,
+) -> CoreResult<tokio::sync::mpsc::Receiver<DesktopDecodeFrame>> {
+    let endpoint_id = client.endpoint_id();
+    let span = tracing::info_span!(
+        "endpoint_session",
+        session_id = %client.session_id(),
+        ?endpoint_id
+    );
+    let (render_frame_tx, render_frame_rx) = tokio::sync::mpsc::channel(180);
+    let (audio_frame_tx, audio_frame_rx) = tokio::sync::mpsc::channel(180);
+
+    let video_frame_tx = span.in_scope(|| {
+        let video_frame_tx = serve_video_decode(endpoint_id, render_frame_tx);
+        serve_audio_decode(endpoint_id, audio_frame_rx);
+        video_frame_tx
+    });
+
+    client
+        .open_desktop_channel(video_frame_tx, audio_frame_tx)
+        .instrument(span)
+        .await?;
+
+    Ok(render_frame_rx)
+}
+
 pub async fn create_file_manager_active_endpoint_client(
     endpoint_id: EndPointID,
     key_pair: Option<(OpeningKey<NonceValue>, SealingKey<NonceValue>)>,
     stream: EndPointStream,
     visit_credentials: Option<Vec<u8>>,
 ) -> CoreResult<Arc<EndPointClient>> {
-    let client =
-        EndPointClient::new_file_manager_active(endpoint_id, key_pair, stream, visit_credentials)
-            .await?;
+    let (session_id, span) = new_session_span(endpoint_id);
+
+    let result = EndPointClient::new_file_manager_active(
+        session_id,
+        endpoint_id,
+        key_pair,
+        stream,
+        visit_credentials,
+    )
+    .instrument(span)
+    .await;
+
+    telemetry::record_connect_result(&result);
 
-    Ok(client)
+    result
 }
 
+/// Serve the visit of active endpoint, `observed_session_id` is the session which it
+/// observes when it visits with an observer token.
 pub async fn create_passive_endpoint_client(
     endpoint_id: EndPointID,
     key_pair: Option<(OpeningKey<NonceValue>, SealingKey<NonceValue>)>,
     stream: EndPointStream,
     visit_credentials: Option<Vec<u8>>,
+    session_slot: SessionSlot,
+    observed_session_id: Option<String>,
 ) -> CoreResult<()> {
-    EndPointClient::new_passive(endpoint_id, key_pair, stream, visit_credentials).await?;
+    let (session_id, span) = new_session_span(endpoint_id);
+
+    EndPointClient::new_passive(
+        session_id,
+        endpoint_id,
+        key_pair,
+        stream,
+        visit_credentials,
+        session_slot,
+        observed_session_id,
+    )
+    .instrument(span)
+    .await?;
+
     Ok(())
 }
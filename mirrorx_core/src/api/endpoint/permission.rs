@@ -0,0 +1,273 @@
+use super::{id::EndPointID, message::EndPointCallRequest};
+use crate::{
+    api::notification::{self, Notification},
+    core_error,
+    error::CoreResult,
+};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU8, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+// the requester's call expires in 60 seconds, host user should decide before that
+const ELEVATION_PROMPT_TIMEOUT: Duration = Duration::from_secs(50);
+
+static VIEW_ONLY_BY_DEFAULT: AtomicBool = AtomicBool::new(false);
+
+// elevation requests which are waiting for host user's decision, keyed by request id
+static PENDING_ELEVATIONS: Lazy<Mutex<HashMap<String, oneshot::Sender<ElevationDecision>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Bit mask of what the active endpoint is allowed to do in a session.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(transparent)]
+pub struct Permissions(u8);
+
+impl Permissions {
+    pub const NONE: Permissions = Permissions(0);
+    pub const INPUT: Permissions = Permissions(1);
+    pub const CLIPBOARD: Permissions = Permissions(1 << 1);
+    pub const FILE_TRANSFER: Permissions = Permissions(1 << 2);
+    // listing and killing processes of the host, it's never granted by default
+    pub const ADMIN_TOOLS: Permissions = Permissions(1 << 3);
+    // exchanging messages between plugins of both endpoints, never granted by default
+    pub const PLUGINS: Permissions = Permissions(1 << 4);
+    pub const ALL: Permissions = Permissions(0b11111);
+    pub const DEFAULT: Permissions = Permissions(0b111);
+
+    const NAMES: [(Permissions, &'static str); 5] = [
+        (Permissions::INPUT, "input"),
+        (Permissions::CLIPBOARD, "clipboard"),
+        (Permissions::FILE_TRANSFER, "file_transfer"),
+        (Permissions::ADMIN_TOOLS, "admin_tools"),
+        (Permissions::PLUGINS, "plugins"),
+    ];
+
+    pub fn from_bits(bits: u8) -> Self {
+        Permissions(bits & Permissions::ALL.0)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(&self, other: Permissions) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn union(&self, other: Permissions) -> Permissions {
+        Permissions(self.0 | other.0)
+    }
+
+    pub fn intersection(&self, other: Permissions) -> Permissions {
+        Permissions(self.0 & other.0)
+    }
+
+    pub fn difference(&self, other: Permissions) -> Permissions {
+        Permissions(self.0 & !other.0)
+    }
+
+    /// Names of permissions in the mask, like `["input", "file_transfer"]`.
+    pub fn names(&self) -> Vec<&'static str> {
+        Permissions::NAMES
+            .iter()
+            .filter(|(permission, _)| self.contains(*permission))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    pub fn from_names<S: AsRef<str>>(names: &[S]) -> CoreResult<Permissions> {
+        let mut permissions = Permissions::NONE;
+
+        for name in names {
+            let (permission, _) = Permissions::NAMES
+                .iter()
+                .find(|(_, permission_name)| *permission_name == name.as_ref())
+                .ok_or(core_error!("unknown permission '{}'", name.as_ref()))?;
+
+            permissions = permissions.union(*permission);
+        }
+
+        Ok(permissions)
+    }
+}
+
+/// How long the elevated permissions are kept.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrantScope {
+    Temporary { secs: u32 },
+    Session,
+}
+
+/// Host user's decision of an elevation request, granting none means rejection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElevationDecision {
+    pub granted: Permissions,
+    pub scope: GrantScope,
+}
+
+impl ElevationDecision {
+    pub fn reject() -> Self {
+        ElevationDecision {
+            granted: Permissions::NONE,
+            scope: GrantScope::Session,
+        }
+    }
+}
+
+/// Permissions of a session, they're enforced by passive endpoint and mirrored by
+/// active endpoint so it knows what it can do.
+#[derive(Debug)]
+pub struct SessionPermissions {
+    current: AtomicU8,
+    // permissions granted for the whole session, expired temporary grants never revoke them
+    persistent: AtomicU8,
+}
+
+impl SessionPermissions {
+    pub fn new(permissions: Permissions) -> Self {
+        Self {
+            current: AtomicU8::new(permissions.bits()),
+            persistent: AtomicU8::new(permissions.bits()),
+        }
+    }
+
+    pub fn get(&self) -> Permissions {
+        Permissions::from_bits(self.current.load(Ordering::SeqCst))
+    }
+
+    /// Overwrite all permissions, used by active endpoint when remote reports them.
+    pub fn set(&self, permissions: Permissions) {
+        self.persistent.store(permissions.bits(), Ordering::SeqCst);
+        self.current.store(permissions.bits(), Ordering::SeqCst);
+    }
+
+    /// Add `permissions` to the mask and returns the updated permissions.
+    pub fn grant(&self, permissions: Permissions, scope: GrantScope) -> Permissions {
+        if scope == GrantScope::Session {
+            self.persistent
+                .fetch_or(permissions.bits(), Ordering::SeqCst);
+        }
+
+        let previous = self.current.fetch_or(permissions.bits(), Ordering::SeqCst);
+        Permissions::from_bits(previous).union(permissions)
+    }
+
+    /// Remove the temporary granted `permissions` from the mask and returns the updated
+    /// permissions, the ones granted for session are kept.
+    pub fn revoke_temporary(&self, permissions: Permissions) -> Permissions {
+        let persistent = Permissions::from_bits(self.persistent.load(Ordering::SeqCst));
+        let revoked = permissions.difference(persistent);
+
+        let previous = self.current.fetch_and(!revoked.bits(), Ordering::SeqCst);
+        Permissions::from_bits(previous).difference(revoked)
+    }
+}
+
+impl EndPointCallRequest {
+    pub fn required_permissions(&self) -> Permissions {
+        match self {
+            EndPointCallRequest::VisitDirectoryRequest(_)
+            | EndPointCallRequest::SendFileRequest(_)
+            | EndPointCallRequest::DownloadFileRequest(_)
+            | EndPointCallRequest::FileManifestRequest(_)
+            | EndPointCallRequest::FsOperationRequest(_) => Permissions::FILE_TRANSFER,
+            // who controls the desktop can reboot it or change its resolution and volume
+            // anyway
+            EndPointCallRequest::SystemActionRequest(_)
+            | EndPointCallRequest::SwitchResolutionRequest(_)
+            | EndPointCallRequest::SystemVolumeRequest(_) => Permissions::INPUT,
+            EndPointCallRequest::ProcessListRequest(_)
+            | EndPointCallRequest::ProcessKillRequest(_) => Permissions::ADMIN_TOOLS,
+            // only who controls the desktop can share it with others
+            EndPointCallRequest::InviteObserverRequest(_) => Permissions::INPUT,
+            // only who controls the desktop may keep host user's hands off it
+            EndPointCallRequest::BlockLocalInputRequest(_) => Permissions::INPUT,
+            // signing in hands over the whole machine, so admin tools are required too
+            EndPointCallRequest::LoginRequest(_) => {
+                Permissions::INPUT.union(Permissions::ADMIN_TOOLS)
+            }
+            EndPointCallRequest::SwitchMonitorRequest(_)
+            | EndPointCallRequest::ElevatePermissionsRequest(_)
+            | EndPointCallRequest::SwitchAudioCaptureRequest(_)
+            | EndPointCallRequest::SwitchAudioProcessingRequest(_)
+            | EndPointCallRequest::SwitchStreamQualityRequest(_)
+            | EndPointCallRequest::SwitchHostPriorityRequest(_)
+            | EndPointCallRequest::MagnifyRequest(_) => Permissions::NONE,
+        }
+    }
+
+    /// Observers share the stream of the session they observe, so they can't switch or
+    /// tune it, they can only refresh their permissions.
+    pub fn observer_allowed(&self) -> bool {
+        matches!(self, EndPointCallRequest::ElevatePermissionsRequest(_))
+    }
+}
+
+/// Set whether visitors start in view-only mode, they can request elevation later.
+pub fn set_view_only_by_default(view_only: bool) {
+    VIEW_ONLY_BY_DEFAULT.store(view_only, Ordering::SeqCst);
+}
+
+/// The initial permissions of passive endpoint session, admin tools are only granted by
+/// elevation.
+pub fn default_permissions() -> Permissions {
+    if VIEW_ONLY_BY_DEFAULT.load(Ordering::SeqCst) {
+        Permissions::NONE
+    } else {
+        Permissions::DEFAULT
+    }
+}
+
+/// Reply the elevation request which host user was prompted with.
+pub fn reply_elevation(request_id: &str, decision: ElevationDecision) -> CoreResult<()> {
+    let tx = PENDING_ELEVATIONS
+        .lock()
+        .unwrap()
+        .remove(request_id)
+        .ok_or(core_error!("elevation request not exists or expired"))?;
+
+    tx.send(decision)
+        .map_err(|_| core_error!("elevation request not exists or expired"))
+}
+
+/// Prompt host user with the requested permissions and wait for the decision, it's
+/// rejected when user doesn't decide in time.
+pub(crate) async fn prompt_elevation(
+    endpoint_id: EndPointID,
+    permissions: Permissions,
+) -> ElevationDecision {
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let (tx, rx) = oneshot::channel();
+
+    PENDING_ELEVATIONS
+        .lock()
+        .unwrap()
+        .insert(request_id.clone(), tx);
+
+    notification::publish(Notification::PermissionElevationRequest {
+        request_id: request_id.clone(),
+        endpoint_id,
+        permissions,
+    });
+
+    match tokio::time::timeout(ELEVATION_PROMPT_TIMEOUT, rx).await {
+        Ok(Ok(decision)) => decision,
+        _ => {
+            tracing::info!(?endpoint_id, "elevation request is not decided in time");
+            PENDING_ELEVATIONS.lock().unwrap().remove(&request_id);
+            ElevationDecision::reject()
+        }
+    }
+}
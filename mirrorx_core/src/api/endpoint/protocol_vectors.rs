@@ -0,0 +1,650 @@
+//! Golden vectors of the endpoint protocol. Every variant of `EndPointMessage` and every
+//! call request and reply has a sample whose bytes are emitted as a vector in each wire
+//! format, the vectors of each release are kept so later builds can prove they still
+//! decode them into the same values and encode the samples into the same bytes. They
+//! also document the wire layout for third-party implementations.
+//!
+//! On the wire every message is prefixed with the one byte header of
+//! `client::codec`, vectors hold the bytes after it.
+
+use super::{
+    client::WireFormat,
+    message::*,
+    permission::{GrantScope, Permissions},
+};
+use crate::{
+    component::{
+        desktop::monitor::Monitor,
+        frame::{ColorPrimaries, ColorRange, ColorSpace},
+        fs::{
+            delta::{BlockSignature, DeltaOp, FileSignature},
+            manifest::ManifestEntry,
+            Directory, Entry, HashedIcon, IconType,
+        },
+        input::key::MouseKey,
+    },
+    core_error,
+    error::CoreResult,
+};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+const ACCEPTED_BREAKS_FILE_NAME: &str = "accepted_breaks";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VectorMismatch {
+    /// The stored vector can't be decoded anymore.
+    Undecodable { name: String, error: String },
+    /// The stored vector decodes into a value other than the sample.
+    ValueChanged { name: String },
+    /// The sample encodes into bytes other than the stored vector.
+    EncodingChanged { name: String },
+    /// The stored vector has no sample, the variant was removed or renamed.
+    SampleMissing { name: String },
+}
+
+/// Samples of every message, named after the variants they cover. Samples of the same
+/// variant are numbered in order, so new ones must be appended to keep the names of old
+/// vectors.
+pub fn sample_messages(format: WireFormat) -> CoreResult<Vec<(String, EndPointMessage)>> {
+    let monitor = Monitor {
+        id: String::from("monitor-1"),
+        name: String::from("Built-in Display"),
+        refresh_rate: 60,
+        width: 1920,
+        height: 1080,
+        is_primary: true,
+        screen_shot: Some(vec![0xff, 0xd8, 0xff, 0xd9]),
+        left: 0,
+        top: 0,
+        scale_factor: 100,
+    };
+
+    let signature = FileSignature {
+        block_size: 4096,
+        blocks: vec![BlockSignature {
+            weak: 0x0102_0304,
+            strong: (0..16).collect(),
+        }],
+    };
+
+    let fs_entry = FsEntry {
+        path: PathBuf::from("/home/user/a.txt"),
+        is_dir: false,
+        size: 1024,
+        modified_time: 1_672_531_200,
+    };
+
+    let mut hashed_icons = HashMap::new();
+    hashed_icons.insert(HashedIcon::Ext(String::from("txt")), Some(vec![1, 2, 3]));
+
+    let directory = Directory {
+        path: PathBuf::from("/home/user"),
+        entries: vec![Entry {
+            is_dir: false,
+            path: PathBuf::from("/home/user/a.txt"),
+            modified_time: 1_672_531_200,
+            size: 1024,
+            icon: IconType::Hashed(HashedIcon::Ext(String::from("txt"))),
+        }],
+        hashed_icons,
+    };
+
+    let call_requests = vec![
+        EndPointCallRequest::VisitDirectoryRequest(EndPointVisitDirectoryRequest {
+            path: Some(PathBuf::from("/home/user")),
+        }),
+        EndPointCallRequest::SendFileRequest(EndPointSendFileRequest {
+            id: String::from("transfer-1"),
+            filename: String::from("a.txt"),
+            path: PathBuf::from("/home/user/a.txt"),
+            size: 1024,
+            resume: false,
+            staged: false,
+            delta: true,
+        }),
+        EndPointCallRequest::DownloadFileRequest(EndPointDownloadFileRequest {
+            id: String::from("transfer-2"),
+            path: PathBuf::from("/home/user/a.txt"),
+            offset: 512,
+        }),
+        EndPointCallRequest::SwitchMonitorRequest(EndPointSwitchMonitorRequest {
+            monitor_id: Some(String::from("monitor-1")),
+        }),
+        EndPointCallRequest::ElevatePermissionsRequest(EndPointElevatePermissionsRequest {
+            permissions: Permissions::INPUT.union(Permissions::CLIPBOARD),
+        }),
+        EndPointCallRequest::SwitchAudioCaptureRequest(EndPointSwitchAudioCaptureRequest {
+            target: AudioCaptureTarget::Process(4321),
+        }),
+        EndPointCallRequest::SwitchAudioProcessingRequest(EndPointSwitchAudioProcessingRequest {
+            enabled: false,
+        }),
+        EndPointCallRequest::SystemActionRequest(EndPointSystemActionRequest {
+            action: SystemAction::Reboot,
+        }),
+        EndPointCallRequest::FileManifestRequest(EndPointFileManifestRequest {
+            path: PathBuf::from("/home/user"),
+        }),
+        EndPointCallRequest::FsOperationRequest(EndPointFsOperationRequest {
+            op: FsOperation::Read {
+                path: PathBuf::from("/home/user/a.txt"),
+                offset: 0,
+                len: 4096,
+            },
+        }),
+        EndPointCallRequest::FsOperationRequest(EndPointFsOperationRequest {
+            op: FsOperation::Write {
+                path: PathBuf::from("/home/user/a.txt"),
+                offset: 0,
+                data: vec![1, 2, 3, 4],
+                truncate: true,
+            },
+        }),
+        EndPointCallRequest::FsOperationRequest(EndPointFsOperationRequest {
+            op: FsOperation::Rename {
+                from: PathBuf::from("/home/user/a.txt"),
+                to: PathBuf::from("/home/user/b.txt"),
+            },
+        }),
+        EndPointCallRequest::SwitchStreamQualityRequest(EndPointSwitchStreamQualityRequest {
+            quality: StreamQuality::ReducedChroma,
+        }),
+        EndPointCallRequest::SwitchHostPriorityRequest(EndPointSwitchHostPriorityRequest {
+            enabled: true,
+        }),
+        EndPointCallRequest::SwitchResolutionRequest(EndPointSwitchResolutionRequest {
+            resolution: Some((1920, 1080)),
+        }),
+        EndPointCallRequest::SystemVolumeRequest(EndPointSystemVolumeRequest {
+            level: Some(30),
+            muted: None,
+        }),
+        EndPointCallRequest::ProcessListRequest(EndPointProcessListRequest {}),
+        EndPointCallRequest::ProcessKillRequest(EndPointProcessKillRequest { pid: 4242 }),
+        EndPointCallRequest::MagnifyRequest(EndPointMagnifyRequest {
+            region: Some(MagnifierRegion {
+                x: 640,
+                y: 360,
+                width: 480,
+                height: 270,
+            }),
+        }),
+        EndPointCallRequest::InviteObserverRequest(EndPointInviteObserverRequest { ttl_secs: 600 }),
+        EndPointCallRequest::BlockLocalInputRequest(EndPointBlockLocalInputRequest {
+            blocked: true,
+        }),
+        EndPointCallRequest::LoginRequest(EndPointLoginRequest {
+            username: String::from("user"),
+            domain: None,
+            password: String::from("password"),
+        }),
+    ];
+
+    let mut samples = vec![
+        EndPointMessage::Error,
+        EndPointMessage::NegotiateDesktopParamsRequest(EndPointNegotiateDesktopParamsRequest {
+            video_codecs: vec![VideoCodec::H264, VideoCodec::Hevc],
+            cursor_modes: vec![CursorMode::Separate, CursorMode::Composited],
+        }),
+        EndPointMessage::NegotiateDesktopParamsResponse(
+            EndPointNegotiateDesktopParamsResponse::Params(EndPointNegotiateVisitDesktopParams {
+                video_codec: VideoCodec::H264,
+                os_type: String::from("Windows"),
+                os_version: String::from("10.0.19045"),
+                primary_monitor: monitor.clone(),
+                color_space: ColorSpace {
+                    primaries: ColorPrimaries::DisplayP3,
+                    range: ColorRange::Full,
+                },
+                cursor_mode: CursorMode::Separate,
+                audio_source: AudioSource::SystemAudio,
+                audio_applications: vec![AudioApplication {
+                    process_id: 4321,
+                    name: String::from("player.exe"),
+                }],
+                watermark: true,
+                keyboard_layout: Some(KeyboardLayout {
+                    platform: String::from("windows"),
+                    id: String::from("04070407"),
+                    language: String::from("de-DE"),
+                    locale: String::from("en-US"),
+                }),
+            }),
+        ),
+        EndPointMessage::NegotiateDesktopParamsResponse(
+            EndPointNegotiateDesktopParamsResponse::VideoError(String::from("no encoder")),
+        ),
+        EndPointMessage::NegotiateFinishedRequest(EndPointNegotiateFinishedRequest {
+            expected_frame_rate: 60,
+            cursor_mode: CursorMode::Separate,
+            audio_capture_target: AudioCaptureTarget::System,
+            audio_processing: true,
+            gop_policy: GopPolicy {
+                key_frame_interval_secs: 2,
+                b_frames: 2,
+            },
+            session_profile: SessionProfile::Streaming,
+        }),
+        EndPointMessage::VideoFrame(EndPointVideoFrame {
+            width: 1920,
+            height: 1080,
+            pts: 123_456,
+            buffer: vec![0, 0, 0, 1, 0x67],
+            timestamp_micros: 1_672_531_200_000_000,
+        }),
+        EndPointMessage::AudioFrame(EndPointAudioFrame {
+            channels: 2,
+            sample_format: AudioSampleFormat::F32,
+            sample_rate: 48000,
+            buffer: vec![0x4f, 0x70, 0x75, 0x73],
+            timestamp_micros: 1_672_531_200_000_000,
+        }),
+        EndPointMessage::InputCommand(EndPointInput {
+            events: vec![
+                InputEvent::Mouse(MouseEvent::Move(MouseKey::None, 100.5, 200.25)),
+                InputEvent::Mouse(MouseEvent::Down(MouseKey::Left, 100.5, 200.25)),
+                InputEvent::Mouse(MouseEvent::ScrollWheel(-1.5)),
+                InputEvent::Keyboard(KeyboardEvent::KeyDown(tao::keyboard::KeyCode::KeyA)),
+            ],
+        }),
+        EndPointMessage::FileTransferBlock(EndPointFileTransferBlock {
+            id: String::from("transfer-1"),
+            data: Some(vec![1, 2, 3, 4]),
+        }),
+        EndPointMessage::FileTransferBlock(EndPointFileTransferBlock {
+            id: String::from("transfer-1"),
+            data: None,
+        }),
+        EndPointMessage::FileTransferError(EndPointFileTransferError {
+            id: String::from("transfer-1"),
+        }),
+        EndPointMessage::Cursor(EndPointCursor {
+            visible: true,
+            x: 640,
+            y: 360,
+            shape: Some(EndPointCursorShape {
+                width: 1,
+                height: 1,
+                hotspot_x: 0,
+                hotspot_y: 0,
+                rgba: vec![0xff, 0xff, 0xff, 0xff],
+            }),
+        }),
+        EndPointMessage::PowerSaving(EndPointPowerSaving {
+            on_battery: true,
+            power_saver: false,
+            max_frame_rate: Some(30),
+        }),
+        EndPointMessage::PluginMessage(EndPointPluginMessage {
+            channel: String::from("clipboard-history"),
+            payload: vec![1, 2, 3],
+        }),
+        EndPointMessage::PermissionsChanged(Permissions::DEFAULT),
+        EndPointMessage::InputBlocked(Some(InputBlockReason::NotElevated)),
+        EndPointMessage::FileDeltaBlock(EndPointFileDeltaBlock {
+            id: String::from("transfer-1"),
+            op: Some(DeltaOp::Copy(7)),
+        }),
+        EndPointMessage::FileDeltaBlock(EndPointFileDeltaBlock {
+            id: String::from("transfer-1"),
+            op: Some(DeltaOp::Literal(vec![1, 2, 3])),
+        }),
+        EndPointMessage::ChannelClosed(EndPointChannel::Desktop),
+        EndPointMessage::BandwidthLimit(EndPointBandwidthLimit {
+            max_kbps: Some(2048),
+        }),
+        EndPointMessage::CaptureInterrupted(Some(CaptureInterruption::ContentProtected)),
+        EndPointMessage::KeyFrameRequest,
+        EndPointMessage::ClockSyncRequest(EndPointClockSyncRequest {
+            origin_micros: 1_672_531_200_000_000,
+        }),
+        EndPointMessage::ClockSyncReply(EndPointClockSyncReply {
+            origin_micros: 1_672_531_200_000_000,
+            received_micros: 1_672_531_200_250_000,
+            transmitted_micros: 1_672_531_200_250_100,
+        }),
+        EndPointMessage::Metadata(EndPointMetadata {
+            entries: vec![EndPointMetadataEntry {
+                key: String::from("clipboard.formats"),
+                value: b"text,image".to_vec(),
+            }],
+        }),
+        EndPointMessage::MagnifierFrame(EndPointMagnifierFrame {
+            region: MagnifierRegion {
+                x: 640,
+                y: 360,
+                width: 480,
+                height: 270,
+            },
+            frame: EndPointVideoFrame {
+                width: 480,
+                height: 270,
+                pts: 123_456,
+                buffer: vec![1, 0, 0, 0],
+                timestamp_micros: 1_672_531_200_000_000,
+            },
+        }),
+        EndPointMessage::LocalInputBlocked(false),
+    ];
+
+    samples.extend(
+        call_requests
+            .into_iter()
+            .enumerate()
+            .map(|(call_id, req)| EndPointMessage::CallRequest(call_id as u16, req)),
+    );
+
+    samples.extend([
+        call_reply(format, EndPointVisitDirectoryResponse { dir: directory })?,
+        call_reply(
+            format,
+            EndPointSendFileReply {
+                offset: 0,
+                signature: Some(signature),
+            },
+        )?,
+        call_reply(format, EndPointDownloadFileReply { size: 1024 })?,
+        call_reply(
+            format,
+            EndPointSwitchMonitorReply {
+                monitor: monitor.clone(),
+            },
+        )?,
+        call_reply(
+            format,
+            EndPointElevatePermissionsReply {
+                permissions: Permissions::DEFAULT,
+                granted: Permissions::FILE_TRANSFER,
+                scope: Some(GrantScope::Temporary { secs: 600 }),
+            },
+        )?,
+        call_reply(
+            format,
+            EndPointSwitchAudioCaptureReply {
+                target: AudioCaptureTarget::System,
+            },
+        )?,
+        call_reply(format, EndPointSwitchAudioProcessingReply { enabled: true })?,
+        call_reply(
+            format,
+            EndPointSwitchStreamQualityReply {
+                quality: StreamQuality::Grayscale,
+            },
+        )?,
+        call_reply(format, EndPointSwitchHostPriorityReply { enabled: true })?,
+        call_reply(format, EndPointSwitchResolutionReply { monitor })?,
+        call_reply(format, EndPointSystemActionReply { delay_secs: 5 })?,
+        call_reply(
+            format,
+            EndPointSystemVolumeReply {
+                level: 30,
+                muted: false,
+            },
+        )?,
+        call_reply(
+            format,
+            EndPointProcessListReply {
+                processes: vec![EndPointProcess {
+                    pid: 4242,
+                    name: String::from("app.exe"),
+                    cpu_usage: 97.5,
+                    memory: 256 * 1024 * 1024,
+                }],
+            },
+        )?,
+        call_reply(format, EndPointProcessKillReply {})?,
+        call_reply(format, EndPointMagnifyReply { region: None })?,
+        call_reply(format, EndPointBlockLocalInputReply { blocked: true })?,
+        call_reply(format, EndPointLoginReply {})?,
+        call_reply(
+            format,
+            EndPointInviteObserverReply {
+                token_id: String::from("fedcba9876543210"),
+                token: String::from("0123456789abcdef"),
+                expire: 1_700_000_600,
+            },
+        )?,
+        call_reply(
+            format,
+            EndPointFileManifestReply {
+                entries: vec![ManifestEntry {
+                    path: String::from("a.txt"),
+                    size: 1024,
+                    hash: String::from("e3b0c44298fc1c149afbf4c8996fb924"),
+                }],
+            },
+        )?,
+        call_reply(
+            format,
+            EndPointFsOperationReply::Metadata(Some(fs_entry.clone())),
+        )?,
+        call_reply(format, EndPointFsOperationReply::Directory(vec![fs_entry]))?,
+        call_reply(format, EndPointFsOperationReply::Data(vec![1, 2, 3]))?,
+        call_reply(format, EndPointFsOperationReply::Done)?,
+        EndPointMessage::CallReply(
+            0,
+            format.serialize(&Err::<(), String>(String::from("permission denied")))?,
+        ),
+    ]);
+
+    // sequence numbers tell apart the samples of the same variant
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let samples = samples
+        .into_iter()
+        .map(|message| {
+            let variant = variant_name(&message);
+            let count = counts.entry(variant.clone()).or_default();
+            *count += 1;
+            (format!("{variant}_{count}"), message)
+        })
+        .collect();
+
+    Ok(samples)
+}
+
+/// Write the vectors of every sample in every wire format into `dir`, with `layout.md`
+/// which lists the bytes of each of them.
+pub fn emit_vectors(dir: &Path) -> CoreResult<()> {
+    std::fs::create_dir_all(dir)?;
+
+    let mut layout = String::from("# Endpoint Message Vectors\n");
+
+    for format in WireFormat::SUPPORTED {
+        let _ = write!(
+            layout,
+            "\n## {}\n\n| Vector | Size | Bytes |\n| --- | --- | --- |\n",
+            format_description(format)
+        );
+
+        for (name, message) in sample_messages(format)? {
+            let buffer = format.serialize(&message)?;
+            std::fs::write(dir.join(&name).with_extension(extension(format)), &buffer)?;
+
+            let _ = writeln!(
+                layout,
+                "| {} | {} | `{}` |",
+                name,
+                buffer.len(),
+                hex::encode(&buffer)
+            );
+        }
+    }
+
+    std::fs::write(dir.join("layout.md"), layout)?;
+
+    Ok(())
+}
+
+/// Check the vectors stored in `dir` against the samples of this build, returns what
+/// doesn't match. Vectors are named after their files, like `cursor_1.cbor`.
+///
+/// Vectors listed in the `accepted_breaks` file of `dir`, one name per line, were broken
+/// on purpose by a protocol version bump and aren't checked.
+pub fn check_vectors(dir: &Path) -> CoreResult<Vec<VectorMismatch>> {
+    let accepted_breaks = match std::fs::read_to_string(dir.join(ACCEPTED_BREAKS_FILE_NAME)) {
+        Ok(content) => content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut samples: HashMap<String, EndPointMessage> = HashMap::new();
+    for format in WireFormat::SUPPORTED {
+        for (name, message) in sample_messages(format)? {
+            samples.insert(format!("{}.{}", name, extension(format)), message);
+        }
+    }
+
+    let mut mismatches = Vec::new();
+    let mut checked = 0;
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        let Some(format) = WireFormat::SUPPORTED.into_iter().find(|format| {
+            path.extension().and_then(|ext| ext.to_str()) == Some(extension(*format))
+        }) else {
+            continue;
+        };
+
+        let Some(name) = path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+
+        checked += 1;
+
+        if accepted_breaks.contains(&name) {
+            continue;
+        }
+
+        let Some(sample) = samples.get(&name) else {
+            mismatches.push(VectorMismatch::SampleMissing { name });
+            continue;
+        };
+
+        let buffer = std::fs::read(&path)?;
+
+        match format.deserialize::<EndPointMessage>(&buffer) {
+            Ok(message) if message == *sample => {}
+            Ok(_) => mismatches.push(VectorMismatch::ValueChanged { name: name.clone() }),
+            Err(err) => {
+                mismatches.push(VectorMismatch::Undecodable {
+                    name: name.clone(),
+                    error: err.to_string(),
+                });
+                continue;
+            }
+        }
+
+        if format.serialize(sample)? != buffer {
+            mismatches.push(VectorMismatch::EncodingChanged { name });
+        }
+    }
+
+    if checked == 0 {
+        return Err(core_error!("no vector in {:?}", dir));
+    }
+
+    Ok(mismatches)
+}
+
+fn extension(format: WireFormat) -> &'static str {
+    match format {
+        WireFormat::Bincode => "bin",
+        WireFormat::Cbor => "cbor",
+    }
+}
+
+fn format_description(format: WireFormat) -> &'static str {
+    match format {
+        WireFormat::Bincode => {
+            "Bincode (`.bin`) with little endian and varint encoding. Enum variants are \
+            encoded as varint of their index, followed by their fields in order."
+        }
+        WireFormat::Cbor => {
+            "CBOR (`.cbor`). Structs are maps keyed by field names, enum variants are \
+            maps of their names to their fields, and unit variants are their names."
+        }
+    }
+}
+
+// call replies are serialized in the wire format of the connection
+fn call_reply<T: Serialize>(format: WireFormat, reply: T) -> CoreResult<EndPointMessage> {
+    let reply_bytes = format.serialize(&Ok::<T, String>(reply))?;
+    Ok(EndPointMessage::CallReply(0, reply_bytes))
+}
+
+// it doesn't compile when a variant is added without a name here, which is the reminder
+// to add a sample of it
+fn variant_name(message: &EndPointMessage) -> String {
+    let name = match message {
+        EndPointMessage::Error => "error",
+        EndPointMessage::CallRequest(_, req) => {
+            return format!(
+                "call_request_{}",
+                match req {
+                    EndPointCallRequest::VisitDirectoryRequest(_) => "visit_directory",
+                    EndPointCallRequest::SendFileRequest(_) => "send_file",
+                    EndPointCallRequest::DownloadFileRequest(_) => "download_file",
+                    EndPointCallRequest::SwitchMonitorRequest(_) => "switch_monitor",
+                    EndPointCallRequest::ElevatePermissionsRequest(_) => "elevate_permissions",
+                    EndPointCallRequest::SwitchAudioCaptureRequest(_) => "switch_audio_capture",
+                    EndPointCallRequest::SwitchAudioProcessingRequest(_) => {
+                        "switch_audio_processing"
+                    }
+                    EndPointCallRequest::SystemActionRequest(_) => "system_action",
+                    EndPointCallRequest::FileManifestRequest(_) => "file_manifest",
+                    EndPointCallRequest::FsOperationRequest(_) => "fs_operation",
+                    EndPointCallRequest::SwitchStreamQualityRequest(_) => "switch_stream_quality",
+                    EndPointCallRequest::SwitchHostPriorityRequest(_) => "switch_host_priority",
+                    EndPointCallRequest::SwitchResolutionRequest(_) => "switch_resolution",
+                    EndPointCallRequest::SystemVolumeRequest(_) => "system_volume",
+                    EndPointCallRequest::ProcessListRequest(_) => "process_list",
+                    EndPointCallRequest::ProcessKillRequest(_) => "process_kill",
+                    EndPointCallRequest::MagnifyRequest(_) => "magnify",
+                    EndPointCallRequest::InviteObserverRequest(_) => "invite_observer",
+                    EndPointCallRequest::BlockLocalInputRequest(_) => "block_local_input",
+                    EndPointCallRequest::LoginRequest(_) => "login",
+                }
+            );
+        }
+        EndPointMessage::CallReply(..) => "call_reply",
+        EndPointMessage::NegotiateDesktopParamsRequest(_) => "negotiate_desktop_params_request",
+        EndPointMessage::NegotiateDesktopParamsResponse(_) => "negotiate_desktop_params_response",
+        EndPointMessage::NegotiateFinishedRequest(_) => "negotiate_finished_request",
+        EndPointMessage::VideoFrame(_) => "video_frame",
+        EndPointMessage::AudioFrame(_) => "audio_frame",
+        EndPointMessage::InputCommand(_) => "input_command",
+        EndPointMessage::FileTransferBlock(_) => "file_transfer_block",
+        EndPointMessage::FileTransferError(_) => "file_transfer_error",
+        EndPointMessage::Cursor(_) => "cursor",
+        EndPointMessage::PowerSaving(_) => "power_saving",
+        EndPointMessage::PluginMessage(_) => "plugin_message",
+        EndPointMessage::PermissionsChanged(_) => "permissions_changed",
+        EndPointMessage::InputBlocked(_) => "input_blocked",
+        EndPointMessage::FileDeltaBlock(_) => "file_delta_block",
+        EndPointMessage::ChannelClosed(_) => "channel_closed",
+        EndPointMessage::BandwidthLimit(_) => "bandwidth_limit",
+        EndPointMessage::CaptureInterrupted(_) => "capture_interrupted",
+        EndPointMessage::KeyFrameRequest => "key_frame_request",
+        EndPointMessage::ClockSyncRequest(_) => "clock_sync_request",
+        EndPointMessage::ClockSyncReply(_) => "clock_sync_reply",
+        EndPointMessage::Metadata(_) => "metadata",
+        EndPointMessage::MagnifierFrame(_) => "magnifier_frame",
+        EndPointMessage::LocalInputBlocked(_) => "local_input_blocked",
+    };
+
+    name.to_string()
+}
@@ -0,0 +1,270 @@
+//! Passive endpoint serves at most `max_sessions` sessions at the same time, visits
+//! beyond that wait in a queue and are admitted in order once a session ends. Host user
+//! can reorder or reject the waiting visits.
+
+use super::id::EndPointID;
+use crate::{
+    api::{
+        event::serialize_endpoint_id,
+        notification::{self, Notification},
+    },
+    core_error,
+    error::CoreResult,
+};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::{oneshot, watch};
+
+/// The longest time a visit waits in the queue.
+pub const QUEUE_TIMEOUT: Duration = Duration::from_secs(300);
+
+// zero means unlimited
+static MAX_SESSIONS: AtomicUsize = AtomicUsize::new(0);
+
+static QUEUE: Lazy<Mutex<QueueState>> = Lazy::new(|| {
+    Mutex::new(QueueState {
+        serving: 0,
+        waiting: VecDeque::new(),
+    })
+});
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueuedVisit {
+    pub id: String,
+    #[serde(rename = "remote", serialize_with = "serialize_endpoint_id")]
+    pub endpoint_id: EndPointID,
+    pub queued_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueRejection {
+    Rejected,
+    Timeout,
+}
+
+/// Occupies one of `max_sessions` until the session which owns it is dropped.
+#[derive(Debug)]
+pub struct SessionSlot {
+    _private: (),
+}
+
+impl Drop for SessionSlot {
+    fn drop(&mut self) {
+        {
+            let mut state = QUEUE.lock().unwrap();
+            state.serving = state.serving.saturating_sub(1);
+        }
+
+        admit_waiting();
+    }
+}
+
+struct QueueState {
+    serving: usize,
+    waiting: VecDeque<WaitingVisit>,
+}
+
+impl QueueState {
+    fn has_free_slot(&self) -> bool {
+        let max_sessions = MAX_SESSIONS.load(Ordering::SeqCst);
+        max_sessions == 0 || self.serving < max_sessions
+    }
+
+    fn update_positions(&self) {
+        for (index, waiting) in self.waiting.iter().enumerate() {
+            if *waiting.position_tx.borrow() != index + 1 {
+                waiting.position_tx.send_replace(index + 1);
+            }
+        }
+    }
+
+    fn remove(&mut self, id: &str) -> Option<WaitingVisit> {
+        let index = self
+            .waiting
+            .iter()
+            .position(|waiting| waiting.visit.id == id)?;
+
+        let waiting = self.waiting.remove(index);
+        self.update_positions();
+        waiting
+    }
+}
+
+struct WaitingVisit {
+    visit: QueuedVisit,
+    decision_tx: oneshot::Sender<Option<SessionSlot>>,
+    // 1-based position in the queue
+    position_tx: watch::Sender<usize>,
+}
+
+/// Set the max count of sessions which are served at the same time, zero means
+/// unlimited. Waiting visits are admitted at once if it's raised.
+pub fn set_max_sessions(max_sessions: usize) {
+    MAX_SESSIONS.store(max_sessions, Ordering::SeqCst);
+    admit_waiting();
+}
+
+pub fn queued_visits() -> Vec<QueuedVisit> {
+    QUEUE
+        .lock()
+        .unwrap()
+        .waiting
+        .iter()
+        .map(|waiting| waiting.visit.clone())
+        .collect()
+}
+
+/// Move the waiting visit to `position` of the queue, zero is the head.
+pub fn move_queued_visit(id: &str, position: usize) -> CoreResult<()> {
+    {
+        let mut state = QUEUE.lock().unwrap();
+        let waiting = state
+            .remove(id)
+            .ok_or(core_error!("queued visit not exists or admitted"))?;
+
+        let position = position.min(state.waiting.len());
+        state.waiting.insert(position, waiting);
+        state.update_positions();
+    }
+
+    publish_queue_changed();
+    Ok(())
+}
+
+pub fn reject_queued_visit(id: &str) -> CoreResult<()> {
+    let waiting = QUEUE
+        .lock()
+        .unwrap()
+        .remove(id)
+        .ok_or(core_error!("queued visit not exists or admitted"))?;
+
+    tracing::info!(endpoint_id = ?waiting.visit.endpoint_id, "reject queued visit");
+
+    let _ = waiting.decision_tx.send(None);
+    publish_queue_changed();
+    Ok(())
+}
+
+/// Take a session slot for the visit of `endpoint_id`, it waits in the queue when all
+/// slots are taken and `on_position` is called whenever its position changes.
+pub(crate) async fn acquire_session_slot<F, Fut>(
+    endpoint_id: EndPointID,
+    mut on_position: F,
+) -> Result<SessionSlot, QueueRejection>
+where
+    F: FnMut(usize) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let id = uuid::Uuid::new_v4().to_string();
+    let (decision_tx, mut decision_rx) = oneshot::channel();
+    let (position_tx, mut position_rx) = watch::channel(0);
+
+    {
+        let mut state = QUEUE.lock().unwrap();
+        if state.waiting.is_empty() && state.has_free_slot() {
+            state.serving += 1;
+            return Ok(SessionSlot { _private: () });
+        }
+
+        state.waiting.push_back(WaitingVisit {
+            visit: QueuedVisit {
+                id: id.clone(),
+                endpoint_id,
+                queued_at: chrono::Utc::now().timestamp(),
+            },
+            decision_tx,
+            position_tx,
+        });
+        state.update_positions();
+    }
+
+    tracing::info!(?endpoint_id, "visit is queued since sessions are full");
+    publish_queue_changed();
+
+    let wait = async {
+        loop {
+            tokio::select! {
+                decision = &mut decision_rx => {
+                    return match decision {
+                        Ok(Some(session_slot)) => Ok(session_slot),
+                        _ => Err(QueueRejection::Rejected),
+                    };
+                }
+                Ok(()) = position_rx.changed() => {
+                    let position = *position_rx.borrow_and_update();
+                    on_position(position).await;
+                }
+            }
+        }
+    };
+
+    let result = tokio::time::timeout(QUEUE_TIMEOUT, wait).await;
+
+    // it's still in the queue when timeout
+    let removed = QUEUE.lock().unwrap().remove(&id).is_some();
+    if removed {
+        publish_queue_changed();
+    }
+
+    match result {
+        Ok(result) => result,
+        Err(_) => match decision_rx.try_recv() {
+            // admitted at the last moment
+            Ok(Some(session_slot)) => Ok(session_slot),
+            _ => {
+                tracing::info!(?endpoint_id, "queued visit timeout");
+                Err(QueueRejection::Timeout)
+            }
+        },
+    }
+}
+
+fn admit_waiting() {
+    let admitted: Vec<WaitingVisit> = {
+        let mut state = QUEUE.lock().unwrap();
+        let mut admitted = Vec::new();
+
+        while state.has_free_slot() {
+            let Some(waiting) = state.waiting.pop_front() else {
+                break;
+            };
+
+            state.serving += 1;
+            admitted.push(waiting);
+        }
+
+        if !admitted.is_empty() {
+            state.update_positions();
+        }
+
+        admitted
+    };
+
+    if admitted.is_empty() {
+        return;
+    }
+
+    for waiting in admitted {
+        tracing::info!(endpoint_id = ?waiting.visit.endpoint_id, "admit queued visit");
+
+        // the slot goes back when visitor has gone, it's released outside of the lock
+        let _ = waiting.decision_tx.send(Some(SessionSlot { _private: () }));
+    }
+
+    publish_queue_changed();
+}
+
+fn publish_queue_changed() {
+    notification::publish(Notification::VisitQueueChanged {
+        visits: queued_visits(),
+    });
+}
@@ -0,0 +1,185 @@
+//! Registry of the live sessions of this endpoint, both the ones it visits and the ones
+//! visiting it. While anyone subscribes, like the tray menu of app, the summary of them
+//! with their traffic is published periodically, so sessions can be watched and closed
+//! without asking windows of them.
+
+use super::{client::EndPointClient, id::EndPointID};
+use crate::{api::event::serialize_endpoint_id, core_error, error::CoreResult};
+use async_broadcast::{InactiveReceiver, Receiver, Sender};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, RwLock, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+// sessions keyed by session id, they're removed once their message loop exits
+static SESSIONS: Lazy<RwLock<HashMap<String, Entry>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+// subscribers only care about the latest summary
+static SUMMARY_CHANNEL: Lazy<(Sender<SessionsSummary>, InactiveReceiver<SessionsSummary>)> =
+    Lazy::new(|| {
+        let (mut tx, rx) = async_broadcast::broadcast(1);
+        tx.set_overflow(true);
+        (tx, rx.deactivate())
+    });
+
+static SAMPLING: AtomicBool = AtomicBool::new(false);
+
+struct Entry {
+    client: Weak<EndPointClient>,
+    active: bool,
+}
+
+/// Bytes of the messages which the session sent and received, shared by the clones of
+/// its client.
+#[derive(Debug, Default)]
+pub(crate) struct Traffic {
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+impl Traffic {
+    pub fn record_sent(&self, bytes: usize) {
+        self.sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_received(&self, bytes: usize) {
+        self.received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn total(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed) + self.received.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SessionSummary {
+    pub session_id: String,
+    #[serde(rename = "remote", serialize_with = "serialize_endpoint_id")]
+    pub endpoint_id: EndPointID,
+    /// Whether this endpoint visits the remote one.
+    pub active: bool,
+    /// Traffic of both directions in the last sample interval.
+    pub bit_rate_kbps: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct SessionsSummary {
+    pub sessions: Vec<SessionSummary>,
+}
+
+impl SessionsSummary {
+    pub fn total_bit_rate_kbps(&self) -> u64 {
+        self.sessions
+            .iter()
+            .map(|session| session.bit_rate_kbps)
+            .sum()
+    }
+}
+
+pub(crate) fn register(client: &Arc<EndPointClient>, active: bool) {
+    SESSIONS.write().unwrap().insert(
+        client.session_id().to_string(),
+        Entry {
+            client: Arc::downgrade(client),
+            active,
+        },
+    );
+}
+
+pub(crate) fn unregister(session_id: &str) {
+    SESSIONS.write().unwrap().remove(session_id);
+}
+
+pub(crate) fn find(session_id: &str) -> Option<Arc<EndPointClient>> {
+    SESSIONS
+        .read()
+        .unwrap()
+        .get(session_id)
+        .and_then(|entry| entry.client.upgrade())
+}
+
+/// Close the session, its windows see it closed like it's closed by themselves.
+pub fn close_session(session_id: &str) -> CoreResult<()> {
+    let client = find(session_id).ok_or(core_error!("session not exists"))?;
+    tracing::info!(?session_id, "close session on request");
+    client.close();
+    Ok(())
+}
+
+/// Subscribe the summary of live sessions, it's published every couple of seconds while
+/// any subscriber is alive.
+pub fn subscribe() -> Receiver<SessionsSummary> {
+    let rx = SUMMARY_CHANNEL.1.activate_cloned();
+
+    if !SAMPLING.swap(true, Ordering::SeqCst) {
+        if let Err(err) = std::thread::Builder::new()
+            .name(String::from("session_summary"))
+            .spawn(serve_sampling)
+        {
+            tracing::error!(?err, "spawn session summary thread failed");
+            SAMPLING.store(false, Ordering::SeqCst);
+        }
+    }
+
+    rx
+}
+
+fn serve_sampling() {
+    // traffic totals of the last sample, keyed by session id
+    let mut last_totals: HashMap<String, u64> = HashMap::new();
+    let mut last_sampled = Instant::now();
+
+    loop {
+        std::thread::sleep(SAMPLE_INTERVAL);
+
+        if SUMMARY_CHANNEL.0.receiver_count() == 0 {
+            SAMPLING.store(false, Ordering::SeqCst);
+
+            // a subscriber may come between the check and the store, it expects this
+            // thread keeps running since it saw the flag set
+            if SUMMARY_CHANNEL.0.receiver_count() == 0 || SAMPLING.swap(true, Ordering::SeqCst) {
+                return;
+            }
+        }
+
+        let elapsed = last_sampled.elapsed().as_secs_f64();
+        last_sampled = Instant::now();
+
+        let mut totals = HashMap::new();
+        let mut sessions = Vec::new();
+
+        for (session_id, entry) in SESSIONS.read().unwrap().iter() {
+            let Some(client) = entry.client.upgrade() else {
+                continue;
+            };
+
+            let total = client.traffic().total();
+            let last_total = last_totals.get(session_id).copied().unwrap_or(total);
+            totals.insert(session_id.clone(), total);
+
+            sessions.push(SessionSummary {
+                session_id: session_id.clone(),
+                endpoint_id: client.endpoint_id(),
+                active: entry.active,
+                bit_rate_kbps: ((total - last_total) as f64 * 8.0 / elapsed / 1000.0) as u64,
+            });
+        }
+
+        last_totals = totals;
+
+        // keep the order stable, so menus listing them don't shuffle
+        sessions.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+
+        let _ = SUMMARY_CHANNEL
+            .0
+            .try_broadcast(SessionsSummary { sessions });
+    }
+}
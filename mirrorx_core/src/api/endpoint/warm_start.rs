@@ -0,0 +1,152 @@
+//! Creating the duplicator and opening the encoder take seconds, they used to start only
+//! after negotiation, so the remote desktop stayed black for a while. Passive endpoint
+//! prepares them speculatively once a desktop visit request arrives, while the user is
+//! prompted and the connection is negotiated, and capture process picks them up when the
+//! session starts.
+//!
+//! Only the default monitor is prepared, capture of other monitors or span canvas starts
+//! cold as before. The encode context is of the preferred H.264 encoder, which viewers
+//! ask for first, sessions negotiating other encoders drop it. Prepared contexts which no
+//! session takes are released after a while.
+
+use crate::{
+    api::endpoint::message::VideoCodec,
+    component::{
+        codec_probe::select_encoder,
+        desktop::Duplicator,
+        video_encoder::{config::probed::ProbedEncoderConfig, encoder::WarmEncodeContext},
+    },
+    core_error,
+};
+use once_cell::sync::Lazy;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// the visit prompt waits for the user, prepared contexts outlive it a little
+const WARM_CONTEXT_TTL: Duration = Duration::from_secs(90);
+
+static WARM_CONTEXT: Lazy<Mutex<Option<WarmContext>>> = Lazy::new(|| Mutex::new(None));
+
+#[cfg(target_os = "windows")]
+pub type WarmDuplicator = (Duplicator, String);
+
+// duplicator of macOS pushes frames into the channel which it's created with
+#[cfg(target_os = "macos")]
+pub type WarmDuplicator = (
+    Duplicator,
+    String,
+    tokio::sync::mpsc::Receiver<crate::component::frame::DesktopEncodeFrame>,
+);
+
+struct WarmContext {
+    duplicator: Option<WarmDuplicator>,
+    encode_context: Option<WarmEncodeContext>,
+    prepared_at: Instant,
+}
+
+/// Prepare the duplicator of the default monitor and its encode context in background,
+/// it does nothing when they're prepared already.
+pub fn prewarm() {
+    if WARM_CONTEXT.lock().unwrap().is_some() {
+        return;
+    }
+
+    crate::utility::spawn::spawn_blocking(|| {
+        let instant = Instant::now();
+
+        let duplicator = match create_duplicator() {
+            Ok(duplicator) => duplicator,
+            Err(err) => {
+                tracing::warn!(?err, "prewarm duplicator failed");
+                return;
+            }
+        };
+
+        let (width, height, color_space) = duplicator.0.frame_format();
+        let encode_context = match select_encoder(&VideoCodec::H264, width as u16, height as u16)
+            .ok_or(core_error!("no encoder supports the monitor"))
+            .and_then(|encoder| ProbedEncoderConfig::from_support(&encoder))
+            .and_then(|config| WarmEncodeContext::new(width, height, color_space, &config))
+        {
+            Ok(encode_context) => Some(encode_context),
+            Err(err) => {
+                tracing::warn!(?err, "prewarm encode context failed");
+                None
+            }
+        };
+
+        tracing::info!(elapsed = ?instant.elapsed(), "capture pipeline prewarmed");
+
+        let mut warm_context = WARM_CONTEXT.lock().unwrap();
+        if warm_context.is_some() {
+            // prepared by another visit request meanwhile
+            return;
+        }
+
+        *warm_context = Some(WarmContext {
+            duplicator: Some(duplicator),
+            encode_context,
+            prepared_at: Instant::now(),
+        });
+        drop(warm_context);
+
+        std::thread::sleep(WARM_CONTEXT_TTL);
+        release_expired();
+    });
+}
+
+/// Take the prepared duplicator if it duplicates `monitor_id`, None means the default
+/// monitor.
+pub fn take_duplicator(monitor_id: Option<&str>) -> Option<WarmDuplicator> {
+    let mut warm_context = WARM_CONTEXT.lock().unwrap();
+    let context = warm_context.as_mut()?;
+
+    let prepared_monitor_id = &context.duplicator.as_ref()?.1;
+    if monitor_id.map_or(false, |monitor_id| monitor_id != prepared_monitor_id) {
+        return None;
+    }
+
+    let duplicator = context.duplicator.take();
+    release_if_drained(&mut warm_context);
+    duplicator
+}
+
+pub fn take_encode_context() -> Option<WarmEncodeContext> {
+    let mut warm_context = WARM_CONTEXT.lock().unwrap();
+    let encode_context = warm_context.as_mut()?.encode_context.take();
+    release_if_drained(&mut warm_context);
+    encode_context
+}
+
+#[cfg(target_os = "windows")]
+fn create_duplicator() -> crate::error::CoreResult<WarmDuplicator> {
+    Duplicator::new(None)
+}
+
+#[cfg(target_os = "macos")]
+fn create_duplicator() -> crate::error::CoreResult<WarmDuplicator> {
+    let (capture_frame_tx, capture_frame_rx) = tokio::sync::mpsc::channel(180);
+    let (duplicator, monitor_id) = Duplicator::new(None, capture_frame_tx)?;
+    Ok((duplicator, monitor_id, capture_frame_rx))
+}
+
+// the slot is emptied once both contexts are taken, so the next visit prepares again
+fn release_if_drained(warm_context: &mut Option<WarmContext>) {
+    if let Some(context) = warm_context {
+        if context.duplicator.is_none() && context.encode_context.is_none() {
+            *warm_context = None;
+        }
+    }
+}
+
+fn release_expired() {
+    let mut warm_context = WARM_CONTEXT.lock().unwrap();
+    if let Some(context) = warm_context.as_ref() {
+        if context.prepared_at.elapsed() >= WARM_CONTEXT_TTL {
+            tracing::info!("release unused prewarmed capture pipeline");
+            *warm_context = None;
+        }
+    }
+}
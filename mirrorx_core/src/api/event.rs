@@ -0,0 +1,182 @@
+//! Events which are pushed to the frontend. Every event the frontend listens to from core
+//! is a variant here, its topic and payload are defined in one place and the app only
+//! dispatches them, so a new event needs neither a new payload struct nor a new emit call.
+
+use super::{
+    endpoint::{
+        client::state::SessionState, id::EndPointID, permission::Permissions, queue::QueuedVisit,
+    },
+    signaling::{fingerprint::PeerTrust, SignalingState},
+};
+use crate::component::{
+    fs::receive_policy::QuarantinedFile, progress::Progress, quality_alert::QualityAlert,
+};
+use async_broadcast::{InactiveReceiver, Receiver, Sender};
+use once_cell::sync::Lazy;
+use serde::{Serialize, Serializer};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum CoreEvent {
+    VisitRequest {
+        // None when the visit is accepted already
+        request_id: Option<String>,
+        #[serde(serialize_with = "serialize_device_id")]
+        active_device_id: i64,
+        #[serde(serialize_with = "serialize_device_id")]
+        passive_device_id: i64,
+        #[serde(rename = "resource_type", serialize_with = "serialize_resource_type")]
+        visit_desktop: bool,
+    },
+    VisitRequestCanceled {
+        request_id: String,
+    },
+    PermissionRequest {
+        request_id: String,
+        #[serde(rename = "remote", serialize_with = "serialize_endpoint_id")]
+        endpoint_id: EndPointID,
+        #[serde(serialize_with = "serialize_permissions")]
+        permissions: Permissions,
+    },
+    LocalInputBlockRequest {
+        request_id: String,
+        #[serde(rename = "remote", serialize_with = "serialize_endpoint_id")]
+        endpoint_id: EndPointID,
+    },
+    PeerVerified {
+        domain: String,
+        #[serde(serialize_with = "serialize_device_id")]
+        remote_device_id: i64,
+        fingerprint: String,
+        short_auth_string: String,
+        trust: PeerTrust,
+    },
+    VisitQueueChanged {
+        visits: Vec<QueuedVisit>,
+    },
+    FileQuarantined(QuarantinedFile),
+    VisitQueuePosition {
+        #[serde(serialize_with = "serialize_device_id")]
+        active_device_id: i64,
+        #[serde(serialize_with = "serialize_device_id")]
+        passive_device_id: i64,
+        position: u32,
+    },
+    SignalingState {
+        domain_id: i64,
+        #[serde(flatten)]
+        state: SignalingState,
+    },
+    SessionState {
+        session_id: String,
+        #[serde(rename = "remote", serialize_with = "serialize_endpoint_id")]
+        endpoint_id: EndPointID,
+        #[serde(flatten)]
+        state: SessionState,
+    },
+    QualityAlert {
+        #[serde(rename = "remote", serialize_with = "serialize_endpoint_id")]
+        endpoint_id: EndPointID,
+        #[serde(flatten)]
+        alert: QualityAlert,
+    },
+    Progress(Progress),
+}
+
+// progress is published several times a second by every running operation
+static CORE_EVENT_CHANNEL: Lazy<(Sender<CoreEvent>, InactiveReceiver<CoreEvent>)> =
+    Lazy::new(|| {
+        let (mut tx, rx) = async_broadcast::broadcast(256);
+        tx.set_overflow(true);
+        (tx, rx.deactivate())
+    });
+
+/// Publish the event to all active subscribers, it's dropped silently when nobody
+/// subscribes.
+pub fn publish(event: CoreEvent) {
+    let _ = CORE_EVENT_CHANNEL.0.try_broadcast(event);
+}
+
+pub fn subscribe() -> Receiver<CoreEvent> {
+    CORE_EVENT_CHANNEL.1.activate_cloned()
+}
+
+impl CoreEvent {
+    /// Name of the frontend event, namespaced by the module which it comes from.
+    pub fn topic(&self) -> &'static str {
+        match self {
+            CoreEvent::VisitRequest { .. } => "core/visit/request",
+            CoreEvent::VisitRequestCanceled { .. } => "core/visit/request_canceled",
+            CoreEvent::PermissionRequest { .. } => "core/permission/request",
+            CoreEvent::LocalInputBlockRequest { .. } => "core/input/block_request",
+            CoreEvent::PeerVerified { .. } => "core/peer/verified",
+            CoreEvent::VisitQueueChanged { .. } => "core/visit/queue_changed",
+            CoreEvent::VisitQueuePosition { .. } => "core/visit/queue_position",
+            CoreEvent::FileQuarantined(_) => "core/fs/quarantined",
+            CoreEvent::SignalingState { .. } => "core/signaling/state",
+            CoreEvent::SessionState { .. } => "core/session/state",
+            CoreEvent::QualityAlert { .. } => "core/session/quality_alert",
+            CoreEvent::Progress(_) => "core/progress",
+        }
+    }
+
+    /// Whether user has to make a decision on it, window should be brought to front.
+    pub fn is_prompt(&self) -> bool {
+        match self {
+            CoreEvent::VisitRequest { request_id, .. } => request_id.is_some(),
+            CoreEvent::PermissionRequest { .. }
+            | CoreEvent::LocalInputBlockRequest { .. }
+            | CoreEvent::FileQuarantined(_) => true,
+            CoreEvent::VisitRequestCanceled { .. }
+            | CoreEvent::PeerVerified { .. }
+            | CoreEvent::VisitQueueChanged { .. }
+            | CoreEvent::VisitQueuePosition { .. }
+            | CoreEvent::SignalingState { .. }
+            | CoreEvent::SessionState { .. }
+            | CoreEvent::QualityAlert { .. }
+            | CoreEvent::Progress(_) => false,
+        }
+    }
+}
+
+// device id in the form which user sees, like `12-3456-7890`
+fn format_device_id(device_id: i64) -> String {
+    let mut device_id = format!("{device_id:0>10}");
+    device_id.insert(2, '-');
+    device_id.insert(7, '-');
+    device_id
+}
+
+fn format_endpoint_id(endpoint_id: &EndPointID) -> String {
+    match endpoint_id {
+        EndPointID::DeviceID {
+            remote_device_id, ..
+        } => format_device_id(*remote_device_id),
+        EndPointID::LANID { remote_ip, .. } => remote_ip.to_string(),
+    }
+}
+
+fn serialize_device_id<S: Serializer>(device_id: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_device_id(*device_id))
+}
+
+pub(crate) fn serialize_endpoint_id<S: Serializer>(
+    endpoint_id: &EndPointID,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_endpoint_id(endpoint_id))
+}
+
+fn serialize_resource_type<S: Serializer>(
+    visit_desktop: &bool,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(if *visit_desktop { "desktop" } else { "files" })
+}
+
+fn serialize_permissions<S: Serializer>(
+    permissions: &Permissions,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    permissions.names().serialize(serializer)
+}
@@ -1,4 +1,6 @@
 pub mod config;
 pub mod endpoint;
+pub mod event;
+pub mod notification;
 pub mod signaling;
 pub mod system;
@@ -0,0 +1,94 @@
+use super::{
+    endpoint::{id::EndPointID, permission::Permissions, queue::QueuedVisit},
+    signaling::{fingerprint::PeerTrust, SignalingState},
+};
+use crate::component::{fs::receive_policy::QuarantinedFile, quality_alert::QualityAlert};
+use async_broadcast::{InactiveReceiver, Receiver, Sender};
+use once_cell::sync::Lazy;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub enum Notification {
+    VisitRequest {
+        // Some when host user has to accept or deny the visit
+        request_id: Option<String>,
+        active_device_id: i64,
+        passive_device_id: i64,
+        visit_desktop: bool,
+    },
+    FileTransferSucceeded {
+        id: String,
+        path: PathBuf,
+    },
+    FileTransferFailed {
+        id: String,
+        path: PathBuf,
+    },
+    FileQuarantined {
+        file: QuarantinedFile,
+    },
+    SessionDisconnected {
+        endpoint_id: EndPointID,
+    },
+    QualityAlert {
+        endpoint_id: EndPointID,
+        alert: QualityAlert,
+    },
+    RemotePowerSaving {
+        endpoint_id: EndPointID,
+        on_battery: bool,
+        power_saver: bool,
+        max_frame_rate: Option<u8>,
+    },
+    PermissionElevationRequest {
+        request_id: String,
+        endpoint_id: EndPointID,
+        permissions: Permissions,
+    },
+    // controller asks to block the local input of host, see `component::local_input_block`
+    LocalInputBlockRequest {
+        request_id: String,
+        endpoint_id: EndPointID,
+    },
+    PeerVerified {
+        domain: String,
+        local_device_id: i64,
+        remote_device_id: i64,
+        fingerprint: String,
+        short_auth_string: String,
+        trust: PeerTrust,
+    },
+    VisitQueueChanged {
+        visits: Vec<QueuedVisit>,
+    },
+    VisitQueuePosition {
+        active_device_id: i64,
+        passive_device_id: i64,
+        position: u32,
+    },
+    VisitRequestCanceled {
+        request_id: String,
+    },
+    SignalingStateChanged {
+        domain_id: i64,
+        state: SignalingState,
+    },
+}
+
+static NOTIFICATION_CHANNEL: Lazy<(Sender<Notification>, InactiveReceiver<Notification>)> =
+    Lazy::new(|| {
+        let (mut tx, rx) = async_broadcast::broadcast(64);
+        tx.set_overflow(true);
+        (tx, rx.deactivate())
+    });
+
+/// Publish a notification to all active subscribers, the oldest one will be
+/// overwritten when channel is full and it will be dropped silently when nobody
+/// subscribes.
+pub fn publish(notification: Notification) {
+    let _ = NOTIFICATION_CHANNEL.0.try_broadcast(notification);
+}
+
+pub fn subscribe() -> Receiver<Notification> {
+    NOTIFICATION_CHANNEL.1.activate_cloned()
+}
@@ -0,0 +1,154 @@
+//! Every device has a long-term identity key which signs the ephemeral exchange key of
+//! each visit, so the signaling server can't replace the exchange key with its own
+//! unnoticed. The fingerprint of remote identity is trusted on first use and a changed
+//! one is warned, both endpoints also derive a short auth string from the agreement
+//! for users to compare out of band.
+
+use crate::{
+    api::{
+        config::LocalStorage,
+        notification::{self, Notification},
+    },
+    core_error,
+    error::CoreResult,
+};
+use base64::{engine::general_purpose::STANDARD as base64_standard, Engine};
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// The identity which remote device proved in the key exchange.
+#[derive(Debug, Clone)]
+pub struct PeerIdentity {
+    pub public_key: Vec<u8>,
+    pub short_auth_string: String,
+}
+
+impl PeerIdentity {
+    pub fn fingerprint(&self) -> String {
+        identity_fingerprint(&self.public_key)
+    }
+}
+
+/// Like `3F2A:91C0:...`, the leading 16 bytes of SHA-256 of the identity public key.
+pub fn identity_fingerprint(public_key: &[u8]) -> String {
+    let digest = Sha256::digest(public_key);
+
+    digest[..16]
+        .chunks(2)
+        .map(|chunk| format!("{:02X}{:02X}", chunk[0], chunk[1]))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PeerTrust {
+    FirstUse,
+    Trusted,
+    Changed { previous: String },
+}
+
+/// Load the identity key of this device, it's created at the first time.
+pub fn load_identity_key(storage: &LocalStorage) -> CoreResult<Ed25519KeyPair> {
+    let pkcs8 = match storage.kv().get_identity_key()? {
+        Some(pkcs8) => base64_standard.decode(pkcs8)?,
+        None => {
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new())?;
+            storage
+                .kv()
+                .set_identity_key(&base64_standard.encode(pkcs8.as_ref()))?;
+            pkcs8.as_ref().to_vec()
+        }
+    };
+
+    Ed25519KeyPair::from_pkcs8(&pkcs8)
+        .map_err(|err| core_error!("load identity key failed ({})", err))
+}
+
+pub(crate) fn sign_exchange_key(
+    identity_key: &Ed25519KeyPair,
+    exchange_public_key: &[u8],
+    exchange_nonce: &[u8],
+) -> Vec<u8> {
+    identity_key
+        .sign(&[exchange_public_key, exchange_nonce].concat())
+        .as_ref()
+        .to_vec()
+}
+
+pub(crate) fn verify_exchange_key(
+    identity_public_key: &[u8],
+    exchange_public_key: &[u8],
+    exchange_nonce: &[u8],
+    signature: &[u8],
+) -> bool {
+    UnparsedPublicKey::new(&ED25519, identity_public_key)
+        .verify(&[exchange_public_key, exchange_nonce].concat(), signature)
+        .is_ok()
+}
+
+pub(crate) fn identity_public_key(identity_key: &Ed25519KeyPair) -> &[u8] {
+    identity_key.public_key().as_ref()
+}
+
+/// Six digits like `042 917`, endpoints with the same agreement get the same one.
+pub(crate) fn derive_short_auth_string(
+    key_material: &[u8],
+    active_exchange_nonce: &[u8],
+    passive_exchange_nonce: &[u8],
+) -> String {
+    let digest = Sha256::new()
+        .chain_update(b"mirrorx short auth string")
+        .chain_update(key_material)
+        .chain_update(active_exchange_nonce)
+        .chain_update(passive_exchange_nonce)
+        .finalize();
+
+    let value = u32::from_le_bytes([digest[0], digest[1], digest[2], digest[3]]) % 1_000_000;
+
+    format!("{:03} {:03}", value / 1000, value % 1000)
+}
+
+/// Check the identity of remote device against the trusted fingerprint, it's trusted
+/// if there's none. The changed fingerprint is kept untrusted until user trusts it.
+pub fn verify_peer(
+    storage: &LocalStorage,
+    domain: &str,
+    local_device_id: i64,
+    remote_device_id: i64,
+    peer: &PeerIdentity,
+) -> CoreResult<PeerTrust> {
+    let fingerprint = peer.fingerprint();
+
+    let trust = match storage.peer().get_fingerprint(domain, remote_device_id)? {
+        Some(previous) if previous == fingerprint => PeerTrust::Trusted,
+        Some(previous) => {
+            tracing::warn!(
+                domain,
+                remote_device_id,
+                %previous,
+                current = %fingerprint,
+                "PEER IDENTITY CHANGED, the session may be intercepted"
+            );
+            PeerTrust::Changed { previous }
+        }
+        None => {
+            storage
+                .peer()
+                .set_fingerprint(domain, remote_device_id, &fingerprint)?;
+            PeerTrust::FirstUse
+        }
+    };
+
+    notification::publish(Notification::PeerVerified {
+        domain: domain.to_string(),
+        local_device_id,
+        remote_device_id,
+        fingerprint,
+        short_auth_string: peer.short_auth_string.clone(),
+        trust: trust.clone(),
+    });
+
+    Ok(trust)
+}
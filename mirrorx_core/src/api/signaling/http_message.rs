@@ -53,3 +53,12 @@ pub struct VisitResponse {
     pub visit_credentials: String,
     pub result: Result<String, VisitFailureReason>,
 }
+
+#[derive(Debug, Serialize)]
+pub struct DirtyShutdownRequest {
+    pub device_id: i64,
+    pub remote_device_ids: Vec<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DirtyShutdownResponse {}
@@ -0,0 +1,465 @@
+//! The session keys of visit are agreed by ephemeral X25519 which is authenticated by
+//! SPAKE2 with the device password:
+//!
+//! 1. active device sends its PAKE message and exchange public key through signaling
+//!    server
+//! 2. passive device replies its PAKE message, exchange public key and the key
+//!    confirmation of the transcript
+//! 3. active device verifies the key confirmation, which fails when the password is
+//!    wrong, and both devices derive session keys from the X25519 agreement salted
+//!    by the PAKE key
+//!
+//! Visiting with the one-time pairing secret or observer token, active device also sends
+//! the random id of it, which is issued with the secret and tells nothing about it.
+//!
+//! Nothing sent through signaling server can be used to guess the password offline,
+//! and the captured traffic can't be decrypted even if the password leaks later, since
+//! the session keys rely on the ephemeral private keys which are never sent.
+
+use super::{
+    fingerprint::{
+        derive_short_auth_string, identity_public_key, sign_exchange_key, verify_exchange_key,
+        PeerIdentity,
+    },
+    observer::find_observer_token,
+    pairing::find_pairing_secret,
+    subscribe_message::{
+        ActiveEndpointKeyExchangeSecret, PassiveEndpointKeyExchangeSecret, VisitFailureReason,
+    },
+};
+use crate::{
+    error::CoreResult,
+    utility::{
+        bincode::{bincode_deserialize, bincode_serialize},
+        nonce_value::NonceValue,
+    },
+};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use ring::{
+    aead::{BoundKey, OpeningKey, SealingKey, UnboundKey},
+    agreement::{EphemeralPrivateKey, UnparsedPublicKey, X25519},
+    signature::Ed25519KeyPair,
+};
+use rsa::rand_core::OsRng;
+use sha2::Sha256;
+use spake2::{Ed25519Group, Identity, Password, Spake2};
+
+const KEY_CONFIRMATION_LABEL: &[u8] = b"mirrorx passive key confirmation";
+
+/// Version of the endpoint protocol, devices of other versions are rejected at key
+/// exchange with `IncompatibleProtocol` since neither the exchange nor the bincode layout
+/// of endpoint messages can be read across versions.
+///
+/// Version 1 is 0.1.5 and earlier, they encrypt the exchange by the password, so they
+/// can't open the request of this version and reply `InvalidPassword` instead.
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// The state of active device between sending visit request and receiving the reply.
+pub struct ActiveKeyExchange {
+    local_device_id: i64,
+    remote_device_id: i64,
+    pake: Spake2<Ed25519Group>,
+    pake_message: Vec<u8>,
+    exchange_private_key: EphemeralPrivateKey,
+    exchange_public_key: Vec<u8>,
+    exchange_nonce: [u8; ring::aead::NONCE_LEN],
+    salt: [u8; 16],
+}
+
+/// The messages which active device sends through signaling server.
+pub struct ActiveKeyExchangeRequest {
+    pub salt: Vec<u8>,
+    pub secret: Vec<u8>,
+    pub secret_nonce: Vec<u8>,
+}
+
+impl ActiveKeyExchange {
+    /// Start key exchange with `password`, it's the one-time pairing secret or observer
+    /// token of `secret_id` when there's one.
+    pub fn start(
+        local_device_id: i64,
+        remote_device_id: i64,
+        password: &str,
+        secret_id: Option<&str>,
+        identity_key: &Ed25519KeyPair,
+    ) -> CoreResult<(ActiveKeyExchange, ActiveKeyExchangeRequest)> {
+        let (pake, pake_message) = Spake2::<Ed25519Group>::start_a(
+            &Password::new(password.as_bytes()),
+            &Identity::new(&local_device_id.to_le_bytes()),
+            &Identity::new(&remote_device_id.to_le_bytes()),
+        );
+
+        let exchange_private_key =
+            EphemeralPrivateKey::generate(&X25519, &ring::rand::SystemRandom::new())?;
+        let exchange_public_key = exchange_private_key.compute_public_key()?;
+
+        let mut exchange_nonce = [0u8; ring::aead::NONCE_LEN];
+        OsRng.fill_bytes(&mut exchange_nonce);
+
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+
+        let identity_signature =
+            sign_exchange_key(identity_key, exchange_public_key.as_ref(), &exchange_nonce);
+
+        let secret = bincode_serialize(&ActiveEndpointKeyExchangeSecret {
+            protocol_version: PROTOCOL_VERSION,
+            pake_message: &pake_message,
+            active_exchange_public_key: exchange_public_key.as_ref(),
+            secret_id: secret_id.map(str::as_bytes),
+            identity_public_key: identity_public_key(identity_key),
+            identity_signature: &identity_signature,
+        })?;
+
+        let exchange = ActiveKeyExchange {
+            local_device_id,
+            remote_device_id,
+            pake,
+            pake_message,
+            exchange_private_key,
+            exchange_public_key: exchange_public_key.as_ref().to_vec(),
+            exchange_nonce,
+            salt,
+        };
+
+        let request = ActiveKeyExchangeRequest {
+            salt: salt.to_vec(),
+            secret,
+            secret_nonce: exchange_nonce.to_vec(),
+        };
+
+        Ok((exchange, request))
+    }
+
+    /// Verify the reply of passive device and derive the session keys, returns
+    /// `InvalidPassword` when passive device's key confirmation mismatches.
+    #[allow(clippy::type_complexity)]
+    pub fn finish(
+        self,
+        secret: &[u8],
+    ) -> Result<(OpeningKey<NonceValue>, SealingKey<NonceValue>, PeerIdentity), VisitFailureReason>
+    {
+        // the layout of the secret is only known within the same protocol version
+        let Ok(passive_secret) = bincode_deserialize::<PassiveEndpointKeyExchangeSecret>(secret)
+        else {
+            return Err(VisitFailureReason::IncompatibleProtocol);
+        };
+
+        if passive_secret.protocol_version != PROTOCOL_VERSION {
+            tracing::warn!(
+                remote_device_id = self.remote_device_id,
+                protocol_version = passive_secret.protocol_version,
+                "remote device speaks incompatible protocol"
+            );
+            return Err(VisitFailureReason::IncompatibleProtocol);
+        }
+
+        let Ok(passive_exchange_nonce) =
+            <[u8; ring::aead::NONCE_LEN]>::try_from(passive_secret.passive_exchange_nonce)
+        else {
+            return Err(VisitFailureReason::InvalidArgs);
+        };
+
+        // the exchange key replaced by others isn't signed by passive device
+        if !verify_exchange_key(
+            passive_secret.identity_public_key,
+            passive_secret.passive_exchange_public_key,
+            passive_secret.passive_exchange_nonce,
+            passive_secret.identity_signature,
+        ) {
+            return Err(VisitFailureReason::InvalidArgs);
+        }
+
+        let Ok(pake_key) = self.pake.finish(passive_secret.pake_message) else {
+            return Err(VisitFailureReason::InvalidArgs);
+        };
+
+        let transcript = Transcript {
+            salt: &self.salt,
+            active_pake_message: &self.pake_message,
+            passive_pake_message: passive_secret.pake_message,
+            active_exchange_public_key: &self.exchange_public_key,
+            passive_exchange_public_key: passive_secret.passive_exchange_public_key,
+            active_exchange_nonce: &self.exchange_nonce,
+            passive_exchange_nonce: &passive_exchange_nonce,
+        };
+
+        if !transcript.verify(&pake_key, passive_secret.key_confirmation) {
+            tracing::info!(
+                local_device_id = self.local_device_id,
+                remote_device_id = self.remote_device_id,
+                "key confirmation mismatch"
+            );
+            return Err(VisitFailureReason::InvalidPassword);
+        }
+
+        let (opening_key, sealing_key, short_auth_string) = agree_session_keys(
+            self.exchange_private_key,
+            passive_secret.passive_exchange_public_key,
+            &pake_key,
+            self.exchange_nonce,
+            passive_exchange_nonce,
+            true,
+        )?;
+
+        let peer = PeerIdentity {
+            public_key: passive_secret.identity_public_key.to_vec(),
+            short_auth_string,
+        };
+
+        Ok((opening_key, sealing_key, peer))
+    }
+}
+
+/// Reply the key exchange of active device with `password`, or the one-time pairing
+/// secret or observer token whose id active device sends. Returns the secret to reply, the
+/// session keys and the id of the session which active device observes with the token.
+#[allow(clippy::type_complexity)]
+pub(crate) fn reply_key_exchange(
+    identity_key: &Ed25519KeyPair,
+    password: &str,
+    active_device_id: i64,
+    passive_device_id: i64,
+    salt: &[u8],
+    secret: &[u8],
+    secret_nonce: &[u8],
+) -> Result<
+    (
+        Vec<u8>,
+        OpeningKey<NonceValue>,
+        SealingKey<NonceValue>,
+        PeerIdentity,
+        Option<String>,
+    ),
+    VisitFailureReason,
+> {
+    let Ok(active_exchange_nonce) = <[u8; ring::aead::NONCE_LEN]>::try_from(secret_nonce) else {
+        return Err(VisitFailureReason::InvalidArgs);
+    };
+
+    // the layout of the secret is only known within the same protocol version
+    let Ok(active_secret) = bincode_deserialize::<ActiveEndpointKeyExchangeSecret>(secret) else {
+        tracing::warn!(active_device_id, "undecodable key exchange of active device");
+        return Err(VisitFailureReason::IncompatibleProtocol);
+    };
+
+    if active_secret.protocol_version != PROTOCOL_VERSION {
+        tracing::warn!(
+            active_device_id,
+            protocol_version = active_secret.protocol_version,
+            "active device speaks incompatible protocol"
+        );
+        return Err(VisitFailureReason::IncompatibleProtocol);
+    }
+
+    if !verify_exchange_key(
+        active_secret.identity_public_key,
+        active_secret.active_exchange_public_key,
+        &active_exchange_nonce,
+        active_secret.identity_signature,
+    ) {
+        return Err(VisitFailureReason::InvalidArgs);
+    }
+
+    // PAKE can't tell which password active device used, the pairing secret or observer
+    // token is found by its id and consumed at once
+    let (password, observed_session_id) = match active_secret.secret_id {
+        Some(secret_id) => match find_pairing_secret(secret_id) {
+            Some(pairing_secret) => (pairing_secret, None),
+            None => match find_observer_token(secret_id) {
+                Some((token, session_id)) => (token, Some(session_id)),
+                None => return Err(VisitFailureReason::InvalidPassword),
+            },
+        },
+        None => (password.to_string(), None),
+    };
+
+    let (pake, pake_message) = Spake2::<Ed25519Group>::start_b(
+        &Password::new(password.as_bytes()),
+        &Identity::new(&active_device_id.to_le_bytes()),
+        &Identity::new(&passive_device_id.to_le_bytes()),
+    );
+
+    let Ok(pake_key) = pake.finish(active_secret.pake_message) else {
+        return Err(VisitFailureReason::InvalidArgs);
+    };
+
+    let exchange_private_key =
+        match EphemeralPrivateKey::generate(&X25519, &ring::rand::SystemRandom::new()) {
+            Ok(private_key) => private_key,
+            Err(_) => return Err(VisitFailureReason::InternalError),
+        };
+
+    let exchange_public_key = match exchange_private_key.compute_public_key() {
+        Ok(public_key) => public_key,
+        Err(err) => {
+            tracing::error!(?err, "compute passive exchange public key failed");
+            return Err(VisitFailureReason::InternalError);
+        }
+    };
+
+    let mut exchange_nonce = [0u8; ring::aead::NONCE_LEN];
+    OsRng.fill_bytes(&mut exchange_nonce);
+
+    let transcript = Transcript {
+        salt,
+        active_pake_message: active_secret.pake_message,
+        passive_pake_message: &pake_message,
+        active_exchange_public_key: active_secret.active_exchange_public_key,
+        passive_exchange_public_key: exchange_public_key.as_ref(),
+        active_exchange_nonce: &active_exchange_nonce,
+        passive_exchange_nonce: &exchange_nonce,
+    };
+
+    let key_confirmation = transcript.confirm(&pake_key);
+
+    let identity_signature =
+        sign_exchange_key(identity_key, exchange_public_key.as_ref(), &exchange_nonce);
+
+    let Ok(reply_secret) = bincode_serialize(&PassiveEndpointKeyExchangeSecret {
+        protocol_version: PROTOCOL_VERSION,
+        pake_message: &pake_message,
+        passive_exchange_public_key: exchange_public_key.as_ref(),
+        passive_exchange_nonce: &exchange_nonce,
+        key_confirmation: &key_confirmation,
+        identity_public_key: identity_public_key(identity_key),
+        identity_signature: &identity_signature,
+    }) else {
+        return Err(VisitFailureReason::InternalError);
+    };
+
+    let (opening_key, sealing_key, short_auth_string) = agree_session_keys(
+        exchange_private_key,
+        active_secret.active_exchange_public_key,
+        &pake_key,
+        exchange_nonce,
+        active_exchange_nonce,
+        false,
+    )?;
+
+    let peer = PeerIdentity {
+        public_key: active_secret.identity_public_key.to_vec(),
+        short_auth_string,
+    };
+
+    Ok((
+        reply_secret,
+        opening_key,
+        sealing_key,
+        peer,
+        observed_session_id,
+    ))
+}
+
+/// Everything both devices sent in the key exchange, passive device proves it derived
+/// the same PAKE key by the MAC of it.
+struct Transcript<'a> {
+    salt: &'a [u8],
+    active_pake_message: &'a [u8],
+    passive_pake_message: &'a [u8],
+    active_exchange_public_key: &'a [u8],
+    passive_exchange_public_key: &'a [u8],
+    active_exchange_nonce: &'a [u8],
+    passive_exchange_nonce: &'a [u8],
+}
+
+impl Transcript<'_> {
+    fn mac(&self, pake_key: &[u8]) -> Hmac<Sha256> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(pake_key)
+            .expect("hmac accepts key of any length");
+
+        for part in [
+            KEY_CONFIRMATION_LABEL,
+            self.salt,
+            self.active_pake_message,
+            self.passive_pake_message,
+            self.active_exchange_public_key,
+            self.passive_exchange_public_key,
+            self.active_exchange_nonce,
+            self.passive_exchange_nonce,
+        ] {
+            // length prefixed so the parts can't be shifted between each other
+            mac.update(&(part.len() as u32).to_le_bytes());
+            mac.update(part);
+        }
+
+        mac
+    }
+
+    fn confirm(&self, pake_key: &[u8]) -> Vec<u8> {
+        self.mac(pake_key).finalize().into_bytes().to_vec()
+    }
+
+    fn verify(&self, pake_key: &[u8], key_confirmation: &[u8]) -> bool {
+        self.mac(pake_key).verify_slice(key_confirmation).is_ok()
+    }
+}
+
+// each device seals with the key expanded by its own nonce and opens with the one
+// expanded by remote's, the nonce sequence of sealing key starts from remote's nonce
+fn agree_session_keys(
+    exchange_private_key: EphemeralPrivateKey,
+    remote_exchange_public_key: &[u8],
+    pake_key: &[u8],
+    exchange_nonce: [u8; ring::aead::NONCE_LEN],
+    remote_exchange_nonce: [u8; ring::aead::NONCE_LEN],
+    active: bool,
+) -> Result<(OpeningKey<NonceValue>, SealingKey<NonceValue>, String), VisitFailureReason> {
+    let (active_exchange_nonce, passive_exchange_nonce) = if active {
+        (exchange_nonce, remote_exchange_nonce)
+    } else {
+        (remote_exchange_nonce, exchange_nonce)
+    };
+
+    let agree_result = ring::agreement::agree_ephemeral(
+        exchange_private_key,
+        &UnparsedPublicKey::new(&X25519, remote_exchange_public_key),
+        ring::error::Unspecified,
+        |key_material| {
+            let prk =
+                ring::hkdf::Salt::new(ring::hkdf::HKDF_SHA512, pake_key).extract(key_material);
+
+            let expand = |info: &[u8]| {
+                prk.expand(&[info], &ring::aead::AES_256_GCM)
+                    .and_then(|okm| {
+                        let mut key = vec![0u8; ring::aead::AES_256_GCM.key_len()];
+                        okm.fill(&mut key)?;
+                        Ok(key)
+                    })
+            };
+
+            let sealing_key = expand(&exchange_nonce)?;
+            let opening_key = expand(&remote_exchange_nonce)?;
+
+            let short_auth_string = derive_short_auth_string(
+                key_material,
+                &active_exchange_nonce,
+                &passive_exchange_nonce,
+            );
+
+            Ok((sealing_key, opening_key, short_auth_string))
+        },
+    );
+
+    let (raw_sealing_key, raw_opening_key, short_auth_string) = match agree_result {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!(?err, "agree ephemeral failed");
+            return Err(VisitFailureReason::InternalError);
+        }
+    };
+
+    let (Ok(unbound_sealing_key), Ok(unbound_opening_key)) = (
+        UnboundKey::new(&ring::aead::AES_256_GCM, &raw_sealing_key),
+        UnboundKey::new(&ring::aead::AES_256_GCM, &raw_opening_key),
+    ) else {
+        tracing::error!("create unbound session key failed");
+        return Err(VisitFailureReason::InternalError);
+    };
+
+    let sealing_key = SealingKey::new(unbound_sealing_key, NonceValue::new(remote_exchange_nonce));
+    let opening_key = OpeningKey::new(unbound_opening_key, NonceValue::new(exchange_nonce));
+
+    Ok((opening_key, sealing_key, short_auth_string))
+}
@@ -1,26 +1,43 @@
+pub mod fingerprint;
 pub mod http_message;
+pub mod key_exchange;
+pub mod observer;
+pub mod pairing;
 pub mod subscribe_message;
+pub mod visit_prompt;
 
 use self::{
+    fingerprint::{load_identity_key, verify_peer, PeerIdentity},
     http_message::{
-        IdentityResponse, RegisterRequest, RegisterResponse, Response, VisitRequest, VisitResponse,
+        DirtyShutdownRequest, DirtyShutdownResponse, IdentityResponse, RegisterRequest,
+        RegisterResponse, Response, VisitRequest, VisitResponse,
     },
+    key_exchange::{reply_key_exchange, ActiveKeyExchange},
     subscribe_message::{
-        ActiveEndpointKeyExchangeSecret, ClientMessage, PassiveEndpointKeyExchangeSecret,
-        ServerMessage, Subscription, VisitFailureReason,
+        ClientMessage, RejectReason, ServerMessage, Subscription, VisitFailureReason,
     },
+    visit_prompt::{cancel_visit, prompt_visit, reject_message},
 };
 use super::{
     config::LocalStorage,
-    endpoint::{create_passive_endpoint_client, id::EndPointID},
+    endpoint::{
+        acl::{acl_admits, AclOrigin},
+        create_passive_endpoint_client,
+        id::EndPointID,
+        queue::{acquire_session_slot, QueueRejection, SessionSlot, QUEUE_TIMEOUT},
+        EndPointStream,
+    },
+    notification::{self, Notification},
 };
 use crate::{
+    component::availability::{admit_visit, availability, subscribe_availability, Availability},
     core_error,
     error::CoreResult,
     utility::{
         bincode::{bincode_deserialize, bincode_serialize},
+        net::{bind_ip, connect_tcp},
         nonce_value::NonceValue,
-        rand::generate_random_ping_value,
+        rand::{generate_random_jitter, generate_random_ping_value},
     },
 };
 use base64::engine::general_purpose::STANDARD as base64_standard;
@@ -30,21 +47,40 @@ use futures::{
     stream::{SplitSink, SplitStream},
     SinkExt, StreamExt,
 };
-use hmac::Hmac;
-use rand::RngCore;
 use reqwest::IntoUrl;
-use ring::aead::{BoundKey, OpeningKey, SealingKey, UnboundKey};
-use rsa::{rand_core::OsRng, BigUint, PublicKey, PublicKeyParts};
-use sha2::Sha256;
-use std::{net::SocketAddr, time::Duration};
-use tokio::net::TcpStream;
+use ring::{
+    aead::{OpeningKey, SealingKey},
+    signature::Ed25519KeyPair,
+};
+use serde::Serialize;
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+use tokio::{net::TcpStream, task::JoinHandle};
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 use url::Url;
 
+// delay of reconnecting the subscription doubles after each failure up to the max
+const SUBSCRIBE_RETRY_MIN_DELAY: Duration = Duration::from_secs(1);
+const SUBSCRIBE_RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+// connection which lasts longer than a ping interval resets the delay
+const SUBSCRIBE_STABLE_DURATION: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum SignalingState {
+    Connecting,
+    Connected,
+    Reconnecting { attempt: u32, retry_in_secs: u64 },
+    Disconnected,
+}
+
 pub struct SignalingClient {
     url: Url,
     http_client: reqwest::Client,
-    subscribe_tx: Option<tokio::sync::mpsc::Sender<Bytes>>,
+    subscription: Option<SubscribeSession>,
 }
 
 impl SignalingClient {
@@ -54,12 +90,13 @@ impl SignalingClient {
         let http_client = reqwest::Client::builder()
             .connect_timeout(Duration::from_secs(10))
             .timeout(Duration::from_secs(10))
+            .local_address(bind_ip())
             .build()?;
 
         Ok(Self {
             url,
             http_client,
-            subscribe_tx: None,
+            subscription: None,
         })
     }
 
@@ -99,14 +136,42 @@ impl SignalingClient {
         Ok(resp)
     }
 
-    #[allow(clippy::type_complexity)]
+    /// Tell the remote devices that sessions with them were interrupted by crash or
+    /// force-quit, so they can release the resources of these sessions.
     #[tracing::instrument(skip(self))]
+    pub async fn dirty_shutdown(
+        &self,
+        device_id: i64,
+        remote_device_ids: Vec<i64>,
+    ) -> CoreResult<Response<DirtyShutdownResponse>> {
+        let url = self.url.join("/api/session/dirty_shutdown")?;
+        let resp = self
+            .http_client
+            .post(url)
+            .json(&DirtyShutdownRequest {
+                device_id,
+                remote_device_ids,
+            })
+            .send()
+            .await?
+            .json::<Response<DirtyShutdownResponse>>()
+            .await?;
+
+        Ok(resp)
+    }
+
+    /// Visit the remote device with its device password, or the one-time pairing secret
+    /// or observer token of `secret_id`.
+    #[allow(clippy::type_complexity)]
+    #[tracing::instrument(skip(self, password, identity_key))]
     pub async fn visit(
         &self,
         local_device_id: i64,
         remote_device_id: i64,
         password: String,
+        secret_id: Option<String>,
         visit_desktop: bool,
+        identity_key: &Ed25519KeyPair,
     ) -> CoreResult<
         Response<
             Result<
@@ -115,6 +180,7 @@ impl SignalingClient {
                     Vec<u8>,
                     OpeningKey<NonceValue>,
                     SealingKey<NonceValue>,
+                    PeerIdentity,
                 ),
                 VisitFailureReason,
             >,
@@ -122,63 +188,12 @@ impl SignalingClient {
     > {
         let url = self.url.join("/api/visit")?;
 
-        let secure_random = ring::rand::SystemRandom::new();
-
-        // generate key pair for passive device key exchange reply
-        let reply_private_key = rsa::RsaPrivateKey::new(&mut OsRng, 4096)?;
-        let reply_public_key = reply_private_key.to_public_key();
-
-        // generate exchange key pair and nonce
-        let active_exchange_private_key = ring::agreement::EphemeralPrivateKey::generate(
-            &ring::agreement::X25519,
-            &secure_random,
-        )?;
-        let active_exchange_public_key = active_exchange_private_key.compute_public_key()?;
-
-        let mut active_exchange_nonce = [0u8; ring::aead::NONCE_LEN];
-        OsRng.fill_bytes(&mut active_exchange_nonce);
-
-        let mut visit_credentials_buffer = [0u8; 16];
-        OsRng.fill_bytes(&mut visit_credentials_buffer);
-
-        // generate and sealing active device key exchange secret
-        let active_device_secret = ActiveEndpointKeyExchangeSecret {
-            exchange_reply_public_key_n: &reply_public_key.n().to_bytes_le(),
-            exchange_reply_public_key_e: &reply_public_key.e().to_bytes_le(),
-            active_exchange_public_key: active_exchange_public_key.as_ref(),
-            active_exchange_nonce: &active_exchange_nonce,
-        };
-
-        // generate secret sealing key with salt
-        let mut active_device_secret_salt = [0u8; 16];
-        OsRng.fill_bytes(&mut active_device_secret_salt);
-
-        let mut active_device_secret_sealing_key = [0u8; 32];
-        pbkdf2::pbkdf2::<Hmac<Sha256>>(
-            password.as_bytes(),
-            &active_device_secret_salt,
-            10000,
-            &mut active_device_secret_sealing_key,
-        );
-
-        let mut active_device_secret_buffer = bincode_serialize(&active_device_secret)?;
-
-        let active_device_secret_sealing_unbound_key = ring::aead::UnboundKey::new(
-            &ring::aead::AES_256_GCM,
-            &active_device_secret_sealing_key,
-        )?;
-
-        let mut active_device_secret_sealing_nonce = [0u8; ring::aead::NONCE_LEN];
-        OsRng.fill_bytes(&mut active_device_secret_sealing_nonce);
-
-        let mut active_device_secret_sealing_key = ring::aead::SealingKey::new(
-            active_device_secret_sealing_unbound_key,
-            NonceValue::new(active_device_secret_sealing_nonce),
-        );
-
-        active_device_secret_sealing_key.seal_in_place_append_tag(
-            ring::aead::Aad::from(local_device_id.to_le_bytes()),
-            &mut active_device_secret_buffer,
+        let (key_exchange, request) = ActiveKeyExchange::start(
+            local_device_id,
+            remote_device_id,
+            &password,
+            secret_id.as_deref(),
+            identity_key,
         )?;
 
         let resp = self
@@ -188,11 +203,12 @@ impl SignalingClient {
                 active_device_id: local_device_id,
                 passive_device_id: remote_device_id,
                 visit_desktop,
-                password_salt: base64_standard.encode(active_device_secret_salt),
-                secret: base64_standard.encode(active_device_secret_buffer),
-                secret_nonce: base64_standard.encode(active_device_secret_sealing_nonce),
+                password_salt: base64_standard.encode(request.salt),
+                secret: base64_standard.encode(request.secret),
+                secret_nonce: base64_standard.encode(request.secret_nonce),
             })
-            .timeout(Duration::from_secs(60))
+            // remote may keep the visit in its queue when its sessions are full
+            .timeout(QUEUE_TIMEOUT + Duration::from_secs(60))
             .send()
             .await?
             .json::<Response<VisitResponse>>()
@@ -207,97 +223,95 @@ impl SignalingClient {
 
                 let visit_credentials = base64_standard.decode(resp.visit_credentials)?;
 
-                let passive_device_secret_buffer =
-                    reply_private_key.decrypt(rsa::Pkcs1v15Encrypt::default(), &secret)?;
-
-                let passive_device_secret: PassiveEndpointKeyExchangeSecret =
-                    bincode_deserialize(&passive_device_secret_buffer)?;
-
-                let passive_exchange_public_key = ring::agreement::UnparsedPublicKey::new(
-                    &ring::agreement::X25519,
-                    passive_device_secret.passive_exchange_public_key,
-                );
-
-                let (raw_sealing_key, raw_opening_key) = ring::agreement::agree_ephemeral(
-                    active_exchange_private_key,
-                    &passive_exchange_public_key,
-                    ring::error::Unspecified,
-                    |key_material| {
-                        let sealing_key =
-                            ring::hkdf::Salt::new(ring::hkdf::HKDF_SHA512, &active_exchange_nonce)
-                                .extract(key_material)
-                                .expand(&["".as_bytes()], &ring::aead::AES_256_GCM)
-                                .and_then(|orm| {
-                                    let mut key = Vec::<u8>::new();
-                                    key.resize(ring::aead::AES_256_GCM.key_len(), 0);
-                                    orm.fill(&mut key)?;
-                                    Ok(key)
-                                })?;
-
-                        let opening_key = ring::hkdf::Salt::new(
-                            ring::hkdf::HKDF_SHA512,
-                            passive_device_secret.passive_exchange_nonce,
-                        )
-                        .extract(key_material)
-                        .expand(&["".as_bytes()], &ring::aead::AES_256_GCM)
-                        .and_then(|orm| {
-                            let mut key = Vec::<u8>::new();
-                            key.resize(ring::aead::AES_256_GCM.key_len(), 0);
-                            orm.fill(&mut key)?;
-                            Ok(key)
-                        })?;
-
-                        Ok((sealing_key, opening_key))
-                    },
-                )?;
-
-                let unbound_sealing_key =
-                    ring::aead::UnboundKey::new(&ring::aead::AES_256_GCM, &raw_sealing_key)?;
-
-                let mut nonce = [0u8; 12];
-                nonce.copy_from_slice(passive_device_secret.passive_exchange_nonce);
-                let sealing_key =
-                    ring::aead::SealingKey::new(unbound_sealing_key, NonceValue::new(nonce));
-
-                let unbound_opening_key =
-                    ring::aead::UnboundKey::new(&ring::aead::AES_256_GCM, &raw_opening_key)?;
-
-                let mut nonce = [0u8; 12];
-                nonce.copy_from_slice(&active_exchange_nonce);
-                let opening_key =
-                    ring::aead::OpeningKey::new(unbound_opening_key, NonceValue::new(nonce));
+                let (opening_key, sealing_key, peer) = match key_exchange.finish(&secret) {
+                    Ok(v) => v,
+                    Err(reason) => return Ok(Response::Message(Err(reason))),
+                };
 
                 Ok(Response::Message(Ok((
                     resp.endpoint_addr,
                     visit_credentials,
                     opening_key,
                     sealing_key,
+                    peer,
                 ))))
             }
             Response::Error(err) => Ok(Response::Error(err)),
         }
     }
 
-    // see https://github.com/rust-lang/rust-clippy/pull/9496, which was merged but not release
-    #[allow(clippy::never_loop)]
+    /// Subscribe visit requests of device in domain `domain_id`, they're verified with
+    /// the password of that domain. The subscription is kept by reconnecting and
+    /// subscribing again whenever the connection is lost, so it keeps running in
+    /// background even if the first attempt which is returned fails.
     pub async fn subscribe(
         &mut self,
         addrs: Vec<SocketAddr>,
+        domain_id: i64,
         device_id: i64,
         device_finger_print: &str,
         storage: LocalStorage,
     ) -> CoreResult<()> {
-        let subscription_bytes = Bytes::from(bincode_serialize(&Subscription {
+        let params = SubscribeParams {
+            addrs,
+            domain_id,
             device_id,
             device_finger_print: device_finger_print.to_string(),
+            storage,
+        };
+
+        let (state_tx, state_rx) = tokio::sync::watch::channel(SignalingState::Connecting);
+        notification::publish(Notification::SignalingStateChanged {
+            domain_id,
+            state: SignalingState::Connecting,
+        });
+
+        let (connection, result) = match params.connect().await {
+            Ok(connection) => (Some(connection), Ok(())),
+            Err(err) => (None, Err(err)),
+        };
+
+        self.subscription = Some(SubscribeSession {
+            domain_id,
+            state_rx,
+            handle: tokio::spawn(keep_subscription(params, connection, state_tx)),
+        });
+
+        result
+    }
+
+    /// State of the subscription connection.
+    pub fn state(&self) -> SignalingState {
+        self.subscription
+            .as_ref()
+            .map(|subscription| *subscription.state_rx.borrow())
+            .unwrap_or(SignalingState::Disconnected)
+    }
+}
+
+struct SubscribeParams {
+    addrs: Vec<SocketAddr>,
+    domain_id: i64,
+    device_id: i64,
+    device_finger_print: String,
+    storage: LocalStorage,
+}
+
+impl SubscribeParams {
+    // see https://github.com/rust-lang/rust-clippy/pull/9496, which was merged but not release
+    #[allow(clippy::never_loop)]
+    async fn connect(&self) -> CoreResult<Framed<TcpStream, LengthDelimitedCodec>> {
+        // availability may change while disconnected, so it's built for every connection
+        let subscription_bytes = Bytes::from(bincode_serialize(&Subscription {
+            device_id: self.device_id,
+            device_finger_print: self.device_finger_print.clone(),
+            availability: availability(),
         })?);
 
-        for addr in addrs {
-            let Ok(Ok(stream)) = tokio::time::timeout(
-                Duration::from_secs(10),
-                tokio::net::TcpStream::connect(addr),
-            )
-            .await else {
+        for addr in &self.addrs {
+            let Ok(Ok(stream)) =
+                tokio::time::timeout(Duration::from_secs(10), connect_tcp(*addr)).await
+            else {
                 continue;
             };
 
@@ -311,29 +325,128 @@ impl SignalingClient {
 
             framed_stream.send(subscription_bytes.clone()).await?;
 
+            return Ok(framed_stream);
+        }
+
+        Err(core_error!("non addr usable"))
+    }
+}
+
+struct SubscribeSession {
+    domain_id: i64,
+    state_rx: tokio::sync::watch::Receiver<SignalingState>,
+    handle: JoinHandle<()>,
+}
+
+impl Drop for SubscribeSession {
+    fn drop(&mut self) {
+        self.handle.abort();
+        notification::publish(Notification::SignalingStateChanged {
+            domain_id: self.domain_id,
+            state: SignalingState::Disconnected,
+        });
+    }
+}
+
+/// Serve the subscription connection and connect again after it's lost, the delay
+/// between attempts grows exponentially with random jitter so devices don't flood the
+/// signaling server all at once when it restarts.
+async fn keep_subscription(
+    params: SubscribeParams,
+    mut connection: Option<Framed<TcpStream, LengthDelimitedCodec>>,
+    state_tx: tokio::sync::watch::Sender<SignalingState>,
+) {
+    let update_state = |state: SignalingState| {
+        state_tx.send_replace(state);
+        notification::publish(Notification::SignalingStateChanged {
+            domain_id: params.domain_id,
+            state,
+        });
+    };
+
+    let mut attempt: u32 = 0;
+
+    loop {
+        if let Some(framed_stream) = connection.take() {
+            update_state(SignalingState::Connected);
+            let connected_at = Instant::now();
+
             let (sink, stream) = framed_stream.split();
-            let (tx, rx) = tokio::sync::mpsc::channel(1);
 
-            tokio::spawn(serve_connection(rx, sink, stream, storage.clone()));
+            // visit replies hold the weak sender, they're dropped with the connection
+            let (tx, rx) = tokio::sync::mpsc::channel(1);
+            serve_connection(
+                rx,
+                tx.downgrade(),
+                sink,
+                stream,
+                params.domain_id,
+                params.storage.clone(),
+            )
+            .await;
+            drop(tx);
 
-            self.subscribe_tx = Some(tx);
+            tracing::warn!(domain_id = params.domain_id, "signaling connection lost");
 
-            return Ok(());
+            // a connection which was refused soon after subscribing isn't a recovery
+            if connected_at.elapsed() >= SUBSCRIBE_STABLE_DURATION {
+                attempt = 0;
+            }
         }
 
-        Err(core_error!("non addr usable"))
+        let delay = subscribe_retry_delay(attempt);
+        attempt = attempt.saturating_add(1);
+
+        update_state(SignalingState::Reconnecting {
+            attempt,
+            retry_in_secs: delay.as_secs_f64().ceil() as u64,
+        });
+
+        tokio::time::sleep(delay).await;
+        update_state(SignalingState::Connecting);
+
+        match params.connect().await {
+            Ok(framed_stream) => {
+                tracing::info!(
+                    domain_id = params.domain_id,
+                    attempt,
+                    "signaling reconnected"
+                );
+                connection = Some(framed_stream);
+            }
+            Err(err) => {
+                tracing::warn!(
+                    ?err,
+                    domain_id = params.domain_id,
+                    attempt,
+                    "signaling reconnect failed"
+                );
+            }
+        }
     }
 }
 
+fn subscribe_retry_delay(attempt: u32) -> Duration {
+    let delay = SUBSCRIBE_RETRY_MIN_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(SUBSCRIBE_RETRY_MAX_DELAY);
+
+    generate_random_jitter(delay)
+}
+
 async fn serve_connection(
     mut rx: tokio::sync::mpsc::Receiver<Bytes>,
+    // replies of visit requests are sent through it, it doesn't keep the connection alive
+    reply_tx: tokio::sync::mpsc::WeakSender<Bytes>,
     mut sink: SplitSink<Framed<TcpStream, LengthDelimitedCodec>, Bytes>,
     mut stream: SplitStream<Framed<TcpStream, LengthDelimitedCodec>>,
+    domain_id: i64,
     storage: LocalStorage,
 ) {
     let mut ticker = tokio::time::interval(Duration::from_secs(60));
     let mut last_ping = None;
     let mut last_ping_value = 0;
+    let mut availability_rx = subscribe_availability();
 
     loop {
         let buffer = tokio::select! {
@@ -353,6 +466,16 @@ async fn serve_connection(
 
                 return;
             }
+            Ok(()) = availability_rx.changed() => {
+                let availability = *availability_rx.borrow_and_update();
+                if let Ok(buffer) = bincode_serialize(&ClientMessage::Availability(availability)) {
+                    if (sink.send(Bytes::from(buffer)).await).is_ok() {
+                        continue;
+                    }
+                }
+
+                return;
+            }
             buffer = rx.recv() => {
                 if let Some(buffer) = buffer {
                     let _ = sink.send(buffer).await;
@@ -389,7 +512,7 @@ async fn serve_connection(
             ServerMessage::VisitRequest {
                 active_device_id,
                 passive_device_id,
-                visit_desktop: _,
+                visit_desktop,
                 endpoint_addr,
                 password_salt,
                 secret,
@@ -397,20 +520,79 @@ async fn serve_connection(
                 passive_visit_credentials,
             } => {
                 let storage = storage.clone();
-                let (tx, rx) = tokio::sync::oneshot::channel();
+                let reply_tx = reply_tx.clone();
+                let acl_denied = !acl_admits(AclOrigin::Device(active_device_id));
+
+                // capture pipeline warms up while the user is prompted and the session
+                // is negotiated, it's released later if the visit doesn't go on
+                #[cfg(feature = "host")]
+                if visit_desktop && !acl_denied {
+                    crate::api::endpoint::warm_start::prewarm();
+                }
+
                 tokio::spawn(async move {
-                    let result = serve_visit_request(
-                        storage,
-                        active_device_id,
-                        passive_device_id,
-                        endpoint_addr,
-                        // visit_desktop,
-                        password_salt,
-                        secret,
-                        secret_nonce,
-                        passive_visit_credentials,
-                    )
-                    .await;
+                    let endpoint_id = EndPointID::DeviceID {
+                        local_device_id: passive_device_id,
+                        remote_device_id: active_device_id,
+                    };
+
+                    // busy user isn't notified until available, the visit waits meanwhile
+                    let result = if acl_denied {
+                        tracing::info!(active_device_id, "reject visit request denied by acl");
+                        Err(rejected(RejectReason::Acl))
+                    } else if !admit_visit().await {
+                        let availability = availability();
+                        tracing::info!(
+                            active_device_id,
+                            ?availability,
+                            "reject visit request since user is unavailable"
+                        );
+
+                        // busy user who isn't back in time didn't reject it
+                        if availability == Availability::DoNotDisturb {
+                            Err(rejected(RejectReason::DoNotDisturb))
+                        } else {
+                            Err(VisitFailureReason::RemoteUnavailable)
+                        }
+                    } else if let Err(message) =
+                        prompt_visit(active_device_id, passive_device_id, visit_desktop).await
+                    {
+                        tracing::info!(active_device_id, "visit request is denied by user");
+                        Err(VisitFailureReason::Rejected {
+                            reason: RejectReason::User,
+                            message,
+                        })
+                    } else {
+                        let on_position = |position| {
+                            send_queue_position(
+                                reply_tx.clone(),
+                                active_device_id,
+                                passive_device_id,
+                                position,
+                            )
+                        };
+
+                        match acquire_session_slot(endpoint_id, on_position).await {
+                            Ok(session_slot) => {
+                                serve_visit_request(
+                                    storage,
+                                    domain_id,
+                                    active_device_id,
+                                    passive_device_id,
+                                    endpoint_addr,
+                                    // visit_desktop,
+                                    password_salt,
+                                    secret,
+                                    secret_nonce,
+                                    passive_visit_credentials,
+                                    session_slot,
+                                )
+                                .await
+                            }
+                            Err(QueueRejection::Rejected) => Err(rejected(RejectReason::User)),
+                            Err(QueueRejection::Timeout) => Err(VisitFailureReason::QueueTimeout),
+                        }
+                    };
 
                     let response = ClientMessage::VisitResponse {
                         active_device_id,
@@ -426,30 +608,74 @@ async fn serve_connection(
                         }
                     };
 
-                    let _ = tx.send(buffer);
-                });
+                    let Some(reply_tx) = reply_tx.upgrade() else {
+                        tracing::warn!("subscription closed before replying visit");
+                        return;
+                    };
 
-                match rx.await {
-                    Ok(buffer) => {
-                        if let Err(err) = sink.send(Bytes::from(buffer)).await {
-                            tracing::error!(?err, "reply visit failed");
-                        }
-                    }
-                    Err(err) => {
-                        tracing::error!(
-                            ?err,
-                            "receive key exchange result failed, this shouldn't happen!"
-                        );
+                    if reply_tx.send(Bytes::from(buffer)).await.is_err() {
+                        tracing::error!("reply visit failed");
                     }
-                }
+                });
             }
+            ServerMessage::VisitQueuePosition {
+                active_device_id,
+                passive_device_id,
+                position,
+            } => {
+                notification::publish(Notification::VisitQueuePosition {
+                    active_device_id,
+                    passive_device_id,
+                    position,
+                });
+            }
+            ServerMessage::VisitCanceled {
+                active_device_id,
+                passive_device_id,
+            } => cancel_visit(active_device_id, passive_device_id),
         }
     }
 }
 
+/// Rejection which active device is told with the text host user left for it.
+fn rejected(reason: RejectReason) -> VisitFailureReason {
+    VisitFailureReason::Rejected {
+        reason,
+        message: reject_message(),
+    }
+}
+
+/// Tell active device its position in the queue, signaling server relays it.
+async fn send_queue_position(
+    reply_tx: tokio::sync::mpsc::WeakSender<Bytes>,
+    active_device_id: i64,
+    passive_device_id: i64,
+    position: usize,
+) {
+    let Some(reply_tx) = reply_tx.upgrade() else {
+        return;
+    };
+
+    let message = ClientMessage::VisitQueuePosition {
+        active_device_id,
+        passive_device_id,
+        position: position as u32,
+    };
+
+    match bincode_serialize(&message) {
+        Ok(buffer) => {
+            if reply_tx.send(Bytes::from(buffer)).await.is_err() {
+                tracing::warn!("send visit queue position failed");
+            }
+        }
+        Err(err) => tracing::error!(?err, "serialize visit queue position failed"),
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn serve_visit_request(
     storage: LocalStorage,
+    domain_id: i64,
     active_device_id: i64,
     passive_device_id: i64,
     endpoint_addr: String,
@@ -457,8 +683,9 @@ async fn serve_visit_request(
     secret: Vec<u8>,
     secret_nonce: Vec<u8>,
     passive_visit_credentials: Vec<u8>,
+    session_slot: SessionSlot,
 ) -> Result<Vec<u8>, VisitFailureReason> {
-    let Ok(domain) = storage.domain().get_primary_domain() else {
+    let Ok(domain) = storage.domain().get_domain_by_id(domain_id) else {
         return Err(VisitFailureReason::InternalError);
     };
 
@@ -466,18 +693,42 @@ async fn serve_visit_request(
         return Err(VisitFailureReason::InternalError);
     };
 
-    let (secret, sealing_key, opening_key) = match key_agreement(
+    let identity_key = match load_identity_key(&storage) {
+        Ok(identity_key) => identity_key,
+        Err(err) => {
+            tracing::error!(?err, "load identity key failed");
+            return Err(VisitFailureReason::InternalError);
+        }
+    };
+
+    // active device which doesn't know the password can't derive the same session keys,
+    // its first message fails to be opened and the session closes
+    let (secret, opening_key, sealing_key, peer, observed_session_id) = reply_key_exchange(
+        &identity_key,
         &domain.password,
         active_device_id,
-        password_salt,
-        secret,
-        secret_nonce,
-    )
-    .await
-    {
-        Ok(v) => v,
+        passive_device_id,
+        &password_salt,
+        &secret,
+        &secret_nonce,
+    )?;
+
+    // a changed identity is warned loudly but doesn't stop the visit
+    if let Err(err) = verify_peer(
+        &storage,
+        &domain.name,
+        passive_device_id,
+        active_device_id,
+        &peer,
+    ) {
+        tracing::error!(?err, "verify peer identity failed");
+    }
+
+    let stream = match EndPointStream::relay(&storage, &domain.name, endpoint_addr) {
+        Ok(stream) => stream,
         Err(err) => {
-            return Err(err);
+            tracing::error!(?err, "read relay tls settings failed");
+            return Err(VisitFailureReason::InternalError);
         }
     };
 
@@ -488,8 +739,10 @@ async fn serve_visit_request(
                 remote_device_id: active_device_id,
             },
             Some((opening_key, sealing_key)),
-            crate::api::endpoint::EndPointStream::ActiveTCP(endpoint_addr),
+            stream,
             Some(passive_visit_credentials),
+            session_slot,
+            observed_session_id,
         )
         .await
         {
@@ -499,203 +752,3 @@ async fn serve_visit_request(
 
     Ok(secret)
 }
-
-async fn key_agreement(
-    domain_password: &str,
-    active_device_id: i64,
-    password_salt: Vec<u8>,
-    mut secret: Vec<u8>,
-    secret_nonce: Vec<u8>,
-) -> Result<(Vec<u8>, SealingKey<NonceValue>, OpeningKey<NonceValue>), VisitFailureReason> {
-    if secret_nonce.len() != ring::aead::NONCE_LEN {
-        return Err(VisitFailureReason::InternalError);
-    }
-
-    // generate secret opening key with salt
-    let mut active_device_secret_opening_key = [0u8; 32];
-    pbkdf2::pbkdf2::<Hmac<Sha256>>(
-        domain_password.as_bytes(),
-        &password_salt,
-        10000,
-        &mut active_device_secret_opening_key,
-    );
-
-    let unbound_key = match ring::aead::UnboundKey::new(
-        &ring::aead::AES_256_GCM,
-        &active_device_secret_opening_key,
-    ) {
-        Ok(unbound_key) => unbound_key,
-        Err(err) => {
-            tracing::error!(?err, "create unbound key failed");
-            return Err(VisitFailureReason::InternalError);
-        }
-    };
-
-    let mut active_device_secret_opening_nonce = [0u8; ring::aead::NONCE_LEN];
-    active_device_secret_opening_nonce[..ring::aead::NONCE_LEN]
-        .copy_from_slice(&secret_nonce[..ring::aead::NONCE_LEN]);
-
-    let mut active_device_secret_opening_key = ring::aead::OpeningKey::new(
-        unbound_key,
-        NonceValue::new(active_device_secret_opening_nonce),
-    );
-
-    let active_device_secret_buffer = match active_device_secret_opening_key.open_in_place(
-        ring::aead::Aad::from(active_device_id.to_le_bytes()),
-        &mut secret,
-    ) {
-        Ok(buffer) => buffer,
-        Err(_) => return Err(VisitFailureReason::InvalidPassword),
-    };
-
-    let active_device_secret =
-        match bincode_deserialize::<ActiveEndpointKeyExchangeSecret>(&*active_device_secret_buffer)
-        {
-            Ok(secret) => secret,
-            Err(_) => {
-                return Err(VisitFailureReason::InvalidArgs);
-            }
-        };
-
-    if active_device_secret.active_exchange_nonce.len() != ring::aead::NONCE_LEN {
-        return Err(VisitFailureReason::InvalidArgs);
-    }
-
-    // generate passive device key exchange pair and nonce
-
-    let system_random_rng = ring::rand::SystemRandom::new();
-
-    let passive_exchange_private_key = match ring::agreement::EphemeralPrivateKey::generate(
-        &ring::agreement::X25519,
-        &system_random_rng,
-    ) {
-        Ok(private_key) => private_key,
-        Err(_) => return Err(VisitFailureReason::InternalError),
-    };
-
-    let passive_exchange_public_key = match passive_exchange_private_key.compute_public_key() {
-        Ok(public_key) => public_key,
-        Err(err) => {
-            tracing::error!(
-                ?err,
-                "compute public key from passive exchange private key failed"
-            );
-            return Err(VisitFailureReason::InternalError);
-        }
-    };
-
-    let mut passive_exchange_nonce = [0u8; ring::aead::NONCE_LEN];
-    OsRng.fill_bytes(&mut passive_exchange_nonce);
-
-    // key agreement
-
-    let mut active_exchange_nonce = [0u8; ring::aead::NONCE_LEN];
-    active_exchange_nonce[..ring::aead::NONCE_LEN]
-        .copy_from_slice(&active_device_secret.active_exchange_nonce[..ring::aead::NONCE_LEN]);
-
-    let active_exchange_public_key = ring::agreement::UnparsedPublicKey::new(
-        &ring::agreement::X25519,
-        active_device_secret.active_exchange_public_key,
-    );
-
-    let agree_result = ring::agreement::agree_ephemeral(
-        passive_exchange_private_key,
-        &active_exchange_public_key,
-        ring::error::Unspecified,
-        |key_material| {
-            let sealing_key =
-                ring::hkdf::Salt::new(ring::hkdf::HKDF_SHA512, &passive_exchange_nonce)
-                    .extract(key_material)
-                    .expand(&["".as_bytes()], &ring::aead::AES_256_GCM)
-                    .and_then(|orm| {
-                        let mut key = Vec::<u8>::new();
-                        key.resize(ring::aead::AES_256_GCM.key_len(), 0);
-                        orm.fill(&mut key)?;
-                        Ok(key)
-                    })?;
-
-            let opening_key = ring::hkdf::Salt::new(
-                ring::hkdf::HKDF_SHA512,
-                active_device_secret.active_exchange_nonce,
-            )
-            .extract(key_material)
-            .expand(&["".as_bytes()], &ring::aead::AES_256_GCM)
-            .and_then(|orm| {
-                let mut key = Vec::<u8>::new();
-                key.resize(ring::aead::AES_256_GCM.key_len(), 0);
-                orm.fill(&mut key)?;
-                Ok(key)
-            })?;
-
-            Ok((sealing_key, opening_key))
-        },
-    );
-
-    let (raw_sealing_key, raw_opening_key) = match agree_result {
-        Ok(v) => v,
-        Err(err) => {
-            tracing::error!(?err, "agree ephemeral failed");
-            return Err(VisitFailureReason::InternalError);
-        }
-    };
-
-    // derive opening and sealing key
-
-    let unbound_sealing_key = match UnboundKey::new(&ring::aead::AES_256_GCM, &raw_sealing_key) {
-        Ok(unbound_sealing_key) => unbound_sealing_key,
-        Err(err) => {
-            tracing::error!(?err, "create unbound sealing key failed");
-            return Err(VisitFailureReason::InternalError);
-        }
-    };
-
-    let sealing_key = SealingKey::new(unbound_sealing_key, NonceValue::new(active_exchange_nonce));
-
-    let unbound_opening_key = match UnboundKey::new(&ring::aead::AES_256_GCM, &raw_opening_key) {
-        Ok(unbound_opening_key) => unbound_opening_key,
-        Err(err) => {
-            tracing::error!(?err, "create unbound opening failed");
-            return Err(VisitFailureReason::InternalError);
-        }
-    };
-
-    let opening_key =
-        ring::aead::OpeningKey::new(unbound_opening_key, NonceValue::new(passive_exchange_nonce));
-
-    // build key exchange response
-
-    let passive_device_secret = PassiveEndpointKeyExchangeSecret {
-        passive_exchange_public_key: passive_exchange_public_key.as_ref(),
-        passive_exchange_nonce: &passive_exchange_nonce,
-    };
-
-    let passive_device_secret_buffer = match bincode_serialize(&passive_device_secret) {
-        Ok(buffer) => buffer,
-        Err(_) => return Err(VisitFailureReason::InternalError),
-    };
-
-    let active_exchange_reply_public_key = match rsa::RsaPublicKey::new(
-        BigUint::from_bytes_le(active_device_secret.exchange_reply_public_key_n),
-        BigUint::from_bytes_le(active_device_secret.exchange_reply_public_key_e),
-    ) {
-        Ok(public_key) => public_key,
-        Err(err) => {
-            tracing::error!(?err, "recover exchange reply public key failed");
-            return Err(VisitFailureReason::InternalError);
-        }
-    };
-
-    let secret_buffer = match active_exchange_reply_public_key.encrypt(
-        &mut OsRng,
-        rsa::Pkcs1v15Encrypt::default(),
-        &passive_device_secret_buffer,
-    ) {
-        Ok(buffer) => buffer,
-        Err(err) => {
-            tracing::error!(?err, "encrypt exchange reply data failed");
-            return Err(VisitFailureReason::InternalError);
-        }
-    };
-
-    Ok((secret_buffer, sealing_key, opening_key))
-}
@@ -0,0 +1,134 @@
+//! The user who controls a session can invite another device to watch it. Passive device
+//! issues a one-time token bound to the session, the invitation carrying it is passed to
+//! the observer out of band and the observer visits with the token in place of the
+//! device password, like the pairing secret, and sends the random id of the token to
+//! tell which one it uses. The session of the observer streams the
+//! frames which are encoded for the observed session and grants no permissions.
+
+use crate::{core_error, error::CoreResult, utility::rand::generate_random_password};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{sync::Mutex, time::Duration};
+use url::Url;
+
+const INVITATION_SCHEME: &str = "mirrorx";
+const INVITATION_HOST: &str = "observe";
+
+/// How long an invitation is valid when the controller doesn't ask for less.
+pub const OBSERVER_INVITATION_MAX_TTL: Duration = Duration::from_secs(60 * 60);
+
+// tokens which are not used yet
+static OBSERVER_TOKENS: Lazy<Mutex<Vec<ObserverToken>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+struct ObserverToken {
+    id: String,
+    token: String,
+    // session id of the observed session
+    session_id: String,
+    expire: i64,
+}
+
+/// The link which is given to the observer.
+#[derive(Debug, Clone, Serialize)]
+pub struct ObserverInvitation {
+    pub device_id: i64,
+    pub domain: String,
+    pub token_id: String,
+    pub token: String,
+    pub expire: i64,
+}
+
+impl ObserverInvitation {
+    /// Encode as `mirrorx://observe?device_id=..&domain=..&token_id=..&token=..&expire=..`.
+    pub fn encode(&self) -> String {
+        let mut url = Url::parse(&format!("{INVITATION_SCHEME}://{INVITATION_HOST}"))
+            .expect("observer invitation base url should be valid");
+
+        url.query_pairs_mut()
+            .append_pair("device_id", &self.device_id.to_string())
+            .append_pair("domain", &self.domain)
+            .append_pair("token_id", &self.token_id)
+            .append_pair("token", &self.token)
+            .append_pair("expire", &self.expire.to_string());
+
+        url.to_string()
+    }
+
+    /// Parse the invitation link, expired invitation is rejected.
+    pub fn parse(invitation: &str) -> CoreResult<ObserverInvitation> {
+        let url = Url::parse(invitation.trim())?;
+
+        if url.scheme() != INVITATION_SCHEME || url.host_str() != Some(INVITATION_HOST) {
+            return Err(core_error!("not an observer invitation"));
+        }
+
+        let query = |key: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.to_string())
+                .ok_or(core_error!("observer invitation missing '{}'", key))
+        };
+
+        let invitation = ObserverInvitation {
+            device_id: query("device_id")?.parse()?,
+            domain: query("domain")?,
+            token_id: query("token_id")?,
+            token: query("token")?,
+            expire: query("expire")?.parse()?,
+        };
+
+        if invitation.expire < chrono::Utc::now().timestamp() {
+            return Err(core_error!("observer invitation is expired"));
+        }
+
+        Ok(invitation)
+    }
+}
+
+/// Issue a token which admits one observer of the session before it expires, returns
+/// the id of token, the token and its expire timestamp.
+pub(crate) fn create_observer_token(session_id: &str, ttl: Duration) -> (String, String, i64) {
+    let id = generate_random_password();
+    let token = generate_random_password();
+    let expire =
+        chrono::Utc::now().timestamp() + ttl.min(OBSERVER_INVITATION_MAX_TTL).as_secs() as i64;
+
+    let mut tokens = OBSERVER_TOKENS.lock().unwrap();
+    remove_expired(&mut tokens);
+    tokens.push(ObserverToken {
+        id: id.clone(),
+        token: token.clone(),
+        session_id: session_id.to_string(),
+        expire,
+    });
+
+    (id, token, expire)
+}
+
+/// Find the token by the id which observer sent and consume it, returns the token and
+/// the session id which it observes.
+pub(crate) fn find_observer_token(token_id: &[u8]) -> Option<(String, String)> {
+    let mut tokens = OBSERVER_TOKENS.lock().unwrap();
+    remove_expired(&mut tokens);
+
+    let index = tokens
+        .iter()
+        .position(|token| token.id.as_bytes() == token_id)?;
+
+    let token = tokens.remove(index);
+    Some((token.token, token.session_id))
+}
+
+/// Revoke the tokens of the session which are not used yet, it's called once the
+/// session ends.
+pub(crate) fn revoke_observer_tokens(session_id: &str) {
+    OBSERVER_TOKENS
+        .lock()
+        .unwrap()
+        .retain(|token| token.session_id != session_id);
+}
+
+fn remove_expired(tokens: &mut Vec<ObserverToken>) {
+    let now = chrono::Utc::now().timestamp();
+    tokens.retain(|token| token.expire >= now);
+}
@@ -0,0 +1,130 @@
+use crate::{
+    api::config::entity::domain::Domain, core_error, error::CoreResult,
+    utility::rand::generate_random_password,
+};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{sync::Mutex, time::Duration};
+use url::Url;
+
+const PAIRING_SCHEME: &str = "mirrorx";
+const PAIRING_HOST: &str = "pair";
+
+// the QR code is expected to be scanned soon after displayed
+const PAIRING_SECRET_TTL: Duration = Duration::from_secs(5 * 60);
+
+// one-time secrets which are accepted in place of the domain password once and then
+// removed
+static PAIRING_SECRETS: Lazy<Mutex<Vec<PairingSecret>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+struct PairingSecret {
+    id: String,
+    secret: String,
+    expire: i64,
+}
+
+/// The content encoded into pairing QR code.
+#[derive(Debug, Clone, Serialize)]
+pub struct PairingPayload {
+    pub device_id: i64,
+    pub domain: String,
+    // random id which active device sends to tell which secret it uses, see
+    // `find_pairing_secret`
+    pub secret_id: String,
+    pub secret: String,
+    pub expire: i64,
+}
+
+impl PairingPayload {
+    /// Encode as `mirrorx://pair?device_id=..&domain=..&secret_id=..&secret=..&expire=..`.
+    pub fn encode(&self) -> String {
+        let mut url = Url::parse(&format!("{PAIRING_SCHEME}://{PAIRING_HOST}"))
+            .expect("pairing base url should be valid");
+
+        url.query_pairs_mut()
+            .append_pair("device_id", &self.device_id.to_string())
+            .append_pair("domain", &self.domain)
+            .append_pair("secret_id", &self.secret_id)
+            .append_pair("secret", &self.secret)
+            .append_pair("expire", &self.expire.to_string());
+
+        url.to_string()
+    }
+
+    /// Parse the scanned payload, expired payload is rejected.
+    pub fn parse(payload: &str) -> CoreResult<PairingPayload> {
+        let url = Url::parse(payload.trim())?;
+
+        if url.scheme() != PAIRING_SCHEME || url.host_str() != Some(PAIRING_HOST) {
+            return Err(core_error!("not a pairing payload"));
+        }
+
+        let query = |key: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.to_string())
+                .ok_or(core_error!("pairing payload missing '{}'", key))
+        };
+
+        let payload = PairingPayload {
+            device_id: query("device_id")?.parse()?,
+            domain: query("domain")?,
+            secret_id: query("secret_id")?,
+            secret: query("secret")?,
+            expire: query("expire")?.parse()?,
+        };
+
+        if payload.expire < chrono::Utc::now().timestamp() {
+            return Err(core_error!("pairing payload is expired"));
+        }
+
+        Ok(payload)
+    }
+}
+
+/// Create a pairing payload with a new one-time secret for the device in domain.
+pub fn create_pairing_payload(domain: &Domain) -> PairingPayload {
+    let secret_id = generate_random_password();
+    let secret = generate_random_password();
+    let expire = chrono::Utc::now().timestamp() + PAIRING_SECRET_TTL.as_secs() as i64;
+
+    let mut secrets = PAIRING_SECRETS.lock().unwrap();
+    remove_expired(&mut secrets);
+    secrets.push(PairingSecret {
+        id: secret_id.clone(),
+        secret: secret.clone(),
+        expire,
+    });
+
+    PairingPayload {
+        device_id: domain.device_id,
+        domain: domain.name.clone(),
+        secret_id,
+        secret,
+        expire,
+    }
+}
+
+/// Revoke all one-time secrets which are not used yet, e.g. the QR code is closed.
+pub fn revoke_pairing_secrets() {
+    PAIRING_SECRETS.lock().unwrap().clear();
+}
+
+/// Find the pairing secret by its id and consume it. Active device which visits with
+/// pairing secret sends the id through signaling server, since the secret can't be found
+/// by trying them in PAKE. The id is random, so it tells nothing about the secret.
+pub(crate) fn find_pairing_secret(secret_id: &[u8]) -> Option<String> {
+    let mut secrets = PAIRING_SECRETS.lock().unwrap();
+    remove_expired(&mut secrets);
+
+    let index = secrets
+        .iter()
+        .position(|secret| secret.id.as_bytes() == secret_id)?;
+
+    Some(secrets.remove(index).secret)
+}
+
+fn remove_expired(secrets: &mut Vec<PairingSecret>) {
+    let now = chrono::Utc::now().timestamp();
+    secrets.retain(|secret| secret.expire >= now);
+}
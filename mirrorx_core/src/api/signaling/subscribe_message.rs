@@ -1,9 +1,11 @@
+use crate::component::availability::Availability;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Subscription {
     pub device_id: i64,
     pub device_finger_print: String,
+    pub availability: Availability,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -12,6 +14,30 @@ pub enum VisitFailureReason {
     InvalidPassword,
     InternalError,
     InvalidArgs,
+    // remote user is busy or doesn't want to be disturbed
+    RemoteUnavailable,
+    // remote sessions are full and the visit waits too long in the queue
+    QueueTimeout,
+    // the access control list of remote device denies the visitor
+    AclDenied,
+    // rejected by remote device for the reason, with the text which host user left for
+    // rejected visitors
+    Rejected {
+        reason: RejectReason,
+        message: Option<String>,
+    },
+    // remote device speaks another version of the endpoint protocol
+    IncompatibleProtocol,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum RejectReason {
+    // denied by remote user at the prompt or in the queue
+    User,
+    DoNotDisturb,
+    Acl,
+    // out of the hours which remote device accepts visits in
+    Schedule,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -31,6 +57,17 @@ pub enum ServerMessage {
         #[serde(with = "serde_bytes")]
         passive_visit_credentials: Vec<u8>,
     },
+    // relayed from passive device whose queue the visit is waiting in
+    VisitQueuePosition {
+        active_device_id: i64,
+        passive_device_id: i64,
+        position: u32,
+    },
+    // active device gave up the visit before passive device replied it
+    VisitCanceled {
+        active_device_id: i64,
+        passive_device_id: i64,
+    },
 }
 
 #[serde_with::serde_as]
@@ -43,18 +80,39 @@ pub enum ClientMessage {
         #[serde_as(as = "Result<serde_with::Bytes, _>")]
         result: Result<Vec<u8>, VisitFailureReason>,
     },
+    Availability(Availability),
+    VisitQueuePosition {
+        active_device_id: i64,
+        passive_device_id: i64,
+        position: u32,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ActiveEndpointKeyExchangeSecret<'a> {
-    pub exchange_reply_public_key_n: &'a [u8],
-    pub exchange_reply_public_key_e: &'a [u8],
+    // see `key_exchange::PROTOCOL_VERSION`
+    pub protocol_version: u16,
+    pub pake_message: &'a [u8],
     pub active_exchange_public_key: &'a [u8],
-    pub active_exchange_nonce: &'a [u8],
+    // random id of the one-time pairing secret or observer token which is used as
+    // password, see `pairing::find_pairing_secret`
+    #[serde(borrow)]
+    pub secret_id: Option<&'a [u8]>,
+    pub identity_public_key: &'a [u8],
+    // signature of exchange public key and nonce by identity key
+    pub identity_signature: &'a [u8],
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PassiveEndpointKeyExchangeSecret<'a> {
+    // see `key_exchange::PROTOCOL_VERSION`
+    pub protocol_version: u16,
+    pub pake_message: &'a [u8],
     pub passive_exchange_public_key: &'a [u8],
     pub passive_exchange_nonce: &'a [u8],
+    // MAC of the key exchange transcript by PAKE key
+    pub key_confirmation: &'a [u8],
+    pub identity_public_key: &'a [u8],
+    // signature of exchange public key and nonce by identity key
+    pub identity_signature: &'a [u8],
 }
@@ -0,0 +1,142 @@
+//! Incoming visits are pushed through the signaling subscription, host user may be asked
+//! to accept or deny them before the key exchange and endpoint handshake start.
+
+use crate::{
+    api::notification::{self, Notification},
+    core_error,
+    error::CoreResult,
+};
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+/// The visit is denied when host user doesn't decide in time.
+pub const VISIT_PROMPT_TIMEOUT: Duration = Duration::from_secs(30);
+
+static CONFIRM_VISIT: AtomicBool = AtomicBool::new(false);
+
+static REJECT_MESSAGE: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+static PENDING_VISITS: Lazy<Mutex<HashMap<String, PendingVisit>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct PendingVisit {
+    active_device_id: i64,
+    passive_device_id: i64,
+    // text which host user left for this visitor when denying it
+    decision_tx: oneshot::Sender<Result<(), Option<String>>>,
+}
+
+/// Set whether host user accepts every visit by hand, otherwise visits with the right
+/// password are accepted and only notified.
+pub fn set_confirm_visit(confirm: bool) {
+    CONFIRM_VISIT.store(confirm, Ordering::SeqCst);
+}
+
+/// Set the text which rejected visitors are shown, None shows the reason only.
+pub fn set_reject_message(message: Option<String>) {
+    *REJECT_MESSAGE.lock().unwrap() = message;
+}
+
+/// The text which host user left for rejected visitors.
+pub(crate) fn reject_message() -> Option<String> {
+    REJECT_MESSAGE.lock().unwrap().clone()
+}
+
+/// Reply the visit request which host user was prompted with, denied visitor is shown
+/// `message` or the configured reject message when it's None.
+pub fn reply_visit(request_id: &str, allow: bool, message: Option<String>) -> CoreResult<()> {
+    let pending = PENDING_VISITS
+        .lock()
+        .unwrap()
+        .remove(request_id)
+        .ok_or(core_error!("visit request not exists or expired"))?;
+
+    pending
+        .decision_tx
+        .send(if allow { Ok(()) } else { Err(message) })
+        .map_err(|_| core_error!("visit request not exists or expired"))
+}
+
+/// Notify host user of the incoming visit and wait for the decision when visits need to
+/// be confirmed, a denied visit returns the text which visitor is shown.
+pub(crate) async fn prompt_visit(
+    active_device_id: i64,
+    passive_device_id: i64,
+    visit_desktop: bool,
+) -> Result<(), Option<String>> {
+    if !CONFIRM_VISIT.load(Ordering::SeqCst) {
+        notification::publish(Notification::VisitRequest {
+            request_id: None,
+            active_device_id,
+            passive_device_id,
+            visit_desktop,
+        });
+
+        return Ok(());
+    }
+
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let (decision_tx, decision_rx) = oneshot::channel();
+
+    PENDING_VISITS.lock().unwrap().insert(
+        request_id.clone(),
+        PendingVisit {
+            active_device_id,
+            passive_device_id,
+            decision_tx,
+        },
+    );
+
+    notification::publish(Notification::VisitRequest {
+        request_id: Some(request_id.clone()),
+        active_device_id,
+        passive_device_id,
+        visit_desktop,
+    });
+
+    match tokio::time::timeout(VISIT_PROMPT_TIMEOUT, decision_rx).await {
+        Ok(Ok(decision)) => decision.map_err(|message| message.or_else(reject_message)),
+        // canceled by active device
+        Ok(Err(_)) => Err(None),
+        Err(_) => {
+            tracing::info!(active_device_id, "visit request is not decided in time");
+            PENDING_VISITS.lock().unwrap().remove(&request_id);
+            notification::publish(Notification::VisitRequestCanceled { request_id });
+            Err(reject_message())
+        }
+    }
+}
+
+/// Drop the prompts of visits which active device gave up, the visits are denied.
+pub(crate) fn cancel_visit(active_device_id: i64, passive_device_id: i64) {
+    let canceled: Vec<String> = {
+        let mut pending_visits = PENDING_VISITS.lock().unwrap();
+        let request_ids: Vec<String> = pending_visits
+            .iter()
+            .filter(|(_, pending)| {
+                pending.active_device_id == active_device_id
+                    && pending.passive_device_id == passive_device_id
+            })
+            .map(|(request_id, _)| request_id.clone())
+            .collect();
+
+        for request_id in &request_ids {
+            pending_visits.remove(request_id);
+        }
+
+        request_ids
+    };
+
+    for request_id in canceled {
+        tracing::info!(active_device_id, "visit request is canceled");
+        notification::publish(Notification::VisitRequestCanceled { request_id });
+    }
+}
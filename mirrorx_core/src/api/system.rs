@@ -1,3 +1,6 @@
+use crate::error::CoreResult;
+use std::time::Duration;
+
 pub fn set_show_cursor(show: bool) {
     #[cfg(target_os = "windows")]
     unsafe {
@@ -15,3 +18,51 @@ pub fn set_show_cursor(show: bool) {
         }
     }
 }
+
+/// Reboot this machine after `delay`. The host comes back online by itself only when
+/// it's started with the system, like running as a service.
+pub fn schedule_reboot(delay: Duration) -> CoreResult<()> {
+    #[cfg(target_os = "windows")]
+    {
+        use crate::core_error;
+
+        let status = std::process::Command::new("shutdown")
+            .args(["/r", "/t", &delay.as_secs().to_string()])
+            .args(["/c", "Rebooted remotely by MirrorX"])
+            .status()?;
+
+        if !status.success() {
+            return Err(core_error!("schedule reboot failed ({})", status));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        use crate::utility::spawn;
+
+        // other platforms can't schedule reboot in seconds, so wait for the delay here
+        spawn::spawn(async move {
+            tokio::time::sleep(delay).await;
+
+            #[cfg(target_os = "macos")]
+            let result = std::process::Command::new("osascript")
+                .args(["-e", "tell application \"System Events\" to restart"])
+                .status();
+
+            #[cfg(not(target_os = "macos"))]
+            let result = std::process::Command::new("systemctl")
+                .arg("reboot")
+                .status();
+
+            match result {
+                Ok(status) if status.success() => tracing::info!("reboot requested"),
+                Ok(status) => tracing::error!(%status, "request reboot failed"),
+                Err(err) => tracing::error!(?err, "request reboot failed"),
+            }
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+//! Emit the golden vectors of the endpoint protocol at release, or check the vectors of
+//! previous releases against this build.
+//!
+//! protocol_vectors emit <dir>
+//! protocol_vectors check <dir>...
+
+use mirrorx_core::api::endpoint::protocol_vectors::{check_vectors, emit_vectors};
+use std::{path::PathBuf, process::ExitCode};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.split_first() {
+        Some((command, dirs)) if command == "emit" && dirs.len() == 1 => {
+            match emit_vectors(&PathBuf::from(&dirs[0])) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => {
+                    eprintln!("emit vectors failed: {err}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Some((command, dirs)) if command == "check" && !dirs.is_empty() => {
+            let mut compatible = true;
+
+            for dir in dirs {
+                match check_vectors(&PathBuf::from(dir)) {
+                    Ok(mismatches) if mismatches.is_empty() => println!("{dir}: compatible"),
+                    Ok(mismatches) => {
+                        compatible = false;
+                        for mismatch in mismatches {
+                            println!("{dir}: {mismatch:?}");
+                        }
+                    }
+                    Err(err) => {
+                        compatible = false;
+                        println!("{dir}: check failed: {err}");
+                    }
+                }
+            }
+
+            if compatible {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        _ => {
+            eprintln!("usage: protocol_vectors emit <dir> | check <dir>...");
+            ExitCode::FAILURE
+        }
+    }
+}
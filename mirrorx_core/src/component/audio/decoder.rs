@@ -74,7 +74,6 @@ impl AudioDecoder {
                         cpal_sample_format_to_av_sample_format(self.out_sample_format);
 
                     self.resampler = Some(Resampler::new(
-                        960 / self.channels as i32,
                         self.channels as _,
                         self.sample_rate as _,
                         input_av_sample_format as _,
@@ -95,25 +94,46 @@ impl AudioDecoder {
                 }
             }
 
+            self.decode_packet(Some(&audio_frame.buffer))
+        }
+    }
+
+    /// Make up the frame which didn't arrive in time with the packet loss concealment of
+    /// Opus, None before any frame is decoded.
+    pub fn conceal(&mut self) -> CoreResult<Option<Vec<u8>>> {
+        if self.opus_decoder.is_null() {
+            return Ok(None);
+        }
+
+        self.decode_packet(None).map(Some)
+    }
+
+    fn decode_packet(&mut self, packet: Option<&[u8]>) -> CoreResult<Vec<u8>> {
+        unsafe {
             let mut buffer = Vec::<u8>::with_capacity(960 * self.sample_format.sample_size());
 
-            let frame_size = buffer.capacity()
-                / self.sample_format.sample_size()
-                / (audio_frame.channels as usize);
+            let frame_size =
+                buffer.capacity() / self.sample_format.sample_size() / (self.channels as usize);
+
+            // null data asks the decoder to conceal the lost packet
+            let (data, len) = match packet {
+                Some(packet) => (packet.as_ptr(), packet.len()),
+                None => (std::ptr::null(), 0),
+            };
 
             let ret = match self.sample_format {
                 SampleFormat::I16 | SampleFormat::U16 => opus_decode(
                     self.opus_decoder,
-                    audio_frame.buffer.as_ptr(),
-                    audio_frame.buffer.len() as _,
+                    data,
+                    len as _,
                     std::mem::transmute(buffer.as_mut_ptr()),
                     frame_size as _,
                     0,
                 ),
                 SampleFormat::F32 => opus_decode_float(
                     self.opus_decoder,
-                    audio_frame.buffer.as_ptr(),
-                    audio_frame.buffer.len() as _,
+                    data,
+                    len as _,
                     std::mem::transmute(buffer.as_mut_ptr()),
                     frame_size as _,
                     0,
@@ -121,6 +141,10 @@ impl AudioDecoder {
                 _ => return Err(core_error!("unsupported sample format")),
             };
 
+            if ret < 0 {
+                return Err(core_error!("opus decode failed ({})", ret));
+            }
+
             buffer.set_len(
                 (ret as usize) * self.sample_format.sample_size() * (self.channels as usize),
             );
@@ -1,7 +1,7 @@
 use super::resampler::{cpal_sample_format_to_av_sample_format, Resampler};
 use crate::{
     api::endpoint::message::{AudioSampleFormat, EndPointAudioFrame},
-    component::frame::AudioEncodeFrame,
+    component::{clock_sync, frame::AudioEncodeFrame},
     core_error,
     error::CoreResult,
 };
@@ -48,9 +48,6 @@ impl AudioEncoder {
 
                 self.resampler = if self.sample_rate != 48000 {
                     let resampler = Resampler::new(
-                        (capture_frame.buffer.len()
-                            / self.sample_format.sample_size()
-                            / (self.channels as usize)) as _,
                         self.channels,
                         self.sample_rate as _,
                         cpal_sample_format_to_av_sample_format(self.sample_format),
@@ -97,6 +94,7 @@ impl AudioEncoder {
                     sample_format: AudioSampleFormat::from(self.sample_format),
                     sample_rate: 48000,
                     buffer: self.encode_buffer[..ret as usize].to_vec(),
+                    timestamp_micros: clock_sync::now_micros(),
                 })
             } else {
                 Err(core_error!("opus encode failed ({})", ret))
@@ -0,0 +1,135 @@
+//! Network delivers audio frames in bursts, playing them as they arrive clicks whenever
+//! the output device asks for samples during a gap. Decoded samples are held in a jitter
+//! buffer instead, playback starts once it holds the target delay, and the target
+//! follows the inter-arrival jitter estimated like RFC 3550 does. When the buffer runs
+//! dry the decode process conceals the missing frame with Opus PLC, and when it holds
+//! far more than the target the oldest samples are dropped to catch up.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Duration of every frame which active endpoint encodes.
+pub const FRAME_DURATION: Duration = Duration::from_millis(10);
+
+const MIN_DELAY: Duration = Duration::from_millis(30);
+const MAX_DELAY: Duration = Duration::from_millis(200);
+
+// samples beyond the target plus this are dropped, so a burst after a stall doesn't
+// leave the playback behind for the rest of the session
+const OVERRUN_MARGIN: Duration = Duration::from_millis(80);
+
+pub type SharedJitterBuffer = Arc<Mutex<JitterBuffer>>;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JitterStats {
+    pub underruns: u64,
+    pub overruns: u64,
+    pub concealed_frames: u64,
+}
+
+pub struct JitterBuffer {
+    samples: VecDeque<u8>,
+    // bytes of one sample of every channel, samples are never split across it
+    frame_bytes: usize,
+    bytes_per_ms: f64,
+    jitter_ms: f64,
+    target_delay: Duration,
+    last_arrival: Option<Instant>,
+    playing: bool,
+    stats: JitterStats,
+}
+
+impl JitterBuffer {
+    pub fn new(channels: u16, sample_size: usize, sample_rate: u32) -> SharedJitterBuffer {
+        let frame_bytes = channels as usize * sample_size;
+
+        Arc::new(Mutex::new(JitterBuffer {
+            samples: VecDeque::new(),
+            frame_bytes,
+            bytes_per_ms: (frame_bytes * sample_rate as usize) as f64 / 1000.0,
+            jitter_ms: 0.0,
+            target_delay: MIN_DELAY,
+            last_arrival: None,
+            playing: false,
+            stats: JitterStats::default(),
+        }))
+    }
+
+    /// Append the decoded samples, `concealed` when they're made up by PLC rather than
+    /// received, they don't count for jitter.
+    pub fn push(&mut self, samples: &[u8], concealed: bool) {
+        if concealed {
+            self.stats.concealed_frames += 1;
+        } else {
+            self.observe_arrival(Instant::now());
+        }
+
+        self.samples.extend(samples);
+
+        let max_bytes = self.bytes_of(self.target_delay + OVERRUN_MARGIN);
+        if self.samples.len() > max_bytes {
+            let excess = self.samples.len() - self.bytes_of(self.target_delay);
+            self.samples.drain(..excess);
+            self.stats.overruns += 1;
+        }
+    }
+
+    /// Fill the output with buffered samples, silence is played until the buffer holds
+    /// the target delay again once it runs dry.
+    pub fn pull(&mut self, output: &mut [u8]) {
+        if !self.playing {
+            if self.samples.len() < self.bytes_of(self.target_delay) {
+                output.fill(0);
+                return;
+            }
+
+            self.playing = true;
+        }
+
+        let available = self.samples.len().min(output.len());
+        for (dst, src) in output.iter_mut().zip(self.samples.drain(..available)) {
+            *dst = src;
+        }
+
+        if available < output.len() {
+            output[available..].fill(0);
+            self.stats.underruns += 1;
+            self.playing = false;
+        }
+    }
+
+    /// Whether the playing buffer has less than one frame left, the next output callback
+    /// underruns unless a frame is concealed.
+    pub fn starving(&self) -> bool {
+        self.playing && self.samples.len() < self.bytes_of(FRAME_DURATION)
+    }
+
+    pub fn target_delay(&self) -> Duration {
+        self.target_delay
+    }
+
+    /// Stats since the last call.
+    pub fn take_stats(&mut self) -> JitterStats {
+        std::mem::take(&mut self.stats)
+    }
+
+    fn observe_arrival(&mut self, now: Instant) {
+        if let Some(last_arrival) = self.last_arrival.replace(now) {
+            let interval_ms = now.duration_since(last_arrival).as_secs_f64() * 1000.0;
+            let deviation_ms = (interval_ms - FRAME_DURATION.as_secs_f64() * 1000.0).abs();
+            self.jitter_ms += (deviation_ms - self.jitter_ms) / 16.0;
+        }
+
+        // three times the jitter covers most arrivals of a normal distribution
+        let target_ms = FRAME_DURATION.as_secs_f64() * 1000.0 + 3.0 * self.jitter_ms;
+        self.target_delay = Duration::from_secs_f64(target_ms / 1000.0).clamp(MIN_DELAY, MAX_DELAY);
+    }
+
+    fn bytes_of(&self, duration: Duration) -> usize {
+        let bytes = (duration.as_secs_f64() * 1000.0 * self.bytes_per_ms) as usize;
+        bytes - bytes % self.frame_bytes
+    }
+}
@@ -1,5 +1,13 @@
 pub mod decoder;
 pub mod encoder;
+pub mod jitter_buffer;
 pub mod player;
+pub mod processor;
 pub mod recorder;
 pub mod resampler;
+
+#[cfg(target_os = "macos")]
+mod screen_capture;
+
+#[cfg(target_os = "windows")]
+mod process_loopback;
@@ -1,10 +1,17 @@
+use super::jitter_buffer::SharedJitterBuffer;
 use crate::{core_error, error::CoreResult};
 use cpal::{
     traits::{DeviceTrait, HostTrait},
-    SampleFormat, SampleRate, SizedSample, Stream, StreamConfig, SupportedStreamConfig,
+    BuildStreamError, Device, SampleFormat, SampleRate, SizedSample, Stream, StreamConfig,
+    StreamError, SupportedStreamConfig,
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
 };
-use tokio::sync::mpsc::{Receiver, Sender};
 
+/// The default config of the default output device, its sample format is swapped for
+/// one which decoded samples can be converted to when the device defaults to another.
 pub fn default_output_config() -> CoreResult<SupportedStreamConfig> {
     let host = cpal::default_host();
 
@@ -16,15 +23,52 @@ pub fn default_output_config() -> CoreResult<SupportedStreamConfig> {
     };
     tracing::info!(name = ?device.name(), "select audio output device");
 
-    Ok(device.default_output_config()?)
+    let config = device.default_output_config()?;
+    if is_playable(config.sample_format()) {
+        return Ok(config);
+    }
+
+    let mut alternatives: Vec<SupportedStreamConfig> = device
+        .supported_output_configs()?
+        .filter(|range| {
+            range.channels() == config.channels()
+                && is_playable(range.sample_format())
+                && range.min_sample_rate() <= config.sample_rate()
+                && config.sample_rate() <= range.max_sample_rate()
+        })
+        .map(|range| range.with_sample_rate(config.sample_rate()))
+        .collect();
+
+    // float takes the samples of opus decoder without conversion
+    alternatives.sort_by_key(|alternative| alternative.sample_format() != SampleFormat::F32);
+
+    alternatives.into_iter().next().ok_or_else(|| {
+        core_error!(
+            "audio output device doesn't support playable sample format besides {:?}",
+            config.sample_format()
+        )
+    })
+}
+
+// the formats which re-sampler produces exactly and whose silence is zeroed bytes, the
+// other integer formats differ in signedness from the ones of FFmpeg
+fn is_playable(sample_format: SampleFormat) -> bool {
+    matches!(
+        sample_format,
+        SampleFormat::I16 | SampleFormat::I32 | SampleFormat::F32 | SampleFormat::F64
+    )
 }
 
-pub fn new_play_stream_and_tx(
+/// Play the samples which are pulled from `jitter_buffer`, `device_lost` is set once the
+/// device is unplugged or switched away, the stream has to be rebuilt on the new default.
+pub fn new_play_stream(
     channels: u16,
     sample_format: SampleFormat,
     sample_rate: SampleRate,
     buffer_size: u32,
-) -> CoreResult<(Stream, Sender<Vec<u8>>)> {
+    jitter_buffer: SharedJitterBuffer,
+    device_lost: Arc<AtomicBool>,
+) -> CoreResult<Stream> {
     let host = cpal::default_host();
 
     let device = match host.default_output_device() {
@@ -48,28 +92,19 @@ pub fn new_play_stream_and_tx(
         buffer_size: cpal::BufferSize::Fixed(buffer_size),
     };
 
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<Vec<u8>>(180);
-    let err_fn = |err| tracing::error!(?err, "an error occurred when play audio sample");
-
     let stream = match sample_format {
-        SampleFormat::I16 => device.build_output_stream(
-            &output_config,
-            move |data, _| play_samples::<i16>(data, &mut rx),
-            err_fn,
-            None,
-        ),
-        SampleFormat::U16 => device.build_output_stream(
-            &output_config,
-            move |data, _| play_samples::<u16>(data, &mut rx),
-            err_fn,
-            None,
-        ),
-        SampleFormat::F32 => device.build_output_stream(
-            &output_config,
-            move |data, _| play_samples::<f32>(data, &mut rx),
-            err_fn,
-            None,
-        ),
+        SampleFormat::I16 => {
+            build_stream::<i16>(&device, &output_config, jitter_buffer, device_lost)
+        }
+        SampleFormat::I32 => {
+            build_stream::<i32>(&device, &output_config, jitter_buffer, device_lost)
+        }
+        SampleFormat::F32 => {
+            build_stream::<f32>(&device, &output_config, jitter_buffer, device_lost)
+        }
+        SampleFormat::F64 => {
+            build_stream::<f64>(&device, &output_config, jitter_buffer, device_lost)
+        }
         _ => {
             return Err(core_error!(
                 "unsupported sample format: {:?}",
@@ -78,20 +113,42 @@ pub fn new_play_stream_and_tx(
         }
     }?;
 
-    Ok((stream, tx))
+    Ok(stream)
 }
 
-fn play_samples<T>(data: &mut [T], rx: &mut Receiver<Vec<u8>>)
+fn build_stream<T>(
+    device: &Device,
+    output_config: &StreamConfig,
+    jitter_buffer: SharedJitterBuffer,
+    device_lost: Arc<AtomicBool>,
+) -> Result<Stream, BuildStreamError>
 where
     T: SizedSample,
 {
-    if let Some(samples) = rx.blocking_recv() {
-        unsafe {
-            std::ptr::copy_nonoverlapping(
-                std::mem::transmute(samples.as_ptr()),
-                data.as_mut_ptr(),
-                (samples.len() / T::FORMAT.sample_size()).min(data.len()),
-            )
-        }
+    device.build_output_stream(
+        output_config,
+        move |data, _| play_samples::<T>(data, &jitter_buffer),
+        move |err| {
+            tracing::error!(?err, "an error occurred when play audio sample");
+
+            if let StreamError::DeviceNotAvailable = err {
+                device_lost.store(true, Ordering::SeqCst);
+            }
+        },
+        None,
+    )
+}
+
+fn play_samples<T>(data: &mut [T], jitter_buffer: &SharedJitterBuffer)
+where
+    T: SizedSample,
+{
+    let output = unsafe {
+        std::slice::from_raw_parts_mut(
+            data.as_mut_ptr() as *mut u8,
+            data.len() * T::FORMAT.sample_size(),
+        )
     };
+
+    jitter_buffer.lock().unwrap().pull(output);
 }
@@ -0,0 +1,356 @@
+//! WASAPI process loopback captures the audio of one application and its child
+//! processes instead of the whole system mix, it's available since Windows 10 2004.
+
+use super::recorder::send_encode_frames;
+use crate::{
+    api::endpoint::message::AudioApplication,
+    component::frame::AudioEncodeFrame,
+    core_error,
+    error::{CoreError, CoreResult},
+    HRESULT,
+};
+use scopeguard::defer;
+use std::{
+    mem::ManuallyDrop,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::mpsc::Sender;
+use windows::{
+    core::{implement, Interface, PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{CloseHandle, WAIT_OBJECT_0},
+        Media::Audio::{
+            eConsole, eRender, ActivateAudioInterfaceAsync, AudioSessionStateExpired,
+            IActivateAudioInterfaceAsyncOperation, IActivateAudioInterfaceCompletionHandler,
+            IActivateAudioInterfaceCompletionHandler_Impl, IAudioCaptureClient, IAudioClient,
+            IAudioSessionControl2, IAudioSessionManager2, IMMDeviceEnumerator, MMDeviceEnumerator,
+            AUDCLNT_BUFFERFLAGS_SILENT, AUDCLNT_SHAREMODE_SHARED,
+            AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM, AUDCLNT_STREAMFLAGS_EVENTCALLBACK,
+            AUDCLNT_STREAMFLAGS_LOOPBACK, AUDIOCLIENT_ACTIVATION_PARAMS,
+            AUDIOCLIENT_ACTIVATION_PARAMS_0, AUDIOCLIENT_ACTIVATION_TYPE_PROCESS_LOOPBACK,
+            AUDIOCLIENT_PROCESS_LOOPBACK_PARAMS, PROCESS_LOOPBACK_MODE_INCLUDE_TARGET_PROCESS_TREE,
+            VIRTUAL_AUDIO_DEVICE_PROCESS_LOOPBACK, WAVEFORMATEX,
+        },
+        System::{
+            Com::{
+                CoCreateInstance, CoInitializeEx, CoUninitialize, IAgileObject, IAgileObject_Impl,
+                StructuredStorage::{
+                    PROPVARIANT, PROPVARIANT_0, PROPVARIANT_0_0, PROPVARIANT_0_0_0,
+                },
+                BLOB, CLSCTX_ALL, COINIT_MULTITHREADED, VT_BLOB,
+            },
+            Threading::{
+                CreateEventW, OpenProcess, QueryFullProcessImageNameW, WaitForSingleObject,
+                PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+            },
+        },
+    },
+};
+
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const CAPTURE_SAMPLE_RATE: u32 = 48000;
+const CAPTURE_CHANNELS: u16 = 2;
+const CAPTURE_BLOCK_ALIGN: u16 = CAPTURE_CHANNELS * 4;
+
+// in 100-nanosecond units
+const CAPTURE_BUFFER_DURATION: i64 = 200_000;
+
+const ACTIVATE_TIMEOUT: Duration = Duration::from_secs(5);
+const WAIT_PACKET_TIMEOUT_MS: u32 = 100;
+
+pub struct ProcessLoopbackCapture {
+    process_id: u32,
+    tx: Sender<AudioEncodeFrame>,
+    exit: Arc<AtomicBool>,
+}
+
+impl ProcessLoopbackCapture {
+    pub fn new(process_id: u32, tx: Sender<AudioEncodeFrame>) -> Self {
+        Self {
+            process_id,
+            tx,
+            exit: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Start capturing in a dedicated thread, returns after the audio client started.
+    pub fn start(&self) -> CoreResult<()> {
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let process_id = self.process_id;
+        let tx = self.tx.clone();
+        let exit = self.exit.clone();
+
+        std::thread::Builder::new()
+            .name(String::from("process_loopback_capture"))
+            .spawn(move || {
+                if let Err(err) = unsafe { serve_capture(process_id, tx, exit, &started_tx) } {
+                    tracing::error!(?err, process_id, "process loopback capture failed");
+                    let _ = started_tx.send(Err(err));
+                }
+            })?;
+
+        started_rx
+            .recv_timeout(ACTIVATE_TIMEOUT)
+            .map_err(|_| CoreError::Timeout)?
+    }
+}
+
+impl Drop for ProcessLoopbackCapture {
+    fn drop(&mut self) {
+        self.exit.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Applications which are playing audio on the default render device now.
+pub fn list_audio_applications() -> CoreResult<Vec<AudioApplication>> {
+    unsafe {
+        // COM may be initialized by the caller thread already
+        let com_initialized = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+
+        defer! {
+            if com_initialized {
+                CoUninitialize();
+            }
+        }
+
+        let device_enumerator: IMMDeviceEnumerator =
+            HRESULT!(CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL));
+        let device = HRESULT!(device_enumerator.GetDefaultAudioEndpoint(eRender, eConsole));
+        let session_manager: IAudioSessionManager2 = HRESULT!(device.Activate(CLSCTX_ALL, None));
+        let sessions = HRESULT!(session_manager.GetSessionEnumerator());
+
+        let mut applications: Vec<AudioApplication> = Vec::new();
+
+        for index in 0..HRESULT!(sessions.GetCount()) {
+            let session = HRESULT!(sessions.GetSession(index));
+            let session: IAudioSessionControl2 = HRESULT!(session.cast());
+
+            if HRESULT!(session.GetState()) == AudioSessionStateExpired {
+                continue;
+            }
+
+            // system sounds session belongs to no process
+            let process_id = HRESULT!(session.GetProcessId());
+            if process_id == 0
+                || applications
+                    .iter()
+                    .any(|application| application.process_id == process_id)
+            {
+                continue;
+            }
+
+            applications.push(AudioApplication {
+                process_id,
+                name: process_name(process_id).unwrap_or_else(|| format!("PID {process_id}")),
+            });
+        }
+
+        Ok(applications)
+    }
+}
+
+unsafe fn serve_capture(
+    process_id: u32,
+    tx: Sender<AudioEncodeFrame>,
+    exit: Arc<AtomicBool>,
+    started_tx: &std::sync::mpsc::Sender<CoreResult<()>>,
+) -> CoreResult<()> {
+    HRESULT!(CoInitializeEx(None, COINIT_MULTITHREADED));
+
+    defer! {
+        CoUninitialize();
+    }
+
+    let audio_client = activate_process_loopback(process_id)?;
+
+    // process loopback has no mix format, the audio engine converts to the requested one
+    let format = WAVEFORMATEX {
+        wFormatTag: WAVE_FORMAT_IEEE_FLOAT,
+        nChannels: CAPTURE_CHANNELS,
+        nSamplesPerSec: CAPTURE_SAMPLE_RATE,
+        nAvgBytesPerSec: CAPTURE_SAMPLE_RATE * CAPTURE_BLOCK_ALIGN as u32,
+        nBlockAlign: CAPTURE_BLOCK_ALIGN,
+        wBitsPerSample: 32,
+        cbSize: 0,
+    };
+
+    HRESULT!(audio_client.Initialize(
+        AUDCLNT_SHAREMODE_SHARED,
+        AUDCLNT_STREAMFLAGS_LOOPBACK
+            | AUDCLNT_STREAMFLAGS_EVENTCALLBACK
+            | AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM,
+        CAPTURE_BUFFER_DURATION,
+        0,
+        &format,
+        None,
+    ));
+
+    let packet_event = HRESULT!(CreateEventW(None, false, false, PCWSTR::null()));
+
+    defer! {
+        CloseHandle(packet_event);
+    }
+
+    HRESULT!(audio_client.SetEventHandle(packet_event));
+
+    let capture_client: IAudioCaptureClient = HRESULT!(audio_client.GetService());
+
+    HRESULT!(audio_client.Start());
+
+    defer! {
+        let _ = audio_client.Stop();
+    }
+
+    let _ = started_tx.send(Ok(()));
+
+    tracing::info!(process_id, "process loopback capture started");
+
+    let mut pending_samples: Vec<f32> = Vec::new();
+
+    while !exit.load(Ordering::SeqCst) {
+        if WaitForSingleObject(packet_event, WAIT_PACKET_TIMEOUT_MS) != WAIT_OBJECT_0 {
+            continue;
+        }
+
+        while HRESULT!(capture_client.GetNextPacketSize()) > 0 {
+            let mut data = std::ptr::null_mut();
+            let mut frames = 0;
+            let mut flags = 0;
+
+            HRESULT!(capture_client.GetBuffer(&mut data, &mut frames, &mut flags, None, None));
+
+            let samples = frames as usize * CAPTURE_CHANNELS as usize;
+            if data.is_null() || flags & AUDCLNT_BUFFERFLAGS_SILENT.0 as u32 != 0 {
+                pending_samples.resize(pending_samples.len() + samples, 0.0);
+            } else {
+                pending_samples
+                    .extend_from_slice(std::slice::from_raw_parts(data as *const f32, samples));
+            }
+
+            HRESULT!(capture_client.ReleaseBuffer(frames));
+        }
+
+        if !send_encode_frames(
+            &mut pending_samples,
+            CAPTURE_CHANNELS,
+            CAPTURE_SAMPLE_RATE,
+            &tx,
+        ) {
+            break;
+        }
+    }
+
+    tracing::info!(process_id, "process loopback capture exit");
+
+    Ok(())
+}
+
+unsafe fn activate_process_loopback(process_id: u32) -> CoreResult<IAudioClient> {
+    let mut activation_params = AUDIOCLIENT_ACTIVATION_PARAMS {
+        ActivationType: AUDIOCLIENT_ACTIVATION_TYPE_PROCESS_LOOPBACK,
+        Anonymous: AUDIOCLIENT_ACTIVATION_PARAMS_0 {
+            ProcessLoopbackParams: AUDIOCLIENT_PROCESS_LOOPBACK_PARAMS {
+                TargetProcessId: process_id,
+                ProcessLoopbackMode: PROCESS_LOOPBACK_MODE_INCLUDE_TARGET_PROCESS_TREE,
+            },
+        },
+    };
+
+    let activation_params = PROPVARIANT {
+        Anonymous: PROPVARIANT_0 {
+            Anonymous: ManuallyDrop::new(PROPVARIANT_0_0 {
+                vt: VT_BLOB,
+                wReserved1: 0,
+                wReserved2: 0,
+                wReserved3: 0,
+                Anonymous: PROPVARIANT_0_0_0 {
+                    blob: BLOB {
+                        cbSize: std::mem::size_of::<AUDIOCLIENT_ACTIVATION_PARAMS>() as u32,
+                        pBlobData: &mut activation_params as *mut _ as *mut u8,
+                    },
+                },
+            }),
+        },
+    };
+
+    let (completed_tx, completed_rx) = std::sync::mpsc::channel();
+    let completion_handler: IActivateAudioInterfaceCompletionHandler = ActivateCompletionHandler {
+        completed_tx: Mutex::new(completed_tx),
+    }
+    .into();
+
+    let operation = HRESULT!(ActivateAudioInterfaceAsync(
+        VIRTUAL_AUDIO_DEVICE_PROCESS_LOOPBACK,
+        &IAudioClient::IID,
+        Some(&activation_params),
+        &completion_handler,
+    ));
+
+    completed_rx
+        .recv_timeout(ACTIVATE_TIMEOUT)
+        .map_err(|_| CoreError::Timeout)?;
+
+    let mut activate_result = windows::core::HRESULT(0);
+    let mut activated_interface = None;
+    HRESULT!(operation.GetActivateResult(&mut activate_result, &mut activated_interface));
+    HRESULT!(activate_result.ok());
+
+    let Some(activated_interface) = activated_interface else {
+        return Err(core_error!(
+            "activate process loopback returns no interface"
+        ));
+    };
+
+    Ok(HRESULT!(activated_interface.cast()))
+}
+
+unsafe fn process_name(process_id: u32) -> Option<String> {
+    let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id).ok()?;
+
+    defer! {
+        CloseHandle(process);
+    }
+
+    let mut buffer = [0u16; 260];
+    let mut length = buffer.len() as u32;
+
+    if !QueryFullProcessImageNameW(
+        process,
+        PROCESS_NAME_WIN32,
+        PWSTR(buffer.as_mut_ptr()),
+        &mut length,
+    )
+    .as_bool()
+    {
+        return None;
+    }
+
+    let path = String::from_utf16(&buffer[..length as usize]).ok()?;
+
+    Path::new(&path)
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+}
+
+// activation completes on another thread, it must be agile
+#[implement(IActivateAudioInterfaceCompletionHandler, IAgileObject)]
+struct ActivateCompletionHandler {
+    completed_tx: Mutex<std::sync::mpsc::Sender<()>>,
+}
+
+impl IActivateAudioInterfaceCompletionHandler_Impl for ActivateCompletionHandler {
+    fn ActivateCompleted(
+        &self,
+        _: &Option<IActivateAudioInterfaceAsyncOperation>,
+    ) -> windows::core::Result<()> {
+        let _ = self.completed_tx.lock().unwrap().send(());
+        Ok(())
+    }
+}
+
+impl IAgileObject_Impl for ActivateCompletionHandler {}
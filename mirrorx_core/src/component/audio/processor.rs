@@ -0,0 +1,156 @@
+//! Echo cancellation and noise suppression of the recorded microphone. What this
+//! process plays is the echo which microphone picks up, so played samples are fed to
+//! every processor as the render reference.
+
+use crate::{component::frame::AudioEncodeFrame, core_error, error::CoreResult};
+use cpal::SampleFormat;
+use once_cell::sync::Lazy;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+use webrtc_audio_processing::{
+    Config, EchoCancellation, EchoCancellationSuppressionLevel, InitializationConfig,
+    NoiseSuppression, NoiseSuppressionLevel, Processor, NUM_SAMPLES_PER_FRAME,
+};
+
+// the processor only accepts frames of 10ms in 48kHz
+const PROCESS_SAMPLE_RATE: u32 = 48000;
+const PROCESS_FRAME_SAMPLES: usize = NUM_SAMPLES_PER_FRAME as usize;
+
+static RENDER_REFERENCES: Lazy<Mutex<Vec<RenderReference>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+static PROCESSOR_ID: AtomicU64 = AtomicU64::new(0);
+
+struct RenderReference {
+    processor_id: u64,
+    processor: Processor,
+    // mono samples which are not enough for a process frame yet
+    pending_samples: Vec<f32>,
+}
+
+pub struct AudioProcessor {
+    id: u64,
+    processor: Processor,
+    channels: u16,
+}
+
+impl AudioProcessor {
+    pub fn new(channels: u16) -> CoreResult<Self> {
+        let mut processor = Processor::new(&InitializationConfig {
+            num_capture_channels: channels as i32,
+            num_render_channels: 1,
+            ..InitializationConfig::default()
+        })?;
+
+        processor.set_config(Config {
+            echo_cancellation: Some(EchoCancellation {
+                suppression_level: EchoCancellationSuppressionLevel::High,
+                enable_extended_filter: true,
+                // delay between playing and recording differs from devices
+                enable_delay_agnostic: true,
+                stream_delay_ms: None,
+            }),
+            noise_suppression: Some(NoiseSuppression {
+                suppression_level: NoiseSuppressionLevel::High,
+            }),
+            enable_high_pass_filter: true,
+            ..Config::default()
+        });
+
+        let id = PROCESSOR_ID.fetch_add(1, Ordering::SeqCst);
+
+        RENDER_REFERENCES.lock().unwrap().push(RenderReference {
+            processor_id: id,
+            processor: processor.clone(),
+            pending_samples: Vec::new(),
+        });
+
+        Ok(AudioProcessor {
+            id,
+            processor,
+            channels,
+        })
+    }
+
+    /// Process the recorded frame in place, it must be interleaved f32 in 48kHz. The
+    /// tail which is shorter than a process frame is left as it is.
+    pub fn process_capture(&mut self, audio_frame: &mut AudioEncodeFrame) -> CoreResult<()> {
+        if audio_frame.sample_format != SampleFormat::F32
+            || audio_frame.sample_rate != PROCESS_SAMPLE_RATE
+            || audio_frame.channels != self.channels
+        {
+            return Err(core_error!(
+                "unsupported audio frame (format: {:?}, rate: {}, channels: {})",
+                audio_frame.sample_format,
+                audio_frame.sample_rate,
+                audio_frame.channels
+            ));
+        }
+
+        let mut samples = bytes_to_samples(&audio_frame.buffer);
+
+        for frame in samples.chunks_exact_mut(PROCESS_FRAME_SAMPLES * self.channels as usize) {
+            self.processor.process_capture_frame(frame)?;
+        }
+
+        audio_frame.buffer = samples.into_iter().flat_map(f32::to_ne_bytes).collect();
+
+        Ok(())
+    }
+}
+
+impl Drop for AudioProcessor {
+    fn drop(&mut self) {
+        RENDER_REFERENCES
+            .lock()
+            .unwrap()
+            .retain(|reference| reference.processor_id != self.id);
+    }
+}
+
+/// Feed the samples which this process plays to processors as the reference of echo,
+/// samples which aren't f32 in 48kHz are ignored.
+pub fn push_render_samples(
+    buffer: &[u8],
+    channels: u16,
+    sample_format: SampleFormat,
+    sample_rate: u32,
+) {
+    if sample_format != SampleFormat::F32 || sample_rate != PROCESS_SAMPLE_RATE || channels == 0 {
+        return;
+    }
+
+    let mut references = RENDER_REFERENCES.lock().unwrap();
+    if references.is_empty() {
+        return;
+    }
+
+    // one mixed channel is enough for the reference
+    let mono_samples: Vec<f32> = bytes_to_samples(buffer)
+        .chunks_exact(channels as usize)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+
+    for reference in references.iter_mut() {
+        reference.pending_samples.extend_from_slice(&mono_samples);
+
+        while reference.pending_samples.len() >= PROCESS_FRAME_SAMPLES {
+            let mut frame: Vec<f32> = reference
+                .pending_samples
+                .drain(..PROCESS_FRAME_SAMPLES)
+                .collect();
+
+            if let Err(err) = reference.processor.process_render_frame(&mut frame) {
+                tracing::warn!(?err, "process audio render frame failed");
+            }
+        }
+    }
+}
+
+fn bytes_to_samples(buffer: &[u8]) -> Vec<f32> {
+    buffer
+        .chunks_exact(4)
+        .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
@@ -1,24 +1,141 @@
-use crate::{component::frame::AudioEncodeFrame, core_error, error::CoreResult};
+use crate::{
+    api::endpoint::message::{AudioApplication, AudioCaptureTarget, AudioSource},
+    component::frame::AudioEncodeFrame,
+    core_error,
+    error::CoreResult,
+};
 use cpal::{
-    traits::{DeviceTrait, HostTrait},
-    Stream, StreamConfig,
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    Device, Stream, StreamConfig, SupportedStreamConfig,
 };
-use tokio::sync::mpsc::Receiver;
+use tokio::sync::mpsc::{Receiver, Sender};
+
+#[cfg(target_os = "macos")]
+use super::screen_capture::{system_audio_capture_available, SystemAudioCapture};
+
+#[cfg(target_os = "windows")]
+use super::process_loopback::{list_audio_applications, ProcessLoopbackCapture};
+
+// interleaved samples of each encode frame, the same as the buffer of device stream
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+const RECORD_FRAME_SAMPLES: usize = 960;
+
+pub enum RecordStream {
+    Device(Stream),
+    #[cfg(target_os = "macos")]
+    SystemAudio(SystemAudioCapture),
+    #[cfg(target_os = "windows")]
+    ProcessLoopback(ProcessLoopbackCapture),
+}
+
+impl RecordStream {
+    pub fn play(&self) -> CoreResult<()> {
+        match self {
+            RecordStream::Device(stream) => Ok(stream.play()?),
+            #[cfg(target_os = "macos")]
+            RecordStream::SystemAudio(capture) => capture.start(),
+            #[cfg(target_os = "windows")]
+            RecordStream::ProcessLoopback(capture) => capture.start(),
+        }
+    }
+}
+
+/// The audio which this device can record for remote.
+#[cfg(target_os = "macos")]
+pub fn record_audio_source() -> AudioSource {
+    if system_audio_capture_available() {
+        AudioSource::SystemAudio
+    } else if cpal::default_host().default_input_device().is_some() {
+        AudioSource::Microphone
+    } else {
+        AudioSource::Unavailable
+    }
+}
+
+/// The audio which this device can record for remote.
+#[cfg(not(target_os = "macos"))]
+pub fn record_audio_source() -> AudioSource {
+    if cpal::default_host().default_output_device().is_some() {
+        AudioSource::SystemAudio
+    } else {
+        AudioSource::Unavailable
+    }
+}
+
+/// Applications whose audio can be captured alone by `AudioCaptureTarget::Process`.
+#[cfg(target_os = "windows")]
+pub fn audio_applications() -> Vec<AudioApplication> {
+    match list_audio_applications() {
+        Ok(applications) => applications,
+        Err(err) => {
+            tracing::warn!(?err, "list audio applications failed");
+            Vec::new()
+        }
+    }
+}
 
-pub fn new_record_stream_and_rx() -> CoreResult<(Stream, Receiver<AudioEncodeFrame>)> {
-    let host = cpal::default_host();
+/// Applications whose audio can be captured alone by `AudioCaptureTarget::Process`.
+#[cfg(not(target_os = "windows"))]
+pub fn audio_applications() -> Vec<AudioApplication> {
+    Vec::new()
+}
 
-    let device = match host.default_output_device() {
-        Some(device) => device,
-        None => {
-            return Err(core_error!("default audio output device not exist"));
+/// Create the record stream of `source`, `target` selects the application to record
+/// when source is system audio and the platform supports it.
+pub fn new_record_stream_and_rx(
+    source: AudioSource,
+    target: AudioCaptureTarget,
+) -> CoreResult<(RecordStream, Receiver<AudioEncodeFrame>)> {
+    let (tx, rx) = tokio::sync::mpsc::channel(180);
+
+    let stream = match (source, target) {
+        #[cfg(target_os = "windows")]
+        (AudioSource::SystemAudio, AudioCaptureTarget::Process(process_id)) => {
+            tracing::info!(process_id, "select audio of application");
+            RecordStream::ProcessLoopback(ProcessLoopbackCapture::new(process_id, tx))
+        }
+        #[cfg(not(target_os = "windows"))]
+        (AudioSource::SystemAudio, AudioCaptureTarget::Process(process_id)) => {
+            tracing::warn!(process_id, "per-application capture is not supported");
+            return new_record_stream_and_rx(source, AudioCaptureTarget::System);
         }
+        #[cfg(target_os = "macos")]
+        (AudioSource::SystemAudio, AudioCaptureTarget::System) => {
+            RecordStream::SystemAudio(SystemAudioCapture::new(tx)?)
+        }
+        #[cfg(not(target_os = "macos"))]
+        (AudioSource::SystemAudio, AudioCaptureTarget::System) => {
+            let Some(device) = cpal::default_host().default_output_device() else {
+                return Err(core_error!("default audio output device not exist"));
+            };
+
+            tracing::info!(name = ?device.name(), "select default audio output device");
+
+            let config = device.default_output_config()?;
+            RecordStream::Device(build_device_stream(device, config, tx)?)
+        }
+        (AudioSource::Microphone, _) => {
+            let Some(device) = cpal::default_host().default_input_device() else {
+                return Err(core_error!("default audio input device not exist"));
+            };
+
+            tracing::info!(name = ?device.name(), "select default audio input device");
+
+            let config = device.default_input_config()?;
+            RecordStream::Device(build_device_stream(device, config, tx)?)
+        }
+        (AudioSource::Unavailable, _) => return Err(core_error!("no audio source to record")),
     };
 
-    tracing::info!(name = ?device.name(), "select default audio output device");
+    Ok((stream, rx))
+}
 
-    let config = device.default_output_config()?;
-    tracing::info!(?config, "audio default output config");
+fn build_device_stream(
+    device: Device,
+    config: SupportedStreamConfig,
+    tx: Sender<AudioEncodeFrame>,
+) -> CoreResult<Stream> {
+    tracing::info!(?config, "audio default config");
 
     let channels = config.channels();
     let sample_format = config.sample_format();
@@ -30,8 +147,7 @@ pub fn new_record_stream_and_rx() -> CoreResult<(Stream, Receiver<AudioEncodeFra
         buffer_size: cpal::BufferSize::Fixed(960),
     };
 
-    let (tx, rx) = tokio::sync::mpsc::channel(180);
-    let error_handler = |err| tracing::error!(?err, "error occurred on the output input stream");
+    let error_handler = |err| tracing::error!(?err, "error occurred on the record stream");
     let stream = device.build_input_stream_raw(
         &config,
         sample_format,
@@ -51,5 +167,36 @@ pub fn new_record_stream_and_rx() -> CoreResult<(Stream, Receiver<AudioEncodeFra
         None,
     )?;
 
-    Ok((stream, rx))
+    Ok(stream)
+}
+
+/// Send interleaved f32 `pending_samples` to encoder by frames, the rest is kept for
+/// next time. Returns false when the receiver is closed.
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+pub(super) fn send_encode_frames(
+    pending_samples: &mut Vec<f32>,
+    channels: u16,
+    sample_rate: u32,
+    tx: &Sender<AudioEncodeFrame>,
+) -> bool {
+    while pending_samples.len() >= RECORD_FRAME_SAMPLES {
+        let buffer = pending_samples
+            .drain(..RECORD_FRAME_SAMPLES)
+            .flat_map(f32::to_ne_bytes)
+            .collect();
+
+        let audio_encode_frame = AudioEncodeFrame {
+            channels,
+            sample_format: cpal::SampleFormat::F32,
+            sample_rate,
+            buffer,
+        };
+
+        if tx.blocking_send(audio_encode_frame).is_err() {
+            tracing::warn!("audio encode frame tx try send failed!");
+            return false;
+        }
+    }
+
+    true
 }
@@ -0,0 +1,373 @@
+//! CoreAudio can't record what macOS plays without a loopback kernel extension, the
+//! audio stream of ScreenCaptureKit is used instead, which is available since macOS 13.
+
+use super::recorder::send_encode_frames;
+use crate::{
+    component::frame::AudioEncodeFrame,
+    core_error,
+    error::{CoreError, CoreResult},
+};
+use block::ConcreteBlock;
+use dispatch::ffi::{dispatch_queue_create, dispatch_release, DISPATCH_QUEUE_SERIAL};
+use libloading::Library;
+use mirrorx_native::os::macos::core_media::*;
+use objc::{
+    class,
+    declare::ClassDecl,
+    msg_send,
+    runtime::{Class, Object, Protocol, Sel, BOOL, YES},
+    sel, sel_impl,
+};
+use objc_foundation::{INSString, NSString};
+use once_cell::sync::Lazy;
+use scopeguard::defer;
+use std::{ffi::CString, os::raw::c_void, time::Duration};
+use tokio::sync::mpsc::Sender;
+
+const SCREEN_CAPTURE_KIT_PATH: &str =
+    "/System/Library/Frameworks/ScreenCaptureKit.framework/ScreenCaptureKit";
+
+const SC_STREAM_OUTPUT_TYPE_AUDIO: isize = 1;
+const CAPTURE_SAMPLE_RATE: isize = 48000;
+const CAPTURE_CHANNEL_COUNT: isize = 2;
+const COMPLETION_TIMEOUT: Duration = Duration::from_secs(5);
+
+// the framework doesn't exist before macOS 12.3, so it's loaded at runtime instead of
+// linked, which registers its classes to objc runtime
+static SCREEN_CAPTURE_KIT: Lazy<Option<Library>> =
+    Lazy::new(|| match unsafe { Library::new(SCREEN_CAPTURE_KIT_PATH) } {
+        Ok(library) => Some(library),
+        Err(err) => {
+            tracing::info!(?err, "ScreenCaptureKit is not available");
+            None
+        }
+    });
+
+static STREAM_OUTPUT_CLASS: Lazy<&'static Class> = Lazy::new(|| {
+    let mut decl = ClassDecl::new("MirrorXAudioStreamOutput", class!(NSObject))
+        .expect("declare audio stream output class failed");
+
+    decl.add_ivar::<*mut c_void>("_state");
+
+    if let Some(protocol) = Protocol::get("SCStreamOutput") {
+        decl.add_protocol(protocol);
+    }
+
+    unsafe {
+        decl.add_method(
+            sel!(stream:didOutputSampleBuffer:ofType:),
+            stream_did_output_sample_buffer
+                as extern "C" fn(&Object, Sel, *mut Object, CMSampleBufferRef, isize),
+        );
+    }
+
+    decl.register()
+});
+
+/// Whether ScreenCaptureKit can capture system audio, it's false before macOS 13.
+pub fn system_audio_capture_available() -> bool {
+    if SCREEN_CAPTURE_KIT.is_none() {
+        return false;
+    }
+
+    let Some(config_class) = Class::get("SCStreamConfiguration") else {
+        return false;
+    };
+
+    unsafe {
+        let available: BOOL =
+            msg_send![config_class, instancesRespondToSelector: sel!(setCapturesAudio:)];
+        available == YES
+    }
+}
+
+pub struct SystemAudioCapture {
+    stream: *mut Object,
+    output: *mut Object,
+}
+
+unsafe impl Send for SystemAudioCapture {}
+
+impl SystemAudioCapture {
+    pub fn new(tx: Sender<AudioEncodeFrame>) -> CoreResult<Self> {
+        if !system_audio_capture_available() {
+            return Err(core_error!("system audio capture is not available"));
+        }
+
+        unsafe {
+            let display = shareable_display()?;
+
+            defer! {
+                let _: () = msg_send![display, release];
+            }
+
+            let windows: *mut Object = msg_send![class!(NSArray), array];
+            let filter: *mut Object = msg_send![class!(SCContentFilter), alloc];
+            let filter: *mut Object =
+                msg_send![filter, initWithDisplay: display excludingWindows: windows];
+
+            if filter.is_null() {
+                return Err(core_error!("create content filter failed"));
+            }
+
+            defer! {
+                let _: () = msg_send![filter, release];
+            }
+
+            let config: *mut Object = msg_send![class!(SCStreamConfiguration), new];
+
+            defer! {
+                let _: () = msg_send![config, release];
+            }
+
+            let _: () = msg_send![config, setCapturesAudio: YES];
+            let _: () = msg_send![config, setExcludesCurrentProcessAudio: YES];
+            let _: () = msg_send![config, setSampleRate: CAPTURE_SAMPLE_RATE];
+            let _: () = msg_send![config, setChannelCount: CAPTURE_CHANNEL_COUNT];
+
+            // desktop is captured by display stream, keep the unused video of this stream tiny
+            let _: () = msg_send![config, setWidth: 2usize];
+            let _: () = msg_send![config, setHeight: 2usize];
+
+            let stream: *mut Object = msg_send![class!(SCStream), alloc];
+            let stream: *mut Object = msg_send![stream, initWithFilter: filter configuration: config delegate: std::ptr::null_mut::<Object>()];
+
+            if stream.is_null() {
+                return Err(core_error!("create capture stream failed"));
+            }
+
+            let output: *mut Object = msg_send![*STREAM_OUTPUT_CLASS, new];
+            let state = Box::into_raw(Box::new(StreamOutputState {
+                tx,
+                pending_samples: Vec::new(),
+            }));
+            (*output).set_ivar::<*mut c_void>("_state", state as *mut c_void);
+
+            let capture = SystemAudioCapture { stream, output };
+
+            let queue_label = CString::new("queue.audio_capture.mirrorx")?;
+            let dispatch_queue = dispatch_queue_create(queue_label.as_ptr(), DISPATCH_QUEUE_SERIAL);
+
+            defer! {
+                dispatch_release(dispatch_queue);
+            }
+
+            let mut error: *mut Object = std::ptr::null_mut();
+            let added: BOOL = msg_send![stream, addStreamOutput: output type: SC_STREAM_OUTPUT_TYPE_AUDIO sampleHandlerQueue: dispatch_queue error: &mut error];
+
+            if added != YES {
+                return Err(core_error!(
+                    "add audio stream output failed ({})",
+                    error_description(error)
+                ));
+            }
+
+            Ok(capture)
+        }
+    }
+
+    pub fn start(&self) -> CoreResult<()> {
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        unsafe {
+            let completion_handler = ConcreteBlock::new(move |error: *mut Object| {
+                let result = if error.is_null() {
+                    Ok(())
+                } else {
+                    Err(error_description(error))
+                };
+
+                let _ = tx.send(result);
+            })
+            .copy();
+
+            let _: () =
+                msg_send![self.stream, startCaptureWithCompletionHandler: &*completion_handler];
+        }
+
+        match rx.recv_timeout(COMPLETION_TIMEOUT) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(reason)) => Err(core_error!("start audio capture failed ({})", reason)),
+            Err(_) => Err(CoreError::Timeout),
+        }
+    }
+}
+
+impl Drop for SystemAudioCapture {
+    fn drop(&mut self) {
+        unsafe {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let completion_handler = ConcreteBlock::new(move |_: *mut Object| {
+                let _ = tx.send(());
+            })
+            .copy();
+
+            let _: () =
+                msg_send![self.stream, stopCaptureWithCompletionHandler: &*completion_handler];
+
+            // sample buffers are delivered until the capture stops, the output state can't
+            // be freed before that
+            let _ = rx.recv_timeout(COMPLETION_TIMEOUT);
+
+            let _: () = msg_send![self.stream, release];
+
+            let state = *(*self.output).get_ivar::<*mut c_void>("_state") as *mut StreamOutputState;
+            (*self.output).set_ivar::<*mut c_void>("_state", std::ptr::null_mut());
+            if !state.is_null() {
+                drop(Box::from_raw(state));
+            }
+
+            let _: () = msg_send![self.output, release];
+        }
+    }
+}
+
+struct StreamOutputState {
+    tx: Sender<AudioEncodeFrame>,
+    // interleaved samples which are not enough for an encode frame yet
+    pending_samples: Vec<f32>,
+}
+
+impl StreamOutputState {
+    unsafe fn push_sample_buffer(&mut self, sample_buffer: CMSampleBufferRef) -> CoreResult<()> {
+        if !CMSampleBufferIsValid(sample_buffer) || !CMSampleBufferDataIsReady(sample_buffer) {
+            return Ok(());
+        }
+
+        let description = CMAudioFormatDescriptionGetStreamBasicDescription(
+            CMSampleBufferGetFormatDescription(sample_buffer),
+        );
+
+        if description.is_null() {
+            return Err(core_error!("audio sample buffer has no stream description"));
+        }
+
+        let description = &*description;
+
+        if description.format_flags & kAudioFormatFlagIsFloat == 0
+            || description.bits_per_channel != 32
+        {
+            return Err(core_error!(
+                "unsupported audio format (flags: {}, bits: {})",
+                description.format_flags,
+                description.bits_per_channel
+            ));
+        }
+
+        let block_buffer = CMSampleBufferGetDataBuffer(sample_buffer);
+        if block_buffer.is_null() {
+            return Err(core_error!("audio sample buffer has no data buffer"));
+        }
+
+        let mut samples = vec![0f32; CMBlockBufferGetDataLength(block_buffer) / 4];
+        let ret = CMBlockBufferCopyDataBytes(
+            block_buffer,
+            0,
+            samples.len() * 4,
+            samples.as_mut_ptr() as *mut c_void,
+        );
+
+        if ret != 0 {
+            return Err(core_error!(
+                "CMBlockBufferCopyDataBytes returns error ({})",
+                ret
+            ));
+        }
+
+        let channels = description.channels_per_frame.max(1) as usize;
+
+        if description.format_flags & kAudioFormatFlagIsNonInterleaved != 0 {
+            // the planes of each channel are laid one after another
+            let frames = samples.len() / channels;
+            self.pending_samples.reserve(frames * channels);
+
+            for frame in 0..frames {
+                for channel in 0..channels {
+                    self.pending_samples.push(samples[channel * frames + frame]);
+                }
+            }
+        } else {
+            self.pending_samples.extend_from_slice(&samples);
+        }
+
+        send_encode_frames(
+            &mut self.pending_samples,
+            channels as u16,
+            description.sample_rate as u32,
+            &self.tx,
+        );
+
+        Ok(())
+    }
+}
+
+extern "C" fn stream_did_output_sample_buffer(
+    this: &Object,
+    _: Sel,
+    _: *mut Object,
+    sample_buffer: CMSampleBufferRef,
+    output_type: isize,
+) {
+    if output_type != SC_STREAM_OUTPUT_TYPE_AUDIO {
+        return;
+    }
+
+    unsafe {
+        let state = *this.get_ivar::<*mut c_void>("_state") as *mut StreamOutputState;
+        if state.is_null() {
+            return;
+        }
+
+        if let Err(err) = (*state).push_sample_buffer(sample_buffer) {
+            tracing::error!(?err, "handle system audio sample buffer failed");
+        }
+    }
+}
+
+/// The display to capture system audio with, it's retained and should be released.
+unsafe fn shareable_display() -> CoreResult<*mut Object> {
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    // the display is passed as address since the handler is called on another thread
+    let completion_handler = ConcreteBlock::new(move |content: *mut Object, error: *mut Object| {
+        let result = if content.is_null() {
+            Err(error_description(error))
+        } else {
+            let displays: *mut Object = msg_send![content, displays];
+            let display: *mut Object = msg_send![displays, firstObject];
+            if display.is_null() {
+                Err(String::from("no shareable display"))
+            } else {
+                let display: *mut Object = msg_send![display, retain];
+                Ok(display as usize)
+            }
+        };
+
+        let _ = tx.send(result);
+    })
+    .copy();
+
+    let _: () = msg_send![
+        class!(SCShareableContent),
+        getShareableContentWithCompletionHandler: &*completion_handler
+    ];
+
+    match rx.recv_timeout(COMPLETION_TIMEOUT) {
+        Ok(Ok(display)) => Ok(display as *mut Object),
+        Ok(Err(reason)) => Err(core_error!("get shareable content failed ({})", reason)),
+        Err(_) => Err(CoreError::Timeout),
+    }
+}
+
+unsafe fn error_description(error: *mut Object) -> String {
+    if error.is_null() {
+        return String::from("unknown error");
+    }
+
+    let description: *mut NSString = msg_send![error, localizedDescription];
+    if description.is_null() {
+        return String::from("unknown error");
+    }
+
+    (*description).as_str().to_string()
+}
@@ -0,0 +1,101 @@
+use super::MINIMIZED_ARG;
+use crate::{core_error, error::CoreResult};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const UNIT_NAME: &str = "mirrorx.service";
+
+// the user unit is started with the graphical session of user, it's wanted by the
+// session target rather than the default target which starts before the display is up
+pub fn registered_exe() -> CoreResult<Option<PathBuf>> {
+    let unit = match std::fs::read_to_string(unit_path()?) {
+        Ok(unit) => unit,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    // the unit file is kept when it's disabled by `systemctl --user disable`
+    let enabled = Command::new("systemctl")
+        .args(["--user", "is-enabled", "--quiet", UNIT_NAME])
+        .status()?
+        .success();
+
+    if !enabled {
+        return Ok(None);
+    }
+
+    let exe = unit
+        .lines()
+        .find_map(|line| line.strip_prefix("ExecStart="))
+        .and_then(|command| command.trim().strip_prefix('"'))
+        .and_then(|command| command.split_once('"'))
+        .map(|(exe, _)| PathBuf::from(exe));
+
+    Ok(exe)
+}
+
+pub fn register(exe: &Path) -> CoreResult<()> {
+    let unit = format!(
+        "[Unit]\n\
+         Description=MirrorX\n\
+         PartOf=graphical-session.target\n\
+         After=graphical-session.target\n\
+         \n\
+         [Service]\n\
+         ExecStart=\"{}\" {MINIMIZED_ARG}\n\
+         Restart=no\n\
+         \n\
+         [Install]\n\
+         WantedBy=graphical-session.target\n",
+        exe.display()
+    );
+
+    let unit_path = unit_path()?;
+    if let Some(dir) = unit_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    std::fs::write(unit_path, unit)?;
+
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", UNIT_NAME])
+}
+
+pub fn unregister() -> CoreResult<()> {
+    let unit_path = unit_path()?;
+    if !unit_path.exists() {
+        return Ok(());
+    }
+
+    systemctl(&["disable", UNIT_NAME])?;
+    std::fs::remove_file(unit_path)?;
+    systemctl(&["daemon-reload"])
+}
+
+fn unit_path() -> CoreResult<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME").ok_or(core_error!("HOME is not set"))?)
+            .join(".config"),
+    };
+
+    Ok(config_dir.join("systemd/user").join(UNIT_NAME))
+}
+
+fn systemctl(args: &[&str]) -> CoreResult<()> {
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()?;
+    if !status.success() {
+        return Err(core_error!(
+            "systemctl --user {} exits with {}",
+            args.join(" "),
+            status
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use super::MINIMIZED_ARG;
+use crate::{core_error, error::CoreResult};
+use std::path::{Path, PathBuf};
+
+const LABEL: &str = "cloud.mirrorx.autostart";
+
+// launchd loads the agents of user at login, the agent only starts the app once
+pub fn registered_exe() -> CoreResult<Option<PathBuf>> {
+    let plist = match std::fs::read_to_string(plist_path()?) {
+        Ok(plist) => plist,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    // the first string of ProgramArguments is the executable
+    let exe = plist
+        .split_once("<key>ProgramArguments</key>")
+        .and_then(|(_, arguments)| arguments.split_once("<string>"))
+        .and_then(|(_, exe)| exe.split_once("</string>"))
+        .map(|(exe, _)| PathBuf::from(unescape(exe)));
+
+    Ok(exe)
+}
+
+pub fn register(exe: &Path) -> CoreResult<()> {
+    let plist = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>{MINIMIZED_ARG}</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>ProcessType</key>
+    <string>Interactive</string>
+</dict>
+</plist>
+"#,
+        escape(&exe.to_string_lossy())
+    );
+
+    let plist_path = plist_path()?;
+    if let Some(dir) = plist_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    std::fs::write(plist_path, plist)?;
+
+    Ok(())
+}
+
+pub fn unregister() -> CoreResult<()> {
+    match std::fs::remove_file(plist_path()?) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+fn plist_path() -> CoreResult<PathBuf> {
+    let home = std::env::var_os("HOME").ok_or(core_error!("HOME is not set"))?;
+
+    Ok(PathBuf::from(home)
+        .join("Library/LaunchAgents")
+        .join(format!("{LABEL}.plist")))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,68 @@
+//! Start the app minimized to tray at login of user, so the device can be visited after
+//! it reboots without anyone opening the app. The registration lives where the platform
+//! keeps login items, it's read back from there instead of a setting which may go stale,
+//! like when user removes the login item by system settings.
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "windows")]
+use self::windows as sys;
+
+#[cfg(target_os = "macos")]
+mod macos;
+
+#[cfg(target_os = "macos")]
+use self::macos as sys;
+
+#[cfg(target_os = "linux")]
+mod linux;
+
+#[cfg(target_os = "linux")]
+use self::linux as sys;
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+use self::unsupported as sys;
+
+use crate::error::CoreResult;
+
+/// The argument which the app is started with at login, it stays in tray without
+/// showing the main window.
+pub const MINIMIZED_ARG: &str = "--minimized";
+
+/// Whether the app is registered to start at login, registrations of the app at
+/// another path don't count since they start a stale copy.
+pub fn autostart_enabled() -> CoreResult<bool> {
+    let registered_exe = sys::registered_exe()?;
+    Ok(registered_exe == Some(std::env::current_exe()?))
+}
+
+pub fn set_autostart_enabled(enabled: bool) -> CoreResult<()> {
+    if enabled {
+        sys::register(&std::env::current_exe()?)?;
+    } else {
+        sys::unregister()?;
+    }
+
+    tracing::info!(?enabled, "set autostart");
+
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+mod unsupported {
+    use crate::{core_error, error::CoreResult};
+    use std::path::{Path, PathBuf};
+
+    pub fn registered_exe() -> CoreResult<Option<PathBuf>> {
+        Ok(None)
+    }
+
+    pub fn register(_: &Path) -> CoreResult<()> {
+        Err(core_error!("autostart isn't supported on this platform"))
+    }
+
+    pub fn unregister() -> CoreResult<()> {
+        Ok(())
+    }
+}
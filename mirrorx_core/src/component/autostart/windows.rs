@@ -0,0 +1,109 @@
+use super::MINIMIZED_ARG;
+use crate::{error::CoreResult, HRESULT};
+use scopeguard::defer;
+use std::path::{Path, PathBuf};
+use windows::{
+    w,
+    Win32::{
+        Foundation::ERROR_FILE_NOT_FOUND,
+        System::Registry::{
+            RegCloseKey, RegDeleteValueW, RegOpenKeyExW, RegQueryValueExW, RegSetValueExW, HKEY,
+            HKEY_CURRENT_USER, KEY_QUERY_VALUE, KEY_SET_VALUE, REG_SAM_FLAGS, REG_SZ,
+        },
+    },
+};
+
+pub fn registered_exe() -> CoreResult<Option<PathBuf>> {
+    unsafe {
+        let key = open_run_key(KEY_QUERY_VALUE)?;
+
+        defer! {
+            let _ = RegCloseKey(key);
+        }
+
+        let mut size = 0u32;
+        let ret = RegQueryValueExW(key, w!("MirrorX"), None, None, None, Some(&mut size));
+
+        if ret == ERROR_FILE_NOT_FOUND {
+            return Ok(None);
+        }
+
+        HRESULT!(ret.ok());
+
+        let mut buffer = vec![0u16; (size as usize + 1) / 2];
+        HRESULT!(RegQueryValueExW(
+            key,
+            w!("MirrorX"),
+            None,
+            None,
+            Some(buffer.as_mut_ptr() as *mut u8),
+            Some(&mut size),
+        )
+        .ok());
+
+        let command = String::from_utf16(&buffer)?;
+        let command = command.trim_end_matches('\0');
+
+        // the command is like `"C:\Program Files\MirrorX\MirrorX.exe" --minimized`
+        let exe = match command.strip_prefix('"') {
+            Some(command) => command.split('"').next().unwrap_or_default(),
+            None => command.split(' ').next().unwrap_or_default(),
+        };
+
+        Ok(Some(PathBuf::from(exe)))
+    }
+}
+
+pub fn register(exe: &Path) -> CoreResult<()> {
+    unsafe {
+        let key = open_run_key(KEY_SET_VALUE)?;
+
+        defer! {
+            let _ = RegCloseKey(key);
+        }
+
+        let command = format!("\"{}\" {}", exe.display(), MINIMIZED_ARG);
+        let data: Vec<u8> = command
+            .encode_utf16()
+            .chain(std::iter::once(0))
+            .flat_map(|c| c.to_le_bytes())
+            .collect();
+
+        HRESULT!(RegSetValueExW(key, w!("MirrorX"), 0, REG_SZ, Some(&data)).ok());
+
+        Ok(())
+    }
+}
+
+pub fn unregister() -> CoreResult<()> {
+    unsafe {
+        let key = open_run_key(KEY_SET_VALUE)?;
+
+        defer! {
+            let _ = RegCloseKey(key);
+        }
+
+        let ret = RegDeleteValueW(key, w!("MirrorX"));
+
+        if ret != ERROR_FILE_NOT_FOUND {
+            HRESULT!(ret.ok());
+        }
+
+        Ok(())
+    }
+}
+
+unsafe fn open_run_key(access: REG_SAM_FLAGS) -> CoreResult<HKEY> {
+    let mut key = HKEY::default();
+
+    HRESULT!(RegOpenKeyExW(
+        HKEY_CURRENT_USER,
+        w!(r"Software\Microsoft\Windows\CurrentVersion\Run"),
+        0,
+        access,
+        &mut key,
+    )
+    .ok());
+
+    Ok(key)
+}
@@ -0,0 +1,91 @@
+//! The availability of local user is told to signaling servers and LAN devices, it
+//! decides how incoming visit requests are treated:
+//!
+//! - Available: visit requests are served and notified as usual
+//! - Busy: visit requests are queued silently until user is available again
+//! - Do Not Disturb: visit requests are rejected at once
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{str::FromStr, time::Duration};
+use tokio::sync::watch;
+
+// busy user may not be back soon, don't keep visitor waiting long
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(50);
+
+static AVAILABILITY: Lazy<watch::Sender<Availability>> =
+    Lazy::new(|| watch::channel(Availability::Available).0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Availability {
+    Available,
+    Busy,
+    DoNotDisturb,
+}
+
+impl Default for Availability {
+    fn default() -> Self {
+        Availability::Available
+    }
+}
+
+impl<'a> From<Availability> for &'a str {
+    fn from(val: Availability) -> Self {
+        match val {
+            Availability::Available => "available",
+            Availability::Busy => "busy",
+            Availability::DoNotDisturb => "do_not_disturb",
+        }
+    }
+}
+
+impl FromStr for Availability {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "available" => Ok(Availability::Available),
+            "busy" => Ok(Availability::Busy),
+            "do_not_disturb" => Ok(Availability::DoNotDisturb),
+            _ => Err(String::from("Unknown availability")),
+        }
+    }
+}
+
+pub fn set_availability(availability: Availability) {
+    AVAILABILITY.send_replace(availability);
+}
+
+pub fn availability() -> Availability {
+    *AVAILABILITY.borrow()
+}
+
+/// Receive the availability whenever user changes it.
+pub(crate) fn subscribe_availability() -> watch::Receiver<Availability> {
+    AVAILABILITY.subscribe()
+}
+
+/// Decide whether an incoming visit request is served. It waits while user is busy and
+/// returns false when user doesn't want to be disturbed or isn't available in time.
+pub(crate) async fn admit_visit() -> bool {
+    let mut availability_rx = subscribe_availability();
+
+    let wait = async {
+        loop {
+            match *availability_rx.borrow_and_update() {
+                Availability::Available => return true,
+                Availability::DoNotDisturb => return false,
+                Availability::Busy => {}
+            }
+
+            if availability_rx.changed().await.is_err() {
+                return false;
+            }
+        }
+    };
+
+    tokio::time::timeout(QUEUE_TIMEOUT, wait)
+        .await
+        .unwrap_or(false)
+}
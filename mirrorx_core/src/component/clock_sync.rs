@@ -0,0 +1,93 @@
+//! Frames carry the wall clock time of the passive endpoint when they're encoded, the
+//! clocks of two machines are seconds apart though, so subtracting the timestamp from
+//! local time conflates the skew with network delay. Active endpoint estimates the
+//! offset the way NTP does: it stamps a request with its time `t1`, passive endpoint
+//! stamps the arrival `t2` and the departure `t3` of the reply, which arrives at `t4`.
+//!
+//! ```text
+//! offset = ((t2 - t1) + (t3 - t4)) / 2
+//! round trip = (t4 - t1) - (t3 - t2)
+//! ```
+//!
+//! The estimate assumes the delays of both directions are equal, queueing breaks it, so
+//! the offset of the sample with the shortest round trip among the recent ones is used.
+
+use crate::api::endpoint::{id::EndPointID, message::EndPointClockSyncReply};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{collections::VecDeque, time::Duration};
+
+/// How often active endpoint samples the clock of passive endpoint.
+pub const CLOCK_SYNC_INTERVAL: Duration = Duration::from_secs(5);
+
+// samples older than these cover the clock drift rather than the queueing
+const MAX_SAMPLES: usize = 8;
+
+static ESTIMATES: Lazy<DashMap<EndPointID, VecDeque<ClockSample>>> = Lazy::new(DashMap::new);
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ClockSample {
+    /// Clock of passive endpoint minus the local one.
+    pub offset_micros: i64,
+    pub round_trip_micros: i64,
+}
+
+/// Wall clock time which the timestamps of endpoint messages are based on.
+pub fn now_micros() -> i64 {
+    chrono::Utc::now().timestamp_micros()
+}
+
+/// Record the sample of the reply which arrived at `arrived_micros`.
+pub(crate) fn record_reply(
+    endpoint_id: EndPointID,
+    reply: &EndPointClockSyncReply,
+    arrived_micros: i64,
+) {
+    let sample = ClockSample {
+        offset_micros: ((reply.received_micros - reply.origin_micros)
+            + (reply.transmitted_micros - arrived_micros))
+            / 2,
+        round_trip_micros: (arrived_micros - reply.origin_micros)
+            - (reply.transmitted_micros - reply.received_micros),
+    };
+
+    // clock stepped backward while the request was on its way
+    if sample.round_trip_micros < 0 {
+        return;
+    }
+
+    let mut samples = ESTIMATES.entry(endpoint_id).or_default();
+    if samples.len() >= MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(sample);
+}
+
+/// The best estimate of the clock of passive endpoint, None until a reply arrives.
+pub fn clock_offset(endpoint_id: &EndPointID) -> Option<ClockSample> {
+    ESTIMATES.get(endpoint_id).and_then(|samples| {
+        samples
+            .iter()
+            .min_by_key(|sample| sample.round_trip_micros)
+            .copied()
+    })
+}
+
+/// Time since passive endpoint stamped `timestamp_micros` on its clock, None when the
+/// offset isn't known yet or the message carries no timestamp.
+pub fn one_way_latency(endpoint_id: &EndPointID, timestamp_micros: i64) -> Option<Duration> {
+    if timestamp_micros == 0 {
+        return None;
+    }
+
+    let offset = clock_offset(endpoint_id)?;
+    let latency_micros = now_micros() - (timestamp_micros - offset.offset_micros);
+
+    // the estimate is off by up to half the round trip, small negatives are noise
+    Some(Duration::from_micros(latency_micros.max(0) as u64))
+}
+
+pub(crate) fn remove_session(endpoint_id: &EndPointID) {
+    ESTIMATES.remove(endpoint_id);
+}
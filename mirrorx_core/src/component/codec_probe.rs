@@ -0,0 +1,436 @@
+//! Hardware encoders and decoders differ by machine, driver and ffmpeg build. They're
+//! probed once in background at startup and the result is cached in local storage, so
+//! codec negotiation picks what the machine can do instead of opening an encoder which
+//! fails when the session starts. The cache is probed again when the version of app, the
+//! operating system or the graphics cards change.
+//!
+//! Encoders are probed by opening them the way sessions do at descending sizes, which
+//! also rules out the ones that can't take NV12 frames from memory, like VAAPI. Decoders
+//! are probed by creating the device of accelerator, their limits aren't known until
+//! decoding.
+
+use crate::{
+    api::{config::LocalStorage, endpoint::message::VideoCodec},
+    error::CoreResult,
+    utility::{os::enum_graphics_cards, spawn},
+};
+use mirrorx_native::ffmpeg::{
+    codecs::{codec::*, codec_id::*},
+    utils::{buffer::av_buffer_unref, hwcontext::*},
+};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{ffi::CStr, sync::RwLock};
+
+#[cfg(feature = "host")]
+use crate::component::{
+    frame::ColorSpace,
+    video_encoder::{config::probed::ProbedEncoderConfig, encoder::WarmEncodeContext},
+};
+
+// descending, the first one which opens is the max resolution of encoder
+#[cfg(feature = "host")]
+const PROBE_RESOLUTIONS: [(u16, u16); 5] = [
+    (7680, 4320),
+    (5120, 2880),
+    (3840, 2160),
+    (2560, 1440),
+    (1920, 1080),
+];
+
+// profiles are probed at the size which every encoder takes
+#[cfg(feature = "host")]
+const PROFILE_PROBE_RESOLUTION: (u16, u16) = (1280, 720);
+
+static CAPABILITIES: Lazy<RwLock<Option<CodecCapabilities>>> = Lazy::new(|| RwLock::new(None));
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Accelerator {
+    /// NVENC encoders, NVDEC decoders through CUDA.
+    Nvidia,
+    /// Quick Sync Video.
+    Intel,
+    /// AMF encoders, AMD decodes through D3D11VA.
+    Amd,
+    VideoToolbox,
+    Vaapi,
+    D3d11va,
+    Software,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CodecSupport {
+    pub codec: VideoCodec,
+    pub accelerator: Accelerator,
+    /// Name of ffmpeg encoder, or of the decoder which the accelerator is attached to.
+    pub ffmpeg_name: String,
+    /// The highest profile which opens, None for decoders which take every profile.
+    pub profile: Option<String>,
+    /// The largest size which opens, None when it isn't known until decoding.
+    pub max_resolution: Option<(u16, u16)>,
+}
+
+impl CodecSupport {
+    pub fn fits(&self, width: u16, height: u16) -> bool {
+        self.max_resolution.map_or(true, |(max_width, max_height)| {
+            width <= max_width && height <= max_height
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CodecCapabilities {
+    // the machine which is probed, capabilities are probed again once it changes
+    fingerprint: String,
+    /// Unix timestamp in seconds.
+    pub probed_at: i64,
+    /// In order of preference, hardware ones come first.
+    pub encoders: Vec<CodecSupport>,
+    pub decoders: Vec<CodecSupport>,
+}
+
+#[cfg(feature = "host")]
+struct EncoderCandidate {
+    codec: VideoCodec,
+    accelerator: Accelerator,
+    ffmpeg_name: &'static str,
+    // in order of preference
+    profiles: &'static [&'static str],
+}
+
+#[cfg(feature = "host")]
+const H264_PROFILES: &[&str] = &["high", "main", "baseline"];
+
+#[cfg(feature = "host")]
+const HEVC_PROFILES: &[&str] = &["main"];
+
+// encoders which aren't built into ffmpeg are skipped without opening, so the list
+// isn't split by platform
+#[cfg(feature = "host")]
+const ENCODER_CANDIDATES: &[EncoderCandidate] = &[
+    EncoderCandidate {
+        codec: VideoCodec::H264,
+        accelerator: Accelerator::Nvidia,
+        ffmpeg_name: "h264_nvenc",
+        profiles: H264_PROFILES,
+    },
+    EncoderCandidate {
+        codec: VideoCodec::H264,
+        accelerator: Accelerator::Intel,
+        ffmpeg_name: "h264_qsv",
+        profiles: H264_PROFILES,
+    },
+    EncoderCandidate {
+        codec: VideoCodec::H264,
+        accelerator: Accelerator::Amd,
+        ffmpeg_name: "h264_amf",
+        profiles: H264_PROFILES,
+    },
+    EncoderCandidate {
+        codec: VideoCodec::H264,
+        accelerator: Accelerator::VideoToolbox,
+        ffmpeg_name: "h264_videotoolbox",
+        profiles: H264_PROFILES,
+    },
+    EncoderCandidate {
+        codec: VideoCodec::H264,
+        accelerator: Accelerator::Vaapi,
+        ffmpeg_name: "h264_vaapi",
+        profiles: H264_PROFILES,
+    },
+    // options of libx264 stick to baseline
+    EncoderCandidate {
+        codec: VideoCodec::H264,
+        accelerator: Accelerator::Software,
+        ffmpeg_name: "libx264",
+        profiles: &["baseline"],
+    },
+    EncoderCandidate {
+        codec: VideoCodec::Hevc,
+        accelerator: Accelerator::Nvidia,
+        ffmpeg_name: "hevc_nvenc",
+        profiles: HEVC_PROFILES,
+    },
+    EncoderCandidate {
+        codec: VideoCodec::Hevc,
+        accelerator: Accelerator::Intel,
+        ffmpeg_name: "hevc_qsv",
+        profiles: HEVC_PROFILES,
+    },
+    EncoderCandidate {
+        codec: VideoCodec::Hevc,
+        accelerator: Accelerator::Amd,
+        ffmpeg_name: "hevc_amf",
+        profiles: HEVC_PROFILES,
+    },
+    EncoderCandidate {
+        codec: VideoCodec::Hevc,
+        accelerator: Accelerator::VideoToolbox,
+        ffmpeg_name: "hevc_videotoolbox",
+        profiles: HEVC_PROFILES,
+    },
+    EncoderCandidate {
+        codec: VideoCodec::Hevc,
+        accelerator: Accelerator::Vaapi,
+        ffmpeg_name: "hevc_vaapi",
+        profiles: HEVC_PROFILES,
+    },
+];
+
+const DECODER_ACCELERATORS: [(Accelerator, AVHWDeviceType); 5] = [
+    (Accelerator::Nvidia, AV_HWDEVICE_TYPE_CUDA),
+    (Accelerator::Intel, AV_HWDEVICE_TYPE_QSV),
+    (Accelerator::D3d11va, AV_HWDEVICE_TYPE_D3D11VA),
+    (Accelerator::VideoToolbox, AV_HWDEVICE_TYPE_VIDEOTOOLBOX),
+    (Accelerator::Vaapi, AV_HWDEVICE_TYPE_VAAPI),
+];
+
+/// Load the cached capabilities, or probe them in background when there are none or
+/// they're outdated. Codecs are negotiated as before probing, with libx264 and software
+/// decoders, until it finishes.
+pub fn serve_probe(storage: LocalStorage) {
+    spawn::spawn_blocking(move || {
+        let fingerprint = fingerprint();
+
+        match load_capabilities(&storage) {
+            Ok(Some(capabilities)) if capabilities.fingerprint == fingerprint => {
+                tracing::info!(?capabilities, "load cached codec capabilities");
+                *CAPABILITIES.write().unwrap() = Some(capabilities);
+                return;
+            }
+            Ok(_) => {}
+            Err(err) => tracing::warn!(?err, "load cached codec capabilities failed"),
+        }
+
+        let instant = std::time::Instant::now();
+        let capabilities = probe(fingerprint);
+        tracing::info!(elapsed = ?instant.elapsed(), ?capabilities, "probe codec capabilities");
+
+        if let Err(err) = save_capabilities(&storage, &capabilities) {
+            tracing::warn!(?err, "save codec capabilities failed");
+        }
+
+        *CAPABILITIES.write().unwrap() = Some(capabilities);
+    });
+}
+
+/// None until probing at startup finishes.
+pub fn codec_capabilities() -> Option<CodecCapabilities> {
+    CAPABILITIES.read().unwrap().clone()
+}
+
+/// The preferred encoder of `codec` which takes frames of the size.
+#[cfg(feature = "host")]
+pub fn select_encoder(codec: &VideoCodec, width: u16, height: u16) -> Option<CodecSupport> {
+    // tiles are encoded without ffmpeg, every machine takes them at any size
+    if *codec == VideoCodec::TileCache {
+        return Some(CodecSupport {
+            codec: VideoCodec::TileCache,
+            accelerator: Accelerator::Software,
+            ffmpeg_name: String::new(),
+            profile: None,
+            max_resolution: None,
+        });
+    }
+
+    match CAPABILITIES.read().unwrap().as_ref() {
+        Some(capabilities) => capabilities
+            .encoders
+            .iter()
+            .find(|encoder| encoder.codec == *codec && encoder.fits(width, height))
+            .cloned(),
+        // the encoder which sessions used before probing existed
+        None => (*codec == VideoCodec::H264).then(|| CodecSupport {
+            codec: VideoCodec::H264,
+            accelerator: Accelerator::Software,
+            ffmpeg_name: String::from("libx264"),
+            profile: Some(String::from("baseline")),
+            max_resolution: None,
+        }),
+    }
+}
+
+/// The codecs among `supported` which this machine decodes, in order of `supported`.
+pub fn decodable_codecs(supported: &[VideoCodec]) -> Vec<VideoCodec> {
+    match CAPABILITIES.read().unwrap().as_ref() {
+        Some(capabilities) => supported
+            .iter()
+            .filter(|codec| {
+                **codec == VideoCodec::TileCache
+                    || capabilities
+                        .decoders
+                        .iter()
+                        .any(|decoder| decoder.codec == **codec)
+            })
+            .cloned()
+            .collect(),
+        None => supported.to_vec(),
+    }
+}
+
+fn probe(fingerprint: String) -> CodecCapabilities {
+    #[cfg(feature = "host")]
+    let encoders = ENCODER_CANDIDATES
+        .iter()
+        .filter_map(probe_encoder)
+        .collect();
+
+    // viewers only decode
+    #[cfg(not(feature = "host"))]
+    let encoders = Vec::new();
+
+    let decoders = [VideoCodec::H264, VideoCodec::Hevc]
+        .into_iter()
+        .flat_map(probe_decoders)
+        .collect();
+
+    CodecCapabilities {
+        fingerprint,
+        probed_at: chrono::Utc::now().timestamp(),
+        encoders,
+        decoders,
+    }
+}
+
+#[cfg(feature = "host")]
+fn probe_encoder(candidate: &EncoderCandidate) -> Option<CodecSupport> {
+    let ffmpeg_name = std::ffi::CString::new(candidate.ffmpeg_name).ok()?;
+    if unsafe { avcodec_find_encoder_by_name(ffmpeg_name.as_ptr()) }.is_null() {
+        return None;
+    }
+
+    let opens = |profile: &str, (width, height): (u16, u16)| {
+        let Ok(config) = ProbedEncoderConfig::new(
+            candidate.accelerator,
+            &candidate.codec,
+            candidate.ffmpeg_name,
+            profile,
+        ) else {
+            return false;
+        };
+
+        WarmEncodeContext::new(width as i32, height as i32, ColorSpace::default(), &config).is_ok()
+    };
+
+    let profile = candidate
+        .profiles
+        .iter()
+        .find(|profile| opens(profile, PROFILE_PROBE_RESOLUTION))?;
+
+    let max_resolution = PROBE_RESOLUTIONS
+        .into_iter()
+        .find(|resolution| opens(profile, *resolution))
+        .unwrap_or(PROFILE_PROBE_RESOLUTION);
+
+    Some(CodecSupport {
+        codec: candidate.codec.clone(),
+        accelerator: candidate.accelerator,
+        ffmpeg_name: candidate.ffmpeg_name.to_string(),
+        profile: Some(profile.to_string()),
+        max_resolution: Some(max_resolution),
+    })
+}
+
+fn probe_decoders(codec: VideoCodec) -> Vec<CodecSupport> {
+    let codec_id = match codec {
+        VideoCodec::H264 => AV_CODEC_ID_H264,
+        VideoCodec::Hevc => AV_CODEC_ID_HEVC,
+        VideoCodec::VP8 => AV_CODEC_ID_VP8,
+        VideoCodec::VP9 => AV_CODEC_ID_VP9,
+        // tiles aren't decoded by ffmpeg
+        VideoCodec::TileCache => return Vec::new(),
+    };
+
+    unsafe {
+        let decoder = avcodec_find_decoder(codec_id);
+        if decoder.is_null() {
+            return Vec::new();
+        }
+
+        let ffmpeg_name = CStr::from_ptr((*decoder).name)
+            .to_string_lossy()
+            .into_owned();
+
+        DECODER_ACCELERATORS
+            .into_iter()
+            .filter(|(_, device_type)| {
+                decoder_accepts_device(decoder, *device_type) && device_available(*device_type)
+            })
+            .map(|(accelerator, _)| accelerator)
+            .chain(std::iter::once(Accelerator::Software))
+            .map(|accelerator| CodecSupport {
+                codec: codec.clone(),
+                accelerator,
+                ffmpeg_name: ffmpeg_name.clone(),
+                profile: None,
+                max_resolution: None,
+            })
+            .collect()
+    }
+}
+
+unsafe fn decoder_accepts_device(decoder: *const AVCodec, device_type: AVHWDeviceType) -> bool {
+    let mut index = 0;
+    loop {
+        let hw_config = avcodec_get_hw_config(decoder, index);
+        if hw_config.is_null() {
+            return false;
+        }
+
+        if (*hw_config).device_type == device_type
+            && (*hw_config).methods & AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX != 0
+        {
+            return true;
+        }
+
+        index += 1;
+    }
+}
+
+// the device can't be created without the driver or the GPU
+unsafe fn device_available(device_type: AVHWDeviceType) -> bool {
+    let mut device_ctx = std::ptr::null_mut();
+    let ret = av_hwdevice_ctx_create(
+        &mut device_ctx,
+        device_type,
+        std::ptr::null(),
+        std::ptr::null_mut(),
+        0,
+    );
+
+    if ret < 0 {
+        return false;
+    }
+
+    av_buffer_unref(&mut device_ctx);
+    true
+}
+
+fn fingerprint() -> String {
+    let graphics_cards = enum_graphics_cards()
+        .ok()
+        .and_then(|graphics_cards| serde_json::to_string(&graphics_cards).ok())
+        .unwrap_or_default();
+
+    format!(
+        "{}/{}/{}",
+        env!("CARGO_PKG_VERSION"),
+        os_info::get(),
+        graphics_cards
+    )
+}
+
+fn load_capabilities(storage: &LocalStorage) -> CoreResult<Option<CodecCapabilities>> {
+    match storage.kv().get_codec_capabilities()? {
+        // a cache written by other versions may not parse, it's probed again then
+        Some(capabilities) => Ok(serde_json::from_str(&capabilities).ok()),
+        None => Ok(None),
+    }
+}
+
+fn save_capabilities(storage: &LocalStorage, capabilities: &CodecCapabilities) -> CoreResult<()> {
+    storage
+        .kv()
+        .set_codec_capabilities(&serde_json::to_string(capabilities)?)
+}
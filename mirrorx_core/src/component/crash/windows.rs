@@ -0,0 +1,51 @@
+use super::{new_report_path, MINIDUMP_EXTENSION};
+use std::os::windows::io::AsRawHandle;
+use windows::Win32::{
+    Foundation::{BOOL, HANDLE},
+    System::{
+        Diagnostics::Debug::{
+            MiniDumpNormal, MiniDumpWithThreadInfo, MiniDumpWriteDump, SetUnhandledExceptionFilter,
+            EXCEPTION_POINTERS, MINIDUMP_EXCEPTION_INFORMATION,
+        },
+        Threading::{GetCurrentProcess, GetCurrentProcessId, GetCurrentThreadId},
+    },
+};
+
+// let the exception go on to the default handler, which terminates the process
+const EXCEPTION_CONTINUE_SEARCH: i32 = 0;
+
+pub fn install_exception_filter() {
+    unsafe {
+        SetUnhandledExceptionFilter(Some(write_minidump));
+    }
+}
+
+// the process is broken here, write the dump with as less work as possible and never
+// panic since nothing would catch it
+unsafe extern "system" fn write_minidump(exception_pointers: *const EXCEPTION_POINTERS) -> i32 {
+    let Some(path) = new_report_path(MINIDUMP_EXTENSION) else {
+        return EXCEPTION_CONTINUE_SEARCH;
+    };
+
+    let Ok(file) = std::fs::File::create(&path) else {
+        return EXCEPTION_CONTINUE_SEARCH;
+    };
+
+    let exception_information = MINIDUMP_EXCEPTION_INFORMATION {
+        ThreadId: GetCurrentThreadId(),
+        ExceptionPointers: exception_pointers as *mut EXCEPTION_POINTERS,
+        ClientPointers: BOOL(0),
+    };
+
+    let _ = MiniDumpWriteDump(
+        GetCurrentProcess(),
+        GetCurrentProcessId(),
+        HANDLE(file.as_raw_handle() as isize),
+        MiniDumpNormal | MiniDumpWithThreadInfo,
+        Some(&exception_information),
+        None,
+        None,
+    );
+
+    EXCEPTION_CONTINUE_SEARCH
+}
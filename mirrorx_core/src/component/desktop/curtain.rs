@@ -0,0 +1,194 @@
+//! Curtain mode blanks the host screen with an overlay while the session goes on. The
+//! overlay may crash, lose its topmost z-order, or miss a monitor which wakes up or is
+//! plugged in, and the host screen becomes visible silently, so a watchdog verifies the
+//! curtain periodically and video transmission is paused until privacy is guaranteed.
+
+use super::monitor::{get_active_monitors, Monitor};
+use crate::error::CoreResult;
+use once_cell::sync::Lazy;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(3);
+
+static CURTAIN: Lazy<Mutex<Option<Curtain>>> = Lazy::new(|| Mutex::new(None));
+
+// false when curtain is active but the watchdog can't verify it's intact
+static PRIVACY_GUARANTEED: AtomicBool = AtomicBool::new(true);
+
+/// The blanking overlay of curtain mode, it's implemented by the platform window which
+/// covers the monitors.
+pub trait CurtainOverlay: Send + Sync {
+    /// Returns whether the overlay is still alive, topmost and covering all `monitors`.
+    fn is_intact(&self, monitors: &[Monitor]) -> CoreResult<bool>;
+}
+
+struct Curtain {
+    overlay: Arc<dyn CurtainOverlay>,
+    // monitor topology when the curtain is raised, the overlay doesn't cover new monitors
+    monitors: Vec<MonitorGeometry>,
+    // bumped on each activation so the watchdog of a previous curtain exits
+    generation: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MonitorGeometry {
+    id: String,
+    left: i32,
+    top: i32,
+    width: u16,
+    height: u16,
+}
+
+/// Raise the curtain with `overlay` covering current monitors and start its watchdog.
+pub fn activate_curtain(overlay: Arc<dyn CurtainOverlay>) -> CoreResult<()> {
+    let monitors = monitor_geometries(&get_active_monitors(false)?);
+
+    let generation = {
+        let mut curtain = CURTAIN.lock().unwrap();
+        let generation = curtain.as_ref().map_or(0, |curtain| curtain.generation + 1);
+
+        *curtain = Some(Curtain {
+            overlay,
+            monitors,
+            generation,
+        });
+
+        generation
+    };
+
+    // transmission resumes after the first verification passes
+    PRIVACY_GUARANTEED.store(false, Ordering::SeqCst);
+
+    std::thread::Builder::new()
+        .name(String::from("curtain_watchdog"))
+        .spawn(move || serve_watchdog(generation))?;
+
+    tracing::info!("curtain activated");
+
+    Ok(())
+}
+
+pub fn deactivate_curtain() {
+    if CURTAIN.lock().unwrap().take().is_some() {
+        PRIVACY_GUARANTEED.store(true, Ordering::SeqCst);
+        tracing::info!("curtain deactivated");
+    }
+}
+
+pub fn curtain_active() -> bool {
+    CURTAIN.lock().unwrap().is_some()
+}
+
+/// Whether the captured desktop can be sent to remote, it's always true when curtain
+/// is not active.
+pub fn transmission_allowed() -> bool {
+    PRIVACY_GUARANTEED.load(Ordering::SeqCst)
+}
+
+fn serve_watchdog(generation: u64) {
+    loop {
+        let Some((overlay, expected_monitors)) = curtain_snapshot(generation) else {
+            break;
+        };
+
+        let guaranteed = match verify_curtain(overlay.as_ref(), &expected_monitors) {
+            Ok(()) => true,
+            Err(reason) => {
+                tracing::error!(%reason, "curtain can't guarantee privacy");
+                false
+            }
+        };
+
+        // the curtain may be deactivated or raised again while verifying
+        if curtain_snapshot(generation).is_none() {
+            break;
+        }
+
+        let previous = PRIVACY_GUARANTEED.swap(guaranteed, Ordering::SeqCst);
+        if previous != guaranteed {
+            if guaranteed {
+                tracing::info!("curtain verified, resume video transmission");
+            } else {
+                tracing::warn!("curtain broken, pause video transmission");
+            }
+        }
+
+        std::thread::sleep(WATCHDOG_INTERVAL);
+    }
+
+    tracing::info!("curtain watchdog exit");
+}
+
+fn curtain_snapshot(generation: u64) -> Option<(Arc<dyn CurtainOverlay>, Vec<MonitorGeometry>)> {
+    match *CURTAIN.lock().unwrap() {
+        Some(ref curtain) if curtain.generation == generation => {
+            Some((curtain.overlay.clone(), curtain.monitors.clone()))
+        }
+        _ => None,
+    }
+}
+
+fn verify_curtain(
+    overlay: &dyn CurtainOverlay,
+    expected_monitors: &[MonitorGeometry],
+) -> Result<(), String> {
+    let monitors =
+        get_active_monitors(false).map_err(|err| format!("enum monitors failed ({err})"))?;
+
+    if monitor_geometries(&monitors) != expected_monitors {
+        return Err(String::from("monitor topology changed"));
+    }
+
+    for monitor in &monitors {
+        if monitor_asleep(monitor)? {
+            // the overlay surface may be discarded while display sleeps, it's verified
+            // again after the display wakes up
+            return Err(format!("monitor '{}' is asleep", monitor.id));
+        }
+    }
+
+    match overlay.is_intact(&monitors) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(String::from("overlay is not intact")),
+        Err(err) => Err(format!("verify overlay failed ({err})")),
+    }
+}
+
+fn monitor_geometries(monitors: &[Monitor]) -> Vec<MonitorGeometry> {
+    let mut geometries: Vec<MonitorGeometry> = monitors
+        .iter()
+        .map(|monitor| MonitorGeometry {
+            id: monitor.id.clone(),
+            left: monitor.left,
+            top: monitor.top,
+            width: monitor.width,
+            height: monitor.height,
+        })
+        .collect();
+
+    geometries.sort_by(|a, b| a.id.cmp(&b.id));
+    geometries
+}
+
+#[cfg(target_os = "macos")]
+fn monitor_asleep(monitor: &Monitor) -> Result<bool, String> {
+    let display_id = monitor
+        .id
+        .parse()
+        .map_err(|_| format!("invalid display id '{}'", monitor.id))?;
+
+    Ok(core_graphics::display::CGDisplay::new(display_id).is_asleep())
+}
+
+// sleeping monitors are detached from desktop duplication, they're caught by the
+// topology check
+#[cfg(not(target_os = "macos"))]
+fn monitor_asleep(_: &Monitor) -> Result<bool, String> {
+    Ok(false)
+}
@@ -0,0 +1,70 @@
+//! Static desktops like a document being read or an unattended screen produce the same
+//! frame over and over, encoding them at full frame rate burns CPU and bandwidth for
+//! nothing. Capture hashes every frame and drops to the idle frame rate once the frames
+//! stay identical for a while, the first changed frame lifts the limit at once.
+
+use crate::component::frame::DesktopEncodeFrame;
+use fxhash::FxHasher64;
+use std::{
+    hash::Hasher,
+    sync::atomic::{AtomicU8, Ordering},
+    time::Duration,
+};
+
+// frames must stay identical this long before capture is considered idle, so a blinking
+// caret or a slow typist doesn't flip the frame rate back and forth
+const IDLE_THRESHOLD: Duration = Duration::from_secs(1);
+
+static IDLE_FRAME_RATE: AtomicU8 = AtomicU8::new(2);
+
+/// Set the frame rate which capture keeps on idle desktop, zero disables the detection.
+pub fn set_idle_frame_rate(frame_rate: u8) {
+    IDLE_FRAME_RATE.store(frame_rate, Ordering::SeqCst);
+}
+
+pub fn idle_frame_rate() -> u8 {
+    IDLE_FRAME_RATE.load(Ordering::SeqCst)
+}
+
+#[derive(Default)]
+pub struct IdleDetector {
+    last_hash: Option<u64>,
+    // capture time of the first frame of the identical run
+    unchanged_since: Option<Duration>,
+}
+
+impl IdleDetector {
+    /// Feed the captured frame, returns the max frame rate while the desktop is idle and
+    /// None once anything changes.
+    pub fn observe(&mut self, frame: &DesktopEncodeFrame) -> Option<u8> {
+        let idle_frame_rate = idle_frame_rate();
+        if idle_frame_rate == 0 {
+            self.last_hash = None;
+            self.unchanged_since = None;
+            return None;
+        }
+
+        let hash = hash_frame(frame);
+        if self.last_hash != Some(hash) {
+            self.last_hash = Some(hash);
+            self.unchanged_since = Some(frame.capture_time);
+            return None;
+        }
+
+        let unchanged_since = *self.unchanged_since.get_or_insert(frame.capture_time);
+        if frame.capture_time.saturating_sub(unchanged_since) < IDLE_THRESHOLD {
+            return None;
+        }
+
+        Some(idle_frame_rate)
+    }
+}
+
+fn hash_frame(frame: &DesktopEncodeFrame) -> u64 {
+    let mut hasher = FxHasher64::default();
+    hasher.write_i32(frame.width);
+    hasher.write_i32(frame.height);
+    hasher.write(&frame.luminance_bytes);
+    hasher.write(&frame.chrominance_bytes);
+    hasher.finish()
+}
@@ -1,9 +1,17 @@
 use crate::{
-    component::{desktop::monitor::NSScreen, frame::DesktopEncodeFrame},
+    component::{
+        desktop::monitor::NSScreen,
+        frame::{ColorPrimaries, ColorRange, ColorSpace, DesktopEncodeFrame},
+    },
     core_error,
     error::CoreResult,
 };
 use block::ConcreteBlock;
+use core_foundation::{
+    base::{CFType, CFTypeRef, TCFType},
+    dictionary::CFDictionary,
+    string::CFString,
+};
 use dispatch::ffi::{dispatch_queue_create, dispatch_release, DISPATCH_QUEUE_SERIAL};
 use mirrorx_native::os::macos::{core_graphics::*, core_video::*, io_surface::*};
 use once_cell::unsync::OnceCell;
@@ -13,6 +21,9 @@ use tokio::sync::mpsc::Sender;
 
 pub struct Duplicator {
     display_stream: CGDisplayStreamRef,
+    width: i32,
+    height: i32,
+    color_space: ColorSpace,
 }
 
 unsafe impl Send for Duplicator {}
@@ -50,6 +61,41 @@ impl Duplicator {
 
             let screen_size = screen.frame().size;
 
+            // keep the wide gamut of P3 screen, otherwise let CoreGraphics convert to sRGB
+            let color_space = ColorSpace {
+                primaries: if screen.can_represent_display_p3() {
+                    ColorPrimaries::DisplayP3
+                } else {
+                    ColorPrimaries::Bt709
+                },
+                range: ColorRange::Full,
+            };
+
+            let cg_color_space = CGColorSpaceCreateWithName(match color_space.primaries {
+                ColorPrimaries::Bt709 => kCGColorSpaceSRGB,
+                ColorPrimaries::DisplayP3 => kCGColorSpaceDisplayP3,
+            });
+
+            if cg_color_space.is_null() {
+                return Err(core_error!("CGColorSpaceCreateWithName returns null"));
+            }
+
+            defer! {
+                CGColorSpaceRelease(cg_color_space);
+            }
+
+            let properties = CFDictionary::from_CFType_pairs(&[
+                (
+                    CFString::wrap_under_get_rule(kCGDisplayStreamYCbCrMatrix).as_CFType(),
+                    CFString::wrap_under_get_rule(kCGDisplayStreamYCbCrMatrix_ITU_R_709_2)
+                        .as_CFType(),
+                ),
+                (
+                    CFString::wrap_under_get_rule(kCGDisplayStreamColorSpace).as_CFType(),
+                    CFType::wrap_under_get_rule(cg_color_space as CFTypeRef),
+                ),
+            ]);
+
             let capture_frame_tx_ptr = Box::into_raw(Box::new(capture_frame_tx));
 
             let epoch: OnceCell<std::time::Instant> = OnceCell::new();
@@ -68,6 +114,7 @@ impl Duplicator {
 
                     frame_available_handler(
                         capture_time,
+                        color_space,
                         capture_frame_tx_ptr,
                         status,
                         display_time,
@@ -84,18 +131,28 @@ impl Duplicator {
                 screen_size.width as usize,
                 screen_size.height as usize,
                 kCVPixelFormatType_420YpCbCr8BiPlanarFullRange as i32,
-                std::ptr::null_mut(),
+                properties.as_concrete_TypeRef(),
                 dispatch_queue,
                 block.deref(),
             );
 
             Ok((
-                Duplicator { display_stream },
+                Duplicator {
+                    display_stream,
+                    width: screen_size.width as i32,
+                    height: screen_size.height as i32,
+                    color_space,
+                },
                 screen.screenNumber().to_string(),
             ))
         }
     }
 
+    /// Width, height and color space of the captured frames.
+    pub fn frame_format(&self) -> (i32, i32, ColorSpace) {
+        (self.width, self.height, self.color_space)
+    }
+
     pub fn start(&self) -> CoreResult<()> {
         unsafe {
             let error_code = CGDisplayStreamStart(self.display_stream);
@@ -127,6 +184,7 @@ impl Duplicator {
 
 unsafe fn frame_available_handler(
     capture_time: Duration,
+    color_space: ColorSpace,
     capture_frame_tx: *mut Sender<DesktopEncodeFrame>,
     status: CGDisplayStreamFrameStatus,
     _display_time: u64,
@@ -184,6 +242,7 @@ unsafe fn frame_available_handler(
 
     let capture_frame = DesktopEncodeFrame {
         capture_time,
+        color_space,
         width: width as i32,
         height: height as i32,
         luminance_bytes,
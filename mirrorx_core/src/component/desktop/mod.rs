@@ -1,4 +1,20 @@
+#[cfg(feature = "host")]
+pub mod curtain;
+pub mod idle;
 pub mod monitor;
+pub mod span;
+#[cfg(feature = "host")]
+pub mod test_pattern;
+
+use crate::api::endpoint::message::CursorMode;
+
+/// Cursor modes which the duplicator of current platform supports.
+#[cfg(target_os = "windows")]
+pub const SUPPORTED_CURSOR_MODES: &[CursorMode] = &[CursorMode::Composited, CursorMode::Separate];
+
+/// Cursor modes which the duplicator of current platform supports.
+#[cfg(target_os = "macos")]
+pub const SUPPORTED_CURSOR_MODES: &[CursorMode] = &[CursorMode::Composited];
 
 #[cfg(target_os = "macos")]
 mod macos;
@@ -8,4 +24,4 @@ pub use macos::Duplicator;
 #[cfg(target_os = "windows")]
 mod windows;
 #[cfg(target_os = "windows")]
-pub use self::windows::Duplicator;
+pub use self::windows::{secure_desktop, Duplicator};
@@ -1,8 +1,16 @@
 use super::Monitor;
-use crate::{core_error, error::CoreResult};
+use crate::{
+    component::frame::{ColorPrimaries, ColorRange, ColorSpace},
+    core_error,
+    error::CoreResult,
+};
 use core_graphics::display::{CGDirectDisplayID, CGRect, *};
 use mirrorx_native::os::macos::core_graphics::*;
-use objc::{class, msg_send, runtime::Class, sel, sel_impl};
+use objc::{
+    class, msg_send,
+    runtime::{Class, BOOL, YES},
+    sel, sel_impl,
+};
 use objc_foundation::{
     INSArray, INSData, INSObject, INSString, NSArray, NSDictionary, NSMutableData, NSObject,
     NSString,
@@ -25,6 +33,23 @@ pub fn get_primary_monitor_params() -> CoreResult<Monitor> {
     Err(core_error!("no primary display"))
 }
 
+/// Display stream captures in full range, and keeps the wide gamut of P3 screen.
+pub fn get_monitor_color_space(monitor_id: &str) -> CoreResult<ColorSpace> {
+    let screen = NSScreen::screens()?
+        .into_iter()
+        .find(|screen| screen.screenNumber().to_string() == monitor_id)
+        .ok_or_else(|| core_error!("can't find screen for monitor '{}'", monitor_id))?;
+
+    Ok(ColorSpace {
+        primaries: if screen.can_represent_display_p3() {
+            ColorPrimaries::DisplayP3
+        } else {
+            ColorPrimaries::Bt709
+        },
+        range: ColorRange::Full,
+    })
+}
+
 pub fn get_active_monitors(take_screen_shot: bool) -> CoreResult<Vec<Monitor>> {
     unsafe {
         let main_display_id = CGMainDisplayID();
@@ -38,6 +63,9 @@ pub fn get_active_monitors(take_screen_shot: bool) -> CoreResult<Vec<Monitor>> {
             let monitor_width = CGDisplayPixelsWide(display_id);
             let monitor_height = CGDisplayPixelsHigh(display_id);
 
+            // global display space has its origin at the top left corner of main display
+            let monitor_origin = CGDisplay::new(display_id).bounds().origin;
+
             let screen_shot_buffer = if take_screen_shot {
                 take_screen_shot_as_png(display_id)
             } else {
@@ -52,8 +80,10 @@ pub fn get_active_monitors(take_screen_shot: bool) -> CoreResult<Vec<Monitor>> {
                 height: monitor_height as u16,
                 is_primary: display_id == main_display_id,
                 screen_shot: screen_shot_buffer,
-                left: 0,
-                top: 0,
+                left: monitor_origin.x as i32,
+                top: monitor_origin.y as i32,
+                // display streams capture frames in points
+                scale_factor: 100,
             });
         }
 
@@ -155,4 +185,15 @@ impl NSScreen {
     pub fn frame(&self) -> CGRect {
         unsafe { msg_send![self.class, frame] }
     }
+
+    /// Returns true if the screen can display colors of Display P3 gamut.
+    pub fn can_represent_display_p3(&self) -> bool {
+        // NSDisplayGamutP3
+        const NS_DISPLAY_GAMUT_P3: isize = 2;
+
+        unsafe {
+            let result: BOOL = msg_send![self.class, canRepresentDisplayGamut: NS_DISPLAY_GAMUT_P3];
+            result == YES
+        }
+    }
 }
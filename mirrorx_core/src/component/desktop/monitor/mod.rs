@@ -4,13 +4,15 @@ use serde::{Deserialize, Serialize};
 mod windows;
 
 #[cfg(target_os = "windows")]
-pub use self::windows::{get_active_monitors, get_primary_monitor_params};
+pub use self::windows::{get_active_monitors, get_monitor_color_space, get_primary_monitor_params};
 
 #[cfg(target_os = "macos")]
 mod macos;
 
 #[cfg(target_os = "macos")]
-pub use macos::{get_active_monitors, get_primary_monitor_params, NSScreen};
+pub use macos::{
+    get_active_monitors, get_monitor_color_space, get_primary_monitor_params, NSScreen,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub struct Monitor {
@@ -21,6 +23,24 @@ pub struct Monitor {
     pub height: u16,
     pub is_primary: bool,
     pub screen_shot: Option<Vec<u8>>,
-    pub left: u16,
-    pub top: u16,
+    /// Origin in virtual desktop space, monitors placed left of or above the primary one
+    /// have negative origin.
+    pub left: i32,
+    pub top: i32,
+    /// Pixels of captured frames per unit of desktop space in percent, positions in the
+    /// frames are divided by it before they're placed on desktop.
+    #[serde(default = "default_scale_factor")]
+    pub scale_factor: u16,
+}
+
+impl Monitor {
+    /// Translate the position in captured frames of monitor into virtual desktop space.
+    pub fn desktop_position(&self, x: f32, y: f32) -> (f32, f32) {
+        let scale = self.scale_factor.max(1) as f32 / 100.0;
+        (self.left as f32 + x / scale, self.top as f32 + y / scale)
+    }
+}
+
+fn default_scale_factor() -> u16 {
+    100
 }
@@ -1,4 +1,12 @@
-use crate::{component::desktop::monitor::Monitor, core_error, error::CoreResult, HRESULT};
+use crate::{
+    component::{
+        desktop::monitor::Monitor,
+        frame::{ColorPrimaries, ColorRange, ColorSpace},
+    },
+    core_error,
+    error::CoreResult,
+    HRESULT,
+};
 use image::ColorType;
 use scopeguard::defer;
 use std::{collections::HashMap, io::Cursor, os::raw::c_void};
@@ -23,6 +31,14 @@ pub fn get_primary_monitor_params() -> CoreResult<Monitor> {
     Err(core_error!("no primary display"))
 }
 
+/// Desktop Duplication outputs sRGB content and the NV12 conversion of it produces limited range.
+pub fn get_monitor_color_space(_monitor_id: &str) -> CoreResult<ColorSpace> {
+    Ok(ColorSpace {
+        primaries: ColorPrimaries::Bt709,
+        range: ColorRange::Limited,
+    })
+}
+
 pub fn get_active_monitors(take_screen_shot: bool) -> CoreResult<Vec<Monitor>> {
     unsafe {
         let all_monitors = enum_all_monitors_path_and_name()?;
@@ -136,16 +152,28 @@ unsafe fn enum_dxgi_outputs(
                     .get(&device_id)
                     .map_or(String::default(), |name| name.clone());
 
+                let width = monitor_info.rcMonitor.right - monitor_info.rcMonitor.left;
+                let height = monitor_info.rcMonitor.bottom - monitor_info.rcMonitor.top;
+
+                // desktop duplication captures in pixels, the monitor rect is scaled
+                // down by DPI unless the process is per-monitor DPI aware
+                let scale_factor = if width > 0 && monitor_resolution_width > 0 {
+                    (monitor_resolution_width as i32 * 100 / width) as u16
+                } else {
+                    100
+                };
+
                 displays.push(Monitor {
                     id: device_id,
                     name,
                     refresh_rate: (refresh_rate.min(u8::MAX as u32) as u8),
-                    width: (monitor_info.rcMonitor.right - monitor_info.rcMonitor.left) as u16,
-                    height: (monitor_info.rcMonitor.bottom - monitor_info.rcMonitor.top) as u16,
+                    width: width as u16,
+                    height: height as u16,
                     is_primary: monitor_is_primary,
                     screen_shot: screent_shot_buffer,
-                    left: monitor_info.rcMonitor.left as u16,
-                    top: monitor_info.rcMonitor.top as u16,
+                    left: monitor_info.rcMonitor.left,
+                    top: monitor_info.rcMonitor.top,
+                    scale_factor,
                 });
             }
         }
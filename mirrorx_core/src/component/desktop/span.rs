@@ -0,0 +1,330 @@
+use super::monitor::Monitor;
+use crate::{component::frame::DesktopEncodeFrame, core_error, error::CoreResult};
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+
+/// The virtual monitor id which stands for all active monitors stitched into one canvas.
+pub const SPAN_MONITOR_ID: &str = "span";
+
+// member monitors and their rects (left, top, width, height) in the stitched canvas,
+// used to translate input position from span canvas to the real monitor. The scale
+// factor of members is the one measured from their frames.
+static SPAN_LAYOUT: Lazy<RwLock<Vec<(Monitor, (f32, f32, f32, f32))>>> =
+    Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Build the virtual monitor which covers the bounds of all monitors.
+pub fn span_monitor(monitors: &[Monitor]) -> CoreResult<Monitor> {
+    let (left, top, right, bottom) = span_bounds(monitors)?;
+
+    Ok(Monitor {
+        id: SPAN_MONITOR_ID.to_string(),
+        name: String::from("All Monitors"),
+        refresh_rate: monitors
+            .iter()
+            .map(|monitor| monitor.refresh_rate)
+            .min()
+            .unwrap_or_default(),
+        width: (right - left) as u16,
+        height: (bottom - top) as u16,
+        is_primary: false,
+        screen_shot: None,
+        left,
+        top,
+        scale_factor: monitors
+            .iter()
+            .map(|monitor| monitor.scale_factor)
+            .max()
+            .unwrap_or(100),
+    })
+}
+
+// the member monitor which pointer located lastly, for the input without position
+static SPAN_POINTER_MONITOR: Lazy<RwLock<Option<Monitor>>> = Lazy::new(|| RwLock::new(None));
+
+/// Translate position in span canvas to the member monitor which contains it.
+pub fn resolve_span_position(x: f32, y: f32) -> Option<(Monitor, f32, f32)> {
+    let (monitor, x, y) = SPAN_LAYOUT
+        .read()
+        .unwrap()
+        .iter()
+        .find(|(_, (left, top, width, height))| {
+            *left <= x && x < *left + *width && *top <= y && y < *top + *height
+        })
+        .map(|(monitor, (left, top, _, _))| (monitor.clone(), x - *left, y - *top))?;
+
+    *SPAN_POINTER_MONITOR.write().unwrap() = Some(monitor.clone());
+
+    Some((monitor, x, y))
+}
+
+pub fn span_pointer_monitor() -> Option<Monitor> {
+    SPAN_POINTER_MONITOR.read().unwrap().clone()
+}
+
+fn span_bounds(monitors: &[Monitor]) -> CoreResult<(i32, i32, i32, i32)> {
+    if monitors.is_empty() {
+        return Err(core_error!("no active monitor"));
+    }
+
+    let left = monitors.iter().map(|monitor| monitor.left).min().unwrap();
+    let top = monitors.iter().map(|monitor| monitor.top).min().unwrap();
+    let right = monitors
+        .iter()
+        .map(|monitor| monitor.left + monitor.width as i32)
+        .max()
+        .unwrap();
+    let bottom = monitors
+        .iter()
+        .map(|monitor| monitor.top + monitor.height as i32)
+        .max()
+        .unwrap();
+
+    Ok((left, top, right, bottom))
+}
+
+/// Compose NV12 frames of multiple monitors into one frame by their layout.
+pub struct FrameStitcher {
+    monitors: Vec<Monitor>,
+    frames: Vec<Option<DesktopEncodeFrame>>,
+}
+
+impl FrameStitcher {
+    pub fn new(monitors: Vec<Monitor>) -> Self {
+        let frames = monitors.iter().map(|_| None).collect();
+        Self { monitors, frames }
+    }
+
+    /// Update the latest frame of monitor at index, returns stitched frame when every
+    /// monitor has at least one frame.
+    pub fn update(
+        &mut self,
+        index: usize,
+        frame: DesktopEncodeFrame,
+    ) -> CoreResult<Option<DesktopEncodeFrame>> {
+        if index >= self.frames.len() {
+            return Err(core_error!("monitor index out of range"));
+        }
+
+        let capture_time = frame.capture_time;
+        let color_space = frame.color_space;
+        self.frames[index] = Some(frame);
+
+        if self.frames.iter().any(|frame| frame.is_none()) {
+            return Ok(None);
+        }
+
+        let (left, top, right, bottom) = span_bounds(&self.monitors)?;
+
+        // monitor size is logical size but frame size may be physical size, and monitors
+        // may be scaled differently. The canvas takes the largest scale so every frame
+        // fits in the rect of its monitor and frames of neighbours never overlap.
+        let scales: Vec<f32> = self
+            .monitors
+            .iter()
+            .zip(self.frames.iter())
+            .map(|(monitor, frame)| match frame {
+                Some(frame) if monitor.width > 0 => frame.width as f32 / monitor.width as f32,
+                _ => 1.0,
+            })
+            .collect();
+
+        let scale = scales.iter().copied().reduce(f32::max).unwrap_or(1.0);
+
+        // NV12 requires even width and height
+        let width = (((right - left) as f32 * scale) as i32) & !1;
+        let height = (((bottom - top) as f32 * scale) as i32) & !1;
+
+        let mut luminance_bytes = vec![16u8; (width * height) as usize];
+        let mut chrominance_bytes = vec![128u8; (width * height / 2) as usize];
+        let mut layout = Vec::with_capacity(self.monitors.len());
+
+        for ((monitor, frame), monitor_scale) in
+            self.monitors.iter().zip(self.frames.iter()).zip(scales)
+        {
+            let Some(frame) = frame else {
+                continue;
+            };
+
+            let offset_x = (((monitor.left - left) as f32 * scale) as i32) & !1;
+            let offset_y = (((monitor.top - top) as f32 * scale) as i32) & !1;
+            let copy_width = frame.width.min(width - offset_x).max(0) as usize;
+            let copy_height = frame.height.min(height - offset_y).max(0);
+
+            for row in 0..copy_height {
+                let src = (row * frame.luminance_stride) as usize;
+                let dst = ((offset_y + row) * width + offset_x) as usize;
+                luminance_bytes[dst..dst + copy_width]
+                    .copy_from_slice(&frame.luminance_bytes[src..src + copy_width]);
+            }
+
+            // chrominance plane has half height and interleaved UV of half width
+            for row in 0..copy_height / 2 {
+                let src = (row * frame.chrominance_stride) as usize;
+                let dst = ((offset_y / 2 + row) * width + offset_x) as usize;
+                chrominance_bytes[dst..dst + copy_width]
+                    .copy_from_slice(&frame.chrominance_bytes[src..src + copy_width]);
+            }
+
+            layout.push((
+                Monitor {
+                    scale_factor: (monitor_scale * 100.0).round() as u16,
+                    ..monitor.clone()
+                },
+                (
+                    offset_x as f32,
+                    offset_y as f32,
+                    frame.width as f32,
+                    frame.height as f32,
+                ),
+            ));
+        }
+
+        *SPAN_LAYOUT.write().unwrap() = layout;
+
+        Ok(Some(DesktopEncodeFrame {
+            capture_time,
+            color_space,
+            width,
+            height,
+            luminance_bytes,
+            luminance_stride: width,
+            chrominance_bytes,
+            chrominance_stride: width,
+        }))
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub struct SpanDuplicator {
+    capture_frame_rx: std::sync::mpsc::Receiver<(usize, CoreResult<DesktopEncodeFrame>)>,
+    stitcher: FrameStitcher,
+}
+
+#[cfg(target_os = "windows")]
+impl SpanDuplicator {
+    pub fn new(monitors: Vec<Monitor>) -> CoreResult<(Self, String)> {
+        if monitors.is_empty() {
+            return Err(core_error!("no active monitor"));
+        }
+
+        let (capture_frame_tx, capture_frame_rx) = std::sync::mpsc::sync_channel(monitors.len());
+
+        // every monitor captures in its own thread because capture blocks until the
+        // monitor content changes, threads exit when the SpanDuplicator is dropped
+        for (index, monitor) in monitors.iter().enumerate() {
+            let monitor_id = monitor.id.clone();
+            let capture_frame_tx = capture_frame_tx.clone();
+
+            std::thread::spawn(move || {
+                let mut duplicator = match super::Duplicator::new(Some(monitor_id)) {
+                    Ok((duplicator, _)) => duplicator,
+                    Err(err) => {
+                        let _ = capture_frame_tx.send((index, Err(err)));
+                        return;
+                    }
+                };
+
+                loop {
+                    let capture_frame = duplicator.capture();
+                    let failed = capture_frame.is_err();
+                    if capture_frame_tx.send((index, capture_frame)).is_err() || failed {
+                        return;
+                    }
+                }
+            });
+        }
+
+        Ok((
+            SpanDuplicator {
+                capture_frame_rx,
+                stitcher: FrameStitcher::new(monitors),
+            },
+            SPAN_MONITOR_ID.to_string(),
+        ))
+    }
+
+    pub fn capture(&mut self) -> CoreResult<DesktopEncodeFrame> {
+        loop {
+            let (index, capture_frame) = self
+                .capture_frame_rx
+                .recv()
+                .map_err(|_| core_error!("span capture frame channel closed"))?;
+
+            if let Some(frame) = self.stitcher.update(index, capture_frame?)? {
+                return Ok(frame);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub struct SpanDuplicator {
+    duplicators: Vec<super::Duplicator>,
+}
+
+#[cfg(target_os = "macos")]
+impl SpanDuplicator {
+    pub fn new(
+        monitors: Vec<Monitor>,
+        capture_frame_tx: tokio::sync::mpsc::Sender<DesktopEncodeFrame>,
+    ) -> CoreResult<(Self, String)> {
+        if monitors.is_empty() {
+            return Err(core_error!("no active monitor"));
+        }
+
+        let (monitor_frame_tx, monitor_frame_rx) = std::sync::mpsc::sync_channel(monitors.len());
+        let mut duplicators = Vec::with_capacity(monitors.len());
+
+        for (index, monitor) in monitors.iter().enumerate() {
+            let (tx, mut rx) = tokio::sync::mpsc::channel(180);
+            let (duplicator, _) = super::Duplicator::new(Some(monitor.id.clone()), tx)?;
+            duplicators.push(duplicator);
+
+            let monitor_frame_tx = monitor_frame_tx.clone();
+            std::thread::spawn(move || {
+                while let Some(capture_frame) = rx.blocking_recv() {
+                    if monitor_frame_tx.send((index, capture_frame)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+
+        std::thread::spawn(move || {
+            let mut stitcher = FrameStitcher::new(monitors);
+
+            while let Ok((index, capture_frame)) = monitor_frame_rx.recv() {
+                match stitcher.update(index, capture_frame) {
+                    Ok(Some(frame)) => {
+                        if capture_frame_tx.blocking_send(frame).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        tracing::error!(?err, "stitch span frame failed");
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok((SpanDuplicator { duplicators }, SPAN_MONITOR_ID.to_string()))
+    }
+
+    pub fn start(&self) -> CoreResult<()> {
+        for duplicator in self.duplicators.iter() {
+            duplicator.start()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn stop(&self) -> CoreResult<()> {
+        for duplicator in self.duplicators.iter() {
+            duplicator.stop()?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,428 @@
+//! Synthetic capture source which draws scrolling color bars, a bouncing box and the
+//! frame number with the wall time instead of duplicating a monitor. It exercises
+//! capture, encoding, transport and decoding on machines without display or GPU, like
+//! headless CI runners and VMs, and keeps demos independent of the host desktop.
+//!
+//! It's enabled by the `test_pattern` setting or the `MIRRORX_TEST_PATTERN` environment
+//! variable, which overrides the setting. Both take `WIDTHxHEIGHT@FPS` like `1280x720@30`,
+//! the environment variable also takes `1` for the default pattern.
+
+use super::monitor::Monitor;
+use crate::component::{
+    frame::{ColorSpace, DesktopEncodeFrame},
+    video_encoder::watermark::{glyph, GLYPH_HEIGHT, GLYPH_WIDTH},
+};
+use once_cell::sync::Lazy;
+use std::{
+    fmt::Display,
+    str::FromStr,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+/// The virtual monitor id which stands for the test pattern.
+pub const TEST_PATTERN_MONITOR_ID: &str = "test_pattern";
+
+const TEST_PATTERN_ENV: &str = "MIRRORX_TEST_PATTERN";
+
+// Y, U and V of 100% color bars in BT.709 limited range, from left to right
+const BARS: [(u8, u8, u8); 8] = [
+    (235, 128, 128),
+    (219, 16, 138),
+    (188, 154, 16),
+    (173, 42, 26),
+    (78, 214, 230),
+    (63, 102, 240),
+    (32, 240, 118),
+    (16, 128, 128),
+];
+
+// bars scroll one frame width per cycle, the box bounces in both directions with
+// different periods so it doesn't repeat the same diagonal
+const BAR_CYCLE: Duration = Duration::from_secs(4);
+const BOX_CYCLE_X: Duration = Duration::from_secs(3);
+const BOX_CYCLE_Y: Duration = Duration::from_secs(2);
+
+const BOX_LUMINANCE: u8 = 126;
+const TEXT_LUMINANCE: u8 = 235;
+const BLACK_LUMINANCE: u8 = 16;
+const NEUTRAL_CHROMINANCE: u8 = 128;
+
+const MIN_WIDTH: u16 = 128;
+const MIN_HEIGHT: u16 = 72;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestPattern {
+    pub width: u16,
+    pub height: u16,
+    pub frame_rate: u8,
+}
+
+impl Default for TestPattern {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            frame_rate: 30,
+        }
+    }
+}
+
+impl Display for TestPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}@{}", self.width, self.height, self.frame_rate)
+    }
+}
+
+impl FromStr for TestPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (size, frame_rate) = match s.trim().split_once('@') {
+            Some((size, frame_rate)) => (
+                size,
+                frame_rate
+                    .parse()
+                    .map_err(|_| String::from("Invalid test pattern frame rate"))?,
+            ),
+            None => (s.trim(), TestPattern::default().frame_rate),
+        };
+
+        let (width, height) = size
+            .split_once('x')
+            .ok_or_else(|| String::from("Test pattern size isn't like 1280x720"))?;
+
+        let pattern = TestPattern {
+            width: width
+                .parse()
+                .map_err(|_| String::from("Invalid test pattern width"))?,
+            height: height
+                .parse()
+                .map_err(|_| String::from("Invalid test pattern height"))?,
+            frame_rate,
+        };
+
+        // NV12 requires even width and height, and the timestamp needs some room
+        if pattern.width < MIN_WIDTH
+            || pattern.height < MIN_HEIGHT
+            || pattern.width % 2 != 0
+            || pattern.height % 2 != 0
+        {
+            return Err(format!(
+                "Test pattern size must be even and at least {MIN_WIDTH}x{MIN_HEIGHT}"
+            ));
+        }
+
+        if pattern.frame_rate == 0 {
+            return Err(String::from("Test pattern frame rate must be positive"));
+        }
+
+        Ok(pattern)
+    }
+}
+
+static CONFIGURED_TEST_PATTERN: Lazy<RwLock<Option<TestPattern>>> = Lazy::new(|| RwLock::new(None));
+
+static ENV_TEST_PATTERN: Lazy<Option<TestPattern>> = Lazy::new(|| {
+    let value = std::env::var(TEST_PATTERN_ENV).ok()?;
+    if value.trim() == "1" {
+        return Some(TestPattern::default());
+    }
+
+    match value.parse() {
+        Ok(pattern) => Some(pattern),
+        Err(err) => {
+            tracing::warn!(?value, ?err, "invalid test pattern environment variable");
+            None
+        }
+    }
+});
+
+/// Set the test pattern which sessions negotiated from now on stream in place of the
+/// primary monitor, None streams the real monitors.
+pub fn set_test_pattern(pattern: Option<TestPattern>) {
+    *CONFIGURED_TEST_PATTERN.write().unwrap() = pattern;
+}
+
+/// The test pattern in effect, the environment variable overrides the setting.
+pub fn test_pattern() -> Option<TestPattern> {
+    ENV_TEST_PATTERN.or(*CONFIGURED_TEST_PATTERN.read().unwrap())
+}
+
+/// Build the virtual monitor which the test pattern is drawn on.
+pub fn test_pattern_monitor(pattern: &TestPattern) -> Monitor {
+    Monitor {
+        id: TEST_PATTERN_MONITOR_ID.to_string(),
+        name: String::from("Test Pattern"),
+        refresh_rate: pattern.frame_rate,
+        width: pattern.width,
+        height: pattern.height,
+        is_primary: true,
+        screen_shot: None,
+        left: 0,
+        top: 0,
+        scale_factor: 100,
+    }
+}
+
+/// Draw NV12 frames of the test pattern, paced at its frame rate.
+pub struct TestPatternGenerator {
+    pattern: TestPattern,
+    epoch: Instant,
+    frame_index: u64,
+}
+
+impl TestPatternGenerator {
+    pub fn new(pattern: TestPattern) -> Self {
+        Self {
+            pattern,
+            epoch: Instant::now(),
+            frame_index: 0,
+        }
+    }
+
+    /// Block until the next frame is due and draw it, frames which are already late are
+    /// skipped rather than drawn in a burst.
+    pub fn next_frame(&mut self) -> DesktopEncodeFrame {
+        let frame_rate = self.pattern.frame_rate as f64;
+        let due = Duration::from_secs_f64(self.frame_index as f64 / frame_rate);
+        let elapsed = self.epoch.elapsed();
+
+        if let Some(wait) = due.checked_sub(elapsed) {
+            std::thread::sleep(wait);
+        } else {
+            self.frame_index = self
+                .frame_index
+                .max((elapsed.as_secs_f64() * frame_rate) as u64);
+        }
+
+        let capture_time = self.epoch.elapsed();
+        let frame = self.draw(capture_time);
+        self.frame_index += 1;
+        frame
+    }
+
+    /// Draw the frame at `capture_time` right away, for the callers which pace frames by
+    /// themselves like benchmarks.
+    pub fn draw(&self, capture_time: Duration) -> DesktopEncodeFrame {
+        let width = self.pattern.width as usize;
+        let height = self.pattern.height as usize;
+
+        let mut luminance_bytes = vec![0u8; width * height];
+        let mut chrominance_bytes = vec![0u8; width * height / 2];
+
+        // bars are the same on every row, draw one row and copy it down
+        let shift = (cycle_progress(capture_time, BAR_CYCLE) * width as f64) as usize;
+        let mut luminance_row = vec![0u8; width];
+        let mut chrominance_row = vec![0u8; width];
+
+        for x in 0..width {
+            let (y, u, v) = BARS[((x + shift) % width) * BARS.len() / width];
+            luminance_row[x] = y;
+
+            // interleaved UV of every 2x2 block takes the color of its left pixels
+            if x % 2 == 0 {
+                chrominance_row[x] = u;
+                chrominance_row[x + 1] = v;
+            }
+        }
+
+        for row in luminance_bytes.chunks_exact_mut(width) {
+            row.copy_from_slice(&luminance_row);
+        }
+
+        for row in chrominance_bytes.chunks_exact_mut(width) {
+            row.copy_from_slice(&chrominance_row);
+        }
+
+        // about the height of small UI text on 1080p, narrow patterns keep the timestamp
+        // inside the frame
+        let scale = (height / 180).min(width / 80).max(1);
+        let band_height = ((2 * (GLYPH_HEIGHT + 2) + 2) * scale + 1) & !1;
+        let canvas_height = height - band_height;
+
+        let box_size = (canvas_height / 4) & !1;
+        let box_left = (bounce(capture_time, BOX_CYCLE_X) * (width - box_size) as f64) as usize;
+        let box_top =
+            (bounce(capture_time, BOX_CYCLE_Y) * (canvas_height - box_size) as f64) as usize;
+
+        fill(
+            &mut luminance_bytes,
+            &mut chrominance_bytes,
+            width,
+            (box_left & !1, box_top & !1, box_size, box_size),
+            BOX_LUMINANCE,
+        );
+
+        fill(
+            &mut luminance_bytes,
+            &mut chrominance_bytes,
+            width,
+            (0, canvas_height, width, band_height),
+            BLACK_LUMINANCE,
+        );
+
+        // wall time rather than capture time, so the latency shows up by comparing it with
+        // the clock of viewer
+        let lines = [
+            format!("{:010}", self.frame_index),
+            chrono::Local::now().format("%H:%M:%S%.3f").to_string(),
+        ];
+
+        for (line_index, line) in lines.iter().enumerate() {
+            let top = canvas_height + (1 + line_index * (GLYPH_HEIGHT + 2)) * scale;
+
+            for (char_index, c) in line.chars().enumerate() {
+                let left = (2 + char_index * (GLYPH_WIDTH + 1)) * scale;
+                draw_glyph(&mut luminance_bytes, width, left, top, scale, c);
+            }
+        }
+
+        DesktopEncodeFrame {
+            capture_time,
+            color_space: ColorSpace::default(),
+            width: width as i32,
+            height: height as i32,
+            luminance_bytes,
+            luminance_stride: width as i32,
+            chrominance_bytes,
+            chrominance_stride: width as i32,
+        }
+    }
+}
+
+// fraction of the current cycle, in [0, 1)
+fn cycle_progress(time: Duration, cycle: Duration) -> f64 {
+    (time.as_secs_f64() / cycle.as_secs_f64()).fract()
+}
+
+// goes from 0 to 1 and back in a cycle
+fn bounce(time: Duration, cycle: Duration) -> f64 {
+    let progress = cycle_progress(time, cycle) * 2.0;
+    if progress < 1.0 {
+        progress
+    } else {
+        2.0 - progress
+    }
+}
+
+// fill the rect (left, top, width, height) with neutral gray of the luminance, left and
+// top must be even
+fn fill(
+    luminance_bytes: &mut [u8],
+    chrominance_bytes: &mut [u8],
+    stride: usize,
+    (left, top, width, height): (usize, usize, usize, usize),
+    luminance: u8,
+) {
+    for y in top..top + height {
+        luminance_bytes[y * stride + left..y * stride + left + width].fill(luminance);
+    }
+
+    for y in top / 2..(top + height) / 2 {
+        chrominance_bytes[y * stride + left..y * stride + left + width].fill(NEUTRAL_CHROMINANCE);
+    }
+}
+
+fn draw_glyph(
+    luminance_bytes: &mut [u8],
+    stride: usize,
+    left: usize,
+    top: usize,
+    scale: usize,
+    c: char,
+) {
+    for (glyph_row, bits) in glyph(c).iter().enumerate() {
+        for glyph_column in 0..GLYPH_WIDTH {
+            if bits & (1 << (GLYPH_WIDTH - 1 - glyph_column)) == 0 {
+                continue;
+            }
+
+            let x = left + glyph_column * scale;
+            let y = top + glyph_row * scale;
+
+            for y in y..y + scale {
+                luminance_bytes[y * stride + x..y * stride + x + scale].fill(TEXT_LUMINANCE);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub struct TestPatternDuplicator {
+    generator: TestPatternGenerator,
+}
+
+#[cfg(target_os = "windows")]
+impl TestPatternDuplicator {
+    pub fn new(pattern: TestPattern) -> (Self, String) {
+        (
+            TestPatternDuplicator {
+                generator: TestPatternGenerator::new(pattern),
+            },
+            TEST_PATTERN_MONITOR_ID.to_string(),
+        )
+    }
+
+    pub fn capture(&mut self) -> crate::error::CoreResult<DesktopEncodeFrame> {
+        Ok(self.generator.next_frame())
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub struct TestPatternDuplicator {
+    pattern: TestPattern,
+    capture_frame_tx: tokio::sync::mpsc::Sender<DesktopEncodeFrame>,
+    running: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+#[cfg(target_os = "macos")]
+impl TestPatternDuplicator {
+    pub fn new(
+        pattern: TestPattern,
+        capture_frame_tx: tokio::sync::mpsc::Sender<DesktopEncodeFrame>,
+    ) -> (Self, String) {
+        (
+            TestPatternDuplicator {
+                pattern,
+                capture_frame_tx,
+                running: Default::default(),
+            },
+            TEST_PATTERN_MONITOR_ID.to_string(),
+        )
+    }
+
+    pub fn start(&self) -> crate::error::CoreResult<()> {
+        use std::sync::atomic::Ordering;
+
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let pattern = self.pattern;
+        let capture_frame_tx = self.capture_frame_tx.clone();
+        let running = self.running.clone();
+
+        // the thread exits once stopped or the capture frame receiver is dropped
+        std::thread::spawn(move || {
+            let mut generator = TestPatternGenerator::new(pattern);
+
+            while running.load(Ordering::SeqCst) {
+                if capture_frame_tx
+                    .blocking_send(generator.next_frame())
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn stop(&self) -> crate::error::CoreResult<()> {
+        self.running
+            .store(false, std::sync::atomic::Ordering::SeqCst);
+        Ok(())
+    }
+}
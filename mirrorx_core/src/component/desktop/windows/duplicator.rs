@@ -1,16 +1,25 @@
 use super::{
     dx_math::{VERTEX_STRIDES, VERTICES},
+    graphics_capture::{GraphicsCapture, NextFrame},
     shader,
     util::{init_directx, prepare_desktop},
 };
 use crate::{
-    component::{desktop::windows::dx_math::Vertex, frame::DesktopEncodeFrame},
+    api::endpoint::message::{CaptureInterruption, EndPointCursor, EndPointCursorShape},
+    component::{
+        desktop::windows::dx_math::Vertex,
+        frame::{ColorPrimaries, ColorRange, ColorSpace, DesktopEncodeFrame},
+    },
     core_error,
     error::{CoreError, CoreResult},
     HRESULT,
 };
 use scopeguard::defer;
-use std::os::raw::c_void;
+use std::{
+    cell::Cell,
+    os::raw::c_void,
+    time::{Duration, Instant},
+};
 use tracing::info;
 use windows::{
     core::{Interface, PCSTR, PCWSTR},
@@ -26,6 +35,24 @@ use windows::{
     },
 };
 
+// pixel shaders convert desktop texture (sRGB) to limited range BT.709
+const FRAME_COLOR_SPACE: ColorSpace = ColorSpace {
+    primaries: ColorPrimaries::Bt709,
+    range: ColorRange::Limited,
+};
+
+// how often desktop duplication is tried again while the fallback captures the monitor
+const DUPLICATION_PROBE_INTERVAL: Duration = Duration::from_secs(2);
+
+const GRAPHICS_CAPTURE_FRAME_TIMEOUT: Duration = Duration::from_millis(500);
+
+enum CaptureSource {
+    Duplication(IDXGIOutputDuplication),
+    // desktop duplication is unavailable, like when a fullscreen exclusive application
+    // holds the output
+    GraphicsCapture(GraphicsCapture),
+}
+
 pub struct Duplicator {
     device: ID3D11Device,
     device_context: ID3D11DeviceContext,
@@ -37,8 +64,11 @@ pub struct Duplicator {
     pixel_shader_luminance: ID3D11PixelShader,
     pixel_shader_chrominance: ID3D11PixelShader,
 
-    duplication: IDXGIOutputDuplication,
+    monitor_id: String,
+    source: CaptureSource,
     dxgi_outdupl_desc: DXGI_OUTDUPL_DESC,
+    protected_content: bool,
+    duplication_probe_time: Cell<Instant>,
 
     backend_texture: ID3D11Texture2D,
     backend_viewport: [D3D11_VIEWPORT; 1],
@@ -63,6 +93,7 @@ pub struct Duplicator {
     mouse_visible: bool,
     mouse_shape_buffer: Vec<u8>,
     mouse_shape_info: DXGI_OUTDUPL_POINTER_SHAPE_INFO,
+    cursor_tx: Option<tokio::sync::mpsc::Sender<EndPointCursor>>,
 
     epoch: once_cell::unsync::OnceCell<std::time::Instant>,
 }
@@ -84,10 +115,37 @@ impl Duplicator {
                 pixel_shader_chrominance,
             ) = init_shaders(&device)?;
 
-            let (duplication, monitor_id) = init_output_duplication(&device, monitor_id)?;
+            let (dxgi_output, monitor_id) = find_output(&device, monitor_id)?;
 
-            let mut dxgi_outdupl_desc = std::mem::zeroed();
-            duplication.GetDesc(&mut dxgi_outdupl_desc);
+            let (source, dxgi_outdupl_desc) = match dxgi_output.DuplicateOutput(&device) {
+                Ok(duplication) => {
+                    let mut dxgi_outdupl_desc = std::mem::zeroed();
+                    duplication.GetDesc(&mut dxgi_outdupl_desc);
+                    (CaptureSource::Duplication(duplication), dxgi_outdupl_desc)
+                }
+                Err(err) if is_duplication_unavailable(&err) => {
+                    tracing::warn!(
+                        ?err,
+                        ?monitor_id,
+                        "desktop duplication is unavailable, fall back to graphics capture"
+                    );
+
+                    let dxgi_output_desc = HRESULT!(dxgi_output.GetDesc());
+                    let graphics_capture = GraphicsCapture::new(&device, dxgi_output_desc.Monitor)?;
+                    let dxgi_outdupl_desc = graphics_capture_desc(&graphics_capture);
+                    (
+                        CaptureSource::GraphicsCapture(graphics_capture),
+                        dxgi_outdupl_desc,
+                    )
+                }
+                Err(err) => {
+                    return Err(CoreError::HResultError {
+                        error: err,
+                        file: file!().to_string(),
+                        line: line!().to_string(),
+                    })
+                }
+            };
 
             let (backend_texture, backend_rtv, backend_viewport) =
                 init_backend_resources(&device, &dxgi_outdupl_desc)?;
@@ -119,8 +177,11 @@ impl Duplicator {
                     pixel_shader,
                     pixel_shader_luminance: pixel_shader_lumina,
                     pixel_shader_chrominance,
-                    duplication,
+                    monitor_id: monitor_id.clone(),
+                    source,
                     dxgi_outdupl_desc,
+                    protected_content: false,
+                    duplication_probe_time: Cell::new(Instant::now()),
                     backend_texture,
                     backend_viewport: [backend_viewport],
                     backend_rtv: [Some(backend_rtv)],
@@ -140,6 +201,7 @@ impl Duplicator {
                     mouse_visible: false,
                     mouse_shape_buffer: Vec::new(),
                     mouse_shape_info: std::mem::zeroed(),
+                    cursor_tx: None,
                     epoch: once_cell::unsync::OnceCell::new(),
                 },
                 monitor_id,
@@ -147,19 +209,46 @@ impl Duplicator {
         }
     }
 
+    /// Whether the duplication is lost, like when the input desktop is switched, and the
+    /// duplicator should be created again.
+    pub fn is_access_lost(err: &CoreError) -> bool {
+        matches!(
+            err,
+            CoreError::HResultError { error, .. } if error.code() == DXGI_ERROR_ACCESS_LOST
+        )
+    }
+
+    /// Why the monitor isn't captured as usual, it's updated with every captured frame.
+    pub fn capture_interruption(&self) -> Option<CaptureInterruption> {
+        if self.protected_content {
+            Some(CaptureInterruption::ContentProtected)
+        } else if matches!(self.source, CaptureSource::GraphicsCapture(_)) {
+            Some(CaptureInterruption::FallbackCapture)
+        } else {
+            None
+        }
+    }
+
+    /// Send cursor to the channel instead of compositing it into desktop frame
+    /// when `cursor_tx` is Some.
+    pub fn set_cursor_tx(&mut self, cursor_tx: Option<tokio::sync::mpsc::Sender<EndPointCursor>>) {
+        self.cursor_tx = cursor_tx;
+    }
+
+    /// Width, height and color space of the captured frames.
+    pub fn frame_format(&self) -> (i32, i32, ColorSpace) {
+        (
+            self.dxgi_outdupl_desc.ModeDesc.Width as i32,
+            self.dxgi_outdupl_desc.ModeDesc.Height as i32,
+            FRAME_COLOR_SPACE,
+        )
+    }
+
     pub fn capture(&mut self) -> CoreResult<DesktopEncodeFrame> {
         unsafe {
             if let Err(err) = self.acquire_frame() {
-                if let CoreError::HResultError {
-                    ref error,
-                    file: _,
-                    line: _,
-                } = err
-                {
-                    if error.code() == DXGI_ERROR_ACCESS_LOST {
-                        // todo: re-init dxig
-                        tracing::warn!("DXGI ACCESS LOST");
-                    }
+                if Duplicator::is_access_lost(&err) {
+                    tracing::warn!("DXGI ACCESS LOST");
                 }
                 return Err(err);
             }
@@ -170,38 +259,87 @@ impl Duplicator {
     }
 
     unsafe fn acquire_frame(&mut self) -> CoreResult<()> {
+        let duplication = match &self.source {
+            CaptureSource::Duplication(duplication) => duplication.clone(),
+            CaptureSource::GraphicsCapture(graphics_capture) => {
+                return self.acquire_graphics_capture_frame(graphics_capture);
+            }
+        };
+
         let mut dxgi_resource = None;
         let mut dxgi_outdupl_frame_info = std::mem::zeroed();
 
         loop {
-            HRESULT!(self.duplication.AcquireNextFrame(
+            HRESULT!(duplication.AcquireNextFrame(
                 INFINITE,
                 &mut dxgi_outdupl_frame_info,
                 &mut dxgi_resource,
             ));
 
-            self.update_mouse(&dxgi_outdupl_frame_info)?;
+            self.update_mouse(&duplication, &dxgi_outdupl_frame_info)?;
 
             if dxgi_outdupl_frame_info.LastPresentTime == 0 {
-                HRESULT!(self.duplication.ReleaseFrame());
+                HRESULT!(duplication.ReleaseFrame());
                 continue;
             }
 
             break;
         }
 
+        // DRM content is blacked out of the frame by the system, tell viewer why
+        self.protected_content = dxgi_outdupl_frame_info.ProtectedContentMaskedOut.as_bool();
+
         if let Some(resource) = dxgi_resource {
             let desktop_texture: ID3D11Texture2D = HRESULT!(resource.cast());
 
             self.device_context
                 .CopyResource(&self.backend_texture, &desktop_texture);
 
-            if self.mouse_visible {
+            if self.mouse_visible && self.cursor_tx.is_none() {
                 self.draw_mouse()?;
             }
         }
 
-        HRESULT!(self.duplication.ReleaseFrame());
+        HRESULT!(duplication.ReleaseFrame());
+        Ok(())
+    }
+
+    unsafe fn acquire_graphics_capture_frame(
+        &self,
+        graphics_capture: &GraphicsCapture,
+    ) -> CoreResult<()> {
+        loop {
+            // desktop duplication comes back once the fullscreen application exits, the
+            // duplicator is created again by the same way as access lost
+            if self.duplication_probe_time.get().elapsed() >= DUPLICATION_PROBE_INTERVAL {
+                self.duplication_probe_time.set(Instant::now());
+
+                if self.probe_duplication().is_ok() {
+                    tracing::info!(
+                        monitor_id = ?self.monitor_id,
+                        "desktop duplication is available again"
+                    );
+                    return Err(access_lost_error());
+                }
+            }
+
+            match graphics_capture.copy_next_frame(
+                &self.device_context,
+                &self.backend_texture,
+                GRAPHICS_CAPTURE_FRAME_TIMEOUT,
+            )? {
+                NextFrame::Copied => return Ok(()),
+                NextFrame::Timeout => continue,
+                NextFrame::Resized => return Err(access_lost_error()),
+            }
+        }
+    }
+
+    unsafe fn probe_duplication(&self) -> CoreResult<()> {
+        let (dxgi_output, _) = find_output(&self.device, Some(self.monitor_id.clone()))?;
+
+        // the probe is released at once, the duplicator created again takes the output
+        HRESULT!(dxgi_output.DuplicateOutput(&self.device));
         Ok(())
     }
 
@@ -327,6 +465,7 @@ impl Duplicator {
 
         Ok(DesktopEncodeFrame {
             capture_time,
+            color_space: FRAME_COLOR_SPACE,
             width: self.dxgi_outdupl_desc.ModeDesc.Width as i32,
             height: self.dxgi_outdupl_desc.ModeDesc.Height as i32,
             luminance_bytes,
@@ -338,6 +477,7 @@ impl Duplicator {
 
     unsafe fn update_mouse(
         &mut self,
+        duplication: &IDXGIOutputDuplication,
         desktop_frame_info: &DXGI_OUTDUPL_FRAME_INFO,
     ) -> CoreResult<()> {
         if desktop_frame_info.LastMouseUpdateTime == 0 {
@@ -365,6 +505,7 @@ impl Duplicator {
         }
 
         if desktop_frame_info.PointerShapeBufferSize == 0 {
+            self.send_cursor(false);
             return Ok(());
         }
 
@@ -375,16 +516,40 @@ impl Duplicator {
         }
 
         let mut buffer_size_required = 0;
-        HRESULT!(self.duplication.GetFramePointerShape(
+        HRESULT!(duplication.GetFramePointerShape(
             desktop_frame_info.PointerShapeBufferSize,
             self.mouse_shape_buffer.as_mut_ptr() as *mut _,
             &mut buffer_size_required,
             &mut self.mouse_shape_info,
         ));
 
+        self.send_cursor(true);
+
         Ok(())
     }
 
+    fn send_cursor(&self, shape_updated: bool) {
+        let Some(ref cursor_tx) = self.cursor_tx else {
+            return;
+        };
+
+        let cursor = EndPointCursor {
+            visible: self.mouse_visible,
+            x: self.mouse_position_x + self.mouse_shape_info.HotSpot.x,
+            y: self.mouse_position_y + self.mouse_shape_info.HotSpot.y,
+            shape: if shape_updated {
+                convert_pointer_shape(&self.mouse_shape_info, &self.mouse_shape_buffer)
+            } else {
+                None
+            },
+        };
+
+        // cursor updates are frequent, drop it when the channel is busy rather than block capture
+        if cursor_tx.try_send(cursor).is_err() {
+            tracing::warn!("cursor channel is full or closed");
+        }
+    }
+
     unsafe fn draw_mouse(&mut self) -> CoreResult<()> {
         let mut full_desc: D3D11_TEXTURE2D_DESC = std::mem::zeroed();
         self.backend_texture.GetDesc(&mut full_desc);
@@ -422,7 +587,7 @@ impl Duplicator {
             .MostDetailedMip = pointer_texture_desc.MipLevels - 1;
         shader_resource_view_desc.Anonymous.Texture2D.MipLevels = pointer_texture_desc.MipLevels;
 
-        let mut init_buffer = std::ptr::null();
+        let mut mask_buffer = Vec::new();
 
         match DXGI_OUTDUPL_POINTER_SHAPE_TYPE(self.mouse_shape_info.Type as i32) {
             DXGI_OUTDUPL_POINTER_SHAPE_TYPE_COLOR => {
@@ -438,8 +603,6 @@ impl Duplicator {
                     self.mouse_shape_buffer
                 );
 
-                std::fs::write(r"F:\ddd_image", &self.mouse_shape_buffer).unwrap();
-
                 pointer_left = self.mouse_position_x;
                 pointer_top = self.mouse_position_y;
                 pointer_width = self.mouse_shape_info.Width as i32;
@@ -450,7 +613,7 @@ impl Duplicator {
                     "DXGI_OUTDUPL_POINTER_SHAPE_INFO: DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MONOCHROME"
                 );
 
-                let buffer = self.process_mono_mask(
+                mask_buffer = self.process_mono_mask(
                     true,
                     &full_desc,
                     &mut pointer_width,
@@ -459,15 +622,13 @@ impl Duplicator {
                     &mut pointer_top,
                     &mut pointer_box,
                 )?;
-
-                init_buffer = buffer.as_ptr()
             }
             DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MASKED_COLOR => {
                 tracing::trace!(
                     "DXGI_OUTDUPL_POINTER_SHAPE_INFO: DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MASKED_COLOR"
                 );
 
-                let buffer = self.process_mono_mask(
+                mask_buffer = self.process_mono_mask(
                     false,
                     &full_desc,
                     &mut pointer_width,
@@ -476,8 +637,6 @@ impl Duplicator {
                     &mut pointer_top,
                     &mut pointer_box,
                 )?;
-
-                init_buffer = buffer.as_ptr()
             }
             _ => {
                 tracing::error!(
@@ -522,7 +681,7 @@ impl Duplicator {
             if self.mouse_shape_info.Type & (DXGI_OUTDUPL_POINTER_SHAPE_TYPE_COLOR.0 as u32) != 0 {
                 self.mouse_shape_buffer.as_ptr() as *const _
             } else {
-                init_buffer as *const _
+                mask_buffer.as_ptr() as *const _
             };
         init_data.SysMemPitch =
             if self.mouse_shape_info.Type & (DXGI_OUTDUPL_POINTER_SHAPE_TYPE_COLOR.0 as u32) != 0 {
@@ -753,10 +912,34 @@ impl Duplicator {
     }
 }
 
-unsafe fn init_output_duplication(
+// desktop duplication can't work on the output now but the monitor is still capturable
+fn is_duplication_unavailable(err: &windows::core::Error) -> bool {
+    err.code() == DXGI_ERROR_UNSUPPORTED || err.code() == DXGI_ERROR_NOT_CURRENTLY_AVAILABLE
+}
+
+fn access_lost_error() -> CoreError {
+    CoreError::HResultError {
+        error: windows::core::Error::from(DXGI_ERROR_ACCESS_LOST),
+        file: file!().to_string(),
+        line: line!().to_string(),
+    }
+}
+
+// textures of the fallback are created as if its frames come from desktop duplication
+fn graphics_capture_desc(graphics_capture: &GraphicsCapture) -> DXGI_OUTDUPL_DESC {
+    let (width, height) = graphics_capture.size();
+
+    let mut dxgi_outdupl_desc: DXGI_OUTDUPL_DESC = unsafe { std::mem::zeroed() };
+    dxgi_outdupl_desc.ModeDesc.Width = width;
+    dxgi_outdupl_desc.ModeDesc.Height = height;
+    dxgi_outdupl_desc.ModeDesc.Format = DXGI_FORMAT_B8G8R8A8_UNORM;
+    dxgi_outdupl_desc
+}
+
+unsafe fn find_output(
     device: &ID3D11Device,
     monitor_id: Option<String>,
-) -> CoreResult<(IDXGIOutputDuplication, String)> {
+) -> CoreResult<(IDXGIOutput1, String)> {
     let dxgi_device: IDXGIDevice = HRESULT!(device.cast());
 
     let dxgi_adapter = HRESULT!(dxgi_device.GetParent::<IDXGIAdapter>());
@@ -811,15 +994,13 @@ unsafe fn init_output_duplication(
 
                 let dxgi_output1: IDXGIOutput1 = HRESULT!(dxgi_output.cast());
 
-                let dxgi_output_duplication = HRESULT!(dxgi_output1.DuplicateOutput(device));
-
-                return Ok((dxgi_output_duplication, device_id));
+                return Ok((dxgi_output1, device_id));
             }
         }
     }
 
     Err(core_error!(
-        "create IDXGIOutputDuplication failed, all Outputs had tried"
+        "can't find DXGI output of monitor, all Outputs had tried"
     ))
 }
 
@@ -1047,3 +1228,83 @@ unsafe fn init_input_layout(device: &ID3D11Device) -> CoreResult<ID3D11InputLayo
 
     Ok(input_layout)
 }
+
+// convert pointer shape of DXGI to RGBA, pixels which invert the screen can't be
+// reproduced without the screen content, so they are drawn as black
+fn convert_pointer_shape(
+    shape_info: &DXGI_OUTDUPL_POINTER_SHAPE_INFO,
+    shape_buffer: &[u8],
+) -> Option<EndPointCursorShape> {
+    let shape_type = DXGI_OUTDUPL_POINTER_SHAPE_TYPE(shape_info.Type as i32);
+    let width = shape_info.Width as usize;
+    let pitch = shape_info.Pitch as usize;
+    let height = if shape_type == DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MONOCHROME {
+        // AND mask and XOR mask are stacked vertically
+        shape_info.Height as usize / 2
+    } else {
+        shape_info.Height as usize
+    };
+
+    let required_len = match shape_type {
+        DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MONOCHROME => height * 2 * pitch,
+        _ => height * pitch,
+    };
+
+    if width == 0 || height == 0 || shape_buffer.len() < required_len {
+        tracing::error!(?width, ?height, "pointer shape buffer is invalid");
+        return None;
+    }
+
+    let mut rgba = vec![0u8; width * height * 4];
+
+    for row in 0..height {
+        for col in 0..width {
+            let pixel = match shape_type {
+                DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MONOCHROME => {
+                    let mask = 0x80 >> (col % 8);
+                    let and_bit = shape_buffer[row * pitch + col / 8] & mask != 0;
+                    let xor_bit = shape_buffer[(row + height) * pitch + col / 8] & mask != 0;
+
+                    match (and_bit, xor_bit) {
+                        (true, false) => [0, 0, 0, 0],
+                        (false, true) => [255, 255, 255, 255],
+                        _ => [0, 0, 0, 255],
+                    }
+                }
+                DXGI_OUTDUPL_POINTER_SHAPE_TYPE_COLOR => {
+                    let bgra = &shape_buffer[row * pitch + col * 4..][..4];
+                    [bgra[2], bgra[1], bgra[0], bgra[3]]
+                }
+                DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MASKED_COLOR => {
+                    let bgra = &shape_buffer[row * pitch + col * 4..][..4];
+
+                    // zero mask replaces the screen pixel, otherwise XOR with it
+                    let alpha = if bgra[3] == 0 || bgra[..3] != [0, 0, 0] {
+                        255
+                    } else {
+                        0
+                    };
+
+                    [bgra[2], bgra[1], bgra[0], alpha]
+                }
+                _ => {
+                    tracing::error!(
+                        shape_type = shape_info.Type,
+                        "pointer shape type is unknown"
+                    );
+                    return None;
+                }
+            };
+
+            rgba[(row * width + col) * 4..][..4].copy_from_slice(&pixel);
+        }
+    }
+
+    Some(EndPointCursorShape {
+        width: width as u32,
+        height: height as u32,
+        hotspot_x: shape_info.HotSpot.x,
+        hotspot_y: shape_info.HotSpot.y,
+        rgba,
+    })
+}
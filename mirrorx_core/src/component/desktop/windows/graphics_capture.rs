@@ -0,0 +1,146 @@
+//! Windows.Graphics.Capture keeps capturing the monitor when desktop duplication is
+//! unavailable, like when a fullscreen exclusive application holds the output. It's
+//! slower than desktop duplication and always composites the cursor, so it's only used
+//! as the fallback.
+
+use crate::{core_error, error::CoreResult, HRESULT};
+use scopeguard::defer;
+use std::{
+    sync::mpsc::{sync_channel, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+use windows::{
+    core::{factory, IInspectable, Interface},
+    Foundation::TypedEventHandler,
+    Graphics::{
+        Capture::{Direct3D11CaptureFramePool, GraphicsCaptureItem, GraphicsCaptureSession},
+        DirectX::{Direct3D11::IDirect3DDevice, DirectXPixelFormat},
+        SizeInt32,
+    },
+    Win32::{
+        Graphics::{
+            Direct3D11::{ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D},
+            Dxgi::IDXGIDevice,
+            Gdi::HMONITOR,
+        },
+        System::WinRT::{
+            Direct3D11::{CreateDirect3D11DeviceFromDXGIDevice, IDirect3DDxgiInterfaceAccess},
+            Graphics::Capture::IGraphicsCaptureItemInterop,
+            RoInitialize, RO_INIT_MULTITHREADED,
+        },
+    },
+};
+
+pub enum NextFrame {
+    Copied,
+    /// No frame arrives before timeout, the content of monitor isn't changed.
+    Timeout,
+    /// The size of content is changed, like when the resolution of monitor is changed,
+    /// the capture should be created again.
+    Resized,
+}
+
+pub struct GraphicsCapture {
+    frame_pool: Direct3D11CaptureFramePool,
+    session: GraphicsCaptureSession,
+    size: SizeInt32,
+    frame_rx: Receiver<()>,
+}
+
+impl GraphicsCapture {
+    pub unsafe fn new(device: &ID3D11Device, monitor: HMONITOR) -> CoreResult<Self> {
+        // the error of thread which has been initialized in another mode is expected
+        let _ = RoInitialize(RO_INIT_MULTITHREADED);
+
+        if !HRESULT!(GraphicsCaptureSession::IsSupported()) {
+            return Err(core_error!("Windows.Graphics.Capture is not supported"));
+        }
+
+        let interop = HRESULT!(factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>());
+        let item: GraphicsCaptureItem = HRESULT!(interop.CreateForMonitor(monitor));
+        let size = HRESULT!(item.Size());
+
+        let dxgi_device: IDXGIDevice = HRESULT!(device.cast());
+        let inspectable = HRESULT!(CreateDirect3D11DeviceFromDXGIDevice(&dxgi_device));
+        let direct3d_device: IDirect3DDevice = HRESULT!(inspectable.cast());
+
+        // frames arrive on the worker thread of free threaded pool, the capture thread is
+        // notified and takes them itself
+        let frame_pool = HRESULT!(Direct3D11CaptureFramePool::CreateFreeThreaded(
+            &direct3d_device,
+            DirectXPixelFormat::B8G8R8A8UIntNormalized,
+            2,
+            size,
+        ));
+
+        let (frame_tx, frame_rx) = sync_channel(1);
+        HRESULT!(frame_pool.FrameArrived(&TypedEventHandler::<
+            Direct3D11CaptureFramePool,
+            IInspectable,
+        >::new(move |_, _| {
+            // one pending notification covers the frames arriving meanwhile
+            let _ = frame_tx.try_send(());
+            Ok(())
+        })));
+
+        let session = HRESULT!(frame_pool.CreateCaptureSession(&item));
+        HRESULT!(session.StartCapture());
+
+        Ok(GraphicsCapture {
+            frame_pool,
+            session,
+            size,
+            frame_rx,
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.size.Width as u32, self.size.Height as u32)
+    }
+
+    /// Wait for the next frame and copy it into the texture of the same size and the
+    /// B8G8R8A8 format.
+    pub unsafe fn copy_next_frame(
+        &self,
+        device_context: &ID3D11DeviceContext,
+        texture: &ID3D11Texture2D,
+        timeout: Duration,
+    ) -> CoreResult<NextFrame> {
+        match self.frame_rx.recv_timeout(timeout) {
+            Ok(()) => {}
+            Err(RecvTimeoutError::Timeout) => return Ok(NextFrame::Timeout),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(core_error!("graphics capture frame pool is closed"))
+            }
+        }
+
+        // the frame of notification may have been taken with the previous one
+        let Ok(frame) = self.frame_pool.TryGetNextFrame() else {
+            return Ok(NextFrame::Timeout);
+        };
+
+        defer! {
+            let _ = frame.Close();
+        }
+
+        let content_size = HRESULT!(frame.ContentSize());
+        if content_size.Width != self.size.Width || content_size.Height != self.size.Height {
+            return Ok(NextFrame::Resized);
+        }
+
+        let surface = HRESULT!(frame.Surface());
+        let access: IDirect3DDxgiInterfaceAccess = HRESULT!(surface.cast());
+        let frame_texture: ID3D11Texture2D = HRESULT!(access.GetInterface());
+
+        device_context.CopyResource(texture, &frame_texture);
+
+        Ok(NextFrame::Copied)
+    }
+}
+
+impl Drop for GraphicsCapture {
+    fn drop(&mut self) {
+        let _ = self.session.Close();
+        let _ = self.frame_pool.Close();
+    }
+}
@@ -1,7 +1,9 @@
 mod duplicator;
 mod dx_math;
+mod graphics_capture;
 mod shader;
 
+pub mod secure_desktop;
 pub mod util;
 
 pub use duplicator::Duplicator;
@@ -0,0 +1,149 @@
+//! UAC prompts and the lock screen are shown on the secure desktop, duplication and input
+//! of the default desktop stop working once it's switched to. Only the agent which runs
+//! as service can attach to the secure desktop, otherwise the viewer is told why its
+//! input is blocked.
+
+use super::util::prepare_desktop;
+use crate::api::endpoint::message::InputBlockReason;
+use scopeguard::defer;
+use std::{
+    cell::Cell,
+    ffi::c_void,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use windows::Win32::{
+    Foundation::{CloseHandle, HANDLE},
+    Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY},
+    System::{
+        StationsAndDesktops::{
+            CloseDesktop, GetUserObjectInformationW, OpenInputDesktop, DESKTOP_CONTROL_FLAGS,
+            HDESK, UOI_NAME,
+        },
+        SystemServices::GENERIC_ALL,
+        Threading::{GetCurrentProcess, OpenProcessToken},
+    },
+};
+
+// bumped whenever the input desktop is switched, threads sending input compare it with
+// the generation they attached to
+static INPUT_DESKTOP_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static ATTACHED_GENERATION: Cell<Option<u64>> = Cell::new(None);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputDesktop {
+    Default,
+    /// The secure desktop which this process can attach to.
+    Secure,
+    /// The secure desktop which is out of reach of this process.
+    SecureInaccessible,
+}
+
+impl InputDesktop {
+    /// Why input of viewer can't reach the desktop, None when it can.
+    pub fn input_block_reason(&self) -> Option<InputBlockReason> {
+        match self {
+            InputDesktop::Default | InputDesktop::Secure => None,
+            InputDesktop::SecureInaccessible => Some(if process_elevated() {
+                InputBlockReason::NotService
+            } else {
+                InputBlockReason::NotElevated
+            }),
+        }
+    }
+}
+
+/// The desktop which receives user input now.
+pub fn current_input_desktop() -> InputDesktop {
+    unsafe {
+        // the secure desktop can't be opened without privilege of LocalSystem
+        let desktop = match OpenInputDesktop(DESKTOP_CONTROL_FLAGS::default(), false, GENERIC_ALL) {
+            Ok(desktop) => desktop,
+            Err(_) => return InputDesktop::SecureInaccessible,
+        };
+
+        defer! {
+            let _ = CloseDesktop(desktop);
+        }
+
+        match desktop_name(desktop) {
+            Some(name) if name.eq_ignore_ascii_case("Default") => InputDesktop::Default,
+            _ => InputDesktop::Secure,
+        }
+    }
+}
+
+/// Record that the input desktop is switched, threads which send input attach to the
+/// new one before their next input.
+pub fn notify_input_desktop_switched() {
+    INPUT_DESKTOP_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+pub fn input_desktop_generation() -> u64 {
+    INPUT_DESKTOP_GENERATION.load(Ordering::SeqCst)
+}
+
+/// Input is only delivered to the desktop which the calling thread is attached to, so
+/// attach current thread to the input desktop if it has been switched.
+pub fn attach_input_desktop() {
+    let generation = input_desktop_generation();
+
+    ATTACHED_GENERATION.with(|attached_generation| {
+        if attached_generation.get() == Some(generation) {
+            return;
+        }
+
+        match unsafe { prepare_desktop() } {
+            Ok(_) => attached_generation.set(Some(generation)),
+            Err(err) => tracing::warn!(?err, "attach thread to input desktop failed"),
+        }
+    });
+}
+
+fn process_elevated() -> bool {
+    unsafe {
+        let mut token = HANDLE::default();
+        if !OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).as_bool() {
+            return false;
+        }
+
+        defer! {
+            let _ = CloseHandle(token);
+        }
+
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut return_length = 0;
+
+        GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut _ as *mut c_void),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut return_length,
+        )
+        .as_bool()
+            && elevation.TokenIsElevated != 0
+    }
+}
+
+unsafe fn desktop_name(desktop: HDESK) -> Option<String> {
+    let mut name = [0u16; 256];
+    let mut length_needed = 0;
+
+    if !GetUserObjectInformationW(
+        HANDLE(desktop.0),
+        UOI_NAME,
+        Some(name.as_mut_ptr() as *mut c_void),
+        (name.len() * std::mem::size_of::<u16>()) as u32,
+        Some(&mut length_needed),
+    )
+    .as_bool()
+    {
+        return None;
+    }
+
+    let length = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+    Some(String::from_utf16_lossy(&name[..length]))
+}
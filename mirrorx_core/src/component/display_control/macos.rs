@@ -0,0 +1,104 @@
+use crate::{core_error, error::CoreResult};
+use core_graphics::display::{CGConfigureOption, CGDirectDisplayID, CGDisplay, CGDisplayMode};
+
+// CGDisplayMode can't be sent across threads, the mode is found again by its size and
+// refresh rate when it's restored
+pub struct DisplayMode {
+    width: u64,
+    height: u64,
+    pixel_width: u64,
+    refresh_rate: f64,
+}
+
+impl DisplayMode {
+    fn of(mode: &CGDisplayMode) -> Self {
+        DisplayMode {
+            width: mode.width(),
+            height: mode.height(),
+            pixel_width: mode.pixel_width(),
+            refresh_rate: mode.refresh_rate(),
+        }
+    }
+
+    // HiDPI modes of the same size are told apart by their pixel size
+    fn matches(&self, mode: &CGDisplayMode) -> bool {
+        mode.width() == self.width
+            && mode.height() == self.height
+            && mode.pixel_width() == self.pixel_width
+            && mode.refresh_rate() == self.refresh_rate
+    }
+}
+
+pub fn current_mode(monitor_id: &str) -> CoreResult<DisplayMode> {
+    let display = display(monitor_id)?;
+    let mode = display
+        .display_mode()
+        .ok_or_else(|| core_error!("CGDisplayCopyDisplayMode returns null"))?;
+
+    Ok(DisplayMode::of(&mode))
+}
+
+pub fn set_resolution(monitor_id: &str, width: u16, height: u16) -> CoreResult<()> {
+    let current_mode = current_mode(monitor_id)?;
+    let display = display(monitor_id)?;
+
+    let modes: Vec<CGDisplayMode> = all_modes(&display)?
+        .into_iter()
+        .filter(|mode| mode.width() == width as u64 && mode.height() == height as u64)
+        .collect();
+
+    // keep the refresh rate and HiDPI of monitor when the resolution supports them
+    let scale = current_mode.pixel_width as f64 / current_mode.width.max(1) as f64;
+    let mode = modes
+        .iter()
+        .max_by_key(|mode| {
+            let mode_scale = mode.pixel_width() as f64 / mode.width().max(1) as f64;
+            (
+                mode.refresh_rate() == current_mode.refresh_rate,
+                mode_scale == scale,
+            )
+        })
+        .ok_or_else(|| core_error!("monitor doesn't support resolution {}x{}", width, height))?;
+
+    configure(&display, mode)
+}
+
+pub fn restore_mode(monitor_id: &str, mode: &DisplayMode) -> CoreResult<()> {
+    let display = display(monitor_id)?;
+    let original_mode = all_modes(&display)?
+        .into_iter()
+        .find(|candidate| mode.matches(candidate))
+        .ok_or_else(|| core_error!("original mode of monitor is gone"))?;
+
+    configure(&display, &original_mode)
+}
+
+fn display(monitor_id: &str) -> CoreResult<CGDisplay> {
+    let display_id: CGDirectDisplayID = monitor_id.parse()?;
+    Ok(CGDisplay::new(display_id))
+}
+
+fn all_modes(display: &CGDisplay) -> CoreResult<Vec<CGDisplayMode>> {
+    CGDisplayMode::all_display_modes(display.id, std::ptr::null())
+        .ok_or_else(|| core_error!("CGDisplayCopyAllDisplayModes returns null"))
+}
+
+fn configure(display: &CGDisplay, mode: &CGDisplayMode) -> CoreResult<()> {
+    let config = display
+        .begin_configuration()
+        .map_err(|err| core_error!("CGBeginDisplayConfiguration returns error code: {}", err))?;
+
+    if let Err(err) = display.configure_display_with_display_mode(&config, mode) {
+        let _ = display.cancel_configuration(&config);
+        return Err(core_error!(
+            "CGConfigureDisplayWithDisplayMode returns error code: {}",
+            err
+        ));
+    }
+
+    // the mode lasts until the agent exits, so the host comes back with its own mode
+    // after the agent crashes
+    display
+        .complete_configuration(&config, CGConfigureOption::ConfigureForAppOnly)
+        .map_err(|err| core_error!("CGCompleteDisplayConfiguration returns error code: {}", err))
+}
@@ -0,0 +1,125 @@
+//! Viewers can change the resolution of the monitor which they're streaming, like
+//! driving a 4K monitor at 1080p to save bandwidth and keep text legible on small
+//! screens. Modes are only changed for the time being, the original mode of monitor is
+//! restored once every session which changed it ends, or a viewer asks for it.
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "windows")]
+use self::windows as sys;
+
+#[cfg(target_os = "macos")]
+mod macos;
+
+#[cfg(target_os = "macos")]
+use self::macos as sys;
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+use self::unsupported as sys;
+
+use crate::error::CoreResult;
+use once_cell::sync::Lazy;
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    sync::Mutex,
+};
+
+// monitors whose resolution is changed, keyed by monitor id
+static CHANGED_MONITORS: Lazy<Mutex<HashMap<String, ChangedMonitor>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct ChangedMonitor {
+    original_mode: sys::DisplayMode,
+    // sessions which changed it, the original mode is restored after all of them end
+    sessions: HashSet<String>,
+}
+
+/// Change the resolution of monitor for the session, the mode of the same refresh rate
+/// is preferred.
+pub fn change_resolution(
+    session_id: &str,
+    monitor_id: &str,
+    width: u16,
+    height: u16,
+) -> CoreResult<()> {
+    let mut changed_monitors = CHANGED_MONITORS.lock().unwrap();
+
+    let changed_monitor = match changed_monitors.entry(monitor_id.to_string()) {
+        Entry::Occupied(entry) => {
+            sys::set_resolution(monitor_id, width, height)?;
+            entry.into_mut()
+        }
+        Entry::Vacant(entry) => {
+            let original_mode = sys::current_mode(monitor_id)?;
+            sys::set_resolution(monitor_id, width, height)?;
+            entry.insert(ChangedMonitor {
+                original_mode,
+                sessions: HashSet::new(),
+            })
+        }
+    };
+
+    changed_monitor.sessions.insert(session_id.to_string());
+
+    tracing::info!(?monitor_id, width, height, "change monitor resolution");
+
+    Ok(())
+}
+
+/// Restore the original resolution of monitor, nothing happens when it's unchanged.
+pub fn restore_resolution(monitor_id: &str) -> CoreResult<()> {
+    let mut changed_monitors = CHANGED_MONITORS.lock().unwrap();
+
+    if let Some(changed_monitor) = changed_monitors.get(monitor_id) {
+        sys::restore_mode(monitor_id, &changed_monitor.original_mode)?;
+        changed_monitors.remove(monitor_id);
+
+        tracing::info!(?monitor_id, "restore monitor resolution");
+    }
+
+    Ok(())
+}
+
+/// Restore the monitors which the session changed and no other sessions hold.
+pub fn session_ended(session_id: &str) {
+    CHANGED_MONITORS
+        .lock()
+        .unwrap()
+        .retain(|monitor_id, changed_monitor| {
+            if !changed_monitor.sessions.remove(session_id) || !changed_monitor.sessions.is_empty()
+            {
+                return true;
+            }
+
+            match sys::restore_mode(monitor_id, &changed_monitor.original_mode) {
+                Ok(()) => tracing::info!(?monitor_id, "restore monitor resolution"),
+                Err(err) => tracing::error!(?monitor_id, ?err, "restore monitor resolution failed"),
+            }
+
+            false
+        });
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod unsupported {
+    use crate::{core_error, error::CoreResult};
+
+    pub type DisplayMode = ();
+
+    pub fn current_mode(_: &str) -> CoreResult<DisplayMode> {
+        Err(core_error!(
+            "display control is not supported on current platform"
+        ))
+    }
+
+    pub fn set_resolution(_: &str, _: u16, _: u16) -> CoreResult<()> {
+        Err(core_error!(
+            "display control is not supported on current platform"
+        ))
+    }
+
+    pub fn restore_mode(_: &str, _: &DisplayMode) -> CoreResult<()> {
+        Ok(())
+    }
+}
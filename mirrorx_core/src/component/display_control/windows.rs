@@ -0,0 +1,165 @@
+use crate::{core_error, error::CoreResult};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::HWND, Graphics::Gdi::*, UI::WindowsAndMessaging::EDD_GET_DEVICE_INTERFACE_NAME,
+    },
+};
+
+pub type DisplayMode = DEVMODEW;
+
+pub fn current_mode(monitor_id: &str) -> CoreResult<DisplayMode> {
+    unsafe {
+        let device_name = device_name(monitor_id)?;
+        current_settings(&device_name)
+    }
+}
+
+pub fn set_resolution(monitor_id: &str, width: u16, height: u16) -> CoreResult<()> {
+    unsafe {
+        let device_name = device_name(monitor_id)?;
+        let current_mode = current_settings(&device_name)?;
+
+        let mut mode: Option<DEVMODEW> = None;
+        let mut mode_index = 0;
+
+        loop {
+            let mut candidate: DEVMODEW = std::mem::zeroed();
+            candidate.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+
+            if !EnumDisplaySettingsW(
+                PCWSTR::from_raw(device_name.as_ptr()),
+                mode_index,
+                &mut candidate,
+            )
+            .as_bool()
+            {
+                break;
+            }
+
+            mode_index += 1;
+
+            if candidate.dmPelsWidth != width as u32
+                || candidate.dmPelsHeight != height as u32
+                || candidate.dmBitsPerPel != current_mode.dmBitsPerPel
+            {
+                continue;
+            }
+
+            // keep the refresh rate of monitor, otherwise take the highest one
+            let preferred = match mode {
+                None => true,
+                Some(mode) if mode.dmDisplayFrequency == current_mode.dmDisplayFrequency => false,
+                Some(mode) => {
+                    candidate.dmDisplayFrequency == current_mode.dmDisplayFrequency
+                        || candidate.dmDisplayFrequency > mode.dmDisplayFrequency
+                }
+            };
+
+            if preferred {
+                mode = Some(candidate);
+            }
+        }
+
+        let Some(mut mode) = mode else {
+            return Err(core_error!(
+                "monitor doesn't support resolution {}x{}",
+                width,
+                height
+            ));
+        };
+
+        mode.dmFields = DM_PELSWIDTH | DM_PELSHEIGHT | DM_BITSPERPEL | DM_DISPLAYFREQUENCY;
+
+        // the mode is temporary and isn't written to registry, so the host comes back
+        // with its own mode after the agent crashes or the host reboots
+        change_settings(&device_name, &mode, CDS_FULLSCREEN)
+    }
+}
+
+pub fn restore_mode(monitor_id: &str, mode: &DisplayMode) -> CoreResult<()> {
+    unsafe {
+        let device_name = device_name(monitor_id)?;
+        let mut mode = *mode;
+        mode.dmFields = DM_PELSWIDTH | DM_PELSHEIGHT | DM_BITSPERPEL | DM_DISPLAYFREQUENCY;
+
+        change_settings(&device_name, &mode, CDS_TYPE(0))
+    }
+}
+
+unsafe fn current_settings(device_name: &[u16]) -> CoreResult<DEVMODEW> {
+    let mut mode: DEVMODEW = std::mem::zeroed();
+    mode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+
+    if !EnumDisplaySettingsW(
+        PCWSTR::from_raw(device_name.as_ptr()),
+        ENUM_CURRENT_SETTINGS,
+        &mut mode,
+    )
+    .as_bool()
+    {
+        return Err(core_error!("EnumDisplaySettingsW failed"));
+    }
+
+    Ok(mode)
+}
+
+unsafe fn change_settings(device_name: &[u16], mode: &DEVMODEW, flags: CDS_TYPE) -> CoreResult<()> {
+    let ret = ChangeDisplaySettingsExW(
+        PCWSTR::from_raw(device_name.as_ptr()),
+        Some(mode as *const _),
+        HWND::default(),
+        flags,
+        None,
+    );
+
+    if ret != DISP_CHANGE_SUCCESSFUL {
+        return Err(core_error!(
+            "ChangeDisplaySettingsExW returns error code: {}",
+            ret.0
+        ));
+    }
+
+    Ok(())
+}
+
+// monitor id is the device interface name of monitor, modes are changed through the
+// GDI device name of adapter output which it's attached to, like `\\.\DISPLAY1`
+unsafe fn device_name(monitor_id: &str) -> CoreResult<Vec<u16>> {
+    let mut adapter_index = 0;
+
+    loop {
+        let mut adapter: DISPLAY_DEVICEW = std::mem::zeroed();
+        adapter.cb = std::mem::size_of::<DISPLAY_DEVICEW>() as u32;
+
+        if !EnumDisplayDevicesW(PCWSTR::null(), adapter_index, &mut adapter, 0).as_bool() {
+            return Err(core_error!("can't find display device of monitor"));
+        }
+
+        adapter_index += 1;
+
+        let mut monitor_index = 0;
+
+        loop {
+            let mut display_device: DISPLAY_DEVICEW = std::mem::zeroed();
+            display_device.cb = std::mem::size_of::<DISPLAY_DEVICEW>() as u32;
+
+            if !EnumDisplayDevicesW(
+                PCWSTR::from_raw(adapter.DeviceName.as_ptr()),
+                monitor_index,
+                &mut display_device,
+                EDD_GET_DEVICE_INTERFACE_NAME,
+            )
+            .as_bool()
+            {
+                break;
+            }
+
+            monitor_index += 1;
+
+            if PCWSTR::from_raw(display_device.DeviceID.as_ptr()).to_string()? == monitor_id {
+                return Ok(adapter.DeviceName.to_vec());
+            }
+        }
+    }
+}
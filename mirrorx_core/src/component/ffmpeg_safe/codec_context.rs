@@ -0,0 +1,136 @@
+use super::{check_receive_ret, check_ret, Frame, Packet};
+use crate::{core_error, error::CoreResult};
+use mirrorx_native::ffmpeg::codecs::{avcodec::*, codec::*, codec_id::AVCodecID};
+use std::{ffi::CStr, ops::Deref, ptr::NonNull};
+
+/// Codec context of a decoder or an encoder. Its options are set by [`CodecContext::configure`]
+/// before it's opened, and frames and packets go through it only after that.
+pub struct CodecContext {
+    ctx: NonNull<AVCodecContext>,
+    codec: *const AVCodec,
+    opened: bool,
+}
+
+unsafe impl Send for CodecContext {}
+
+impl CodecContext {
+    pub fn new_decoder(codec_id: AVCodecID) -> CoreResult<CodecContext> {
+        let codec = unsafe { avcodec_find_decoder(codec_id) };
+        if codec.is_null() {
+            return Err(core_error!("avcodec_find_decoder returns null"));
+        }
+
+        Self::alloc(codec)
+    }
+
+    pub fn new_encoder(name: &CStr) -> CoreResult<CodecContext> {
+        let codec = unsafe { avcodec_find_encoder_by_name(name.as_ptr()) };
+        if codec.is_null() {
+            return Err(core_error!(
+                "avcodec_find_encoder_by_name returns null pointer"
+            ));
+        }
+
+        Self::alloc(codec)
+    }
+
+    fn alloc(codec: *const AVCodec) -> CoreResult<CodecContext> {
+        let ctx = unsafe { avcodec_alloc_context3(codec) };
+        let ctx = NonNull::new(ctx).ok_or(core_error!("avcodec_alloc_context3 returns null"))?;
+
+        Ok(CodecContext {
+            ctx,
+            codec,
+            opened: false,
+        })
+    }
+
+    /// Set the options of context, they can't be changed once it's opened.
+    pub fn configure<F>(&mut self, f: F) -> CoreResult<()>
+    where
+        F: FnOnce(&mut AVCodecContext) -> CoreResult<()>,
+    {
+        if self.opened {
+            return Err(core_error!("codec context is opened"));
+        }
+
+        f(unsafe { self.ctx.as_mut() })
+    }
+
+    pub fn open(&mut self) -> CoreResult<()> {
+        if self.opened {
+            return Ok(());
+        }
+
+        check_ret("avcodec_open2", unsafe {
+            avcodec_open2(self.ctx.as_ptr(), self.codec, std::ptr::null_mut())
+        })?;
+
+        self.opened = true;
+
+        Ok(())
+    }
+
+    pub fn is_hardware(&self) -> bool {
+        !self.hw_device_ctx.is_null()
+    }
+
+    pub fn send_packet(&mut self, packet: &Packet) -> CoreResult<()> {
+        self.ensure_opened()?;
+        check_ret("avcodec_send_packet", unsafe {
+            avcodec_send_packet(self.ctx.as_ptr(), packet.as_ptr())
+        })?;
+
+        Ok(())
+    }
+
+    /// Take the next decoded frame, false when the decoder needs more packets.
+    pub fn receive_frame(&mut self, frame: &mut Frame) -> CoreResult<bool> {
+        self.ensure_opened()?;
+        check_receive_ret("avcodec_receive_frame", unsafe {
+            avcodec_receive_frame(self.ctx.as_ptr(), frame.as_mut_ptr())
+        })
+    }
+
+    /// Send the frame to encoder, the frame is referenced by encoder so it must be made
+    /// writable before it's written again.
+    pub fn send_frame(&mut self, frame: &Frame) -> CoreResult<()> {
+        self.ensure_opened()?;
+        check_ret("avcodec_send_frame", unsafe {
+            avcodec_send_frame(self.ctx.as_ptr(), frame.as_ptr() as *mut _)
+        })?;
+
+        Ok(())
+    }
+
+    /// Take the next encoded packet, false when the encoder needs more frames.
+    pub fn receive_packet(&mut self, packet: &mut Packet) -> CoreResult<bool> {
+        self.ensure_opened()?;
+        check_receive_ret("avcodec_receive_packet", unsafe {
+            avcodec_receive_packet(self.ctx.as_ptr(), packet.as_mut_ptr())
+        })
+    }
+
+    fn ensure_opened(&self) -> CoreResult<()> {
+        if self.opened {
+            Ok(())
+        } else {
+            Err(core_error!("codec context isn't opened"))
+        }
+    }
+}
+
+impl Deref for CodecContext {
+    type Target = AVCodecContext;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.ctx.as_ref() }
+    }
+}
+
+impl Drop for CodecContext {
+    fn drop(&mut self) {
+        let mut ctx = self.ctx.as_ptr();
+        unsafe { avcodec_free_context(&mut ctx) }
+    }
+}
@@ -0,0 +1,179 @@
+use super::check_ret;
+use crate::{core_error, error::CoreResult};
+use mirrorx_native::ffmpeg::utils::{
+    avutil::*, frame::*, hwcontext::av_hwframe_transfer_data, pixfmt::*,
+};
+use std::ptr::NonNull;
+
+pub struct Frame(NonNull<AVFrame>);
+
+unsafe impl Send for Frame {}
+
+impl Frame {
+    pub fn new() -> CoreResult<Frame> {
+        let frame = unsafe { av_frame_alloc() };
+        NonNull::new(frame)
+            .map(Frame)
+            .ok_or(core_error!("av_frame_alloc returns null"))
+    }
+
+    /// Allocate the planes of a video frame, their data is written by [`Frame::plane_mut`].
+    pub fn alloc_video_buffer(
+        &mut self,
+        format: AVPixelFormat,
+        width: i32,
+        height: i32,
+    ) -> CoreResult<()> {
+        unsafe {
+            av_frame_unref(self.as_mut_ptr());
+
+            let frame = self.0.as_mut();
+            frame.format = format;
+            frame.width = width;
+            frame.height = height;
+
+            check_ret(
+                "av_frame_get_buffer",
+                av_frame_get_buffer(self.as_mut_ptr(), 0),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Make sure the planes aren't shared with the codec before they're written again.
+    pub fn make_writable(&mut self) -> CoreResult<()> {
+        check_ret("av_frame_make_writable", unsafe {
+            av_frame_make_writable(self.as_mut_ptr())
+        })?;
+
+        Ok(())
+    }
+
+    /// Download the frame which hardware decoder outputs into this one.
+    pub fn transfer_from(&mut self, hw_frame: &Frame) -> CoreResult<()> {
+        check_ret("av_hwframe_transfer_data", unsafe {
+            av_hwframe_transfer_data(self.as_mut_ptr(), hw_frame.as_ptr(), 0)
+        })?;
+
+        Ok(())
+    }
+
+    pub fn unref(&mut self) {
+        unsafe {
+            av_frame_unref(self.as_mut_ptr());
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.inner().width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.inner().height
+    }
+
+    pub fn format(&self) -> AVPixelFormat {
+        self.inner().format
+    }
+
+    pub fn color_range(&self) -> AVColorRange {
+        self.inner().color_range
+    }
+
+    pub fn color_primaries(&self) -> AVColorPrimaries {
+        self.inner().color_primaries
+    }
+
+    pub fn set_pts(&mut self, pts: i64) {
+        unsafe { self.0.as_mut().pts = pts }
+    }
+
+    /// Force the encoder to code the frame as a key frame, or leave the choice to it.
+    pub fn set_key_frame(&mut self, key_frame: bool) {
+        unsafe {
+            self.0.as_mut().pict_type = if key_frame {
+                AV_PICTURE_TYPE_I
+            } else {
+                AV_PICTURE_TYPE_NONE
+            }
+        }
+    }
+
+    pub fn line_size(&self, index: usize) -> i32 {
+        self.inner().linesize.get(index).copied().unwrap_or(0)
+    }
+
+    /// The plane in CPU memory, None if the format isn't known or the frame lives in
+    /// hardware.
+    pub fn plane(&self, index: usize) -> Option<&[u8]> {
+        let (data, size) = self.plane_parts(index)?;
+        Some(unsafe { std::slice::from_raw_parts(data, size) })
+    }
+
+    pub fn plane_mut(&mut self, index: usize) -> Option<&mut [u8]> {
+        let (data, size) = self.plane_parts(index)?;
+        Some(unsafe { std::slice::from_raw_parts_mut(data, size) })
+    }
+
+    /// Copy the rows of `src` which are `src_stride` apart into the plane, rows beyond
+    /// either of them are left untouched.
+    pub fn copy_plane_from(&mut self, index: usize, src: &[u8], src_stride: i32) -> CoreResult<()> {
+        let line_size = self.line_size(index) as usize;
+        let src_stride = src_stride as usize;
+        let plane = self
+            .plane_mut(index)
+            .ok_or(core_error!("frame has no plane {}", index))?;
+
+        if src_stride == 0 || line_size == 0 {
+            return Ok(());
+        }
+
+        let row_bytes = line_size.min(src_stride);
+        for (dst_row, src_row) in plane.chunks_mut(line_size).zip(src.chunks(src_stride)) {
+            let len = row_bytes.min(dst_row.len()).min(src_row.len());
+            dst_row[..len].copy_from_slice(&src_row[..len]);
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn as_ptr(&self) -> *const AVFrame {
+        self.0.as_ptr()
+    }
+
+    pub(super) fn as_mut_ptr(&mut self) -> *mut AVFrame {
+        self.0.as_ptr()
+    }
+
+    fn inner(&self) -> &AVFrame {
+        unsafe { self.0.as_ref() }
+    }
+
+    fn plane_parts(&self, index: usize) -> Option<(*mut u8, usize)> {
+        let frame = self.inner();
+
+        let plane_height = match (frame.format, index) {
+            (AV_PIX_FMT_NV12, 0) | (AV_PIX_FMT_YUV420P | AV_PIX_FMT_YUVJ420P, 0) => frame.height,
+            (AV_PIX_FMT_NV12, 1) | (AV_PIX_FMT_YUV420P | AV_PIX_FMT_YUVJ420P, 1 | 2) => {
+                (frame.height + 1) / 2
+            }
+            _ => return None,
+        };
+
+        let data = frame.data[index];
+        let line_size = frame.linesize[index];
+        if data.is_null() || line_size <= 0 || plane_height <= 0 {
+            return None;
+        }
+
+        Some((data, line_size as usize * plane_height as usize))
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        let mut frame = self.0.as_ptr();
+        unsafe { av_frame_free(&mut frame) }
+    }
+}
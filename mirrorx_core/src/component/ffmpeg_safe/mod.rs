@@ -0,0 +1,39 @@
+//! Owned wrappers of the FFmpeg objects which codecs and re-samplers work with. Raw
+//! pointers never leave them except for the FFI calls made here, they're freed once
+//! dropped, and the data borrowed from them can't outlive them or be read while FFmpeg
+//! writes it.
+
+mod codec_context;
+mod frame;
+mod packet;
+mod resample_context;
+
+pub use codec_context::CodecContext;
+pub use frame::Frame;
+pub use packet::Packet;
+pub use resample_context::{ResampleContext, ResampleFormat};
+
+use crate::{core_error, error::CoreResult};
+use mirrorx_native::ffmpeg::utils::error::{AVERROR, AVERROR_EOF};
+
+fn check_ret(function: &str, ret: i32) -> CoreResult<i32> {
+    if ret == AVERROR(libc::EAGAIN) {
+        Err(core_error!("{} returns EAGAIN", function))
+    } else if ret == AVERROR_EOF {
+        Err(core_error!("{} returns AVERROR_EOF", function))
+    } else if ret < 0 {
+        Err(core_error!("{} returns error code: {}", function, ret))
+    } else {
+        Ok(ret)
+    }
+}
+
+// receiving returns EAGAIN when the codec needs more input and EOF once it's flushed,
+// neither is an error for the caller which drains the output
+fn check_receive_ret(function: &str, ret: i32) -> CoreResult<bool> {
+    if ret == AVERROR(libc::EAGAIN) || ret == AVERROR_EOF {
+        Ok(false)
+    } else {
+        check_ret(function, ret).map(|_| true)
+    }
+}
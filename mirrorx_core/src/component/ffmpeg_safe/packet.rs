@@ -0,0 +1,70 @@
+use super::check_ret;
+use crate::{core_error, error::CoreResult};
+use mirrorx_native::ffmpeg::codecs::packet::*;
+use std::ptr::NonNull;
+
+/// Packet which owns its buffer, the data is copied in instead of pointing to memory
+/// which FFmpeg may keep after the owner is gone.
+pub struct Packet(NonNull<AVPacket>);
+
+unsafe impl Send for Packet {}
+
+impl Packet {
+    pub fn new() -> CoreResult<Packet> {
+        let packet = unsafe { av_packet_alloc() };
+        NonNull::new(packet)
+            .map(Packet)
+            .ok_or(core_error!("av_packet_alloc returns null"))
+    }
+
+    /// Replace the content with a copy of `data`.
+    pub fn fill(&mut self, data: &[u8], pts: i64) -> CoreResult<()> {
+        let size = i32::try_from(data.len()).map_err(|_| core_error!("packet is too large"))?;
+
+        unsafe {
+            av_packet_unref(self.as_mut_ptr());
+            check_ret("av_new_packet", av_new_packet(self.as_mut_ptr(), size))?;
+
+            let packet = self.0.as_mut();
+            std::ptr::copy_nonoverlapping(data.as_ptr(), packet.data, data.len());
+            packet.pts = pts;
+            packet.dts = pts;
+        }
+
+        Ok(())
+    }
+
+    pub fn data(&self) -> &[u8] {
+        unsafe {
+            let packet = self.0.as_ref();
+            if packet.data.is_null() || packet.size <= 0 {
+                &[]
+            } else {
+                std::slice::from_raw_parts(packet.data, packet.size as usize)
+            }
+        }
+    }
+
+    pub fn pts(&self) -> i64 {
+        unsafe { self.0.as_ref().pts }
+    }
+
+    pub fn unref(&mut self) {
+        unsafe { av_packet_unref(self.as_mut_ptr()) }
+    }
+
+    pub(super) fn as_ptr(&self) -> *const AVPacket {
+        self.0.as_ptr()
+    }
+
+    pub(super) fn as_mut_ptr(&mut self) -> *mut AVPacket {
+        self.0.as_ptr()
+    }
+}
+
+impl Drop for Packet {
+    fn drop(&mut self) {
+        let mut packet = self.0.as_ptr();
+        unsafe { av_packet_free(&mut packet) }
+    }
+}
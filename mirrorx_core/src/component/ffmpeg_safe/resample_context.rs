@@ -0,0 +1,148 @@
+use super::check_ret;
+use crate::{core_error, error::CoreResult};
+use mirrorx_native::ffmpeg::{
+    swresample::*,
+    utils::{
+        channel_layout::AVChannelLayout,
+        mathematics::*,
+        opt::*,
+        samplefmt::{av_get_bytes_per_sample, AVSampleFormat},
+    },
+};
+use std::{ffi::CStr, os::raw::c_void, ptr::NonNull};
+
+/// Re-sampler of interleaved samples, the output is written to a buffer it owns instead
+/// of the arrays FFmpeg allocates.
+pub struct ResampleContext {
+    ctx: NonNull<SwrContext>,
+    in_rate: i32,
+    in_frame_bytes: usize,
+    out_rate: i32,
+    out_frame_bytes: usize,
+    out_buffer: Vec<u8>,
+}
+
+unsafe impl Send for ResampleContext {}
+
+pub struct ResampleFormat<'a> {
+    pub channel_layout: &'a AVChannelLayout,
+    pub sample_rate: i32,
+    pub sample_format: AVSampleFormat,
+}
+
+impl ResampleContext {
+    pub fn new(input: ResampleFormat, output: ResampleFormat) -> CoreResult<ResampleContext> {
+        let in_frame_bytes = frame_bytes(&input)?;
+        let out_frame_bytes = frame_bytes(&output)?;
+
+        let ctx = unsafe { swr_alloc() };
+        let ctx = NonNull::new(ctx).ok_or(core_error!("init swr context failed"))?;
+
+        // freed on drop if the options below fail
+        let mut resample_context = ResampleContext {
+            ctx,
+            in_rate: input.sample_rate,
+            in_frame_bytes,
+            out_rate: output.sample_rate,
+            out_frame_bytes,
+            out_buffer: Vec::new(),
+        };
+
+        resample_context.set_format(&input, "in")?;
+        resample_context.set_format(&output, "out")?;
+
+        check_ret("swr_init", unsafe {
+            swr_init(resample_context.ctx.as_ptr())
+        })?;
+
+        Ok(resample_context)
+    }
+
+    /// Convert the complete frames of `input`, trailing bytes of a partial frame are
+    /// dropped instead of being read past.
+    pub fn convert(&mut self, input: &[u8]) -> CoreResult<&[u8]> {
+        let in_samples = (input.len() / self.in_frame_bytes) as i32;
+
+        let out_samples = unsafe {
+            av_rescale_rnd(
+                swr_get_delay(self.ctx.as_ptr(), self.in_rate as _) + in_samples as i64,
+                self.out_rate.into(),
+                self.in_rate.into(),
+                AV_ROUND_UP,
+            )
+        } as i32;
+
+        self.out_buffer
+            .resize(out_samples as usize * self.out_frame_bytes, 0);
+
+        let in_data = input.as_ptr();
+        let mut out_data = self.out_buffer.as_mut_ptr();
+        let converted_samples = check_ret("swr_convert", unsafe {
+            swr_convert(
+                self.ctx.as_ptr(),
+                &mut out_data,
+                out_samples,
+                &in_data,
+                in_samples,
+            )
+        })?;
+
+        Ok(&self.out_buffer[..converted_samples as usize * self.out_frame_bytes])
+    }
+
+    fn set_format(&mut self, format: &ResampleFormat, direction: &str) -> CoreResult<()> {
+        let obj = self.ctx.as_ptr() as *mut c_void;
+
+        let option = |name: &str| std::ffi::CString::new(format!("{direction}_{name}"));
+        let channel_layout = option("chlayout")?;
+        let sample_rate = option("sample_rate")?;
+        let sample_format = option("sample_fmt")?;
+
+        unsafe {
+            set_option(
+                &channel_layout,
+                av_opt_set_chlayout(obj, channel_layout.as_ptr(), format.channel_layout, 0),
+            )?;
+            set_option(
+                &sample_rate,
+                av_opt_set_int(obj, sample_rate.as_ptr(), format.sample_rate.into(), 0),
+            )?;
+            set_option(
+                &sample_format,
+                av_opt_set_sample_fmt(obj, sample_format.as_ptr(), format.sample_format, 0),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for ResampleContext {
+    fn drop(&mut self) {
+        let mut ctx = self.ctx.as_ptr();
+        unsafe { swr_free(&mut ctx) }
+    }
+}
+
+fn set_option(name: &CStr, ret: i32) -> CoreResult<()> {
+    if ret < 0 {
+        return Err(core_error!("set swr option {:?} failed ({})", name, ret));
+    }
+
+    Ok(())
+}
+
+// bytes of one sample of every channel, only the interleaved formats are accepted
+fn frame_bytes(format: &ResampleFormat) -> CoreResult<usize> {
+    let bytes_per_sample = unsafe { av_get_bytes_per_sample(format.sample_format) };
+    let channels = format.channel_layout.nb_channels;
+    if bytes_per_sample <= 0 || channels <= 0 {
+        return Err(core_error!(
+            "unsupported sample format {} of {} channels",
+            format.sample_format,
+            channels
+        ));
+    }
+
+    Ok(bytes_per_sample as usize * channels as usize)
+}
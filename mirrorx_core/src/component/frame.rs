@@ -1,8 +1,42 @@
 use cpal::SampleFormat;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorPrimaries {
+    /// BT.709 primaries, the same as sRGB.
+    Bt709,
+    /// DCI-P3 primaries with D65 white point, used by wide-gamut displays.
+    DisplayP3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorRange {
+    /// Y in [0, 255], also known as JPEG range.
+    Full,
+    /// Y in [16, 235] and UV in [16, 240], also known as MPEG range.
+    Limited,
+}
+
+/// Color space of desktop frame, the YCbCr matrix is always BT.709.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColorSpace {
+    pub primaries: ColorPrimaries,
+    pub range: ColorRange,
+}
+
+impl Default for ColorSpace {
+    fn default() -> Self {
+        Self {
+            primaries: ColorPrimaries::Bt709,
+            range: ColorRange::Limited,
+        }
+    }
+}
+
 pub struct DesktopEncodeFrame {
     pub capture_time: Duration,
+    pub color_space: ColorSpace,
     pub width: i32,
     pub height: i32,
     pub luminance_bytes: Vec<u8>,
@@ -27,6 +61,7 @@ pub struct DesktopDecodeFrame {
     pub plane_data: Vec<Vec<u8>>,
     pub line_sizes: Vec<i32>,
     pub format: DesktopDecodeFrameFormat,
+    pub color_space: ColorSpace,
 }
 
 impl Default for DesktopDecodeFrame {
@@ -37,6 +72,7 @@ impl Default for DesktopDecodeFrame {
             plane_data: Vec::new(),
             line_sizes: Vec::new(),
             format: DesktopDecodeFrameFormat::NV12,
+            color_space: ColorSpace::default(),
         }
     }
 }
@@ -0,0 +1,347 @@
+//! The little of HTTP/1.1 which WebDAV clients of file explorers speak.
+
+use crate::{core_error, error::CoreResult};
+use std::collections::HashMap;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+// headers and bodies of requests other than PUT are small
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+const MAX_SMALL_BODY_SIZE: usize = 1024 * 1024;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    headers: HashMap<String, String>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(|value| value.as_str())
+    }
+
+    fn content_length(&self) -> CoreResult<Option<u64>> {
+        self.header("content-length")
+            .map(|length| {
+                length
+                    .trim()
+                    .parse()
+                    .map_err(|_| core_error!("invalid content length"))
+            })
+            .transpose()
+    }
+
+    fn chunked(&self) -> bool {
+        self.header("transfer-encoding")
+            .map(|encoding| encoding.to_ascii_lowercase().contains("chunked"))
+            .unwrap_or(false)
+    }
+
+    pub fn keep_alive(&self) -> bool {
+        !self
+            .header("connection")
+            .map(|connection| connection.eq_ignore_ascii_case("close"))
+            .unwrap_or(false)
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    pub fn body(mut self, content_type: &str, body: Vec<u8>) -> Self {
+        self.headers
+            .push(("Content-Type", content_type.to_string()));
+        self.body = body;
+        self
+    }
+}
+
+/// Read the request line and headers, None when client closes the connection.
+pub async fn read_request<R>(reader: &mut R) -> CoreResult<Option<Request>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = String::new();
+    let mut header_size = 0;
+
+    // clients may send blank lines between requests
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+
+        if !line.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(core_error!("invalid request line"));
+    };
+
+    let method = method.to_ascii_uppercase();
+    let path = target.split('?').next().unwrap_or_default().to_string();
+    let mut headers = HashMap::new();
+
+    loop {
+        line.clear();
+        let n = reader.read_line(&mut line).await?;
+        header_size += n;
+
+        if n == 0 || header_size > MAX_HEADER_SIZE {
+            return Err(core_error!("invalid request headers"));
+        }
+
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    Ok(Some(Request {
+        method,
+        path,
+        headers,
+    }))
+}
+
+/// Body of request which is read piece by piece, in either content length or chunked
+/// encoding.
+pub struct BodyReader {
+    chunked: bool,
+    // bytes left in the body, or in the current chunk for chunked encoding
+    remaining: u64,
+    finished: bool,
+}
+
+impl BodyReader {
+    pub fn new(request: &Request) -> CoreResult<Self> {
+        let chunked = request.chunked();
+        let remaining = if chunked {
+            0
+        } else {
+            request.content_length()?.unwrap_or(0)
+        };
+
+        Ok(BodyReader {
+            chunked,
+            remaining,
+            finished: !chunked && remaining == 0,
+        })
+    }
+
+    /// Read at most `max` bytes of body, empty when the body ends.
+    pub async fn read<R>(&mut self, reader: &mut R, max: usize) -> CoreResult<Vec<u8>>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        if self.finished {
+            return Ok(Vec::new());
+        }
+
+        if self.chunked && self.remaining == 0 {
+            self.remaining = read_chunk_size(reader).await?;
+
+            if self.remaining == 0 {
+                // trailers end with an empty line
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+                        break;
+                    }
+                }
+
+                self.finished = true;
+                return Ok(Vec::new());
+            }
+        }
+
+        let len = self.remaining.min(max as u64) as usize;
+        let mut buffer = vec![0u8; len];
+        reader.read_exact(&mut buffer).await?;
+        self.remaining -= len as u64;
+
+        if self.remaining == 0 {
+            if self.chunked {
+                // every chunk ends with CRLF
+                let mut crlf = String::new();
+                reader.read_line(&mut crlf).await?;
+            } else {
+                self.finished = true;
+            }
+        }
+
+        Ok(buffer)
+    }
+
+    /// Read the whole body of which size is limited.
+    pub async fn read_to_end<R>(&mut self, reader: &mut R) -> CoreResult<Vec<u8>>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let mut body = Vec::new();
+
+        loop {
+            let buffer = self.read(reader, MAX_SMALL_BODY_SIZE).await?;
+            if buffer.is_empty() {
+                return Ok(body);
+            }
+
+            body.extend_from_slice(&buffer);
+            if body.len() > MAX_SMALL_BODY_SIZE {
+                return Err(core_error!("request body is too large"));
+            }
+        }
+    }
+}
+
+async fn read_chunk_size<R>(reader: &mut R) -> CoreResult<u64>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+
+    // chunk extensions follow the size after ';'
+    let size = line.trim().split(';').next().unwrap_or_default();
+    u64::from_str_radix(size, 16).map_err(|_| core_error!("invalid chunk size"))
+}
+
+/// Write the status line and headers, `content_length` of body is always given so the
+/// connection can be kept alive.
+pub async fn write_head<W>(
+    writer: &mut W,
+    status: u16,
+    headers: &[(&'static str, String)],
+    content_length: u64,
+) -> CoreResult<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut head = format!("HTTP/1.1 {} {}\r\n", status, reason_phrase(status));
+
+    for (name, value) in headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+
+    head.push_str(&format!("Content-Length: {content_length}\r\n\r\n"));
+    writer.write_all(head.as_bytes()).await?;
+
+    Ok(())
+}
+
+pub async fn write_response<W>(
+    writer: &mut W,
+    response: Response,
+    head_only: bool,
+) -> CoreResult<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    write_head(
+        writer,
+        response.status,
+        &response.headers,
+        response.body.len() as u64,
+    )
+    .await?;
+
+    if !head_only {
+        writer.write_all(&response.body).await?;
+    }
+
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Decode `%XX` of url path.
+pub fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Encode url path, '/' is kept as separator.
+pub fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    encoded
+}
+
+/// Escape text in XML.
+pub fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        206 => "Partial Content",
+        207 => "Multi-Status",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        412 => "Precondition Failed",
+        416 => "Range Not Satisfiable",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        _ => "Internal Server Error",
+    }
+}
@@ -0,0 +1,565 @@
+//! Local WebDAV server which exposes the filesystem of the connected peer, so users can
+//! mount the remote machine in their native file explorer. Every WebDAV method is
+//! translated into fs operation calls of the endpoint client.
+//!
+//! It only listens on loopback and every url is under a random token, local processes
+//! which don't know the url can't reach the remote filesystem.
+
+mod http;
+
+use self::http::{
+    percent_decode, percent_encode, read_request, write_head, write_response, xml_escape,
+    BodyReader, Request, Response,
+};
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{
+            EndPointCallRequest, EndPointFsOperationReply, EndPointFsOperationRequest, FsEntry,
+            FsOperation,
+        },
+    },
+    core_error,
+    error::CoreResult,
+    utility::spawn,
+};
+use chrono::TimeZone;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+};
+use tokio::{
+    io::{AsyncBufRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+// bytes of file which are read or written by one fs operation call
+const TRANSFER_CHUNK_SIZE: usize = 1024 * 1024;
+
+const ALLOWED_METHODS: &str =
+    "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, PROPPATCH, MKCOL, MOVE, LOCK, UNLOCK";
+
+pub struct FsBridge {
+    addr: SocketAddr,
+    token: String,
+    serve_handle: JoinHandle<()>,
+}
+
+impl FsBridge {
+    /// Start serving the filesystem of `client` on a free port of loopback.
+    pub async fn serve(client: Arc<EndPointClient>) -> CoreResult<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let addr = listener.local_addr()?;
+        let token = uuid::Uuid::new_v4().simple().to_string();
+
+        tracing::info!(?addr, "fs bridge listening");
+
+        let serve_token = token.clone();
+        let serve_handle = spawn::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        spawn::spawn(serve_connection(
+                            client.clone(),
+                            serve_token.clone(),
+                            stream,
+                        ));
+                    }
+                    Err(err) => {
+                        tracing::error!(?err, "accept fs bridge connection failed");
+                        break;
+                    }
+                }
+            }
+
+            tracing::info!("fs bridge serve loop exit");
+        });
+
+        Ok(FsBridge {
+            addr,
+            token,
+            serve_handle,
+        })
+    }
+
+    /// The url which file explorers mount.
+    pub fn url(&self) -> String {
+        format!("http://{}/{}/", self.addr, self.token)
+    }
+
+    pub fn stop(&self) {
+        self.serve_handle.abort();
+    }
+}
+
+impl Drop for FsBridge {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+async fn serve_connection(client: Arc<EndPointClient>, token: String, stream: TcpStream) {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    loop {
+        let request = match read_request(&mut reader).await {
+            Ok(Some(request)) => request,
+            Ok(None) => break,
+            Err(err) => {
+                tracing::warn!(?err, "read fs bridge request failed");
+                break;
+            }
+        };
+
+        let Ok(mut body) = BodyReader::new(&request) else {
+            let _ = write_response(&mut writer, Response::new(400), false).await;
+            break;
+        };
+
+        let keep_alive = request.keep_alive();
+
+        if let Err(err) = handle_request(
+            &client,
+            &token,
+            &request,
+            &mut body,
+            &mut reader,
+            &mut writer,
+        )
+        .await
+        {
+            tracing::error!(
+                ?err,
+                method = %request.method,
+                path = %request.path,
+                "handle fs bridge request failed"
+            );
+
+            // the connection is closed anyway, the response may follow a partial one
+            let _ = write_response(&mut writer, Response::new(500), false).await;
+            break;
+        }
+
+        // the rest of body would be parsed as the next request
+        loop {
+            match body.read(&mut reader, TRANSFER_CHUNK_SIZE).await {
+                Ok(buffer) if buffer.is_empty() => break,
+                Ok(_) => {}
+                Err(_) => return,
+            }
+        }
+
+        if !keep_alive {
+            break;
+        }
+    }
+}
+
+async fn handle_request<R, W>(
+    client: &EndPointClient,
+    token: &str,
+    request: &Request,
+    body: &mut BodyReader,
+    reader: &mut R,
+    writer: &mut W,
+) -> CoreResult<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let Some(path) = remote_path(token, &request.path) else {
+        return write_response(writer, Response::new(404), false).await;
+    };
+
+    let head_only = request.method == "HEAD";
+
+    let response = match request.method.as_str() {
+        "OPTIONS" => Response::new(200)
+            .header("DAV", "1, 2")
+            .header("MS-Author-Via", "DAV")
+            .header("Allow", ALLOWED_METHODS),
+        "PROPFIND" => {
+            body.read_to_end(reader).await?;
+            propfind(client, token, path, request.header("depth")).await?
+        }
+        "PROPPATCH" => {
+            body.read_to_end(reader).await?;
+            proppatch(client, token, path).await?
+        }
+        "GET" | "HEAD" => {
+            return get(client, path, request.header("range"), head_only, writer).await;
+        }
+        "PUT" => put(client, path, body, reader).await?,
+        "DELETE" => match metadata(client, path.clone()).await? {
+            Some(_) => {
+                fs_operation(client, FsOperation::Remove(path)).await?;
+                Response::new(204)
+            }
+            None => Response::new(404),
+        },
+        "MKCOL" => {
+            body.read_to_end(reader).await?;
+
+            if metadata(client, path.clone()).await?.is_some() {
+                Response::new(405)
+            } else {
+                fs_operation(client, FsOperation::CreateDirectory(path)).await?;
+                Response::new(201)
+            }
+        }
+        "MOVE" => {
+            let overwrite = request
+                .header("overwrite")
+                .map(|overwrite| !overwrite.eq_ignore_ascii_case("F"))
+                .unwrap_or(true);
+
+            match request
+                .header("destination")
+                .and_then(|destination| remote_path(token, url_path(destination)))
+            {
+                Some(destination) => move_entry(client, path, destination, overwrite).await?,
+                None => Response::new(400),
+            }
+        }
+        "LOCK" => {
+            body.read_to_end(reader).await?;
+            lock(request.header("timeout"))
+        }
+        "UNLOCK" => Response::new(204),
+        _ => Response::new(405).header("Allow", ALLOWED_METHODS),
+    };
+
+    write_response(writer, response, head_only).await
+}
+
+async fn propfind(
+    client: &EndPointClient,
+    token: &str,
+    path: PathBuf,
+    depth: Option<&str>,
+) -> CoreResult<Response> {
+    let Some(entry) = metadata(client, path.clone()).await? else {
+        return Ok(Response::new(404));
+    };
+
+    let mut entries = vec![entry.clone()];
+
+    if entry.is_dir && depth != Some("0") {
+        match fs_operation(client, FsOperation::ReadDirectory(path)).await? {
+            EndPointFsOperationReply::Directory(children) => entries.extend(children),
+            _ => return Err(core_error!("unexpected fs operation reply")),
+        }
+    }
+
+    let mut xml =
+        String::from(r#"<?xml version="1.0" encoding="utf-8"?><D:multistatus xmlns:D="DAV:">"#);
+
+    for entry in entries {
+        let name = entry
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| entry.path.to_string_lossy().into_owned());
+
+        let modified = chrono::Utc
+            .timestamp_opt(entry.modified_time, 0)
+            .single()
+            .map(|modified| modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+            .unwrap_or_default();
+
+        let resource_type = if entry.is_dir { "<D:collection/>" } else { "" };
+
+        xml.push_str(&format!(
+            "<D:response><D:href>{}</D:href><D:propstat><D:prop>\
+            <D:displayname>{}</D:displayname>\
+            <D:resourcetype>{}</D:resourcetype>\
+            <D:getcontentlength>{}</D:getcontentlength>\
+            <D:getlastmodified>{}</D:getlastmodified>\
+            </D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>",
+            xml_escape(&href(token, &entry)),
+            xml_escape(&name),
+            resource_type,
+            entry.size,
+            modified
+        ));
+    }
+
+    xml.push_str("</D:multistatus>");
+
+    Ok(Response::new(207).body("application/xml; charset=utf-8", xml.into_bytes()))
+}
+
+// file explorers set their own properties like times, which are accepted and ignored
+async fn proppatch(client: &EndPointClient, token: &str, path: PathBuf) -> CoreResult<Response> {
+    let Some(entry) = metadata(client, path).await? else {
+        return Ok(Response::new(404));
+    };
+
+    let xml = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><D:multistatus xmlns:D="DAV:"><D:response><D:href>{}</D:href><D:propstat><D:prop/><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response></D:multistatus>"#,
+        xml_escape(&href(token, &entry))
+    );
+
+    Ok(Response::new(207).body("application/xml; charset=utf-8", xml.into_bytes()))
+}
+
+async fn get<W>(
+    client: &EndPointClient,
+    path: PathBuf,
+    range: Option<&str>,
+    head_only: bool,
+    writer: &mut W,
+) -> CoreResult<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let entry = match metadata(client, path.clone()).await? {
+        Some(entry) if !entry.is_dir => entry,
+        Some(_) => return write_response(writer, Response::new(405), head_only).await,
+        None => return write_response(writer, Response::new(404), head_only).await,
+    };
+
+    let (status, start, end) = match range.map(|range| parse_range(range, entry.size)) {
+        Some(Some((start, end))) => (206, start, end),
+        Some(None) => {
+            let response =
+                Response::new(416).header("Content-Range", format!("bytes */{}", entry.size));
+            return write_response(writer, response, head_only).await;
+        }
+        None => (200, 0, entry.size),
+    };
+
+    let mut headers = vec![
+        ("Content-Type", String::from("application/octet-stream")),
+        ("Accept-Ranges", String::from("bytes")),
+    ];
+
+    if status == 206 {
+        headers.push((
+            "Content-Range",
+            format!("bytes {}-{}/{}", start, end - 1, entry.size),
+        ));
+    }
+
+    write_head(writer, status, &headers, end - start).await?;
+
+    if head_only {
+        writer.flush().await?;
+        return Ok(());
+    }
+
+    let mut offset = start;
+    while offset < end {
+        let len = (end - offset).min(TRANSFER_CHUNK_SIZE as u64) as u32;
+
+        let data = match fs_operation(
+            client,
+            FsOperation::Read {
+                path: path.clone(),
+                offset,
+                len,
+            },
+        )
+        .await?
+        {
+            EndPointFsOperationReply::Data(data) if !data.is_empty() => data,
+            // the file is shrunk during reading, the response can't be completed
+            _ => return Err(core_error!("read remote file ended unexpectedly")),
+        };
+
+        offset += data.len() as u64;
+        writer.write_all(&data).await?;
+    }
+
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn put<R>(
+    client: &EndPointClient,
+    path: PathBuf,
+    body: &mut BodyReader,
+    reader: &mut R,
+) -> CoreResult<Response>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let existed = match metadata(client, path.clone()).await? {
+        Some(entry) if entry.is_dir => return Ok(Response::new(405)),
+        Some(_) => true,
+        None => false,
+    };
+
+    let mut offset = 0;
+    loop {
+        let data = body.read(reader, TRANSFER_CHUNK_SIZE).await?;
+        let len = data.len() as u64;
+
+        // the first write creates or truncates the file even if the body is empty
+        if data.is_empty() && offset > 0 {
+            break;
+        }
+
+        fs_operation(
+            client,
+            FsOperation::Write {
+                path: path.clone(),
+                offset,
+                data,
+                truncate: offset == 0,
+            },
+        )
+        .await?;
+
+        if len == 0 {
+            break;
+        }
+
+        offset += len;
+    }
+
+    Ok(Response::new(if existed { 204 } else { 201 }))
+}
+
+async fn move_entry(
+    client: &EndPointClient,
+    from: PathBuf,
+    to: PathBuf,
+    overwrite: bool,
+) -> CoreResult<Response> {
+    if metadata(client, from.clone()).await?.is_none() {
+        return Ok(Response::new(404));
+    }
+
+    let existed = metadata(client, to.clone()).await?.is_some();
+    if existed {
+        if !overwrite {
+            return Ok(Response::new(412));
+        }
+
+        fs_operation(client, FsOperation::Remove(to.clone())).await?;
+    }
+
+    fs_operation(client, FsOperation::Rename { from, to }).await?;
+
+    Ok(Response::new(if existed { 204 } else { 201 }))
+}
+
+// locks are not enforced, but clients like Finder mount read-only without them
+fn lock(timeout: Option<&str>) -> Response {
+    let lock_token = format!("opaquelocktoken:{}", uuid::Uuid::new_v4());
+    let timeout = timeout.unwrap_or("Second-3600");
+
+    let xml = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><D:prop xmlns:D="DAV:"><D:lockdiscovery><D:activelock><D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope><D:depth>infinity</D:depth><D:timeout>{}</D:timeout><D:locktoken><D:href>{}</D:href></D:locktoken></D:activelock></D:lockdiscovery></D:prop>"#,
+        xml_escape(timeout),
+        lock_token
+    );
+
+    Response::new(200)
+        .header("Lock-Token", format!("<{lock_token}>"))
+        .body("application/xml; charset=utf-8", xml.into_bytes())
+}
+
+async fn metadata(client: &EndPointClient, path: PathBuf) -> CoreResult<Option<FsEntry>> {
+    match fs_operation(client, FsOperation::Metadata(path)).await? {
+        EndPointFsOperationReply::Metadata(entry) => Ok(entry),
+        _ => Err(core_error!("unexpected fs operation reply")),
+    }
+}
+
+async fn fs_operation(
+    client: &EndPointClient,
+    op: FsOperation,
+) -> CoreResult<EndPointFsOperationReply> {
+    client
+        .call(EndPointCallRequest::FsOperationRequest(
+            EndPointFsOperationRequest { op },
+        ))
+        .await
+}
+
+/// Map the url path under token to the path at remote, the first segment like `C:` is
+/// the drive of Windows.
+fn remote_path(token: &str, url_path: &str) -> Option<PathBuf> {
+    let rest = url_path.strip_prefix('/')?.strip_prefix(token)?;
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return None;
+    }
+
+    let rest = percent_decode(rest);
+    let segments: Vec<&str> = rest
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    // never walk out of what the url names
+    if segments.iter().any(|segment| *segment == "..") {
+        return None;
+    }
+
+    let path = match segments.split_first() {
+        Some((drive, rest)) if is_drive(drive) => format!("{}/{}", drive, rest.join("/")),
+        _ => format!("/{}", segments.join("/")),
+    };
+
+    Some(PathBuf::from(path))
+}
+
+fn href(token: &str, entry: &FsEntry) -> String {
+    let path = entry.path.to_string_lossy().replace('\\', "/");
+    let path = path.trim_matches('/');
+
+    let mut href = format!("/{token}/{path}");
+    if entry.is_dir && !path.is_empty() {
+        href.push('/');
+    }
+
+    percent_encode(&href)
+}
+
+fn is_drive(segment: &str) -> bool {
+    let bytes = segment.as_bytes();
+    bytes.len() == 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+// destination header is an absolute url
+fn url_path(url: &str) -> &str {
+    match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map(|index| &rest[index..]).unwrap_or("/"),
+        None => url,
+    }
+}
+
+// only a single range is supported, returns the half-open range
+fn parse_range(range: &str, size: u64) -> Option<(u64, u64)> {
+    let range = range.trim().strip_prefix("bytes=")?;
+    if range.contains(',') {
+        return None;
+    }
+
+    let (start, end) = range.split_once('-')?;
+
+    let (start, end) = if start.is_empty() {
+        // the last `end` bytes
+        let suffix: u64 = end.parse().ok()?;
+        (size.saturating_sub(suffix), size)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = if end.is_empty() {
+            size
+        } else {
+            end.parse::<u64>().ok()?.saturating_add(1).min(size)
+        };
+        (start, end)
+    };
+
+    if start >= end {
+        return None;
+    }
+
+    Some((start, end))
+}
@@ -0,0 +1,232 @@
+//! Block-diff of a file against the older copy which remote already has, like rsync.
+//! Receiver splits its copy into blocks and replies the checksums of each block, sender
+//! rolls a window over the new file and only sends bytes which match no block.
+
+use crate::{core_error, error::CoreResult};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+const MIN_BLOCK_SIZE: u64 = 4 * 1024;
+const MAX_BLOCK_SIZE: u64 = 1024 * 1024;
+
+// literal bytes are sent in pieces no larger than a file transfer block
+const MAX_LITERAL_SIZE: usize = 64 * 1024;
+
+// bytes of the sha256 digest which are kept as the strong checksum
+const STRONG_CHECKSUM_SIZE: usize = 16;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct FileSignature {
+    pub block_size: u32,
+    // only whole blocks, the tail of file which is shorter than a block is never reused
+    pub blocks: Vec<BlockSignature>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct BlockSignature {
+    pub weak: u32,
+    #[serde(with = "serde_bytes")]
+    pub strong: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum DeltaOp {
+    // copy the block of `index` from the older copy
+    Copy(u64),
+    Literal(#[serde(with = "serde_bytes")] Vec<u8>),
+}
+
+impl DeltaOp {
+    /// Bytes of the new file which the op produces.
+    pub fn output_size(&self, block_size: u32) -> u64 {
+        match self {
+            DeltaOp::Copy(_) => block_size as u64,
+            DeltaOp::Literal(data) => data.len() as u64,
+        }
+    }
+}
+
+/// Adler-32 like checksum which slides over the file one byte at a time.
+struct RollingChecksum {
+    a: u32,
+    b: u32,
+    len: u32,
+}
+
+impl RollingChecksum {
+    fn new(window: &[u8]) -> Self {
+        let mut a: u32 = 0;
+        let mut b: u32 = 0;
+
+        for (i, byte) in window.iter().enumerate() {
+            a = a.wrapping_add(*byte as u32);
+            b = b.wrapping_add((window.len() - i) as u32 * *byte as u32);
+        }
+
+        RollingChecksum {
+            a: a & 0xffff,
+            b: b & 0xffff,
+            len: window.len() as u32,
+        }
+    }
+
+    fn roll(&mut self, out_byte: u8, in_byte: u8) {
+        self.a = self
+            .a
+            .wrapping_sub(out_byte as u32)
+            .wrapping_add(in_byte as u32)
+            & 0xffff;
+
+        self.b = self
+            .b
+            .wrapping_sub(self.len.wrapping_mul(out_byte as u32))
+            .wrapping_add(self.a)
+            & 0xffff;
+    }
+
+    fn digest(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+/// Block size for a file of `size`, the square root of size keeps both signature and
+/// delta small like rsync does.
+pub fn block_size_for(size: u64) -> u32 {
+    ((size as f64).sqrt() as u64).clamp(MIN_BLOCK_SIZE, MAX_BLOCK_SIZE) as u32
+}
+
+/// Compute checksums of each block of the older copy at `path`. It reads the whole file
+/// so it should run in a blocking thread.
+pub fn compute_signature(path: &Path) -> CoreResult<FileSignature> {
+    let size = path.metadata()?.len();
+    let block_size = block_size_for(size);
+
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut block = vec![0u8; block_size as usize];
+    let mut blocks = Vec::with_capacity((size / block_size as u64) as usize);
+
+    for _ in 0..size / block_size as u64 {
+        reader.read_exact(&mut block)?;
+
+        blocks.push(BlockSignature {
+            weak: RollingChecksum::new(&block).digest(),
+            strong: strong_checksum(&block),
+        });
+    }
+
+    Ok(FileSignature { block_size, blocks })
+}
+
+/// Compare the new file at `path` with `signature` of the older copy, `emit` is called
+/// with ops in order which rebuild the new file from the older copy. It reads the whole
+/// file so it should run in a blocking thread.
+pub fn compute_delta<F>(path: &Path, signature: &FileSignature, mut emit: F) -> CoreResult<()>
+where
+    F: FnMut(DeltaOp) -> CoreResult<()>,
+{
+    let block_size = signature.block_size as usize;
+
+    let mut weak_index: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (index, block) in signature.blocks.iter().enumerate() {
+        weak_index.entry(block.weak).or_default().push(index);
+    }
+
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut buffer: Vec<u8> = Vec::with_capacity(block_size * 4);
+    let mut start = 0;
+    let mut literal: Vec<u8> = Vec::with_capacity(MAX_LITERAL_SIZE);
+    let mut rolling: Option<RollingChecksum> = None;
+
+    loop {
+        // drop the bytes which have been passed to keep memory bounded
+        if start >= block_size * 2 {
+            buffer.drain(..start);
+            start = 0;
+        }
+
+        if !fill_buffer(&mut reader, &mut buffer, start + block_size)? {
+            break;
+        }
+
+        let window = &buffer[start..start + block_size];
+        let checksum = rolling.get_or_insert_with(|| RollingChecksum::new(window));
+
+        if let Some(index) = weak_index.get(&checksum.digest()).and_then(|indexes| {
+            let strong = strong_checksum(window);
+            indexes
+                .iter()
+                .find(|index| signature.blocks[**index].strong == strong)
+        }) {
+            if !literal.is_empty() {
+                emit(DeltaOp::Literal(std::mem::take(&mut literal)))?;
+            }
+
+            emit(DeltaOp::Copy(*index as u64))?;
+            start += block_size;
+            rolling = None;
+            continue;
+        }
+
+        literal.push(buffer[start]);
+        if literal.len() >= MAX_LITERAL_SIZE {
+            emit(DeltaOp::Literal(std::mem::take(&mut literal)))?;
+        }
+
+        if fill_buffer(&mut reader, &mut buffer, start + block_size + 1)? {
+            if let Some(checksum) = rolling.as_mut() {
+                checksum.roll(buffer[start], buffer[start + block_size]);
+            }
+        } else {
+            rolling = None;
+        }
+
+        start += 1;
+    }
+
+    // the tail which is shorter than a block
+    literal.extend_from_slice(&buffer[start..]);
+    for chunk in literal.chunks(MAX_LITERAL_SIZE) {
+        emit(DeltaOp::Literal(chunk.to_vec()))?;
+    }
+
+    Ok(())
+}
+
+/// Read the block of `index` from the older copy.
+pub fn read_block(file: &mut File, block_size: u32, index: u64) -> CoreResult<Vec<u8>> {
+    // index comes from the remote, an offset beyond u64 is rejected
+    let offset = index
+        .checked_mul(block_size as u64)
+        .ok_or(core_error!("block index out of range"))?;
+
+    let mut block = vec![0u8; block_size as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut block)?;
+    Ok(block)
+}
+
+fn strong_checksum(block: &[u8]) -> Vec<u8> {
+    Sha256::digest(block)[..STRONG_CHECKSUM_SIZE].to_vec()
+}
+
+// read until buffer has `len` bytes, returns false if file ends before that
+fn fill_buffer(reader: &mut impl Read, buffer: &mut Vec<u8>, len: usize) -> CoreResult<bool> {
+    let mut chunk = [0u8; 64 * 1024];
+
+    while buffer.len() < len {
+        let n = reader.read(&mut chunk)?;
+        if n == 0 {
+            return Ok(false);
+        }
+
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,185 @@
+//! Both ends of a transfer list the paths, sizes and hashes of what they have, the
+//! manifests are compared after transfer to prove the copy is complete and intact.
+
+use crate::error::CoreResult;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ManifestEntry {
+    // relative to the root of transfer and separated by '/' whatever the platform is,
+    // empty when the root is a file
+    pub path: String,
+    pub size: u64,
+    // hex of sha256
+    pub hash: String,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationStatus {
+    Matched,
+    SizeMismatched,
+    HashMismatched,
+    MissingLocal,
+    MissingRemote,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq, Clone)]
+pub struct VerificationEntry {
+    pub path: PathBuf,
+    pub status: VerificationStatus,
+    pub local_size: Option<u64>,
+    pub remote_size: Option<u64>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct VerificationReport {
+    pub entries: Vec<VerificationEntry>,
+    // bytes of matched files
+    pub verified_bytes: u64,
+    pub intact: bool,
+}
+
+impl VerificationReport {
+    /// Merge the report of another root of the same transfer.
+    pub fn merge(&mut self, other: VerificationReport) {
+        self.entries.extend(other.entries);
+        self.verified_bytes += other.verified_bytes;
+        self.intact = self
+            .entries
+            .iter()
+            .all(|entry| entry.status == VerificationStatus::Matched);
+    }
+}
+
+/// List every file under `root` with its size and hash, `root` itself is listed when it's
+/// a file and nothing is listed when it doesn't exist. It reads all files so it should run
+/// in a blocking thread.
+pub fn build_manifest(root: &Path) -> CoreResult<Vec<ManifestEntry>> {
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    collect_files(root, &mut files)?;
+
+    let mut entries = files
+        .into_par_iter()
+        .map(|path| {
+            let size = path.metadata()?.len();
+            let hash = hash_file(&path)?;
+            let path = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            Ok(ManifestEntry { path, size, hash })
+        })
+        .collect::<CoreResult<Vec<ManifestEntry>>>()?;
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(entries)
+}
+
+/// Compare manifests of the same root from both ends, paths of entries in report are
+/// joined to `local_root`.
+pub fn compare_manifests(
+    local_root: &Path,
+    local: Vec<ManifestEntry>,
+    remote: Vec<ManifestEntry>,
+) -> VerificationReport {
+    let mut pairs: BTreeMap<String, (Option<ManifestEntry>, Option<ManifestEntry>)> =
+        BTreeMap::new();
+
+    for entry in local {
+        let path = entry.path.clone();
+        pairs.entry(path).or_default().0 = Some(entry);
+    }
+
+    for entry in remote {
+        let path = entry.path.clone();
+        pairs.entry(path).or_default().1 = Some(entry);
+    }
+
+    let mut report = VerificationReport::default();
+
+    for (path, (local, remote)) in pairs {
+        let status = match (&local, &remote) {
+            (Some(local), Some(remote)) if local.size != remote.size => {
+                VerificationStatus::SizeMismatched
+            }
+            (Some(local), Some(remote)) if local.hash != remote.hash => {
+                VerificationStatus::HashMismatched
+            }
+            (Some(_), Some(_)) => VerificationStatus::Matched,
+            (Some(_), None) => VerificationStatus::MissingRemote,
+            _ => VerificationStatus::MissingLocal,
+        };
+
+        if status == VerificationStatus::Matched {
+            report.verified_bytes += local.as_ref().map_or(0, |local| local.size);
+        }
+
+        let path = if path.is_empty() {
+            local_root.to_path_buf()
+        } else {
+            local_root.join(path)
+        };
+
+        report.entries.push(VerificationEntry {
+            path,
+            status,
+            local_size: local.map(|local| local.size),
+            remote_size: remote.map(|remote| remote.size),
+        });
+    }
+
+    report.intact = report
+        .entries
+        .iter()
+        .all(|entry| entry.status == VerificationStatus::Matched);
+
+    report
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> CoreResult<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    for entry in std::fs::read_dir(path)? {
+        collect_files(&entry?.path(), files)?;
+    }
+
+    Ok(())
+}
+
+fn hash_file(path: &Path) -> CoreResult<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..n]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
@@ -4,9 +4,17 @@ mod macos;
 #[cfg(target_os = "windows")]
 mod windows;
 
+pub mod bridge;
+pub mod delta;
+pub mod manifest;
+pub mod receive_policy;
+pub mod staging;
 pub mod transfer;
 
-use crate::error::CoreResult;
+use crate::{
+    component::progress::{ProgressKind, ProgressTracker},
+    error::CoreResult,
+};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -63,14 +71,31 @@ pub fn read_root_directory() -> CoreResult<Directory> {
 }
 
 fn read_icon(path: &Path) -> CoreResult<Vec<u8>> {
-    #[cfg(not(target_os = "windows"))]
+    #[cfg(target_os = "macos")]
     return self::macos::NSWorkspace::sharedWorkspace()?.iconForFile(path);
 
     #[cfg(target_os = "windows")]
     return self::windows::read_icon(path);
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    return Err(crate::core_error!(
+        "read icon is not supported on current platform"
+    ));
 }
 
 pub fn read_directory<P>(path: P) -> CoreResult<Directory>
+where
+    P: AsRef<Path> + Into<PathBuf>,
+{
+    read_directory_with_progress(path, None)
+}
+
+/// Read the directory and report progress of reading entry icons under `operation_id`,
+/// which takes most of the time for large directories.
+pub fn read_directory_with_progress<P>(
+    path: P,
+    operation_id: Option<String>,
+) -> CoreResult<Directory>
 where
     P: AsRef<Path> + Into<PathBuf>,
 {
@@ -121,6 +146,15 @@ where
         });
     }
 
+    let progress = operation_id.map(|operation_id| {
+        ProgressTracker::new(
+            operation_id,
+            ProgressKind::DirectoryListing,
+            Some(entry_stats.len() as u64),
+            0,
+        )
+    });
+
     let entries: Vec<Entry> = entry_stats
         .into_par_iter()
         .map(|entry| {
@@ -158,6 +192,10 @@ where
                 }
             };
 
+            if let Some(ref progress) = progress {
+                progress.advance(1);
+            }
+
             Entry {
                 is_dir: entry.is_dir,
                 path: entry.path,
@@ -194,6 +232,10 @@ where
         .map(|(_, hashed_icon, icon_bytes)| (hashed_icon, icon_bytes))
         .collect();
 
+    if let Some(progress) = progress {
+        progress.finish(true);
+    }
+
     Ok(Directory {
         path: path.into(),
         entries,
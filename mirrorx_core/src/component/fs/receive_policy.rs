@@ -0,0 +1,260 @@
+//! What this device accepts from the files which remote sends. Files of blocked extensions
+//! or larger than the limit are refused before anything is written. With quarantine on,
+//! received files are kept in the quarantine directory under app data dir instead of
+//! where remote sends them, they're moved there only after user confirms. Files which are
+//! still in quarantine when the app exits are discarded at the next start. Files which
+//! remote writes in place through the file bridge are checked as well, and refused while
+//! quarantine is on.
+
+use super::transfer::publish_transfer_result;
+use crate::{core_error, error::CoreResult};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+};
+
+static RECEIVE_POLICY: Lazy<RwLock<ReceivePolicy>> =
+    Lazy::new(|| RwLock::new(ReceivePolicy::default()));
+
+static QUARANTINE_ROOT: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+
+// keyed by transfer id, files being received are kept here as well
+static QUARANTINED_FILES: Lazy<Mutex<HashMap<String, QuarantinedFile>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceivePolicy {
+    /// Extensions without the leading dot, they're matched case-insensitively.
+    pub blocked_extensions: Vec<String>,
+    /// None means no limit.
+    pub max_size_mb: Option<u32>,
+    pub quarantine: bool,
+}
+
+impl ReceivePolicy {
+    pub fn validate(&self) -> CoreResult<()> {
+        if self.max_size_mb == Some(0) {
+            return Err(core_error!("max size must be positive"));
+        }
+
+        for extension in &self.blocked_extensions {
+            let extension = extension.trim_start_matches('.');
+            if extension.is_empty() || extension.contains(['/', '\\']) {
+                return Err(core_error!("invalid extension '{}'", extension));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn blocks(&self, filename: &str) -> bool {
+        // Windows drops the trailing dots and spaces, "a.exe. " is still an executable
+        let filename = filename.trim_end_matches(['.', ' ']).to_lowercase();
+        let Some(extension) = Path::new(&filename).extension() else {
+            return false;
+        };
+
+        let extension = extension.to_string_lossy();
+        self.blocked_extensions
+            .iter()
+            .any(|blocked| blocked.trim_start_matches('.').to_lowercase() == extension)
+    }
+}
+
+/// The file which is received into quarantine.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantinedFile {
+    pub id: String,
+    /// Where remote sends it, it's moved there once user releases it.
+    pub destination: PathBuf,
+    pub size: u64,
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    received: bool,
+}
+
+/// Set the policy of received files, only the transfers requested later pick it up.
+pub fn set_receive_policy(policy: ReceivePolicy) {
+    *RECEIVE_POLICY.write().unwrap() = policy;
+}
+
+pub fn receive_policy() -> ReceivePolicy {
+    RECEIVE_POLICY.read().unwrap().clone()
+}
+
+/// Set the root of quarantine and discard the files which were left in it.
+pub fn init_quarantine_area(root: PathBuf) -> CoreResult<()> {
+    if root.exists() {
+        std::fs::remove_dir_all(&root)?;
+    }
+
+    std::fs::create_dir_all(&root)?;
+    *QUARANTINE_ROOT.lock().unwrap() = Some(root);
+
+    Ok(())
+}
+
+/// Refuse the file which the policy doesn't accept.
+pub(crate) fn check_receive(filename: &str, size: u64) -> CoreResult<()> {
+    let policy = RECEIVE_POLICY.read().unwrap();
+
+    if policy.blocks(filename) {
+        return Err(core_error!("file type is not accepted by remote"));
+    }
+
+    if let Some(max_size_mb) = policy.max_size_mb {
+        if size > max_size_mb as u64 * 1024 * 1024 {
+            return Err(core_error!(
+                "file is larger than remote accepts (size: {}, max: {}MB)",
+                size,
+                max_size_mb
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Where the file sent to `destination` is written when quarantine is on, it's None
+/// otherwise.
+pub(crate) fn quarantine_path(
+    id: &str,
+    destination: &Path,
+    size: u64,
+) -> CoreResult<Option<PathBuf>> {
+    if !RECEIVE_POLICY.read().unwrap().quarantine {
+        return Ok(None);
+    }
+
+    let filename = destination
+        .file_name()
+        .ok_or(core_error!("invalid filename"))?;
+
+    let mut files = QUARANTINED_FILES.lock().unwrap();
+    if files.contains_key(id) {
+        return Err(core_error!("transfer id is in use"));
+    }
+
+    // transfer id is given by remote, the directory which keeps files of the same name
+    // apart is named locally so it never leaves quarantine
+    let dir = quarantine_root()?.join(uuid::Uuid::new_v4().to_string());
+    std::fs::create_dir(&dir)?;
+    let path = dir.join(filename);
+
+    files.insert(
+        id.to_string(),
+        QuarantinedFile {
+            id: id.to_string(),
+            destination: destination.to_path_buf(),
+            size,
+            path: path.clone(),
+            received: false,
+        },
+    );
+
+    Ok(Some(path))
+}
+
+/// Mark the file of transfer `id` received when it succeeds, otherwise discard it. Returns
+/// the file when it's received into quarantine.
+pub(crate) fn finish_quarantine(id: &str, succeed: bool) -> Option<QuarantinedFile> {
+    let mut files = QUARANTINED_FILES.lock().unwrap();
+
+    if succeed {
+        let file = files.get_mut(id)?;
+        file.received = true;
+        Some(file.clone())
+    } else {
+        let file = files.remove(id)?;
+        remove_quarantine_dir(&file);
+        Some(file)
+    }
+}
+
+/// Files which are received and wait for user to confirm.
+pub fn quarantined_files() -> Vec<QuarantinedFile> {
+    let mut files: Vec<QuarantinedFile> = QUARANTINED_FILES
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|file| file.received)
+        .cloned()
+        .collect();
+
+    files.sort_by(|a, b| a.id.cmp(&b.id));
+    files
+}
+
+/// Move the quarantined file to where remote sent it, returns the destination.
+pub fn release_quarantined_file(id: &str) -> CoreResult<PathBuf> {
+    let file = take_received(id)?;
+
+    // it's kept when it can't be moved, user can discard it or release it again
+    if file.destination.exists() {
+        restore(file);
+        return Err(core_error!("file already exists"));
+    }
+
+    // quarantine may be on another volume than the destination
+    let moved = std::fs::rename(&file.path, &file.destination)
+        .or_else(|_| std::fs::copy(&file.path, &file.destination).map(|_| ()));
+
+    if let Err(err) = moved {
+        restore(file);
+        return Err(err.into());
+    }
+
+    remove_quarantine_dir(&file);
+
+    tracing::info!(id, destination = ?file.destination, "release quarantined file");
+
+    publish_transfer_result(file.id, file.destination.clone(), true);
+
+    Ok(file.destination)
+}
+
+pub fn discard_quarantined_file(id: &str) -> CoreResult<()> {
+    let file = take_received(id)?;
+    remove_quarantine_dir(&file);
+
+    tracing::info!(id, destination = ?file.destination, "discard quarantined file");
+
+    Ok(())
+}
+
+fn take_received(id: &str) -> CoreResult<QuarantinedFile> {
+    let mut files = QUARANTINED_FILES.lock().unwrap();
+
+    match files.get(id) {
+        Some(file) if file.received => Ok(files.remove(id).unwrap()),
+        Some(_) => Err(core_error!("file is still being received")),
+        None => Err(core_error!("quarantined file not found")),
+    }
+}
+
+fn restore(file: QuarantinedFile) {
+    QUARANTINED_FILES
+        .lock()
+        .unwrap()
+        .insert(file.id.clone(), file);
+}
+
+fn remove_quarantine_dir(file: &QuarantinedFile) {
+    if let Some(dir) = file.path.parent() {
+        if let Err(err) = std::fs::remove_dir_all(dir) {
+            tracing::warn!(?err, ?dir, "remove quarantine directory failed");
+        }
+    }
+}
+
+fn quarantine_root() -> CoreResult<PathBuf> {
+    QUARANTINE_ROOT
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or(core_error!("quarantine area not initialize"))
+}
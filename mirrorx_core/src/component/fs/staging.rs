@@ -0,0 +1,190 @@
+//! Files which are pasted or dropped into a session have no destination chosen by user,
+//! they're saved in the staging directory of the session under app data dir. The whole
+//! staging area is limited by quota, and directories of ended sessions are removed once
+//! they have been kept for the retention days.
+
+use crate::{core_error, error::CoreResult};
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+// the end time of session is written in it, directories without it are of crashed sessions
+const SESSION_ENDED_MARKER: &str = ".session_ended";
+
+static STAGING_ROOT: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+
+// sessions of this process whose staging directories are in use
+static ACTIVE_SESSIONS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+static QUOTA_BYTES: AtomicU64 = AtomicU64::new(1024 * 1024 * 1024);
+
+static RETENTION_DAYS: AtomicU32 = AtomicU32::new(7);
+
+/// Set the root of staging area and remove the expired directories in it.
+pub fn init_staging_area(root: PathBuf) -> CoreResult<()> {
+    std::fs::create_dir_all(&root)?;
+    *STAGING_ROOT.lock().unwrap() = Some(root);
+
+    cleanup_expired_staging()
+}
+
+pub fn set_staging_quota(quota_bytes: u64) {
+    QUOTA_BYTES.store(quota_bytes, Ordering::SeqCst);
+}
+
+pub fn set_staging_retention_days(days: u32) {
+    RETENTION_DAYS.store(days, Ordering::SeqCst);
+}
+
+pub fn staging_root() -> CoreResult<PathBuf> {
+    STAGING_ROOT
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or(core_error!("staging area not initialize"))
+}
+
+/// Bytes of all files in the staging area.
+pub fn staging_usage() -> CoreResult<u64> {
+    dir_size(&staging_root()?)
+}
+
+/// The staging directory of `session_id`, it's created at the first time.
+pub fn session_staging_dir(session_id: &str) -> CoreResult<PathBuf> {
+    let dir = staging_root()?.join(session_id);
+    std::fs::create_dir_all(&dir)?;
+
+    ACTIVE_SESSIONS
+        .lock()
+        .unwrap()
+        .insert(session_id.to_string());
+
+    Ok(dir)
+}
+
+/// Check the staging area has room for `size` more bytes.
+pub fn reserve_staging_space(size: u64) -> CoreResult<()> {
+    let quota = QUOTA_BYTES.load(Ordering::SeqCst);
+    let usage = staging_usage()?;
+
+    if usage.saturating_add(size) > quota {
+        return Err(core_error!(
+            "staging area quota exceeded (usage: {}, quota: {}, required: {})",
+            usage,
+            quota,
+            size
+        ));
+    }
+
+    Ok(())
+}
+
+/// Remove the staging directories of ended sessions, ones of active sessions are kept.
+pub fn clear_staging_area() -> CoreResult<()> {
+    let root = staging_root()?;
+    let active_sessions = ACTIVE_SESSIONS.lock().unwrap().clone();
+
+    for entry in std::fs::read_dir(root)? {
+        let entry = entry?;
+        if active_sessions.contains(&*entry.file_name().to_string_lossy()) {
+            continue;
+        }
+
+        remove_entry(&entry.path());
+    }
+
+    Ok(())
+}
+
+/// Record the end of session, its staging directory is expired after the retention days.
+pub(crate) fn session_ended(session_id: &str) {
+    if !ACTIVE_SESSIONS.lock().unwrap().remove(session_id) {
+        return;
+    }
+
+    let Ok(root) = staging_root() else {
+        return;
+    };
+
+    let marker_path = root.join(session_id).join(SESSION_ENDED_MARKER);
+    let ended_at = chrono::Utc::now().timestamp().to_string();
+
+    if let Err(err) = std::fs::write(marker_path, ended_at) {
+        tracing::error!(?err, "write session ended marker failed");
+    }
+
+    if let Err(err) = cleanup_expired_staging() {
+        tracing::error!(?err, "cleanup expired staging area failed");
+    }
+}
+
+fn cleanup_expired_staging() -> CoreResult<()> {
+    let root = staging_root()?;
+    let active_sessions = ACTIVE_SESSIONS.lock().unwrap().clone();
+    let retention_secs = RETENTION_DAYS.load(Ordering::SeqCst) as i64 * 24 * 60 * 60;
+    let now = chrono::Utc::now().timestamp();
+
+    for entry in std::fs::read_dir(root)? {
+        let entry = entry?;
+        if active_sessions.contains(&*entry.file_name().to_string_lossy()) {
+            continue;
+        }
+
+        let path = entry.path();
+        let ended_at = std::fs::read_to_string(path.join(SESSION_ENDED_MARKER))
+            .ok()
+            .and_then(|ended_at| ended_at.trim().parse::<i64>().ok())
+            // the session crashed, count from the last time its directory changed
+            .or_else(|| modified_time(&path));
+
+        match ended_at {
+            Some(ended_at) if now - ended_at < retention_secs => {}
+            _ => {
+                tracing::info!(?path, "remove expired staging directory");
+                remove_entry(&path);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn remove_entry(path: &Path) {
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+
+    if let Err(err) = result {
+        tracing::error!(?err, ?path, "remove staging entry failed");
+    }
+}
+
+fn modified_time(path: &Path) -> Option<i64> {
+    let modified = path.metadata().ok()?.modified().ok()?;
+    Some(chrono::DateTime::<chrono::Utc>::from(modified).timestamp())
+}
+
+fn dir_size(path: &Path) -> CoreResult<u64> {
+    let mut size = 0;
+
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+
+    Ok(size)
+}
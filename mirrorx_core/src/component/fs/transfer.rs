@@ -1,15 +1,37 @@
 use crate::{
-    api::endpoint::{
-        client::EndPointClient,
-        message::{EndPointFileTransferBlock, EndPointFileTransferError, EndPointMessage},
+    api::{
+        endpoint::{
+            client::EndPointClient,
+            message::{
+                EndPointFileDeltaBlock, EndPointFileTransferBlock, EndPointFileTransferError,
+                EndPointMessage,
+            },
+        },
+        notification::{self, Notification},
     },
+    component::{
+        fs::{
+            delta::{self, DeltaOp, FileSignature},
+            receive_policy::finish_quarantine,
+        },
+        governor,
+        progress::{ProgressKind, ProgressTracker},
+    },
+    core_error,
     error::CoreResult,
+    service::plugin::{self, SessionEvent},
+    utility::spawn,
 };
 use moka::future::{Cache, CacheBuilder};
 use once_cell::sync::Lazy;
-use std::{path::Path, sync::Arc, time::Duration};
+use std::{
+    io::{SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, BufWriter},
     sync::mpsc::{UnboundedReceiver, UnboundedSender},
 };
 
@@ -19,18 +41,26 @@ pub static APPEND_FILES: Lazy<Cache<String, UnboundedSender<Option<Vec<u8>>>>> =
         .build()
 });
 
-pub static BYTES_TRANSFERRED_CACHE: Lazy<Cache<String, u64>> = Lazy::new(|| {
+pub static PATCH_FILES: Lazy<Cache<String, UnboundedSender<Option<DeltaOp>>>> = Lazy::new(|| {
     CacheBuilder::new(64)
         .time_to_idle(Duration::from_secs(3 * 60))
         .build()
 });
 
-pub async fn create_file_append_session(id: String, path: &Path) -> CoreResult<()> {
+/// Create the session which receives file blocks from remote, the file is truncated to
+/// `offset` and appended when resuming an interrupted transfer. `total` is the size of the
+/// whole file which progress is reported against.
+pub async fn create_file_append_session(
+    id: String,
+    path: &Path,
+    offset: u64,
+    total: u64,
+) -> CoreResult<()> {
     let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
 
     APPEND_FILES.insert(id.clone(), tx).await;
 
-    if let Err(err) = save_file_from_remote(id.clone(), path, rx).await {
+    if let Err(err) = save_file_from_remote(id.clone(), path, offset, total, rx).await {
         APPEND_FILES.invalidate(&id).await;
         return Err(err);
     }
@@ -61,15 +91,157 @@ pub async fn append_file_block(client: Arc<EndPointClient>, block: EndPointFileT
         .await;
 }
 
+/// Create the session which rebuilds the file at `path` from delta blocks of remote, the
+/// existing file is the basis and it's replaced only when the new file is complete.
+pub async fn create_file_patch_session(
+    id: String,
+    path: &Path,
+    block_size: u32,
+    total: u64,
+) -> CoreResult<()> {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+    PATCH_FILES.insert(id.clone(), tx).await;
+
+    if let Err(err) = patch_file_from_remote(id.clone(), path, block_size, total, rx).await {
+        PATCH_FILES.invalidate(&id).await;
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+pub async fn delete_file_patch_session(id: &str) {
+    PATCH_FILES.invalidate(id).await
+}
+
+pub async fn patch_file_block(client: Arc<EndPointClient>, block: EndPointFileDeltaBlock) {
+    if let Some(tx) = PATCH_FILES.get(&block.id) {
+        match tx.send(block.op) {
+            Ok(_) => return,
+            Err(_) => {
+                tracing::error!(id = block.id, "patch file block channel failed");
+            }
+        }
+    } else {
+        tracing::error!(id = block.id, "file session not exists");
+    }
+
+    let _ = client
+        .send(&EndPointMessage::FileTransferError(
+            EndPointFileTransferError { id: block.id },
+        ))
+        .await;
+}
+
+async fn patch_file_from_remote(
+    id: String,
+    path: &Path,
+    block_size: u32,
+    total: u64,
+    mut rx: UnboundedReceiver<Option<DeltaOp>>,
+) -> CoreResult<()> {
+    let file_name = path
+        .file_name()
+        .ok_or(core_error!("patch file has no filename"))?;
+
+    let patch_path = path.with_file_name(format!("{}.mirrorx-delta", file_name.to_string_lossy()));
+
+    let mut basis = std::fs::File::open(path)?;
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(&patch_path)?);
+    let path = path.to_path_buf();
+
+    let progress = ProgressTracker::new(id.clone(), ProgressKind::Receive, Some(total), 0);
+
+    spawn::spawn_blocking(move || {
+        let mut succeed = false;
+        let mut written = 0u64;
+
+        loop {
+            let Some(op) = rx.blocking_recv() else {
+                tracing::info!("exit patch file");
+                break;
+            };
+
+            let Some(op) = op else {
+                succeed = true;
+                break;
+            };
+
+            // the size which remote declared is what the receive policy checked
+            written += op.output_size(block_size);
+            if written > total {
+                tracing::error!(id, total, "remote patches file beyond its declared size");
+                break;
+            }
+
+            let result = match &op {
+                DeltaOp::Copy(index) => delta::read_block(&mut basis, block_size, *index)
+                    .and_then(|block| writer.write_all(&block).map_err(Into::into)),
+                DeltaOp::Literal(data) => writer.write_all(data).map_err(Into::into),
+            };
+
+            if let Err(err) = result {
+                tracing::error!(?err, "write patch file has error occurred");
+                break;
+            }
+
+            progress.advance(op.output_size(block_size));
+        }
+
+        if writer.flush().is_err() {
+            succeed = false;
+        }
+
+        drop(writer);
+        drop(basis);
+
+        if succeed {
+            if let Err(err) = std::fs::rename(&patch_path, &path) {
+                tracing::error!(?err, "replace file with patched file failed");
+                succeed = false;
+            }
+        }
+
+        if !succeed {
+            let _ = std::fs::remove_file(&patch_path);
+        }
+
+        futures::executor::block_on(PATCH_FILES.invalidate(&id));
+
+        progress.finish(succeed);
+        publish_transfer_result(id, path, succeed);
+    });
+
+    Ok(())
+}
+
 async fn save_file_from_remote(
     id: String,
     path: &Path,
+    offset: u64,
+    total: u64,
     mut rx: UnboundedReceiver<Option<Vec<u8>>>,
 ) -> CoreResult<()> {
-    let file = tokio::fs::File::create(path).await?;
+    let file = if offset > 0 {
+        let mut file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+        file.set_len(offset).await?;
+        file.seek(SeekFrom::End(0)).await?;
+        file
+    } else {
+        tokio::fs::File::create(path).await?
+    };
+
     let mut writer = BufWriter::new(file);
+    let path = path.to_path_buf();
+
+    let progress = ProgressTracker::new(id.clone(), ProgressKind::Receive, Some(total), offset);
+
+    spawn::spawn(async move {
+        let mut succeed = false;
+        let mut exceeded = false;
+        let mut written = offset;
 
-    tokio::spawn(async move {
         loop {
             let Some(buffer) = rx.recv().await else {
                 tracing::info!("exit write file");
@@ -78,22 +250,44 @@ async fn save_file_from_remote(
 
             match buffer {
                 Some(buffer) => {
+                    // the size which remote declared is what the receive policy checked
+                    written += buffer.len() as u64;
+                    if written > total {
+                        tracing::error!(id, total, "remote sends file beyond its declared size");
+                        exceeded = true;
+                        break;
+                    }
+
                     if let Err(err) = writer.write_all(&buffer).await {
                         tracing::error!(?err, "write file has error occurred");
                         break;
                     }
 
-                    update_transferred_bytes_count(&id, buffer.len() as _).await;
+                    progress.advance(buffer.len() as _);
                 }
                 None => {
+                    succeed = true;
                     break;
                 }
             }
         }
 
-        let _ = writer.flush().await;
+        if writer.flush().await.is_err() {
+            succeed = false;
+        }
+
+        drop(writer);
+
+        if exceeded {
+            if let Err(err) = tokio::fs::remove_file(&path).await {
+                tracing::warn!(?err, ?path, "remove oversized file failed");
+            }
+        }
 
         APPEND_FILES.invalidate(&id).await;
+
+        progress.finish(succeed);
+        publish_transfer_result(id, path, succeed);
     });
 
     Ok(())
@@ -103,14 +297,28 @@ pub async fn send_file_to_remote(
     id: String,
     client: Arc<EndPointClient>,
     path: &Path,
+    offset: u64,
 ) -> CoreResult<()> {
-    let file = tokio::fs::File::open(path).await?;
+    let mut file = tokio::fs::File::open(path).await?;
+    let total = file.metadata().await?.len();
+    file.seek(SeekFrom::Start(offset)).await?;
+
     let mut reader = BufReader::new(file);
+    let path = path.to_path_buf();
 
-    tokio::spawn(async move {
+    let progress = ProgressTracker::new(id.clone(), ProgressKind::Send, Some(total), offset);
+
+    spawn::spawn(async move {
         let mut buffer = [0u8; 1024 * 64];
+        let mut succeed = false;
 
         loop {
+            // defer chunks to leave resources for the desktop session when host is overloaded
+            let chunk_delay = governor::file_transfer_chunk_delay();
+            if !chunk_delay.is_zero() {
+                tokio::time::sleep(chunk_delay).await;
+            }
+
             let (message, n) = match reader.read(&mut buffer).await {
                 Ok(n) => {
                     let content = if n > 0 {
@@ -143,26 +351,131 @@ pub async fn send_file_to_remote(
                 break;
             }
 
-            update_transferred_bytes_count(&id, n as _).await;
+            progress.advance(n as _);
 
             match message {
-                EndPointMessage::FileTransferBlock(message) if message.data.is_none() => break,
+                EndPointMessage::FileTransferBlock(message) if message.data.is_none() => {
+                    succeed = true;
+                    break;
+                }
                 EndPointMessage::FileTransferError(_) => break,
                 _ => {}
             }
         }
+
+        progress.finish(succeed);
+        publish_transfer_result(id, path, succeed);
     });
 
     Ok(())
 }
 
-pub fn query_transferred_bytes_count(id: &str) -> u64 {
-    BYTES_TRANSFERRED_CACHE.get(id).unwrap_or_default()
+/// Send the file at `path` as delta blocks against `signature` of the existing file at
+/// remote, blocks which remote already has are not sent again.
+pub async fn send_file_delta_to_remote(
+    id: String,
+    client: Arc<EndPointClient>,
+    path: &Path,
+    signature: FileSignature,
+) -> CoreResult<()> {
+    // progress counts the size of the rebuilt file at remote rather than bytes on wire
+    let total = tokio::fs::metadata(path).await?.len();
+    let progress = ProgressTracker::new(id.clone(), ProgressKind::Send, Some(total), 0);
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let path = path.to_path_buf();
+
+    let delta_path = path.clone();
+    let block_size = signature.block_size;
+    spawn::spawn_blocking(move || {
+        let result = delta::compute_delta(&delta_path, &signature, |op| {
+            tx.blocking_send(Some(op))
+                .map_err(|_| core_error!("delta block channel closed"))
+        });
+
+        match result {
+            Ok(_) => {
+                let _ = tx.blocking_send(None);
+            }
+            Err(err) => tracing::error!(?err, "compute file delta failed"),
+        }
+    });
+
+    spawn::spawn(async move {
+        let mut succeed = false;
+
+        loop {
+            // defer chunks to leave resources for the desktop session when host is overloaded
+            let chunk_delay = governor::file_transfer_chunk_delay();
+            if !chunk_delay.is_zero() {
+                tokio::time::sleep(chunk_delay).await;
+            }
+
+            let (message, n) = match rx.recv().await {
+                Some(op) => {
+                    let n = op.as_ref().map_or(0, |op| op.output_size(block_size));
+                    (
+                        EndPointMessage::FileDeltaBlock(EndPointFileDeltaBlock {
+                            id: id.clone(),
+                            op,
+                        }),
+                        n,
+                    )
+                }
+                None => (
+                    EndPointMessage::FileTransferError(EndPointFileTransferError {
+                        id: id.clone(),
+                    }),
+                    0,
+                ),
+            };
+
+            if let Err(err) = client.send(&message).await {
+                tracing::error!(?err, "send file delta message failed");
+                break;
+            }
+
+            progress.advance(n);
+
+            match message {
+                EndPointMessage::FileDeltaBlock(message) if message.op.is_none() => {
+                    succeed = true;
+                    break;
+                }
+                EndPointMessage::FileTransferError(_) => break,
+                _ => {}
+            }
+        }
+
+        progress.finish(succeed);
+        publish_transfer_result(id, path, succeed);
+    });
+
+    Ok(())
 }
 
-async fn update_transferred_bytes_count(id: &str, delta: u64) {
-    let transferred = BYTES_TRANSFERRED_CACHE.get(id).unwrap_or_default() + delta;
-    BYTES_TRANSFERRED_CACHE
-        .insert(id.to_string(), transferred)
-        .await;
+pub(super) fn publish_transfer_result(id: String, mut path: PathBuf, succeed: bool) {
+    if let Some(file) = finish_quarantine(&id, succeed) {
+        // it's told to have been transferred once user releases it
+        if succeed {
+            notification::publish(Notification::FileQuarantined { file });
+            return;
+        }
+
+        path = file.destination;
+    }
+
+    plugin::publish(SessionEvent::FileTransferred {
+        id: id.clone(),
+        path: path.clone(),
+        succeed,
+    });
+
+    let notification = if succeed {
+        Notification::FileTransferSucceeded { id, path }
+    } else {
+        Notification::FileTransferFailed { id, path }
+    };
+
+    notification::publish(notification);
 }
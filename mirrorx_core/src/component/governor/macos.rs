@@ -0,0 +1,135 @@
+use super::ResourceUsage;
+use crate::{core_error, error::CoreResult};
+use core_foundation::{
+    base::{kCFAllocatorDefault, CFType, TCFType},
+    dictionary::CFDictionary,
+    number::CFNumber,
+    string::CFString,
+};
+use mirrorx_native::os::macos::{io_kit::*, mach::*};
+use scopeguard::defer;
+
+pub struct UsageSampler {
+    last_cpu_ticks: host_cpu_load_info,
+}
+
+impl UsageSampler {
+    pub fn new() -> CoreResult<Self> {
+        Ok(Self {
+            last_cpu_ticks: get_cpu_ticks()?,
+        })
+    }
+
+    pub fn sample(&mut self) -> CoreResult<ResourceUsage> {
+        let cpu_ticks = get_cpu_ticks()?;
+
+        let deltas: Vec<u64> = cpu_ticks
+            .cpu_ticks
+            .iter()
+            .zip(self.last_cpu_ticks.cpu_ticks.iter())
+            .map(|(ticks, last_ticks)| ticks.wrapping_sub(*last_ticks) as u64)
+            .collect();
+
+        self.last_cpu_ticks = cpu_ticks;
+
+        let total: u64 = deltas.iter().sum();
+        let cpu = if total == 0 {
+            0.0
+        } else {
+            1.0 - (deltas[CPU_STATE_IDLE] as f32 / total as f32)
+        };
+
+        let gpu = match get_gpu_usage() {
+            Ok(usage) => usage,
+            Err(err) => {
+                tracing::warn!(?err, "sample gpu utilization failed");
+                None
+            }
+        };
+
+        Ok(ResourceUsage { cpu, gpu })
+    }
+}
+
+fn get_cpu_ticks() -> CoreResult<host_cpu_load_info> {
+    unsafe {
+        let mut info = host_cpu_load_info::default();
+        let mut count = HOST_CPU_LOAD_INFO_COUNT;
+
+        let ret = host_statistics(
+            mach_host_self(),
+            HOST_CPU_LOAD_INFO,
+            &mut info as *mut _ as *mut i32,
+            &mut count,
+        );
+
+        if ret != KERN_SUCCESS {
+            return Err(core_error!("host_statistics returns error code: {}", ret));
+        }
+
+        Ok(info)
+    }
+}
+
+// the max utilization of all GPUs which report performance statistics
+fn get_gpu_usage() -> CoreResult<Option<f32>> {
+    unsafe {
+        let matching = IOServiceMatching(b"IOAccelerator\0".as_ptr() as *const _);
+        if matching.is_null() {
+            return Err(core_error!("IOServiceMatching returns null"));
+        }
+
+        // IOServiceGetMatchingServices consumes the matching dictionary
+        let mut iterator = 0;
+        let ret = IOServiceGetMatchingServices(kIOMasterPortDefault, matching as _, &mut iterator);
+        if ret != KERN_SUCCESS {
+            return Err(core_error!(
+                "IOServiceGetMatchingServices returns error code: {}",
+                ret
+            ));
+        }
+
+        defer! {
+            IOObjectRelease(iterator);
+        }
+
+        let statistics_key = CFString::from_static_string("PerformanceStatistics");
+        let utilization_key = CFString::from_static_string("Device Utilization %");
+        let mut usage: Option<f32> = None;
+
+        loop {
+            let service = IOIteratorNext(iterator);
+            if service == 0 {
+                break;
+            }
+
+            let statistics = IORegistryEntryCreateCFProperty(
+                service,
+                statistics_key.as_concrete_TypeRef(),
+                kCFAllocatorDefault,
+                0,
+            );
+
+            IOObjectRelease(service);
+
+            if statistics.is_null() {
+                continue;
+            }
+
+            let statistics: CFDictionary<CFString, CFType> =
+                CFDictionary::wrap_under_create_rule(statistics as _);
+
+            let utilization = statistics
+                .find(&utilization_key)
+                .and_then(|value| value.downcast::<CFNumber>())
+                .and_then(|value| value.to_i64());
+
+            if let Some(utilization) = utilization {
+                let utilization = (utilization as f32 / 100.0).clamp(0.0, 1.0);
+                usage = Some(usage.map_or(utilization, |usage| usage.max(utilization)));
+            }
+        }
+
+        Ok(usage)
+    }
+}
@@ -0,0 +1,195 @@
+pub mod priority;
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "windows")]
+use self::windows::UsageSampler;
+
+#[cfg(target_os = "macos")]
+mod macos;
+
+#[cfg(target_os = "macos")]
+use self::macos::UsageSampler;
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+use self::unsupported::UsageSampler;
+
+use std::{
+    sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+const HIGH_CPU_USAGE: f32 = 0.85;
+const HIGH_GPU_USAGE: f32 = 0.9;
+const LOW_USAGE: f32 = 0.6;
+
+// step the level only when usage keeps high or low in consecutive samples,
+// so short spikes don't make the session quality flapping
+const STEP_SAMPLES: u32 = 3;
+const MAX_LEVEL: u8 = 2;
+
+static LEVEL: AtomicU8 = AtomicU8::new(0);
+static SERVING_SESSIONS: AtomicUsize = AtomicUsize::new(0);
+static SAMPLING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceUsage {
+    /// CPU utilization of the whole host in [0, 1].
+    pub cpu: f32,
+    /// GPU utilization of the whole host in [0, 1], None when it can't be sampled.
+    pub gpu: Option<f32>,
+}
+
+/// Keeps the governor sampling host resource usage while any passive session is serving.
+#[derive(Debug)]
+pub struct GovernorGuard {
+    _private: (),
+}
+
+impl GovernorGuard {
+    pub fn acquire() -> Self {
+        SERVING_SESSIONS.fetch_add(1, Ordering::SeqCst);
+
+        if !SAMPLING.swap(true, Ordering::SeqCst) {
+            if let Err(err) = std::thread::Builder::new()
+                .name(String::from("resource_governor"))
+                .spawn(serve_sampling)
+            {
+                tracing::error!(?err, "spawn resource governor thread failed");
+                SAMPLING.store(false, Ordering::SeqCst);
+            }
+        }
+
+        Self { _private: () }
+    }
+}
+
+impl Drop for GovernorGuard {
+    fn drop(&mut self) {
+        SERVING_SESSIONS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Returns the max frame rate which sessions should keep, None means unlimited.
+pub fn max_frame_rate() -> Option<u8> {
+    match LEVEL.load(Ordering::SeqCst) {
+        0 => None,
+        1 => Some(30),
+        _ => Some(15),
+    }
+}
+
+/// Returns the scale which sessions should apply to their encoding bit rate.
+pub fn bit_rate_scale() -> f32 {
+    match LEVEL.load(Ordering::SeqCst) {
+        0 => 1.0,
+        1 => 0.6,
+        _ => 0.35,
+    }
+}
+
+/// Returns the delay which file transfer should wait before sending next chunk.
+pub fn file_transfer_chunk_delay() -> Duration {
+    match LEVEL.load(Ordering::SeqCst) {
+        0 => Duration::ZERO,
+        1 => Duration::from_millis(10),
+        _ => Duration::from_millis(50),
+    }
+}
+
+fn serve_sampling() {
+    tracing::info!("resource governor start");
+
+    let mut sampler = match UsageSampler::new() {
+        Ok(sampler) => Some(sampler),
+        Err(err) => {
+            tracing::error!(?err, "create resource usage sampler failed");
+            None
+        }
+    };
+
+    let mut high_samples = 0;
+    let mut low_samples = 0;
+
+    loop {
+        if SERVING_SESSIONS.load(Ordering::SeqCst) == 0 {
+            SAMPLING.store(false, Ordering::SeqCst);
+
+            // another session may acquire between the check and the store above
+            if SERVING_SESSIONS.load(Ordering::SeqCst) == 0 || SAMPLING.swap(true, Ordering::SeqCst)
+            {
+                break;
+            }
+        }
+
+        std::thread::sleep(SAMPLE_INTERVAL);
+
+        let Some(ref mut sampler) = sampler else {
+            continue;
+        };
+
+        let usage = match sampler.sample() {
+            Ok(usage) => usage,
+            Err(err) => {
+                tracing::error!(?err, "sample resource usage failed");
+                continue;
+            }
+        };
+
+        let overloaded =
+            usage.cpu > HIGH_CPU_USAGE || usage.gpu.map_or(false, |gpu| gpu > HIGH_GPU_USAGE);
+        let relaxed = usage.cpu < LOW_USAGE && usage.gpu.map_or(true, |gpu| gpu < LOW_USAGE);
+
+        (high_samples, low_samples) = match (overloaded, relaxed) {
+            (true, _) => (high_samples + 1, 0),
+            (_, true) => (0, low_samples + 1),
+            _ => (0, 0),
+        };
+
+        let level = LEVEL.load(Ordering::SeqCst);
+
+        if high_samples >= STEP_SAMPLES && level < MAX_LEVEL {
+            tracing::warn!(
+                ?usage,
+                level = level + 1,
+                "host overloaded, step down sessions"
+            );
+            LEVEL.store(level + 1, Ordering::SeqCst);
+            high_samples = 0;
+        } else if low_samples >= STEP_SAMPLES && level > 0 {
+            tracing::info!(?usage, level = level - 1, "host relaxed, step up sessions");
+            LEVEL.store(level - 1, Ordering::SeqCst);
+            low_samples = 0;
+        }
+    }
+
+    LEVEL.store(0, Ordering::SeqCst);
+
+    tracing::info!("resource governor exit");
+}
+
+// mobile viewer builds never serve passive sessions, the governor just keeps the
+// default level there
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod unsupported {
+    use super::ResourceUsage;
+    use crate::{core_error, error::CoreResult};
+
+    pub struct UsageSampler;
+
+    impl UsageSampler {
+        pub fn new() -> CoreResult<Self> {
+            Err(core_error!(
+                "resource usage sampling is not supported on current platform"
+            ))
+        }
+
+        pub fn sample(&mut self) -> CoreResult<ResourceUsage> {
+            Err(core_error!(
+                "resource usage sampling is not supported on current platform"
+            ))
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Capture and encoding compete with the foreground work of user on busy hosts. Their
+//! threads run at the priority and on the cores which host configures, and viewers can
+//! ask for host priority mode which lowers the priority further and caps the frame rate
+//! of session. Settings apply to the threads which start later.
+//!
+//! The threads are borrowed from the blocking pool of runtime, so `ThreadTuning` restores
+//! them when it's dropped and other tasks don't inherit the tuning.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Mutex,
+    },
+};
+
+/// The max frame rate of sessions in host priority mode.
+pub const HOST_PRIORITY_MAX_FRAME_RATE: u8 = 15;
+
+static THREAD_PRIORITY: AtomicU8 = AtomicU8::new(ThreadPriority::Normal as u8);
+static CORES: Lazy<Mutex<Option<Vec<usize>>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub enum ThreadPriority {
+    Normal,
+    BelowNormal,
+    Lowest,
+}
+
+impl<'a> From<ThreadPriority> for &'a str {
+    fn from(val: ThreadPriority) -> Self {
+        match val {
+            ThreadPriority::Normal => "normal",
+            ThreadPriority::BelowNormal => "below_normal",
+            ThreadPriority::Lowest => "lowest",
+        }
+    }
+}
+
+impl FromStr for ThreadPriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(ThreadPriority::Normal),
+            "below_normal" => Ok(ThreadPriority::BelowNormal),
+            "lowest" => Ok(ThreadPriority::Lowest),
+            _ => Err(String::from("Unknown thread priority")),
+        }
+    }
+}
+
+impl ThreadPriority {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => ThreadPriority::BelowNormal,
+            2 => ThreadPriority::Lowest,
+            _ => ThreadPriority::Normal,
+        }
+    }
+}
+
+pub fn set_encoder_thread_priority(priority: ThreadPriority) {
+    THREAD_PRIORITY.store(priority as u8, Ordering::SeqCst);
+}
+
+pub fn encoder_thread_priority() -> ThreadPriority {
+    ThreadPriority::from_u8(THREAD_PRIORITY.load(Ordering::SeqCst))
+}
+
+/// Pin capture and encode threads to the cores, None lets them run on any core.
+pub fn set_encoder_cores(cores: Option<Vec<usize>>) {
+    *CORES.lock().unwrap() = cores.filter(|cores| !cores.is_empty());
+}
+
+pub fn encoder_cores() -> Option<Vec<usize>> {
+    CORES.lock().unwrap().clone()
+}
+
+/// Parse the cores like `0,2,4-7`.
+pub fn parse_cores(s: &str) -> Result<Vec<usize>, String> {
+    let mut cores = Vec::new();
+
+    for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (first.trim(), last.trim()),
+            None => (part, part),
+        };
+
+        let (Ok(first), Ok(last)) = (first.parse::<usize>(), last.parse::<usize>()) else {
+            return Err(format!("invalid core \"{part}\""));
+        };
+
+        if first > last {
+            return Err(format!("invalid core range \"{part}\""));
+        }
+
+        if last >= usize::BITS as usize {
+            return Err(format!("core {last} is out of range"));
+        }
+
+        cores.extend(first..=last);
+    }
+
+    cores.sort_unstable();
+    cores.dedup();
+
+    Ok(cores)
+}
+
+/// Priority and affinity of the current thread for capture or encoding, they're
+/// restored when it's dropped.
+pub struct ThreadTuning {
+    host_priority: bool,
+    saved_priority: Option<sys::SavedPriority>,
+    saved_affinity: Option<sys::SavedAffinity>,
+}
+
+impl ThreadTuning {
+    pub fn apply(host_priority: bool) -> Self {
+        let mut tuning = ThreadTuning {
+            host_priority,
+            saved_priority: None,
+            saved_affinity: None,
+        };
+
+        tuning.tune_priority();
+
+        if let Some(cores) = encoder_cores() {
+            match sys::set_affinity(&cores) {
+                Ok(saved_affinity) => tuning.saved_affinity = Some(saved_affinity),
+                Err(err) => tracing::warn!(?err, ?cores, "pin thread to cores failed"),
+            }
+        }
+
+        tuning
+    }
+
+    /// Tune the priority again once host priority mode of session is switched.
+    pub fn update(&mut self, host_priority: bool) {
+        if self.host_priority != host_priority {
+            self.host_priority = host_priority;
+            self.tune_priority();
+        }
+    }
+
+    fn tune_priority(&mut self) {
+        let priority = if self.host_priority {
+            ThreadPriority::Lowest
+        } else {
+            encoder_thread_priority()
+        };
+
+        // keep untouched threads as they are
+        if priority == ThreadPriority::Normal && self.saved_priority.is_none() {
+            return;
+        }
+
+        if self.saved_priority.is_none() {
+            self.saved_priority = Some(sys::current_priority());
+        }
+
+        if let Err(err) = sys::set_priority(priority) {
+            tracing::warn!(?err, ?priority, "set thread priority failed");
+        }
+    }
+}
+
+impl Drop for ThreadTuning {
+    fn drop(&mut self) {
+        if let Some(saved_priority) = self.saved_priority.take() {
+            if let Err(err) = sys::restore_priority(saved_priority) {
+                tracing::warn!(?err, "restore thread priority failed");
+            }
+        }
+
+        if let Some(saved_affinity) = self.saved_affinity.take() {
+            if let Err(err) = sys::restore_affinity(saved_affinity) {
+                tracing::warn!(?err, "restore thread affinity failed");
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod sys {
+    use super::ThreadPriority;
+    use crate::{core_error, error::CoreResult, HRESULT};
+    use windows::Win32::System::Threading::{
+        GetCurrentThread, GetThreadPriority, SetThreadAffinityMask, SetThreadPriority,
+        THREAD_PRIORITY, THREAD_PRIORITY_BELOW_NORMAL, THREAD_PRIORITY_LOWEST,
+        THREAD_PRIORITY_NORMAL,
+    };
+
+    pub type SavedPriority = i32;
+    pub type SavedAffinity = usize;
+
+    pub fn current_priority() -> SavedPriority {
+        unsafe { GetThreadPriority(GetCurrentThread()) }
+    }
+
+    pub fn set_priority(priority: ThreadPriority) -> CoreResult<()> {
+        let priority = match priority {
+            ThreadPriority::Normal => THREAD_PRIORITY_NORMAL,
+            ThreadPriority::BelowNormal => THREAD_PRIORITY_BELOW_NORMAL,
+            ThreadPriority::Lowest => THREAD_PRIORITY_LOWEST,
+        };
+
+        restore_priority(priority.0)
+    }
+
+    pub fn restore_priority(priority: SavedPriority) -> CoreResult<()> {
+        unsafe {
+            HRESULT!(SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY(priority)).ok());
+        }
+
+        Ok(())
+    }
+
+    pub fn set_affinity(cores: &[usize]) -> CoreResult<SavedAffinity> {
+        let mask = cores.iter().fold(0usize, |mask, core| mask | (1 << core));
+        let saved_affinity = unsafe { SetThreadAffinityMask(GetCurrentThread(), mask) };
+        if saved_affinity == 0 {
+            return Err(core_error!("SetThreadAffinityMask failed"));
+        }
+
+        Ok(saved_affinity)
+    }
+
+    pub fn restore_affinity(affinity: SavedAffinity) -> CoreResult<()> {
+        if unsafe { SetThreadAffinityMask(GetCurrentThread(), affinity) } == 0 {
+            return Err(core_error!("SetThreadAffinityMask failed"));
+        }
+
+        Ok(())
+    }
+}
+
+// threads are classed by quality of service on macOS, which schedules them on
+// performance or efficiency cores by itself, so they can't be pinned
+#[cfg(target_os = "macos")]
+mod sys {
+    use super::ThreadPriority;
+    use crate::{core_error, error::CoreResult};
+    use libc::{
+        pthread_get_qos_class_np, pthread_self, pthread_set_qos_class_self_np, qos_class_t,
+    };
+
+    pub type SavedPriority = qos_class_t;
+    pub type SavedAffinity = ();
+
+    pub fn current_priority() -> SavedPriority {
+        let mut qos_class = qos_class_t::QOS_CLASS_DEFAULT;
+        let mut relative_priority = 0;
+        unsafe { pthread_get_qos_class_np(pthread_self(), &mut qos_class, &mut relative_priority) };
+
+        // threads which nobody classed run as default
+        match qos_class {
+            qos_class_t::QOS_CLASS_UNSPECIFIED => qos_class_t::QOS_CLASS_DEFAULT,
+            qos_class => qos_class,
+        }
+    }
+
+    pub fn set_priority(priority: ThreadPriority) -> CoreResult<()> {
+        restore_priority(match priority {
+            ThreadPriority::Normal => qos_class_t::QOS_CLASS_DEFAULT,
+            ThreadPriority::BelowNormal => qos_class_t::QOS_CLASS_UTILITY,
+            ThreadPriority::Lowest => qos_class_t::QOS_CLASS_BACKGROUND,
+        })
+    }
+
+    pub fn restore_priority(priority: SavedPriority) -> CoreResult<()> {
+        let ret = unsafe { pthread_set_qos_class_self_np(priority, 0) };
+        if ret != 0 {
+            return Err(core_error!(
+                "pthread_set_qos_class_self_np returns error code: {}",
+                ret
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn set_affinity(_: &[usize]) -> CoreResult<SavedAffinity> {
+        Err(core_error!(
+            "core affinity is not supported on current platform"
+        ))
+    }
+
+    pub fn restore_affinity(_: SavedAffinity) -> CoreResult<()> {
+        Ok(())
+    }
+}
+
+// mobile viewer builds never capture their desktop
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod sys {
+    use super::ThreadPriority;
+    use crate::{core_error, error::CoreResult};
+
+    pub type SavedPriority = ();
+    pub type SavedAffinity = ();
+
+    pub fn current_priority() -> SavedPriority {}
+
+    pub fn set_priority(_: ThreadPriority) -> CoreResult<()> {
+        Err(core_error!(
+            "thread priority is not supported on current platform"
+        ))
+    }
+
+    pub fn restore_priority(_: SavedPriority) -> CoreResult<()> {
+        Ok(())
+    }
+
+    pub fn set_affinity(_: &[usize]) -> CoreResult<SavedAffinity> {
+        Err(core_error!(
+            "core affinity is not supported on current platform"
+        ))
+    }
+
+    pub fn restore_affinity(_: SavedAffinity) -> CoreResult<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+use super::ResourceUsage;
+use crate::{core_error, error::CoreResult};
+use windows::{
+    core::PCWSTR,
+    w,
+    Win32::{
+        Foundation::FILETIME,
+        System::{
+            Performance::{
+                PdhAddEnglishCounterW, PdhCloseQuery, PdhCollectQueryData,
+                PdhGetFormattedCounterArrayW, PdhOpenQueryW, PDH_FMT_COUNTERVALUE_ITEM_W,
+                PDH_FMT_DOUBLE,
+            },
+            Threading::GetSystemTimes,
+        },
+    },
+};
+
+const ERROR_SUCCESS: u32 = 0;
+const PDH_MORE_DATA: u32 = 0x800007D2;
+
+pub struct UsageSampler {
+    last_cpu_times: (u64, u64),
+    gpu_query: Option<(isize, isize)>,
+}
+
+impl UsageSampler {
+    pub fn new() -> CoreResult<Self> {
+        let gpu_query = match open_gpu_query() {
+            Ok(query) => Some(query),
+            Err(err) => {
+                tracing::warn!(?err, "open gpu utilization query failed");
+                None
+            }
+        };
+
+        Ok(Self {
+            last_cpu_times: get_cpu_times()?,
+            gpu_query,
+        })
+    }
+
+    pub fn sample(&mut self) -> CoreResult<ResourceUsage> {
+        let (idle, total) = get_cpu_times()?;
+        let (last_idle, last_total) = self.last_cpu_times;
+        self.last_cpu_times = (idle, total);
+
+        let total_delta = total.saturating_sub(last_total);
+        let cpu = if total_delta == 0 {
+            0.0
+        } else {
+            1.0 - (idle.saturating_sub(last_idle) as f32 / total_delta as f32)
+        };
+
+        let gpu =
+            self.gpu_query
+                .and_then(|(query, counter)| match sample_gpu_usage(query, counter) {
+                    Ok(usage) => Some(usage),
+                    Err(err) => {
+                        tracing::warn!(?err, "sample gpu utilization failed");
+                        None
+                    }
+                });
+
+        Ok(ResourceUsage { cpu, gpu })
+    }
+}
+
+impl Drop for UsageSampler {
+    fn drop(&mut self) {
+        if let Some((query, _)) = self.gpu_query {
+            unsafe {
+                PdhCloseQuery(query);
+            }
+        }
+    }
+}
+
+// returns (idle, kernel + user), kernel time includes idle time
+fn get_cpu_times() -> CoreResult<(u64, u64)> {
+    unsafe {
+        let mut idle_time = FILETIME::default();
+        let mut kernel_time = FILETIME::default();
+        let mut user_time = FILETIME::default();
+
+        if !GetSystemTimes(
+            Some(&mut idle_time),
+            Some(&mut kernel_time),
+            Some(&mut user_time),
+        )
+        .as_bool()
+        {
+            return Err(core_error!("GetSystemTimes failed"));
+        }
+
+        let to_u64 =
+            |time: FILETIME| (time.dwHighDateTime as u64) << 32 | time.dwLowDateTime as u64;
+
+        Ok((to_u64(idle_time), to_u64(kernel_time) + to_u64(user_time)))
+    }
+}
+
+fn open_gpu_query() -> CoreResult<(isize, isize)> {
+    unsafe {
+        let mut query = 0;
+        let ret = PdhOpenQueryW(PCWSTR::null(), 0, &mut query);
+        if ret != ERROR_SUCCESS {
+            return Err(core_error!("PdhOpenQueryW returns error code: {}", ret));
+        }
+
+        // utilization of every 3D engine of every process
+        let mut counter = 0;
+        let ret = PdhAddEnglishCounterW(
+            query,
+            w!("\\GPU Engine(*engtype_3D)\\Utilization Percentage"),
+            0,
+            &mut counter,
+        );
+
+        if ret != ERROR_SUCCESS {
+            PdhCloseQuery(query);
+            return Err(core_error!(
+                "PdhAddEnglishCounterW returns error code: {}",
+                ret
+            ));
+        }
+
+        // rate counter requires two samples, collect the first one here
+        PdhCollectQueryData(query);
+
+        Ok((query, counter))
+    }
+}
+
+fn sample_gpu_usage(query: isize, counter: isize) -> CoreResult<f32> {
+    unsafe {
+        let ret = PdhCollectQueryData(query);
+        if ret != ERROR_SUCCESS {
+            return Err(core_error!(
+                "PdhCollectQueryData returns error code: {}",
+                ret
+            ));
+        }
+
+        let mut buffer_size = 0;
+        let mut item_count = 0;
+        let ret = PdhGetFormattedCounterArrayW(
+            counter,
+            PDH_FMT_DOUBLE,
+            &mut buffer_size,
+            &mut item_count,
+            None,
+        );
+
+        if ret != PDH_MORE_DATA {
+            return Err(core_error!(
+                "PdhGetFormattedCounterArrayW returns error code: {}",
+                ret
+            ));
+        }
+
+        let item_size = std::mem::size_of::<PDH_FMT_COUNTERVALUE_ITEM_W>();
+        let mut items: Vec<PDH_FMT_COUNTERVALUE_ITEM_W> =
+            Vec::with_capacity(buffer_size as usize / item_size + 1);
+
+        let ret = PdhGetFormattedCounterArrayW(
+            counter,
+            PDH_FMT_DOUBLE,
+            &mut buffer_size,
+            &mut item_count,
+            Some(items.as_mut_ptr()),
+        );
+
+        if ret != ERROR_SUCCESS {
+            return Err(core_error!(
+                "PdhGetFormattedCounterArrayW returns error code: {}",
+                ret
+            ));
+        }
+
+        items.set_len(item_count as usize);
+
+        let utilization: f64 = items
+            .iter()
+            .map(|item| item.FmtValue.Anonymous.doubleValue)
+            .sum();
+
+        Ok((utilization / 100.0).clamp(0.0, 1.0) as f32)
+    }
+}
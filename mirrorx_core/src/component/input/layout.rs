@@ -0,0 +1,109 @@
+//! Keyboard layout of this device. Host tells viewer its layout in negotiation, keys are
+//! sent as key codes so a viewer typing on another layout gets other characters than the
+//! ones printed on its keys, like `z` and `y` swapped between US and German layouts.
+
+use crate::api::endpoint::message::KeyboardLayout;
+
+/// The layout which the foreground application types with, None when it can't be told.
+#[cfg(target_os = "windows")]
+pub fn current_keyboard_layout() -> Option<KeyboardLayout> {
+    use windows::Win32::{
+        Globalization::LCIDToLocaleName,
+        UI::{
+            Input::KeyboardAndMouse::GetKeyboardLayout,
+            WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId},
+        },
+    };
+
+    // layouts are per thread, host itself isn't the one which user types into
+    let hkl = unsafe { GetKeyboardLayout(GetWindowThreadProcessId(GetForegroundWindow(), None)) };
+    if hkl.0 == 0 {
+        return None;
+    }
+
+    // the low word is the language of the layout
+    let language_id = (hkl.0 as usize & 0xFFFF) as u32;
+    let mut language = [0u16; 85];
+    let len = unsafe { LCIDToLocaleName(language_id, Some(&mut language), 0) };
+    if len <= 1 {
+        return None;
+    }
+
+    Some(KeyboardLayout {
+        platform: String::from("windows"),
+        id: format!("{:08X}", hkl.0 as usize as u32),
+        language: String::from_utf16_lossy(&language[..len as usize - 1]),
+        locale: sys_locale::get_locale()?,
+    })
+}
+
+/// The layout of the current keyboard input source, None when it can't be told.
+#[cfg(target_os = "macos")]
+pub fn current_keyboard_layout() -> Option<KeyboardLayout> {
+    use core_foundation::{
+        array::{CFArray, CFArrayRef},
+        base::{CFRelease, TCFType},
+        string::{CFString, CFStringRef},
+    };
+    use std::ffi::c_void;
+
+    #[link(name = "Carbon", kind = "framework")]
+    extern "C" {
+        static kTISPropertyInputSourceID: CFStringRef;
+        static kTISPropertyInputSourceLanguages: CFStringRef;
+        fn TISCopyCurrentKeyboardLayoutInputSource() -> *const c_void;
+        fn TISGetInputSourceProperty(source: *const c_void, key: CFStringRef) -> *const c_void;
+    }
+
+    unsafe {
+        let source = TISCopyCurrentKeyboardLayoutInputSource();
+        if source.is_null() {
+            return None;
+        }
+
+        scopeguard::defer! {
+            CFRelease(source);
+        }
+
+        let id = TISGetInputSourceProperty(source, kTISPropertyInputSourceID);
+        let languages = TISGetInputSourceProperty(source, kTISPropertyInputSourceLanguages);
+        if id.is_null() || languages.is_null() {
+            return None;
+        }
+
+        // the first language is the one which the layout is made for
+        let languages = CFArray::<CFString>::wrap_under_get_rule(languages as CFArrayRef);
+        let language = languages.get(0)?.to_string();
+
+        Some(KeyboardLayout {
+            platform: String::from("macos"),
+            id: CFString::wrap_under_get_rule(id as CFStringRef).to_string(),
+            language,
+            locale: sys_locale::get_locale()?,
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub fn current_keyboard_layout() -> Option<KeyboardLayout> {
+    None
+}
+
+/// Whether keys pressed on `local` layout type the same characters on `remote` layout.
+/// Layouts of different platforms are only compared by their language, like `de-DE` and
+/// `de` match.
+pub fn layouts_match(local: &KeyboardLayout, remote: &KeyboardLayout) -> bool {
+    if local.platform == remote.platform {
+        return local.id == remote.id;
+    }
+
+    let primary_language = |language: &str| {
+        language
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    };
+
+    primary_language(&local.language) == primary_language(&remote.language)
+}
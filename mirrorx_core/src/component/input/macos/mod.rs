@@ -12,11 +12,13 @@ use core_graphics::{
     event_source::{CGEventSource, CGEventSourceStateID},
 };
 
+/// Stamped into the user data of posted events, so the block of local input tells them
+/// from the physical input.
+pub const INJECTED_EVENT_USER_DATA: i64 = 0x4D_52_58;
+
 // https://developer.mozilla.org/en-US/docs/Web/API/UI_Events/Keyboard_event_code_values
 
 pub fn mouse_up(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResult<()> {
-    let display_id = monitor.id.parse::<u32>()?;
-
     let (event_type, mouse_button) = match key {
         MouseKey::None => return Err(core_error!("unsupport key")),
         MouseKey::Left => (CGEventType::LeftMouseUp, CGMouseButton::Left),
@@ -39,7 +41,7 @@ pub fn mouse_up(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResult
     };
 
     unsafe {
-        post_mouse_event(display_id, x, y, move |event_source, point| {
+        post_mouse_event(monitor, x, y, move |event_source, point| {
             let event = CGEvent::new_mouse_event(event_source, event_type, point, mouse_button)
                 .map_err(|_| core_error!("create mouse CGEvent failed"))?;
 
@@ -53,8 +55,6 @@ pub fn mouse_up(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResult
 }
 
 pub fn mouse_down(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResult<()> {
-    let display_id = monitor.id.parse::<u32>()?;
-
     let (event_type, mouse_button) = match key {
         MouseKey::None => return Err(core_error!("unsupport key")),
         MouseKey::Left => (CGEventType::LeftMouseDown, CGMouseButton::Left),
@@ -77,7 +77,7 @@ pub fn mouse_down(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResu
     };
 
     unsafe {
-        post_mouse_event(display_id, x, y, move |event_source, point| {
+        post_mouse_event(monitor, x, y, move |event_source, point| {
             let event = CGEvent::new_mouse_event(event_source, event_type, point, mouse_button)
                 .map_err(|_| core_error!("create mouse CGEvent failed"))?;
 
@@ -91,8 +91,6 @@ pub fn mouse_down(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResu
 }
 
 pub fn mouse_move(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResult<()> {
-    let display_id = monitor.id.parse::<u32>()?;
-
     let (event_type, mouse_button) = match key {
         MouseKey::None => (CGEventType::MouseMoved, CGMouseButton::Left),
         MouseKey::Left => (CGEventType::LeftMouseDragged, CGMouseButton::Left),
@@ -115,7 +113,7 @@ pub fn mouse_move(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResu
     };
 
     unsafe {
-        post_mouse_event(display_id, x, y, move |event_source, point| {
+        post_mouse_event(monitor, x, y, move |event_source, point| {
             let event = CGEvent::new_mouse_event(event_source, event_type, point, mouse_button)
                 .map_err(|_| core_error!("create mouse CGEvent failed"))?;
 
@@ -129,10 +127,8 @@ pub fn mouse_move(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResu
 }
 
 pub fn mouse_scroll_wheel(monitor: &Monitor, delta: f32) -> CoreResult<()> {
-    let display_id = monitor.id.parse::<u32>()?;
-
     unsafe {
-        post_mouse_event(display_id, 0f32, 0f32, move |event_source, _| {
+        post_mouse_event(monitor, 0f32, 0f32, move |event_source, _| {
             if let Ok(event) = CGEvent::new_scroll_event(
                 event_source,
                 ScrollEventUnit::PIXEL,
@@ -150,8 +146,6 @@ pub fn mouse_scroll_wheel(monitor: &Monitor, delta: f32) -> CoreResult<()> {
 }
 
 pub fn mouse_double_click(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResult<()> {
-    let display_id = monitor.id.parse::<u32>()?;
-
     let (event_type, mouse_button) = match key {
         MouseKey::None => return Err(core_error!("unsupport key")),
         MouseKey::Left => (CGEventType::LeftMouseDown, CGMouseButton::Left),
@@ -174,7 +168,7 @@ pub fn mouse_double_click(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) ->
     };
 
     unsafe {
-        post_mouse_event(display_id, x, y, move |event_source, point| {
+        post_mouse_event(monitor, x, y, move |event_source, point| {
             let event = CGEvent::new_mouse_event(event_source, event_type, point, mouse_button)
                 .map_err(|_| core_error!("create mouse CGEvent failed"))?;
 
@@ -198,22 +192,35 @@ pub fn keyboard_down(key: &tao::keyboard::KeyCode) -> CoreResult<()> {
 }
 
 unsafe fn post_mouse_event(
-    display_id: CGDirectDisplayID,
+    monitor: &Monitor,
     x: f32,
     y: f32,
     event_create_fn: impl Fn(CGEventSource, CGPoint) -> CoreResult<Vec<CGEvent>> + 'static + Send,
 ) -> CoreResult<()> {
+    let display_id: CGDirectDisplayID = monitor.id.parse()?;
+
+    // events are posted in global display space, but the cursor is moved in the space
+    // of display
+    let (desktop_x, desktop_y) = monitor.desktop_position(x, y);
+    let desktop_point = CGPoint::new(desktop_x as f64, desktop_y as f64);
+    let display_point = CGPoint::new(
+        (desktop_x - monitor.left as f32) as f64,
+        (desktop_y - monitor.top as f32) as f64,
+    );
+
     // todo: use self created serial queue
     dispatch::Queue::global(dispatch::QueuePriority::High).barrier_async(move || {
         if let Ok(event_source) = CGEventSource::new(CGEventSourceStateID::HIDSystemState) {
-            let point = CGPoint::new(x as f64, y as f64);
-
-            if let Ok(events) = event_create_fn(event_source, point) {
+            if let Ok(events) = event_create_fn(event_source, desktop_point) {
                 for event in events.iter() {
+                    event.set_integer_value_field(
+                        EventField::EVENT_SOURCE_USER_DATA,
+                        INJECTED_EVENT_USER_DATA,
+                    );
                     event.post(CGEventTapLocation::HID);
                 }
 
-                let _ = CGDisplayMoveCursorToPoint(display_id, point);
+                let _ = CGDisplayMoveCursorToPoint(display_id, display_point);
             }
         }
     });
@@ -225,6 +232,10 @@ fn post_keyboard_event(key: &tao::keyboard::KeyCode, press: bool) -> CoreResult<
     if let Some(vk_key) = map_key_code(key) {
         if let Ok(source) = CGEventSource::new(CGEventSourceStateID::HIDSystemState) {
             if let Ok(event) = CGEvent::new_keyboard_event(source, vk_key, press) {
+                event.set_integer_value_field(
+                    EventField::EVENT_SOURCE_USER_DATA,
+                    INJECTED_EVENT_USER_DATA,
+                );
                 event.post(CGEventTapLocation::HID);
                 Ok(())
             } else {
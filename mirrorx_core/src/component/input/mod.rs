@@ -1,4 +1,5 @@
 pub mod key;
+pub mod layout;
 
 #[cfg(target_os = "macos")]
 mod macos;
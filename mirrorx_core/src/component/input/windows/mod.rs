@@ -23,7 +23,7 @@ pub fn mouse_up(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResult
         0
     };
 
-    unsafe { send_input(&[(mouse_data, dw_flags)], monitor.left, monitor.top, x, y) }
+    unsafe { send_input(&[(mouse_data, dw_flags)], monitor, x, y) }
 }
 
 pub fn mouse_down(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResult<()> {
@@ -44,7 +44,7 @@ pub fn mouse_down(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResu
         0
     };
 
-    unsafe { send_input(&[(mouse_data, dw_flags)], monitor.left, monitor.top, x, y) }
+    unsafe { send_input(&[(mouse_data, dw_flags)], monitor, x, y) }
 }
 
 pub fn mouse_double_click(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResult<()> {
@@ -95,7 +95,7 @@ pub fn mouse_double_click(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) ->
         args.push((mouse_data, up_flags));
     }
 
-    unsafe { send_input(&args, monitor.left, monitor.top, x, y) }
+    unsafe { send_input(&args, monitor, x, y) }
 }
 
 pub fn mouse_move(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResult<()> {
@@ -132,15 +132,14 @@ pub fn mouse_move(monitor: &Monitor, key: &MouseKey, x: f32, y: f32) -> CoreResu
         0
     };
 
-    unsafe { send_input(&[(mouse_data, dw_flags)], monitor.left, monitor.top, x, y) }
+    unsafe { send_input(&[(mouse_data, dw_flags)], monitor, x, y) }
 }
 
 pub fn mouse_scroll_wheel(monitor: &Monitor, delta: f32) -> CoreResult<()> {
     unsafe {
         send_input(
             &[(delta.round() as i32, MOUSEEVENTF_WHEEL)],
-            monitor.left,
-            monitor.top,
+            monitor,
             0f32,
             0f32,
         )
@@ -157,16 +156,19 @@ pub fn keyboard_down(key: &tao::keyboard::KeyCode) -> CoreResult<()> {
 
 unsafe fn send_input(
     args: &[(i32, MOUSE_EVENT_FLAGS)],
-    left: u16,
-    top: u16,
+    monitor: &Monitor,
     screen_coordinate_x: f32,
     screen_coordinate_y: f32,
 ) -> CoreResult<()> {
-    let dx = ((left as f32 + screen_coordinate_x) * 65535f32
+    let (desktop_x, desktop_y) = monitor.desktop_position(screen_coordinate_x, screen_coordinate_y);
+
+    // absolute position of virtual desktop counts from its top left corner, which is
+    // left of or above the primary monitor when other monitors are placed there
+    let dx = ((desktop_x - GetSystemMetrics(SM_XVIRTUALSCREEN) as f32) * 65535f32
         / GetSystemMetrics(SM_CXVIRTUALSCREEN) as f32)
         .round() as i32;
 
-    let dy = ((top as f32 + screen_coordinate_y) * 65535f32
+    let dy = ((desktop_y - GetSystemMetrics(SM_YVIRTUALSCREEN) as f32) * 65535f32
         / GetSystemMetrics(SM_CYVIRTUALSCREEN) as f32)
         .round() as i32;
 
@@ -1,4 +1,8 @@
-use crate::error::CoreResult;
+use super::{advertised_device_ids, key::lan_key};
+use crate::{
+    component::availability::{availability, subscribe_availability, Availability},
+    error::CoreResult,
+};
 use serde::{Deserialize, Serialize};
 use std::{
     net::{IpAddr, Ipv4Addr, SocketAddr},
@@ -15,11 +19,16 @@ pub enum BroadcastPacket {
     TargetDead(String),
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct TargetLivePacket {
     pub hostname: String,
     pub os: String,
     pub os_version: String,
+    pub availability: Availability,
+    // appended after the packet since bincode can't skip unknown fields, peers of
+    // previous releases ignore the trailing bytes
+    #[serde(skip)]
+    pub device_ids: Vec<i64>,
 }
 
 pub struct Discover {
@@ -41,8 +50,7 @@ impl Discover {
         tracing::info!(interface = interface_name, ?ip, "lan discover listen");
 
         let dead_packet = bincode::serialize(&BroadcastPacket::TargetDead(hostname.to_string()))?;
-        let live_packet =
-            bincode::serialize(&BroadcastPacket::TargetLive(create_live_packet(hostname)?))?;
+        let mut live_packet = create_live_packet(hostname)?;
 
         let writer = Arc::new(stream);
         let reader = writer.clone();
@@ -67,7 +75,8 @@ impl Discover {
                     }
                 };
 
-                let packet = match bincode::deserialize::<BroadcastPacket>(&buffer[..buffer_len]) {
+                let mut reader = &buffer[..buffer_len];
+                let mut packet: BroadcastPacket = match bincode::deserialize_from(&mut reader) {
                     Ok(v) => v,
                     Err(err) => {
                         tracing::error!(
@@ -79,16 +88,23 @@ impl Discover {
                     }
                 };
 
+                if let BroadcastPacket::TargetLive(ref mut live_packet) = packet {
+                    live_packet.device_ids = bincode::deserialize(reader).unwrap_or_default();
+                }
+
                 let _ = packet_tx.send((target_addr, packet)).await;
             }
         });
 
         tokio::spawn(async move {
             let mut ticker = tokio::time::interval(Duration::from_secs(11));
+            let mut availability_rx = subscribe_availability();
 
             loop {
                 tokio::select! {
                     _ = ticker.tick() => (),
+                    // tell others at once instead of waiting next tick
+                    Ok(()) = availability_rx.changed() => (),
                     _ = &mut write_exit_rx => {
                         let _ = writer.send(&dead_packet).await;
                         tracing::info!("lan discover broadcast loop exit");
@@ -100,8 +116,29 @@ impl Discover {
                     continue;
                 }
 
+                live_packet.availability = availability();
+
+                let mut buffer =
+                    match bincode::serialize(&BroadcastPacket::TargetLive(live_packet.clone())) {
+                        Ok(buffer) => buffer,
+                        Err(err) => {
+                            tracing::error!(?err, "serialize lan discover live packet failed");
+                            continue;
+                        }
+                    };
+
+                // only peers sharing the lan key can connect directly, others don't need
+                // to know the device ids
+                if lan_key().is_some() {
+                    let device_ids = advertised_device_ids();
+                    if let Err(err) = bincode::serialize_into(&mut buffer, &device_ids) {
+                        tracing::error!(?err, "serialize lan discover device ids failed");
+                        continue;
+                    }
+                }
+
                 if let Err(err) = writer
-                    .send_to(&live_packet, (Ipv4Addr::BROADCAST, 48000))
+                    .send_to(&buffer, (Ipv4Addr::BROADCAST, 48000))
                     .await
                 {
                     tracing::warn!(?err, "lan discover broadcast failed");
@@ -180,5 +217,7 @@ fn create_live_packet(hostname: &str) -> CoreResult<TargetLivePacket> {
         hostname: hostname.to_string(),
         os,
         os_version,
+        availability: availability(),
+        device_ids: Vec::new(),
     })
 }
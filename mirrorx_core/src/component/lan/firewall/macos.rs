@@ -0,0 +1,49 @@
+use super::FirewallState;
+use crate::{core_error, error::CoreResult};
+use std::process::Command;
+
+const SOCKET_FILTER_FW: &str = "/usr/libexec/ApplicationFirewall/socketfilterfw";
+
+pub fn firewall_state() -> CoreResult<FirewallState> {
+    // like `Firewall is enabled. (State = 1)`
+    let enabled = socket_filter_fw(&["--getglobalstate"])?;
+    let enabled = enabled.contains("enabled");
+
+    // like `Firewall is set to block all non-essential incoming connections`, or
+    // `Block all DISABLED!` on earlier releases
+    let block_all = socket_filter_fw(&["--getblockall"])?.to_lowercase();
+    let blocks_all_inbound = block_all.contains("enabled") || block_all.contains("is set to block");
+
+    // like `Incoming connection to the application is blocked`, apps without any rule
+    // are told they're not part of the firewall
+    let exe = std::env::current_exe()?;
+    let app_blocked = socket_filter_fw(&["--getappblocked", &exe.to_string_lossy()])?;
+    let app_allowed = if app_blocked.contains("is blocked") {
+        Some(false)
+    } else if app_blocked.contains("is permitted") {
+        Some(true)
+    } else {
+        None
+    };
+
+    Ok(FirewallState {
+        enabled,
+        blocks_all_inbound: enabled && blocks_all_inbound,
+        app_allowed,
+        // macOS firewall doesn't tell networks apart
+        public_network: false,
+    })
+}
+
+fn socket_filter_fw(args: &[&str]) -> CoreResult<String> {
+    let output = Command::new(SOCKET_FILTER_FW).args(args).output()?;
+    if !output.status.success() {
+        return Err(core_error!(
+            "socketfilterfw {} exits with {}",
+            args.join(" "),
+            output.status
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,38 @@
+//! State of the OS firewall which LAN visits pass through. Firewalls don't tell whether
+//! they'd drop a connection before it comes, so the state only hints the likely cause
+//! when visitors can't reach the listener.
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "windows")]
+use self::windows as sys;
+
+#[cfg(target_os = "macos")]
+mod macos;
+
+#[cfg(target_os = "macos")]
+use self::macos as sys;
+
+use crate::error::CoreResult;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FirewallState {
+    pub enabled: bool,
+    pub blocks_all_inbound: bool,
+    /// Whether the rules of firewall let the app receive connections, None when they
+    /// can't be told, like the app isn't listed in any rule yet.
+    pub app_allowed: Option<bool>,
+    /// The connected network is treated as public, apps which aren't allowed for public
+    /// networks can't receive connections.
+    pub public_network: bool,
+}
+
+/// None on platforms whose firewall state can't be read without privileges.
+pub fn firewall_state() -> CoreResult<Option<FirewallState>> {
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    return sys::firewall_state().map(Some);
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    Ok(None)
+}
@@ -0,0 +1,63 @@
+use super::FirewallState;
+use crate::{error::CoreResult, HRESULT};
+use scopeguard::defer;
+use windows::Win32::{
+    Foundation::VARIANT_FALSE,
+    NetworkManagement::WindowsFirewall::{
+        INetFwPolicy2, NetFwPolicy2, NET_FW_PROFILE2_DOMAIN, NET_FW_PROFILE2_PRIVATE,
+        NET_FW_PROFILE2_PUBLIC,
+    },
+    System::Com::{
+        CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_INPROC_SERVER,
+        COINIT_MULTITHREADED,
+    },
+};
+
+// rules allowing the app are matched by its path and the network profile, telling
+// whether the app is allowed needs walking every rule, so it's left unknown
+pub fn firewall_state() -> CoreResult<FirewallState> {
+    unsafe {
+        // COM may be initialized by the caller thread already
+        let com_initialized = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+
+        defer! {
+            if com_initialized {
+                CoUninitialize();
+            }
+        }
+
+        let policy: INetFwPolicy2 =
+            HRESULT!(CoCreateInstance(&NetFwPolicy2, None, CLSCTX_INPROC_SERVER));
+
+        let current_profiles = HRESULT!(policy.CurrentProfileTypes());
+
+        let mut state = FirewallState {
+            enabled: false,
+            blocks_all_inbound: false,
+            app_allowed: None,
+            public_network: false,
+        };
+
+        // more than one profile is active when interfaces connect different networks
+        for profile in [
+            NET_FW_PROFILE2_DOMAIN,
+            NET_FW_PROFILE2_PRIVATE,
+            NET_FW_PROFILE2_PUBLIC,
+        ] {
+            if current_profiles & profile.0 == 0 {
+                continue;
+            }
+
+            if HRESULT!(policy.FirewallEnabled(profile)) == VARIANT_FALSE {
+                continue;
+            }
+
+            state.enabled = true;
+            state.blocks_all_inbound |=
+                HRESULT!(policy.BlockAllInboundTraffic(profile)) != VARIANT_FALSE;
+            state.public_network |= profile == NET_FW_PROFILE2_PUBLIC;
+        }
+
+        Ok(state)
+    }
+}
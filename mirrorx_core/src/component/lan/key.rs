@@ -0,0 +1,306 @@
+//! LAN key is a secret shared by the devices in a LAN, it's entered on both devices by
+//! users instead of exchanged by signaling server. When it's set, both endpoints prove
+//! the knowledge of it and agree the session keys before the endpoint stream starts:
+//!
+//! 1. active endpoint sends `KeyExchangeHello` which seals its ephemeral exchange public
+//!    key with a key derived from LAN key
+//! 2. passive endpoint opens it, replies `KeyExchangeReply` sealed in the same way with
+//!    its own ephemeral exchange public key, or closes the stream if it can't be opened
+//! 3. both endpoints derive the session keys from the agreement
+
+use crate::{
+    core_error,
+    error::{CoreError, CoreResult},
+    utility::{
+        bincode::{bincode_deserialize, bincode_serialize},
+        net::connect_tcp,
+        nonce_value::NonceValue,
+        rand::generate_random_password,
+    },
+};
+use hmac::Hmac;
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use ring::aead::{Aad, BoundKey, LessSafeKey, Nonce, OpeningKey, SealingKey, UnboundKey};
+use rsa::rand_core::OsRng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::Sha256;
+use std::{net::SocketAddr, sync::RwLock, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+pub(super) const KEY_EXCHANGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// distinguish the sealed secrets of both directions so they can't be reflected
+const ACTIVE_SECRET_AAD: &[u8] = b"mirrorx lan active";
+const PASSIVE_SECRET_AAD: &[u8] = b"mirrorx lan passive";
+
+static LAN_KEY: Lazy<RwLock<Option<String>>> = Lazy::new(|| RwLock::new(None));
+
+#[derive(Serialize, Deserialize)]
+struct KeyExchangeHello {
+    salt: Vec<u8>,
+    sealing_nonce: Vec<u8>,
+    secret: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyExchangeReply {
+    sealing_nonce: Vec<u8>,
+    secret: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct KeyExchangeSecret {
+    pub(super) exchange_public_key: Vec<u8>,
+    pub(super) exchange_nonce: [u8; ring::aead::NONCE_LEN],
+}
+
+/// Set the LAN key which incoming LAN connections must prove, None accepts them
+/// without encryption.
+pub fn set_lan_key(lan_key: Option<String>) {
+    *LAN_KEY.write().unwrap() = lan_key.filter(|lan_key| !lan_key.is_empty());
+}
+
+pub fn lan_key() -> Option<String> {
+    LAN_KEY.read().unwrap().clone()
+}
+
+/// Generate a random LAN key for user to enter on other devices.
+pub fn generate_lan_key() -> String {
+    generate_random_password()
+}
+
+/// Connect to the LAN node and agree the session keys with `lan_key`, the key pair is
+/// `(opening_key, sealing_key)`.
+pub async fn connect_with_lan_key(
+    remote_addr: SocketAddr,
+    lan_key: &str,
+) -> CoreResult<(TcpStream, (OpeningKey<NonceValue>, SealingKey<NonceValue>))> {
+    let mut stream = tokio::time::timeout(KEY_EXCHANGE_TIMEOUT, connect_tcp(remote_addr))
+        .await
+        .map_err(|_| CoreError::Timeout)??;
+
+    let key_pair = tokio::time::timeout(
+        KEY_EXCHANGE_TIMEOUT,
+        active_key_exchange(&mut stream, lan_key),
+    )
+    .await
+    .map_err(|_| CoreError::Timeout)??;
+
+    Ok((stream, key_pair))
+}
+
+/// Agree the session keys with the active endpoint on accepted `stream`.
+pub(crate) async fn accept_with_lan_key(
+    stream: &mut TcpStream,
+    lan_key: &str,
+) -> CoreResult<(OpeningKey<NonceValue>, SealingKey<NonceValue>)> {
+    tokio::time::timeout(KEY_EXCHANGE_TIMEOUT, passive_key_exchange(stream, lan_key))
+        .await
+        .map_err(|_| CoreError::Timeout)?
+}
+
+async fn active_key_exchange(
+    stream: &mut TcpStream,
+    lan_key: &str,
+) -> CoreResult<(OpeningKey<NonceValue>, SealingKey<NonceValue>)> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+
+    let secret_key = derive_secret_key(lan_key, &salt)?;
+
+    let secure_random = ring::rand::SystemRandom::new();
+    let exchange_private_key =
+        ring::agreement::EphemeralPrivateKey::generate(&ring::agreement::X25519, &secure_random)?;
+    let exchange_public_key = exchange_private_key.compute_public_key()?;
+
+    let mut exchange_nonce = [0u8; ring::aead::NONCE_LEN];
+    OsRng.fill_bytes(&mut exchange_nonce);
+
+    let (sealing_nonce, secret) = seal_secret(
+        &secret_key,
+        ACTIVE_SECRET_AAD,
+        &KeyExchangeSecret {
+            exchange_public_key: exchange_public_key.as_ref().to_vec(),
+            exchange_nonce,
+        },
+    )?;
+
+    write_packet(
+        stream,
+        &KeyExchangeHello {
+            salt: salt.to_vec(),
+            sealing_nonce,
+            secret,
+        },
+    )
+    .await?;
+
+    let reply: KeyExchangeReply = read_packet(stream)
+        .await
+        .map_err(|_| core_error!("remote rejected the lan key"))?;
+
+    let remote_secret = open_secret(
+        &secret_key,
+        PASSIVE_SECRET_AAD,
+        &reply.sealing_nonce,
+        reply.secret,
+    )
+    .map_err(|_| core_error!("lan key mismatch"))?;
+
+    agree_session_keys(exchange_private_key, exchange_nonce, remote_secret)
+}
+
+async fn passive_key_exchange(
+    stream: &mut TcpStream,
+    lan_key: &str,
+) -> CoreResult<(OpeningKey<NonceValue>, SealingKey<NonceValue>)> {
+    let hello: KeyExchangeHello = read_packet(stream).await?;
+
+    let secret_key = derive_secret_key(lan_key, &hello.salt)?;
+
+    // nothing is replied when remote doesn't know the lan key
+    let remote_secret = open_secret(
+        &secret_key,
+        ACTIVE_SECRET_AAD,
+        &hello.sealing_nonce,
+        hello.secret,
+    )
+    .map_err(|_| core_error!("lan key mismatch"))?;
+
+    let secure_random = ring::rand::SystemRandom::new();
+    let exchange_private_key =
+        ring::agreement::EphemeralPrivateKey::generate(&ring::agreement::X25519, &secure_random)?;
+    let exchange_public_key = exchange_private_key.compute_public_key()?;
+
+    let mut exchange_nonce = [0u8; ring::aead::NONCE_LEN];
+    OsRng.fill_bytes(&mut exchange_nonce);
+
+    let (sealing_nonce, secret) = seal_secret(
+        &secret_key,
+        PASSIVE_SECRET_AAD,
+        &KeyExchangeSecret {
+            exchange_public_key: exchange_public_key.as_ref().to_vec(),
+            exchange_nonce,
+        },
+    )?;
+
+    write_packet(
+        stream,
+        &KeyExchangeReply {
+            sealing_nonce,
+            secret,
+        },
+    )
+    .await?;
+
+    agree_session_keys(exchange_private_key, exchange_nonce, remote_secret)
+}
+
+// keys are derived in the same way as the visit through signaling server, each side
+// seals with the key salted by its own nonce and opens with the one salted by remote's
+pub(super) fn agree_session_keys(
+    exchange_private_key: ring::agreement::EphemeralPrivateKey,
+    exchange_nonce: [u8; ring::aead::NONCE_LEN],
+    remote_secret: KeyExchangeSecret,
+) -> CoreResult<(OpeningKey<NonceValue>, SealingKey<NonceValue>)> {
+    let remote_exchange_public_key = ring::agreement::UnparsedPublicKey::new(
+        &ring::agreement::X25519,
+        remote_secret.exchange_public_key,
+    );
+
+    let (raw_sealing_key, raw_opening_key) = ring::agreement::agree_ephemeral(
+        exchange_private_key,
+        &remote_exchange_public_key,
+        ring::error::Unspecified,
+        |key_material| {
+            let sealing_key = derive_session_key(key_material, &exchange_nonce)?;
+            let opening_key = derive_session_key(key_material, &remote_secret.exchange_nonce)?;
+            Ok((sealing_key, opening_key))
+        },
+    )?;
+
+    let unbound_sealing_key = UnboundKey::new(&ring::aead::AES_256_GCM, &raw_sealing_key)?;
+    let sealing_key = SealingKey::new(
+        unbound_sealing_key,
+        NonceValue::new(remote_secret.exchange_nonce),
+    );
+
+    let unbound_opening_key = UnboundKey::new(&ring::aead::AES_256_GCM, &raw_opening_key)?;
+    let opening_key = OpeningKey::new(unbound_opening_key, NonceValue::new(exchange_nonce));
+
+    Ok((opening_key, sealing_key))
+}
+
+fn derive_session_key(
+    key_material: &[u8],
+    salt: &[u8],
+) -> Result<Vec<u8>, ring::error::Unspecified> {
+    ring::hkdf::Salt::new(ring::hkdf::HKDF_SHA512, salt)
+        .extract(key_material)
+        .expand(&["".as_bytes()], &ring::aead::AES_256_GCM)
+        .and_then(|orm| {
+            let mut key = vec![0u8; ring::aead::AES_256_GCM.key_len()];
+            orm.fill(&mut key)?;
+            Ok(key)
+        })
+}
+
+fn derive_secret_key(lan_key: &str, salt: &[u8]) -> CoreResult<LessSafeKey> {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(lan_key.as_bytes(), salt, 10000, &mut key);
+
+    Ok(LessSafeKey::new(UnboundKey::new(
+        &ring::aead::AES_256_GCM,
+        &key,
+    )?))
+}
+
+fn seal_secret(
+    key: &LessSafeKey,
+    aad: &[u8],
+    secret: &KeyExchangeSecret,
+) -> CoreResult<(Vec<u8>, Vec<u8>)> {
+    let mut nonce = [0u8; ring::aead::NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+
+    let mut buffer = bincode_serialize(secret)?;
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(aad),
+        &mut buffer,
+    )?;
+
+    Ok((nonce.to_vec(), buffer))
+}
+
+fn open_secret(
+    key: &LessSafeKey,
+    aad: &[u8],
+    nonce: &[u8],
+    mut secret: Vec<u8>,
+) -> CoreResult<KeyExchangeSecret> {
+    let nonce = Nonce::try_assume_unique_for_key(nonce)?;
+    let buffer = key.open_in_place(nonce, Aad::from(aad), &mut secret)?;
+    bincode_deserialize(buffer)
+}
+
+pub(super) async fn write_packet<T: Serialize>(
+    stream: &mut TcpStream,
+    packet: &T,
+) -> CoreResult<()> {
+    let buffer = bincode_serialize(packet)?;
+    stream.write_u16_le(buffer.len() as u16).await?;
+    stream.write_all(&buffer).await?;
+    Ok(())
+}
+
+pub(super) async fn read_packet<T: DeserializeOwned>(stream: &mut TcpStream) -> CoreResult<T> {
+    let len = stream.read_u16_le().await?;
+    let mut buffer = vec![0u8; len as usize];
+    stream.read_exact(&mut buffer).await?;
+    bincode_deserialize(&buffer)
+}
@@ -0,0 +1,342 @@
+//! Devices in the same LAN can be paired offline, without signaling server or password:
+//! each device shows its peer card, which carries its long-term identity public key, as
+//! QR code or file and the other one imports it. Once both devices imported the card of
+//! the other, LAN connections between them are authenticated by the identity keys:
+//!
+//! 1. active endpoint sends the paired marker and `PeerExchange` which carries its
+//!    identity public key and the ephemeral exchange public key signed by it
+//! 2. passive endpoint closes the stream unless the identity is paired, otherwise it
+//!    replies `PeerExchange` in the same way, the signature covers the exchange public key
+//!    of active endpoint as well so the reply can't be replayed
+//! 3. active endpoint checks the identity is paired, both endpoints derive the session
+//!    keys from the agreement
+
+use super::{
+    get_hostname,
+    key::{agree_session_keys, read_packet, write_packet, KeyExchangeSecret, KEY_EXCHANGE_TIMEOUT},
+};
+use crate::{
+    api::{
+        config::{entity::keystore::PairedPeer, LocalStorage},
+        signaling::fingerprint::{
+            identity_fingerprint, identity_public_key, load_identity_key, sign_exchange_key,
+            verify_exchange_key,
+        },
+    },
+    core_error,
+    error::{CoreError, CoreResult},
+    utility::{net::connect_tcp, nonce_value::NonceValue},
+};
+use base64::{engine::general_purpose::STANDARD as base64_standard, Engine};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use ring::{
+    aead::{OpeningKey, SealingKey},
+    agreement::EphemeralPrivateKey,
+    signature::{Ed25519KeyPair, ED25519_PUBLIC_KEY_LEN},
+};
+use rsa::rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use url::Url;
+
+const PEER_CARD_SCHEME: &str = "mirrorx";
+const PEER_CARD_HOST: &str = "peer";
+
+// LAN key hello starts with its length and plain endpoint stream with the length of its
+// first frame, neither of them is zero
+const PAIRED_MARKER: u16 = 0;
+
+static IDENTITY_KEY: Lazy<RwLock<Option<Arc<Ed25519KeyPair>>>> = Lazy::new(|| RwLock::new(None));
+
+// the stored peers are cached so LAN listener, which has no storage, checks them as well
+static PAIRED_PEERS: Lazy<RwLock<Vec<PairedPeer>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+#[derive(Serialize, Deserialize)]
+struct PeerExchange {
+    identity_public_key: Vec<u8>,
+    exchange_public_key: Vec<u8>,
+    exchange_nonce: [u8; ring::aead::NONCE_LEN],
+    signature: Vec<u8>,
+}
+
+/// What a device shows to be paired with.
+#[derive(Debug, Clone)]
+pub struct PeerCard {
+    pub name: String,
+    pub public_key: Vec<u8>,
+}
+
+impl PeerCard {
+    pub fn fingerprint(&self) -> String {
+        identity_fingerprint(&self.public_key)
+    }
+
+    /// Encode as `mirrorx://peer?name=..&key=..`.
+    pub fn encode(&self) -> String {
+        let mut url = Url::parse(&format!("{PEER_CARD_SCHEME}://{PEER_CARD_HOST}"))
+            .expect("peer card base url should be valid");
+
+        url.query_pairs_mut()
+            .append_pair("name", &self.name)
+            .append_pair("key", &base64_standard.encode(&self.public_key));
+
+        url.to_string()
+    }
+
+    pub fn parse(card: &str) -> CoreResult<PeerCard> {
+        let url = Url::parse(card.trim())?;
+
+        if url.scheme() != PEER_CARD_SCHEME || url.host_str() != Some(PEER_CARD_HOST) {
+            return Err(core_error!("not a peer card"));
+        }
+
+        let query = |key: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.to_string())
+                .ok_or(core_error!("peer card missing '{}'", key))
+        };
+
+        let public_key = base64_standard.decode(query("key")?)?;
+        if public_key.len() != ED25519_PUBLIC_KEY_LEN {
+            return Err(core_error!("peer card carries an invalid key"));
+        }
+
+        Ok(PeerCard {
+            name: query("name")?,
+            public_key,
+        })
+    }
+}
+
+/// Load the identity key of this device and the paired peers, which LAN connections are
+/// authenticated with.
+pub fn load_keystore(storage: &LocalStorage) -> CoreResult<()> {
+    *IDENTITY_KEY.write().unwrap() = Some(Arc::new(load_identity_key(storage)?));
+    set_paired_peers(storage.keystore().get_peers()?);
+    Ok(())
+}
+
+/// Replace the peers which LAN connections are authenticated with, it's called whenever
+/// the stored peers change.
+pub fn set_paired_peers(peers: Vec<PairedPeer>) {
+    *PAIRED_PEERS.write().unwrap() = peers;
+}
+
+pub fn has_paired_peers() -> bool {
+    !PAIRED_PEERS.read().unwrap().is_empty()
+}
+
+/// The peer card of this device, it's named by the host name.
+pub fn export_peer_card(storage: &LocalStorage) -> CoreResult<String> {
+    let identity_key = load_identity_key(storage)?;
+
+    Ok(PeerCard {
+        name: get_hostname()?,
+        public_key: identity_public_key(&identity_key).to_vec(),
+    }
+    .encode())
+}
+
+/// Pair the device of the peer card, connections from it are accepted once it imported
+/// the card of this device as well.
+pub fn import_peer_card(storage: &LocalStorage, card: &str) -> CoreResult<PeerCard> {
+    let card = PeerCard::parse(card)?;
+
+    let identity_key = load_identity_key(storage)?;
+    if card.public_key == identity_public_key(&identity_key) {
+        return Err(core_error!("can't pair with this device itself"));
+    }
+
+    storage.keystore().add_peer(&card.name, &card.public_key)?;
+
+    tracing::info!(name = %card.name, fingerprint = %card.fingerprint(), "pair peer");
+
+    Ok(card)
+}
+
+/// Connect to the paired LAN node and agree the session keys with the identity keys, the
+/// key pair is `(opening_key, sealing_key)`.
+pub async fn connect_with_peer_key(
+    remote_addr: SocketAddr,
+) -> CoreResult<(
+    TcpStream,
+    (OpeningKey<NonceValue>, SealingKey<NonceValue>),
+    PairedPeer,
+)> {
+    let identity_key = identity_key()?;
+
+    let mut stream = tokio::time::timeout(KEY_EXCHANGE_TIMEOUT, connect_tcp(remote_addr))
+        .await
+        .map_err(|_| CoreError::Timeout)??;
+
+    let (key_pair, peer) = tokio::time::timeout(
+        KEY_EXCHANGE_TIMEOUT,
+        active_peer_exchange(&mut stream, &identity_key),
+    )
+    .await
+    .map_err(|_| CoreError::Timeout)??;
+
+    Ok((stream, key_pair, peer))
+}
+
+/// Whether the active endpoint of accepted `stream` authenticates with its identity key,
+/// nothing is consumed from the stream.
+pub(crate) async fn requests_peer_key(stream: &TcpStream) -> bool {
+    let mut marker = [0u8; 2];
+
+    matches!(
+        tokio::time::timeout(KEY_EXCHANGE_TIMEOUT, stream.peek(&mut marker)).await,
+        Ok(Ok(2))
+    ) && u16::from_le_bytes(marker) == PAIRED_MARKER
+}
+
+/// Agree the session keys with the paired active endpoint on accepted `stream`.
+pub(crate) async fn accept_with_peer_key(
+    stream: &mut TcpStream,
+) -> CoreResult<((OpeningKey<NonceValue>, SealingKey<NonceValue>), PairedPeer)> {
+    let identity_key = identity_key()?;
+
+    tokio::time::timeout(
+        KEY_EXCHANGE_TIMEOUT,
+        passive_peer_exchange(stream, &identity_key),
+    )
+    .await
+    .map_err(|_| CoreError::Timeout)?
+}
+
+async fn active_peer_exchange(
+    stream: &mut TcpStream,
+    identity_key: &Ed25519KeyPair,
+) -> CoreResult<((OpeningKey<NonceValue>, SealingKey<NonceValue>), PairedPeer)> {
+    let (exchange_private_key, exchange_public_key, exchange_nonce) = generate_exchange_key()?;
+
+    stream.write_u16_le(PAIRED_MARKER).await?;
+    write_packet(
+        stream,
+        &PeerExchange {
+            identity_public_key: identity_public_key(identity_key).to_vec(),
+            signature: sign_exchange_key(identity_key, &exchange_public_key, &exchange_nonce),
+            exchange_public_key: exchange_public_key.clone(),
+            exchange_nonce,
+        },
+    )
+    .await?;
+
+    let reply: PeerExchange = read_packet(stream)
+        .await
+        .map_err(|_| core_error!("remote device isn't paired with this device"))?;
+
+    let peer =
+        paired_peer(&reply.identity_public_key).ok_or(core_error!("remote device isn't paired"))?;
+
+    if !verify_exchange_key(
+        &reply.identity_public_key,
+        &reply.exchange_public_key,
+        &[&reply.exchange_nonce[..], &exchange_public_key].concat(),
+        &reply.signature,
+    ) {
+        return Err(core_error!("remote device signed exchange key incorrectly"));
+    }
+
+    let key_pair = agree_session_keys(
+        exchange_private_key,
+        exchange_nonce,
+        KeyExchangeSecret {
+            exchange_public_key: reply.exchange_public_key,
+            exchange_nonce: reply.exchange_nonce,
+        },
+    )?;
+
+    Ok((key_pair, peer))
+}
+
+async fn passive_peer_exchange(
+    stream: &mut TcpStream,
+    identity_key: &Ed25519KeyPair,
+) -> CoreResult<((OpeningKey<NonceValue>, SealingKey<NonceValue>), PairedPeer)> {
+    if stream.read_u16_le().await? != PAIRED_MARKER {
+        return Err(core_error!("stream isn't authenticated with peer key"));
+    }
+
+    let hello: PeerExchange = read_packet(stream).await?;
+
+    // nothing is replied when remote isn't paired
+    let peer =
+        paired_peer(&hello.identity_public_key).ok_or(core_error!("remote device isn't paired"))?;
+
+    if !verify_exchange_key(
+        &hello.identity_public_key,
+        &hello.exchange_public_key,
+        &hello.exchange_nonce,
+        &hello.signature,
+    ) {
+        return Err(core_error!("remote device signed exchange key incorrectly"));
+    }
+
+    let (exchange_private_key, exchange_public_key, exchange_nonce) = generate_exchange_key()?;
+
+    write_packet(
+        stream,
+        &PeerExchange {
+            identity_public_key: identity_public_key(identity_key).to_vec(),
+            signature: sign_exchange_key(
+                identity_key,
+                &exchange_public_key,
+                &[&exchange_nonce[..], &hello.exchange_public_key].concat(),
+            ),
+            exchange_public_key,
+            exchange_nonce,
+        },
+    )
+    .await?;
+
+    let key_pair = agree_session_keys(
+        exchange_private_key,
+        exchange_nonce,
+        KeyExchangeSecret {
+            exchange_public_key: hello.exchange_public_key,
+            exchange_nonce: hello.exchange_nonce,
+        },
+    )?;
+
+    Ok((key_pair, peer))
+}
+
+fn generate_exchange_key() -> CoreResult<(EphemeralPrivateKey, Vec<u8>, [u8; ring::aead::NONCE_LEN])>
+{
+    let secure_random = ring::rand::SystemRandom::new();
+    let exchange_private_key =
+        EphemeralPrivateKey::generate(&ring::agreement::X25519, &secure_random)?;
+    let exchange_public_key = exchange_private_key.compute_public_key()?.as_ref().to_vec();
+
+    let mut exchange_nonce = [0u8; ring::aead::NONCE_LEN];
+    OsRng.fill_bytes(&mut exchange_nonce);
+
+    Ok((exchange_private_key, exchange_public_key, exchange_nonce))
+}
+
+fn identity_key() -> CoreResult<Arc<Ed25519KeyPair>> {
+    IDENTITY_KEY
+        .read()
+        .unwrap()
+        .clone()
+        .ok_or(core_error!("keystore not loaded"))
+}
+
+fn paired_peer(public_key: &[u8]) -> Option<PairedPeer> {
+    PAIRED_PEERS
+        .read()
+        .unwrap()
+        .iter()
+        .find(|peer| peer.public_key == public_key)
+        .cloned()
+}
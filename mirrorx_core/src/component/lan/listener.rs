@@ -0,0 +1,227 @@
+use super::{
+    key::{accept_with_lan_key, lan_key},
+    keystore::{accept_with_peer_key, requests_peer_key},
+};
+use crate::{
+    api::endpoint::{
+        acl::{acl_admits, AclOrigin},
+        create_passive_endpoint_client,
+        id::EndPointID,
+        queue::acquire_session_slot,
+        EndPointStream,
+    },
+    component::availability::{admit_visit, availability},
+    error::CoreResult,
+};
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashSet,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    net::{TcpListener, TcpSocket, TcpStream},
+    task::JoinHandle,
+};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Port which LAN visitors connect to, peers don't negotiate it.
+pub const LISTEN_PORT: u16 = 48001;
+
+// local addresses of the probe connections, the listener drops them at once instead of
+// serving them as visits
+static PROBE_ADDRS: Lazy<Mutex<HashSet<SocketAddr>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+pub struct Listener {
+    local_addr: SocketAddr,
+    remote_accepted: Arc<AtomicBool>,
+    exit_tx: Option<tokio::sync::oneshot::Sender<()>>,
+    serve_handle: Option<JoinHandle<()>>,
+}
+
+impl Listener {
+    /// Listen on the address of one interface, or all of them with the unspecified one.
+    pub async fn new(ip: IpAddr) -> CoreResult<Self> {
+        let listener = TcpListener::bind((ip, LISTEN_PORT)).await?;
+        let local_addr = listener.local_addr()?;
+        let remote_accepted = Arc::new(AtomicBool::new(false));
+        let (exit_tx, mut exit_rx) = tokio::sync::oneshot::channel();
+        tracing::info!(?local_addr, "local lan listener listen");
+
+        let accepted = remote_accepted.clone();
+        let serve_handle = tokio::spawn(async move {
+            loop {
+                let (stream, addr) = tokio::select! {
+                    _ = &mut exit_rx => {
+                        tracing::info!("local lan listener exit");
+                        return;
+                    },
+                    res = listener.accept() => match res {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            tracing::error!(?err, "local lan listener accept stream failed");
+                            continue;
+                        }
+                    }
+                };
+
+                if PROBE_ADDRS.lock().unwrap().remove(&addr) {
+                    continue;
+                }
+
+                tracing::info!(?addr, "local lan listener accept stream");
+                accepted.store(true, Ordering::SeqCst);
+
+                // key exchange waits for remote, don't block accepting others
+                tokio::spawn(serve_stream(stream, addr));
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            remote_accepted,
+            exit_tx: Some(exit_tx),
+            serve_handle: Some(serve_handle),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Whether any visitor has reached the listener since it started, which proves the
+    /// firewall lets visitors in.
+    pub fn remote_accepted(&self) -> bool {
+        self.remote_accepted.load(Ordering::SeqCst)
+    }
+
+    /// Stop listening and wait until the port is released, so it can be bound again.
+    pub async fn close(mut self) {
+        if let Some(exit_tx) = self.exit_tx.take() {
+            let _ = exit_tx.send(());
+        }
+
+        if let Some(serve_handle) = self.serve_handle.take() {
+            let _ = serve_handle.await;
+        }
+    }
+}
+
+/// Connect to the listener from `local_ip`, which tells whether it's reachable through
+/// the interface of that address.
+pub async fn probe(local_ip: IpAddr, listener_addr: SocketAddr) -> bool {
+    let (socket, probe_addr) = match bind_probe_socket(local_ip) {
+        Ok(socket) => socket,
+        Err(err) => {
+            tracing::warn!(?err, ?local_ip, "bind lan listener probe socket failed");
+            return false;
+        }
+    };
+
+    PROBE_ADDRS.lock().unwrap().insert(probe_addr);
+
+    match tokio::time::timeout(PROBE_TIMEOUT, socket.connect(listener_addr)).await {
+        // the listener removes the address when it accepts the stream
+        Ok(Ok(_)) => true,
+        _ => {
+            PROBE_ADDRS.lock().unwrap().remove(&probe_addr);
+            false
+        }
+    }
+}
+
+fn bind_probe_socket(local_ip: IpAddr) -> std::io::Result<(TcpSocket, SocketAddr)> {
+    let socket = match local_ip {
+        IpAddr::V4(_) => TcpSocket::new_v4()?,
+        IpAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+
+    socket.bind(SocketAddr::new(local_ip, 0))?;
+    let probe_addr = socket.local_addr()?;
+
+    Ok((socket, probe_addr))
+}
+
+async fn serve_stream(mut stream: TcpStream, addr: SocketAddr) {
+    // LAN visitor has no channel for a rejection reason, the stream is just closed
+    if !acl_admits(AclOrigin::Lan(addr.ip())) {
+        tracing::info!(?addr, "reject lan visit denied by acl");
+        return;
+    }
+
+    // the visit waits while user is busy, the stream is closed when it's rejected
+    if !admit_visit().await {
+        tracing::info!(
+            ?addr,
+            availability = ?availability(),
+            "reject lan visit since user is unavailable"
+        );
+        return;
+    }
+
+    // remote must prove the lan key when it's set, or the identity key when it's paired
+    let endpoint_id = EndPointID::LANID {
+        local_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        remote_ip: addr.ip(),
+    };
+
+    // LAN visitor isn't told its position, it just waits for the stream
+    let session_slot = match acquire_session_slot(endpoint_id, |_| async {}).await {
+        Ok(session_slot) => session_slot,
+        Err(rejection) => {
+            tracing::info!(?addr, ?rejection, "lan visit isn't admitted from the queue");
+            return;
+        }
+    };
+
+    // paired devices authenticate with their identity keys instead of the lan key
+    let key_pair = if requests_peer_key(&stream).await {
+        match accept_with_peer_key(&mut stream).await {
+            Ok((key_pair, peer)) => {
+                tracing::info!(?addr, name = %peer.name, "paired peer authenticated");
+                Some(key_pair)
+            }
+            Err(err) => {
+                tracing::warn!(?addr, ?err, "peer key exchange failed");
+                return;
+            }
+        }
+    } else {
+        match lan_key() {
+            Some(lan_key) => match accept_with_lan_key(&mut stream, &lan_key).await {
+                Ok(key_pair) => Some(key_pair),
+                Err(err) => {
+                    tracing::warn!(?addr, ?err, "lan key exchange failed");
+                    return;
+                }
+            },
+            None => None,
+        }
+    };
+
+    if let Err(err) = create_passive_endpoint_client(
+        endpoint_id,
+        key_pair,
+        EndPointStream::PassiveTCP(stream),
+        None,
+        session_slot,
+        None,
+    )
+    .await
+    {
+        tracing::error!(?err, "create passive endpoint client from lan failed");
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Some(exit_tx) = self.exit_tx.take() {
+            let _ = exit_tx.send(());
+        }
+    }
+}
@@ -1,19 +1,35 @@
+pub mod key;
+pub mod keystore;
+
 mod discover;
-mod server;
+mod firewall;
+mod listener;
+
+pub use self::listener::LISTEN_PORT;
 
-use self::discover::BroadcastPacket;
-use crate::{error::CoreResult, utility::os::enum_broadcast_network_interfaces};
+use self::{discover::BroadcastPacket, firewall::firewall_state, listener::Listener};
+use super::availability::Availability;
+use crate::{
+    api::config::change::{self, ConfigChange},
+    error::CoreResult,
+    utility::{net::bind_ip, os::enum_broadcast_network_interfaces},
+};
+use async_broadcast::RecvError;
 use fxhash::FxHashMap;
+use once_cell::sync::Lazy;
 use serde::Serialize;
 use std::{
-    net::{IpAddr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Weak,
     },
     time::Duration,
 };
-use tokio::sync::{mpsc::Receiver, RwLock};
+use tokio::sync::{mpsc::Receiver, Mutex, RwLock};
+
+static ADVERTISED_DEVICE_IDS: Lazy<std::sync::RwLock<Vec<i64>>> =
+    Lazy::new(|| std::sync::RwLock::new(Vec::new()));
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Node {
@@ -21,24 +37,92 @@ pub struct Node {
     pub addrs: FxHashMap<IpAddr, i64>,
     pub os: String,
     pub os_version: String,
+    pub availability: Availability,
+    pub device_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallVerdict {
+    /// Visitors have reached the listener, or no firewall stands in their way.
+    Open,
+    /// The firewall blocks the app or the network, visitors can't reach the listener.
+    LikelyBlocked,
+    Unknown,
+}
+
+/// What user can do when LAN visitors can't connect, the app tells them in its words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListenerHint {
+    /// The listener isn't reachable even from this device, its port may be taken by
+    /// another app or the address it's bound to is gone, restarting LAN may help.
+    NotListening,
+    /// No network interface can be reached by other devices, connect to a network.
+    NoInterface,
+    /// The listener is reachable on this device but not through the interface, it's
+    /// likely bound to another interface.
+    InterfaceUnreachable,
+    /// The firewall blocks all inbound connections, turn it off in system settings.
+    FirewallBlocksAllInbound,
+    /// A rule of firewall blocks the app, allow it in system settings.
+    FirewallBlocksApp,
+    /// The network is treated as public, mark it private or allow the app for public
+    /// networks.
+    PublicNetwork,
+    /// The firewall is on and whether it allows the app is unknown, make sure the app
+    /// is allowed if visitors can't connect.
+    AllowAppInFirewall,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ListenerReport {
+    /// None when the listener failed to bind its address.
+    pub bound_addr: Option<SocketAddr>,
+    /// Whether the listener is reachable through loopback, None when it's bound to one
+    /// interface.
+    pub loopback_reachable: Option<bool>,
+    /// The address of interface which the listener is probed through.
+    pub interface_addr: Option<IpAddr>,
+    pub interface_reachable: bool,
+    /// Whether any visitor has reached the listener since it started.
+    pub remote_accepted: bool,
+    pub firewall: FirewallVerdict,
+    pub hints: Vec<ListenerHint>,
+}
+
+/// Set the device ids of every domain which live packets carry, so peers sharing the
+/// lan key can reach the device directly when they visit it by device id.
+pub fn set_advertised_device_ids(device_ids: Vec<i64>) {
+    *ADVERTISED_DEVICE_IDS.write().unwrap() = device_ids;
+}
+
+fn advertised_device_ids() -> Vec<i64> {
+    ADVERTISED_DEVICE_IDS.read().unwrap().clone()
 }
 
 pub struct LANProvider {
     nodes_cache: Arc<RwLock<FxHashMap<String, Node>>>,
     discoverable: Arc<AtomicBool>,
+    listener: Arc<Mutex<Option<Listener>>>,
     _discovers: Vec<discover::Discover>,
-    _server: server::Server,
 }
 
 impl LANProvider {
-    pub async fn new() -> CoreResult<Self> {
+    /// Listen on `listen_ip` for LAN visitors, or all interfaces when it's None.
+    pub async fn new(discoverable: bool, listen_ip: Option<IpAddr>) -> CoreResult<Self> {
         let hostname = format!("{}.mirrorx.lan", get_hostname()?);
         let mut discovers = Vec::new();
-        let discoverable = Arc::new(AtomicBool::new(true));
+        let discoverable = Arc::new(AtomicBool::new(discoverable));
         let (packet_tx, packet_rx) = tokio::sync::mpsc::channel(64);
 
         if cfg!(target_os = "windows") {
-            let broadcast_interfaces = enum_broadcast_network_interfaces()?;
+            // only the preferred interface broadcasts when user picks one
+            let bind_ip = bind_ip();
+            let broadcast_interfaces = enum_broadcast_network_interfaces()?
+                .into_iter()
+                .filter(|(_, ip)| bind_ip.map_or(true, |bind_ip| bind_ip == *ip));
+
             for (interface_name, ip) in broadcast_interfaces {
                 discovers.push(
                     discover::Discover::new(
@@ -64,16 +148,19 @@ impl LANProvider {
             );
         }
 
-        let server = server::Server::new().await?;
+        let listen_ip = listen_ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        let listener = Arc::new(Mutex::new(Some(Listener::new(listen_ip).await?)));
         let nodes_cache = Arc::new(RwLock::new(FxHashMap::default()));
 
         serve_discover_nodes(hostname, nodes_cache.clone(), packet_rx);
+        serve_discoverable_changes(Arc::downgrade(&discoverable));
+        serve_listen_ip_changes(Arc::downgrade(&listener));
 
         Ok(LANProvider {
             nodes_cache,
             discoverable,
+            listener,
             _discovers: discovers,
-            _server: server,
         })
     }
 
@@ -83,6 +170,18 @@ impl LANProvider {
         nodes
     }
 
+    /// The most recently seen address of the node which advertises `device_id`.
+    pub async fn find_device(&self, device_id: i64) -> Option<IpAddr> {
+        self.nodes_cache
+            .read()
+            .await
+            .values()
+            .filter(|node| node.device_ids.contains(&device_id))
+            .flat_map(|node| node.addrs.iter())
+            .max_by_key(|(_, ts)| **ts)
+            .map(|(addr, _)| *addr)
+    }
+
     pub fn discoverable(&self) -> bool {
         self.discoverable.load(Ordering::SeqCst)
     }
@@ -90,6 +189,179 @@ impl LANProvider {
     pub fn set_discoverable(&self, discoverable: bool) {
         self.discoverable.store(discoverable, Ordering::SeqCst)
     }
+
+    /// Stop discovering and listening, and wait until the listener releases its port.
+    pub async fn close(self) {
+        if let Some(listener) = self.listener.lock().await.take() {
+            listener.close().await;
+        }
+    }
+
+    /// Probe the listener through loopback and an interface, and read the firewall state
+    /// to hint why visitors can't connect.
+    pub async fn listener_report(&self) -> ListenerReport {
+        let (bound_addr, remote_accepted) = match *self.listener.lock().await {
+            Some(ref listener) => (Some(listener.local_addr()), listener.remote_accepted()),
+            None => (None, false),
+        };
+
+        let mut report = ListenerReport {
+            bound_addr,
+            loopback_reachable: None,
+            interface_addr: None,
+            interface_reachable: false,
+            remote_accepted,
+            firewall: FirewallVerdict::Unknown,
+            hints: Vec::new(),
+        };
+
+        let Some(bound_addr) = bound_addr else {
+            report.hints.push(ListenerHint::NotListening);
+            return report;
+        };
+
+        if bound_addr.ip().is_unspecified() {
+            let loopback_reachable =
+                listener::probe(IpAddr::V4(Ipv4Addr::LOCALHOST), bound_addr).await;
+            report.loopback_reachable = Some(loopback_reachable);
+
+            report.interface_addr = match enum_broadcast_network_interfaces() {
+                Ok(interfaces) => interfaces.into_iter().map(|(_, ip)| ip).next(),
+                Err(err) => {
+                    tracing::warn!(?err, "enum network interfaces failed");
+                    None
+                }
+            };
+        } else {
+            report.interface_addr = Some(bound_addr.ip());
+        }
+
+        if let Some(interface_addr) = report.interface_addr {
+            let listener_addr = SocketAddr::new(interface_addr, bound_addr.port());
+            report.interface_reachable = listener::probe(interface_addr, listener_addr).await;
+        }
+
+        match (report.loopback_reachable, report.interface_addr) {
+            (Some(false), _) => report.hints.push(ListenerHint::NotListening),
+            (_, None) => report.hints.push(ListenerHint::NoInterface),
+            (None, Some(_)) if !report.interface_reachable => {
+                report.hints.push(ListenerHint::NotListening)
+            }
+            (Some(true), Some(_)) if !report.interface_reachable => {
+                report.hints.push(ListenerHint::InterfaceUnreachable)
+            }
+            _ => {}
+        }
+
+        // visitors got in, whatever the firewall says
+        if remote_accepted {
+            report.firewall = FirewallVerdict::Open;
+            return report;
+        }
+
+        let firewall_state = match firewall_state() {
+            Ok(Some(firewall_state)) => firewall_state,
+            Ok(None) => return report,
+            Err(err) => {
+                tracing::warn!(?err, "read firewall state failed");
+                return report;
+            }
+        };
+
+        if !firewall_state.enabled {
+            report.firewall = FirewallVerdict::Open;
+            return report;
+        }
+
+        if firewall_state.blocks_all_inbound {
+            report.hints.push(ListenerHint::FirewallBlocksAllInbound);
+        }
+
+        if firewall_state.public_network {
+            report.hints.push(ListenerHint::PublicNetwork);
+        }
+
+        match firewall_state.app_allowed {
+            Some(false) => report.hints.push(ListenerHint::FirewallBlocksApp),
+            None => report.hints.push(ListenerHint::AllowAppInFirewall),
+            Some(true) => {}
+        }
+
+        report.firewall = if firewall_state.blocks_all_inbound
+            || firewall_state.app_allowed == Some(false)
+            || firewall_state.public_network
+        {
+            FirewallVerdict::LikelyBlocked
+        } else if firewall_state.app_allowed == Some(true) {
+            FirewallVerdict::Open
+        } else {
+            FirewallVerdict::Unknown
+        };
+
+        report
+    }
+}
+
+// the listener is bound again when the address is changed in settings, the process exits
+// once the provider is dropped
+fn serve_listen_ip_changes(listener: Weak<Mutex<Option<Listener>>>) {
+    let mut rx = change::subscribe();
+
+    tokio::spawn(async move {
+        loop {
+            let change = match rx.recv().await {
+                Ok(change) => change,
+                Err(RecvError::Overflowed(_)) => continue,
+                Err(RecvError::Closed) => return,
+            };
+
+            let ConfigChange::LanListenIp(listen_ip) = change else {
+                continue;
+            };
+
+            let Some(listener) = listener.upgrade() else {
+                return;
+            };
+
+            let mut listener = listener.lock().await;
+
+            // the port must be released before it's bound on another address
+            if let Some(previous) = listener.take() {
+                previous.close().await;
+            }
+
+            match Listener::new(listen_ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))).await {
+                Ok(new_listener) => *listener = Some(new_listener),
+                Err(err) => tracing::error!(?err, ?listen_ip, "bind lan listener failed"),
+            }
+        }
+    });
+}
+
+// discoverability is switched live when it's changed in settings, the process exits once
+// the provider and its discovers are dropped
+fn serve_discoverable_changes(discoverable: Weak<AtomicBool>) {
+    let mut rx = change::subscribe();
+
+    tokio::spawn(async move {
+        loop {
+            let change = match rx.recv().await {
+                Ok(change) => change,
+                Err(RecvError::Overflowed(_)) => continue,
+                Err(RecvError::Closed) => return,
+            };
+
+            let ConfigChange::LanDiscoverable(value) = change else {
+                continue;
+            };
+
+            let Some(discoverable) = discoverable.upgrade() else {
+                return;
+            };
+
+            discoverable.store(value, Ordering::SeqCst);
+        }
+    });
 }
 
 fn serve_discover_nodes(
@@ -140,6 +412,9 @@ async fn update_nodes(
 
             let mut nodes = nodes_cache.write().await;
             if let Some(node) = (*nodes).get_mut(&live_packet.hostname) {
+                node.availability = live_packet.availability;
+                node.device_ids = live_packet.device_ids;
+
                 if let Some(ts) = node.addrs.get_mut(&addr.ip()) {
                     (*ts) = chrono::Utc::now().timestamp();
                 } else {
@@ -161,6 +436,8 @@ async fn update_nodes(
                         addrs,
                         os: live_packet.os,
                         os_version: live_packet.os_version,
+                        availability: live_packet.availability,
+                        device_ids: live_packet.device_ids,
                     },
                 );
             }
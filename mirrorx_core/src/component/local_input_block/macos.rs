@@ -0,0 +1,157 @@
+use crate::{component::input::INJECTED_EVENT_USER_DATA, core_error, error::CoreResult};
+use core_foundation::runloop::{kCFRunLoopCommonModes, kCFRunLoopDefaultMode, CFRunLoop};
+use core_graphics::event::{
+    CGEventFlags, CGEventTap, CGEventTapLocation, CGEventTapOptions, CGEventTapPlacement,
+    CGEventType, EventField,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+    time::Duration,
+};
+
+const KVK_ESCAPE: i64 = 0x35;
+
+// the run loop may be stopped before it runs, the flag is checked at least this often
+const STOP_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// the event types of mouse and keyboard which the tap drops
+const BLOCKED_EVENTS: [CGEventType; 14] = [
+    CGEventType::LeftMouseDown,
+    CGEventType::LeftMouseUp,
+    CGEventType::RightMouseDown,
+    CGEventType::RightMouseUp,
+    CGEventType::MouseMoved,
+    CGEventType::LeftMouseDragged,
+    CGEventType::RightMouseDragged,
+    CGEventType::KeyDown,
+    CGEventType::KeyUp,
+    CGEventType::FlagsChanged,
+    CGEventType::ScrollWheel,
+    CGEventType::OtherMouseDown,
+    CGEventType::OtherMouseUp,
+    CGEventType::OtherMouseDragged,
+];
+
+// swallowed modifiers never reach the flags of later events, the tap tracks them itself
+// to tell the escape hotkey
+static CONTROL_PRESSED: AtomicBool = AtomicBool::new(false);
+static OPTION_PRESSED: AtomicBool = AtomicBool::new(false);
+
+struct RunLoop(CFRunLoop);
+
+// stopping a run loop from other threads is safe
+unsafe impl Send for RunLoop {}
+
+/// Event tap which drops the physical input, the input which sessions post is marked with
+/// `INJECTED_EVENT_USER_DATA` and passes.
+pub struct Block {
+    run_loop: RunLoop,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Drop for Block {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.run_loop.0.stop();
+    }
+}
+
+pub fn start(on_escape: fn()) -> CoreResult<Block> {
+    let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+    let stopped = Arc::new(AtomicBool::new(false));
+    let thread_stopped = stopped.clone();
+
+    std::thread::Builder::new()
+        .name(String::from("local_input_block"))
+        .spawn(move || serve_event_tap(on_escape, thread_stopped, ready_tx))?;
+
+    let run_loop = ready_rx
+        .recv()
+        .map_err(|_| core_error!("local input block thread exited"))??;
+
+    Ok(Block { run_loop, stopped })
+}
+
+fn serve_event_tap(
+    on_escape: fn(),
+    stopped: Arc<AtomicBool>,
+    ready_tx: Sender<CoreResult<RunLoop>>,
+) {
+    CONTROL_PRESSED.store(false, Ordering::SeqCst);
+    OPTION_PRESSED.store(false, Ordering::SeqCst);
+
+    let tap = CGEventTap::new(
+        CGEventTapLocation::HID,
+        CGEventTapPlacement::HeadInsertEventTap,
+        CGEventTapOptions::Default,
+        BLOCKED_EVENTS.to_vec(),
+        move |_, event_type, event| {
+            if event.get_integer_value_field(EventField::EVENT_SOURCE_USER_DATA)
+                == INJECTED_EVENT_USER_DATA
+            {
+                return None;
+            }
+
+            match event_type {
+                CGEventType::FlagsChanged => {
+                    let flags = event.get_flags();
+                    CONTROL_PRESSED.store(
+                        flags.contains(CGEventFlags::CGEventFlagControl),
+                        Ordering::SeqCst,
+                    );
+                    OPTION_PRESSED.store(
+                        flags.contains(CGEventFlags::CGEventFlagAlternate),
+                        Ordering::SeqCst,
+                    );
+                }
+                CGEventType::KeyDown => {
+                    if event.get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE)
+                        == KVK_ESCAPE
+                        && CONTROL_PRESSED.load(Ordering::SeqCst)
+                        && OPTION_PRESSED.load(Ordering::SeqCst)
+                    {
+                        on_escape();
+                    }
+                }
+                _ => {}
+            }
+
+            // events turned into null events are dropped
+            event.set_type(CGEventType::Null);
+            None
+        },
+    );
+
+    let tap = match tap {
+        Ok(tap) => tap,
+        Err(_) => {
+            // event taps which modify events require accessibility permission
+            let _ = ready_tx.send(Err(core_error!("create event tap failed")));
+            return;
+        }
+    };
+
+    let source = match tap.mach_port.create_runloop_source(0) {
+        Ok(source) => source,
+        Err(_) => {
+            let _ = ready_tx.send(Err(core_error!("create event tap run loop source failed")));
+            return;
+        }
+    };
+
+    let run_loop = CFRunLoop::get_current();
+    unsafe { run_loop.add_source(&source, kCFRunLoopCommonModes) };
+    tap.enable();
+
+    let _ = ready_tx.send(Ok(RunLoop(run_loop)));
+
+    while !stopped.load(Ordering::SeqCst) {
+        CFRunLoop::run_in_mode(unsafe { kCFRunLoopDefaultMode }, STOP_CHECK_INTERVAL, false);
+    }
+
+    tracing::info!("local input block thread exit");
+}
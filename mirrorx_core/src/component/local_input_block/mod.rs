@@ -0,0 +1,159 @@
+//! Who controls the desktop can ask to block the local mouse and keyboard of host, so
+//! host user doesn't get in the way, like during maintenance. Host user confirms it first
+//! and releases it any time with the escape hotkey, Ctrl+Alt+Esc (Control+Option+Escape
+//! on macOS), it's released as well once the session which blocked it ends. Only the
+//! physical input is dropped, the input which sessions inject still reaches the desktop.
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "windows")]
+use self::windows as sys;
+
+#[cfg(target_os = "macos")]
+mod macos;
+
+#[cfg(target_os = "macos")]
+use self::macos as sys;
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+use self::unsupported as sys;
+
+use crate::{
+    api::{
+        endpoint::id::EndPointID,
+        notification::{self, Notification},
+    },
+    core_error,
+    error::CoreResult,
+};
+use once_cell::sync::Lazy;
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+use tokio::sync::oneshot;
+
+// the requester's call expires in 60 seconds, host user should decide before that
+const CONFIRM_PROMPT_TIMEOUT: Duration = Duration::from_secs(50);
+
+static INPUT_BLOCK: Lazy<Mutex<Option<InputBlock>>> = Lazy::new(|| Mutex::new(None));
+
+// block requests which are waiting for host user's confirmation, keyed by request id
+static PENDING_CONFIRMATIONS: Lazy<Mutex<HashMap<String, oneshot::Sender<bool>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct InputBlock {
+    session_id: String,
+    // input is unblocked once it's dropped
+    _block: sys::Block,
+    // fired when host user releases it with the escape hotkey
+    escaped_tx: oneshot::Sender<()>,
+}
+
+/// Block the local input for the session, the returned receiver fires when host user
+/// releases it with the escape hotkey.
+pub(crate) fn block_local_input(session_id: &str) -> CoreResult<oneshot::Receiver<()>> {
+    let mut input_block = INPUT_BLOCK.lock().unwrap();
+
+    if let Some(ref input_block) = *input_block {
+        if input_block.session_id != session_id {
+            return Err(core_error!("local input is blocked by another session"));
+        }
+    }
+
+    let block = sys::start(escape_pressed)?;
+    let (escaped_tx, escaped_rx) = oneshot::channel();
+
+    *input_block = Some(InputBlock {
+        session_id: session_id.to_string(),
+        _block: block,
+        escaped_tx,
+    });
+
+    tracing::info!(?session_id, "block local input");
+
+    Ok(escaped_rx)
+}
+
+/// Unblock the local input if the session blocked it.
+pub(crate) fn release_local_input(session_id: &str) {
+    let mut input_block = INPUT_BLOCK.lock().unwrap();
+
+    if input_block
+        .as_ref()
+        .map_or(false, |input_block| input_block.session_id == session_id)
+    {
+        input_block.take();
+        tracing::info!(?session_id, "release local input");
+    }
+}
+
+pub(crate) fn local_input_blocked_by(session_id: &str) -> bool {
+    INPUT_BLOCK
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or(false, |input_block| input_block.session_id == session_id)
+}
+
+/// Unblock the local input which the session blocked.
+pub fn session_ended(session_id: &str) {
+    release_local_input(session_id);
+}
+
+// the platform block calls it once host user presses the escape hotkey
+fn escape_pressed() {
+    if let Some(input_block) = INPUT_BLOCK.lock().unwrap().take() {
+        tracing::info!(session_id = ?input_block.session_id, "local input released by hotkey");
+        let _ = input_block.escaped_tx.send(());
+    }
+}
+
+/// Reply the block request which host user was prompted with.
+pub fn reply_local_input_block(request_id: &str, allowed: bool) -> CoreResult<()> {
+    let tx = PENDING_CONFIRMATIONS
+        .lock()
+        .unwrap()
+        .remove(request_id)
+        .ok_or(core_error!("block request not exists or expired"))?;
+
+    tx.send(allowed)
+        .map_err(|_| core_error!("block request not exists or expired"))
+}
+
+/// Prompt host user to confirm blocking the local input and wait for the decision, it's
+/// rejected when user doesn't decide in time.
+pub(crate) async fn prompt_local_input_block(endpoint_id: EndPointID) -> bool {
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let (tx, rx) = oneshot::channel();
+
+    PENDING_CONFIRMATIONS
+        .lock()
+        .unwrap()
+        .insert(request_id.clone(), tx);
+
+    notification::publish(Notification::LocalInputBlockRequest {
+        request_id: request_id.clone(),
+        endpoint_id,
+    });
+
+    match tokio::time::timeout(CONFIRM_PROMPT_TIMEOUT, rx).await {
+        Ok(Ok(allowed)) => allowed,
+        _ => {
+            tracing::info!(?endpoint_id, "block request is not confirmed in time");
+            PENDING_CONFIRMATIONS.lock().unwrap().remove(&request_id);
+            false
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod unsupported {
+    use crate::{core_error, error::CoreResult};
+
+    pub struct Block;
+
+    pub fn start(_: fn()) -> CoreResult<Block> {
+        Err(core_error!(
+            "blocking local input is not supported on current platform"
+        ))
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{core_error, error::CoreResult};
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+    },
+};
+use windows::Win32::{
+    Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM},
+    System::Threading::GetCurrentThreadId,
+    UI::{
+        Input::KeyboardAndMouse::{
+            VK_CONTROL, VK_ESCAPE, VK_LCONTROL, VK_LMENU, VK_MENU, VK_RCONTROL, VK_RMENU,
+        },
+        WindowsAndMessaging::{
+            CallNextHookEx, GetMessageW, PostThreadMessageW, SetWindowsHookExW,
+            UnhookWindowsHookEx, HC_ACTION, HHOOK, KBDLLHOOKSTRUCT, LLKHF_INJECTED, LLKHF_UP,
+            LLMHF_INJECTED, MSG, MSLLHOOKSTRUCT, WH_KEYBOARD_LL, WH_MOUSE_LL, WM_QUIT,
+        },
+    },
+};
+
+// swallowed modifiers never reach the key state of system, the hook tracks them itself
+// to tell the escape hotkey
+static CONTROL_PRESSED: AtomicBool = AtomicBool::new(false);
+static ALT_PRESSED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static ON_ESCAPE: Cell<Option<fn()>> = Cell::new(None);
+}
+
+/// Low level hooks which drop the physical input. `BlockInput` isn't used as it drops the
+/// escape hotkey as well and it requires elevation.
+pub struct Block {
+    thread_id: u32,
+}
+
+impl Drop for Block {
+    fn drop(&mut self) {
+        unsafe {
+            if !PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0)).as_bool() {
+                tracing::error!("post quit message to local input block thread failed");
+            }
+        }
+    }
+}
+
+pub fn start(on_escape: fn()) -> CoreResult<Block> {
+    let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+
+    std::thread::Builder::new()
+        .name(String::from("local_input_block"))
+        .spawn(move || serve_hooks(on_escape, ready_tx))?;
+
+    let thread_id = ready_rx
+        .recv()
+        .map_err(|_| core_error!("local input block thread exited"))??;
+
+    Ok(Block { thread_id })
+}
+
+fn serve_hooks(on_escape: fn(), ready_tx: Sender<CoreResult<u32>>) {
+    ON_ESCAPE.with(|cell| cell.set(Some(on_escape)));
+    CONTROL_PRESSED.store(false, Ordering::SeqCst);
+    ALT_PRESSED.store(false, Ordering::SeqCst);
+
+    unsafe {
+        let keyboard_hook = match SetWindowsHookExW(
+            WH_KEYBOARD_LL,
+            Some(keyboard_hook_proc),
+            HINSTANCE::default(),
+            0,
+        ) {
+            Ok(hook) => hook,
+            Err(err) => {
+                let _ = ready_tx.send(Err(core_error!("install keyboard hook failed ({:?})", err)));
+                return;
+            }
+        };
+
+        let mouse_hook =
+            match SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_hook_proc), HINSTANCE::default(), 0) {
+                Ok(hook) => hook,
+                Err(err) => {
+                    UnhookWindowsHookEx(keyboard_hook);
+                    let _ =
+                        ready_tx.send(Err(core_error!("install mouse hook failed ({:?})", err)));
+                    return;
+                }
+            };
+
+        let _ = ready_tx.send(Ok(GetCurrentThreadId()));
+
+        // hooks are called while the thread waits for messages, it exits with WM_QUIT
+        let mut msg = MSG::default();
+        while GetMessageW(&mut msg, HWND::default(), 0, 0).0 > 0 {}
+
+        UnhookWindowsHookEx(mouse_hook);
+        UnhookWindowsHookEx(keyboard_hook);
+    }
+
+    tracing::info!("local input block thread exit");
+}
+
+unsafe extern "system" fn keyboard_hook_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code != HC_ACTION as i32 {
+        return CallNextHookEx(HHOOK::default(), code, wparam, lparam);
+    }
+
+    let info = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+
+    // the input of sessions is injected
+    if info.flags.0 & LLKHF_INJECTED.0 != 0 {
+        return CallNextHookEx(HHOOK::default(), code, wparam, lparam);
+    }
+
+    let pressed = info.flags.0 & LLKHF_UP.0 == 0;
+    let vk_code = info.vkCode as u16;
+
+    if vk_code == VK_CONTROL.0 || vk_code == VK_LCONTROL.0 || vk_code == VK_RCONTROL.0 {
+        CONTROL_PRESSED.store(pressed, Ordering::SeqCst);
+    } else if vk_code == VK_MENU.0 || vk_code == VK_LMENU.0 || vk_code == VK_RMENU.0 {
+        ALT_PRESSED.store(pressed, Ordering::SeqCst);
+    } else if vk_code == VK_ESCAPE.0
+        && pressed
+        && CONTROL_PRESSED.load(Ordering::SeqCst)
+        && ALT_PRESSED.load(Ordering::SeqCst)
+    {
+        if let Some(on_escape) = ON_ESCAPE.with(|cell| cell.get()) {
+            on_escape();
+        }
+    }
+
+    LRESULT(1)
+}
+
+unsafe extern "system" fn mouse_hook_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION as i32 {
+        let info = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+        if info.flags & LLMHF_INJECTED == 0 {
+            return LRESULT(1);
+        }
+    }
+
+    CallNextHookEx(HHOOK::default(), code, wparam, lparam)
+}
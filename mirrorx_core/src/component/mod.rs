@@ -1,10 +1,37 @@
 #![allow(non_snake_case)]
 
 pub mod audio;
+pub mod autostart;
+pub mod availability;
+pub mod clock_sync;
+pub mod codec_probe;
+pub mod crash;
 pub mod desktop;
+#[cfg(feature = "host")]
+pub mod display_control;
+pub mod ffmpeg_safe;
 pub mod frame;
 pub mod fs;
+pub mod governor;
 pub mod input;
 pub mod lan;
+#[cfg(feature = "host")]
+pub mod local_input_block;
+pub mod power;
+#[cfg(feature = "host")]
+pub mod process;
+pub mod progress;
+pub mod quality_alert;
+pub mod session_profile;
+pub mod session_stats;
+#[cfg(feature = "host")]
+pub mod system_volume;
+pub mod telemetry;
+pub mod tile_cache;
+#[cfg(feature = "host")]
+pub mod unattended_login;
+pub mod updater;
 pub mod video_decoder;
+#[cfg(feature = "host")]
 pub mod video_encoder;
+pub mod video_recorder;
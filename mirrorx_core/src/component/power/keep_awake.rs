@@ -0,0 +1,106 @@
+//! Hosts which fall asleep drop their sessions, and the ones which turn off their display
+//! stream it black. System and display are kept awake while passive sessions are
+//! connected, it's released once the last of them ends or it's turned off. The display
+//! which is off already is woken by the wake display action of viewers.
+
+#[cfg(target_os = "windows")]
+use super::windows as sys;
+
+#[cfg(target_os = "macos")]
+use super::macos as sys;
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+use self::unsupported as sys;
+
+use crate::error::CoreResult;
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+static KEEP_AWAKE_ENABLED: AtomicBool = AtomicBool::new(true);
+
+static KEEP_AWAKE: Lazy<Mutex<KeepAwake>> = Lazy::new(|| Mutex::new(KeepAwake::default()));
+
+#[derive(Default)]
+struct KeepAwake {
+    // passive sessions which are connected
+    sessions: HashSet<String>,
+    assertion: Option<sys::AwakeAssertion>,
+}
+
+impl KeepAwake {
+    // hold the assertion when it's enabled and any session is connected
+    fn update(&mut self) {
+        let wanted = KEEP_AWAKE_ENABLED.load(Ordering::SeqCst) && !self.sessions.is_empty();
+        if wanted == self.assertion.is_some() {
+            return;
+        }
+
+        if !wanted {
+            self.assertion = None;
+            tracing::info!("release keep awake");
+            return;
+        }
+
+        match sys::acquire_awake_assertion() {
+            Ok(assertion) => {
+                self.assertion = Some(assertion);
+                tracing::info!("keep system and display awake");
+            }
+            Err(err) => tracing::error!(?err, "keep system and display awake failed"),
+        }
+    }
+}
+
+/// Set whether system and display are kept awake during passive sessions, it's enabled by
+/// default and applies to the connected sessions right away.
+pub fn set_keep_awake_enabled(enabled: bool) {
+    KEEP_AWAKE_ENABLED.store(enabled, Ordering::SeqCst);
+    KEEP_AWAKE.lock().unwrap().update();
+}
+
+pub fn keep_awake_enabled() -> bool {
+    KEEP_AWAKE_ENABLED.load(Ordering::SeqCst)
+}
+
+pub(crate) fn session_started(session_id: &str) {
+    let mut keep_awake = KEEP_AWAKE.lock().unwrap();
+    keep_awake.sessions.insert(session_id.to_string());
+    keep_awake.update();
+}
+
+pub fn session_ended(session_id: &str) {
+    let mut keep_awake = KEEP_AWAKE.lock().unwrap();
+    if keep_awake.sessions.remove(session_id) {
+        keep_awake.update();
+    }
+}
+
+/// Turn on the display which is turned off by idle timeout.
+pub fn wake_display() -> CoreResult<()> {
+    sys::wake_display()
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod unsupported {
+    use crate::{core_error, error::CoreResult};
+
+    pub type AwakeAssertion = ();
+
+    pub fn acquire_awake_assertion() -> CoreResult<AwakeAssertion> {
+        Err(core_error!(
+            "keep awake is not supported on current platform"
+        ))
+    }
+
+    pub fn wake_display() -> CoreResult<()> {
+        Err(core_error!(
+            "waking display is not supported on current platform"
+        ))
+    }
+}
@@ -0,0 +1,107 @@
+use super::PowerState;
+use crate::{core_error, error::CoreResult};
+use core_foundation::{
+    base::{CFRelease, TCFType},
+    string::CFString,
+};
+use mirrorx_native::os::macos::io_kit::*;
+use objc::{
+    class, msg_send,
+    runtime::{Object, BOOL, YES},
+    sel, sel_impl,
+};
+use scopeguard::defer;
+
+pub fn get_power_state() -> CoreResult<PowerState> {
+    unsafe {
+        let snapshot = IOPSCopyPowerSourcesInfo();
+        if snapshot.is_null() {
+            return Err(core_error!("IOPSCopyPowerSourcesInfo returns null"));
+        }
+
+        defer! {
+            CFRelease(snapshot);
+        }
+
+        let source_type = IOPSGetProvidingPowerSourceType(snapshot);
+        let on_battery = !source_type.is_null()
+            && CFString::wrap_under_get_rule(source_type)
+                == CFString::wrap_under_get_rule(kIOPMBatteryPowerKey);
+
+        // low power mode is only available since macOS 12
+        let process_info: *mut Object = msg_send![class!(NSProcessInfo), processInfo];
+        let low_power_mode_available: BOOL =
+            msg_send![process_info, respondsToSelector: sel!(isLowPowerModeEnabled)];
+
+        let power_saver = if low_power_mode_available == YES {
+            let enabled: BOOL = msg_send![process_info, isLowPowerModeEnabled];
+            enabled == YES
+        } else {
+            false
+        };
+
+        Ok(PowerState {
+            on_battery,
+            power_saver,
+        })
+    }
+}
+
+/// Keeps display and thereby system awake until it's dropped.
+pub struct AwakeAssertion {
+    id: IOPMAssertionID,
+}
+
+pub fn acquire_awake_assertion() -> CoreResult<AwakeAssertion> {
+    let assertion_type = CFString::from_static_string("PreventUserIdleDisplaySleep");
+    let name = CFString::from_static_string("MirrorX remote session");
+    let mut id: IOPMAssertionID = 0;
+
+    let ret = unsafe {
+        IOPMAssertionCreateWithName(
+            assertion_type.as_concrete_TypeRef(),
+            kIOPMAssertionLevelOn,
+            name.as_concrete_TypeRef(),
+            &mut id,
+        )
+    };
+
+    if ret != 0 {
+        return Err(core_error!(
+            "IOPMAssertionCreateWithName returns error ({})",
+            ret
+        ));
+    }
+
+    Ok(AwakeAssertion { id })
+}
+
+impl Drop for AwakeAssertion {
+    fn drop(&mut self) {
+        let ret = unsafe { IOPMAssertionRelease(self.id) };
+        if ret != 0 {
+            tracing::error!(ret, "IOPMAssertionRelease returns error");
+        }
+    }
+}
+
+pub fn wake_display() -> CoreResult<()> {
+    let name = CFString::from_static_string("MirrorX remote session");
+    let mut id: IOPMAssertionID = 0;
+
+    // declared activity turns on the display like user's input does
+    let ret = unsafe {
+        IOPMAssertionDeclareUserActivity(name.as_concrete_TypeRef(), kIOPMUserActiveLocal, &mut id)
+    };
+
+    if ret != 0 {
+        return Err(core_error!(
+            "IOPMAssertionDeclareUserActivity returns error ({})",
+            ret
+        ));
+    }
+
+    unsafe { IOPMAssertionRelease(id) };
+
+    Ok(())
+}
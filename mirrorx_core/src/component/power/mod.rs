@@ -0,0 +1,46 @@
+pub mod keep_awake;
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "windows")]
+pub use self::windows::get_power_state;
+
+#[cfg(target_os = "macos")]
+mod macos;
+
+#[cfg(target_os = "macos")]
+pub use macos::get_power_state;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static POWER_SAVING_ENABLED: AtomicBool = AtomicBool::new(true);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PowerState {
+    pub on_battery: bool,
+    pub power_saver: bool,
+}
+
+impl PowerState {
+    /// Returns the max frame rate which capture should keep in this power state,
+    /// None means unlimited.
+    pub fn max_frame_rate(&self) -> Option<u8> {
+        if self.power_saver {
+            Some(15)
+        } else if self.on_battery {
+            Some(30)
+        } else {
+            None
+        }
+    }
+}
+
+/// Set whether capture adapts to the power state, it's enabled by default.
+pub fn set_power_saving_enabled(enabled: bool) {
+    POWER_SAVING_ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn power_saving_enabled() -> bool {
+    POWER_SAVING_ENABLED.load(Ordering::SeqCst)
+}
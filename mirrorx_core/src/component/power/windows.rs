@@ -0,0 +1,95 @@
+use super::PowerState;
+use crate::{core_error, error::CoreResult};
+use std::sync::mpsc::Sender;
+use windows::Win32::{
+    Foundation::GetLastError,
+    System::Power::{
+        GetSystemPowerStatus, SetThreadExecutionState, ES_CONTINUOUS, ES_DISPLAY_REQUIRED,
+        ES_SYSTEM_REQUIRED, SYSTEM_POWER_STATUS,
+    },
+    UI::Input::KeyboardAndMouse::{
+        SendInput, INPUT, INPUT_0, INPUT_MOUSE, MOUSEEVENTF_MOVE, MOUSEINPUT,
+    },
+};
+
+// ACLineStatus
+const AC_LINE_OFFLINE: u8 = 0;
+
+// SystemStatusFlag
+const BATTERY_SAVER_ON: u8 = 1;
+
+pub fn get_power_state() -> CoreResult<PowerState> {
+    unsafe {
+        let mut status = SYSTEM_POWER_STATUS::default();
+        if !GetSystemPowerStatus(&mut status).as_bool() {
+            return Err(core_error!("GetSystemPowerStatus failed"));
+        }
+
+        Ok(PowerState {
+            on_battery: status.ACLineStatus == AC_LINE_OFFLINE,
+            power_saver: status.SystemStatusFlag == BATTERY_SAVER_ON,
+        })
+    }
+}
+
+/// Keeps system and display awake until it's dropped. Execution state belongs to the
+/// thread which sets it, so a dedicated thread holds it.
+pub struct AwakeAssertion {
+    // the thread resets execution state once it's dropped
+    _release_tx: Sender<()>,
+}
+
+pub fn acquire_awake_assertion() -> CoreResult<AwakeAssertion> {
+    let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+
+    std::thread::Builder::new()
+        .name(String::from("keep_awake"))
+        .spawn(move || unsafe {
+            let previous =
+                SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED | ES_DISPLAY_REQUIRED);
+
+            let _ = ready_tx.send(previous.0 != 0);
+            if previous.0 == 0 {
+                return;
+            }
+
+            let _ = release_rx.recv();
+            SetThreadExecutionState(ES_CONTINUOUS);
+        })?;
+
+    if !ready_rx.recv().unwrap_or(false) {
+        return Err(core_error!("SetThreadExecutionState failed"));
+    }
+
+    Ok(AwakeAssertion {
+        _release_tx: release_tx,
+    })
+}
+
+pub fn wake_display() -> CoreResult<()> {
+    unsafe {
+        // resets the idle timer of display, but it doesn't turn on the display which is off
+        SetThreadExecutionState(ES_DISPLAY_REQUIRED);
+
+        // a move of no distance turns it on like user's input does
+        let inputs = [INPUT {
+            r#type: INPUT_MOUSE,
+            Anonymous: INPUT_0 {
+                mi: MOUSEINPUT {
+                    dwFlags: MOUSEEVENTF_MOVE,
+                    ..Default::default()
+                },
+            },
+        }];
+
+        if SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) as usize == inputs.len() {
+            Ok(())
+        } else {
+            Err(core_error!(
+                "SendInput failed ({:?})",
+                GetLastError().to_hresult()
+            ))
+        }
+    }
+}
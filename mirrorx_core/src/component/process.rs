@@ -0,0 +1,65 @@
+//! Running processes of passive endpoint are listed for viewers with admin tools, so an
+//! application which freezes the remote desktop can be found and terminated without
+//! reaching the task manager through the frozen UI.
+
+use crate::{core_error, error::CoreResult};
+use sysinfo::{Pid, PidExt, ProcessExt, ProcessRefreshKind, System, SystemExt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    /// Usage of one core in percent, busy processes exceed 100 on multi-core machines.
+    pub cpu_usage: f32,
+    /// Resident memory in bytes.
+    pub memory: u64,
+}
+
+/// List running processes, the busiest come first. It blocks for the minimum interval
+/// which CPU usage is measured in.
+pub fn list_processes() -> Vec<ProcessInfo> {
+    let refresh_kind = ProcessRefreshKind::new().with_cpu();
+    let mut system = System::new();
+
+    // CPU usage is the difference of two refreshes
+    system.refresh_processes_specifics(refresh_kind);
+    std::thread::sleep(System::MINIMUM_CPU_UPDATE_INTERVAL);
+    system.refresh_processes_specifics(refresh_kind);
+
+    let mut processes: Vec<ProcessInfo> = system
+        .processes()
+        .iter()
+        .map(|(pid, process)| ProcessInfo {
+            pid: pid.as_u32(),
+            name: process.name().to_string(),
+            cpu_usage: process.cpu_usage(),
+            memory: process.memory(),
+        })
+        .collect();
+
+    processes.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage));
+    processes
+}
+
+/// Terminate the process forcibly, the process serving the session can't be killed.
+pub fn kill_process(pid: u32) -> CoreResult<()> {
+    if pid == std::process::id() {
+        return Err(core_error!("can't kill the process serving the session"));
+    }
+
+    let mut system = System::new();
+    let pid = Pid::from_u32(pid);
+    if !system.refresh_process_specifics(pid, ProcessRefreshKind::new()) {
+        return Err(core_error!("process not exists"));
+    }
+
+    let Some(process) = system.process(pid) else {
+        return Err(core_error!("process not exists"));
+    };
+
+    if !process.kill() {
+        return Err(core_error!("kill process failed"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,161 @@
+//! Long operations push their progress to the frontend rather than being polled. Each of
+//! them owns a tracker which publishes the progress at most every `REPORT_INTERVAL`, with
+//! the throughput and ETA computed here so every view shows the same numbers.
+
+use crate::api::event::{self, CoreEvent};
+use serde::Serialize;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+// weight of the latest sample in the smoothed throughput
+const THROUGHPUT_SMOOTHING: f64 = 0.3;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressKind {
+    /// Sending a file to remote, counted in bytes.
+    Send,
+    /// Receiving a file from remote, counted in bytes.
+    Receive,
+    /// Reading a directory, counted in entries.
+    DirectoryListing,
+    /// Recording the video of session, counted in bytes and never has a total.
+    Recording,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressState {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Progress {
+    pub operation_id: String,
+    pub kind: ProgressKind,
+    pub state: ProgressState,
+    pub done: u64,
+    pub total: Option<u64>,
+    /// Units of `kind` per second.
+    pub throughput: u64,
+    pub eta_secs: Option<u64>,
+}
+
+pub struct ProgressTracker {
+    operation_id: String,
+    kind: ProgressKind,
+    total: Option<u64>,
+    // trackers are advanced from blocking tasks and parallel iterators as well
+    state: Mutex<TrackerState>,
+}
+
+struct TrackerState {
+    done: u64,
+    sampled_done: u64,
+    sampled_at: Instant,
+    throughput: Option<f64>,
+    finished: bool,
+}
+
+impl ProgressTracker {
+    /// Track the operation which has `done` of `total` units finished already, like a
+    /// resumed transfer.
+    pub fn new(
+        operation_id: impl Into<String>,
+        kind: ProgressKind,
+        total: Option<u64>,
+        done: u64,
+    ) -> Self {
+        Self {
+            operation_id: operation_id.into(),
+            kind,
+            total,
+            state: Mutex::new(TrackerState {
+                done,
+                sampled_done: done,
+                sampled_at: Instant::now(),
+                throughput: None,
+                finished: false,
+            }),
+        }
+    }
+
+    pub fn done(&self) -> u64 {
+        self.state.lock().unwrap().done
+    }
+
+    pub fn advance(&self, delta: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.done += delta;
+
+        let elapsed = state.sampled_at.elapsed();
+        if elapsed < REPORT_INTERVAL {
+            return;
+        }
+
+        let sample = (state.done - state.sampled_done) as f64 / elapsed.as_secs_f64();
+        state.throughput = Some(match state.throughput {
+            Some(throughput) => {
+                throughput * (1.0 - THROUGHPUT_SMOOTHING) + sample * THROUGHPUT_SMOOTHING
+            }
+            None => sample,
+        });
+        state.sampled_done = state.done;
+        state.sampled_at = Instant::now();
+
+        self.publish(&state, ProgressState::Running);
+    }
+
+    /// Publish the final progress, it's published only once however many times it's
+    /// called.
+    pub fn finish(&self, succeed: bool) {
+        let mut state = self.state.lock().unwrap();
+        if state.finished {
+            return;
+        }
+
+        state.finished = true;
+
+        let progress_state = if succeed {
+            ProgressState::Succeeded
+        } else {
+            ProgressState::Failed
+        };
+
+        self.publish(&state, progress_state);
+    }
+
+    fn publish(&self, state: &TrackerState, progress_state: ProgressState) {
+        let throughput = state.throughput.unwrap_or_default();
+
+        let eta_secs = match (self.total, progress_state) {
+            (Some(total), ProgressState::Running) if throughput > 0.0 => {
+                Some((total.saturating_sub(state.done) as f64 / throughput).ceil() as u64)
+            }
+            _ => None,
+        };
+
+        event::publish(CoreEvent::Progress(Progress {
+            operation_id: self.operation_id.clone(),
+            kind: self.kind,
+            state: progress_state,
+            done: state.done,
+            total: self.total,
+            throughput: throughput as u64,
+            eta_secs,
+        }));
+    }
+}
+
+impl Drop for ProgressTracker {
+    // the operation which stopped without finishing is failed
+    fn drop(&mut self) {
+        self.finish(false);
+    }
+}
@@ -0,0 +1,187 @@
+//! Alerts of degraded session quality, taken from the session stats which viewer samples
+//! every few seconds. A condition has to hold for several samples in a row to be raised,
+//! so a short hiccup doesn't, and it's raised once until it clears. Every alert carries
+//! the remedy which most likely helps, UI suggests it and the ones which only affect the
+//! stream are applied right away when auto-tune is on.
+
+use super::session_stats::SessionStats;
+use crate::api::endpoint::message::{StreamQuality, VideoCodec};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+// samples in a row which a condition holds for before it's raised
+const SUSTAINED_SAMPLES: u32 = 3;
+
+// audio frames concealed by the jitter buffer, they're what lost or late packets leave
+const MAX_LOSS_PERCENT: f32 = 5.0;
+
+// a third of the frame rate which viewer asks for
+const MIN_FRAME_RATE: f32 = 20.0;
+
+// frames of a still desktop are tiny, a low frame rate with them is the idle capture of
+// remote rather than degradation
+const MIN_MOVING_FRAME_BYTES: u64 = 2048;
+
+static AUTO_TUNE: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityIssue {
+    PacketLoss,
+    DecodeErrors,
+    LowFrameRate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QualityRemedy {
+    ReduceStreamQuality {
+        quality: StreamQuality,
+    },
+    /// Resolution of remote monitor changes for its user as well, it's never applied
+    /// automatically.
+    LowerResolution,
+    /// Sessions negotiated later prefer H.264 to the tile codec.
+    PreferH264,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QualityAlert {
+    pub issue: QualityIssue,
+    /// Measured over the last sample, percent of lost audio frames, decode errors or
+    /// frames per second.
+    pub value: f32,
+    pub remedy: Option<QualityRemedy>,
+    /// Whether auto-tune applied the remedy.
+    pub applied: bool,
+}
+
+/// Apply the remedies which only affect the stream once an alert is raised.
+pub fn set_quality_auto_tune(enabled: bool) {
+    AUTO_TUNE.store(enabled, Ordering::SeqCst);
+}
+
+pub fn quality_auto_tune() -> bool {
+    AUTO_TUNE.load(Ordering::SeqCst)
+}
+
+/// The remedy of `issue` for the stream of `codec` at `quality`.
+pub fn remedy(
+    issue: QualityIssue,
+    codec: &VideoCodec,
+    quality: StreamQuality,
+) -> Option<QualityRemedy> {
+    match issue {
+        // less bandwidth is taken by reduced colors and frame rate
+        QualityIssue::PacketLoss => match quality {
+            StreamQuality::Full => Some(QualityRemedy::ReduceStreamQuality {
+                quality: StreamQuality::ReducedChroma,
+            }),
+            StreamQuality::ReducedChroma => Some(QualityRemedy::ReduceStreamQuality {
+                quality: StreamQuality::Grayscale,
+            }),
+            StreamQuality::Grayscale => Some(QualityRemedy::LowerResolution),
+        },
+        QualityIssue::DecodeErrors => {
+            (*codec == VideoCodec::TileCache).then_some(QualityRemedy::PreferH264)
+        }
+        // fewer pixels to capture, encode and decode
+        QualityIssue::LowFrameRate => Some(QualityRemedy::LowerResolution),
+    }
+}
+
+/// Raises the issues of a session from its stats.
+#[derive(Default)]
+pub struct QualityMonitor {
+    last_sample: Option<(Instant, SessionStats)>,
+    sustained: HashMap<QualityIssue, u32>,
+    raised: HashSet<QualityIssue>,
+}
+
+impl QualityMonitor {
+    /// Feed the stats sampled now, returns the issues which have just been sustained long
+    /// enough along with their measured values. `frame_rate_limited` is whether remote
+    /// lowers the frame rate on purpose, like for reduced stream quality or host priority.
+    pub fn sample(
+        &mut self,
+        stats: SessionStats,
+        frame_rate_limited: bool,
+    ) -> Vec<(QualityIssue, f32)> {
+        let now = Instant::now();
+        let Some((last_instant, last)) = self.last_sample.replace((now, stats.clone())) else {
+            return Vec::new();
+        };
+
+        let elapsed = now.duration_since(last_instant).as_secs_f32();
+        if elapsed <= 0.0 {
+            return Vec::new();
+        }
+
+        let frames = stats.video_frames.saturating_sub(last.video_frames);
+        let bytes = stats.video_bytes.saturating_sub(last.video_bytes);
+        let audio_frames = stats.audio_frames.saturating_sub(last.audio_frames);
+        let concealed = stats
+            .audio_concealed_frames
+            .saturating_sub(last.audio_concealed_frames);
+        let decode_errors = stats.decode_errors.saturating_sub(last.decode_errors);
+
+        let loss_percent = if audio_frames > 0 {
+            concealed as f32 * 100.0 / audio_frames as f32
+        } else {
+            0.0
+        };
+        let frame_rate = frames as f32 / elapsed;
+
+        let measures = [
+            (
+                QualityIssue::PacketLoss,
+                loss_percent,
+                loss_percent >= MAX_LOSS_PERCENT,
+            ),
+            (
+                QualityIssue::DecodeErrors,
+                decode_errors as f32,
+                decode_errors > 0,
+            ),
+            (
+                QualityIssue::LowFrameRate,
+                frame_rate,
+                !frame_rate_limited
+                    && frames > 0
+                    && frame_rate < MIN_FRAME_RATE
+                    && bytes / frames >= MIN_MOVING_FRAME_BYTES,
+            ),
+        ];
+
+        let mut issues = Vec::new();
+        for (issue, value, degraded) in measures {
+            if !degraded {
+                self.sustained.remove(&issue);
+                self.raised.remove(&issue);
+                continue;
+            }
+
+            let sustained = self.sustained.entry(issue).or_default();
+            *sustained += 1;
+
+            if *sustained >= SUSTAINED_SAMPLES && self.raised.insert(issue) {
+                issues.push((issue, value));
+            }
+        }
+
+        issues
+    }
+
+    /// Forget the issue so it's raised again if it holds on, like after its remedy is
+    /// applied.
+    pub fn reset(&mut self, issue: QualityIssue) {
+        self.sustained.remove(&issue);
+        self.raised.remove(&issue);
+    }
+}
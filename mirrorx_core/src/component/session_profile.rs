@@ -0,0 +1,50 @@
+//! Session profiles tune the pipeline of a session for what it's for. Viewer picks one and
+//! asks remote for it in negotiation:
+//!
+//! - interactive: the capture queue is short and the encoder skips the frames which
+//!   queued up behind it, the encoder tunes for zero latency and viewer presents the
+//!   latest decoded frame only
+//! - streaming: frames queue up instead of being dropped, the encoder may look ahead and
+//!   viewer buffers decoded frames to present them at a steady pace
+
+use crate::api::endpoint::message::SessionProfile;
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+use tokio::sync::mpsc::Receiver;
+
+static SESSION_PROFILE: Lazy<RwLock<SessionProfile>> =
+    Lazy::new(|| RwLock::new(SessionProfile::default()));
+
+/// Set the profile which the viewer asks remote for. Only affects sessions negotiated
+/// later.
+pub fn set_session_profile(profile: SessionProfile) {
+    *SESSION_PROFILE.write().unwrap() = profile;
+}
+
+pub fn session_profile() -> SessionProfile {
+    *SESSION_PROFILE.read().unwrap()
+}
+
+/// Captured frames which wait for the encoder.
+pub(crate) fn capture_queue_size(profile: SessionProfile) -> usize {
+    match profile {
+        SessionProfile::Interactive => 4,
+        SessionProfile::Streaming => 180,
+    }
+}
+
+/// The latest of `frame` and the frames queued after it for interactive sessions, the
+/// older ones are dropped. Streaming sessions take every frame in order.
+pub(crate) fn take_latest_frame<T>(
+    profile: SessionProfile,
+    rx: &mut Receiver<T>,
+    mut frame: T,
+) -> T {
+    if profile == SessionProfile::Interactive {
+        while let Ok(next_frame) = rx.try_recv() {
+            frame = next_frame;
+        }
+    }
+
+    frame
+}
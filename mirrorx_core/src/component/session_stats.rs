@@ -0,0 +1,214 @@
+use super::{audio::jitter_buffer::JitterStats, clock_sync};
+use crate::api::endpoint::id::EndPointID;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// counters of active endpoint sessions which are receiving or sending video, they're
+// removed once the video decode or encode process exits
+static SESSION_COUNTERS: Lazy<Mutex<HashMap<EndPointID, Counter>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct Counter {
+    started: Instant,
+    started_at: i64,
+    video_frames: u64,
+    video_bytes: u64,
+    encoder_stalls: u64,
+    backlog_drops: u64,
+    dropped_video_frames: u64,
+    decode_errors: u64,
+    // smoothed like the round trip time of TCP, so one late frame doesn't swing it
+    video_latency: Option<Duration>,
+    audio_latency: Option<Duration>,
+    audio_frames: u64,
+    audio_underruns: u64,
+    audio_overruns: u64,
+    audio_concealed_frames: u64,
+    audio_jitter_delay: Option<Duration>,
+}
+
+impl Counter {
+    fn new() -> Counter {
+        Counter {
+            started: Instant::now(),
+            started_at: chrono::Utc::now().timestamp(),
+            video_frames: 0,
+            video_bytes: 0,
+            encoder_stalls: 0,
+            backlog_drops: 0,
+            dropped_video_frames: 0,
+            decode_errors: 0,
+            video_latency: None,
+            audio_latency: None,
+            audio_frames: 0,
+            audio_underruns: 0,
+            audio_overruns: 0,
+            audio_concealed_frames: 0,
+            audio_jitter_delay: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionStats {
+    pub started_at: i64,
+    pub duration_secs: u64,
+    pub video_frames: u64,
+    pub video_bytes: u64,
+    pub average_frame_rate: f32,
+    pub average_bit_rate_kbps: u64,
+    pub encoder_stalls: u64,
+    pub backlog_drops: u64,
+    pub dropped_video_frames: u64,
+    /// Video frames which the decoder failed on.
+    pub decode_errors: u64,
+    /// One-way latencies from encoding on passive endpoint, None until its clock offset is
+    /// estimated.
+    pub video_latency_ms: Option<u64>,
+    pub audio_latency_ms: Option<u64>,
+    /// Audio frames played, the concealed ones included.
+    pub audio_frames: u64,
+    pub audio_underruns: u64,
+    pub audio_overruns: u64,
+    pub audio_concealed_frames: u64,
+    /// Delay the audio jitter buffer currently holds before playing.
+    pub audio_jitter_delay_ms: Option<u64>,
+    /// Clock of passive endpoint minus the local one.
+    pub clock_offset_ms: Option<i64>,
+}
+
+pub(crate) fn record_video_frame(endpoint_id: EndPointID, bytes: usize) {
+    let mut counters = SESSION_COUNTERS.lock().unwrap();
+    let counter = counters.entry(endpoint_id).or_insert_with(Counter::new);
+
+    counter.video_frames += 1;
+    counter.video_bytes += bytes as u64;
+}
+
+pub(crate) fn record_video_latency(endpoint_id: EndPointID, latency: Duration) {
+    let mut counters = SESSION_COUNTERS.lock().unwrap();
+    let counter = counters.entry(endpoint_id).or_insert_with(Counter::new);
+
+    counter.video_latency = Some(smooth_latency(counter.video_latency, latency));
+}
+
+/// Audio latency is kept only alongside the video of the session.
+pub(crate) fn record_audio_latency(endpoint_id: EndPointID, latency: Duration) {
+    if let Some(counter) = SESSION_COUNTERS.lock().unwrap().get_mut(&endpoint_id) {
+        counter.audio_latency = Some(smooth_latency(counter.audio_latency, latency));
+    }
+}
+
+/// Accumulates the jitter buffer stats taken since the last record, it's recorded for
+/// every audio frame pushed into the buffer.
+pub(crate) fn record_audio_jitter(
+    endpoint_id: EndPointID,
+    stats: JitterStats,
+    target_delay: Duration,
+) {
+    if let Some(counter) = SESSION_COUNTERS.lock().unwrap().get_mut(&endpoint_id) {
+        counter.audio_frames += 1;
+        counter.audio_underruns += stats.underruns;
+        counter.audio_overruns += stats.overruns;
+        counter.audio_concealed_frames += stats.concealed_frames;
+        counter.audio_jitter_delay = Some(target_delay);
+    }
+}
+
+fn smooth_latency(smoothed: Option<Duration>, latency: Duration) -> Duration {
+    match smoothed {
+        Some(smoothed) => (smoothed * 7 + latency) / 8,
+        None => latency,
+    }
+}
+
+/// Counts a video frame whose send was blocked by the full outgoing queue.
+pub(crate) fn record_encoder_stall(endpoint_id: EndPointID) {
+    let mut counters = SESSION_COUNTERS.lock().unwrap();
+    let counter = counters.entry(endpoint_id).or_insert_with(Counter::new);
+
+    counter.encoder_stalls += 1;
+}
+
+/// Counts the queued video frames which were dropped as they fell behind.
+pub(crate) fn record_video_backlog_dropped(endpoint_id: EndPointID, frames: usize) {
+    let mut counters = SESSION_COUNTERS.lock().unwrap();
+    let counter = counters.entry(endpoint_id).or_insert_with(Counter::new);
+
+    counter.backlog_drops += 1;
+    counter.dropped_video_frames += frames as u64;
+}
+
+pub(crate) fn record_decode_error(endpoint_id: EndPointID) {
+    if let Some(counter) = SESSION_COUNTERS.lock().unwrap().get_mut(&endpoint_id) {
+        counter.decode_errors += 1;
+    }
+}
+
+pub(crate) fn remove_session(endpoint_id: &EndPointID) {
+    SESSION_COUNTERS.lock().unwrap().remove(endpoint_id);
+}
+
+pub fn session_stats(endpoint_id: &EndPointID) -> Option<SessionStats> {
+    SESSION_COUNTERS
+        .lock()
+        .unwrap()
+        .get(endpoint_id)
+        .map(|counter| build_stats(endpoint_id, counter))
+}
+
+/// Returns the stats of all sessions which are receiving video.
+pub fn all_session_stats() -> Vec<(EndPointID, SessionStats)> {
+    SESSION_COUNTERS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(endpoint_id, counter)| (*endpoint_id, build_stats(endpoint_id, counter)))
+        .collect()
+}
+
+fn build_stats(endpoint_id: &EndPointID, counter: &Counter) -> SessionStats {
+    let elapsed = counter.started.elapsed().as_secs_f64();
+
+    let (average_frame_rate, average_bit_rate_kbps) = if elapsed > 0.0 {
+        (
+            (counter.video_frames as f64 / elapsed) as f32,
+            (counter.video_bytes as f64 * 8.0 / elapsed / 1000.0) as u64,
+        )
+    } else {
+        (0.0, 0)
+    };
+
+    SessionStats {
+        started_at: counter.started_at,
+        duration_secs: elapsed as u64,
+        video_frames: counter.video_frames,
+        video_bytes: counter.video_bytes,
+        average_frame_rate,
+        average_bit_rate_kbps,
+        encoder_stalls: counter.encoder_stalls,
+        backlog_drops: counter.backlog_drops,
+        dropped_video_frames: counter.dropped_video_frames,
+        decode_errors: counter.decode_errors,
+        video_latency_ms: counter
+            .video_latency
+            .map(|latency| latency.as_millis() as u64),
+        audio_latency_ms: counter
+            .audio_latency
+            .map(|latency| latency.as_millis() as u64),
+        audio_frames: counter.audio_frames,
+        audio_underruns: counter.audio_underruns,
+        audio_overruns: counter.audio_overruns,
+        audio_concealed_frames: counter.audio_concealed_frames,
+        audio_jitter_delay_ms: counter
+            .audio_jitter_delay
+            .map(|delay| delay.as_millis() as u64),
+        clock_offset_ms: clock_sync::clock_offset(endpoint_id)
+            .map(|sample| sample.offset_micros / 1000),
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{core_error, error::CoreResult};
+use mirrorx_native::os::macos::core_audio::*;
+use std::{ffi::c_void, mem::size_of};
+
+pub fn volume_scalar() -> CoreResult<f32> {
+    let device_id = default_output_device()?;
+    get_property(
+        device_id,
+        output_address(kAudioHardwareServiceDeviceProperty_VirtualMainVolume),
+    )
+}
+
+pub fn muted() -> CoreResult<bool> {
+    let device_id = default_output_device()?;
+    let muted: u32 = get_property(device_id, output_address(kAudioDevicePropertyMute))?;
+    Ok(muted != 0)
+}
+
+pub fn set_volume_scalar(scalar: f32) -> CoreResult<()> {
+    let device_id = default_output_device()?;
+    set_property(
+        device_id,
+        output_address(kAudioHardwareServiceDeviceProperty_VirtualMainVolume),
+        scalar,
+    )
+}
+
+pub fn set_muted(muted: bool) -> CoreResult<()> {
+    let device_id = default_output_device()?;
+    set_property(
+        device_id,
+        output_address(kAudioDevicePropertyMute),
+        muted as u32,
+    )
+}
+
+fn default_output_device() -> CoreResult<AudioObjectID> {
+    let address = AudioObjectPropertyAddress {
+        mSelector: kAudioHardwarePropertyDefaultOutputDevice,
+        mScope: kAudioObjectPropertyScopeGlobal,
+        mElement: kAudioObjectPropertyElementMain,
+    };
+
+    let device_id: AudioObjectID = get_property(kAudioObjectSystemObject, address)?;
+    if device_id == kAudioObjectUnknown {
+        return Err(core_error!("default audio output device not exist"));
+    }
+
+    Ok(device_id)
+}
+
+// the virtual main volume and mute cover every channel of the device
+fn output_address(selector: u32) -> AudioObjectPropertyAddress {
+    AudioObjectPropertyAddress {
+        mSelector: selector,
+        mScope: kAudioDevicePropertyScopeOutput,
+        mElement: kAudioObjectPropertyElementMain,
+    }
+}
+
+fn get_property<T: Default>(
+    object_id: AudioObjectID,
+    address: AudioObjectPropertyAddress,
+) -> CoreResult<T> {
+    let mut value = T::default();
+    let mut size = size_of::<T>() as u32;
+
+    let ret = unsafe {
+        AudioObjectGetPropertyData(
+            object_id,
+            &address,
+            0,
+            std::ptr::null(),
+            &mut size,
+            &mut value as *mut T as *mut c_void,
+        )
+    };
+
+    if ret != 0 {
+        return Err(core_error!(
+            "AudioObjectGetPropertyData returns error ({})",
+            ret
+        ));
+    }
+
+    Ok(value)
+}
+
+fn set_property<T>(
+    object_id: AudioObjectID,
+    address: AudioObjectPropertyAddress,
+    value: T,
+) -> CoreResult<()> {
+    let ret = unsafe {
+        AudioObjectSetPropertyData(
+            object_id,
+            &address,
+            0,
+            std::ptr::null(),
+            size_of::<T>() as u32,
+            &value as *const T as *const c_void,
+        )
+    };
+
+    if ret != 0 {
+        return Err(core_error!(
+            "AudioObjectSetPropertyData returns error ({})",
+            ret
+        ));
+    }
+
+    Ok(())
+}
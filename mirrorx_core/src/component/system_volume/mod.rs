@@ -0,0 +1,89 @@
+//! Viewers control the master volume of the default output device of passive endpoint,
+//! so a blaring machine can be silenced without hunting for the mixer in the streamed
+//! desktop. Only the device which is playing now is controlled, the volumes of single
+//! applications are left alone.
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "windows")]
+use self::windows as sys;
+
+#[cfg(target_os = "macos")]
+mod macos;
+
+#[cfg(target_os = "macos")]
+use self::macos as sys;
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+use self::unsupported as sys;
+
+use crate::error::CoreResult;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemVolume {
+    /// Master volume in percent.
+    pub level: u8,
+    pub muted: bool,
+}
+
+pub fn system_volume() -> CoreResult<SystemVolume> {
+    let scalar = sys::volume_scalar()?;
+    let muted = sys::muted()?;
+
+    Ok(SystemVolume {
+        level: (scalar.clamp(0.0, 1.0) * 100.0).round() as u8,
+        muted,
+    })
+}
+
+/// Change the volume or mute of default output device, what's None is left unchanged.
+/// Returns the volume after the change.
+pub fn set_system_volume(level: Option<u8>, muted: Option<bool>) -> CoreResult<SystemVolume> {
+    if let Some(level) = level {
+        sys::set_volume_scalar(level.min(100) as f32 / 100.0)?;
+    }
+
+    if let Some(muted) = muted {
+        sys::set_muted(muted)?;
+    }
+
+    let volume = system_volume()?;
+
+    tracing::info!(
+        level = volume.level,
+        muted = volume.muted,
+        "change system volume"
+    );
+
+    Ok(volume)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod unsupported {
+    use crate::{core_error, error::CoreResult};
+
+    pub fn volume_scalar() -> CoreResult<f32> {
+        Err(core_error!(
+            "volume control is not supported on current platform"
+        ))
+    }
+
+    pub fn muted() -> CoreResult<bool> {
+        Err(core_error!(
+            "volume control is not supported on current platform"
+        ))
+    }
+
+    pub fn set_volume_scalar(_: f32) -> CoreResult<()> {
+        Err(core_error!(
+            "volume control is not supported on current platform"
+        ))
+    }
+
+    pub fn set_muted(_: bool) -> CoreResult<()> {
+        Err(core_error!(
+            "volume control is not supported on current platform"
+        ))
+    }
+}
@@ -0,0 +1,59 @@
+use crate::{error::CoreResult, HRESULT};
+use scopeguard::defer;
+use windows::Win32::{
+    Media::Audio::{
+        eConsole, eRender, Endpoints::IAudioEndpointVolume, IMMDeviceEnumerator, MMDeviceEnumerator,
+    },
+    System::Com::{
+        CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_ALL, COINIT_MULTITHREADED,
+    },
+};
+
+pub fn volume_scalar() -> CoreResult<f32> {
+    with_endpoint_volume(|endpoint_volume| unsafe {
+        Ok(HRESULT!(endpoint_volume.GetMasterVolumeLevelScalar()))
+    })
+}
+
+pub fn muted() -> CoreResult<bool> {
+    with_endpoint_volume(|endpoint_volume| unsafe {
+        Ok(HRESULT!(endpoint_volume.GetMute()).as_bool())
+    })
+}
+
+pub fn set_volume_scalar(scalar: f32) -> CoreResult<()> {
+    with_endpoint_volume(|endpoint_volume| unsafe {
+        HRESULT!(endpoint_volume.SetMasterVolumeLevelScalar(scalar, std::ptr::null()));
+        Ok(())
+    })
+}
+
+pub fn set_muted(muted: bool) -> CoreResult<()> {
+    with_endpoint_volume(|endpoint_volume| unsafe {
+        HRESULT!(endpoint_volume.SetMute(muted, std::ptr::null()));
+        Ok(())
+    })
+}
+
+// the default device may change between calls, so it's looked up every time
+fn with_endpoint_volume<T>(
+    f: impl FnOnce(&IAudioEndpointVolume) -> CoreResult<T>,
+) -> CoreResult<T> {
+    unsafe {
+        // COM may be initialized by the caller thread already
+        let com_initialized = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+
+        defer! {
+            if com_initialized {
+                CoUninitialize();
+            }
+        }
+
+        let device_enumerator: IMMDeviceEnumerator =
+            HRESULT!(CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL));
+        let device = HRESULT!(device_enumerator.GetDefaultAudioEndpoint(eRender, eConsole));
+        let endpoint_volume: IAudioEndpointVolume = HRESULT!(device.Activate(CLSCTX_ALL, None));
+
+        f(&endpoint_volume)
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{
+    api::{config::LocalStorage, endpoint::message::VideoCodec},
+    error::{CoreError, CoreResult},
+};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+pub const DEFAULT_TELEMETRY_URL: &str = "https://mirrorx.cloud/api/telemetry";
+
+const UPLOAD_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// telemetry is opt-in, nothing is recorded until user enables it
+static TELEMETRY_ENABLED: AtomicBool = AtomicBool::new(false);
+
+static UPLOADER_STARTED: AtomicBool = AtomicBool::new(false);
+
+static AGGREGATE: Lazy<Mutex<Aggregate>> = Lazy::new(|| Mutex::new(Aggregate::new()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureCategory {
+    VisitRejected,
+    Timeout,
+    Network,
+    KeyExchange,
+    Negotiate,
+    Other,
+}
+
+impl From<&CoreError> for FailureCategory {
+    fn from(err: &CoreError) -> Self {
+        match err {
+            CoreError::Timeout => FailureCategory::Timeout,
+            CoreError::VisitFailed(_) => FailureCategory::VisitRejected,
+            CoreError::IO(_)
+            | CoreError::OutgoingMessageChannelFull
+            | CoreError::OutgoingMessageChannelDisconnect => FailureCategory::Network,
+            CoreError::RSAError(_) | CoreError::RingUnspecifiedError(_) => {
+                FailureCategory::KeyExchange
+            }
+            CoreError::Other { message, .. } if message.starts_with("negotiate") => {
+                FailureCategory::Negotiate
+            }
+            _ => FailureCategory::Other,
+        }
+    }
+}
+
+struct Aggregate {
+    period_start: i64,
+    connect_attempts: u64,
+    connect_successes: u64,
+    video_bytes: u64,
+    video_duration: Duration,
+    codecs: HashMap<String, u64>,
+    failures: HashMap<FailureCategory, u64>,
+}
+
+impl Aggregate {
+    fn new() -> Self {
+        Self {
+            period_start: chrono::Utc::now().timestamp(),
+            connect_attempts: 0,
+            connect_successes: 0,
+            video_bytes: 0,
+            video_duration: Duration::ZERO,
+            codecs: HashMap::new(),
+            failures: HashMap::new(),
+        }
+    }
+
+    fn merge(&mut self, other: Aggregate) {
+        self.period_start = self.period_start.min(other.period_start);
+        self.connect_attempts += other.connect_attempts;
+        self.connect_successes += other.connect_successes;
+        self.video_bytes += other.video_bytes;
+        self.video_duration += other.video_duration;
+
+        for (codec, count) in other.codecs {
+            *self.codecs.entry(codec).or_default() += count;
+        }
+
+        for (category, count) in other.failures {
+            *self.failures.entry(category).or_default() += count;
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.connect_attempts == 0 && self.video_duration.is_zero()
+    }
+}
+
+/// The anonymous report which is uploaded, it contains only the aggregated numbers
+/// without any device id, address or name.
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetryReport {
+    pub os: String,
+    pub arch: String,
+    pub period_start: i64,
+    pub period_end: i64,
+    pub connect_attempts: u64,
+    pub connect_success_rate: Option<f32>,
+    pub average_bit_rate_kbps: Option<u64>,
+    pub codecs: HashMap<String, u64>,
+    pub failures: HashMap<FailureCategory, u64>,
+}
+
+pub fn set_telemetry_enabled(enabled: bool) {
+    TELEMETRY_ENABLED.store(enabled, Ordering::SeqCst);
+
+    if !enabled {
+        *AGGREGATE.lock().unwrap() = Aggregate::new();
+    }
+}
+
+pub fn telemetry_enabled() -> bool {
+    TELEMETRY_ENABLED.load(Ordering::SeqCst)
+}
+
+fn aggregate<F: FnOnce(&mut Aggregate)>(f: F) {
+    if telemetry_enabled() {
+        f(&mut AGGREGATE.lock().unwrap());
+    }
+}
+
+pub fn record_connect_result<T>(result: &CoreResult<T>) {
+    match result {
+        Ok(_) => aggregate(|aggregate| {
+            aggregate.connect_attempts += 1;
+            aggregate.connect_successes += 1;
+        }),
+        Err(err) => record_connect_failure(err.into()),
+    }
+}
+
+pub fn record_connect_failure(category: FailureCategory) {
+    aggregate(|aggregate| {
+        aggregate.connect_attempts += 1;
+        *aggregate.failures.entry(category).or_default() += 1;
+    });
+}
+
+pub fn record_video_codec(codec: &VideoCodec) {
+    aggregate(|aggregate| {
+        *aggregate.codecs.entry(format!("{codec:?}")).or_default() += 1;
+    });
+}
+
+pub fn record_video_traffic(bytes: u64, duration: Duration) {
+    aggregate(|aggregate| {
+        aggregate.video_bytes += bytes;
+        aggregate.video_duration += duration;
+    });
+}
+
+/// Build the report from current aggregate, it's exactly what would be uploaded.
+pub fn preview() -> TelemetryReport {
+    build_report(&AGGREGATE.lock().unwrap())
+}
+
+fn build_report(aggregate: &Aggregate) -> TelemetryReport {
+    let connect_success_rate = (aggregate.connect_attempts > 0)
+        .then(|| aggregate.connect_successes as f32 / aggregate.connect_attempts as f32);
+
+    let video_secs = aggregate.video_duration.as_secs_f64();
+    let average_bit_rate_kbps = (video_secs > 0.0)
+        .then(|| (aggregate.video_bytes as f64 * 8.0 / video_secs / 1000.0) as u64);
+
+    TelemetryReport {
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        period_start: aggregate.period_start,
+        period_end: chrono::Utc::now().timestamp(),
+        connect_attempts: aggregate.connect_attempts,
+        connect_success_rate,
+        average_bit_rate_kbps,
+        codecs: aggregate.codecs.clone(),
+        failures: aggregate.failures.clone(),
+    }
+}
+
+/// Upload the aggregated report in batch periodically, the aggregate is reset after
+/// uploaded successfully.
+pub fn serve_batch_upload(storage: LocalStorage) {
+    if UPLOADER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPLOAD_INTERVAL);
+        // the first tick completes immediately, skip it to collect something first
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            if !telemetry_enabled() {
+                continue;
+            }
+
+            let url = match storage.kv().get_telemetry_url() {
+                Ok(url) => url.unwrap_or_else(|| DEFAULT_TELEMETRY_URL.to_string()),
+                Err(err) => {
+                    tracing::error!(?err, "get telemetry url failed");
+                    continue;
+                }
+            };
+
+            if let Err(err) = upload(&url).await {
+                tracing::warn!(?err, "upload telemetry report failed");
+            }
+        }
+    });
+}
+
+async fn upload(url: &str) -> CoreResult<()> {
+    let aggregate = std::mem::replace(&mut *AGGREGATE.lock().unwrap(), Aggregate::new());
+    if aggregate.is_empty() {
+        AGGREGATE.lock().unwrap().period_start = aggregate.period_start;
+        return Ok(());
+    }
+
+    let report = build_report(&aggregate);
+
+    let result = async {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?
+            .post(url)
+            .json(&report)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        CoreResult::Ok(())
+    }
+    .await;
+
+    // put back the records for next batch, merged with the ones recorded during upload
+    if result.is_err() && telemetry_enabled() {
+        AGGREGATE.lock().unwrap().merge(aggregate);
+    }
+
+    result
+}
@@ -0,0 +1,158 @@
+//! Desktops of terminals and IDEs redraw the same tiles again and again, which video
+//! codecs encode anew every time. The tile codec splits NV12 frames into tiles and hashes
+//! them, tiles which didn't change since the last frame are skipped and those which were
+//! sent before are referenced by hash, only new tiles are sent as zstd compressed pixels.
+//!
+//! Both endpoints keep the tiles of the same hashes: they're inserted in the order the
+//! frames carry them and the oldest is evicted first, so the encoder knows what the
+//! decoder holds without being told. A key frame clears both caches, it's sent when the
+//! stream starts and whenever the decoder lost track, like after frames were shed.
+
+use super::frame::ColorSpace;
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Width and height of tiles in pixels, tiles at the right and bottom edges are smaller.
+pub const TILE_SIZE: usize = 64;
+
+// about 24MB of NV12 tiles on the decoder side
+pub const TILE_CACHE_CAPACITY: usize = 4096;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TileFrame {
+    // the decoder drops its cache and every tile of the frame is sent
+    pub key_frame: bool,
+    pub color_space: ColorSpace,
+    // tiles which are unchanged since the previous frame are left out
+    pub updates: Vec<TileUpdate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TileUpdate {
+    // row-major index of the tile in frame
+    pub index: u32,
+    pub content: TileContent,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum TileContent {
+    // the tile of the hash which was sent before
+    Cached(u64),
+    // luminance rows followed by interleaved chrominance rows, zstd compressed
+    Pixels {
+        hash: u64,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
+}
+
+/// The rectangle of tile `index` in frame of the size, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl TileRect {
+    /// Rows of the tile in the chrominance plane, which has half the rows of frame.
+    pub fn chrominance_rows(&self) -> std::ops::Range<usize> {
+        self.y / 2..(self.y + self.height + 1) / 2
+    }
+
+    /// Bytes of the tile in NV12, luminance and chrominance together.
+    pub fn byte_len(&self) -> usize {
+        self.width * (self.height + self.chrominance_rows().len())
+    }
+}
+
+/// Tiles of the frame in row-major order.
+pub fn tile_rects(width: usize, height: usize) -> impl Iterator<Item = TileRect> {
+    (0..height).step_by(TILE_SIZE).flat_map(move |y| {
+        (0..width).step_by(TILE_SIZE).map(move |x| TileRect {
+            x,
+            y,
+            width: TILE_SIZE.min(width - x),
+            height: TILE_SIZE.min(height - y),
+        })
+    })
+}
+
+/// The rectangle of the tile at row-major `index`, None when it's out of frame.
+pub fn tile_rect(width: usize, height: usize, index: usize) -> Option<TileRect> {
+    let columns = (width + TILE_SIZE - 1) / TILE_SIZE;
+    if index >= tile_count(width, height) {
+        return None;
+    }
+
+    let x = (index % columns) * TILE_SIZE;
+    let y = (index / columns) * TILE_SIZE;
+
+    Some(TileRect {
+        x,
+        y,
+        width: TILE_SIZE.min(width - x),
+        height: TILE_SIZE.min(height - y),
+    })
+}
+
+pub fn tile_count(width: usize, height: usize) -> usize {
+    ((width + TILE_SIZE - 1) / TILE_SIZE) * ((height + TILE_SIZE - 1) / TILE_SIZE)
+}
+
+// a collision shows a wrong tile until it changes, so it's a cryptographic hash rather
+// than a fast one
+pub fn tile_hash(bytes: &[u8]) -> u64 {
+    let mut hash = [0u8; 8];
+    hash.copy_from_slice(&digest(&SHA256, bytes).as_ref()[..8]);
+    u64::from_le_bytes(hash)
+}
+
+/// Tiles by hash, the oldest is evicted once it's full.
+pub struct TileCache<T> {
+    order: VecDeque<u64>,
+    tiles: HashMap<u64, T>,
+}
+
+impl<T> TileCache<T> {
+    pub fn new() -> Self {
+        Self {
+            order: VecDeque::with_capacity(TILE_CACHE_CAPACITY),
+            tiles: HashMap::with_capacity(TILE_CACHE_CAPACITY),
+        }
+    }
+
+    pub fn get(&self, hash: u64) -> Option<&T> {
+        self.tiles.get(&hash)
+    }
+
+    pub fn contains(&self, hash: u64) -> bool {
+        self.tiles.contains_key(&hash)
+    }
+
+    pub fn insert(&mut self, hash: u64, tile: T) {
+        if self.tiles.insert(hash, tile).is_some() {
+            return;
+        }
+
+        self.order.push_back(hash);
+        if self.order.len() > TILE_CACHE_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.tiles.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.order.clear();
+        self.tiles.clear();
+    }
+}
+
+impl<T> Default for TileCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,151 @@
+//! Hosts running as service at the login screen can be signed in by who controls them.
+//! The credentials are submitted over the encrypted channel, the host agent checks them
+//! with the system and enters them into the login screen. It's off unless host user
+//! enables unattended login, and it takes the admin tools permission besides input.
+//! Failed attempts of a remote device on an account make it wait longer before the next
+//! one, and it's locked out after `MAX_FAILED_ATTEMPTS` until `FAILED_ATTEMPTS_DECAY`
+//! passes since the last one, so it can't guess the password by reconnecting.
+
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(target_os = "windows")]
+use self::windows as sys;
+
+#[cfg(not(target_os = "windows"))]
+use self::unsupported as sys;
+
+use crate::{core_error, error::CoreResult};
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+
+// doubled with every failed attempt
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+// failed attempts are forgotten once there's none for this long
+const FAILED_ATTEMPTS_DECAY: Duration = Duration::from_secs(60 * 60);
+
+static UNATTENDED_LOGIN_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// keyed by remote and the lowercased account
+static FAILED_ATTEMPTS: Lazy<Mutex<HashMap<(String, String), FailedAttempts>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct FailedAttempts {
+    count: u32,
+    retry_at: Instant,
+    last_attempt: Instant,
+}
+
+/// Set whether remote can sign in at the login screen of this device, it's disabled by
+/// default.
+pub fn set_unattended_login_enabled(enabled: bool) {
+    UNATTENDED_LOGIN_ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn unattended_login_enabled() -> bool {
+    UNATTENDED_LOGIN_ENABLED.load(Ordering::SeqCst)
+}
+
+/// Sign in at the login screen with the credentials which `remote` submits, it's the
+/// device id of visitor or its address in LAN. `domain` is the local machine when it's
+/// None. It blocks until the credentials are entered.
+pub(crate) fn login(
+    remote: &str,
+    username: &str,
+    domain: Option<&str>,
+    password: &str,
+) -> CoreResult<()> {
+    if !unattended_login_enabled() {
+        return Err(core_error!("unattended login is disabled on remote device"));
+    }
+
+    sys::ensure_login_screen()?;
+
+    let account = match domain {
+        Some(domain) => format!("{domain}\\{username}"),
+        None => username.to_string(),
+    };
+    let key = (remote.to_string(), account.to_lowercase());
+
+    let failed_count = {
+        let mut failed_attempts = FAILED_ATTEMPTS.lock().unwrap();
+
+        let now = Instant::now();
+        failed_attempts.retain(|_, attempts| {
+            now.duration_since(attempts.last_attempt) < FAILED_ATTEMPTS_DECAY
+        });
+
+        let attempts = failed_attempts
+            .entry(key.clone())
+            .or_insert(FailedAttempts {
+                count: 0,
+                retry_at: now,
+                last_attempt: now,
+            });
+
+        if attempts.count >= MAX_FAILED_ATTEMPTS {
+            return Err(core_error!(
+                "unattended login is locked out after too many failed attempts"
+            ));
+        }
+
+        let wait = attempts.retry_at.saturating_duration_since(now);
+        if !wait.is_zero() {
+            return Err(core_error!(
+                "unattended login failed recently, retry in {} seconds",
+                wait.as_secs() + 1
+            ));
+        }
+
+        // counted as failed until the system accepts the credentials, so parallel attempts
+        // can't skip the delay while the lock is released for the check
+        attempts.count += 1;
+        attempts.retry_at = now + RETRY_DELAY * 2u32.pow(attempts.count - 1);
+        attempts.last_attempt = now;
+        attempts.count
+    };
+
+    // wrong credentials are rejected here rather than typed into the login screen
+    if let Err(err) = sys::check_credentials(username, domain, password) {
+        tracing::warn!(
+            remote,
+            failed_attempts = failed_count,
+            "unattended login failed"
+        );
+
+        return Err(err);
+    }
+
+    FAILED_ATTEMPTS.lock().unwrap().remove(&key);
+
+    sys::enter_credentials(username, password)
+}
+
+#[cfg(not(target_os = "windows"))]
+mod unsupported {
+    use crate::{core_error, error::CoreResult};
+
+    pub fn ensure_login_screen() -> CoreResult<()> {
+        Err(core_error!(
+            "unattended login is not supported on current platform"
+        ))
+    }
+
+    pub fn check_credentials(_: &str, _: Option<&str>, _: &str) -> CoreResult<()> {
+        ensure_login_screen()
+    }
+
+    pub fn enter_credentials(_: &str, _: &str) -> CoreResult<()> {
+        ensure_login_screen()
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{
+    component::desktop::secure_desktop::{
+        attach_input_desktop, current_input_desktop, InputDesktop,
+    },
+    core_error,
+    error::CoreResult,
+};
+use scopeguard::defer;
+use std::ffi::c_void;
+use widestring::{U16CStr, U16CString};
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{CloseHandle, GetLastError, HANDLE},
+        Security::{
+            GetTokenInformation, IsWellKnownSid, LogonUserW, TokenUser, WinLocalSystemSid,
+            LOGON32_LOGON_INTERACTIVE, LOGON32_PROVIDER_DEFAULT, TOKEN_QUERY, TOKEN_USER,
+        },
+        System::{
+            RemoteDesktop::{
+                WTSFreeMemory, WTSGetActiveConsoleSessionId, WTSQuerySessionInformationW,
+                WTSUserName, WTS_CURRENT_SERVER_HANDLE,
+            },
+            Threading::{GetCurrentProcess, OpenProcessToken},
+        },
+        UI::Input::KeyboardAndMouse::{
+            SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS,
+            KEYEVENTF_KEYUP, KEYEVENTF_UNICODE, VIRTUAL_KEY, VK_RETURN, VK_TAB,
+        },
+    },
+};
+
+// only LocalSystem can reach the secure desktop which the login screen is shown on
+pub fn ensure_login_screen() -> CoreResult<()> {
+    if !process_is_local_system() {
+        return Err(core_error!(
+            "unattended login requires MirrorX running as service"
+        ));
+    }
+
+    if current_input_desktop() != InputDesktop::Secure {
+        return Err(core_error!("remote device isn't showing the login screen"));
+    }
+
+    Ok(())
+}
+
+/// The credentials are entered into the login screen of the default password provider.
+/// The lock screen of the console user only asks for password, other users are typed in
+/// the user name field, so the login screen should ask for the user name, like with the
+/// "Don't display last signed-in" policy.
+pub fn enter_credentials(username: &str, password: &str) -> CoreResult<()> {
+    let typed_username = match console_session_user() {
+        Some(user) if user.eq_ignore_ascii_case(username) => None,
+        _ => Some(username),
+    };
+
+    // the thread attaches to the secure desktop for its input, so it's not a pooled one
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .name(String::from("unattended_login"))
+            .spawn_scoped(scope, || type_credentials(typed_username, password))?
+            .join()
+            .map_err(|_| core_error!("unattended login thread panicked"))?
+    })
+}
+
+pub fn check_credentials(username: &str, domain: Option<&str>, password: &str) -> CoreResult<()> {
+    let username = U16CString::from_str(username).map_err(|_| core_error!("invalid user name"))?;
+    // accounts of the local machine
+    let domain =
+        U16CString::from_str(domain.unwrap_or(".")).map_err(|_| core_error!("invalid domain"))?;
+    let password = U16CString::from_str(password).map_err(|_| core_error!("invalid password"))?;
+
+    unsafe {
+        let mut token = HANDLE::default();
+        if !LogonUserW(
+            PCWSTR(username.as_ptr()),
+            PCWSTR(domain.as_ptr()),
+            PCWSTR(password.as_ptr()),
+            LOGON32_LOGON_INTERACTIVE,
+            LOGON32_PROVIDER_DEFAULT,
+            &mut token,
+        )
+        .as_bool()
+        {
+            return Err(core_error!(
+                "check credentials failed ({:?})",
+                GetLastError().to_hresult()
+            ));
+        }
+
+        let _ = CloseHandle(token);
+    }
+
+    Ok(())
+}
+
+fn type_credentials(username: Option<&str>, password: &str) -> CoreResult<()> {
+    attach_input_desktop();
+
+    let mut inputs = Vec::new();
+
+    if let Some(username) = username {
+        push_text(&mut inputs, username);
+        push_key(&mut inputs, VK_TAB);
+    }
+
+    push_text(&mut inputs, password);
+    push_key(&mut inputs, VK_RETURN);
+
+    unsafe {
+        if SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) as usize == inputs.len() {
+            Ok(())
+        } else {
+            Err(core_error!(
+                "SendInput failed ({:?})",
+                GetLastError().to_hresult()
+            ))
+        }
+    }
+}
+
+// characters are typed as unicode, so they don't depend on the layout of login screen
+fn push_text(inputs: &mut Vec<INPUT>, text: &str) {
+    for unit in text.encode_utf16() {
+        for flags in [KEYEVENTF_UNICODE, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP] {
+            inputs.push(keyboard_input(VIRTUAL_KEY(0), unit, flags));
+        }
+    }
+}
+
+fn push_key(inputs: &mut Vec<INPUT>, key: VIRTUAL_KEY) {
+    for flags in [KEYBD_EVENT_FLAGS(0), KEYEVENTF_KEYUP] {
+        inputs.push(keyboard_input(key, 0, flags));
+    }
+}
+
+fn keyboard_input(key: VIRTUAL_KEY, scan: u16, flags: KEYBD_EVENT_FLAGS) -> INPUT {
+    INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: key,
+                wScan: scan,
+                dwFlags: flags,
+                ..Default::default()
+            },
+        },
+    }
+}
+
+fn process_is_local_system() -> bool {
+    unsafe {
+        let mut token = HANDLE::default();
+        if !OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).as_bool() {
+            return false;
+        }
+
+        defer! {
+            let _ = CloseHandle(token);
+        }
+
+        // TOKEN_USER is followed by the SID it points to
+        let mut buffer = [0usize; 64];
+        let mut return_length = 0;
+
+        if !GetTokenInformation(
+            token,
+            TokenUser,
+            Some(buffer.as_mut_ptr() as *mut c_void),
+            std::mem::size_of_val(&buffer) as u32,
+            &mut return_length,
+        )
+        .as_bool()
+        {
+            return false;
+        }
+
+        let user = &*(buffer.as_ptr() as *const TOKEN_USER);
+        IsWellKnownSid(user.User.Sid, WinLocalSystemSid).as_bool()
+    }
+}
+
+// the user of the console session, None when nobody is signed in
+fn console_session_user() -> Option<String> {
+    unsafe {
+        let mut buffer = PWSTR::null();
+        let mut bytes_returned = 0;
+
+        if !WTSQuerySessionInformationW(
+            WTS_CURRENT_SERVER_HANDLE,
+            WTSGetActiveConsoleSessionId(),
+            WTSUserName,
+            &mut buffer,
+            &mut bytes_returned,
+        )
+        .as_bool()
+        {
+            return None;
+        }
+
+        defer! {
+            WTSFreeMemory(buffer.0 as *mut c_void);
+        }
+
+        let user = U16CStr::from_ptr_str(buffer.0).to_string_lossy();
+        (!user.is_empty()).then_some(user)
+    }
+}
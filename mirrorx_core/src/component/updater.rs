@@ -0,0 +1,245 @@
+use crate::{api::config::entity::kv::UpdateChannel, core_error, error::CoreResult};
+use base64::{engine::general_purpose::STANDARD as base64_standard, Engine};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub const DEFAULT_UPDATE_URL: &str = "https://mirrorx.cloud/update/";
+
+// the ed25519 public key (base64) of release signing key, injected by release build
+const UPDATE_PUBLIC_KEY: Option<&str> = option_env!("MIRRORX_UPDATE_PUBLIC_KEY");
+
+/// The manifest served by update server, `manifest` is the raw json of
+/// [`ReleaseManifest`] and `signature` is the base64 ed25519 signature of it.
+#[derive(Debug, Deserialize)]
+struct SignedManifest {
+    manifest: String,
+    signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseManifest {
+    pub version: String,
+    pub channel: UpdateChannel,
+    pub notes: String,
+    pub pub_date: i64,
+    /// Packages keyed by platform, like `windows-x86_64` and `macos-aarch64`.
+    pub packages: HashMap<String, ReleasePackage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleasePackage {
+    pub url: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl ReleaseManifest {
+    pub fn package(&self) -> Option<&ReleasePackage> {
+        self.packages.get(&current_platform())
+    }
+}
+
+fn current_platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// Fetch and verify the release manifest of channel, returns it only when it's newer
+/// than current version and has the package for current platform.
+#[tracing::instrument]
+pub async fn check_update(
+    update_url: &str,
+    channel: UpdateChannel,
+    current_version: &str,
+) -> CoreResult<Option<ReleaseManifest>> {
+    let channel_name: &str = channel.into();
+    let url = url::Url::parse(update_url)?.join(&format!("{channel_name}.json"))?;
+
+    let signed_manifest = http_client()?
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<SignedManifest>()
+        .await?;
+
+    verify_signature(&signed_manifest)?;
+
+    let manifest: ReleaseManifest = serde_json::from_str(&signed_manifest.manifest)?;
+
+    // a manifest of another channel can't be replayed to switch the channel
+    if manifest.channel != channel {
+        return Err(core_error!("release manifest channel mismatch"));
+    }
+
+    if compare_version(&manifest.version, current_version)? != Ordering::Greater {
+        return Ok(None);
+    }
+
+    if manifest.package().is_none() {
+        tracing::warn!(version = ?manifest.version, "release has no package for current platform");
+        return Ok(None);
+    }
+
+    Ok(Some(manifest))
+}
+
+fn verify_signature(signed_manifest: &SignedManifest) -> CoreResult<()> {
+    let Some(public_key) = UPDATE_PUBLIC_KEY else {
+        return Err(core_error!("update public key not configured"));
+    };
+
+    let public_key = base64_standard.decode(public_key)?;
+    let signature = base64_standard.decode(&signed_manifest.signature)?;
+
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(signed_manifest.manifest.as_bytes(), &signature)
+        .map_err(|_| core_error!("release manifest signature is invalid"))
+}
+
+/// Download the package of current platform into `dir` and verify its checksum.
+#[tracing::instrument(skip(manifest))]
+pub async fn download_package(manifest: &ReleaseManifest, dir: &Path) -> CoreResult<PathBuf> {
+    let Some(package) = manifest.package() else {
+        return Err(core_error!("release has no package for current platform"));
+    };
+
+    let url = url::Url::parse(&package.url)?;
+    let file_name = url
+        .path_segments()
+        .and_then(|segments| segments.last())
+        .filter(|name| !name.is_empty())
+        .ok_or(core_error!("invalid package url"))?;
+
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(file_name);
+
+    let mut resp = http_client()?
+        .get(url.clone())
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let mut file = std::fs::File::create(&path)?;
+    let mut hasher = Sha256::new();
+    let mut downloaded = 0;
+
+    while let Some(chunk) = resp.chunk().await? {
+        downloaded += chunk.len() as u64;
+        if downloaded > package.size {
+            return Err(core_error!("package is larger than manifest declared"));
+        }
+
+        hasher.update(&chunk);
+        file.write_all(&chunk)?;
+    }
+
+    file.flush()?;
+    drop(file);
+
+    let checksum = hex::encode(hasher.finalize());
+    if downloaded != package.size || !checksum.eq_ignore_ascii_case(&package.sha256) {
+        let _ = std::fs::remove_file(&path);
+        return Err(core_error!("package checksum mismatch"));
+    }
+
+    Ok(path)
+}
+
+/// Hand off the downloaded package to the OS installer, the caller should quit the
+/// application after it returns so the installer can replace the files.
+#[tracing::instrument]
+pub fn launch_installer(package_path: &Path) -> CoreResult<()> {
+    #[cfg(target_os = "windows")]
+    {
+        let is_msi = package_path
+            .extension()
+            .map_or(false, |extension| extension.eq_ignore_ascii_case("msi"));
+
+        if is_msi {
+            std::process::Command::new("msiexec")
+                .arg("/i")
+                .arg(package_path)
+                .spawn()?;
+        } else {
+            std::process::Command::new(package_path).spawn()?;
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg(package_path)
+            .spawn()?;
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    return Err(core_error!(
+        "install package is not supported on current platform: {:?}",
+        package_path
+    ));
+
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    Ok(())
+}
+
+fn http_client() -> CoreResult<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .build()?)
+}
+
+/// Compare versions like `1.2.3` and `1.2.3-beta.1`, a pre-release is older than the
+/// release of the same version.
+fn compare_version(a: &str, b: &str) -> CoreResult<Ordering> {
+    let (a_numbers, a_pre_release) = parse_version(a)?;
+    let (b_numbers, b_pre_release) = parse_version(b)?;
+
+    let ordering = a_numbers
+        .cmp(&b_numbers)
+        .then_with(|| match (a_pre_release, b_pre_release) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a), Some(b)) => compare_pre_release(a, b),
+        });
+
+    Ok(ordering)
+}
+
+fn parse_version(version: &str) -> CoreResult<(Vec<u64>, Option<&str>)> {
+    let version = version.trim_start_matches('v');
+    let (numbers, pre_release) = match version.split_once('-') {
+        Some((numbers, pre_release)) => (numbers, Some(pre_release)),
+        None => (version, None),
+    };
+
+    let numbers = numbers
+        .split('.')
+        .map(|number| number.parse::<u64>())
+        .collect::<Result<Vec<u64>, _>>()?;
+
+    Ok((numbers, pre_release))
+}
+
+fn compare_pre_release(a: &str, b: &str) -> Ordering {
+    for (a, b) in a.split('.').zip(b.split('.')) {
+        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => a.cmp(b),
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    a.split('.').count().cmp(&b.split('.').count())
+}
@@ -1 +1,2 @@
 pub mod decoder;
+pub mod tile;
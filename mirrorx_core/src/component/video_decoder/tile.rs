@@ -0,0 +1,115 @@
+use crate::{
+    api::endpoint::message::EndPointVideoFrame,
+    component::{
+        frame::{DesktopDecodeFrame, DesktopDecodeFrameFormat},
+        tile_cache::{tile_rect, TileCache, TileContent, TileFrame},
+    },
+    core_error,
+    error::CoreResult,
+    utility::bincode::bincode_deserialize,
+};
+
+/// Largest width or height a peer may announce, the planes are allocated from them.
+const MAX_DIMENSION: usize = 16384;
+
+/// Decoder of the tile codec, see `tile_cache`. It paints the tiles onto the NV12 frame
+/// which it keeps, unchanged tiles are left as the previous frame drew them.
+#[derive(Default)]
+pub struct TileDecoder {
+    width: usize,
+    height: usize,
+    luminance: Vec<u8>,
+    chrominance: Vec<u8>,
+    cache: TileCache<Vec<u8>>,
+    key_frame_received: bool,
+}
+
+impl TileDecoder {
+    pub fn decode(&mut self, video_frame: &EndPointVideoFrame) -> CoreResult<DesktopDecodeFrame> {
+        let tile_frame: TileFrame = bincode_deserialize(&video_frame.buffer)?;
+
+        let width = video_frame.width as usize;
+        let height = video_frame.height as usize;
+
+        if tile_frame.key_frame {
+            if width > MAX_DIMENSION || height > MAX_DIMENSION {
+                return Err(core_error!(
+                    "tile frame {}x{} exceeds {}x{}",
+                    width,
+                    height,
+                    MAX_DIMENSION,
+                    MAX_DIMENSION
+                ));
+            }
+
+            let luminance_len = width
+                .checked_mul(height)
+                .ok_or(core_error!("tile frame size overflows"))?;
+            let chrominance_len = width
+                .checked_mul((height + 1) / 2)
+                .ok_or(core_error!("tile frame size overflows"))?;
+
+            self.width = width;
+            self.height = height;
+            self.luminance = vec![0; luminance_len];
+            self.chrominance = vec![0; chrominance_len];
+            self.cache.clear();
+            self.key_frame_received = true;
+        } else if !self.key_frame_received || (self.width, self.height) != (width, height) {
+            // the caches are out of sync, fails until the requested key frame arrives
+            self.key_frame_received = false;
+            return Err(core_error!("tile frame arrives before key frame"));
+        }
+
+        for update in tile_frame.updates {
+            let rect = tile_rect(width, height, update.index as usize)
+                .ok_or(core_error!("tile index {} is out of frame", update.index))?;
+
+            let tile = match update.content {
+                TileContent::Cached(hash) => self.cache.get(hash).cloned().ok_or_else(|| {
+                    self.key_frame_received = false;
+                    core_error!("tile {:x} isn't cached", hash)
+                })?,
+                TileContent::Pixels { hash, data } => {
+                    let tile = zstd::bulk::decompress(&data, rect.byte_len())?;
+                    self.cache.insert(hash, tile.clone());
+                    tile
+                }
+            };
+
+            if tile.len() != rect.byte_len() {
+                return Err(core_error!(
+                    "tile has {} bytes, expect {}",
+                    tile.len(),
+                    rect.byte_len()
+                ));
+            }
+
+            let (luminance_tile, chrominance_tile) = tile.split_at(rect.width * rect.height);
+
+            for (row, bytes) in
+                (rect.y..rect.y + rect.height).zip(luminance_tile.chunks(rect.width))
+            {
+                let offset = row * width + rect.x;
+                self.luminance[offset..offset + rect.width].copy_from_slice(bytes);
+            }
+
+            for (row, bytes) in rect
+                .chrominance_rows()
+                .zip(chrominance_tile.chunks(rect.width))
+            {
+                let offset = row * width + rect.x;
+                self.chrominance[offset..offset + rect.width].copy_from_slice(bytes);
+            }
+        }
+
+        Ok(DesktopDecodeFrame {
+            width: video_frame.width,
+            height: video_frame.height,
+            plane_data: vec![self.luminance.clone(), self.chrominance.clone()],
+            line_sizes: vec![video_frame.width, video_frame.width],
+            format: DesktopDecodeFrameFormat::NV12,
+            color_space: tile_frame.color_space,
+        })
+    }
+}
@@ -1,5 +1,5 @@
 use super::{set_codec_ctx_option, EncoderConfig};
-use crate::error::CoreResult;
+use crate::{api::endpoint::message::SessionProfile, error::CoreResult};
 use mirrorx_native::ffmpeg::codecs::{avcodec::AVCodecContext, codec_id::*};
 use std::ffi::CString;
 
@@ -16,7 +16,7 @@ impl Default for H264VideoToolboxConfig {
 }
 
 impl EncoderConfig for H264VideoToolboxConfig {
-    fn apply_option(&self, codec_ctx: *mut AVCodecContext) -> CoreResult<()> {
+    fn apply_option(&self, codec_ctx: *mut AVCodecContext, _: SessionProfile) -> CoreResult<()> {
         set_codec_ctx_option(codec_ctx, "profile", "high", 0)?;
         set_codec_ctx_option(codec_ctx, "level", "5.0", 0)?;
         // set_codec_ctx_option(codec_ctx, "realtime", "true", 0)?;
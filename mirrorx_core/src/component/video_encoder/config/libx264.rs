@@ -1,5 +1,5 @@
 use super::{set_codec_ctx_option, EncoderConfig};
-use crate::error::CoreResult;
+use crate::{api::endpoint::message::SessionProfile, error::CoreResult};
 use mirrorx_native::ffmpeg::codecs::{avcodec::AVCodecContext, codec_id::*};
 use std::ffi::CString;
 
@@ -16,11 +16,24 @@ impl Default for Libx264Config {
 }
 
 impl EncoderConfig for Libx264Config {
-    fn apply_option(&self, codec_ctx: *mut AVCodecContext) -> CoreResult<()> {
-        set_codec_ctx_option(codec_ctx, "profile", "baseline", 0)?;
+    fn apply_option(
+        &self,
+        codec_ctx: *mut AVCodecContext,
+        profile: SessionProfile,
+    ) -> CoreResult<()> {
+        // baseline profile has no B-frames, they're dropped by it silently
+        let b_frames = unsafe { (*codec_ctx).max_b_frames };
+        let h264_profile = if b_frames > 0 { "main" } else { "baseline" };
+
+        set_codec_ctx_option(codec_ctx, "profile", h264_profile, 0)?;
         set_codec_ctx_option(codec_ctx, "level", "5.0", 0)?;
         set_codec_ctx_option(codec_ctx, "preset", "ultrafast", 0)?;
-        set_codec_ctx_option(codec_ctx, "tune", "zerolatency", 0)?;
+
+        // streaming keeps the frame threads, they hold back a few frames but encode
+        // faster
+        if profile == SessionProfile::Interactive {
+            set_codec_ctx_option(codec_ctx, "tune", "zerolatency", 0)?;
+        }
 
         Ok(())
     }
@@ -1,8 +1,9 @@
 pub mod h264_videotoolbox;
 pub mod hevc_videotoolbox;
 pub mod libx264;
+pub mod probed;
 
-use crate::{core_error, error::CoreResult};
+use crate::{api::endpoint::message::SessionProfile, core_error, error::CoreResult};
 use mirrorx_native::ffmpeg::{
     codecs::{avcodec::AVCodecContext, codec_id::AVCodecID},
     utils::{
@@ -13,7 +14,12 @@ use mirrorx_native::ffmpeg::{
 use std::ffi::CString;
 
 pub trait EncoderConfig {
-    fn apply_option(&self, codec_ctx: *mut AVCodecContext) -> CoreResult<()>;
+    /// Set the private options of the encoder, tuned for the profile of the session.
+    fn apply_option(
+        &self,
+        codec_ctx: *mut AVCodecContext,
+        profile: SessionProfile,
+    ) -> CoreResult<()>;
     fn ffmpeg_encoder_name(&self) -> *const i8;
     fn av_codec_id(&self) -> AVCodecID;
 }
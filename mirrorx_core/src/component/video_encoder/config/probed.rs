@@ -0,0 +1,109 @@
+use super::{libx264::Libx264Config, set_codec_ctx_option, EncoderConfig};
+use crate::{
+    api::endpoint::message::{SessionProfile, VideoCodec},
+    component::codec_probe::{Accelerator, CodecSupport},
+    core_error,
+    error::CoreResult,
+};
+use mirrorx_native::ffmpeg::codecs::{avcodec::AVCodecContext, codec_id::*};
+use std::ffi::CString;
+
+/// Encoder which codec probing found, with the options for low latency of its
+/// accelerator.
+pub struct ProbedEncoderConfig {
+    accelerator: Accelerator,
+    av_codec_id: AVCodecID,
+    ffmpeg_encoder_name: CString,
+    profile: String,
+}
+
+impl ProbedEncoderConfig {
+    pub fn new(
+        accelerator: Accelerator,
+        codec: &VideoCodec,
+        ffmpeg_encoder_name: &str,
+        profile: &str,
+    ) -> CoreResult<Self> {
+        let av_codec_id = match codec {
+            VideoCodec::H264 => AV_CODEC_ID_H264,
+            VideoCodec::Hevc => AV_CODEC_ID_HEVC,
+            _ => return Err(core_error!("unsupported encode codec ({:?})", codec)),
+        };
+
+        Ok(ProbedEncoderConfig {
+            accelerator,
+            av_codec_id,
+            ffmpeg_encoder_name: CString::new(ffmpeg_encoder_name)?,
+            profile: profile.to_string(),
+        })
+    }
+
+    pub fn from_support(support: &CodecSupport) -> CoreResult<Self> {
+        Self::new(
+            support.accelerator,
+            &support.codec,
+            &support.ffmpeg_name,
+            support.profile.as_deref().unwrap_or("main"),
+        )
+    }
+}
+
+impl EncoderConfig for ProbedEncoderConfig {
+    fn apply_option(
+        &self,
+        codec_ctx: *mut AVCodecContext,
+        profile: SessionProfile,
+    ) -> CoreResult<()> {
+        if self.accelerator == Accelerator::Software {
+            return Libx264Config::default().apply_option(codec_ctx, profile);
+        }
+
+        set_codec_ctx_option(codec_ctx, "profile", &self.profile, 0)?;
+
+        // streaming leaves the encoders their default queue depth and lookahead
+        let interactive = profile == SessionProfile::Interactive;
+
+        match self.accelerator {
+            Accelerator::Nvidia => {
+                set_codec_ctx_option(codec_ctx, "preset", "p1", 0)?;
+                if interactive {
+                    set_codec_ctx_option(codec_ctx, "tune", "ull", 0)?;
+                    set_codec_ctx_option(codec_ctx, "zerolatency", "1", 0)?;
+                } else {
+                    set_codec_ctx_option(codec_ctx, "tune", "hq", 0)?;
+                }
+            }
+            Accelerator::Intel => {
+                set_codec_ctx_option(codec_ctx, "preset", "veryfast", 0)?;
+                if interactive {
+                    set_codec_ctx_option(codec_ctx, "async_depth", "1", 0)?;
+                }
+            }
+            Accelerator::Amd => {
+                let usage = if interactive {
+                    "ultralowlatency"
+                } else {
+                    "transcoding"
+                };
+                set_codec_ctx_option(codec_ctx, "usage", usage, 0)?;
+                set_codec_ctx_option(codec_ctx, "quality", "speed", 0)?;
+            }
+            Accelerator::VideoToolbox => {
+                if interactive {
+                    set_codec_ctx_option(codec_ctx, "realtime", "true", 0)?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn ffmpeg_encoder_name(&self) -> *const i8 {
+        self.ffmpeg_encoder_name.as_ptr()
+    }
+
+    fn av_codec_id(&self) -> AVCodecID {
+        self.av_codec_id
+    }
+}
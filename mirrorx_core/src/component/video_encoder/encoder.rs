@@ -2,32 +2,84 @@ use super::config::EncoderConfig;
 use crate::{
     api::endpoint::{
         client::EndPointClient,
-        message::{EndPointMessage, EndPointVideoFrame},
+        message::{EndPointVideoFrame, GopPolicy, SessionProfile},
+    },
+    component::{
+        clock_sync,
+        ffmpeg_safe::{CodecContext, Frame, Packet},
+        frame::{ColorPrimaries, ColorRange, ColorSpace, DesktopEncodeFrame},
     },
-    component::frame::DesktopEncodeFrame,
     core_error,
-    error::CoreResult,
+    error::{CoreError, CoreResult},
 };
 use mirrorx_native::ffmpeg::{
-    codecs::{avcodec::*, codec::*, packet::*},
-    utils::{error::*, frame::*, imgutils::*, log::*, pixfmt::*, rational::AVRational},
+    codecs::avcodec::*,
+    utils::{log::*, pixfmt::*, rational::AVRational},
+};
+use std::{
+    ffi::{CStr, CString},
+    sync::Arc,
+    time::Duration,
 };
-use std::sync::Arc;
 
-pub struct VideoEncoder<T>
+const DEFAULT_BIT_RATE: i64 = 4000 * 1000;
+
+const FRAME_RATE: i32 = 60;
+
+// key frames are only forced when the viewer asks for them
+const ON_DEMAND_GOP_SIZE: i32 = 4000;
+
+/// Where the encoded frames go, sessions send them to the remote endpoint and benchmarks
+/// decode them in place.
+pub trait VideoFrameSink {
+    fn send_video_frame(&self, frame: EndPointVideoFrame) -> CoreResult<()>;
+
+    /// Drop the frames which are sent but still queued when they fall too far behind,
+    /// true if they're dropped and the next frame should be a key frame.
+    fn shed_backlog(&self) -> bool {
+        false
+    }
+}
+
+impl VideoFrameSink for Arc<EndPointClient> {
+    fn send_video_frame(&self, frame: EndPointVideoFrame) -> CoreResult<()> {
+        self.blocking_send_video_frame(frame)
+    }
+
+    fn shed_backlog(&self) -> bool {
+        self.shed_video_backlog()
+    }
+}
+
+impl VideoFrameSink for std::sync::mpsc::Sender<EndPointVideoFrame> {
+    fn send_video_frame(&self, frame: EndPointVideoFrame) -> CoreResult<()> {
+        self.send(frame)
+            .map_err(|_| CoreError::OutgoingMessageChannelDisconnect)
+    }
+}
+
+pub struct VideoEncoder<T, S = Arc<EndPointClient>>
 where
     T: EncoderConfig,
+    S: VideoFrameSink,
 {
     encoder_config: T,
     encode_context: Option<EncodeContext>,
-    client: Arc<EndPointClient>,
+    sink: S,
+    max_frame_rate: Option<u8>,
+    last_capture_time: Option<Duration>,
+    bit_rate_scale: f32,
+    gop_policy: GopPolicy,
+    session_profile: SessionProfile,
+    force_key_frame: bool,
 }
 
-impl<T> VideoEncoder<T>
+impl<T, S> VideoEncoder<T, S>
 where
     T: EncoderConfig,
+    S: VideoFrameSink,
 {
-    pub fn new(encoder_config: T, client: Arc<EndPointClient>) -> CoreResult<VideoEncoder<T>> {
+    pub fn new(encoder_config: T, sink: S) -> CoreResult<VideoEncoder<T, S>> {
         unsafe {
             av_log_set_level(AV_LOG_INFO);
             av_log_set_flags(AV_LOG_SKIP_REPEATED);
@@ -36,192 +88,245 @@ where
         Ok(VideoEncoder {
             encoder_config,
             encode_context: None,
-            client,
+            sink,
+            max_frame_rate: None,
+            last_capture_time: None,
+            bit_rate_scale: 1.0,
+            gop_policy: GopPolicy::default(),
+            session_profile: SessionProfile::default(),
+            force_key_frame: false,
         })
     }
 
+    /// Start with the encode context which is created ahead of the session, it's
+    /// recreated as usual if the first frame doesn't match it. Contexts of other encoders
+    /// are dropped.
+    pub fn adopt_warm_context(&mut self, warm_context: WarmEncodeContext) {
+        let ffmpeg_encoder_name =
+            unsafe { CStr::from_ptr(self.encoder_config.ffmpeg_encoder_name()) };
+        if self.encode_context.is_none() && ffmpeg_encoder_name == warm_context.1.as_c_str() {
+            self.encode_context = Some(warm_context.0);
+        }
+    }
+
+    /// Drop the frames which exceed the max frame rate, None means unlimited.
+    pub fn set_max_frame_rate(&mut self, max_frame_rate: Option<u8>) {
+        self.max_frame_rate = max_frame_rate.filter(|frame_rate| *frame_rate > 0);
+    }
+
+    /// Scale the encoding bit rate, the encode context is recreated when the scale changes.
+    pub fn set_bit_rate_scale(&mut self, bit_rate_scale: f32) {
+        let bit_rate_scale = bit_rate_scale.clamp(0.1, 1.0);
+        if (self.bit_rate_scale - bit_rate_scale).abs() > f32::EPSILON {
+            self.bit_rate_scale = bit_rate_scale;
+            self.encode_context = None;
+        }
+    }
+
+    /// Set the key frames and B-frames to encode with, the encode context is recreated
+    /// when the policy changes.
+    pub fn set_gop_policy(&mut self, gop_policy: GopPolicy) {
+        self.gop_policy = gop_policy;
+    }
+
+    /// Tune the encoder for the session profile, the encode context is recreated when the
+    /// profile changes.
+    pub fn set_session_profile(&mut self, session_profile: SessionProfile) {
+        self.session_profile = session_profile;
+    }
+
+    /// Code the next encoded frame as a key frame, so the remote decoder recovers from
+    /// the frames it failed to decode.
+    pub fn force_key_frame(&mut self) {
+        self.force_key_frame = true;
+    }
+
     pub fn encode(&mut self, capture_frame: DesktopEncodeFrame) -> CoreResult<()> {
-        unsafe {
-            let mut ret: i32;
-
-            if let Some(ref encode_context) = self.encode_context {
-                if (*encode_context.codec_ctx).width != capture_frame.width
-                    || (*encode_context.codec_ctx).height != capture_frame.height
-                {
-                    self.encode_context = None;
-                }
+        if let (Some(max_frame_rate), Some(last_capture_time)) =
+            (self.max_frame_rate, self.last_capture_time)
+        {
+            let min_interval = Duration::from_secs(1) / max_frame_rate as u32;
+            if capture_frame.capture_time.saturating_sub(last_capture_time) < min_interval {
+                return Ok(());
             }
+        }
 
-            if self.encode_context.is_none() {
-                self.encode_context = Some(EncodeContext::new(
-                    capture_frame.width,
-                    capture_frame.height,
-                    &self.encoder_config,
-                )?);
+        self.last_capture_time = Some(capture_frame.capture_time);
+
+        if let Some(ref encode_context) = self.encode_context {
+            if encode_context.codec_ctx.width != capture_frame.width
+                || encode_context.codec_ctx.height != capture_frame.height
+                || encode_context.color_space != capture_frame.color_space
+                || encode_context.gop_policy != self.gop_policy
+                || encode_context.session_profile != self.session_profile
+            {
+                self.encode_context = None;
             }
+        }
 
-            let Some(ref encode_context)= self.encode_context else{
-                return Err(core_error!("encode context is empty"))
-            };
+        if self.encode_context.is_none() {
+            self.encode_context = Some(EncodeContext::new(
+                capture_frame.width,
+                capture_frame.height,
+                capture_frame.color_space,
+                (DEFAULT_BIT_RATE as f32 * self.bit_rate_scale) as i64,
+                self.gop_policy,
+                self.session_profile,
+                &self.encoder_config,
+            )?);
+        }
 
-            ret = av_frame_make_writable(encode_context.frame);
-            if ret < 0 {
-                return Err(core_error!(
-                    "av_frame_make_writable returns error code: {}",
-                    ret
-                ));
-            }
+        let Some(ref mut encode_context) = self.encode_context else {
+            return Err(core_error!("encode context is empty"));
+        };
 
-            (*(encode_context).frame).data[0] = capture_frame.luminance_bytes.as_ptr() as *mut _;
-            (*(encode_context).frame).linesize[0] = capture_frame.luminance_stride;
-            (*(encode_context).frame).data[1] = capture_frame.chrominance_bytes.as_ptr() as *mut _;
-            (*(encode_context).frame).linesize[1] = capture_frame.chrominance_stride;
-            (*(encode_context).frame).pts = (capture_frame.capture_time.as_secs_f64()
-                * ((*(encode_context).codec_ctx).time_base.den as f64))
-                as i64;
-
-            ret = avcodec_send_frame((encode_context).codec_ctx, (encode_context).frame);
-
-            if ret != 0 {
-                if ret == AVERROR(libc::EAGAIN) {
-                    return Err(core_error!("avcodec_send_frame returns EAGAIN"));
-                } else if ret == AVERROR_EOF {
-                    return Err(core_error!("avcodec_send_frame returns AVERROR_EOF"));
-                }
-                return Err(core_error!(
-                    "avcodec_send_frame returns error code: {}",
-                    ret
-                ));
-            }
+        // the previous frame may still be referenced by encoder
+        encode_context.frame.make_writable()?;
+        encode_context.frame.copy_plane_from(
+            0,
+            &capture_frame.luminance_bytes,
+            capture_frame.luminance_stride,
+        )?;
+        encode_context.frame.copy_plane_from(
+            1,
+            &capture_frame.chrominance_bytes,
+            capture_frame.chrominance_stride,
+        )?;
+        encode_context.frame.set_key_frame(self.force_key_frame);
+        self.force_key_frame = false;
+        encode_context.frame.set_pts(
+            (capture_frame.capture_time.as_secs_f64()
+                * (encode_context.codec_ctx.time_base.den as f64)) as i64,
+        );
 
-            loop {
-                ret = avcodec_receive_packet((encode_context).codec_ctx, (encode_context).packet);
-
-                if ret == AVERROR(libc::EAGAIN) || ret == AVERROR_EOF {
-                    return Ok(());
-                } else if ret < 0 {
-                    return Err(core_error!(
-                        "avcodec_receive_packet returns error code: {}",
-                        ret
-                    ));
-                }
-
-                let frame = EndPointVideoFrame {
-                    width: (*(encode_context).codec_ctx).width,
-                    height: (*(encode_context).codec_ctx).height,
-                    pts: (*(encode_context).packet).pts,
-                    buffer: std::slice::from_raw_parts(
-                        (*(encode_context).packet).data,
-                        (*(encode_context).packet).size as usize,
-                    )
-                    .to_vec(),
-                };
-
-                self.client
-                    .blocking_send(&EndPointMessage::VideoFrame(frame))?;
-
-                av_packet_unref((encode_context).packet);
+        encode_context.codec_ctx.send_frame(&encode_context.frame)?;
+
+        while encode_context
+            .codec_ctx
+            .receive_packet(&mut encode_context.packet)?
+        {
+            // the packet references the dropped frames, skip it until the key frame
+            if self.sink.shed_backlog() {
+                self.force_key_frame = true;
+                encode_context.packet.unref();
+                continue;
             }
+
+            let frame = EndPointVideoFrame {
+                width: encode_context.codec_ctx.width,
+                height: encode_context.codec_ctx.height,
+                pts: encode_context.packet.pts(),
+                buffer: encode_context.packet.data().to_vec(),
+                timestamp_micros: clock_sync::now_micros(),
+            };
+
+            self.sink.send_video_frame(frame)?;
+
+            encode_context.packet.unref();
         }
+
+        Ok(())
+    }
+}
+
+/// Encode context of the expected frame format which is created before the session
+/// starts, so the first frame doesn't wait for the codec to be opened.
+pub struct WarmEncodeContext(EncodeContext, CString);
+
+impl WarmEncodeContext {
+    pub fn new(
+        width: i32,
+        height: i32,
+        color_space: ColorSpace,
+        encoder_config: &dyn EncoderConfig,
+    ) -> CoreResult<WarmEncodeContext> {
+        let encode_context = EncodeContext::new(
+            width,
+            height,
+            color_space,
+            DEFAULT_BIT_RATE,
+            GopPolicy::default(),
+            SessionProfile::default(),
+            encoder_config,
+        )?;
+        let ffmpeg_encoder_name =
+            unsafe { CStr::from_ptr(encoder_config.ffmpeg_encoder_name()) }.to_owned();
+        Ok(WarmEncodeContext(encode_context, ffmpeg_encoder_name))
     }
 }
 
 struct EncodeContext {
-    codec_ctx: *mut AVCodecContext,
-    frame: *mut AVFrame,
-    packet: *mut AVPacket,
+    color_space: ColorSpace,
+    gop_policy: GopPolicy,
+    session_profile: SessionProfile,
+    codec_ctx: CodecContext,
+    frame: Frame,
+    packet: Packet,
 }
 
 impl EncodeContext {
     pub fn new(
         width: i32,
         height: i32,
+        color_space: ColorSpace,
+        bit_rate: i64,
+        gop_policy: GopPolicy,
+        session_profile: SessionProfile,
         encoder_config: &dyn EncoderConfig,
     ) -> CoreResult<EncodeContext> {
-        unsafe {
-            let codec = avcodec_find_encoder(encoder_config.av_codec_id());
-            if codec.is_null() {
-                return Err(core_error!("avcodec_find_encoder returns null pointer"));
-            }
+        let ffmpeg_encoder_name = unsafe { CStr::from_ptr(encoder_config.ffmpeg_encoder_name()) };
+        let mut codec_ctx = CodecContext::new_encoder(ffmpeg_encoder_name)?;
 
-            let encoder_context = EncodeContext {
-                codec_ctx: avcodec_alloc_context3(codec),
-                frame: av_frame_alloc(),
-                packet: av_packet_alloc(),
+        codec_ctx.configure(|codec_ctx| {
+            codec_ctx.width = width;
+            codec_ctx.height = height;
+            codec_ctx.framerate = AVRational {
+                num: FRAME_RATE,
+                den: 1,
             };
+            codec_ctx.time_base = AVRational {
+                num: 1,
+                den: FRAME_RATE,
+            };
+            codec_ctx.gop_size = match gop_policy.key_frame_interval_secs {
+                0 => ON_DEMAND_GOP_SIZE,
+                secs => secs as i32 * FRAME_RATE,
+            };
+            codec_ctx.bit_rate = bit_rate;
+            codec_ctx.rc_max_rate = bit_rate;
+            codec_ctx.rc_min_rate = bit_rate;
+            codec_ctx.rc_buffer_size = (bit_rate * 2) as i32;
+            codec_ctx.has_b_frames = gop_policy.b_frames as i32;
+            codec_ctx.max_b_frames = gop_policy.b_frames as i32;
+            codec_ctx.pix_fmt = AV_PIX_FMT_NV12;
+            codec_ctx.flags2 |= AV_CODEC_FLAG2_LOCAL_HEADER;
+            // signal color space in bitstream, so the decoder side can convert it correctly
+            codec_ctx.color_range = match color_space.range {
+                ColorRange::Full => AVCOL_RANGE_JPEG,
+                ColorRange::Limited => AVCOL_RANGE_MPEG,
+            };
+            codec_ctx.color_primaries = match color_space.primaries {
+                ColorPrimaries::Bt709 => AVCOL_PRI_BT709,
+                ColorPrimaries::DisplayP3 => AVCOL_PRI_SMPTE432,
+            };
+            codec_ctx.color_trc = AVCOL_TRC_BT709;
+            codec_ctx.colorspace = AVCOL_SPC_BT709;
 
-            if encoder_context.codec_ctx.is_null()
-                || encoder_context.frame.is_null()
-                || encoder_context.packet.is_null()
-            {
-                return Err(core_error!("avcodec_alloc_context3 returns null pointer"));
-            }
-
-            (*encoder_context.codec_ctx).width = width;
-            (*encoder_context.codec_ctx).height = height;
-            (*encoder_context.codec_ctx).framerate = AVRational { num: 60, den: 1 };
-            (*encoder_context.codec_ctx).time_base = AVRational { num: 1, den: 60 };
-            (*encoder_context.codec_ctx).gop_size = 4000;
-            (*encoder_context.codec_ctx).bit_rate = 4000 * 1000;
-            (*encoder_context.codec_ctx).rc_max_rate = 4000 * 1000;
-            (*encoder_context.codec_ctx).rc_min_rate = 4000 * 1000;
-            (*encoder_context.codec_ctx).rc_buffer_size = 4000 * 1000 * 2;
-            (*encoder_context.codec_ctx).has_b_frames = 0;
-            (*encoder_context.codec_ctx).max_b_frames = 0;
-            (*encoder_context.codec_ctx).pix_fmt = AV_PIX_FMT_NV12;
-            (*encoder_context.codec_ctx).flags2 |= AV_CODEC_FLAG2_LOCAL_HEADER;
-            (*encoder_context.codec_ctx).color_range = AVCOL_RANGE_JPEG;
-            (*encoder_context.codec_ctx).color_primaries = AVCOL_PRI_BT709;
-            (*encoder_context.codec_ctx).color_trc = AVCOL_TRC_BT709;
-            (*encoder_context.codec_ctx).colorspace = AVCOL_SPC_BT709;
-
-            (*encoder_context.frame).format = (*encoder_context.codec_ctx).pix_fmt;
-            (*encoder_context.frame).width = width;
-            (*encoder_context.frame).height = height;
-
-            encoder_config.apply_option(encoder_context.codec_ctx)?;
-
-            let mut ret = av_frame_get_buffer(encoder_context.frame, 0);
-            if ret < 0 {
-                return Err(core_error!(
-                    "av_frame_get_buffer returns error code: {}",
-                    ret
-                ));
-            }
-
-            let packet_size =
-                av_image_get_buffer_size((*encoder_context.codec_ctx).pix_fmt, width, height, 1);
-
-            ret = av_new_packet(encoder_context.packet, packet_size);
-            if ret < 0 {
-                return Err(core_error!("av_new_packet returns error code: {}", ret));
-            }
-
-            let ret = avcodec_open2(encoder_context.codec_ctx, codec, std::ptr::null_mut());
-            if ret != 0 {
-                return Err(core_error!("avcodec_open2 returns null pointer"));
-            }
-
-            Ok(encoder_context)
-        }
-    }
-}
+            encoder_config.apply_option(codec_ctx, session_profile)
+        })?;
 
-impl Drop for EncodeContext {
-    fn drop(&mut self) {
-        unsafe {
-            if !self.codec_ctx.is_null() {
-                avcodec_send_frame(self.codec_ctx, std::ptr::null_mut());
-                avcodec_free_context(&mut self.codec_ctx);
-            }
+        codec_ctx.open()?;
 
-            if !self.frame.is_null() {
-                av_frame_free(&mut self.frame);
-            }
+        let mut frame = Frame::new()?;
+        frame.alloc_video_buffer(AV_PIX_FMT_NV12, width, height)?;
 
-            if !self.packet.is_null() {
-                av_packet_free(&mut self.packet);
-            }
-        }
+        Ok(EncodeContext {
+            color_space,
+            gop_policy,
+            session_profile,
+            codec_ctx,
+            frame,
+            packet: Packet::new()?,
+        })
     }
 }
@@ -0,0 +1,67 @@
+//! Filters captured frames before they're encoded, the savings modes of stream quality
+//! leave less detail for the encoder so it fits the bitrate of slow links.
+
+use crate::{api::endpoint::message::StreamQuality, component::frame::DesktopEncodeFrame};
+
+// chroma samples of NV12 cover 2x2 pixels, so a block of 4x4 samples covers 8x8 pixels
+const CHROMA_BLOCK_SIZE: usize = 4;
+
+const NEUTRAL_CHROMA: u8 = 128;
+
+impl StreamQuality {
+    pub fn max_frame_rate(self) -> Option<u8> {
+        match self {
+            StreamQuality::Full => None,
+            StreamQuality::ReducedChroma | StreamQuality::Grayscale => Some(10),
+        }
+    }
+}
+
+pub fn filter_frame(frame: &mut DesktopEncodeFrame, quality: StreamQuality) {
+    match quality {
+        StreamQuality::Full => {}
+        StreamQuality::ReducedChroma => subsample_chroma(frame),
+        StreamQuality::Grayscale => frame.chrominance_bytes.fill(NEUTRAL_CHROMA),
+    }
+}
+
+// average the interleaved UV samples of NV12 over every block, flat blocks cost the
+// encoder almost nothing
+fn subsample_chroma(frame: &mut DesktopEncodeFrame) {
+    let stride = frame.chrominance_stride as usize;
+    let columns = (frame.width as usize + 1) / 2;
+    let rows = (frame.height as usize + 1) / 2;
+    let bytes = &mut frame.chrominance_bytes;
+
+    if stride < columns * 2 || bytes.len() < rows * stride {
+        tracing::warn!("unexpected chrominance layout, skip chroma subsampling");
+        return;
+    }
+
+    for block_row in (0..rows).step_by(CHROMA_BLOCK_SIZE) {
+        let block_rows = block_row..(block_row + CHROMA_BLOCK_SIZE).min(rows);
+
+        for block_column in (0..columns).step_by(CHROMA_BLOCK_SIZE) {
+            let block_columns = block_column..(block_column + CHROMA_BLOCK_SIZE).min(columns);
+
+            let (mut u, mut v, mut count) = (0u32, 0u32, 0u32);
+            for row in block_rows.clone() {
+                for column in block_columns.clone() {
+                    let offset = row * stride + column * 2;
+                    u += bytes[offset] as u32;
+                    v += bytes[offset + 1] as u32;
+                    count += 1;
+                }
+            }
+
+            let (u, v) = ((u / count) as u8, (v / count) as u8);
+            for row in block_rows.clone() {
+                for column in block_columns.clone() {
+                    let offset = row * stride + column * 2;
+                    bytes[offset] = u;
+                    bytes[offset + 1] = v;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! The magnified region is streamed in the tile codec besides the desktop. Tiles are
+//! lossless, so small text stays readable, and they don't take a session of hardware
+//! encoder which may only allow a few of them.
+
+use super::{encoder::VideoFrameSink, tile::TileEncoder};
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{EndPointMagnifierFrame, EndPointMessage, EndPointVideoFrame, MagnifierRegion},
+    },
+    component::frame::DesktopEncodeFrame,
+    core_error,
+    error::CoreResult,
+};
+use std::sync::Arc;
+
+// reading doesn't need the frame rate of desktop
+const MAGNIFIER_MAX_FRAME_RATE: u8 = 15;
+
+// regions smaller than a tile aren't worth a stream
+const MIN_REGION_SIZE: u16 = 64;
+
+/// Clamp the region into the monitor of the size and align it to even pixels, None when
+/// too little of it is left.
+pub fn clamp_region(region: MagnifierRegion, width: u16, height: u16) -> Option<MagnifierRegion> {
+    let x = region.x.min(width) & !1;
+    let y = region.y.min(height) & !1;
+    let region_width = region.width.min(width - x) & !1;
+    let region_height = region.height.min(height - y) & !1;
+
+    if region_width < MIN_REGION_SIZE || region_height < MIN_REGION_SIZE {
+        return None;
+    }
+
+    Some(MagnifierRegion {
+        x,
+        y,
+        width: region_width,
+        height: region_height,
+    })
+}
+
+struct MagnifierSink {
+    client: Arc<EndPointClient>,
+    region: MagnifierRegion,
+}
+
+impl VideoFrameSink for MagnifierSink {
+    fn send_video_frame(&self, frame: EndPointVideoFrame) -> CoreResult<()> {
+        self.client
+            .blocking_send(&EndPointMessage::MagnifierFrame(EndPointMagnifierFrame {
+                region: self.region,
+                frame,
+            }))
+    }
+}
+
+pub struct Magnifier {
+    client: Arc<EndPointClient>,
+    // encoder starts over with a key frame when the region changes
+    encoder: Option<(MagnifierRegion, TileEncoder<MagnifierSink>)>,
+}
+
+impl Magnifier {
+    pub fn new(client: Arc<EndPointClient>) -> Self {
+        Magnifier {
+            client,
+            encoder: None,
+        }
+    }
+
+    pub fn force_key_frame(&mut self) {
+        if let Some((_, encoder)) = self.encoder.as_mut() {
+            encoder.force_key_frame();
+        }
+    }
+
+    /// Encode the region of the frame which active endpoint asked for, if any.
+    pub fn encode(&mut self, frame: &DesktopEncodeFrame) -> CoreResult<()> {
+        let Some(region) = self
+            .client
+            .magnifier_region()
+            .and_then(|region| clamp_region(region, frame.width as u16, frame.height as u16))
+        else {
+            self.encoder = None;
+            return Ok(());
+        };
+
+        if self.encoder.as_ref().map(|(current, _)| *current) != Some(region) {
+            let mut encoder = TileEncoder::new(MagnifierSink {
+                client: self.client.clone(),
+                region,
+            });
+            encoder.set_max_frame_rate(Some(MAGNIFIER_MAX_FRAME_RATE));
+            self.encoder = Some((region, encoder));
+        }
+
+        let Some((_, encoder)) = self.encoder.as_mut() else {
+            return Ok(());
+        };
+
+        encoder.encode(crop_frame(frame, region)?)
+    }
+}
+
+fn crop_frame(
+    frame: &DesktopEncodeFrame,
+    region: MagnifierRegion,
+) -> CoreResult<DesktopEncodeFrame> {
+    let x = region.x as usize;
+    let y = region.y as usize;
+    let width = region.width as usize;
+    let height = region.height as usize;
+
+    let mut luminance_bytes = Vec::with_capacity(width * height);
+    let luminance_stride = frame.luminance_stride as usize;
+    for row in y..y + height {
+        let offset = row * luminance_stride + x;
+        luminance_bytes.extend_from_slice(
+            frame
+                .luminance_bytes
+                .get(offset..offset + width)
+                .ok_or(core_error!("luminance plane is smaller than frame"))?,
+        );
+    }
+
+    // interleaved UV of half width and height, the even offset keeps the pairs whole
+    let mut chrominance_bytes = Vec::with_capacity(width * height / 2);
+    let chrominance_stride = frame.chrominance_stride as usize;
+    for row in y / 2..(y + height) / 2 {
+        let offset = row * chrominance_stride + x;
+        chrominance_bytes.extend_from_slice(
+            frame
+                .chrominance_bytes
+                .get(offset..offset + width)
+                .ok_or(core_error!("chrominance plane is smaller than frame"))?,
+        );
+    }
+
+    Ok(DesktopEncodeFrame {
+        capture_time: frame.capture_time,
+        color_space: frame.color_space,
+        width: region.width as i32,
+        height: region.height as i32,
+        luminance_bytes,
+        luminance_stride: region.width as i32,
+        chrominance_bytes,
+        chrominance_stride: region.width as i32,
+    })
+}
@@ -1,2 +1,6 @@
 pub mod config;
 pub mod encoder;
+pub mod filter;
+pub mod magnifier;
+pub mod tile;
+pub mod watermark;
@@ -0,0 +1,176 @@
+use super::encoder::VideoFrameSink;
+use crate::{
+    api::endpoint::{client::EndPointClient, message::EndPointVideoFrame},
+    component::{
+        clock_sync,
+        frame::DesktopEncodeFrame,
+        tile_cache::{
+            tile_count, tile_hash, tile_rects, TileCache, TileContent, TileFrame, TileRect,
+            TileUpdate,
+        },
+    },
+    core_error,
+    error::CoreResult,
+    utility::bincode::bincode_serialize,
+};
+use std::{sync::Arc, time::Duration};
+
+// tiles are small, the fastest level compresses text and flat UI well enough
+const COMPRESS_LEVEL: i32 = 1;
+
+/// Encoder of the tile codec, see `tile_cache`. Tiles are lossless, so the size of the
+/// stream depends on how much of the desktop changes rather than on a bit rate.
+pub struct TileEncoder<S = Arc<EndPointClient>>
+where
+    S: VideoFrameSink,
+{
+    sink: S,
+    size: (i32, i32),
+    cache: TileCache<()>,
+    // hash of every tile of the previous frame
+    previous_hashes: Vec<u64>,
+    tile_buffer: Vec<u8>,
+    max_frame_rate: Option<u8>,
+    last_capture_time: Option<Duration>,
+    force_key_frame: bool,
+}
+
+impl<S> TileEncoder<S>
+where
+    S: VideoFrameSink,
+{
+    pub fn new(sink: S) -> TileEncoder<S> {
+        TileEncoder {
+            sink,
+            size: (0, 0),
+            cache: TileCache::new(),
+            previous_hashes: Vec::new(),
+            tile_buffer: Vec::new(),
+            max_frame_rate: None,
+            last_capture_time: None,
+            force_key_frame: true,
+        }
+    }
+
+    /// Drop the frames which exceed the max frame rate, None means unlimited.
+    pub fn set_max_frame_rate(&mut self, max_frame_rate: Option<u8>) {
+        self.max_frame_rate = max_frame_rate.filter(|frame_rate| *frame_rate > 0);
+    }
+
+    /// Send every tile with the next frame, so the remote decoder recovers from the
+    /// frames it failed to decode.
+    pub fn force_key_frame(&mut self) {
+        self.force_key_frame = true;
+    }
+
+    pub fn encode(&mut self, capture_frame: DesktopEncodeFrame) -> CoreResult<()> {
+        if let (Some(max_frame_rate), Some(last_capture_time)) =
+            (self.max_frame_rate, self.last_capture_time)
+        {
+            let min_interval = Duration::from_secs(1) / max_frame_rate as u32;
+            if capture_frame.capture_time.saturating_sub(last_capture_time) < min_interval {
+                return Ok(());
+            }
+        }
+
+        self.last_capture_time = Some(capture_frame.capture_time);
+
+        if self.size != (capture_frame.width, capture_frame.height) {
+            self.size = (capture_frame.width, capture_frame.height);
+            self.force_key_frame = true;
+        }
+
+        // the dropped frames carried tiles which the decoder never cached
+        if self.sink.shed_backlog() {
+            self.force_key_frame = true;
+            return Ok(());
+        }
+
+        let key_frame = self.force_key_frame;
+        self.force_key_frame = false;
+
+        let width = capture_frame.width as usize;
+        let height = capture_frame.height as usize;
+
+        if key_frame {
+            self.cache.clear();
+            self.previous_hashes.clear();
+            self.previous_hashes.resize(tile_count(width, height), 0);
+        }
+
+        let mut updates = Vec::new();
+
+        for (index, rect) in tile_rects(width, height).enumerate() {
+            copy_tile(&capture_frame, rect, &mut self.tile_buffer)?;
+            let hash = tile_hash(&self.tile_buffer);
+
+            if !key_frame && self.previous_hashes[index] == hash {
+                continue;
+            }
+
+            self.previous_hashes[index] = hash;
+
+            let content = if self.cache.contains(hash) {
+                TileContent::Cached(hash)
+            } else {
+                self.cache.insert(hash, ());
+                TileContent::Pixels {
+                    hash,
+                    data: zstd::bulk::compress(&self.tile_buffer, COMPRESS_LEVEL)?,
+                }
+            };
+
+            updates.push(TileUpdate {
+                index: index as u32,
+                content,
+            });
+        }
+
+        // the desktop is still, the decoder shows the same frame
+        if updates.is_empty() {
+            return Ok(());
+        }
+
+        let tile_frame = TileFrame {
+            key_frame,
+            color_space: capture_frame.color_space,
+            updates,
+        };
+
+        self.sink.send_video_frame(EndPointVideoFrame {
+            width: capture_frame.width,
+            height: capture_frame.height,
+            pts: capture_frame.capture_time.as_micros() as i64,
+            buffer: bincode_serialize(&tile_frame)?,
+            timestamp_micros: clock_sync::now_micros(),
+        })
+    }
+}
+
+// luminance rows of the tile followed by its chrominance rows, without stride padding
+fn copy_tile(frame: &DesktopEncodeFrame, rect: TileRect, buffer: &mut Vec<u8>) -> CoreResult<()> {
+    buffer.clear();
+
+    let luminance_stride = frame.luminance_stride as usize;
+    for row in rect.y..rect.y + rect.height {
+        let offset = row * luminance_stride + rect.x;
+        let bytes = frame
+            .luminance_bytes
+            .get(offset..offset + rect.width)
+            .ok_or(core_error!("luminance plane is smaller than frame"))?;
+        buffer.extend_from_slice(bytes);
+    }
+
+    // chrominance is interleaved UV of half width, so the tile spans the same bytes
+    let chrominance_stride = frame.chrominance_stride as usize;
+    for row in rect.chrominance_rows() {
+        let offset = row * chrominance_stride + rect.x;
+        let bytes = frame
+            .chrominance_bytes
+            .get(offset..offset + rect.width)
+            .ok_or(core_error!("chrominance plane is smaller than frame"))?;
+        buffer.extend_from_slice(bytes);
+    }
+
+    Ok(())
+}
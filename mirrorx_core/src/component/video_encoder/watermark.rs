@@ -0,0 +1,183 @@
+//! Watermark stamps the device id of the viewer and the time into captured frames before
+//! they're encoded, so screenshots and recordings leaked by the viewer can be traced back
+//! to the session. It's tiled over the whole frame and drawn semi-transparently into the
+//! luminance plane only, the desktop stays readable under it.
+
+use crate::{api::endpoint::id::EndPointID, component::frame::DesktopEncodeFrame};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub(crate) const GLYPH_WIDTH: usize = 5;
+pub(crate) const GLYPH_HEIGHT: usize = 7;
+
+// one blank column and two blank rows between glyphs and lines
+const GLYPH_ADVANCE: usize = GLYPH_WIDTH + 1;
+const LINE_ADVANCE: usize = GLYPH_HEIGHT + 2;
+
+// stamps are spread this many times their size apart
+const TILE_SPACING: usize = 2;
+
+// out of 8, how much of the stamped pixels is taken by the watermark
+const OPACITY: u16 = 3;
+
+static WATERMARK_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Set whether sessions negotiated from now on stream watermarked desktop.
+pub fn set_watermark_enabled(enabled: bool) {
+    WATERMARK_ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn watermark_enabled() -> bool {
+    WATERMARK_ENABLED.load(Ordering::SeqCst)
+}
+
+pub struct Watermark {
+    viewer: String,
+    // the second which the mask is rendered at
+    timestamp: i64,
+    // glyph pixels of the stamp, row by row
+    mask: Vec<bool>,
+    mask_width: usize,
+    mask_height: usize,
+}
+
+impl Watermark {
+    pub fn new(endpoint_id: &EndPointID) -> Self {
+        let viewer = match endpoint_id {
+            EndPointID::DeviceID {
+                remote_device_id, ..
+            } => {
+                let device_id = format!("{remote_device_id:010}");
+                format!(
+                    "{}-{}-{}",
+                    &device_id[..2],
+                    &device_id[2..6],
+                    &device_id[6..]
+                )
+            }
+            EndPointID::LANID { remote_ip, .. } => remote_ip.to_string(),
+        };
+
+        Self {
+            viewer,
+            timestamp: i64::MIN,
+            mask: Vec::new(),
+            mask_width: 0,
+            mask_height: 0,
+        }
+    }
+
+    /// Stamp the frame, the time in it is refreshed every second.
+    pub fn apply(&mut self, frame: &mut DesktopEncodeFrame) {
+        let now = chrono::Local::now();
+        if now.timestamp() != self.timestamp {
+            self.timestamp = now.timestamp();
+            self.render(&now.format("%Y-%m-%d %H:%M:%S").to_string());
+        }
+
+        let width = frame.width as usize;
+        let height = frame.height as usize;
+        let stride = frame.luminance_stride as usize;
+
+        if stride < width || frame.luminance_bytes.len() < height * stride {
+            tracing::warn!("unexpected luminance layout, skip watermark");
+            return;
+        }
+
+        // about the height of small UI text on 1080p
+        let scale = (height / 360).max(1);
+        let stamp_width = self.mask_width * scale;
+        let stamp_height = self.mask_height * scale;
+        if stamp_width == 0 || stamp_height == 0 {
+            return;
+        }
+
+        let tile_width = stamp_width * TILE_SPACING;
+        let tile_height = stamp_height * TILE_SPACING * 2;
+
+        // rows of stamps are staggered so they don't line up into columns
+        for (tile_row, top) in (0..height).step_by(tile_height).enumerate() {
+            let offset = if tile_row % 2 == 0 { 0 } else { tile_width / 2 };
+
+            for left in (offset..width).step_by(tile_width) {
+                self.stamp(frame, left, top, scale);
+            }
+        }
+    }
+
+    fn render(&mut self, time: &str) {
+        let lines = [self.viewer.as_str(), time];
+        let columns = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+        self.mask_width = columns * GLYPH_ADVANCE;
+        self.mask_height = lines.len() * LINE_ADVANCE;
+        self.mask.clear();
+        self.mask.resize(self.mask_width * self.mask_height, false);
+
+        for (line_index, line) in lines.iter().enumerate() {
+            for (char_index, c) in line.chars().enumerate() {
+                let glyph = glyph(c);
+
+                for (glyph_row, bits) in glyph.iter().enumerate() {
+                    for glyph_column in 0..GLYPH_WIDTH {
+                        if bits & (1 << (GLYPH_WIDTH - 1 - glyph_column)) == 0 {
+                            continue;
+                        }
+
+                        let x = char_index * GLYPH_ADVANCE + glyph_column;
+                        let y = line_index * LINE_ADVANCE + glyph_row;
+                        self.mask[y * self.mask_width + x] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn stamp(&self, frame: &mut DesktopEncodeFrame, left: usize, top: usize, scale: usize) {
+        let width = frame.width as usize;
+        let height = frame.height as usize;
+        let stride = frame.luminance_stride as usize;
+
+        for y in top..(top + self.mask_height * scale).min(height) {
+            let mask_row = &self.mask[((y - top) / scale) * self.mask_width..];
+            let row = &mut frame.luminance_bytes[y * stride..];
+
+            for x in left..(left + self.mask_width * scale).min(width) {
+                if !mask_row[(x - left) / scale] {
+                    continue;
+                }
+
+                // bright text on dark desktop and dark text on bright desktop, so it
+                // shows up on both
+                let luminance = row[x] as u16;
+                let target = if luminance < 128 { 235 } else { 16 };
+                row[x] = ((luminance * (8 - OPACITY) + target * OPACITY) / 8) as u8;
+            }
+        }
+    }
+}
+
+// 5x7 glyphs of the characters which device ids, addresses and times consist of, rows
+// from top to bottom with the leftmost pixel in the highest bit
+pub(crate) fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c {
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        'a' => [0x00, 0x00, 0x0e, 0x01, 0x0f, 0x11, 0x0f],
+        'b' => [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1e],
+        'c' => [0x00, 0x00, 0x0e, 0x10, 0x10, 0x11, 0x0e],
+        'd' => [0x01, 0x01, 0x0d, 0x13, 0x11, 0x11, 0x0f],
+        'e' => [0x00, 0x00, 0x0e, 0x11, 0x1f, 0x10, 0x0e],
+        'f' => [0x06, 0x09, 0x08, 0x1c, 0x08, 0x08, 0x08],
+        '-' => [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00],
+        ':' => [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        _ => [0x00; GLYPH_HEIGHT],
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{
+    api::endpoint::id::EndPointID,
+    component::progress::{ProgressKind, ProgressTracker},
+    core_error,
+    error::CoreResult,
+};
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+// NAL unit types of H.264 which a playable stream can start from
+const NAL_UNIT_TYPE_IDR: u8 = 5;
+const NAL_UNIT_TYPE_SPS: u8 = 7;
+
+static RECORDINGS: Lazy<Mutex<HashMap<EndPointID, Recording>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct Recording {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    // frames before the first key frame can't be decoded, they're skipped
+    key_frame_received: bool,
+    // recorded bytes, the recording has no total so only throughput is reported
+    progress: ProgressTracker,
+}
+
+/// Start recording the received video of session into `path` as raw H.264 stream,
+/// which can be played or remuxed by common tools like ffmpeg.
+pub fn start_recording(endpoint_id: EndPointID, path: &Path) -> CoreResult<()> {
+    let mut recordings = RECORDINGS.lock().unwrap();
+    if recordings.contains_key(&endpoint_id) {
+        return Err(core_error!("session is already recording"));
+    }
+
+    let file = File::create(path)?;
+
+    recordings.insert(
+        endpoint_id,
+        Recording {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            key_frame_received: false,
+            progress: ProgressTracker::new(
+                recording_operation_id(&endpoint_id),
+                ProgressKind::Recording,
+                None,
+                0,
+            ),
+        },
+    );
+
+    tracing::info!(?endpoint_id, ?path, "start recording");
+
+    Ok(())
+}
+
+/// Stop recording the session and returns the path of recorded file.
+pub fn stop_recording(endpoint_id: &EndPointID) -> CoreResult<PathBuf> {
+    let Some(mut recording) = RECORDINGS.lock().unwrap().remove(endpoint_id) else {
+        return Err(core_error!("session is not recording"));
+    };
+
+    let result = recording.writer.flush();
+    recording.progress.finish(result.is_ok());
+    result?;
+
+    tracing::info!(?endpoint_id, path = ?recording.path, "stop recording");
+
+    Ok(recording.path)
+}
+
+pub fn is_recording(endpoint_id: &EndPointID) -> bool {
+    RECORDINGS.lock().unwrap().contains_key(endpoint_id)
+}
+
+pub(crate) fn write_video_frame(endpoint_id: &EndPointID, buffer: &[u8]) {
+    let mut recordings = RECORDINGS.lock().unwrap();
+    let Some(recording) = recordings.get_mut(endpoint_id) else {
+        return;
+    };
+
+    if !recording.key_frame_received {
+        if !is_key_frame(buffer) {
+            return;
+        }
+
+        recording.key_frame_received = true;
+    }
+
+    if let Err(err) = recording.writer.write_all(buffer) {
+        tracing::error!(?endpoint_id, ?err, "write recording failed, stop recording");
+        recordings.remove(endpoint_id);
+        return;
+    }
+
+    recording.progress.advance(buffer.len() as _);
+}
+
+/// Operation id which progress of the session recording is reported under.
+pub fn recording_operation_id(endpoint_id: &EndPointID) -> String {
+    format!("recording/{endpoint_id}")
+}
+
+/// Stop the recording when session ends, so the file is flushed.
+pub(crate) fn finish_recording(endpoint_id: &EndPointID) {
+    if is_recording(endpoint_id) {
+        if let Err(err) = stop_recording(endpoint_id) {
+            tracing::error!(?endpoint_id, ?err, "finish recording failed");
+        }
+    }
+}
+
+fn is_key_frame(buffer: &[u8]) -> bool {
+    // scan the annex-b start codes, both 3 and 4 bytes start codes end with 0x000001
+    buffer.windows(4).any(|window| {
+        window[..3] == [0, 0, 1]
+            && matches!(window[3] & 0x1F, NAL_UNIT_TYPE_IDR | NAL_UNIT_TYPE_SPS)
+    })
+}
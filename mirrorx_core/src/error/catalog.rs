@@ -0,0 +1,263 @@
+//! Stable keys of errors, so they can be translated instead of showing the message which
+//! is written for logs. Keys never change once they're released, the catalog lists them
+//! with their params and the English message for clients to build translations from.
+
+use super::CoreError;
+use crate::api::signaling::{
+    http_message::HttpError,
+    subscribe_message::{RejectReason, VisitFailureReason},
+};
+use serde::Serialize;
+use std::{collections::BTreeMap, error::Error};
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ErrorKeyEntry {
+    pub key: &'static str,
+    pub params: &'static [&'static str],
+    /// The English message with params in braces, like `I/O error: {detail}`.
+    pub message: &'static str,
+}
+
+const fn entry(
+    key: &'static str,
+    params: &'static [&'static str],
+    message: &'static str,
+) -> ErrorKeyEntry {
+    ErrorKeyEntry {
+        key,
+        params,
+        message,
+    }
+}
+
+const DETAIL: &[&str] = &["detail"];
+
+// text which host user left for rejected visitors, it's empty when there's none
+const REJECT_MESSAGE: &[&str] = &["message"];
+
+static ERROR_CATALOG: &[ErrorKeyEntry] = &[
+    entry("core.other", &["message"], "{message}"),
+    entry(
+        "core.outgoing_channel_full",
+        &[],
+        "Too many messages are waiting to be sent",
+    ),
+    entry(
+        "core.outgoing_channel_disconnected",
+        &[],
+        "The session has been closed",
+    ),
+    entry("core.io", DETAIL, "I/O error: {detail}"),
+    entry("core.cstring", DETAIL, "Invalid string: {detail}"),
+    entry("core.parse_int", DETAIL, "Invalid number: {detail}"),
+    entry("core.sqlite", DETAIL, "Database error: {detail}"),
+    entry("core.timeout", &[], "Operation timed out"),
+    entry("core.canceled", &[], "Operation canceled"),
+    entry("core.oneshot_receive", DETAIL, "Internal error: {detail}"),
+    entry("core.bincode", DETAIL, "Invalid message: {detail}"),
+    entry("core.cbor_serialize", DETAIL, "Invalid message: {detail}"),
+    entry("core.cbor_deserialize", DETAIL, "Invalid message: {detail}"),
+    entry("core.rsa", DETAIL, "Encryption error: {detail}"),
+    entry("core.ring", DETAIL, "Encryption error: {detail}"),
+    entry("core.http", DETAIL, "Network request failed: {detail}"),
+    entry("core.url", DETAIL, "Invalid address: {detail}"),
+    entry("core.json", DETAIL, "Invalid data: {detail}"),
+    entry("core.base64", DETAIL, "Invalid data: {detail}"),
+    entry("core.windows_api", DETAIL, "System error: {detail}"),
+    entry("core.utf8", DETAIL, "Invalid text: {detail}"),
+    entry("core.utf16", DETAIL, "Invalid text: {detail}"),
+    entry("core.audio_devices", DETAIL, "Audio device error: {detail}"),
+    entry(
+        "core.audio_build_stream",
+        DETAIL,
+        "Audio device error: {detail}",
+    ),
+    entry(
+        "core.audio_play_stream",
+        DETAIL,
+        "Audio device error: {detail}",
+    ),
+    entry(
+        "core.audio_default_config",
+        DETAIL,
+        "Audio device error: {detail}",
+    ),
+    entry(
+        "core.audio_supported_configs",
+        DETAIL,
+        "Audio device error: {detail}",
+    ),
+    entry("core.storage_pool", DETAIL, "Database error: {detail}"),
+    entry("core.convert", DETAIL, "Internal error: {detail}"),
+    entry("core.image", DETAIL, "Image error: {detail}"),
+    entry(
+        "core.network_interfaces",
+        DETAIL,
+        "Network interface error: {detail}",
+    ),
+    entry("core.zip", DETAIL, "Archive error: {detail}"),
+    entry("core.load_library", DETAIL, "Load library failed: {detail}"),
+    entry(
+        "core.audio_processing",
+        DETAIL,
+        "Audio processing error: {detail}",
+    ),
+    entry("visit.remote_reject", &[], "Remote user rejected the visit"),
+    entry("visit.invalid_password", &[], "Incorrect password"),
+    entry("visit.internal_error", &[], "Remote device internal error"),
+    entry(
+        "visit.invalid_args",
+        &[],
+        "Invalid request args used at key exchange",
+    ),
+    entry(
+        "visit.remote_unavailable",
+        &[],
+        "Remote user is busy or in do not disturb",
+    ),
+    entry(
+        "visit.queue_timeout",
+        &[],
+        "Waited too long in the queue of remote device",
+    ),
+    entry(
+        "visit.acl_denied",
+        &[],
+        "Denied by access control list of remote device",
+    ),
+    entry(
+        "visit.incompatible_protocol",
+        &[],
+        "Remote device runs an incompatible release, update both devices",
+    ),
+    entry(
+        "visit.rejected_by_user",
+        REJECT_MESSAGE,
+        "Remote user rejected the visit. {message}",
+    ),
+    entry(
+        "visit.rejected_do_not_disturb",
+        REJECT_MESSAGE,
+        "Remote user doesn't want to be disturbed. {message}",
+    ),
+    entry(
+        "visit.rejected_by_acl",
+        REJECT_MESSAGE,
+        "Denied by access control list of remote device. {message}",
+    ),
+    entry(
+        "visit.rejected_by_schedule",
+        REJECT_MESSAGE,
+        "Remote device doesn't accept visits at this time. {message}",
+    ),
+    entry("signaling.internal", &[], "Signaling server internal error"),
+    entry("signaling.timeout", &[], "Signaling server timed out"),
+    entry(
+        "signaling.invalid_args",
+        &[],
+        "Invalid request to signaling server",
+    ),
+    entry(
+        "signaling.resource_exhausted",
+        &[],
+        "Signaling server is busy",
+    ),
+    entry("signaling.remote_offline", &[], "Remote device is offline"),
+];
+
+/// Every error key with its params and English message.
+pub fn error_catalog() -> &'static [ErrorKeyEntry] {
+    ERROR_CATALOG
+}
+
+impl CoreError {
+    /// The stable key which the message is translated by, it's listed in the catalog.
+    pub fn key(&self) -> &'static str {
+        match self {
+            CoreError::Other { .. } => "core.other",
+            CoreError::OutgoingMessageChannelFull => "core.outgoing_channel_full",
+            CoreError::OutgoingMessageChannelDisconnect => "core.outgoing_channel_disconnected",
+            CoreError::IO(_) => "core.io",
+            CoreError::CStringNullError(_) => "core.cstring",
+            CoreError::ParseIntError(_) => "core.parse_int",
+            CoreError::SQLiteError(_) => "core.sqlite",
+            CoreError::Timeout => "core.timeout",
+            CoreError::Canceled => "core.canceled",
+            CoreError::OneshotReceiveError(_) => "core.oneshot_receive",
+            CoreError::BincodeError(_) => "core.bincode",
+            CoreError::CborSerializeError(_) => "core.cbor_serialize",
+            CoreError::CborDeserializeError(_) => "core.cbor_deserialize",
+            CoreError::RSAError(_) => "core.rsa",
+            CoreError::RingUnspecifiedError(_) => "core.ring",
+            CoreError::ReqwestError(_) => "core.http",
+            CoreError::UrlError(_) => "core.url",
+            CoreError::SerdeJsonError(_) => "core.json",
+            CoreError::Base64Error(_) => "core.base64",
+            #[cfg(target_os = "windows")]
+            CoreError::HResultError { .. } => "core.windows_api",
+            CoreError::FromUTF8Error(_) => "core.utf8",
+            CoreError::FromUTF16Error(_) => "core.utf16",
+            CoreError::AudioDevicesError(_) => "core.audio_devices",
+            CoreError::AudioDeviceBuildStreamError(_) => "core.audio_build_stream",
+            CoreError::AudioDevicePlayStreamError(_) => "core.audio_play_stream",
+            CoreError::AudioDeviceDefaultConfigError(_) => "core.audio_default_config",
+            CoreError::AudioDeviceSupportedConfigsError(_) => "core.audio_supported_configs",
+            CoreError::R2D2PoolError(_) => "core.storage_pool",
+            CoreError::ConvertError(_) => "core.convert",
+            CoreError::ImageError(_) => "core.image",
+            CoreError::NetworkInterfacesError(_) => "core.network_interfaces",
+            CoreError::ZipError(_) => "core.zip",
+            CoreError::LibloadingError(_) => "core.load_library",
+            CoreError::AudioProcessingError(_) => "core.audio_processing",
+            CoreError::VisitFailed(reason) => match reason {
+                VisitFailureReason::RemoteReject => "visit.remote_reject",
+                VisitFailureReason::InvalidPassword => "visit.invalid_password",
+                VisitFailureReason::InternalError => "visit.internal_error",
+                VisitFailureReason::InvalidArgs => "visit.invalid_args",
+                VisitFailureReason::RemoteUnavailable => "visit.remote_unavailable",
+                VisitFailureReason::QueueTimeout => "visit.queue_timeout",
+                VisitFailureReason::AclDenied => "visit.acl_denied",
+                VisitFailureReason::IncompatibleProtocol => "visit.incompatible_protocol",
+                VisitFailureReason::Rejected { reason, .. } => match reason {
+                    RejectReason::User => "visit.rejected_by_user",
+                    RejectReason::DoNotDisturb => "visit.rejected_do_not_disturb",
+                    RejectReason::Acl => "visit.rejected_by_acl",
+                    RejectReason::Schedule => "visit.rejected_by_schedule",
+                },
+            },
+            CoreError::SignalingServerError(err) => match err {
+                HttpError::Internal => "signaling.internal",
+                HttpError::Timeout => "signaling.timeout",
+                HttpError::InvalidArgs => "signaling.invalid_args",
+                HttpError::ResourceExhausted => "signaling.resource_exhausted",
+                HttpError::RemoteOffline => "signaling.remote_offline",
+            },
+        }
+    }
+
+    /// The params which the message of key is filled with.
+    pub fn params(&self) -> BTreeMap<&'static str, String> {
+        let mut params = BTreeMap::new();
+
+        match self {
+            CoreError::Other { message, .. } => {
+                params.insert("message", message.clone());
+            }
+            CoreError::VisitFailed(VisitFailureReason::Rejected { message, .. }) => {
+                params.insert("message", message.clone().unwrap_or_default());
+            }
+            #[cfg(target_os = "windows")]
+            CoreError::HResultError { error, .. } => {
+                params.insert("detail", error.to_string());
+            }
+            // the wrapped errors are their sources
+            err => {
+                if let Some(source) = err.source() {
+                    params.insert("detail", source.to_string());
+                }
+            }
+        }
+
+        params
+    }
+}
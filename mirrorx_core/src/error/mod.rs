@@ -1,3 +1,9 @@
+mod catalog;
+
+pub use self::catalog::{error_catalog, ErrorKeyEntry};
+
+use crate::api::signaling::{http_message::HttpError, subscribe_message::VisitFailureReason};
+use serde::ser::SerializeStruct;
 use std::{
     io,
     string::{FromUtf16Error, FromUtf8Error},
@@ -36,12 +42,21 @@ pub enum CoreError {
     #[error("operation timeout")]
     Timeout,
 
+    #[error("operation canceled")]
+    Canceled,
+
     #[error("tokio oneshot channel receive error ({0:?})")]
     OneshotReceiveError(#[from] tokio::sync::oneshot::error::RecvError),
 
     #[error("bincode serialization or deserialization failed ({0:?})")]
     BincodeError(#[from] bincode::Error),
 
+    #[error("cbor serialization failed ({0:?})")]
+    CborSerializeError(#[from] ciborium::ser::Error<io::Error>),
+
+    #[error("cbor deserialization failed ({0:?})")]
+    CborDeserializeError(#[from] ciborium::de::Error<io::Error>),
+
     #[error("rsa error ({0:?})")]
     RSAError(#[from] rsa::errors::Error),
 
@@ -86,6 +101,9 @@ pub enum CoreError {
     #[error("audio device get default config failed ({0:?})")]
     AudioDeviceDefaultConfigError(#[from] cpal::DefaultStreamConfigError),
 
+    #[error("audio device get supported configs failed ({0:?})")]
+    AudioDeviceSupportedConfigsError(#[from] cpal::SupportedStreamConfigsError),
+
     #[error("r2d2 connection pool error ({0:?})")]
     R2D2PoolError(#[from] r2d2::Error),
 
@@ -97,13 +115,34 @@ pub enum CoreError {
 
     #[error("get network interfaces error ({0:?})")]
     NetworkInterfacesError(#[from] network_interface::Error),
+
+    #[error("zip archive error ({0:?})")]
+    ZipError(#[from] zip::result::ZipError),
+
+    #[error("load dynamic library error ({0:?})")]
+    LibloadingError(#[from] libloading::Error),
+
+    #[error("audio processing error ({0:?})")]
+    AudioProcessingError(#[from] webrtc_audio_processing::Error),
+
+    #[error("Visit Failed ({0:?})")]
+    VisitFailed(VisitFailureReason),
+
+    #[error("signaling server error ({0:?})")]
+    SignalingServerError(HttpError),
 }
 
+// the key and params let the message be translated, the message is kept for those which
+// show it as is
 impl serde::Serialize for CoreError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(self.to_string().as_str())
+        let mut state = serializer.serialize_struct("CoreError", 3)?;
+        state.serialize_field("key", self.key())?;
+        state.serialize_field("params", &self.params())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
     }
 }
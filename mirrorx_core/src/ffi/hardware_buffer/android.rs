@@ -0,0 +1,150 @@
+#![allow(non_camel_case_types)]
+
+use super::{chroma_sample, luminance_row};
+use crate::{core_error, error::CoreResult, DesktopDecodeFrame};
+use std::ffi::{c_int, c_void};
+
+const AHARDWAREBUFFER_FORMAT_Y8CB8CR8_420: u32 = 0x23;
+const AHARDWAREBUFFER_USAGE_CPU_WRITE_OFTEN: u64 = 3 << 4;
+const AHARDWAREBUFFER_USAGE_GPU_SAMPLED_IMAGE: u64 = 1 << 8;
+
+#[repr(C)]
+struct AHardwareBuffer {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct AHardwareBuffer_Desc {
+    width: u32,
+    height: u32,
+    layers: u32,
+    format: u32,
+    usage: u64,
+    stride: u32,
+    rfu0: u32,
+    rfu1: u64,
+}
+
+#[repr(C)]
+struct AHardwareBuffer_Plane {
+    data: *mut c_void,
+    pixel_stride: u32,
+    row_stride: u32,
+}
+
+#[repr(C)]
+struct AHardwareBuffer_Planes {
+    plane_count: u32,
+    planes: [AHardwareBuffer_Plane; 4],
+}
+
+extern "C" {
+    fn AHardwareBuffer_allocate(
+        desc: *const AHardwareBuffer_Desc,
+        out_buffer: *mut *mut AHardwareBuffer,
+    ) -> c_int;
+    fn AHardwareBuffer_release(buffer: *mut AHardwareBuffer);
+    fn AHardwareBuffer_lockPlanes(
+        buffer: *mut AHardwareBuffer,
+        usage: u64,
+        fence: i32,
+        rect: *const c_void,
+        out_planes: *mut AHardwareBuffer_Planes,
+    ) -> c_int;
+    fn AHardwareBuffer_unlock(buffer: *mut AHardwareBuffer, fence: *mut i32) -> c_int;
+}
+
+/// The frame copied into an `AHardwareBuffer` (API level 29), which can be imported
+/// by Vulkan or EGL for rendering without another copy.
+pub struct HardwareBuffer(*mut AHardwareBuffer);
+
+impl HardwareBuffer {
+    pub fn from_frame(frame: &DesktopDecodeFrame) -> CoreResult<Self> {
+        let desc = AHardwareBuffer_Desc {
+            width: frame.width as u32,
+            height: frame.height as u32,
+            layers: 1,
+            format: AHARDWAREBUFFER_FORMAT_Y8CB8CR8_420,
+            usage: AHARDWAREBUFFER_USAGE_CPU_WRITE_OFTEN | AHARDWAREBUFFER_USAGE_GPU_SAMPLED_IMAGE,
+            stride: 0,
+            rfu0: 0,
+            rfu1: 0,
+        };
+
+        unsafe {
+            let mut buffer = std::ptr::null_mut();
+            let ret = AHardwareBuffer_allocate(&desc, &mut buffer);
+            if ret != 0 {
+                return Err(core_error!(
+                    "AHardwareBuffer_allocate returns error code: {}",
+                    ret
+                ));
+            }
+
+            // release the buffer by drop if copy failed
+            let buffer = HardwareBuffer(buffer);
+
+            let mut planes: AHardwareBuffer_Planes = std::mem::zeroed();
+            let ret = AHardwareBuffer_lockPlanes(
+                buffer.0,
+                AHARDWAREBUFFER_USAGE_CPU_WRITE_OFTEN,
+                -1,
+                std::ptr::null(),
+                &mut planes,
+            );
+            if ret != 0 {
+                return Err(core_error!(
+                    "AHardwareBuffer_lockPlanes returns error code: {}",
+                    ret
+                ));
+            }
+
+            copy_frame(frame, &planes);
+
+            let ret = AHardwareBuffer_unlock(buffer.0, std::ptr::null_mut());
+            if ret != 0 {
+                return Err(core_error!(
+                    "AHardwareBuffer_unlock returns error code: {}",
+                    ret
+                ));
+            }
+
+            Ok(buffer)
+        }
+    }
+
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.0 as *mut c_void
+    }
+}
+
+impl Drop for HardwareBuffer {
+    fn drop(&mut self) {
+        unsafe { AHardwareBuffer_release(self.0) }
+    }
+}
+
+unsafe fn copy_frame(frame: &DesktopDecodeFrame, planes: &AHardwareBuffer_Planes) {
+    let width = frame.width as usize;
+    let height = frame.height as usize;
+
+    let luminance = &planes.planes[0];
+    for y in 0..height {
+        let row = luminance_row(frame, y);
+        let dst = (luminance.data as *mut u8).add(y * luminance.row_stride as usize);
+        std::ptr::copy_nonoverlapping(row.as_ptr(), dst, width);
+    }
+
+    // the chroma layout is decided by the gralloc, it may be planar or interleaved
+    let (cb, cr) = (&planes.planes[1], &planes.planes[2]);
+    for y in 0..height / 2 {
+        for x in 0..width / 2 {
+            let (u, v) = chroma_sample(frame, x, y);
+            let cb_offset = y * cb.row_stride as usize + x * cb.pixel_stride as usize;
+            let cr_offset = y * cr.row_stride as usize + x * cr.pixel_stride as usize;
+
+            *(cb.data as *mut u8).add(cb_offset) = u;
+            *(cr.data as *mut u8).add(cr_offset) = v;
+        }
+    }
+}
@@ -0,0 +1,171 @@
+#![allow(non_upper_case_globals)]
+
+use super::{chroma_sample, luminance_row};
+use crate::{component::frame::ColorRange, core_error, error::CoreResult, DesktopDecodeFrame};
+use std::ffi::c_void;
+
+// '420v' and '420f'
+const K_CV_PIXEL_FORMAT_TYPE_420_YP_CB_CR8_BI_PLANAR_VIDEO_RANGE: u32 = 0x34323076;
+const K_CV_PIXEL_FORMAT_TYPE_420_YP_CB_CR8_BI_PLANAR_FULL_RANGE: u32 = 0x34323066;
+
+const K_CV_RETURN_SUCCESS: i32 = 0;
+
+type CFTypeRef = *const c_void;
+type CFStringRef = *const c_void;
+type CFDictionaryRef = *const c_void;
+type CVPixelBufferRef = *mut c_void;
+
+#[repr(C)]
+struct CFDictionaryCallBacks {
+    _private: [u8; 0],
+}
+
+extern "C" {
+    static kCFTypeDictionaryKeyCallBacks: CFDictionaryCallBacks;
+    static kCFTypeDictionaryValueCallBacks: CFDictionaryCallBacks;
+    static kCVPixelBufferIOSurfacePropertiesKey: CFStringRef;
+
+    fn CFDictionaryCreate(
+        allocator: CFTypeRef,
+        keys: *const CFTypeRef,
+        values: *const CFTypeRef,
+        num_values: isize,
+        key_call_backs: *const CFDictionaryCallBacks,
+        value_call_backs: *const CFDictionaryCallBacks,
+    ) -> CFDictionaryRef;
+    fn CFRelease(cf: CFTypeRef);
+
+    fn CVPixelBufferCreate(
+        allocator: CFTypeRef,
+        width: usize,
+        height: usize,
+        pixel_format_type: u32,
+        pixel_buffer_attributes: CFDictionaryRef,
+        pixel_buffer_out: *mut CVPixelBufferRef,
+    ) -> i32;
+    fn CVPixelBufferRelease(pixel_buffer: CVPixelBufferRef);
+    fn CVPixelBufferLockBaseAddress(pixel_buffer: CVPixelBufferRef, lock_flags: u64) -> i32;
+    fn CVPixelBufferUnlockBaseAddress(pixel_buffer: CVPixelBufferRef, unlock_flags: u64) -> i32;
+    fn CVPixelBufferGetBaseAddressOfPlane(
+        pixel_buffer: CVPixelBufferRef,
+        plane_index: usize,
+    ) -> *mut c_void;
+    fn CVPixelBufferGetBytesPerRowOfPlane(
+        pixel_buffer: CVPixelBufferRef,
+        plane_index: usize,
+    ) -> usize;
+}
+
+/// The frame copied into an IOSurface backed `CVPixelBuffer`, which can be wrapped
+/// as Metal texture by `CVMetalTextureCache` without another copy.
+pub struct HardwareBuffer(CVPixelBufferRef);
+
+impl HardwareBuffer {
+    pub fn from_frame(frame: &DesktopDecodeFrame) -> CoreResult<Self> {
+        let pixel_format = match frame.color_space.range {
+            ColorRange::Full => K_CV_PIXEL_FORMAT_TYPE_420_YP_CB_CR8_BI_PLANAR_FULL_RANGE,
+            ColorRange::Limited => K_CV_PIXEL_FORMAT_TYPE_420_YP_CB_CR8_BI_PLANAR_VIDEO_RANGE,
+        };
+
+        unsafe {
+            // an empty IOSurface properties dictionary makes the buffer IOSurface backed
+            let io_surface_properties = CFDictionaryCreate(
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+                0,
+                &kCFTypeDictionaryKeyCallBacks,
+                &kCFTypeDictionaryValueCallBacks,
+            );
+
+            let keys = [kCVPixelBufferIOSurfacePropertiesKey];
+            let values = [io_surface_properties];
+            let attributes = CFDictionaryCreate(
+                std::ptr::null(),
+                keys.as_ptr(),
+                values.as_ptr(),
+                1,
+                &kCFTypeDictionaryKeyCallBacks,
+                &kCFTypeDictionaryValueCallBacks,
+            );
+
+            let mut pixel_buffer = std::ptr::null_mut();
+            let ret = CVPixelBufferCreate(
+                std::ptr::null(),
+                frame.width as usize,
+                frame.height as usize,
+                pixel_format,
+                attributes,
+                &mut pixel_buffer,
+            );
+
+            CFRelease(attributes);
+            CFRelease(io_surface_properties);
+
+            if ret != K_CV_RETURN_SUCCESS {
+                return Err(core_error!(
+                    "CVPixelBufferCreate returns error code: {}",
+                    ret
+                ));
+            }
+
+            // release the buffer by drop if copy failed
+            let buffer = HardwareBuffer(pixel_buffer);
+
+            let ret = CVPixelBufferLockBaseAddress(buffer.0, 0);
+            if ret != K_CV_RETURN_SUCCESS {
+                return Err(core_error!(
+                    "CVPixelBufferLockBaseAddress returns error code: {}",
+                    ret
+                ));
+            }
+
+            copy_frame(frame, buffer.0);
+
+            let ret = CVPixelBufferUnlockBaseAddress(buffer.0, 0);
+            if ret != K_CV_RETURN_SUCCESS {
+                return Err(core_error!(
+                    "CVPixelBufferUnlockBaseAddress returns error code: {}",
+                    ret
+                ));
+            }
+
+            Ok(buffer)
+        }
+    }
+
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+impl Drop for HardwareBuffer {
+    fn drop(&mut self) {
+        unsafe { CVPixelBufferRelease(self.0) }
+    }
+}
+
+unsafe fn copy_frame(frame: &DesktopDecodeFrame, pixel_buffer: CVPixelBufferRef) {
+    let width = frame.width as usize;
+    let height = frame.height as usize;
+
+    let luminance = CVPixelBufferGetBaseAddressOfPlane(pixel_buffer, 0) as *mut u8;
+    let luminance_stride = CVPixelBufferGetBytesPerRowOfPlane(pixel_buffer, 0);
+    for y in 0..height {
+        let row = luminance_row(frame, y);
+        let dst = luminance.add(y * luminance_stride);
+        std::ptr::copy_nonoverlapping(row.as_ptr(), dst, width);
+    }
+
+    // the buffer is always NV12, planar YUV420P frames are interleaved here
+    let chrominance = CVPixelBufferGetBaseAddressOfPlane(pixel_buffer, 1) as *mut u8;
+    let chrominance_stride = CVPixelBufferGetBytesPerRowOfPlane(pixel_buffer, 1);
+    for y in 0..height / 2 {
+        let dst = chrominance.add(y * chrominance_stride);
+        for x in 0..width / 2 {
+            let (u, v) = chroma_sample(frame, x, y);
+            *dst.add(x * 2) = u;
+            *dst.add(x * 2 + 1) = v;
+        }
+    }
+}
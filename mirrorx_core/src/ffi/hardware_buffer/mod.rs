@@ -0,0 +1,32 @@
+#[cfg(target_os = "android")]
+mod android;
+#[cfg(target_os = "android")]
+pub use self::android::HardwareBuffer;
+
+#[cfg(target_os = "ios")]
+mod ios;
+#[cfg(target_os = "ios")]
+pub use self::ios::HardwareBuffer;
+
+use crate::{component::frame::DesktopDecodeFrameFormat, DesktopDecodeFrame};
+
+/// Returns the row of luminance plane.
+fn luminance_row(frame: &DesktopDecodeFrame, y: usize) -> &[u8] {
+    let offset = y * frame.line_sizes[0] as usize;
+    &frame.plane_data[0][offset..offset + frame.width as usize]
+}
+
+/// Returns the (Cb, Cr) of chroma sample at (x, y) of the subsampled chroma planes,
+/// decoder outputs either interleaved NV12 or planar YUV420P.
+fn chroma_sample(frame: &DesktopDecodeFrame, x: usize, y: usize) -> (u8, u8) {
+    match frame.format {
+        DesktopDecodeFrameFormat::NV12 => {
+            let offset = y * frame.line_sizes[1] as usize + x * 2;
+            (frame.plane_data[1][offset], frame.plane_data[1][offset + 1])
+        }
+        DesktopDecodeFrameFormat::YUV420P => (
+            frame.plane_data[1][y * frame.line_sizes[1] as usize + x],
+            frame.plane_data[2][y * frame.line_sizes[2] as usize + x],
+        ),
+    }
+}
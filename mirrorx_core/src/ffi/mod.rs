@@ -0,0 +1,171 @@
+//! C ABI of the viewer pipeline for the mobile apps.
+//!
+//! The mobile builds disable the default `host` feature, iOS links the crate as
+//! `staticlib` and Android loads it as `cdylib`:
+//!
+//! ```text
+//! cargo rustc -p mirrorx_core --no-default-features --target aarch64-apple-ios --crate-type staticlib
+//! cargo rustc -p mirrorx_core --no-default-features --target aarch64-linux-android --crate-type cdylib
+//! ```
+//!
+//! Functions return null or false on failure, the reason can be read by
+//! [`mirrorx_last_error`] on the same thread.
+
+mod hardware_buffer;
+mod session;
+
+pub use session::*;
+
+use crate::{
+    api::{
+        config::{entity::domain::Domain, LocalStorage},
+        signaling::{http_message::Response, SignalingClient},
+    },
+    core_error,
+    error::{CoreError, CoreResult},
+};
+use once_cell::sync::Lazy;
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+};
+use tokio::runtime::Runtime;
+
+static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .thread_name("mirrorx_core")
+        .enable_all()
+        .build()
+        .expect("create tokio runtime failed")
+});
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// The storage handle, it's created by [`mirrorx_storage_open`] and freed by
+/// [`mirrorx_storage_close`].
+pub struct MirrorXStorage(LocalStorage);
+
+/// Returns the message of last failed call on current thread, or null when there's
+/// none. The string is owned by the library and valid until next call.
+#[no_mangle]
+pub extern "C" fn mirrorx_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Open the storage database at `path`, it's created if not exists.
+///
+/// # Safety
+///
+/// `path` must be a valid nul-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn mirrorx_storage_open(path: *const c_char) -> *mut MirrorXStorage {
+    let result = read_c_str(path).and_then(LocalStorage::new);
+
+    match take_result(result) {
+        Some(storage) => Box::into_raw(Box::new(MirrorXStorage(storage))),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// `storage` must be returned by [`mirrorx_storage_open`] and not closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn mirrorx_storage_close(storage: *mut MirrorXStorage) {
+    if !storage.is_null() {
+        drop(Box::from_raw(storage));
+    }
+}
+
+/// Register this device to the domain server at `addr` and save it as the primary
+/// domain, it does nothing when the storage already has any domain.
+///
+/// # Safety
+///
+/// `storage` must be a valid storage handle and `addr` must be a valid
+/// nul-terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn mirrorx_domain_register(
+    storage: *const MirrorXStorage,
+    addr: *const c_char,
+) -> bool {
+    let result = (|| {
+        let storage = storage.as_ref().ok_or(core_error!("storage is null"))?;
+        let addr = read_c_str(addr)?;
+
+        RUNTIME.block_on(register_domain(&storage.0, addr))
+    })();
+
+    take_result(result).is_some()
+}
+
+async fn register_domain(storage: &LocalStorage, addr: String) -> CoreResult<()> {
+    if storage.domain().get_domain_count()? > 0 {
+        return Ok(());
+    }
+
+    let client = SignalingClient::new(addr.clone())?;
+    let identity = match client.identity().await? {
+        Response::Message(resp) => resp,
+        Response::Error(err) => return Err(CoreError::SignalingServerError(err)),
+    };
+
+    let finger_print = crate::utility::rand::generate_device_finger_print();
+    let register = match client.domain_register(0, &finger_print).await? {
+        Response::Message(resp) => resp,
+        Response::Error(err) => return Err(CoreError::SignalingServerError(err)),
+    };
+
+    storage.domain().add_domain(Domain {
+        id: 0,
+        name: identity.domain,
+        addr,
+        signaling_port: identity.signaling_port,
+        subscribe_port: identity.subscribe_port,
+        is_primary: true,
+        device_id: register.device_id,
+        password: crate::utility::rand::generate_random_password(),
+        finger_print,
+        remarks: String::default(),
+    })?;
+
+    Ok(())
+}
+
+unsafe fn read_c_str(ptr: *const c_char) -> CoreResult<String> {
+    if ptr.is_null() {
+        return Err(core_error!("string is null"));
+    }
+
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(String::from)
+        .map_err(|_| core_error!("string is not valid utf-8"))
+}
+
+fn take_result<T>(result: CoreResult<T>) -> Option<T> {
+    match result {
+        Ok(value) => {
+            set_last_error(None);
+            Some(value)
+        }
+        Err(err) => {
+            tracing::error!(?err, "ffi call failed");
+            set_last_error(Some(err));
+            None
+        }
+    }
+}
+
+fn set_last_error(err: Option<CoreError>) {
+    // error message never contains nul in practice, drop the message if it does
+    let message = err.and_then(|err| CString::new(err.to_string()).ok());
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message);
+}
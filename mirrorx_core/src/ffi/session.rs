@@ -0,0 +1,279 @@
+use super::{hardware_buffer::HardwareBuffer, read_c_str, take_result, MirrorXStorage, RUNTIME};
+use crate::{
+    api::{
+        endpoint::{
+            client::EndPointClient,
+            create_desktop_active_endpoint_client,
+            id::EndPointID,
+            message::{EndPointInput, EndPointMessage, InputEvent, MouseEvent},
+            EndPointStream,
+        },
+        signaling::{
+            fingerprint::{load_identity_key, verify_peer},
+            http_message::Response,
+            SignalingClient,
+        },
+    },
+    component::{
+        input::key::MouseKey,
+        telemetry::{self, FailureCategory},
+    },
+    core_error,
+    error::{CoreError, CoreResult},
+    DesktopDecodeFrame,
+};
+use std::{
+    ffi::{c_char, c_void},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicI32, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::mpsc::Receiver;
+
+/// Called on the frame thread for every decoded frame. `buffer` is an
+/// `AHardwareBuffer*` on Android and a `CVPixelBufferRef` on iOS, it's only valid
+/// during the call, acquire or retain it to keep it longer.
+pub type MirrorXFrameCallback =
+    extern "C" fn(user_data: *mut c_void, buffer: *mut c_void, width: i32, height: i32);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub enum MirrorXTouchPhase {
+    Began = 0,
+    Moved = 1,
+    Ended = 2,
+}
+
+/// A single pointer touch, the position is normalized to [0, 1] of the frame.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MirrorXTouchEvent {
+    pub phase: MirrorXTouchPhase,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// The visiting desktop session, it's created by [`mirrorx_session_visit`] and freed
+/// by [`mirrorx_session_close`].
+pub struct MirrorXSession {
+    client: Arc<EndPointClient>,
+    frame_size: Arc<(AtomicI32, AtomicI32)>,
+    closed: Arc<AtomicBool>,
+}
+
+struct FrameCallback {
+    callback: MirrorXFrameCallback,
+    user_data: *mut c_void,
+}
+
+// the caller is responsible for the thread safety of user data, it's only passed back
+unsafe impl Send for FrameCallback {}
+
+/// Visit the desktop of remote device through the primary domain, decoded frames are
+/// delivered to `frame_callback` with `user_data` until the session is closed.
+///
+/// # Safety
+///
+/// `storage` must be a valid storage handle, `remote_device_id` and `password` must
+/// be valid nul-terminated UTF-8 strings.
+#[no_mangle]
+pub unsafe extern "C" fn mirrorx_session_visit(
+    storage: *const MirrorXStorage,
+    remote_device_id: *const c_char,
+    password: *const c_char,
+    frame_callback: MirrorXFrameCallback,
+    user_data: *mut c_void,
+) -> *mut MirrorXSession {
+    let frame_callback = FrameCallback {
+        callback: frame_callback,
+        user_data,
+    };
+
+    let result = (|| -> CoreResult<MirrorXSession> {
+        let storage = storage.as_ref().ok_or(core_error!("storage is null"))?;
+        let remote_device_id = read_c_str(remote_device_id)?;
+        let password = read_c_str(password)?;
+
+        let (client, render_frame_rx) =
+            RUNTIME.block_on(visit_desktop(&storage.0, remote_device_id, password))?;
+
+        let frame_size = Arc::new((AtomicI32::new(0), AtomicI32::new(0)));
+        let closed = Arc::new(AtomicBool::new(false));
+        serve_frame_export(
+            render_frame_rx,
+            frame_callback,
+            frame_size.clone(),
+            closed.clone(),
+        )?;
+
+        Ok(MirrorXSession {
+            client,
+            frame_size,
+            closed,
+        })
+    })();
+
+    match take_result(result) {
+        Some(session) => Box::into_raw(Box::new(session)),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Send the touch as left mouse button events to remote desktop.
+///
+/// # Safety
+///
+/// `session` must be returned by [`mirrorx_session_visit`] and not closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn mirrorx_session_touch(
+    session: *const MirrorXSession,
+    event: MirrorXTouchEvent,
+) -> bool {
+    let result = (|| {
+        let session = session.as_ref().ok_or(core_error!("session is null"))?;
+
+        let width = session.frame_size.0.load(Ordering::SeqCst);
+        let height = session.frame_size.1.load(Ordering::SeqCst);
+        if width == 0 || height == 0 {
+            // no frame displayed yet, there's nothing to touch
+            return Ok(());
+        }
+
+        // mouse position is in the pixel space of remote frame
+        let x = event.x.clamp(0.0, 1.0) * width as f32;
+        let y = event.y.clamp(0.0, 1.0) * height as f32;
+
+        let mouse_event = match event.phase {
+            MirrorXTouchPhase::Began => MouseEvent::Down(MouseKey::Left, x, y),
+            MirrorXTouchPhase::Moved => MouseEvent::Move(MouseKey::Left, x, y),
+            MirrorXTouchPhase::Ended => MouseEvent::Up(MouseKey::Left, x, y),
+        };
+
+        session
+            .client
+            .try_send(&EndPointMessage::InputCommand(EndPointInput {
+                events: vec![InputEvent::Mouse(mouse_event)],
+            }))
+    })();
+
+    take_result(result).is_some()
+}
+
+/// Close the session like the desktop window does by dropping the client, no more
+/// frame is delivered after it returns except the one which is being delivered.
+///
+/// # Safety
+///
+/// `session` must be returned by [`mirrorx_session_visit`] and not closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn mirrorx_session_close(session: *mut MirrorXSession) {
+    if !session.is_null() {
+        let session = Box::from_raw(session);
+        session.closed.store(true, Ordering::SeqCst);
+    }
+}
+
+async fn visit_desktop(
+    storage: &crate::api::config::LocalStorage,
+    remote_device_id: String,
+    password: String,
+) -> CoreResult<(Arc<EndPointClient>, Receiver<DesktopDecodeFrame>)> {
+    let remote_device_id: i64 = remote_device_id.replace('-', "").parse()?;
+    let primary_domain = storage.domain().get_primary_domain()?;
+    let local_device_id = primary_domain.device_id;
+
+    let identity_key = load_identity_key(storage)?;
+
+    let signaling_client = SignalingClient::new(primary_domain.addr.clone())?;
+    let resp = signaling_client
+        .visit(
+            local_device_id,
+            remote_device_id,
+            password,
+            None,
+            true,
+            &identity_key,
+        )
+        .await?;
+
+    let (endpoint_addr, visit_credentials, opening_key, sealing_key, peer) = match resp {
+        Response::Message(result) => match result {
+            Ok(v) => v,
+            Err(reason) => {
+                telemetry::record_connect_failure(FailureCategory::VisitRejected);
+                return Err(CoreError::VisitFailed(reason));
+            }
+        },
+        Response::Error(err) => return Err(CoreError::SignalingServerError(err)),
+    };
+
+    let endpoint_addr: SocketAddr = endpoint_addr
+        .parse()
+        .map_err(|_| core_error!("parse endpoint addr failed"))?;
+
+    if let Err(err) = verify_peer(
+        storage,
+        &primary_domain.name,
+        local_device_id,
+        remote_device_id,
+        &peer,
+    ) {
+        tracing::error!(?err, "verify peer identity failed");
+    }
+
+    create_desktop_active_endpoint_client(
+        EndPointID::DeviceID {
+            local_device_id,
+            remote_device_id,
+        },
+        Some((opening_key, sealing_key)),
+        EndPointStream::relay(storage, &primary_domain.name, endpoint_addr)?,
+        Some(visit_credentials),
+    )
+    .await
+}
+
+fn serve_frame_export(
+    mut render_frame_rx: Receiver<DesktopDecodeFrame>,
+    frame_callback: FrameCallback,
+    frame_size: Arc<(AtomicI32, AtomicI32)>,
+    closed: Arc<AtomicBool>,
+) -> CoreResult<()> {
+    std::thread::Builder::new()
+        .name(String::from("frame_export"))
+        .spawn(move || {
+            while let Some(frame) = render_frame_rx.blocking_recv() {
+                if closed.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                frame_size.0.store(frame.width, Ordering::SeqCst);
+                frame_size.1.store(frame.height, Ordering::SeqCst);
+
+                let buffer = match HardwareBuffer::from_frame(&frame) {
+                    Ok(buffer) => buffer,
+                    Err(err) => {
+                        tracing::error!(?err, "export frame to hardware buffer failed");
+                        continue;
+                    }
+                };
+
+                if closed.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                (frame_callback.callback)(
+                    frame_callback.user_data,
+                    buffer.as_ptr(),
+                    frame.width,
+                    frame.height,
+                );
+            }
+
+            tracing::info!("frame export thread exit");
+        })?;
+
+    Ok(())
+}
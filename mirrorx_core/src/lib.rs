@@ -1,6 +1,9 @@
 pub mod api;
 pub mod component;
 pub mod error;
+#[cfg(any(target_os = "android", target_os = "ios"))]
+pub mod ffi;
+pub mod service;
 pub mod utility;
 
 pub use component::frame::DesktopDecodeFrame;
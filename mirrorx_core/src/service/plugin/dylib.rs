@@ -0,0 +1,262 @@
+//! Plugins in dynamic libraries export these C functions, only the name is required:
+//!
+//! ```c
+//! const char* mirrorx_plugin_name(void);
+//! void mirrorx_plugin_init(MirrorXPluginHost host);
+//! // required with init, stops and joins every thread the plugin started
+//! void mirrorx_plugin_shutdown(void);
+//! // json array of channel names, like ["asset_agent"]
+//! const char* mirrorx_plugin_channels(void);
+//! // json of SessionEvent, like {"type":"disconnected","session_id":"..."}
+//! void mirrorx_plugin_on_event(const char* event);
+//! void mirrorx_plugin_on_channel_message(const char* session_id, const char* channel,
+//!                                        const uint8_t* payload, size_t len);
+//! ```
+//!
+//! Strings returned by plugin must stay valid while the plugin is loaded. Name and
+//! channels are read before the plugin is registered, so they must not start any work.
+//! The library is unloaded once the plugin is unregistered and no dispatch uses it,
+//! right after shutdown returns.
+
+use super::{register_plugin, send_channel_message, Plugin, PluginSession, SessionEvent};
+use crate::{core_error, error::CoreResult};
+use libloading::Library;
+use std::{
+    ffi::{c_char, CStr, CString},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+type NameFn = unsafe extern "C" fn() -> *const c_char;
+type InitFn = unsafe extern "C" fn(host: MirrorXPluginHost);
+type ShutdownFn = unsafe extern "C" fn();
+type ChannelsFn = unsafe extern "C" fn() -> *const c_char;
+type OnEventFn = unsafe extern "C" fn(event: *const c_char);
+type OnChannelMessageFn = unsafe extern "C" fn(
+    session_id: *const c_char,
+    channel: *const c_char,
+    payload: *const u8,
+    len: usize,
+);
+
+/// Functions which host provides to plugin.
+#[repr(C)]
+pub struct MirrorXPluginHost {
+    pub send_channel_message: unsafe extern "C" fn(
+        session_id: *const c_char,
+        channel: *const c_char,
+        payload: *const u8,
+        len: usize,
+    ) -> bool,
+}
+
+struct DynamicPlugin {
+    name: String,
+    channels: Vec<String>,
+    on_event: Option<OnEventFn>,
+    on_channel_message: Option<OnChannelMessageFn>,
+    shutdown: Option<ShutdownFn>,
+    initialized: AtomicBool,
+    // functions above are valid only when library is loaded
+    _library: Library,
+}
+
+impl Drop for DynamicPlugin {
+    fn drop(&mut self) {
+        // plugin threads must be gone before the library is unloaded with the fields
+        if !self.initialized.load(Ordering::SeqCst) {
+            return;
+        }
+
+        if let Some(shutdown) = self.shutdown {
+            unsafe { shutdown() }
+            tracing::info!(name = self.name, "plugin shut down");
+        }
+    }
+}
+
+impl Plugin for DynamicPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn channels(&self) -> Vec<String> {
+        self.channels.clone()
+    }
+
+    fn on_session_event(&self, event: &SessionEvent) {
+        let Some(on_event) = self.on_event else {
+            return;
+        };
+
+        let event = match serde_json::to_string(event).map(CString::new) {
+            Ok(Ok(event)) => event,
+            _ => {
+                tracing::error!(name = self.name, "serialize plugin event failed");
+                return;
+            }
+        };
+
+        unsafe { on_event(event.as_ptr()) }
+    }
+
+    fn on_channel_message(&self, session: &PluginSession, channel: &str, payload: &[u8]) {
+        let Some(on_channel_message) = self.on_channel_message else {
+            return;
+        };
+
+        let (Ok(session_id), Ok(channel)) =
+            (CString::new(session.session_id()), CString::new(channel))
+        else {
+            return;
+        };
+
+        unsafe {
+            on_channel_message(
+                session_id.as_ptr(),
+                channel.as_ptr(),
+                payload.as_ptr(),
+                payload.len(),
+            )
+        }
+    }
+}
+
+/// Load the plugin from dynamic library and register it, returns the plugin name.
+///
+/// The library runs in process with full privilege, only load the trusted ones.
+pub fn load_plugin(path: &Path) -> CoreResult<String> {
+    unsafe {
+        let library = Library::new(path)?;
+
+        // resolve all symbols before any plugin code runs
+        let name_fn = *library.get::<NameFn>(b"mirrorx_plugin_name\0")?;
+
+        let channels_fn = library
+            .get::<ChannelsFn>(b"mirrorx_plugin_channels\0")
+            .ok()
+            .map(|symbol| *symbol);
+
+        let on_event = library
+            .get::<OnEventFn>(b"mirrorx_plugin_on_event\0")
+            .ok()
+            .map(|symbol| *symbol);
+
+        let on_channel_message = library
+            .get::<OnChannelMessageFn>(b"mirrorx_plugin_on_channel_message\0")
+            .ok()
+            .map(|symbol| *symbol);
+
+        let init_fn = library
+            .get::<InitFn>(b"mirrorx_plugin_init\0")
+            .ok()
+            .map(|symbol| *symbol);
+
+        let shutdown = library
+            .get::<ShutdownFn>(b"mirrorx_plugin_shutdown\0")
+            .ok()
+            .map(|symbol| *symbol);
+
+        // threads started by init can't be stopped before unloading without shutdown
+        if init_fn.is_some() && shutdown.is_none() {
+            return Err(core_error!("plugin exports init without shutdown"));
+        }
+
+        let name = read_plugin_str(name_fn()).ok_or(core_error!("plugin name is invalid"))?;
+
+        let channels = match channels_fn.and_then(|channels_fn| read_plugin_str(channels_fn())) {
+            Some(channels) => serde_json::from_str(&channels)?,
+            None => Vec::new(),
+        };
+
+        let plugin = Arc::new(DynamicPlugin {
+            name: name.clone(),
+            channels,
+            on_event,
+            on_channel_message,
+            shutdown,
+            initialized: AtomicBool::new(false),
+            _library: library,
+        });
+
+        // registered before init, so a plugin rejected for its name or channels never
+        // starts and its library is unloaded without shutdown
+        register_plugin(plugin.clone())?;
+
+        if let Some(init_fn) = init_fn {
+            plugin.initialized.store(true, Ordering::SeqCst);
+            init_fn(MirrorXPluginHost {
+                send_channel_message: host_send_channel_message,
+            });
+        }
+
+        tracing::info!(?path, name, "plugin loaded");
+
+        Ok(name)
+    }
+}
+
+/// Load all plugins in `dir`, a plugin failed to load doesn't stop others.
+pub fn load_plugins_from_dir(dir: &Path) -> CoreResult<Vec<String>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut names = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        let is_library = path.extension().map_or(false, |extension| {
+            extension == std::env::consts::DLL_EXTENSION
+        });
+
+        if !is_library {
+            continue;
+        }
+
+        match load_plugin(&path) {
+            Ok(name) => names.push(name),
+            Err(err) => tracing::error!(?path, ?err, "load plugin failed"),
+        }
+    }
+
+    Ok(names)
+}
+
+unsafe fn read_plugin_str(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    CStr::from_ptr(ptr).to_str().ok().map(String::from)
+}
+
+unsafe extern "C" fn host_send_channel_message(
+    session_id: *const c_char,
+    channel: *const c_char,
+    payload: *const u8,
+    len: usize,
+) -> bool {
+    let (Some(session_id), Some(channel)) = (read_plugin_str(session_id), read_plugin_str(channel))
+    else {
+        return false;
+    };
+
+    let payload = if payload.is_null() || len == 0 {
+        Vec::new()
+    } else {
+        std::slice::from_raw_parts(payload, len).to_vec()
+    };
+
+    match send_channel_message(&session_id, &channel, payload) {
+        Ok(_) => true,
+        Err(err) => {
+            tracing::error!(?err, "plugin send channel message failed");
+            false
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Plugins subscribe to the session lifecycle events and exchange messages with their
+//! counterparts on remote endpoint through named sub-channels, so integrations can be
+//! built without forking the crate. Plugins are either registered in process by
+//! [`register_plugin`], or loaded from dynamic libraries by [`load_plugin`].
+
+mod dylib;
+
+pub use dylib::{load_plugin, load_plugins_from_dir};
+
+use crate::{
+    api::endpoint::{
+        client::EndPointClient,
+        message::{EndPointMessage, EndPointPluginMessage, InputEvent},
+        permission::Permissions,
+        sessions,
+    },
+    core_error,
+    error::CoreResult,
+};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        mpsc::{SyncSender, TrySendError},
+        Arc, Mutex, RwLock,
+    },
+};
+
+// dispatches waiting for slow plugins, later ones are dropped
+const DISPATCH_QUEUE_CAPACITY: usize = 1024;
+
+static REGISTRY: Lazy<RwLock<Registry>> = Lazy::new(|| RwLock::new(Registry::default()));
+
+// plugins are called on the dispatch thread, so a slow plugin never blocks sessions
+static DISPATCH_TX: Lazy<Mutex<SyncSender<Dispatch>>> = Lazy::new(|| {
+    let (tx, rx) = std::sync::mpsc::sync_channel(DISPATCH_QUEUE_CAPACITY);
+
+    let _ = std::thread::Builder::new()
+        .name(String::from("plugin_dispatch"))
+        .spawn(move || {
+            while let Ok(dispatch) = rx.recv() {
+                serve_dispatch(dispatch);
+            }
+        });
+
+    Mutex::new(tx)
+});
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionEvent {
+    Connected {
+        session_id: String,
+        endpoint_id: String,
+        active: bool,
+    },
+    Disconnected {
+        session_id: String,
+    },
+    InputReceived {
+        session_id: String,
+        events: Vec<InputEvent>,
+    },
+    FileTransferred {
+        id: String,
+        path: PathBuf,
+        succeed: bool,
+    },
+}
+
+pub trait Plugin: Send + Sync {
+    /// The unique name of plugin.
+    fn name(&self) -> &str;
+
+    /// Names of the sub-channels this plugin receives messages from, a channel can only
+    /// be owned by one plugin.
+    fn channels(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn on_session_event(&self, _event: &SessionEvent) {}
+
+    fn on_channel_message(&self, _session: &PluginSession, _channel: &str, _payload: &[u8]) {}
+}
+
+/// The session which a channel message comes from.
+pub struct PluginSession {
+    session_id: String,
+}
+
+impl PluginSession {
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Send message to the plugin owns `channel` on remote endpoint.
+    pub fn send(&self, channel: &str, payload: Vec<u8>) -> CoreResult<()> {
+        send_channel_message(&self.session_id, channel, payload)
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    plugins: Vec<Arc<dyn Plugin>>,
+    channels: HashMap<String, Arc<dyn Plugin>>,
+}
+
+enum Dispatch {
+    Event(SessionEvent),
+    ChannelMessage {
+        session_id: String,
+        message: EndPointPluginMessage,
+    },
+}
+
+pub fn register_plugin(plugin: Arc<dyn Plugin>) -> CoreResult<()> {
+    let mut registry = REGISTRY.write().unwrap();
+
+    if registry
+        .plugins
+        .iter()
+        .any(|registered| registered.name() == plugin.name())
+    {
+        return Err(core_error!(
+            "plugin '{}' is already registered",
+            plugin.name()
+        ));
+    }
+
+    let channels = plugin.channels();
+    if let Some(channel) = channels
+        .iter()
+        .find(|channel| registry.channels.contains_key(*channel))
+    {
+        return Err(core_error!("plugin channel '{}' is already owned", channel));
+    }
+
+    for channel in channels {
+        registry.channels.insert(channel, plugin.clone());
+    }
+
+    tracing::info!(name = plugin.name(), "plugin registered");
+    registry.plugins.push(plugin);
+
+    Ok(())
+}
+
+/// Remove the plugin from registry, a plugin loaded from dynamic library is shut down
+/// and unloaded once the dispatch which still calls it returns.
+pub fn unregister_plugin(name: &str) {
+    // dropped outside of the lock, shutdown may wait for plugin threads which call host
+    let removed: Vec<Arc<dyn Plugin>> = {
+        let mut registry = REGISTRY.write().unwrap();
+        registry.channels.retain(|_, plugin| plugin.name() != name);
+        let (removed, kept) = std::mem::take(&mut registry.plugins)
+            .into_iter()
+            .partition(|plugin| plugin.name() == name);
+        registry.plugins = kept;
+        removed
+    };
+
+    drop(removed);
+}
+
+/// Returns names of all registered plugins.
+pub fn plugins() -> Vec<String> {
+    REGISTRY
+        .read()
+        .unwrap()
+        .plugins
+        .iter()
+        .map(|plugin| plugin.name().to_string())
+        .collect()
+}
+
+/// Send message to the plugin owns `channel` on the remote endpoint of session.
+pub fn send_channel_message(session_id: &str, channel: &str, payload: Vec<u8>) -> CoreResult<()> {
+    let client = sessions::find(session_id).ok_or(core_error!("session not exists"))?;
+
+    if !client.permissions().contains(Permissions::PLUGINS) {
+        return Err(core_error!("permission denied (plugins)"));
+    }
+
+    client.try_send(&EndPointMessage::PluginMessage(EndPointPluginMessage {
+        channel: channel.to_string(),
+        payload,
+    }))
+}
+
+pub(crate) fn session_connected(client: &Arc<EndPointClient>, active: bool) {
+    publish(SessionEvent::Connected {
+        session_id: client.session_id().to_string(),
+        endpoint_id: client.endpoint_id().to_string(),
+        active,
+    });
+}
+
+pub(crate) fn session_disconnected(session_id: &str) {
+    publish(SessionEvent::Disconnected {
+        session_id: session_id.to_string(),
+    });
+}
+
+pub(crate) fn publish(event: SessionEvent) {
+    // don't build the dispatch when nobody listens, input events are frequent
+    if REGISTRY.read().unwrap().plugins.is_empty() {
+        return;
+    }
+
+    send_dispatch(Dispatch::Event(event));
+}
+
+pub(crate) fn dispatch_channel_message(session_id: &str, message: EndPointPluginMessage) {
+    if REGISTRY.read().unwrap().channels.is_empty() {
+        return;
+    }
+
+    send_dispatch(Dispatch::ChannelMessage {
+        session_id: session_id.to_string(),
+        message,
+    });
+}
+
+fn send_dispatch(dispatch: Dispatch) {
+    // never blocks the session, remote endpoint may send faster than plugins consume
+    if let Err(TrySendError::Full(_)) = DISPATCH_TX.lock().unwrap().try_send(dispatch) {
+        tracing::warn!("plugin dispatch queue is full, drop dispatch");
+    }
+}
+
+fn serve_dispatch(dispatch: Dispatch) {
+    match dispatch {
+        Dispatch::Event(event) => {
+            let plugins = REGISTRY.read().unwrap().plugins.clone();
+            for plugin in plugins {
+                plugin.on_session_event(&event);
+            }
+        }
+        Dispatch::ChannelMessage {
+            session_id,
+            message,
+        } => {
+            let plugin = REGISTRY
+                .read()
+                .unwrap()
+                .channels
+                .get(&message.channel)
+                .cloned();
+
+            let Some(plugin) = plugin else {
+                tracing::warn!(channel = message.channel, "no plugin owns the channel");
+                return;
+            };
+
+            plugin.on_channel_message(
+                &PluginSession { session_id },
+                &message.channel,
+                &message.payload,
+            );
+        }
+    }
+}
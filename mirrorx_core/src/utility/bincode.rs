@@ -21,6 +21,15 @@ where
     Ok(buffer)
 }
 
+pub fn bincode_serialize_into<W, S>(writer: W, t: &S) -> CoreResult<()>
+where
+    W: std::io::Write,
+    S: ?Sized + serde::Serialize,
+{
+    SERIALIZER.serialize_into(writer, t)?;
+    Ok(())
+}
+
 pub fn bincode_deserialize<'a, T>(bytes: &'a [u8]) -> CoreResult<T>
 where
     T: serde::Deserialize<'a>,
@@ -0,0 +1,68 @@
+//! Long running operations register a token under the request id which frontend generates
+//! for them, so they can be canceled from another command while they're still awaited.
+
+use crate::error::{CoreError, CoreResult};
+use once_cell::sync::Lazy;
+use std::{collections::HashMap, future::Future, sync::Mutex};
+use tokio_util::sync::CancellationToken;
+
+static CANCEL_TOKENS: Lazy<Mutex<HashMap<String, CancellationToken>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Keeps the token registered until the operation finishes.
+pub struct CancelRegistration {
+    request_id: Option<String>,
+    token: CancellationToken,
+}
+
+impl CancelRegistration {
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for CancelRegistration {
+    fn drop(&mut self) {
+        if let Some(ref request_id) = self.request_id {
+            CANCEL_TOKENS.lock().unwrap().remove(request_id);
+        }
+    }
+}
+
+/// Register a token for the operation, operations without request id can't be canceled
+/// but share the same code path.
+pub fn register(request_id: Option<String>) -> CancelRegistration {
+    let token = CancellationToken::new();
+
+    if let Some(ref request_id) = request_id {
+        CANCEL_TOKENS
+            .lock()
+            .unwrap()
+            .insert(request_id.clone(), token.clone());
+    }
+
+    CancelRegistration { request_id, token }
+}
+
+/// Cancel the operation of the request, false when it's finished already or unknown.
+pub fn cancel(request_id: &str) -> bool {
+    match CANCEL_TOKENS.lock().unwrap().remove(request_id) {
+        Some(token) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Await the future until it completes or the token is canceled, the future is dropped
+/// on cancellation.
+pub async fn cancelable<F, T>(token: &CancellationToken, future: F) -> CoreResult<T>
+where
+    F: Future<Output = CoreResult<T>>,
+{
+    tokio::select! {
+        _ = token.cancelled() => Err(CoreError::Canceled),
+        result = future => result,
+    }
+}
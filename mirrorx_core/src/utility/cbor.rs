@@ -0,0 +1,28 @@
+use crate::error::CoreResult;
+use serde::de::DeserializeOwned;
+
+pub fn cbor_serialize<S>(t: &S) -> CoreResult<Vec<u8>>
+where
+    S: ?Sized + serde::Serialize,
+{
+    let mut buffer = Vec::new();
+    ciborium::ser::into_writer(t, &mut buffer)?;
+    Ok(buffer)
+}
+
+pub fn cbor_serialize_into<W, S>(writer: W, t: &S) -> CoreResult<()>
+where
+    W: std::io::Write,
+    S: ?Sized + serde::Serialize,
+{
+    ciborium::ser::into_writer(t, writer)?;
+    Ok(())
+}
+
+pub fn cbor_deserialize<T>(bytes: &[u8]) -> CoreResult<T>
+where
+    T: DeserializeOwned,
+{
+    let ty = ciborium::de::from_reader(bytes)?;
+    Ok(ty)
+}
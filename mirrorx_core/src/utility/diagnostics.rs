@@ -0,0 +1,560 @@
+use crate::{
+    api::{config::LocalStorage, signaling::SignalingClient},
+    core_error,
+    error::CoreResult,
+    utility::os::{enum_broadcast_network_interfaces, enum_graphics_cards, GraphicsCards},
+};
+use mirrorx_native::ffmpeg::{
+    codecs::{
+        codec::{avcodec_find_decoder, avcodec_find_encoder_by_name},
+        codec_id::{AV_CODEC_ID_H264, AV_CODEC_ID_HEVC},
+    },
+    utils::hwcontext::{
+        av_hwdevice_get_type_name, av_hwdevice_iterate_types, AV_HWDEVICE_TYPE_NONE,
+    },
+};
+use rand::RngCore;
+use serde::Serialize;
+use std::{
+    ffi::{CStr, CString},
+    io::{BufRead, BufReader, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio::net::UdpSocket;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+// only the latest rolling log files are attached, older ones rarely help
+const MAX_LOG_FILES: usize = 3;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+// two servers with different addresses, a symmetric NAT maps the same local socket
+// to different public ports for them
+const STUN_SERVERS: [&str; 2] = ["stun.l.google.com:19302", "stun1.l.google.com:19302"];
+
+const STUN_MAGIC_COOKIE: u32 = 0x2112_A442;
+
+// the keys whose values should never leave the device
+const SENSITIVE_KEYS: [&str; 6] = [
+    "password",
+    "secret",
+    "token",
+    "finger_print",
+    "private_key",
+    "sealing_key",
+];
+
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Debug, Serialize)]
+pub struct ConfigSummary {
+    pub domains: Vec<DomainSummary>,
+    pub language: Option<String>,
+    pub theme: Option<String>,
+    pub power_saving_enabled: bool,
+    pub interrupted_transfers: usize,
+}
+
+/// The domain config without password and device finger print.
+#[derive(Debug, Serialize)]
+pub struct DomainSummary {
+    pub name: String,
+    pub addr: String,
+    pub signaling_port: u16,
+    pub subscribe_port: u16,
+    pub is_primary: bool,
+    pub device_id: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CapabilityReport {
+    pub os: String,
+    pub os_version: String,
+    pub arch: String,
+    pub graphics_cards: Vec<GraphicsCards>,
+    /// Available encoders by ffmpeg encoder name.
+    pub encoders: Vec<(String, bool)>,
+    pub h264_decoder: bool,
+    pub hevc_decoder: bool,
+    pub hw_device_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NatType {
+    /// No STUN server responds, UDP is blocked or the network is down.
+    Blocked,
+    /// The mapped address is one of the local addresses.
+    Open,
+    /// All STUN servers see the same mapped address.
+    Cone,
+    /// STUN servers see different mapped addresses, direct connection is unlikely.
+    Symmetric,
+    Unknown,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StunProbe {
+    pub server: String,
+    pub mapped_addr: Option<SocketAddr>,
+    pub rtt_ms: Option<u128>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NatReport {
+    pub local_addrs: Vec<(String, IpAddr)>,
+    pub stun_probes: Vec<StunProbe>,
+    pub nat_type: NatType,
+    pub signaling_reachable: Option<bool>,
+    pub signaling_rtt_ms: Option<u128>,
+}
+
+/// Assemble the diagnostics zip for bug reports at `output_path`, secrets in config
+/// and logs are redacted before written.
+pub async fn create_diagnostics_bundle(
+    storage: &LocalStorage,
+    log_dir: PathBuf,
+    output_path: PathBuf,
+) -> CoreResult<()> {
+    let config_summary = summarize_config(storage)?;
+    let primary_domain_addr = storage.domain().get_primary_domain().ok().map(|d| d.addr);
+    let nat_report = test_nat(primary_domain_addr).await;
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    tokio::task::spawn_blocking(move || {
+        let _ = tx.send(write_bundle(
+            &output_path,
+            &log_dir,
+            &config_summary,
+            &nat_report,
+        ));
+    });
+
+    rx.await?
+}
+
+fn write_bundle(
+    output_path: &Path,
+    log_dir: &Path,
+    config_summary: &ConfigSummary,
+    nat_report: &NatReport,
+) -> CoreResult<()> {
+    let file = std::fs::File::create(output_path)?;
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file("config.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(config_summary)?)?;
+
+    zip.start_file("capabilities.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&report_capabilities())?)?;
+
+    zip.start_file("nat.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(nat_report)?)?;
+
+    for log_file in recent_log_files(log_dir)? {
+        let Some(file_name) = log_file.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+
+        zip.start_file(format!("logs/{file_name}"), options)?;
+
+        let reader = BufReader::new(std::fs::File::open(&log_file)?);
+        for line in reader.lines() {
+            zip.write_all(redact_log_line(&line?).as_bytes())?;
+            zip.write_all(b"\n")?;
+        }
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
+
+fn recent_log_files(log_dir: &Path) -> CoreResult<Vec<PathBuf>> {
+    if !log_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut log_files: Vec<PathBuf> = std::fs::read_dir(log_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+
+    // rolling log files are suffixed by date
+    log_files.sort();
+
+    let skip = log_files.len().saturating_sub(MAX_LOG_FILES);
+    Ok(log_files.split_off(skip))
+}
+
+fn summarize_config(storage: &LocalStorage) -> CoreResult<ConfigSummary> {
+    let domain_count = storage.domain().get_domain_count()?;
+    let (_, domains) = storage.domain().get_domains(1, domain_count.max(1))?;
+
+    let domains = domains
+        .into_iter()
+        .map(|domain| DomainSummary {
+            name: domain.name,
+            addr: domain.addr,
+            signaling_port: domain.signaling_port,
+            subscribe_port: domain.subscribe_port,
+            is_primary: domain.is_primary,
+            device_id: domain.device_id,
+        })
+        .collect();
+
+    let theme = storage.kv().get_theme()?.map(|theme| {
+        let theme: &str = theme.into();
+        theme.to_string()
+    });
+
+    Ok(ConfigSummary {
+        domains,
+        language: storage.kv().get_language()?,
+        theme,
+        power_saving_enabled: storage.kv().get_power_saving_enabled()?,
+        interrupted_transfers: storage.recovery().get_transfers()?.len(),
+    })
+}
+
+fn report_capabilities() -> CapabilityReport {
+    let info = os_info::get();
+
+    let graphics_cards = match enum_graphics_cards() {
+        Ok(graphics_cards) => graphics_cards,
+        Err(err) => {
+            tracing::warn!(?err, "enum graphics cards failed");
+            Vec::new()
+        }
+    };
+
+    let encoders = [
+        "libx264",
+        "h264_videotoolbox",
+        "hevc_videotoolbox",
+        "h264_nvenc",
+        "h264_qsv",
+        "h264_amf",
+    ]
+    .into_iter()
+    .map(|name| {
+        let available = CString::new(name)
+            .map(|name| unsafe { !avcodec_find_encoder_by_name(name.as_ptr()).is_null() })
+            .unwrap_or(false);
+
+        (name.to_string(), available)
+    })
+    .collect();
+
+    let mut hw_device_types = Vec::new();
+    unsafe {
+        let mut hw_device_type = av_hwdevice_iterate_types(AV_HWDEVICE_TYPE_NONE);
+        while hw_device_type != AV_HWDEVICE_TYPE_NONE {
+            let name = av_hwdevice_get_type_name(hw_device_type);
+            if !name.is_null() {
+                hw_device_types.push(CStr::from_ptr(name).to_string_lossy().to_string());
+            }
+
+            hw_device_type = av_hwdevice_iterate_types(hw_device_type);
+        }
+    }
+
+    CapabilityReport {
+        os: info.os_type().to_string(),
+        os_version: info.version().to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        graphics_cards,
+        encoders,
+        h264_decoder: unsafe { !avcodec_find_decoder(AV_CODEC_ID_H264).is_null() },
+        hevc_decoder: unsafe { !avcodec_find_decoder(AV_CODEC_ID_HEVC).is_null() },
+        hw_device_types,
+    }
+}
+
+/// Probe the public mapped address with STUN servers and classify the NAT, also checks
+/// the reachability of primary domain signaling server if it exists.
+pub async fn test_nat(primary_domain_addr: Option<String>) -> NatReport {
+    let local_addrs = match enum_broadcast_network_interfaces() {
+        Ok(local_addrs) => local_addrs,
+        Err(err) => {
+            tracing::warn!(?err, "enum network interfaces failed");
+            Vec::new()
+        }
+    };
+
+    let mut stun_probes = Vec::new();
+
+    match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => {
+            for server in STUN_SERVERS {
+                let started = Instant::now();
+                let probe = match stun_binding(&socket, server).await {
+                    Ok(mapped_addr) => StunProbe {
+                        server: server.to_string(),
+                        mapped_addr: Some(mapped_addr),
+                        rtt_ms: Some(started.elapsed().as_millis()),
+                        error: None,
+                    },
+                    Err(err) => StunProbe {
+                        server: server.to_string(),
+                        mapped_addr: None,
+                        rtt_ms: None,
+                        error: Some(err.to_string()),
+                    },
+                };
+
+                stun_probes.push(probe);
+            }
+        }
+        Err(err) => tracing::warn!(?err, "bind nat test socket failed"),
+    };
+
+    let nat_type = classify_nat(&local_addrs, &stun_probes);
+
+    let (signaling_reachable, signaling_rtt_ms) = match primary_domain_addr {
+        Some(addr) => {
+            let started = Instant::now();
+            let reachable = match SignalingClient::new(addr) {
+                Ok(client) => client.identity().await.is_ok(),
+                Err(_) => false,
+            };
+
+            (
+                Some(reachable),
+                reachable.then(|| started.elapsed().as_millis()),
+            )
+        }
+        None => (None, None),
+    };
+
+    NatReport {
+        local_addrs,
+        stun_probes,
+        nat_type,
+        signaling_reachable,
+        signaling_rtt_ms,
+    }
+}
+
+fn classify_nat(local_addrs: &[(String, IpAddr)], stun_probes: &[StunProbe]) -> NatType {
+    let mapped_addrs: Vec<SocketAddr> = stun_probes
+        .iter()
+        .filter_map(|probe| probe.mapped_addr)
+        .collect();
+
+    let Some(first_mapped_addr) = mapped_addrs.first() else {
+        return NatType::Blocked;
+    };
+
+    if local_addrs
+        .iter()
+        .any(|(_, ip)| *ip == first_mapped_addr.ip())
+    {
+        return NatType::Open;
+    }
+
+    if mapped_addrs.len() < 2 {
+        return NatType::Unknown;
+    }
+
+    if mapped_addrs.iter().all(|addr| addr == first_mapped_addr) {
+        NatType::Cone
+    } else {
+        NatType::Symmetric
+    }
+}
+
+async fn stun_binding(socket: &UdpSocket, server: &str) -> CoreResult<SocketAddr> {
+    let server_addr = tokio::net::lookup_host(server)
+        .await?
+        .find(|addr| addr.is_ipv4())
+        .ok_or(core_error!("resolve stun server failed"))?;
+
+    let mut transaction_id = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut transaction_id);
+
+    // binding request header without attributes
+    let mut request = Vec::with_capacity(20);
+    request.extend_from_slice(&0x0001u16.to_be_bytes());
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(&transaction_id);
+
+    socket.send_to(&request, server_addr).await?;
+
+    let mut buffer = [0u8; 512];
+    let deadline = Instant::now() + PROBE_TIMEOUT;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let (len, from) = tokio::time::timeout(remaining, socket.recv_from(&mut buffer))
+            .await
+            .map_err(|_| core_error!("stun server not respond"))??;
+
+        // skip the late responses of previous probes
+        if from != server_addr || len < 20 || buffer[8..20] != transaction_id {
+            continue;
+        }
+
+        return parse_binding_response(&buffer[..len], &transaction_id);
+    }
+}
+
+fn parse_binding_response(response: &[u8], transaction_id: &[u8; 12]) -> CoreResult<SocketAddr> {
+    if u16::from_be_bytes([response[0], response[1]]) != 0x0101 {
+        return Err(core_error!("stun binding request failed"));
+    }
+
+    let mut offset = 20;
+    let mut mapped_addr = None;
+
+    while offset + 4 <= response.len() {
+        let attr_type = u16::from_be_bytes([response[offset], response[offset + 1]]);
+        let attr_len = u16::from_be_bytes([response[offset + 2], response[offset + 3]]) as usize;
+        let value_offset = offset + 4;
+
+        if value_offset + attr_len > response.len() {
+            break;
+        }
+
+        let value = &response[value_offset..value_offset + attr_len];
+
+        match attr_type {
+            // XOR-MAPPED-ADDRESS
+            0x0020 => return parse_mapped_address(value, Some(transaction_id)),
+            // MAPPED-ADDRESS, only used when server doesn't support XOR-MAPPED-ADDRESS
+            0x0001 => mapped_addr = Some(parse_mapped_address(value, None)?),
+            _ => {}
+        }
+
+        // attributes are padded to 4 bytes boundary
+        offset = value_offset + ((attr_len + 3) & !3);
+    }
+
+    mapped_addr.ok_or(core_error!("stun response has no mapped address"))
+}
+
+fn parse_mapped_address(value: &[u8], xor_id: Option<&[u8; 12]>) -> CoreResult<SocketAddr> {
+    if value.len() < 8 {
+        return Err(core_error!("invalid stun mapped address"));
+    }
+
+    let magic_cookie = STUN_MAGIC_COOKIE.to_be_bytes();
+
+    let mut port = u16::from_be_bytes([value[2], value[3]]);
+    if xor_id.is_some() {
+        port ^= (STUN_MAGIC_COOKIE >> 16) as u16;
+    }
+
+    match value[1] {
+        0x01 => {
+            let mut octets = [value[4], value[5], value[6], value[7]];
+            if xor_id.is_some() {
+                for (octet, mask) in octets.iter_mut().zip(magic_cookie) {
+                    *octet ^= mask;
+                }
+            }
+
+            Ok(SocketAddr::new(IpAddr::V4(Ipv4Addr::from(octets)), port))
+        }
+        0x02 if value.len() >= 20 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&value[4..20]);
+            if let Some(transaction_id) = xor_id {
+                let masks = magic_cookie.iter().chain(transaction_id.iter());
+                for (octet, mask) in octets.iter_mut().zip(masks) {
+                    *octet ^= mask;
+                }
+            }
+
+            Ok(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(octets)), port))
+        }
+        _ => Err(core_error!("invalid stun mapped address family")),
+    }
+}
+
+/// Redact the sensitive fields of a json log line, plain text lines are redacted by
+/// searching `key: "value"` and `key=value` patterns.
+fn redact_log_line(line: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(line) {
+        Ok(mut record) => {
+            redact_json_value(&mut record);
+            record.to_string()
+        }
+        Err(_) => redact_text(line),
+    }
+}
+
+fn is_sensitive_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SENSITIVE_KEYS
+        .iter()
+        .any(|sensitive| key.contains(sensitive))
+}
+
+fn redact_json_value(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if is_sensitive_key(key) {
+                    *value = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    redact_json_value(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact_json_value),
+        // debug formatted fields are logged as string
+        serde_json::Value::String(text) => *text = redact_text(text),
+        _ => {}
+    }
+}
+
+fn redact_text(text: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+
+    'search: while !rest.is_empty() {
+        let lower = rest.to_ascii_lowercase();
+
+        let found = SENSITIVE_KEYS
+            .iter()
+            .filter_map(|key| lower.find(key).map(|index| (index, key.len())))
+            .min_by_key(|(index, _)| *index);
+
+        let Some((index, key_len)) = found else {
+            break;
+        };
+
+        let value_start = index + key_len;
+        redacted.push_str(&rest[..value_start]);
+        rest = &rest[value_start..];
+
+        // keep the separator and quote, then skip the value
+        for separator in [": \"", "=\"", ": ", "="] {
+            if let Some(value) = rest.strip_prefix(separator) {
+                redacted.push_str(separator);
+                redacted.push_str(REDACTED);
+
+                let value_end = if separator.ends_with('"') {
+                    value.find('"').unwrap_or(value.len())
+                } else {
+                    value
+                        .find(|c: char| c.is_whitespace() || c == ',' || c == '}')
+                        .unwrap_or(value.len())
+                };
+
+                rest = &value[value_end..];
+                continue 'search;
+            }
+        }
+    }
+
+    redacted.push_str(rest);
+    redacted
+}
@@ -23,7 +23,7 @@ macro_rules! core_error {
 
 #[macro_export]
 macro_rules! call {
-    ($exp:expr) => {
-        bincode_serialize(&$exp.map_err(|err| err.to_string()))
+    ($client:expr, $exp:expr) => {
+        $client.serialize_reply(&$exp.map_err(|err| err.to_string()))
     };
 }
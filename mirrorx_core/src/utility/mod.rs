@@ -1,5 +1,10 @@
 pub mod bincode;
+pub mod cancel;
+pub mod cbor;
+pub mod diagnostics;
 pub mod macros;
+pub mod net;
 pub mod nonce_value;
 pub mod os;
 pub mod rand;
+pub mod spawn;
@@ -0,0 +1,37 @@
+//! Machines with VPN, LAN and Wi-Fi have several routes to a peer and the OS doesn't
+//! always pick the one user expects. Outgoing connections are bound to the preferred
+//! interface address when it's set, so they leave through that interface.
+
+use once_cell::sync::Lazy;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::RwLock,
+};
+use tokio::net::{TcpSocket, TcpStream};
+
+static BIND_IP: Lazy<RwLock<Option<IpAddr>>> = Lazy::new(|| RwLock::new(None));
+
+/// Set the interface address which outgoing connections are bound to, None leaves the
+/// route to the OS.
+pub fn set_bind_ip(bind_ip: Option<IpAddr>) {
+    *BIND_IP.write().unwrap() = bind_ip;
+}
+
+pub fn bind_ip() -> Option<IpAddr> {
+    *BIND_IP.read().unwrap()
+}
+
+/// Connect to `addr` from the preferred interface address, the OS picks the route when
+/// no address is preferred or it's of the other IP version.
+pub async fn connect_tcp(addr: SocketAddr) -> std::io::Result<TcpStream> {
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+
+    if let Some(bind_ip) = bind_ip().filter(|bind_ip| bind_ip.is_ipv4() == addr.is_ipv4()) {
+        socket.bind(SocketAddr::new(bind_ip, 0))?;
+    }
+
+    socket.connect(addr).await
+}
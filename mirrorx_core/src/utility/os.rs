@@ -58,6 +58,38 @@ pub fn enum_graphics_cards() -> CoreResult<Vec<GraphicsCards>> {
     Ok(graphics_cards)
 }
 
+#[derive(Debug, Serialize)]
+pub struct InterfaceAddr {
+    pub name: String,
+    pub ip: IpAddr,
+    /// Whether LAN discovery broadcasts on it.
+    pub broadcast: bool,
+}
+
+/// Addresses of every interface which isn't loopback, for user to pick the route.
+pub fn enum_network_interfaces() -> CoreResult<Vec<InterfaceAddr>> {
+    let interfaces = network_interface::NetworkInterface::show()?;
+    let mut valid_interfaces = Vec::new();
+
+    for interface in interfaces {
+        let Some(addr) = interface.addr else {
+            continue;
+        };
+
+        if addr.ip().is_loopback() {
+            continue;
+        }
+
+        valid_interfaces.push(InterfaceAddr {
+            name: interface.name,
+            ip: addr.ip(),
+            broadcast: addr.broadcast().is_some(),
+        });
+    }
+
+    Ok(valid_interfaces)
+}
+
 pub fn enum_broadcast_network_interfaces() -> CoreResult<Vec<(String, IpAddr)>> {
     let interfaces = network_interface::NetworkInterface::show()?;
     let mut valid_interfaces = Vec::new();
@@ -1,4 +1,5 @@
 use rand::Rng;
+use std::time::Duration;
 
 #[inline]
 pub fn generate_device_finger_print() -> String {
@@ -18,3 +19,9 @@ pub fn generate_random_password() -> String {
 pub fn generate_random_ping_value() -> i32 {
     rand::thread_rng().gen()
 }
+
+/// Scale `delay` randomly between its half and itself.
+#[inline]
+pub fn generate_random_jitter(delay: Duration) -> Duration {
+    delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+}
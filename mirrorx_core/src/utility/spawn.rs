@@ -0,0 +1,23 @@
+//! Spawned tasks don't inherit the span of their spawner, these helpers carry the
+//! current span into the task, so logs of a session keep its session id.
+
+use std::future::Future;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(future.in_current_span())
+}
+
+pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || span.in_scope(f))
+}
@@ -0,0 +1,167 @@
+//! Sessions connected in process by `test_support`, run with `--features test-support`.
+
+use mirrorx_core::{
+    api::{
+        endpoint::{
+            client::{
+                state::SessionState,
+                test_support::{connect_desktop_pair, connect_files_pair},
+                EndPointClient,
+            },
+            message::{
+                EndPointCallRequest, EndPointInput, EndPointMessage, EndPointSendFileReply,
+                EndPointSendFileRequest, EndPointVideoFrame, InputEvent, MouseEvent,
+            },
+        },
+        notification::{self, Notification},
+    },
+    component::{fs::transfer::send_file_to_remote, input::key::MouseKey},
+};
+use std::time::Duration;
+
+const WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[tokio::test]
+async fn desktop_session_is_negotiated() {
+    let mut pair = connect_desktop_pair().await.unwrap();
+
+    let monitor = pair.active.monitor().await.unwrap();
+    assert_eq!(monitor.id, "mock-monitor");
+    wait_for_state(&pair.active, SessionState::Streaming).await;
+
+    let frame = video_frame(1);
+    pair.peer.push_video_frame(frame.clone()).unwrap();
+    assert_eq!(recv_video_frame(&mut pair.video_frames).await, frame);
+}
+
+#[tokio::test]
+async fn input_is_forwarded_to_peer() {
+    let mut pair = connect_desktop_pair().await.unwrap();
+    wait_for_state(&pair.active, SessionState::Streaming).await;
+
+    let input = mouse_move(10.0, 20.0);
+    pair.active
+        .send(&EndPointMessage::InputCommand(input.clone()))
+        .await
+        .unwrap();
+
+    let received = tokio::time::timeout(WAIT_TIMEOUT, pair.peer.recv_input())
+        .await
+        .unwrap();
+    assert_eq!(received, Some(input));
+}
+
+#[tokio::test]
+async fn file_is_sent_to_passive_endpoint() {
+    let pair = connect_files_pair().await.unwrap();
+
+    let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let content: Vec<u8> = (0..200 * 1024).map(|i| (i % 251) as u8).collect();
+    let source = dir.join("source.bin");
+    std::fs::write(&source, &content).unwrap();
+    let destination = dir.join("received.bin");
+
+    let mut notifications = notification::subscribe();
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let reply: EndPointSendFileReply = pair
+        .active
+        .call(EndPointCallRequest::SendFileRequest(
+            EndPointSendFileRequest {
+                id: id.clone(),
+                filename: String::from("received.bin"),
+                path: dir.clone(),
+                size: content.len() as u64,
+                resume: false,
+                staged: false,
+                delta: false,
+            },
+        ))
+        .await
+        .unwrap();
+    assert_eq!(reply.offset, 0);
+
+    send_file_to_remote(id, pair.active.clone(), &source, reply.offset)
+        .await
+        .unwrap();
+
+    // both endpoints tell the transfer result, the receiving one tells the destination
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        loop {
+            match notifications.recv().await.unwrap() {
+                Notification::FileTransferSucceeded { path, .. } if path == destination => break,
+                Notification::FileTransferFailed { path, .. } => {
+                    panic!("transfer of {path:?} failed")
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(std::fs::read(&destination).unwrap(), content);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(start_paused = true)]
+async fn session_is_resumed_after_peer_restarts() {
+    let mut pair = connect_desktop_pair().await.unwrap();
+    wait_for_state(&pair.active, SessionState::Streaming).await;
+
+    pair.restart_peer().await.unwrap();
+    wait_for_state(&pair.active, SessionState::Streaming).await;
+
+    let frame = video_frame(2);
+    pair.peer.push_video_frame(frame.clone()).unwrap();
+    assert_eq!(recv_video_frame(&mut pair.video_frames).await, frame);
+
+    let input = mouse_move(30.0, 40.0);
+    pair.active
+        .send(&EndPointMessage::InputCommand(input.clone()))
+        .await
+        .unwrap();
+
+    let received = tokio::time::timeout(WAIT_TIMEOUT, pair.peer.recv_input())
+        .await
+        .unwrap();
+    assert_eq!(received, Some(input));
+}
+
+async fn wait_for_state(client: &EndPointClient, state: SessionState) {
+    tokio::time::timeout(WAIT_TIMEOUT, async {
+        while client.state() != state {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("session is {:?} rather than {state:?}", client.state()));
+}
+
+async fn recv_video_frame(
+    frames: &mut tokio::sync::mpsc::Receiver<EndPointVideoFrame>,
+) -> EndPointVideoFrame {
+    tokio::time::timeout(WAIT_TIMEOUT, frames.recv())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+fn video_frame(pts: i64) -> EndPointVideoFrame {
+    EndPointVideoFrame {
+        width: 1920,
+        height: 1080,
+        pts,
+        buffer: vec![0, 0, 0, 1, pts as u8],
+        timestamp_micros: 0,
+    }
+}
+
+fn mouse_move(x: f32, y: f32) -> EndPointInput {
+    EndPointInput {
+        events: vec![InputEvent::Mouse(MouseEvent::Move(MouseKey::None, x, y))],
+    }
+}
@@ -0,0 +1,20 @@
+//! Vectors of every release under `protocol_vectors` must still be decoded and encoded
+//! by this build, run with `--features protocol-vectors`.
+
+use mirrorx_core::api::endpoint::protocol_vectors::check_vectors;
+use std::path::Path;
+
+#[test]
+fn releases_are_compatible() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("protocol_vectors");
+
+    let mut releases = 0;
+    for entry in std::fs::read_dir(root).unwrap() {
+        let dir = entry.unwrap().path();
+        let mismatches = check_vectors(&dir).unwrap();
+        assert!(mismatches.is_empty(), "{dir:?}: {mismatches:#?}");
+        releases += 1;
+    }
+
+    assert!(releases > 0, "no vectors of releases");
+}
@@ -3,10 +3,63 @@ use std::path::PathBuf;
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
 
+    // cfg in build script is evaluated for the host, so the mobile targets which are
+    // cross compiled have to be checked by the target env
+    let target_os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    if target_os == "android" || target_os == "ios" {
+        link_mobile_sys(&target_os);
+        link_mobile_media_libraries_artifacts();
+        return;
+    }
+
     link_sys();
     link_media_libraries_artifacts();
 }
 
+fn link_mobile_sys(target_os: &str) {
+    if target_os == "android" {
+        // AHardwareBuffer
+        println!("cargo:rustc-link-lib=android");
+        println!("cargo:rustc-link-lib=log");
+    } else {
+        println!("cargo:rustc-link-lib=framework=CoreFoundation");
+        println!("cargo:rustc-link-lib=framework=CoreVideo");
+        println!("cargo:rustc-link-lib=framework=CoreMedia");
+        println!("cargo:rustc-link-lib=framework=VideoToolbox");
+        println!("cargo:rustc-link-lib=framework=IOSurface");
+        println!("cargo:rustc-link-lib=framework=AudioToolbox");
+        println!("cargo:rustc-link-lib=c++");
+    }
+}
+
+/// Mobile builds only contain the viewer pipeline, so only decoders are linked.
+fn link_mobile_media_libraries_artifacts() {
+    let mirrorx_media_libraries_path = match std::env::var("MIRRORX_MEDIA_LIBS_PATH") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => panic!("environment variable 'MIRRORX_MEDIA_LIBS_PATH' not exists"),
+    };
+
+    println!(
+        "cargo:rustc-link-search={}",
+        mirrorx_media_libraries_path
+            .join("opus")
+            .join("lib")
+            .display()
+    );
+    println!("cargo:rustc-link-lib=opus");
+
+    println!(
+        "cargo:rustc-link-search={}",
+        mirrorx_media_libraries_path
+            .join("ffmpeg")
+            .join("lib")
+            .display()
+    );
+    println!("cargo:rustc-link-lib=avcodec");
+    println!("cargo:rustc-link-lib=avutil");
+    println!("cargo:rustc-link-lib=swresample");
+}
+
 fn link_sys() {
     #[cfg(target_os = "macos")]
     {
@@ -18,6 +71,8 @@ fn link_sys() {
         println!("cargo:rustc-link-lib=framework=CoreServices");
         println!("cargo:rustc-link-lib=framework=AppKit");
         println!("cargo:rustc-link-lib=framework=IOSurface");
+        println!("cargo:rustc-link-lib=framework=IOKit");
+        println!("cargo:rustc-link-lib=framework=CoreAudio");
         println!("cargo:rustc-link-lib=c++");
     }
 }
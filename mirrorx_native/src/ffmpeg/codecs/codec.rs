@@ -13,6 +13,8 @@ use std::os::raw::{c_char, c_void};
 
 pub const AV_CODEC_CAP_TRUNCATED: i32 = 1 << 3;
 
+pub const AV_CODEC_HW_CONFIG_METHOD_HW_DEVICE_CTX: i32 = 0x01;
+
 #[repr(C)]
 pub struct AVProfile {
     pub profile: i32,
@@ -9,4 +9,5 @@ pub struct AVBufferRef {
 
 extern "C" {
     pub fn av_buffer_ref(buf: *const AVBufferRef) -> *mut AVBufferRef;
+    pub fn av_buffer_unref(buf: *mut *mut AVBufferRef);
 }
@@ -0,0 +1,53 @@
+#![allow(non_snake_case)]
+
+use super::four_char_code;
+use std::os::raw::c_void;
+
+pub type AudioObjectID = u32;
+pub type AudioObjectPropertySelector = u32;
+pub type AudioObjectPropertyScope = u32;
+pub type AudioObjectPropertyElement = u32;
+
+pub const kAudioObjectSystemObject: AudioObjectID = 1;
+pub const kAudioObjectUnknown: AudioObjectID = 0;
+
+pub const kAudioObjectPropertyScopeGlobal: AudioObjectPropertyScope =
+    four_char_code('g', 'l', 'o', 'b');
+pub const kAudioDevicePropertyScopeOutput: AudioObjectPropertyScope =
+    four_char_code('o', 'u', 't', 'p');
+pub const kAudioObjectPropertyElementMain: AudioObjectPropertyElement = 0;
+
+pub const kAudioHardwarePropertyDefaultOutputDevice: AudioObjectPropertySelector =
+    four_char_code('d', 'O', 'u', 't');
+pub const kAudioDevicePropertyMute: AudioObjectPropertySelector =
+    four_char_code('m', 'u', 't', 'e');
+pub const kAudioHardwareServiceDeviceProperty_VirtualMainVolume: AudioObjectPropertySelector =
+    four_char_code('v', 'm', 'v', 'c');
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AudioObjectPropertyAddress {
+    pub mSelector: AudioObjectPropertySelector,
+    pub mScope: AudioObjectPropertyScope,
+    pub mElement: AudioObjectPropertyElement,
+}
+
+extern "C" {
+    pub fn AudioObjectGetPropertyData(
+        inObjectID: AudioObjectID,
+        inAddress: *const AudioObjectPropertyAddress,
+        inQualifierDataSize: u32,
+        inQualifierData: *const c_void,
+        ioDataSize: *mut u32,
+        outData: *mut c_void,
+    ) -> i32;
+
+    pub fn AudioObjectSetPropertyData(
+        inObjectID: AudioObjectID,
+        inAddress: *const AudioObjectPropertyAddress,
+        inQualifierDataSize: u32,
+        inQualifierData: *const c_void,
+        inDataSize: u32,
+        inData: *const c_void,
+    ) -> i32;
+}
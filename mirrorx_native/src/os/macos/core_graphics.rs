@@ -5,6 +5,8 @@ use std::os::raw::c_void;
 
 pub type CGImageDestinationRef = *mut c_void;
 
+pub type CGColorSpaceRef = *mut c_void;
+
 pub type CGDisplayStreamRef = *mut c_void;
 pub type CGDisplayStreamUpdateRef = *mut c_void;
 
@@ -26,6 +28,11 @@ pub type CGDisplayStreamFrameAvailableHandler<'a> = &'a block::Block<
 
 extern "C" {
     pub static kUTTypePNG: CFStringRef;
+    pub static kCGDisplayStreamColorSpace: CFStringRef;
+    pub static kCGDisplayStreamYCbCrMatrix: CFStringRef;
+    pub static kCGDisplayStreamYCbCrMatrix_ITU_R_709_2: CFStringRef;
+    pub static kCGColorSpaceSRGB: CFStringRef;
+    pub static kCGColorSpaceDisplayP3: CFStringRef;
 }
 
 extern "C" {
@@ -56,4 +63,6 @@ extern "C" {
     );
     pub fn CGImageDestinationFinalize(idst: CGImageDestinationRef) -> bool;
     pub fn CGImageRelease(image: CGImageRef);
+    pub fn CGColorSpaceCreateWithName(name: CFStringRef) -> CGColorSpaceRef;
+    pub fn CGColorSpaceRelease(space: CGColorSpaceRef);
 }
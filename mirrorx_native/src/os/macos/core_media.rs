@@ -52,6 +52,23 @@ pub type CMBlockBufferFlags = u32;
 
 pub type CMItemCount = CFIndex;
 
+pub const kAudioFormatFlagIsFloat: u32 = 1 << 0;
+pub const kAudioFormatFlagIsNonInterleaved: u32 = 1 << 5;
+
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct AudioStreamBasicDescription {
+    pub sample_rate: f64,
+    pub format_id: u32,
+    pub format_flags: u32,
+    pub bytes_per_packet: u32,
+    pub frames_per_packet: u32,
+    pub bytes_per_frame: u32,
+    pub channels_per_frame: u32,
+    pub bits_per_channel: u32,
+    pub reserved: u32,
+}
+
 extern "C" {
     pub static kCMSampleAttachmentKey_NotSync: CFStringRef;
     pub static kCMSampleAttachmentKey_DependsOnOthers: CFStringRef;
@@ -120,6 +137,16 @@ extern "C" {
         total_length_out: *mut u32,
         data_pointer_out: *mut *const u8,
     ) -> OSStatus;
+    pub fn CMBlockBufferGetDataLength(the_buffer: CMBlockBufferRef) -> usize;
+    pub fn CMBlockBufferCopyDataBytes(
+        the_source_buffer: CMBlockBufferRef,
+        offset_to_data: usize,
+        data_length: usize,
+        destination: *mut c_void,
+    ) -> OSStatus;
+    pub fn CMAudioFormatDescriptionGetStreamBasicDescription(
+        desc: CMFormatDescriptionRef,
+    ) -> *const AudioStreamBasicDescription;
     pub fn CMBlockBufferCreateWithMemoryBlock(
         structure_allocator: CFAllocatorRef,
         memory_block: *mut c_void,
@@ -0,0 +1,55 @@
+#![allow(non_camel_case_types)]
+
+use core_foundation::{
+    base::{CFAllocatorRef, CFTypeRef},
+    dictionary::{CFDictionaryRef, CFMutableDictionaryRef},
+    string::CFStringRef,
+};
+use std::os::raw::c_char;
+
+pub type io_object_t = u32;
+pub type io_iterator_t = io_object_t;
+pub type io_registry_entry_t = io_object_t;
+pub type IOPMAssertionID = u32;
+pub type IOPMAssertionLevel = u32;
+pub type IOPMUserActiveType = u32;
+
+pub const kIOMasterPortDefault: u32 = 0;
+pub const kIOPMAssertionLevelOn: IOPMAssertionLevel = 255;
+pub const kIOPMUserActiveLocal: IOPMUserActiveType = 0;
+
+extern "C" {
+    pub static kIOPMACPowerKey: CFStringRef;
+    pub static kIOPMBatteryPowerKey: CFStringRef;
+}
+
+extern "C" {
+    pub fn IOPSCopyPowerSourcesInfo() -> CFTypeRef;
+    pub fn IOPSGetProvidingPowerSourceType(snapshot: CFTypeRef) -> CFStringRef;
+    pub fn IOServiceMatching(name: *const c_char) -> CFMutableDictionaryRef;
+    pub fn IOServiceGetMatchingServices(
+        master_port: u32,
+        matching: CFDictionaryRef,
+        existing: *mut io_iterator_t,
+    ) -> i32;
+    pub fn IOIteratorNext(iterator: io_iterator_t) -> io_object_t;
+    pub fn IOObjectRelease(object: io_object_t) -> i32;
+    pub fn IORegistryEntryCreateCFProperty(
+        entry: io_registry_entry_t,
+        key: CFStringRef,
+        allocator: CFAllocatorRef,
+        options: u32,
+    ) -> CFTypeRef;
+    pub fn IOPMAssertionCreateWithName(
+        assertion_type: CFStringRef,
+        assertion_level: IOPMAssertionLevel,
+        assertion_name: CFStringRef,
+        assertion_id: *mut IOPMAssertionID,
+    ) -> i32;
+    pub fn IOPMAssertionDeclareUserActivity(
+        assertion_name: CFStringRef,
+        user_type: IOPMUserActiveType,
+        assertion_id: *mut IOPMAssertionID,
+    ) -> i32;
+    pub fn IOPMAssertionRelease(assertion_id: IOPMAssertionID) -> i32;
+}
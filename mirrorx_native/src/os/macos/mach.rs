@@ -0,0 +1,35 @@
+#![allow(non_camel_case_types)]
+
+pub type mach_port_t = u32;
+pub type kern_return_t = i32;
+pub type host_flavor_t = i32;
+pub type natural_t = u32;
+pub type mach_msg_type_number_t = natural_t;
+
+pub const KERN_SUCCESS: kern_return_t = 0;
+
+pub const HOST_CPU_LOAD_INFO: host_flavor_t = 3;
+pub const HOST_CPU_LOAD_INFO_COUNT: mach_msg_type_number_t =
+    (std::mem::size_of::<host_cpu_load_info>() / std::mem::size_of::<natural_t>()) as _;
+
+pub const CPU_STATE_USER: usize = 0;
+pub const CPU_STATE_SYSTEM: usize = 1;
+pub const CPU_STATE_IDLE: usize = 2;
+pub const CPU_STATE_NICE: usize = 3;
+pub const CPU_STATE_MAX: usize = 4;
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct host_cpu_load_info {
+    pub cpu_ticks: [natural_t; CPU_STATE_MAX],
+}
+
+extern "C" {
+    pub fn mach_host_self() -> mach_port_t;
+    pub fn host_statistics(
+        host_priv: mach_port_t,
+        flavor: host_flavor_t,
+        host_info_out: *mut i32,
+        host_info_out_cnt: *mut mach_msg_type_number_t,
+    ) -> kern_return_t;
+}